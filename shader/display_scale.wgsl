@@ -0,0 +1,14 @@
+// Prepended to the slice shaders, the built-in colormaps and custom ones alike, and to
+// volume.wgsl. They pass the log_scale and log_floor of their own parameters.
+
+// log10(x) = log2(x) * LOG10_2
+const LOG10_2: f32 = 0.30103;
+
+// maps a non-negative scaled value to [0, 1], logarithmically over `-log_floor` decades when
+// log_scale is not 0
+fn display_scale(value: f32, log_scale: u32, log_floor: f32) -> f32 {
+    if (log_scale == 0u) {
+        return value;
+    }
+    return clamp((log2(max(value, 1e-30)) * LOG10_2 - log_floor) / -log_floor, 0.0, 1.0);
+}
@@ -0,0 +1,46 @@
+@group(0)
+@binding(0)
+var field: texture_3d<f32>;
+
+@group(0)
+@binding(1)
+var permittivity: texture_3d<f32>;
+
+@group(0)
+@binding(2)
+var output: texture_storage_3d<r32float, write>;
+
+// components are read at the same index, ignoring the half cell stagger of the Yee grid
+fn squared_norm(index: vec3<u32>) -> f32 {
+    let value = textureLoad(field, index, 0).xyz;
+    return dot(value, value);
+}
+
+@compute
+@workgroup_size(4, 4, 4)
+fn magnitude(@builtin(global_invocation_id) index: vec3<u32>) {
+    if any(index >= textureDimensions(output)) {
+        return;
+    }
+    textureStore(output, index, vec4<f32>(sqrt(squared_norm(index)), 0.0, 0.0, 0.0));
+}
+
+@compute
+@workgroup_size(4, 4, 4)
+fn electric_energy(@builtin(global_invocation_id) index: vec3<u32>) {
+    if any(index >= textureDimensions(output)) {
+        return;
+    }
+    let energy = 0.5 * textureLoad(permittivity, index, 0).x * squared_norm(index);
+    textureStore(output, index, vec4<f32>(energy, 0.0, 0.0, 0.0));
+}
+
+// permeability is 1 everywhere
+@compute
+@workgroup_size(4, 4, 4)
+fn magnetic_energy(@builtin(global_invocation_id) index: vec3<u32>) {
+    if any(index >= textureDimensions(output)) {
+        return;
+    }
+    textureStore(output, index, vec4<f32>(0.5 * squared_norm(index), 0.0, 0.0, 0.0));
+}
@@ -0,0 +1,110 @@
+struct DftParam {
+    low: vec3<u32>, // first texel of the box
+    frequency_count: u32,
+    high: vec3<u32>, // last texel of the box
+    step: u32, // updates done so far, E is at step * dt and H half a step earlier
+    faces: u32, // bit 2 * axis + side per face that is recorded
+};
+
+var<push_constant> c_param: DftParam;
+
+@group(0)
+@binding(0)
+var electric_field: texture_3d<f32>;
+
+@group(0)
+@binding(1)
+var magnetic_field: texture_3d<f32>;
+
+// per frequency the cycles of one step and the fraction of a cycle 1024 steps advance, both
+// taken in f64 on the host so the phase stays accurate over long runs
+@group(0)
+@binding(2)
+var<storage, read> frequencies: array<vec2<f32>>;
+
+// per frequency and face cell the real and imaginary parts of E, then of H, at the cell center
+@group(0)
+@binding(3)
+var<storage, read_write> transforms: array<vec4<f32>>;
+
+const TAU = 6.283185307179586;
+
+fn load(field: texture_3d<f32>, texel: vec3<u32>) -> vec3<f32> {
+    return textureLoad(field, min(texel, textureDimensions(field) - 1u), 0).xyz;
+}
+
+// E_i sits half a cell along i from the texel, averaged over the four around the center
+fn centered_electric(texel: vec3<u32>) -> vec3<f32> {
+    let x = load(electric_field, texel).x + load(electric_field, texel + vec3<u32>(0u, 1u, 0u)).x
+        + load(electric_field, texel + vec3<u32>(0u, 0u, 1u)).x + load(electric_field, texel + vec3<u32>(0u, 1u, 1u)).x;
+    let y = load(electric_field, texel).y + load(electric_field, texel + vec3<u32>(1u, 0u, 0u)).y
+        + load(electric_field, texel + vec3<u32>(0u, 0u, 1u)).y + load(electric_field, texel + vec3<u32>(1u, 0u, 1u)).y;
+    let z = load(electric_field, texel).z + load(electric_field, texel + vec3<u32>(1u, 0u, 0u)).z
+        + load(electric_field, texel + vec3<u32>(0u, 1u, 0u)).z + load(electric_field, texel + vec3<u32>(1u, 1u, 0u)).z;
+    return 0.25 * vec3<f32>(x, y, z);
+}
+
+// H_i sits half a cell off the center along i, averaged over the two around it
+fn centered_magnetic(texel: vec3<u32>) -> vec3<f32> {
+    let here = load(magnetic_field, texel);
+    return 0.5 * (here + vec3<f32>(
+        load(magnetic_field, texel + vec3<u32>(1u, 0u, 0u)).x,
+        load(magnetic_field, texel + vec3<u32>(0u, 1u, 0u)).y,
+        load(magnetic_field, texel + vec3<u32>(0u, 0u, 1u)).z,
+    ));
+}
+
+// cells of `face`, none unless it is recorded
+fn face_cells(extent: vec3<u32>, face: u32) -> u32 {
+    let axis = face / 2u;
+    return ((c_param.faces >> face) & 1u) * extent[(axis + 1u) % 3u] * extent[(axis + 2u) % 3u];
+}
+
+@compute
+@workgroup_size(64)
+fn accumulate(
+    @builtin(global_invocation_id) index: vec3<u32>,
+    @builtin(num_workgroups) workgroups: vec3<u32>,
+) {
+    let extent = c_param.high - c_param.low + 1u;
+    var total = 0u;
+    for (var face = 0u; face < 6u; face++) {
+        total += face_cells(extent, face);
+    }
+    let cell = index.x + index.y * workgroups.x * 64u;
+    if cell >= total {
+        return;
+    }
+
+    // faces go low x, high x, low y, ..., each x-fastest over the two other axes
+    var face = 0u;
+    var first = 0u;
+    while cell >= first + face_cells(extent, face) {
+        first += face_cells(extent, face);
+        face += 1u;
+    }
+    let axis = face / 2u;
+    let u = select(0u, 1u, axis == 0u);
+    let v = select(2u, 1u, axis == 2u);
+    let local = cell - first;
+    var texel = c_param.low;
+    if face % 2u == 1u {
+        texel[axis] = c_param.high[axis];
+    }
+    texel[u] += local % extent[u];
+    texel[v] += local / extent[u];
+
+    let electric = centered_electric(texel);
+    let magnetic = centered_magnetic(texel);
+    for (var i = 0u; i < c_param.frequency_count; i++) {
+        let frequency = frequencies[i];
+        let cycles = fract(fract(frequency.y * f32(c_param.step / 1024u)) + frequency.x * f32(c_param.step % 1024u));
+        let electric_phase = -TAU * cycles;
+        let magnetic_phase = electric_phase + 0.5 * TAU * frequency.x;
+        let base = 4u * (i * total + cell);
+        transforms[base] += vec4<f32>(electric * cos(electric_phase), 0.0);
+        transforms[base + 1u] += vec4<f32>(electric * sin(electric_phase), 0.0);
+        transforms[base + 2u] += vec4<f32>(magnetic * cos(magnetic_phase), 0.0);
+        transforms[base + 3u] += vec4<f32>(magnetic * sin(magnetic_phase), 0.0);
+    }
+}
@@ -0,0 +1,43 @@
+struct Window {
+    origin: vec3<u32>, // first texel read
+    stride: vec3<u32>, // texels between two read along each axis
+};
+
+@group(0)
+@binding(0)
+var source: texture_3d<f32>;
+
+@group(0)
+@binding(1)
+var<uniform> window: Window;
+
+@group(0)
+@binding(2)
+var vector_output: texture_storage_3d<rgba32float, write>;
+
+@group(0)
+@binding(3)
+var scalar_output: texture_storage_3d<r32float, write>;
+
+// each output texel takes the source texel it lands on, without averaging its neighbours
+fn sample(index: vec3<u32>) -> vec4<f32> {
+    return textureLoad(source, window.origin + index * window.stride, 0);
+}
+
+@compute
+@workgroup_size(4, 4, 4)
+fn vector(@builtin(global_invocation_id) index: vec3<u32>) {
+    if any(index >= textureDimensions(vector_output)) {
+        return;
+    }
+    textureStore(vector_output, index, sample(index));
+}
+
+@compute
+@workgroup_size(4, 4, 4)
+fn scalar(@builtin(global_invocation_id) index: vec3<u32>) {
+    if any(index >= textureDimensions(scalar_output)) {
+        return;
+    }
+    textureStore(scalar_output, index, sample(index));
+}
@@ -0,0 +1,64 @@
+struct EnergyParam {
+    offset: vec3<u32>, // first texel of the interior
+    dt: f32,
+    end: vec3<u32>, // past the last texel of the interior
+};
+
+var<push_constant> c_param: EnergyParam;
+
+@group(0)
+@binding(0)
+var electric_field: texture_3d<f32>;
+
+@group(0)
+@binding(1)
+var magnetic_field: texture_3d<f32>;
+
+@group(0)
+@binding(2)
+var electric_constants: texture_storage_3d<rg32float, read>;
+
+@group(0)
+@binding(3)
+var magnetic_constants: texture_storage_3d<rg32float, read>;
+
+// one sum per workgroup, added up on the host
+@group(0)
+@binding(4)
+var<storage, read_write> partial_sums: array<f32>;
+
+var<workgroup> cell_energy: array<f32, WORKGROUP_SIZE>;
+
+// the second update constant is dt / epsilon (dt / mu for H), zero in PEC cells, where the
+// field is zero as well
+fn weighted_square(field: vec3<f32>, constant: f32) -> f32 {
+    if constant == 0.0 {
+        return 0.0;
+    }
+    return c_param.dt / constant * dot(field, field);
+}
+
+@compute
+@workgroup_size(WORKGROUP_X, WORKGROUP_Y, WORKGROUP_Z)
+fn sum_energy(
+    @builtin(global_invocation_id) index: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) workgroup: vec3<u32>,
+    @builtin(num_workgroups) workgroups: vec3<u32>,
+) {
+    let texel = index + c_param.offset;
+    var energy = 0.0;
+    if all(texel < c_param.end) {
+        energy = weighted_square(textureLoad(electric_field, texel, 0).xyz, textureLoad(electric_constants, texel).y)
+            + weighted_square(textureLoad(magnetic_field, texel, 0).xyz, textureLoad(magnetic_constants, texel).y);
+    }
+    cell_energy[local_index] = energy;
+    workgroupBarrier();
+    if local_index == 0u {
+        var sum = 0.0;
+        for (var i = 0u; i < WORKGROUP_SIZE; i++) {
+            sum += cell_energy[i];
+        }
+        partial_sums[workgroup.x + workgroups.x * (workgroup.y + workgroups.y * workgroup.z)] = sum;
+    }
+}
@@ -4,6 +4,12 @@ struct Param {
     sin_t: f32,
     envelope: f32,
     dt: f32,
+    // grid axis the source plane is normal to, 0 = x, 1 = y, 2 = z
+    normal: u32,
+    // k dx, zero without a Bloch wave vector
+    bloch_step: vec3<f32>,
+    // added to the Bloch phase of the texel, -pi / 2 for the imaginary half of the fields
+    phase_offset: f32,
 }
 
 var<push_constant> c_param: Param;
@@ -22,36 +28,48 @@ var mode_source_z: texture_storage_2d<rg32float, read>;
 
 @group(1)
 @binding(0)
-var update_field_x: texture_storage_3d<r32float, read_write>;
+var update_field: texture_storage_3d<rgba32float, read_write>;
 
 @group(1)
 @binding(1)
-var update_field_y: texture_storage_3d<r32float, read_write>;
-
-@group(1)
-@binding(2)
-var update_field_z: texture_storage_3d<r32float, read_write>;
-
-@group(1)
-@binding(3)
 var constants_map: texture_storage_3d<rg32float, read>;
 
 @compute
 @workgroup_size(WORKGROUP_X, WORKGROUP_Y, 1)
 fn excite_field_mode(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
-    let actual_texel = vec3<i32>(c_param.position + global_invocation_id);
-    let source_texel = vec2<i32>(global_invocation_id.xy);
+    // the mode's x and y run along the remaining axes in x, y, z order
+    let uv = global_invocation_id.xy;
+    var offset: vec3<u32>;
+    switch c_param.normal {
+        case 0u: {
+            offset = vec3<u32>(0u, uv.x, uv.y);
+        }
+        case 1u: {
+            offset = vec3<u32>(uv.x, 0u, uv.y);
+        }
+        default: {
+            offset = vec3<u32>(uv.x, uv.y, 0u);
+        }
+    }
+    let actual_texel = vec3<i32>(c_param.position + offset);
+    let source_texel = vec2<i32>(uv);
     let complex_x = textureLoad(mode_source_x, source_texel).xy;
     let complex_y = textureLoad(mode_source_y, source_texel).xy;
     let complex_z = textureLoad(mode_source_z, source_texel).xy;
 
-    let prev_field = vec3<f32>(textureLoad(update_field_x, actual_texel).x, textureLoad(update_field_y, actual_texel).x, textureLoad(update_field_z, actual_texel).x);
+    let prev_field = textureLoad(update_field, actual_texel).xyz;
+
+    // the carrier turned by the Bloch phase of the texel
+    let phase = dot(c_param.bloch_step, vec3<f32>(actual_texel)) + c_param.phase_offset;
+    let cos_t = c_param.cos_t * cos(phase) - c_param.sin_t * sin(phase);
+    let sin_t = c_param.cos_t * sin(phase) + c_param.sin_t * cos(phase);
+
+    let x = complex_x.x * cos_t + complex_x.y * sin_t;
+    let y = complex_y.x * cos_t + complex_y.y * sin_t;
+    let z = complex_z.x * cos_t + complex_z.y * sin_t;
 
-    let x = complex_x.x * c_param.cos_t + complex_x.y * c_param.sin_t;
-    let y = complex_y.x * c_param.cos_t + complex_y.y * c_param.sin_t;
-    let z = complex_z.x * c_param.cos_t + complex_z.y * c_param.sin_t;
+    // zero constants mark PEC cells, where the electric field must stay zero
+    let conductor = textureLoad(constants_map, actual_texel).y == 0.0;
 
-    textureStore(update_field_x, actual_texel, vec4<f32>(prev_field.x + x * c_param.envelope * c_param.dt, 0.0, 0.0, 1.0));
-    textureStore(update_field_y, actual_texel, vec4<f32>(prev_field.y + y * c_param.envelope * c_param.dt, 0.0, 0.0, 1.0));
-    textureStore(update_field_z, actual_texel, vec4<f32>(prev_field.z + z * c_param.envelope * c_param.dt, 0.0, 0.0, 1.0));
+    textureStore(update_field, actual_texel, vec4<f32>(prev_field + vec3<f32>(x, y, z) * c_param.envelope * c_param.dt * f32(!conductor), 0.0));
 }
@@ -0,0 +1,70 @@
+// appended to update.wgsl, same entry points as fdtd-3d.wgsl but each workgroup first stages the
+// conjugative field of its block plus one layer of halo in workgroup memory.
+// HALO_X/Y/Z are WORKGROUP_X/Y/Z + 1 and HALO_VOLUME their product, replaced on the Rust side.
+
+// the u suffix makes the replaced literals unsigned
+const WORKGROUP = vec3<u32>(WORKGROUP_Xu, WORKGROUP_Yu, WORKGROUP_Zu);
+const HALO = vec3<u32>(HALO_Xu, HALO_Yu, HALO_Zu);
+
+var<workgroup> tile: array<vec3<f32>, HALO_VOLUME>;
+
+fn tile_index(local: vec3<u32>) -> u32 {
+    return (local.z * HALO.y + local.y) * HALO.x + local.x;
+}
+
+// tile position 0 holds the texel `origin`, the block itself starts at `origin` for H and one
+// texel further for E since their curls look in opposite directions
+fn load_tile(origin: vec3<i32>, local_index: u32) {
+    for (var i = local_index; i < HALO.x * HALO.y * HALO.z; i += WORKGROUP.x * WORKGROUP.y * WORKGROUP.z) {
+        let local = vec3<u32>(i % HALO.x, (i / HALO.x) % HALO.y, i / (HALO.x * HALO.y));
+        tile[i] = load_conjugative_field(origin + vec3<i32>(local));
+    }
+    workgroupBarrier();
+}
+
+@compute
+@workgroup_size(WORKGROUP_X, WORKGROUP_Y, WORKGROUP_Z)
+fn update_magnetic_field(
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    let block = workgroup_id * WORKGROUP;
+    load_tile(vec3<i32>(block + c_param.offset), local_index);
+    let global_invocation_id = block + local_id;
+    if any(global_invocation_id + c_param.offset >= c_param.end) {
+        return;
+    }
+    let texel = vec3<i32>(global_invocation_id + c_param.offset);
+    let curl = magnetic_curl(
+        tile[tile_index(local_id)],
+        tile[tile_index(local_id + vec3<u32>(1u, 0u, 0u))],
+        tile[tile_index(local_id + vec3<u32>(0u, 1u, 0u))],
+        tile[tile_index(local_id + vec3<u32>(0u, 0u, 1u))],
+    );
+    store_magnetic(texel, load_update_field(texel), curl, vec3<f32>(0.0));
+}
+
+@compute
+@workgroup_size(WORKGROUP_X, WORKGROUP_Y, WORKGROUP_Z)
+fn update_electric_field(
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    let block = workgroup_id * WORKGROUP;
+    load_tile(vec3<i32>(block + c_param.offset) - vec3<i32>(1), local_index);
+    let global_invocation_id = block + local_id;
+    if any(global_invocation_id + c_param.offset >= c_param.end) {
+        return;
+    }
+    let texel = vec3<i32>(global_invocation_id + c_param.offset);
+    let local = local_id + vec3<u32>(1u);
+    let curl = electric_curl(
+        tile[tile_index(local)],
+        tile[tile_index(local - vec3<u32>(1u, 0u, 0u))],
+        tile[tile_index(local - vec3<u32>(0u, 1u, 0u))],
+        tile[tile_index(local - vec3<u32>(0u, 0u, 1u))],
+    );
+    store_electric(texel, load_update_field(texel), curl, vec3<f32>(0.0));
+}
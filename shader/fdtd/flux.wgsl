@@ -0,0 +1,115 @@
+struct FluxParam {
+    low: vec3<u32>, // first texel of the box
+    faces: u32, // bit 2 * axis + side per face that is recorded
+    high: vec3<u32>, // last texel of the box
+};
+
+var<push_constant> c_param: FluxParam;
+
+@group(0)
+@binding(0)
+var electric_field: texture_3d<f32>;
+
+@group(0)
+@binding(1)
+var magnetic_field: texture_3d<f32>;
+
+// per face cell the running sum of the outward E x H at the cell center
+@group(0)
+@binding(2)
+var<storage, read_write> sums: array<f32>;
+
+// per workgroup the sum of the current outward E x H over its cells
+@group(0)
+@binding(3)
+var<storage, read_write> partial_sums: array<f32>;
+
+var<workgroup> workgroup_flux: array<f32, 64>;
+
+fn load(field: texture_3d<f32>, texel: vec3<u32>) -> vec3<f32> {
+    return textureLoad(field, min(texel, textureDimensions(field) - 1u), 0).xyz;
+}
+
+// E_i sits half a cell along i from the texel, averaged over the four around the center
+fn centered_electric(texel: vec3<u32>) -> vec3<f32> {
+    let x = load(electric_field, texel).x + load(electric_field, texel + vec3<u32>(0u, 1u, 0u)).x
+        + load(electric_field, texel + vec3<u32>(0u, 0u, 1u)).x + load(electric_field, texel + vec3<u32>(0u, 1u, 1u)).x;
+    let y = load(electric_field, texel).y + load(electric_field, texel + vec3<u32>(1u, 0u, 0u)).y
+        + load(electric_field, texel + vec3<u32>(0u, 0u, 1u)).y + load(electric_field, texel + vec3<u32>(1u, 0u, 1u)).y;
+    let z = load(electric_field, texel).z + load(electric_field, texel + vec3<u32>(1u, 0u, 0u)).z
+        + load(electric_field, texel + vec3<u32>(0u, 1u, 0u)).z + load(electric_field, texel + vec3<u32>(1u, 1u, 0u)).z;
+    return 0.25 * vec3<f32>(x, y, z);
+}
+
+// H_i sits half a cell off the center along i, averaged over the two around it
+fn centered_magnetic(texel: vec3<u32>) -> vec3<f32> {
+    let here = load(magnetic_field, texel);
+    return 0.5 * (here + vec3<f32>(
+        load(magnetic_field, texel + vec3<u32>(1u, 0u, 0u)).x,
+        load(magnetic_field, texel + vec3<u32>(0u, 1u, 0u)).y,
+        load(magnetic_field, texel + vec3<u32>(0u, 0u, 1u)).z,
+    ));
+}
+
+// cells of `face`, none unless it is recorded
+fn face_cells(extent: vec3<u32>, face: u32) -> u32 {
+    let axis = face / 2u;
+    return ((c_param.faces >> face) & 1u) * extent[(axis + 1u) % 3u] * extent[(axis + 2u) % 3u];
+}
+
+// the outward E x H at face cell `cell`, also added to its running sum
+fn cell_flux(cell: u32, extent: vec3<u32>) -> f32 {
+    // the same layout as the surface transforms
+    var face = 0u;
+    var first = 0u;
+    while cell >= first + face_cells(extent, face) {
+        first += face_cells(extent, face);
+        face += 1u;
+    }
+    let axis = face / 2u;
+    let u = select(0u, 1u, axis == 0u);
+    let v = select(2u, 1u, axis == 2u);
+    let local = cell - first;
+    var texel = c_param.low;
+    if face % 2u == 1u {
+        texel[axis] = c_param.high[axis];
+    }
+    texel[u] += local % extent[u];
+    texel[v] += local / extent[u];
+
+    let poynting = cross(centered_electric(texel), centered_magnetic(texel));
+    let flux = select(-1.0, 1.0, face % 2u == 1u) * poynting[axis];
+    sums[cell] += flux;
+    return flux;
+}
+
+@compute
+@workgroup_size(64)
+fn accumulate(
+    @builtin(global_invocation_id) index: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) workgroup: vec3<u32>,
+    @builtin(num_workgroups) workgroups: vec3<u32>,
+) {
+    let extent = c_param.high - c_param.low + 1u;
+    var total = 0u;
+    for (var face = 0u; face < 6u; face++) {
+        total += face_cells(extent, face);
+    }
+    let cell = index.x + index.y * workgroups.x * 64u;
+    var flux = 0.0;
+    if cell < total {
+        flux = cell_flux(cell, extent);
+    }
+
+    workgroup_flux[local_index] = flux;
+    for (var stride = 32u; stride > 0u; stride /= 2u) {
+        workgroupBarrier();
+        if local_index < stride {
+            workgroup_flux[local_index] += workgroup_flux[local_index + stride];
+        }
+    }
+    if local_index == 0u {
+        partial_sums[workgroup.x + workgroup.y * workgroups.x] = workgroup_flux[0];
+    }
+}
@@ -0,0 +1,29 @@
+struct IntensityParam {
+    weight: f32, // of the newest sample, 1 restarts the average
+};
+
+var<push_constant> c_param: IntensityParam;
+
+@group(0)
+@binding(0)
+var electric_field: texture_3d<f32>;
+
+@group(0)
+@binding(1)
+var intensity: texture_storage_3d<r32float, read_write>;
+
+@compute
+@workgroup_size(WORKGROUP_X, WORKGROUP_Y, WORKGROUP_Z)
+fn accumulate_intensity(@builtin(global_invocation_id) index: vec3<u32>) {
+    if any(index >= textureDimensions(intensity)) {
+        return;
+    }
+    let field = textureLoad(electric_field, index, 0).xyz;
+    let sample = dot(field, field);
+    // the previous contents are stale after a restart and must not leak through as NaN
+    var average = sample;
+    if (c_param.weight < 1.0) {
+        average = mix(textureLoad(intensity, index).x, sample, c_param.weight);
+    }
+    textureStore(intensity, index, vec4<f32>(average, 0.0, 0.0, 0.0));
+}
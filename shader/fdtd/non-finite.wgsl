@@ -0,0 +1,29 @@
+@group(0)
+@binding(0)
+var electric_field: texture_3d<f32>;
+
+@group(0)
+@binding(1)
+var magnetic_field: texture_3d<f32>;
+
+@group(0)
+@binding(2)
+var<storage, read_write> non_finite_cells: atomic<u32>;
+
+// infinite or NaN when the exponent bits are all set, comparing a NaN with itself may be
+// folded away by the compiler
+fn is_finite(field: vec3<f32>) -> bool {
+    let exponent = vec3<u32>(0x7f800000u);
+    return all((bitcast<vec3<u32>>(field) & exponent) != exponent);
+}
+
+@compute
+@workgroup_size(WORKGROUP_X, WORKGROUP_Y, WORKGROUP_Z)
+fn count_non_finite(@builtin(global_invocation_id) index: vec3<u32>) {
+    if any(index >= textureDimensions(electric_field)) {
+        return;
+    }
+    if !is_finite(textureLoad(electric_field, index, 0).xyz) || !is_finite(textureLoad(magnetic_field, index, 0).xyz) {
+        atomicAdd(&non_finite_cells, 1u);
+    }
+}
@@ -0,0 +1,39 @@
+struct Plane {
+    low: vec3<u32>,   // first texel of the plane
+    normal: u32,      // grid axis the plane is normal to, 0 = x, 1 = y, 2 = z
+    size: vec2<u32>,  // cells along the two other axes, in x, y, z order
+};
+
+@group(0)
+@binding(0)
+var electric_field: texture_3d<f32>;
+
+@group(0)
+@binding(1)
+var magnetic_field: texture_3d<f32>;
+
+@group(0)
+@binding(2)
+var<uniform> plane: Plane;
+
+// E along the two plane axes, then H along them, per cell with the first axis running fastest
+@group(0)
+@binding(3)
+var<storage, read_write> tangential: array<vec4<f32>>;
+
+@compute
+@workgroup_size(8, 8, 1)
+fn record(@builtin(global_invocation_id) index: vec3<u32>) {
+    if any(index.xy >= plane.size) {
+        return;
+    }
+    let u = select(0u, 1u, plane.normal == 0u);
+    let v = select(2u, 1u, plane.normal == 2u);
+    var texel = plane.low;
+    texel[u] += index.x;
+    texel[v] += index.y;
+    let electric = textureLoad(electric_field, texel, 0);
+    let magnetic = textureLoad(magnetic_field, texel, 0);
+    tangential[index.x + index.y * plane.size.x] =
+        vec4<f32>(electric[u], electric[v], magnetic[u], magnetic[v]);
+}
@@ -32,35 +32,30 @@ var psi_z_y: texture_storage_3d<r32float, read_write>;
 
 @group(0)
 @binding(6)
-var field_x: texture_storage_3d<r32float, read>;
+var field: texture_storage_3d<rgba32float, read>;
 
 @group(0)
 @binding(7)
-var field_y: texture_storage_3d<r32float, read>;
-
-@group(0)
-@binding(8)
-var field_z: texture_storage_3d<r32float, read>;
-
-@group(0)
-@binding(9)
 var constants_map: texture_storage_3d<rg32float, read>;
 
 @compute
-@workgroup_size(8, 8, 8)
+@workgroup_size(WORKGROUP_X, WORKGROUP_Y, WORKGROUP_Z)
 fn update_electric_psi(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    if any(global_invocation_id >= textureDimensions(psi_x_y)) {
+        return;
+    }
     let pml_texel = vec3<i32>(global_invocation_id);
     let field_texel = vec3<i32>(global_invocation_id + c_param.offset);
-    let local_h = vec3<f32>(textureLoad(field_x, field_texel).x, textureLoad(field_y, field_texel).x, textureLoad(field_z, field_texel).x);
+    let local_h = textureLoad(field, field_texel).xyz;
     let x_actual_texel = vec3<i32>(field_texel.x - 1, field_texel.y, field_texel.z);
-    let h_shift_x_y = textureLoad(field_y, x_actual_texel).x;
-    let h_shift_x_z = textureLoad(field_z, x_actual_texel).x;
+    let h_shift_x_y = textureLoad(field, x_actual_texel).y;
+    let h_shift_x_z = textureLoad(field, x_actual_texel).z;
     let y_actual_texel = vec3<i32>(field_texel.x, field_texel.y - 1, field_texel.z);
-    let h_shift_y_x = textureLoad(field_x, y_actual_texel).x;
-    let h_shift_y_z = textureLoad(field_z, y_actual_texel).x;
+    let h_shift_y_x = textureLoad(field, y_actual_texel).x;
+    let h_shift_y_z = textureLoad(field, y_actual_texel).z;
     let z_actual_texel = vec3<i32>(field_texel.x, field_texel.y, field_texel.z - 1);
-    let h_shift_z_x = textureLoad(field_x, z_actual_texel).x;
-    let h_shift_z_y = textureLoad(field_y, z_actual_texel).x;
+    let h_shift_z_x = textureLoad(field, z_actual_texel).x;
+    let h_shift_z_y = textureLoad(field, z_actual_texel).y;
     let constant = textureLoad(constants_map, field_texel).xy;
     let c = (c_param.psi_constant - 1.0) * c_param.alpha_factor;
     let new_psi_x_y = textureLoad(psi_x_y, pml_texel).x * c_param.psi_constant + (local_h.z - h_shift_y_z) * constant.x * c;
@@ -78,20 +73,23 @@ fn update_electric_psi(@builtin(global_invocation_id) global_invocation_id: vec3
 }
 
 @compute
-@workgroup_size(8, 8, 8)
+@workgroup_size(WORKGROUP_X, WORKGROUP_Y, WORKGROUP_Z)
 fn update_magnetic_psi(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    if any(global_invocation_id >= textureDimensions(psi_x_y)) {
+        return;
+    }
     let pml_texel = vec3<i32>(global_invocation_id);
     let field_texel = vec3<i32>(global_invocation_id + c_param.offset);
-    let local_e = vec3<f32>(textureLoad(field_x, field_texel).x, textureLoad(field_y, field_texel).x, textureLoad(field_z, field_texel).x);
+    let local_e = textureLoad(field, field_texel).xyz;
     let x_actual_texel = vec3<i32>(field_texel.x + 1, field_texel.y, field_texel.z);
-    let e_shift_x_y = textureLoad(field_y, x_actual_texel).x;
-    let e_shift_x_z = textureLoad(field_z, x_actual_texel).x;
+    let e_shift_x_y = textureLoad(field, x_actual_texel).y;
+    let e_shift_x_z = textureLoad(field, x_actual_texel).z;
     let y_actual_texel = vec3<i32>(field_texel.x, field_texel.y + 1, field_texel.z);
-    let e_shift_y_x = textureLoad(field_x, y_actual_texel).x;
-    let e_shift_y_z = textureLoad(field_z, y_actual_texel).x;
+    let e_shift_y_x = textureLoad(field, y_actual_texel).x;
+    let e_shift_y_z = textureLoad(field, y_actual_texel).z;
     let z_actual_texel = vec3<i32>(field_texel.x, field_texel.y, field_texel.z + 1);
-    let e_shift_z_x = textureLoad(field_x, z_actual_texel).x;
-    let e_shift_z_y = textureLoad(field_y, z_actual_texel).x;
+    let e_shift_z_x = textureLoad(field, z_actual_texel).x;
+    let e_shift_z_y = textureLoad(field, z_actual_texel).y;
     let constant = textureLoad(constants_map, field_texel).xy;
     let c = (c_param.psi_constant - 1.0) * c_param.alpha_factor;
     let new_psi_x_y = textureLoad(psi_x_y, pml_texel).x * c_param.psi_constant - (local_e.z - e_shift_y_z) * constant.x * c;
@@ -15,29 +15,28 @@ var psi_z_x: texture_storage_3d<r32float, read_write>;
 
 @group(0)
 @binding(2)
-var field_y: texture_storage_3d<r32float, read>;
+var field: texture_storage_3d<rgba32float, read>;
 
 @group(0)
 @binding(3)
-var field_z: texture_storage_3d<r32float, read>;
-
-@group(0)
-@binding(4)
 var constants_map: texture_storage_3d<rg32float, read>;
 
 @group(0)
-@binding(5)
+@binding(4)
 var psi_constant_map: texture_storage_2d<r32float, read>;
 
 @compute
-@workgroup_size(8, 8, 8)
+@workgroup_size(WORKGROUP_X, WORKGROUP_Y, WORKGROUP_Z)
 fn update_electric_psi(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    if any(global_invocation_id >= textureDimensions(psi_y_x)) {
+        return;
+    }
     let pml_texel = vec3<i32>(global_invocation_id);
     let field_texel = vec3<i32>(global_invocation_id + c_param.offset);
-    let local_h = vec3<f32>(0.0, textureLoad(field_y, field_texel).x, textureLoad(field_z, field_texel).x);
+    let local_h = textureLoad(field, field_texel).xyz;
     let actual_texel = vec3<i32>(field_texel.x - 1, field_texel.y, field_texel.z);
-    let h_shift_x_y = textureLoad(field_y, actual_texel).x;
-    let h_shift_x_z = textureLoad(field_z, actual_texel).x;
+    let h_shift_x_y = textureLoad(field, actual_texel).y;
+    let h_shift_x_z = textureLoad(field, actual_texel).z;
     let constant = textureLoad(constants_map, field_texel).xy;
     let psi_constant = textureLoad(psi_constant_map, pml_texel.yz).x;
     let c = (psi_constant - 1.0) * c_param.alpha_factor;
@@ -48,14 +47,17 @@ fn update_electric_psi(@builtin(global_invocation_id) global_invocation_id: vec3
 }
 
 @compute
-@workgroup_size(8, 8, 8)
+@workgroup_size(WORKGROUP_X, WORKGROUP_Y, WORKGROUP_Z)
 fn update_magnetic_psi(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    if any(global_invocation_id >= textureDimensions(psi_y_x)) {
+        return;
+    }
     let pml_texel = vec3<i32>(global_invocation_id);
     let field_texel = vec3<i32>(global_invocation_id + c_param.offset);
-    let local_e = vec3<f32>(0.0, textureLoad(field_y, field_texel).x, textureLoad(field_z, field_texel).x);
+    let local_e = textureLoad(field, field_texel).xyz;
     let actual_texel = vec3<i32>(field_texel.x + 1, field_texel.y, field_texel.z);
-    let e_shift_x_y = textureLoad(field_y, actual_texel).x;
-    let e_shift_x_z = textureLoad(field_z, actual_texel).x;
+    let e_shift_x_y = textureLoad(field, actual_texel).y;
+    let e_shift_x_z = textureLoad(field, actual_texel).z;
     let constant = textureLoad(constants_map, field_texel).xy;
     let psi_constant = textureLoad(psi_constant_map, pml_texel.yz).x;
     let c = (psi_constant - 1.0) * c_param.alpha_factor;
@@ -15,29 +15,28 @@ var psi_y_z: texture_storage_3d<r32float, read_write>;
 
 @group(0)
 @binding(2)
-var field_x: texture_storage_3d<r32float, read>;
+var field: texture_storage_3d<rgba32float, read>;
 
 @group(0)
 @binding(3)
-var field_y: texture_storage_3d<r32float, read>;
-
-@group(0)
-@binding(4)
 var constants_map: texture_storage_3d<rg32float, read>;
 
 @group(0)
-@binding(5)
+@binding(4)
 var psi_constant_map: texture_storage_2d<r32float, read>;
 
 @compute
-@workgroup_size(8, 8, 8)
+@workgroup_size(WORKGROUP_X, WORKGROUP_Y, WORKGROUP_Z)
 fn update_electric_psi(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    if any(global_invocation_id >= textureDimensions(psi_x_z)) {
+        return;
+    }
     let pml_texel = vec3<i32>(global_invocation_id);
     let field_texel = vec3<i32>(global_invocation_id + c_param.offset);
-    let local_h = vec3<f32>(textureLoad(field_x, field_texel).x, textureLoad(field_y, field_texel).x, 0.0);
+    let local_h = textureLoad(field, field_texel).xyz;
     let actual_texel = vec3<i32>(field_texel.x, field_texel.y, field_texel.z - 1);
-    let h_shift_z_x = textureLoad(field_x, actual_texel).x;
-    let h_shift_z_y = textureLoad(field_y, actual_texel).x;
+    let h_shift_z_x = textureLoad(field, actual_texel).x;
+    let h_shift_z_y = textureLoad(field, actual_texel).y;
     let constant = textureLoad(constants_map, field_texel).xy;
     let psi_constant = textureLoad(psi_constant_map, pml_texel.xy).x;
     let c = (psi_constant - 1.0) * c_param.alpha_factor;
@@ -48,14 +47,17 @@ fn update_electric_psi(@builtin(global_invocation_id) global_invocation_id: vec3
 }
 
 @compute
-@workgroup_size(8, 8, 8)
+@workgroup_size(WORKGROUP_X, WORKGROUP_Y, WORKGROUP_Z)
 fn update_magnetic_psi(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    if any(global_invocation_id >= textureDimensions(psi_x_z)) {
+        return;
+    }
     let pml_texel = vec3<i32>(global_invocation_id);
     let field_texel = vec3<i32>(global_invocation_id + c_param.offset);
-    let local_e = vec3<f32>(textureLoad(field_x, field_texel).x, textureLoad(field_y, field_texel).x, 0.0);
+    let local_e = textureLoad(field, field_texel).xyz;
     let actual_texel = vec3<i32>(field_texel.x, field_texel.y, field_texel.z + 1);
-    let e_shift_z_x = textureLoad(field_x, actual_texel).x;
-    let e_shift_z_y = textureLoad(field_y, actual_texel).x;
+    let e_shift_z_x = textureLoad(field, actual_texel).x;
+    let e_shift_z_y = textureLoad(field, actual_texel).y;
     let constant = textureLoad(constants_map, field_texel).xy;
     let psi_constant = textureLoad(psi_constant_map, pml_texel.xy).x;
     let c = (psi_constant - 1.0) * c_param.alpha_factor;
@@ -0,0 +1,131 @@
+// Yee update shared by fdtd-3d.wgsl and the PML field shaders, which are appended to this file.
+
+struct Param {
+    dimension: vec3<u32>, // total dimension, only required to ensure boundary
+    pmc_faces: u32, // bit 2 * axis + side set where that face is a PMC wall instead of a PEC one
+    offset: vec3<u32>, // first texel covered by the dispatch
+    periodic: u32, // bit per axis whose faces wrap onto each other
+    end: vec3<u32>, // past the last texel of the interior, unused by the PML field shaders
+    partner_sign: f32, // -1 updating the real half of the Bloch fields, 1 the imaginary one
+    bloch_phase: vec3<f32>, // k L across each periodic axis, zero without a Bloch wave vector
+}
+
+var<push_constant> c_param: Param;
+
+// x, y, z components in rgb, a is unused
+@group(0)
+@binding(0)
+var update_field: texture_storage_3d<rgba32float, read_write>;
+
+@group(0)
+@binding(1)
+var conjugative_field: texture_storage_3d<rgba32float, read>;
+
+@group(0)
+@binding(2)
+var constants_map: texture_storage_3d<rg32float, read>;
+
+// the conjugative field of the other half of the Bloch fields, the conjugative field itself
+// without a Bloch wave vector
+@group(0)
+@binding(3)
+var partner_field: texture_storage_3d<rgba32float, read>;
+
+fn load_update_field(texel: vec3<i32>) -> vec3<f32> {
+    return textureLoad(update_field, texel).xyz;
+}
+
+fn is_periodic(axis: u32) -> bool {
+    return ((c_param.periodic >> axis) & 1u) == 1u;
+}
+
+// wraps around periodic axes, and is zero outside the grid otherwise rather than whatever
+// out-of-bounds reads return on the device, so the walls see no field beyond them
+fn load_conjugative_field(texel: vec3<i32>) -> vec3<f32> {
+    let dimension = vec3<i32>(c_param.dimension);
+    let periodic = vec3<bool>(is_periodic(0u), is_periodic(1u), is_periodic(2u));
+    let source = select(texel, (texel + dimension) % dimension, periodic);
+    if any(source < vec3<i32>(0)) || any(source >= dimension) {
+        return vec3<f32>(0.0);
+    }
+    let field = textureLoad(conjugative_field, source).xyz;
+    // past the high face the complex field is exp(i k L) times the wrapped one, past the low
+    // face exp(-i k L) times it, its real and imaginary halves mix
+    let phase = dot(c_param.bloch_phase, vec3<f32>(source < texel) - vec3<f32>(source > texel));
+    if phase == 0.0 {
+        return field;
+    }
+    let partner = textureLoad(partner_field, source).xyz;
+    return field * cos(phase) + partner * (c_param.partner_sign * sin(phase));
+}
+
+// whether texel lies in the outermost layer of a face normal to axis that is a PMC wall, or a PEC
+// one when pmc is false, periodic axes have no walls
+fn on_wall(texel: vec3<i32>, axis: u32, pmc: bool) -> bool {
+    if is_periodic(axis) {
+        return false;
+    }
+    let low = ((c_param.pmc_faces >> (2u * axis)) & 1u) == 1u;
+    let high = ((c_param.pmc_faces >> (2u * axis + 1u)) & 1u) == 1u;
+    return (texel[axis] == 0 && low == pmc) || (texel[axis] == i32(c_param.dimension[axis]) - 1 && high == pmc);
+}
+
+// e_shift_* is E one cell further along that axis
+fn magnetic_curl(local_e: vec3<f32>, e_shift_x: vec3<f32>, e_shift_y: vec3<f32>, e_shift_z: vec3<f32>) -> vec3<f32> {
+    let diff_hx = (e_shift_z.y - local_e.y) - (e_shift_y.z - local_e.z);
+    let diff_hy = (e_shift_x.z - local_e.z) - (e_shift_z.x - local_e.x);
+    let diff_hz = (e_shift_y.x - local_e.x) - (e_shift_x.y - local_e.y);
+    return vec3<f32>(diff_hx, diff_hy, diff_hz);
+}
+
+// psi is the PML correction, zero in the interior. Every pass stores through this one expression
+// so that a PML without loss updates bit for bit like the interior.
+fn store_magnetic(texel: vec3<i32>, prev_h: vec3<f32>, curl: vec3<f32>, psi: vec3<f32>) {
+    let constant = textureLoad(constants_map, texel).x;
+
+    // PEC: no normal magnetic field
+    // PMC: no tangential magnetic field
+    var store_value = (prev_h + constant * curl + psi) * vec3<f32>(
+        f32(!on_wall(texel, 0u, false) && !on_wall(texel, 1u, true) && !on_wall(texel, 2u, true)),
+        f32(!on_wall(texel, 1u, false) && !on_wall(texel, 0u, true) && !on_wall(texel, 2u, true)),
+        f32(!on_wall(texel, 2u, false) && !on_wall(texel, 0u, true) && !on_wall(texel, 1u, true)),
+    );
+    textureStore(update_field, texel, vec4<f32>(store_value, 0.0));
+}
+
+fn update_magnetic(texel: vec3<i32>, prev_h: vec3<f32>, psi: vec3<f32>) {
+    let local_e = load_conjugative_field(texel);
+    let e_shift_x = load_conjugative_field(vec3<i32>(texel.x + 1, texel.y, texel.z));
+    let e_shift_y = load_conjugative_field(vec3<i32>(texel.x, texel.y + 1, texel.z));
+    let e_shift_z = load_conjugative_field(vec3<i32>(texel.x, texel.y, texel.z + 1));
+    store_magnetic(texel, prev_h, magnetic_curl(local_e, e_shift_x, e_shift_y, e_shift_z), psi);
+}
+
+// h_shift_* is H one cell back along that axis
+fn electric_curl(local_h: vec3<f32>, h_shift_x: vec3<f32>, h_shift_y: vec3<f32>, h_shift_z: vec3<f32>) -> vec3<f32> {
+    let diff_ex = (local_h.z - h_shift_y.z) - (local_h.y - h_shift_z.y);
+    let diff_ey = (local_h.x - h_shift_z.x) - (local_h.z - h_shift_x.z);
+    let diff_ez = (local_h.y - h_shift_x.y) - (local_h.x - h_shift_y.x);
+    return vec3<f32>(diff_ex, diff_ey, diff_ez);
+}
+
+fn store_electric(texel: vec3<i32>, prev_e: vec3<f32>, curl: vec3<f32>, psi: vec3<f32>) {
+    let constant = textureLoad(constants_map, texel).x;
+
+    // PEC: no tangential electric field
+    // PMC: no normal electric field
+    var store_value = (prev_e + constant * curl + psi) * vec3<f32>(
+        f32(!on_wall(texel, 0u, true) && !on_wall(texel, 1u, false) && !on_wall(texel, 2u, false)),
+        f32(!on_wall(texel, 1u, true) && !on_wall(texel, 0u, false) && !on_wall(texel, 2u, false)),
+        f32(!on_wall(texel, 2u, true) && !on_wall(texel, 0u, false) && !on_wall(texel, 1u, false)),
+    );
+    textureStore(update_field, texel, vec4<f32>(store_value, 0.0));
+}
+
+fn update_electric(texel: vec3<i32>, prev_e: vec3<f32>, psi: vec3<f32>) {
+    let local_h = load_conjugative_field(texel);
+    let h_shift_x = load_conjugative_field(vec3<i32>(texel.x - 1, texel.y, texel.z));
+    let h_shift_y = load_conjugative_field(vec3<i32>(texel.x, texel.y - 1, texel.z));
+    let h_shift_z = load_conjugative_field(vec3<i32>(texel.x, texel.y, texel.z - 1));
+    store_electric(texel, prev_e, electric_curl(local_h, h_shift_x, h_shift_y, h_shift_z), psi);
+}
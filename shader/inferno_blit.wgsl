@@ -0,0 +1,92 @@
+struct SliceParam {
+    slice_position: f32,
+    slice_mode: u32,
+    scaling_factor: f32,
+    log_scale: u32,
+    log_floor: f32,
+    component: u32,
+    view_scale: vec2<f32>,
+    view_offset: vec2<f32>,
+    overlay_opacity: f32,
+};
+
+var<push_constant> c_param: SliceParam;
+
+struct VertexOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+};
+
+// x, y, z components in rgb
+@group(0)
+@binding(0)
+var t_field: texture_3d<f32>;
+
+@group(0)
+@binding(1)
+var s_sampler: sampler;
+
+// permittivity, for the material outlines
+@group(0)
+@binding(2)
+var t_constants: texture_3d<f32>;
+
+// the selected signed component, or the length for component 3
+fn select_component(field: vec3<f32>) -> f32 {
+    if (c_param.component == 3u) {
+        return length(field);
+    }
+    return field[c_param.component];
+}
+
+// blends the outlines of the materials over `color`
+fn outline(color: vec3<f32>, slice: vec3<f32>) -> vec3<f32> {
+    if (c_param.overlay_opacity <= 0.0) {
+        return color;
+    }
+    let size = vec3<i32>(textureDimensions(t_constants));
+    let texel = clamp(vec3<i32>(slice * vec3<f32>(size)), vec3<i32>(0), size - 1);
+    // neighbours along the axes spanning the slice
+    var steps = array<vec3<i32>, 2>(vec3<i32>(1, 0, 0), vec3<i32>(0, 1, 0));
+    if (c_param.slice_mode == 1u) {
+        steps[1] = vec3<i32>(0, 0, 1);
+    } else if (c_param.slice_mode == 2u) {
+        steps[0] = vec3<i32>(0, 1, 0);
+        steps[1] = vec3<i32>(0, 0, 1);
+    }
+    let center = textureLoad(t_constants, texel, 0).x;
+    var edge = false;
+    for (var i = 0; i < 2; i++) {
+        edge = edge || textureLoad(t_constants, min(texel + steps[i], size - 1), 0).x != center;
+    }
+    return mix(color, vec3<f32>(1.0), select(0.0, c_param.overlay_opacity, edge));
+}
+
+// polynomial fit of matplotlib's inferno, t in [0, 1]
+fn inferno(t: f32) -> vec3<f32> {
+    let c0 = vec3<f32>(0.0002189403691192265, 0.001651004631001012, -0.01948089843709184);
+    let c1 = vec3<f32>(0.1065134194856116, 0.5639564367884091, 3.932712388889277);
+    let c2 = vec3<f32>(11.60249308247187, -3.972853965665698, -15.9423941062914);
+    let c3 = vec3<f32>(-41.70399613139459, 17.43639888205313, 44.35414519872813);
+    let c4 = vec3<f32>(77.162935699427, -33.40235894210092, -81.80730925738993);
+    let c5 = vec3<f32>(-71.31942824499214, 32.62606426397723, 73.20951985803202);
+    let c6 = vec3<f32>(25.13112622477341, -12.24266895238567, -23.07032500287172);
+    return c0 + t * (c1 + t * (c2 + t * (c3 + t * (c4 + t * (c5 + t * c6)))));
+}
+
+@fragment
+fn fs_main(fin: VertexOut) -> @location(0) vec4<f32> {
+    var slice = vec3<f32>(0.0);
+    if (c_param.slice_mode == 0u) {
+        slice = vec3<f32>(fin.tex_coord, c_param.slice_position);
+    } else if (c_param.slice_mode == 1u) {
+        slice = vec3<f32>(fin.tex_coord.x, c_param.slice_position, fin.tex_coord.y);
+    } else if (c_param.slice_mode == 2u) {
+        slice = vec3<f32>(c_param.slice_position, fin.tex_coord.x, fin.tex_coord.y);
+    } else {
+        discard;
+    }
+    let value = select_component(textureSample(t_field, s_sampler, slice).xyz) * c_param.scaling_factor;
+    let color = clamp(inferno(clamp(display_scale(abs(value), c_param.log_scale, c_param.log_floor), 0.0, 1.0)), vec3<f32>(0.0), vec3<f32>(1.0));
+    return vec4<f32>(outline(color, slice), 1.0);
+}
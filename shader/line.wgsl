@@ -0,0 +1,82 @@
+// line plot of the field along the z column of the 1D mode, drawn over the whole window with
+// z running left to right and a scaled value of 1 at the top edge
+
+struct SliceParam {
+    slice_position: f32,
+    slice_mode: u32,
+    scaling_factor: f32,
+    log_scale: u32,
+    log_floor: f32,
+    component: u32,
+    view_scale: vec2<f32>,
+    view_offset: vec2<f32>,
+    overlay_opacity: f32,
+};
+
+var<push_constant> c_param: SliceParam;
+
+struct VertexOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+};
+
+// x, y, z components in rgb
+@group(0)
+@binding(0)
+var t_field: texture_3d<f32>;
+
+@group(0)
+@binding(1)
+var s_sampler: sampler;
+
+// permittivity, shades the background
+@group(0)
+@binding(2)
+var t_constants: texture_3d<f32>;
+
+const LINE_COLOR = vec3<f32>(1.0, 0.8, 0.0);
+const ZERO_COLOR = vec3<f32>(0.3, 0.3, 0.3);
+
+// the selected signed component, for component 3 the sum, as only one of them is nonzero in 1D
+fn cell_value(z: i32) -> f32 {
+    let field = textureLoad(t_field, vec3<i32>(0, 0, z), 0).xyz;
+    if (c_param.component == 3u) {
+        return field.x + field.y + field.z;
+    }
+    return field[c_param.component];
+}
+
+// scaled value at u in [0, 1] along the column, linear between the cell centers
+fn value_at(u: f32) -> f32 {
+    let cells = i32(textureDimensions(t_field).z);
+    let position = clamp(u * f32(cells) - 0.5, 0.0, f32(cells - 1));
+    let low = i32(floor(position));
+    let high = min(low + 1, cells - 1);
+    return mix(cell_value(low), cell_value(high), position - f32(low)) * c_param.scaling_factor;
+}
+
+@fragment
+fn fs_main(fin: VertexOut) -> @location(0) vec4<f32> {
+    let pixel = fwidth(fin.tex_coord);
+    let u = fin.tex_coord.x;
+    let y = 1.0 - 2.0 * fin.tex_coord.y;
+    let line_width = 3.0 * pixel.y;
+
+    let cells = i32(textureDimensions(t_constants).z);
+    let cell = clamp(i32(u * f32(cells)), 0, cells - 1);
+    let permittivity = textureLoad(t_constants, vec3<i32>(0, 0, cell), 0).x;
+    // denser media are lighter, vacuum stays black
+    var color = vec3<f32>(0.25 * (1.0 - 1.0 / max(permittivity, 1.0)));
+    if (abs(y) < pixel.y) {
+        color = ZERO_COLOR;
+    }
+
+    // covers the span the line crosses within the pixel, so steep parts stay connected
+    let values = vec3<f32>(value_at(u - pixel.x), value_at(u), value_at(u + pixel.x));
+    let low = min(values.x, min(values.y, values.z)) - line_width;
+    let high = max(values.x, max(values.y, values.z)) + line_width;
+    if (y >= low && y <= high) {
+        color = LINE_COLOR;
+    }
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,17 @@
+struct VertexOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) pos: vec2<f32>, @location(1) color: vec4<f32>) -> VertexOut {
+    var vout: VertexOut;
+    vout.position = vec4<f32>(pos, 0.0, 1.0);
+    vout.color = color;
+    return vout;
+}
+
+@fragment
+fn fs_main(fin: VertexOut) -> @location(0) vec4<f32> {
+    return fin.color;
+}
@@ -0,0 +1,94 @@
+struct SliceParam {
+    slice_position: f32,
+    slice_mode: u32,
+    scaling_factor: f32,
+    log_scale: u32,
+    log_floor: f32,
+    component: u32,
+    view_scale: vec2<f32>,
+    view_offset: vec2<f32>,
+    overlay_opacity: f32,
+};
+
+var<push_constant> c_param: SliceParam;
+
+struct VertexOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+};
+
+// x, y, z components in rgb
+@group(0)
+@binding(0)
+var t_field: texture_3d<f32>;
+
+@group(0)
+@binding(1)
+var s_sampler: sampler;
+
+// permittivity, for the material outlines
+@group(0)
+@binding(2)
+var t_constants: texture_3d<f32>;
+
+// the selected signed component, or the length for component 3
+fn select_component(field: vec3<f32>) -> f32 {
+    if (c_param.component == 3u) {
+        return length(field);
+    }
+    return field[c_param.component];
+}
+
+// blends the outlines of the materials over `color`
+fn outline(color: vec3<f32>, slice: vec3<f32>) -> vec3<f32> {
+    if (c_param.overlay_opacity <= 0.0) {
+        return color;
+    }
+    let size = vec3<i32>(textureDimensions(t_constants));
+    let texel = clamp(vec3<i32>(slice * vec3<f32>(size)), vec3<i32>(0), size - 1);
+    // neighbours along the axes spanning the slice
+    var steps = array<vec3<i32>, 2>(vec3<i32>(1, 0, 0), vec3<i32>(0, 1, 0));
+    if (c_param.slice_mode == 1u) {
+        steps[1] = vec3<i32>(0, 0, 1);
+    } else if (c_param.slice_mode == 2u) {
+        steps[0] = vec3<i32>(0, 1, 0);
+        steps[1] = vec3<i32>(0, 0, 1);
+    }
+    let center = textureLoad(t_constants, texel, 0).x;
+    var edge = false;
+    for (var i = 0; i < 2; i++) {
+        edge = edge || textureLoad(t_constants, min(texel + steps[i], size - 1), 0).x != center;
+    }
+    return mix(color, vec3<f32>(1.0), select(0.0, c_param.overlay_opacity, edge));
+}
+
+// diverging blue-white-red map similar to matplotlib's seismic, s in [-1, 1]
+fn seismic(s: f32) -> vec3<f32> {
+    if (s < -0.5) {
+        return vec3<f32>(0.0, 0.0, 0.3 + 1.4 * (s + 1.0));
+    } else if (s < 0.0) {
+        let t = 1.0 + 2.0 * s;
+        return vec3<f32>(t, t, 1.0);
+    } else if (s < 0.5) {
+        let t = 1.0 - 2.0 * s;
+        return vec3<f32>(1.0, t, t);
+    }
+    return vec3<f32>(1.0 - (s - 0.5), 0.0, 0.0);
+}
+
+// the material view only has an x component
+@fragment
+fn fs_main(fin: VertexOut) -> @location(0) vec4<f32> {
+    var slice = vec3<f32>(0.0);
+    if (c_param.slice_mode == 0u) {
+        slice = vec3<f32>(fin.tex_coord, c_param.slice_position);
+    } else if (c_param.slice_mode == 1u) {
+        slice = vec3<f32>(fin.tex_coord.x, c_param.slice_position, fin.tex_coord.y);
+    } else if (c_param.slice_mode == 2u) {
+        slice = vec3<f32>(c_param.slice_position, fin.tex_coord.x, fin.tex_coord.y);
+    } else {
+        discard;
+    }
+    let value = select_component(textureSample(t_field, s_sampler, slice).xyz) * c_param.scaling_factor;
+    return vec4<f32>(outline(seismic(clamp(sign(value) * display_scale(abs(value), c_param.log_scale, c_param.log_floor), -1.0, 1.0)), slice), 1.0);
+}
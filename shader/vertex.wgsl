@@ -1,3 +1,18 @@
+struct SliceParam {
+    slice_position: f32,
+    slice_mode: u32,
+    scaling_factor: f32,
+    log_scale: u32,
+    log_floor: f32,
+    component: u32,
+    // maps the unit quad into clip space, letterboxing the slice
+    view_scale: vec2<f32>,
+    view_offset: vec2<f32>,
+    overlay_opacity: f32,
+};
+
+var<push_constant> c_param: SliceParam;
+
 struct VertexOut {
     @builtin(position) position: vec4<f32>,
     @location(0) tex_coord: vec2<f32>,
@@ -6,7 +21,7 @@ struct VertexOut {
 @vertex
 fn vs_main(@location(0) pos: vec2<f32>, @location(1) tex_coord: vec2<f32>) -> VertexOut {
     var vout: VertexOut;
-    vout.position = vec4<f32>(pos, 0.0, 1.0);
+    vout.position = vec4<f32>(pos * c_param.view_scale + c_param.view_offset, 0.0, 1.0);
     vout.tex_coord = tex_coord;
     return vout;
 }
@@ -0,0 +1,91 @@
+struct SliceParam {
+    slice_position: f32,
+    slice_mode: u32,
+    scaling_factor: f32,
+    log_scale: u32,
+    log_floor: f32,
+    component: u32,
+    view_scale: vec2<f32>,
+    view_offset: vec2<f32>,
+    overlay_opacity: f32,
+};
+
+var<push_constant> c_param: SliceParam;
+
+struct VertexOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+};
+
+// x, y, z components in rgb
+@group(0)
+@binding(0)
+var t_field: texture_3d<f32>;
+
+@group(0)
+@binding(1)
+var s_sampler: sampler;
+
+// permittivity, for the material outlines
+@group(0)
+@binding(2)
+var t_constants: texture_3d<f32>;
+
+// the selected signed component, or the length for component 3
+fn select_component(field: vec3<f32>) -> f32 {
+    if (c_param.component == 3u) {
+        return length(field);
+    }
+    return field[c_param.component];
+}
+
+// blends the outlines of the materials over `color`
+fn outline(color: vec3<f32>, slice: vec3<f32>) -> vec3<f32> {
+    if (c_param.overlay_opacity <= 0.0) {
+        return color;
+    }
+    let size = vec3<i32>(textureDimensions(t_constants));
+    let texel = clamp(vec3<i32>(slice * vec3<f32>(size)), vec3<i32>(0), size - 1);
+    // neighbours along the axes spanning the slice
+    var steps = array<vec3<i32>, 2>(vec3<i32>(1, 0, 0), vec3<i32>(0, 1, 0));
+    if (c_param.slice_mode == 1u) {
+        steps[1] = vec3<i32>(0, 0, 1);
+    } else if (c_param.slice_mode == 2u) {
+        steps[0] = vec3<i32>(0, 1, 0);
+        steps[1] = vec3<i32>(0, 0, 1);
+    }
+    let center = textureLoad(t_constants, texel, 0).x;
+    var edge = false;
+    for (var i = 0; i < 2; i++) {
+        edge = edge || textureLoad(t_constants, min(texel + steps[i], size - 1), 0).x != center;
+    }
+    return mix(color, vec3<f32>(1.0), select(0.0, c_param.overlay_opacity, edge));
+}
+
+// polynomial fit of matplotlib's viridis, t in [0, 1]
+fn viridis(t: f32) -> vec3<f32> {
+    let c0 = vec3<f32>(0.2777273272234177, 0.005407344544966578, 0.3340998053353061);
+    let c1 = vec3<f32>(0.1050930431085774, 1.404613529898575, 1.384590162594685);
+    let c2 = vec3<f32>(-0.3308618287255563, 0.214847559468213, 0.09509516302823659);
+    let c3 = vec3<f32>(-4.634230498983486, -5.799100973351585, -19.33244095627987);
+    let c4 = vec3<f32>(6.228269936347081, 14.17993336680509, 56.69055260068105);
+    let c5 = vec3<f32>(4.776384997670288, -13.74514537774601, -65.35303263337234);
+    let c6 = vec3<f32>(-5.435455855934631, 4.645852612178535, 26.3124352495832);
+    return c0 + t * (c1 + t * (c2 + t * (c3 + t * (c4 + t * (c5 + t * c6)))));
+}
+
+@fragment
+fn fs_main(fin: VertexOut) -> @location(0) vec4<f32> {
+    var slice = vec3<f32>(0.0);
+    if (c_param.slice_mode == 0u) {
+        slice = vec3<f32>(fin.tex_coord, c_param.slice_position);
+    } else if (c_param.slice_mode == 1u) {
+        slice = vec3<f32>(fin.tex_coord.x, c_param.slice_position, fin.tex_coord.y);
+    } else if (c_param.slice_mode == 2u) {
+        slice = vec3<f32>(c_param.slice_position, fin.tex_coord.x, fin.tex_coord.y);
+    } else {
+        discard;
+    }
+    let value = select_component(textureSample(t_field, s_sampler, slice).xyz) * c_param.scaling_factor;
+    return vec4<f32>(outline(viridis(clamp(display_scale(abs(value), c_param.log_scale, c_param.log_floor), 0.0, 1.0)), slice), 1.0);
+}
@@ -0,0 +1,134 @@
+struct VolumeParam {
+    eye: vec3<f32>,
+    aspect: f32,
+    half_extent: vec3<f32>, // of the grid box, centered on the origin
+    scaling_factor: f32,
+    log_scale: u32,
+    log_floor: f32,
+    component: u32,
+    colormap: u32, // 0 viridis, 1 inferno, 2 seismic, 3 grayscale
+};
+
+var<push_constant> c_param: VolumeParam;
+
+struct VertexOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+};
+
+// x, y, z components in rgb
+@group(0)
+@binding(0)
+var t_field: texture_3d<f32>;
+
+// tan of half the vertical field of view
+const TAN_HALF_FOV: f32 = 0.5;
+// samples along the diagonal of the box
+const SAMPLES: f32 = 256.0;
+// optical depth of a full-scale value over the diagonal
+const DENSITY: f32 = 4.0;
+
+@vertex
+fn vs_main(@location(0) pos: vec2<f32>, @location(1) tex_coord: vec2<f32>) -> VertexOut {
+    var vout: VertexOut;
+    vout.position = vec4<f32>(pos, 0.0, 1.0);
+    vout.ndc = pos;
+    return vout;
+}
+
+// the selected signed component, or the length for component 3
+fn select_component(field: vec3<f32>) -> f32 {
+    if (c_param.component == 3u) {
+        return length(field);
+    }
+    return field[c_param.component];
+}
+
+fn viridis(t: f32) -> vec3<f32> {
+    let c0 = vec3<f32>(0.2777273272234177, 0.005407344544966578, 0.3340998053353061);
+    let c1 = vec3<f32>(0.1050930431085774, 1.404613529898575, 1.384590162594685);
+    let c2 = vec3<f32>(-0.3308618287255563, 0.214847559468213, 0.09509516302823659);
+    let c3 = vec3<f32>(-4.634230498983486, -5.799100973351585, -19.33244095627987);
+    let c4 = vec3<f32>(6.228269936347081, 14.17993336680509, 56.69055260068105);
+    let c5 = vec3<f32>(4.776384997670288, -13.74514537774601, -65.35303263337234);
+    let c6 = vec3<f32>(-5.435455855934631, 4.645852612178535, 26.3124352495832);
+    return c0 + t * (c1 + t * (c2 + t * (c3 + t * (c4 + t * (c5 + t * c6)))));
+}
+
+fn inferno(t: f32) -> vec3<f32> {
+    let c0 = vec3<f32>(0.0002189403691192265, 0.001651004631001012, -0.01948089843709184);
+    let c1 = vec3<f32>(0.1065134194856116, 0.5639564367884091, 3.932712388889277);
+    let c2 = vec3<f32>(11.60249308247187, -3.972853965665698, -15.9423941062914);
+    let c3 = vec3<f32>(-41.70399613139459, 17.43639888205313, 44.35414519872813);
+    let c4 = vec3<f32>(77.162935699427, -33.40235894210092, -81.80730925738993);
+    let c5 = vec3<f32>(-71.31942824499214, 32.62606426397723, 73.20951985803202);
+    let c6 = vec3<f32>(25.13112622477341, -12.24266895238567, -23.07032500287172);
+    return c0 + t * (c1 + t * (c2 + t * (c3 + t * (c4 + t * (c5 + t * c6)))));
+}
+
+fn seismic(s: f32) -> vec3<f32> {
+    if (s < -0.5) {
+        return vec3<f32>(0.0, 0.0, 0.3 + 1.4 * (s + 1.0));
+    } else if (s < 0.0) {
+        let t = 1.0 + 2.0 * s;
+        return vec3<f32>(t, t, 1.0);
+    } else if (s < 0.5) {
+        let t = 1.0 - 2.0 * s;
+        return vec3<f32>(1.0, t, t);
+    }
+    return vec3<f32>(1.0 - (s - 0.5), 0.0, 0.0);
+}
+
+// color and opacity of a sample, the opacity follows the displayed magnitude
+fn transfer(value: f32) -> vec4<f32> {
+    let magnitude = clamp(display_scale(abs(value), c_param.log_scale, c_param.log_floor), 0.0, 1.0);
+    var color = vec3<f32>(magnitude);
+    if (c_param.colormap == 0u) {
+        color = viridis(magnitude);
+    } else if (c_param.colormap == 1u) {
+        color = clamp(inferno(magnitude), vec3<f32>(0.0), vec3<f32>(1.0));
+    } else if (c_param.colormap == 2u) {
+        color = seismic(sign(value) * magnitude);
+    }
+    return vec4<f32>(color, magnitude);
+}
+
+// front-to-back compositing along the view ray through the grid box
+@fragment
+fn fs_main(fin: VertexOut) -> @location(0) vec4<f32> {
+    let forward = normalize(-c_param.eye);
+    let right = normalize(cross(forward, vec3<f32>(0.0, 1.0, 0.0)));
+    let up = cross(right, forward);
+    let direction = normalize(
+        forward + (fin.ndc.x * c_param.aspect * right + fin.ndc.y * up) * TAN_HALF_FOV
+    );
+
+    // slab intersection with the box
+    let inverse = 1.0 / direction;
+    let t0 = (-c_param.half_extent - c_param.eye) * inverse;
+    let t1 = (c_param.half_extent - c_param.eye) * inverse;
+    let near = min(t0, t1);
+    let far = max(t0, t1);
+    let t_enter = max(max(near.x, near.y), max(near.z, 0.0));
+    let t_exit = min(min(far.x, far.y), far.z);
+    if (t_enter >= t_exit) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    let diagonal = 2.0 * length(c_param.half_extent);
+    let step = diagonal / SAMPLES;
+    let size = vec3<i32>(textureDimensions(t_field));
+    var color = vec3<f32>(0.0);
+    var alpha = 0.0;
+    for (var t = t_enter + 0.5 * step; t < t_exit && alpha < 0.99; t += step) {
+        let position = c_param.eye + t * direction;
+        let uvw = (position + c_param.half_extent) / (2.0 * c_param.half_extent);
+        let texel = clamp(vec3<i32>(uvw * vec3<f32>(size)), vec3<i32>(0), size - 1);
+        let value = select_component(textureLoad(t_field, texel, 0).xyz) * c_param.scaling_factor;
+        let sample = transfer(value);
+        let opacity = 1.0 - exp(-sample.a * DENSITY * step / diagonal);
+        color += (1.0 - alpha) * opacity * sample.rgb;
+        alpha += (1.0 - alpha) * opacity;
+    }
+    return vec4<f32>(color, 1.0);
+}
@@ -11,20 +11,13 @@ struct VertexOut {
     @location(0) tex_coord: vec2<f32>,
 };
 
+// x, y, z components in rgb
 @group(0)
 @binding(0)
-var t_texture_x: texture_3d<f32>;
+var t_field: texture_3d<f32>;
 
 @group(0)
 @binding(1)
-var t_texture_y: texture_3d<f32>;
-
-@group(0)
-@binding(2)
-var t_texture_z: texture_3d<f32>;
-
-@group(0)
-@binding(3)
 var s_sampler: sampler;
 
 @fragment
@@ -39,5 +32,5 @@ fn fs_main(fin: VertexOut) -> @location(0) vec4<f32> {
     } else {
         discard;
     }
-    return vec4<f32>(vec3<f32>(textureSample(t_texture_x, s_sampler, slice).x, textureSample(t_texture_y, s_sampler, slice).x, textureSample(t_texture_z, s_sampler, slice).x) * c_param.scaling_factor, 1.0);
+    return vec4<f32>(textureSample(t_field, s_sampler, slice).xyz * c_param.scaling_factor, 1.0);
 }
@@ -0,0 +1,234 @@
+use crate::fdtd;
+
+const MAGIC: &[u8; 8] = b"GREMSCKP";
+// 2: E and H packed into one Rgba32Float texture each
+const VERSION: u32 = 2;
+
+// layout (little endian): magic, version, step, grid dimension[3], boundary kind (PML faces left
+// out in bits 8 to 13, PMC symmetry planes in bits 16 to 21, periodic axes in bits 24 to 26),
+// boundary cells,
+// spatial step, temporal step, texture count, then per texture its extent[3] followed by raw texels
+fn boundary_tag(boundary: &fdtd::BoundaryCondition) -> [u32; 2] {
+    match *boundary {
+        fdtd::BoundaryCondition::PML { cells, .. } => {
+            // in the upper bits, keeping full PML checkpoints as they were
+            let disabled = boundary
+                .pml_faces()
+                .unwrap_or_default()
+                .sides()
+                .iter()
+                .flatten()
+                .enumerate()
+                .fold(0, |mask, (i, enabled)| mask | ((!enabled as u32) << i));
+            [
+                disabled << 8 | boundary.pmc_faces() << 16 | boundary.periodic_axes() << 24,
+                cells,
+            ]
+        }
+        fdtd::BoundaryCondition::PEC => [1, 0],
+        fdtd::BoundaryCondition::PMC => [2, 0],
+    }
+}
+
+/// Reads back the whole simulation state and writes it to `path`, blocking until done.
+/// The file is written next to `path` first and renamed, so a crash never leaves a truncated checkpoint.
+pub fn save(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    fdtd: &fdtd::FDTD,
+    step: u32,
+    settings: &crate::settings::FDTDSettings,
+    path: &std::path::Path,
+) -> anyhow::Result<()> {
+    use std::io::Write;
+
+    let textures = fdtd.state_textures();
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    let readbacks = textures
+        .iter()
+        .map(|texture| {
+            let size = texture.size();
+            let unpadded_bytes_per_row = size.width * texture.format().block_size(None).unwrap();
+            let padded_bytes_per_row_padding = (wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
+                - unpadded_bytes_per_row % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+                % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+            let padded_bytes_per_row = unpadded_bytes_per_row + padded_bytes_per_row_padding;
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Checkpoint"),
+                size: (padded_bytes_per_row * size.height * size.depth_or_array_layers) as u64,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+            encoder.copy_texture_to_buffer(
+                texture.as_image_copy(),
+                wgpu::ImageCopyBuffer {
+                    buffer: &buffer,
+                    layout: wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(padded_bytes_per_row),
+                        rows_per_image: Some(size.height),
+                    },
+                },
+                size,
+            );
+            (buffer, size, unpadded_bytes_per_row, padded_bytes_per_row)
+        })
+        .collect::<Vec<_>>();
+    let index = queue.submit(Some(encoder.finish()));
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    for (buffer, ..) in readbacks.iter() {
+        let sender = sender.clone();
+        buffer.slice(..).map_async(wgpu::MapMode::Read, move |v| {
+            let _ = sender.send(v);
+        });
+    }
+    drop(sender);
+    device.poll(wgpu::Maintain::WaitForSubmissionIndex(index));
+    for result in receiver {
+        result?;
+    }
+
+    let temporary_path = path.with_extension("tmp");
+    let mut file = std::io::BufWriter::new(std::fs::File::create(&temporary_path)?);
+    file.write_all(MAGIC)?;
+    let grid_dimension = fdtd.get_dimension();
+    let boundary = boundary_tag(&settings.boundary);
+    for value in [
+        VERSION,
+        step,
+        grid_dimension[0],
+        grid_dimension[1],
+        grid_dimension[2],
+        boundary[0],
+        boundary[1],
+    ] {
+        file.write_all(&value.to_le_bytes())?;
+    }
+    file.write_all(&settings.spatial_step.to_le_bytes())?;
+    file.write_all(&settings.temporal_step().to_le_bytes())?;
+    file.write_all(&(readbacks.len() as u32).to_le_bytes())?;
+    for (buffer, size, unpadded_bytes_per_row, padded_bytes_per_row) in readbacks.iter() {
+        for value in [size.width, size.height, size.depth_or_array_layers] {
+            file.write_all(&value.to_le_bytes())?;
+        }
+        {
+            let data = buffer.slice(..).get_mapped_range();
+            for row in data.chunks(*padded_bytes_per_row as usize) {
+                file.write_all(&row[..*unpadded_bytes_per_row as usize])?;
+            }
+        }
+        buffer.unmap();
+    }
+    file.into_inner()?.sync_all()?;
+    std::fs::rename(&temporary_path, path)?;
+
+    Ok(())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + len)
+            .ok_or_else(|| anyhow::anyhow!("Checkpoint is truncated"))?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into()?))
+    }
+
+    fn f32(&mut self) -> anyhow::Result<f32> {
+        Ok(f32::from_le_bytes(self.bytes(4)?.try_into()?))
+    }
+}
+
+/// Validates the checkpoint at `path` against the current setup and uploads its state.
+/// Returns the step the checkpoint was taken at.
+pub fn load(
+    queue: &wgpu::Queue,
+    fdtd: &fdtd::FDTD,
+    settings: &crate::settings::FDTDSettings,
+    path: &std::path::Path,
+) -> anyhow::Result<u32> {
+    let data = std::fs::read(path)?;
+    let mut reader = Reader {
+        data: &data,
+        offset: 0,
+    };
+
+    anyhow::ensure!(
+        reader.bytes(MAGIC.len())? == MAGIC,
+        "{} is not a checkpoint",
+        path.display()
+    );
+    let version = reader.u32()?;
+    anyhow::ensure!(
+        version == VERSION,
+        "Unsupported checkpoint version {}",
+        version
+    );
+    let step = reader.u32()?;
+    let grid_dimension = [reader.u32()?, reader.u32()?, reader.u32()?];
+    anyhow::ensure!(
+        grid_dimension == fdtd.get_dimension(),
+        "Checkpoint grid {:?} does not match the preset grid {:?}",
+        grid_dimension,
+        fdtd.get_dimension()
+    );
+    let checkpoint_boundary = [reader.u32()?, reader.u32()?];
+    anyhow::ensure!(
+        checkpoint_boundary == boundary_tag(&settings.boundary),
+        "Checkpoint boundary does not match the preset boundary {:?}",
+        settings.boundary
+    );
+    let checkpoint_steps = [reader.f32()?, reader.f32()?];
+    anyhow::ensure!(
+        checkpoint_steps == [settings.spatial_step, settings.temporal_step()],
+        "Checkpoint was taken with spatial/temporal step {:?}, the preset uses {:?}",
+        checkpoint_steps,
+        [settings.spatial_step, settings.temporal_step()]
+    );
+
+    let textures = fdtd.state_textures();
+    let count = reader.u32()? as usize;
+    anyhow::ensure!(
+        count == textures.len(),
+        "Checkpoint holds {} textures, expected {}",
+        count,
+        textures.len()
+    );
+    for texture in textures {
+        let size = texture.size();
+        let extent = [reader.u32()?, reader.u32()?, reader.u32()?];
+        anyhow::ensure!(
+            extent == [size.width, size.height, size.depth_or_array_layers],
+            "Checkpoint texture extent {:?} does not match {:?}",
+            extent,
+            size
+        );
+        let bytes_per_texel = texture.format().block_size(None).unwrap();
+        let texels = reader.bytes(
+            (size.width * size.height * size.depth_or_array_layers * bytes_per_texel) as usize,
+        )?;
+        queue.write_texture(
+            texture.as_image_copy(),
+            texels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(size.width * bytes_per_texel),
+                rows_per_image: Some(size.height),
+            },
+            size,
+        );
+    }
+
+    Ok(step)
+}
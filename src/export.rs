@@ -0,0 +1,1067 @@
+use wgpu::util::DeviceExt;
+
+use crate::fdtd;
+
+// readback buffers kept around per size, enough for a few exports in flight
+const POOLED_BUFFERS: usize = 4;
+
+/// What an export writes out of a field.
+#[derive(Clone, Copy, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportQuantity {
+    /// One file per component
+    #[default]
+    Components,
+    /// |F| in a single file
+    Magnitude,
+    /// Energy density, eps |E|^2 / 2 or |H|^2 / 2
+    Energy,
+    /// Time-averaged |E|^2 in a single file, the field is ignored
+    Intensity,
+}
+
+/// Number format of the values in DDS files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Precision {
+    #[default]
+    F32,
+    /// Half the size, but about three significant digits, magnitudes above 65504 turn
+    /// infinite and below 6e-8 to zero. The values are converted after readback, the
+    /// simulation keeps running in f32.
+    F16,
+}
+
+/// Part of the grid an export covers.
+#[derive(Clone, Copy, Debug)]
+pub enum Region {
+    Volume,
+    /// The plane at the given grid index along the slice axis
+    Slice(fdtd::SliceMode, u32),
+    /// The column along z of the 1D mode, written as CSV, `first` is the world z of its first
+    /// cell and `step` the distance to the next one
+    Line {
+        first: f32,
+        step: f32,
+    },
+}
+
+impl Region {
+    /// The column of `fdtd`, which must run in the 1D mode.
+    pub fn line(fdtd: &fdtd::FDTD) -> Self {
+        Region::Line {
+            first: fdtd.cell_center(2, 0),
+            step: fdtd.cell_center(2, 1) - fdtd.cell_center(2, 0),
+        }
+    }
+}
+
+/// Box of texels an export covers, every `stride`-th along each axis from `low` on. A slice
+/// keeps its own plane along the normal and a line its column, whatever the box.
+#[derive(Clone, Copy, Debug)]
+pub struct Crop {
+    pub low: [u32; 3],
+    pub size: [u32; 3], // texels of the box before striding
+    pub stride: [u32; 3],
+}
+
+impl Crop {
+    /// The whole grid at full resolution.
+    pub fn full(dimension: [u32; 3]) -> Self {
+        Self {
+            low: [0; 3],
+            size: dimension,
+            stride: [1; 3],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct DownsampleParam {
+    origin: [u32; 3],
+    _padding: u32,
+    stride: [u32; 3],
+    _padding2: u32,
+}
+
+/// Position and column names of a line written as CSV.
+struct Csv {
+    first: f32,
+    step: f32,
+    columns: Vec<String>, // one per channel, further channels are left out
+}
+
+/// Mirror image appended to the data across one of its faces.
+struct Mirror {
+    axis: usize, // of the written data, not the grid
+    high: bool,
+    signs: Vec<f32>, // per channel
+}
+
+/// Constants of the run written into the metadata sidecar of every export.
+#[derive(Clone, serde::Serialize)]
+pub struct RunMetadata {
+    pub spatial_step: f32,
+    pub temporal_step: f32, // ct
+    pub units: Option<crate::units::Units>,
+    pub domain: [[f32; 2]; 3],
+    pub boundary: fdtd::BoundaryCondition,
+    pub dimension_mode: fdtd::DimensionMode,
+}
+
+/// The sidecar of a job, completed per file once the written extent is known.
+struct Metadata {
+    fields: serde_json::Value, // an object
+    names: Vec<String>,        // per channel
+    origin: [f32; 3],          // world coordinates of the first cell along each axis of the data
+    spacing: [f32; 3],         // between two written cells along each axis of the data
+}
+
+type Pool = std::sync::Arc<std::sync::Mutex<std::collections::HashMap<u64, Vec<wgpu::Buffer>>>>;
+
+struct Job {
+    buffer: wgpu::Buffer,
+    // the degenerate axis of a slice is dropped, leaving depth 1
+    dimension: [u32; 3],
+    volume: bool,
+    padded_bytes_per_row: u32,
+    unpadded_bytes_per_row: u32,
+    // f32 channels per texel in the buffer
+    channels: usize,
+    // one file per leading channel, e.g. x, y, z of a field texture, a single one for CSV
+    paths: Vec<std::path::PathBuf>,
+    mirrors: Vec<Mirror>,
+    csv: Option<Csv>,
+    metadata: Option<Metadata>,
+    precision: Precision,
+}
+
+/// Writes field exports without stalling the render loop.
+/// The copy is recorded into the frame encoder, once submitted the buffer is mapped and handed to a
+/// worker thread which waits for the mapping and serializes the DDS file.
+pub struct Exporter {
+    prefix: String,
+    metadata: Option<RunMetadata>, // no sidecars when unset
+    combine_bind_group_layout: wgpu::BindGroupLayout,
+    magnitude_pipeline: wgpu::ComputePipeline,
+    electric_energy_pipeline: wgpu::ComputePipeline,
+    magnetic_energy_pipeline: wgpu::ComputePipeline,
+    downsample_vector_pipeline: wgpu::ComputePipeline,
+    downsample_scalar_pipeline: wgpu::ComputePipeline,
+    // target of the combine pass, created on the first magnitude or energy export
+    combined: Option<wgpu::Texture>,
+    pending: Vec<Job>,
+    sender: std::sync::mpsc::Sender<(
+        Job,
+        std::sync::mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>,
+    )>,
+    in_flight: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    // staging buffers come back here once their data was written
+    pool: Pool,
+    writer: std::thread::JoinHandle<()>,
+}
+
+impl Exporter {
+    /// Files are written to the working directory as `<prefix>-<D3|D2-slice>-<field>...-<step>.dds`,
+    /// lines as `<prefix>-D1-<field>...-<step>.csv`. With `metadata`, each file gets a `.json`
+    /// sidecar of the same name describing the grid, the run and what was exported.
+    pub fn new(device: &wgpu::Device, prefix: String, metadata: Option<RunMetadata>) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D3,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let combine_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Export Combine"),
+                entries: &[
+                    texture_entry(0),
+                    texture_entry(1),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: wgpu::StorageTextureAccess::WriteOnly,
+                            format: wgpu::TextureFormat::R32Float,
+                            view_dimension: wgpu::TextureViewDimension::D3,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let combine_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Export Combine"),
+                bind_group_layouts: &[&combine_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let combine_shader_module =
+            device.create_shader_module(wgpu::include_wgsl!("../shader/fdtd/combine.wgsl"));
+        let combine_pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Export Combine"),
+                layout: Some(&combine_pipeline_layout),
+                module: &combine_shader_module,
+                entry_point,
+            })
+        };
+        let magnitude_pipeline = combine_pipeline("magnitude");
+        let electric_energy_pipeline = combine_pipeline("electric_energy");
+        let magnetic_energy_pipeline = combine_pipeline("magnetic_energy");
+        let downsample_shader_module =
+            device.create_shader_module(wgpu::include_wgsl!("../shader/fdtd/downsample.wgsl"));
+        let downsample_pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Export Downsample"),
+                layout: None,
+                module: &downsample_shader_module,
+                entry_point,
+            })
+        };
+        let downsample_vector_pipeline = downsample_pipeline("vector");
+        let downsample_scalar_pipeline = downsample_pipeline("scalar");
+
+        let in_flight = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let (sender, receiver) = std::sync::mpsc::channel::<(
+            Job,
+            std::sync::mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>,
+        )>();
+        let pool = Pool::default();
+        let writer = {
+            let in_flight = in_flight.clone();
+            let pool = pool.clone();
+            std::thread::spawn(move || {
+                for (job, mapped) in receiver {
+                    let result = mapped
+                        .recv()
+                        .map_err(anyhow::Error::from)
+                        .and_then(|mapped| mapped.map_err(anyhow::Error::from));
+                    match result {
+                        Ok(()) => {
+                            write(&job);
+                            job.buffer.unmap();
+                        }
+                        Err(err) => {
+                            for path in &job.paths {
+                                eprintln!("Failed to export {}: {}", path.display(), err);
+                            }
+                        }
+                    }
+                    let mut pool = pool.lock().unwrap();
+                    let buffers = pool.entry(job.buffer.size()).or_default();
+                    if buffers.len() < POOLED_BUFFERS {
+                        buffers.push(job.buffer);
+                    }
+                    drop(pool);
+                    in_flight.fetch_sub(1, std::sync::atomic::Ordering::AcqRel);
+                }
+            })
+        };
+
+        Self {
+            prefix,
+            metadata,
+            combine_bind_group_layout,
+            magnitude_pipeline,
+            electric_energy_pipeline,
+            magnetic_energy_pipeline,
+            downsample_vector_pipeline,
+            downsample_scalar_pipeline,
+            combined: None,
+            pending: vec![],
+            sender,
+            in_flight,
+            pool,
+            writer,
+        }
+    }
+
+    /// Records the copies for `quantity` of `field` over `region` within `crop` into `encoder`,
+    /// `map_pending` must be called once it was submitted. With `unfold`, the data is mirrored
+    /// across each of its symmetry planes, giving the full domain.
+    #[allow(clippy::too_many_arguments)]
+    pub fn request(
+        &mut self,
+        device: &wgpu::Device,
+        fdtd: &fdtd::FDTD,
+        encoder: &mut wgpu::CommandEncoder,
+        field: fdtd::FieldType,
+        quantity: ExportQuantity,
+        region: Region,
+        crop: Crop,
+        precision: Precision,
+        unfold: Option<fdtd::SymmetryFaces>,
+        step: u32,
+    ) -> anyhow::Result<()> {
+        let texture = match field {
+            fdtd::FieldType::E => fdtd.get_electric_field_texture(),
+            fdtd::FieldType::H => fdtd.get_magnetic_field_texture(),
+        };
+        let directory = std::env::current_dir()?;
+        let region_name = match region {
+            Region::Volume => "D3".to_string(),
+            Region::Slice(mode, index) => format!("D2-{:?}{}", mode, index),
+            Region::Line { .. } => "D1".to_string(),
+        };
+        let extension = match region {
+            Region::Line { .. } => "csv",
+            Region::Volume | Region::Slice(..) => "dds",
+        };
+        // axes of the written data, a slice keeps its plane axes
+        let data_axes = match region {
+            Region::Volume => [Some(0), Some(1), Some(2)],
+            Region::Slice(mode, _) => {
+                let [u, v] = mode.plane_axes();
+                [0, 1, 2].map(|axis| [u, v].iter().position(|plane| *plane == axis))
+            }
+            Region::Line { .. } => [None, None, Some(0)],
+        };
+        let metadata = self.metadata.as_ref().map(|run| {
+            let world_axes = match region {
+                Region::Volume => vec![0, 1, 2],
+                Region::Slice(mode, _) => mode.plane_axes().to_vec(),
+                Region::Line { .. } => vec![2],
+            };
+            let (mut origin, mut spacing) = ([0.0; 3], [0.0; 3]);
+            for (data_axis, axis) in world_axes.iter().enumerate() {
+                origin[data_axis] = fdtd.cell_center(*axis, crop.low[*axis]);
+                spacing[data_axis] = run.spatial_step * crop.stride[*axis] as f32;
+            }
+            let region = match region {
+                Region::Volume => serde_json::json!({ "type": "volume" }),
+                Region::Slice(mode, index) => serde_json::json!({
+                    "type": "slice",
+                    "normal": mode,
+                    "index": index,
+                    "position": fdtd.cell_center(mode.axis(), index),
+                }),
+                Region::Line { .. } => serde_json::json!({ "type": "line" }),
+            };
+            // times in the time unit of the preset, ct without units
+            let speed_of_light = crate::units::speed_of_light(run.units.as_ref());
+            let fields = serde_json::json!({
+                "grems_version": env!("CARGO_PKG_VERSION"),
+                // set when building, e.g. GREMS_COMMIT=$(git rev-parse HEAD) cargo build
+                "commit": option_env!("GREMS_COMMIT"),
+                "grid_dimension": fdtd.get_dimension(),
+                "spatial_step": run.spatial_step,
+                "temporal_step": run.temporal_step / speed_of_light,
+                "units": run.units,
+                "domain": run.domain,
+                "boundary": run.boundary,
+                "dimension_mode": run.dimension_mode,
+                "step": step,
+                "time": step as f32 * run.temporal_step / speed_of_light,
+                "field": field,
+                "quantity": quantity,
+                "precision": precision,
+                "region": region,
+                "crop": {
+                    "first_texel": crop.low,
+                    "size": crop.size,
+                    "stride": crop.stride,
+                },
+                "unfold": unfold.is_some(),
+                "axes": world_axes.iter().map(|axis| ["x", "y", "z"][*axis]).collect::<Vec<_>>(),
+            });
+            Metadata {
+                fields,
+                names: vec![],
+                origin,
+                spacing,
+            }
+        });
+        let mirrors = |components: bool| -> Vec<Mirror> {
+            let Some(unfold) = unfold else {
+                return vec![];
+            };
+            unfold
+                .sides()
+                .iter()
+                .enumerate()
+                .flat_map(|(normal, sides)| {
+                    sides
+                        .iter()
+                        .enumerate()
+                        .filter_map(move |(side, symmetry)| {
+                            let symmetry = (*symmetry)?;
+                            Some(Mirror {
+                                axis: data_axes[normal]?,
+                                high: side == 1,
+                                // magnitudes and energies are even
+                                signs: if components {
+                                    (0..3)
+                                        .map(|axis| symmetry.parity(field, axis, normal))
+                                        .collect()
+                                } else {
+                                    vec![1.0]
+                                },
+                            })
+                        })
+                })
+                .collect()
+        };
+
+        match quantity {
+            ExportQuantity::Components => {
+                let columns: Vec<String> = ["x", "y", "z"]
+                    .iter()
+                    .map(|component| format!("{:?}{}", field, component))
+                    .collect();
+                // the components share a texture, so one copy is split into a file each, a
+                // line keeps them as columns of one file
+                let paths = match region {
+                    Region::Line { .. } => vec![directory.join(format!(
+                        "{}-{}-{:?}-{}.csv",
+                        self.prefix, region_name, field, step
+                    ))],
+                    Region::Volume | Region::Slice(..) => columns
+                        .iter()
+                        .map(|column| {
+                            directory.join(format!(
+                                "{}-{}-{}-{}.dds",
+                                self.prefix, region_name, column, step
+                            ))
+                        })
+                        .collect(),
+                };
+                self.copy_out(
+                    device,
+                    encoder,
+                    texture,
+                    region,
+                    crop,
+                    paths,
+                    mirrors(true),
+                    columns,
+                    metadata,
+                    precision,
+                );
+            }
+            ExportQuantity::Intensity => {
+                let texture = fdtd.get_intensity_texture().ok_or_else(|| {
+                    anyhow::anyhow!("Intensity is only averaged with an \"intensity\" section")
+                })?;
+                self.copy_out(
+                    device,
+                    encoder,
+                    texture,
+                    region,
+                    crop,
+                    vec![directory.join(format!(
+                        "{}-{}-E-intensity-{}.{}",
+                        self.prefix, region_name, step, extension
+                    ))],
+                    mirrors(false),
+                    vec!["intensity".to_string()],
+                    metadata,
+                    precision,
+                );
+            }
+            ExportQuantity::Magnitude | ExportQuantity::Energy => {
+                let dimension = fdtd.get_dimension();
+                let combined = match self.combined.take() {
+                    Some(texture)
+                        if texture.size()
+                            == wgpu::Extent3d {
+                                width: dimension[0],
+                                height: dimension[1],
+                                depth_or_array_layers: dimension[2],
+                            } =>
+                    {
+                        texture
+                    }
+                    _ => device.create_texture(&wgpu::TextureDescriptor {
+                        label: Some("Export Combine"),
+                        size: wgpu::Extent3d {
+                            width: dimension[0],
+                            height: dimension[1],
+                            depth_or_array_layers: dimension[2],
+                        },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: wgpu::TextureDimension::D3,
+                        format: wgpu::TextureFormat::R32Float,
+                        usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC,
+                        view_formats: &[],
+                    }),
+                };
+
+                let views = [texture, fdtd.get_permittivity_texture(), &combined]
+                    .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Export Combine"),
+                    layout: &self.combine_bind_group_layout,
+                    entries: &views
+                        .iter()
+                        .enumerate()
+                        .map(|(binding, view)| wgpu::BindGroupEntry {
+                            binding: binding as u32,
+                            resource: wgpu::BindingResource::TextureView(view),
+                        })
+                        .collect::<Vec<_>>(),
+                });
+                let (pipeline, name) = match (quantity, field) {
+                    (ExportQuantity::Magnitude, _) => (&self.magnitude_pipeline, "magnitude"),
+                    (_, fdtd::FieldType::E) => (&self.electric_energy_pipeline, "energy"),
+                    (_, fdtd::FieldType::H) => (&self.magnetic_energy_pipeline, "energy"),
+                };
+                {
+                    let mut cpass =
+                        encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+                    cpass.set_pipeline(pipeline);
+                    cpass.set_bind_group(0, &bind_group, &[]);
+                    cpass.dispatch_workgroups(
+                        (dimension[0] as f32 / 4.0).ceil() as u32,
+                        (dimension[1] as f32 / 4.0).ceil() as u32,
+                        (dimension[2] as f32 / 4.0).ceil() as u32,
+                    );
+                }
+                self.copy_out(
+                    device,
+                    encoder,
+                    &combined,
+                    region,
+                    crop,
+                    vec![directory.join(format!(
+                        "{}-{}-{:?}-{}-{}.{}",
+                        self.prefix, region_name, field, name, step, extension
+                    ))],
+                    mirrors(false),
+                    vec![format!("{:?}-{}", field, name)],
+                    metadata,
+                    precision,
+                );
+                self.combined = Some(combined);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn copy_out(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        region: Region,
+        crop: Crop,
+        paths: Vec<std::path::PathBuf>,
+        mirrors: Vec<Mirror>,
+        columns: Vec<String>, // names of the channels, as CSV columns and in the sidecars
+        metadata: Option<Metadata>,
+        precision: Precision,
+    ) {
+        let (mut origin, mut extent) = (crop.low, crop.size);
+        match region {
+            Region::Volume => (),
+            Region::Slice(mode, index) => {
+                origin[mode.axis()] = index;
+                extent[mode.axis()] = 1;
+            }
+            Region::Line { .. } => {
+                (origin[0], origin[1]) = (0, 0);
+                (extent[0], extent[1]) = (1, 1);
+            }
+        }
+        let first = origin;
+        let downsampled;
+        let texture = if crop.stride == [1; 3] {
+            texture
+        } else {
+            downsampled = self.downsample(device, encoder, texture, origin, extent, crop.stride);
+            (origin, extent) = (
+                [0; 3],
+                [0, 1, 2].map(|axis| extent[axis].div_ceil(crop.stride[axis])),
+            );
+            &downsampled
+        };
+        let dimension = extent;
+        let (origin, extent) = (
+            wgpu::Origin3d {
+                x: origin[0],
+                y: origin[1],
+                z: origin[2],
+            },
+            wgpu::Extent3d {
+                width: extent[0],
+                height: extent[1],
+                depth_or_array_layers: extent[2],
+            },
+        );
+
+        let bytes_per_pixel = texture.format().block_size(None).unwrap();
+        let unpadded_bytes_per_row = dimension[0] * bytes_per_pixel;
+        let padded_bytes_per_row_padding = (wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
+            - unpadded_bytes_per_row % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row + padded_bytes_per_row_padding;
+
+        let size = (padded_bytes_per_row * dimension[1] * dimension[2]) as u64;
+        let pooled = self
+            .pool
+            .lock()
+            .unwrap()
+            .get_mut(&size)
+            .and_then(|buffers| buffers.pop());
+        let buffer = pooled.unwrap_or_else(|| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Export"),
+                size,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            })
+        });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBufferBase {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(dimension[1]),
+                },
+            },
+            extent,
+        );
+
+        // the rows of a slice read back in order, only the plane's two extents matter
+        let (dimension, volume) = match region {
+            Region::Volume => (dimension, true),
+            Region::Slice(fdtd::SliceMode::X, _) => ([dimension[1], dimension[2], 1], false),
+            Region::Slice(fdtd::SliceMode::Y, _) => ([dimension[0], dimension[2], 1], false),
+            Region::Slice(fdtd::SliceMode::Z, _) => ([dimension[0], dimension[1], 1], false),
+            Region::Line { .. } => ([dimension[2], 1, 1], false),
+        };
+        let metadata = metadata.map(|metadata| Metadata {
+            names: columns.clone(),
+            ..metadata
+        });
+        let csv = match region {
+            Region::Line { first: z, step } => Some(Csv {
+                first: z + first[2] as f32 * step,
+                step: step * crop.stride[2] as f32,
+                columns,
+            }),
+            Region::Volume | Region::Slice(..) => None,
+        };
+        self.pending.push(Job {
+            buffer,
+            dimension,
+            volume,
+            padded_bytes_per_row,
+            unpadded_bytes_per_row,
+            channels: bytes_per_pixel as usize / std::mem::size_of::<f32>(),
+            paths,
+            mirrors,
+            csv,
+            metadata,
+            precision,
+        });
+    }
+
+    /// Picks every `stride`-th texel of `texture` over the box at `origin` of `extent` into a new
+    /// texture of the same format.
+    fn downsample(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        origin: [u32; 3],
+        extent: [u32; 3],
+        stride: [u32; 3],
+    ) -> wgpu::Texture {
+        let dimension = [0, 1, 2].map(|axis| extent[axis].div_ceil(stride[axis]));
+        let format = texture.format();
+        let downsampled = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Export Downsample"),
+            size: wgpu::Extent3d {
+                width: dimension[0],
+                height: dimension[1],
+                depth_or_array_layers: dimension[2],
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let (pipeline, output) = match format {
+            wgpu::TextureFormat::R32Float => (&self.downsample_scalar_pipeline, 3),
+            _ => (&self.downsample_vector_pipeline, 2),
+        };
+        let window = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Export Downsample"),
+            contents: bytemuck::bytes_of(&DownsampleParam {
+                origin,
+                _padding: 0,
+                stride,
+                _padding2: 0,
+            }),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let source_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let output_view = downsampled.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Export Downsample"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&source_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: window.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: output,
+                    resource: wgpu::BindingResource::TextureView(&output_view),
+                },
+            ],
+        });
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+        cpass.set_pipeline(pipeline);
+        cpass.set_bind_group(0, &bind_group, &[]);
+        cpass.dispatch_workgroups(
+            dimension[0].div_ceil(4),
+            dimension[1].div_ceil(4),
+            dimension[2].div_ceil(4),
+        );
+        drop(cpass);
+        downsampled
+    }
+
+    /// Starts mapping the buffers filled by `request` and passes them on to the writer.
+    pub fn map_pending(&mut self) {
+        for job in self.pending.drain(..) {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            job.buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |v| {
+                    let _ = sender.send(v);
+                });
+            self.in_flight
+                .fetch_add(1, std::sync::atomic::Ordering::AcqRel);
+            let _ = self.sender.send((job, receiver));
+        }
+    }
+
+    /// Lets map callbacks fire, the GPU is only polled while exports are outstanding.
+    pub fn poll(&self, device: &wgpu::Device) {
+        if self.in_flight.load(std::sync::atomic::Ordering::Acquire) > 0 {
+            device.poll(wgpu::Maintain::Poll);
+        }
+    }
+
+    /// Blocks until every requested export is on disk.
+    pub fn finish(mut self, device: &wgpu::Device) {
+        self.map_pending();
+        device.poll(wgpu::Maintain::Wait);
+        drop(self.sender);
+        let _ = self.writer.join();
+    }
+}
+
+fn write(job: &Job) {
+    let raw_data: Vec<u8> = job
+        .buffer
+        .slice(..)
+        .get_mapped_range()
+        .chunks(job.padded_bytes_per_row as usize)
+        .flat_map(|row| &row[..job.unpadded_bytes_per_row as usize])
+        .cloned()
+        .collect();
+
+    if let Some(csv) = &job.csv {
+        if let Err(err) = write_csv(job, csv, bytemuck::pod_collect_to_vec(&raw_data))
+            .and_then(|()| write_metadata(job, &job.paths[0], None))
+        {
+            eprintln!("Failed to export {}: {}", job.paths[0].display(), err);
+        }
+        return;
+    }
+
+    let texel_size = job.channels * std::mem::size_of::<f32>();
+    for (channel, path) in job.paths.iter().enumerate() {
+        let data = if job.channels == 1 {
+            raw_data.clone()
+        } else {
+            raw_data
+                .chunks(texel_size)
+                .flat_map(|texel| &texel[channel * 4..channel * 4 + 4])
+                .cloned()
+                .collect()
+        };
+        let (dimension, data) = if job.mirrors.is_empty() {
+            (job.dimension, data)
+        } else {
+            let mut dimension = job.dimension;
+            let mut values: Vec<f32> = bytemuck::pod_collect_to_vec(&data);
+            for mirror in &job.mirrors {
+                values = unfold(&values, &mut dimension, mirror, mirror.signs[channel]);
+            }
+            (dimension, bytemuck::cast_slice(&values).to_vec())
+        };
+        let data = match job.precision {
+            Precision::F32 => data,
+            Precision::F16 => bytemuck::pod_collect_to_vec::<u8, f32>(&data)
+                .into_iter()
+                .flat_map(|value| to_f16(value).to_le_bytes())
+                .collect(),
+        };
+        if let Err(err) = write_dds(job, dimension, data, path)
+            .and_then(|()| write_metadata(job, path, Some(channel)))
+        {
+            eprintln!("Failed to export {}: {}", path.display(), err);
+        }
+    }
+}
+
+/// Writes the sidecar of `path`, which holds `channel` or, without one, all channels as
+/// columns. Unfolding moves the origin by the images appended before the data.
+fn write_metadata(job: &Job, path: &std::path::Path, channel: Option<usize>) -> anyhow::Result<()> {
+    let Some(metadata) = &job.metadata else {
+        return Ok(());
+    };
+    let mut dimension = job.dimension;
+    let mut origin = metadata.origin;
+    for mirror in &job.mirrors {
+        if !mirror.high {
+            origin[mirror.axis] -=
+                (dimension[mirror.axis] - 1) as f32 * metadata.spacing[mirror.axis];
+        }
+        dimension[mirror.axis] = 2 * dimension[mirror.axis] - 1;
+    }
+    let axes = match job.csv {
+        Some(_) => 1,
+        None if job.volume => 3,
+        None => 2,
+    };
+    let mut fields = metadata.fields.clone();
+    let object = fields.as_object_mut().unwrap();
+    object.insert(
+        "file".to_string(),
+        path.file_name().unwrap().to_string_lossy().into(),
+    );
+    match channel {
+        Some(channel) => object.insert(
+            "channel".to_string(),
+            metadata.names[channel].clone().into(),
+        ),
+        None => object.insert("columns".to_string(), metadata.names.clone().into()),
+    };
+    object.insert("dimension".to_string(), dimension[..axes].into());
+    object.insert("origin".to_string(), origin[..axes].into());
+    object.insert("spacing".to_string(), metadata.spacing[..axes].into());
+    std::fs::write(
+        path.with_extension("json"),
+        serde_json::to_string_pretty(&fields)?,
+    )?;
+    Ok(())
+}
+
+/// IEEE half precision bits of `value`, rounded to nearest even.
+fn to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let mantissa = bits & 0x7f_ffff;
+    if (bits >> 23) & 0xff == 0xff {
+        // infinities stay, NaNs keep a mantissa bit
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    // subnormals shift the mantissa with its implicit bit further down
+    let (mantissa, shift) = match exponent {
+        1.. => (mantissa | (exponent as u32) << 23, 13),
+        -10..=0 => (mantissa | 0x80_0000, (14 - exponent) as u32),
+        _ => return sign,
+    };
+    let (half, rest) = (mantissa >> shift, mantissa & ((1 << shift) - 1));
+    let halfway = 1 << (shift - 1);
+    // a carry out of the mantissa moves on to the next exponent, or to infinity
+    let rounded = half + (rest > halfway || (rest == halfway && half & 1 == 1)) as u32;
+    sign | rounded as u16
+}
+
+/// The value of IEEE half precision `bits`.
+pub fn from_f16(bits: u16) -> f32 {
+    let sign = ((bits & 0x8000) as u32) << 16;
+    let exponent = ((bits >> 10) & 0x1f) as u32;
+    let mantissa = (bits & 0x3ff) as u32;
+    let magnitude = match exponent {
+        0 => mantissa as f32 * 2f32.powi(-24),
+        0x1f => f32::from_bits(0x7f80_0000 | mantissa << 13),
+        _ => f32::from_bits((exponent + 127 - 15) << 23 | mantissa << 13),
+    };
+    f32::from_bits(sign | magnitude.to_bits())
+}
+
+/// Appends the mirror image of `values` across the `mirror` face, scaled by `sign`. The
+/// outermost layer is the plane itself and is kept once.
+fn unfold(values: &[f32], dimension: &mut [u32; 3], mirror: &Mirror, sign: f32) -> Vec<f32> {
+    let [width, height, _] = dimension.map(|d| d as usize);
+    let n = dimension[mirror.axis] as usize;
+    dimension[mirror.axis] = 2 * dimension[mirror.axis] - 1;
+    let mut unfolded = Vec::with_capacity(dimension.iter().map(|d| *d as usize).product());
+    for z in 0..dimension[2] as usize {
+        for y in 0..dimension[1] as usize {
+            for x in 0..dimension[0] as usize {
+                let mut texel = [x, y, z];
+                let i = texel[mirror.axis];
+                // index into the simulated half and whether this is the image
+                let (source, image) = match mirror.high {
+                    false if i < n - 1 => (n - 1 - i, true),
+                    false => (i - (n - 1), false),
+                    true if i < n => (i, false),
+                    true => (2 * (n - 1) - i, true),
+                };
+                texel[mirror.axis] = source;
+                let value = values[texel[0] + width * (texel[1] + height * texel[2])];
+                unfolded.push(if image { value * sign } else { value });
+            }
+        }
+    }
+    unfolded
+}
+
+/// Writes a line as rows of the position and one value per column, unfolded across the
+/// mirrors of the job.
+fn write_csv(job: &Job, csv: &Csv, values: Vec<f32>) -> anyhow::Result<()> {
+    let text = csv_text(csv, job.dimension, job.channels, &job.mirrors, &values);
+    std::fs::write(&job.paths[0], text)?;
+    Ok(())
+}
+
+/// The CSV of a line of `dimension[0]` texels with `channels` values each.
+fn csv_text(
+    csv: &Csv,
+    dimension: [u32; 3],
+    channels: usize,
+    mirrors: &[Mirror],
+    values: &[f32],
+) -> String {
+    let mut unfolded = dimension;
+    let mut first = csv.first;
+    let columns: Vec<Vec<f32>> = (0..csv.columns.len())
+        .map(|channel| {
+            let mut column: Vec<f32> = values
+                .iter()
+                .skip(channel)
+                .step_by(channels)
+                .copied()
+                .collect();
+            unfolded = dimension;
+            for mirror in mirrors {
+                column = unfold(&column, &mut unfolded, mirror, mirror.signs[channel]);
+            }
+            column
+        })
+        .collect();
+    // the image across the low face comes first
+    for mirror in mirrors.iter().filter(|mirror| !mirror.high) {
+        first -= (dimension[mirror.axis] - 1) as f32 * csv.step;
+    }
+
+    let mut text = format!("z,{}\n", csv.columns.join(","));
+    for row in 0..unfolded[0] as usize {
+        text += &(first + row as f32 * csv.step).to_string();
+        for column in &columns {
+            text += &format!(",{}", column[row]);
+        }
+        text.push('\n');
+    }
+    text
+}
+
+fn write_dds(
+    job: &Job,
+    dimension: [u32; 3],
+    data: Vec<u8>,
+    path: &std::path::Path,
+) -> anyhow::Result<()> {
+    let mut dds = ddsfile::Dds::new_dxgi(ddsfile::NewDxgiParams {
+        height: dimension[1],
+        width: dimension[0],
+        depth: job.volume.then_some(dimension[2]),
+        format: match job.precision {
+            Precision::F32 => ddsfile::DxgiFormat::R32_Float,
+            Precision::F16 => ddsfile::DxgiFormat::R16_Float,
+        },
+        mipmap_levels: None,
+        array_layers: None,
+        caps2: None,
+        is_cubemap: false,
+        resource_dimension: if job.volume {
+            ddsfile::D3D10ResourceDimension::Texture3D
+        } else {
+            ddsfile::D3D10ResourceDimension::Texture2D
+        },
+        alpha_mode: ddsfile::AlphaMode::Unknown,
+    })?;
+    dds.data = data;
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .create(true)
+        .open(path)?;
+    dds.write(&mut file)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line() -> Csv {
+        Csv {
+            first: 1.0,
+            step: 0.5,
+            columns: ["Ex", "Ey", "Ez"].map(String::from).to_vec(),
+        }
+    }
+
+    #[test]
+    fn line_writes_one_row_per_cell_and_one_column_per_component() {
+        // texels of 4 channels, the fourth is padding
+        let values = [1.0, 2.0, 3.0, 9.0, 4.0, 5.0, 6.0, 9.0, 7.0, 8.0, 0.25, 9.0];
+        assert_eq!(
+            csv_text(&line(), [3, 1, 1], 4, &[], &values),
+            "z,Ex,Ey,Ez\n1,1,2,3\n1.5,4,5,6\n2,7,8,0.25\n"
+        );
+    }
+
+    #[test]
+    fn line_unfolds_across_a_low_mirror() {
+        let values = [1.0, 2.0, 3.0, 9.0, 4.0, 5.0, 6.0, 9.0];
+        let mirror = Mirror {
+            axis: 0,
+            high: false,
+            signs: vec![-1.0, 1.0, 1.0, 1.0],
+        };
+        // the first cell is the mirror plane and kept once, the image starts a step earlier
+        assert_eq!(
+            csv_text(&line(), [2, 1, 1], 4, &[mirror], &values),
+            "z,Ex,Ey,Ez\n0.5,-4,5,6\n1,1,2,3\n1.5,4,5,6\n"
+        );
+    }
+}
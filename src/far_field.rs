@@ -0,0 +1,210 @@
+use std::f64::consts::PI;
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::fdtd::SurfaceFields;
+
+type Complex = nalgebra::Complex<f64>;
+
+/// The far-field E in one direction, without the outgoing exp(-ikr) / r.
+#[derive(Clone, Copy, Debug)]
+pub struct FarFieldSample {
+    pub wavelength: f32, // in vacuum
+    pub theta: f32,      // radians from +z
+    pub phi: f32,        // radians from +x towards +y
+    pub e_theta: Complex,
+    pub e_phi: Complex,
+}
+
+impl FarFieldSample {
+    /// |E|², proportional to the power radiated per unit solid angle.
+    pub fn intensity(&self) -> f64 {
+        self.e_theta.norm_sqr() + self.e_phi.norm_sqr()
+    }
+}
+
+// n × v for the outward normal n along `axis` with `sign`
+fn normal_cross(axis: usize, sign: f64, v: [Complex; 3]) -> [Complex; 3] {
+    let [x, y, z] = v;
+    let zero = Complex::default();
+    match axis {
+        0 => [zero, -z, y],
+        1 => [z, zero, -x],
+        _ => [-y, x, zero],
+    }
+    .map(|component| component * sign)
+}
+
+/// Far-field E by the surface equivalence principle, radiated by the currents J = n × H and
+/// M = -n × E on the faces of the near-field box into a homogeneous medium of relative
+/// `permittivity` and `permeability`. One sample per frequency on a grid of `theta_count` polar
+/// angles from 0 to pi inclusive by `phi_count` azimuths over a full turn, phi running fastest.
+pub fn transform(
+    near_field: &SurfaceFields,
+    permittivity: f32,
+    permeability: f32,
+    theta_count: u32,
+    phi_count: u32,
+) -> Vec<FarFieldSample> {
+    let refractive_index = (permittivity as f64 * permeability as f64).sqrt();
+    let impedance = (permeability as f64 / permittivity as f64).sqrt();
+    let area = near_field.area as f64;
+    let directions: Vec<(f64, f64)> = (0..theta_count)
+        .flat_map(|theta| {
+            let theta = PI * theta as f64 / (theta_count - 1).max(1) as f64;
+            (0..phi_count).map(move |phi| (theta, 2.0 * PI * phi as f64 / phi_count as f64))
+        })
+        .collect();
+
+    let mut samples = vec![];
+    for (index, frequency) in near_field.frequencies.iter().enumerate() {
+        let currents: Vec<([Complex; 3], [Complex; 3])> = near_field
+            .cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                let [electric, magnetic] = near_field
+                    .fields(index, i)
+                    .map(|field| field.map(|component| component * area));
+                let (axis, sign) = cell.normal;
+                (
+                    normal_cross(axis, sign as f64, magnetic),
+                    normal_cross(axis, -sign as f64, electric),
+                )
+            })
+            .collect();
+        let wavenumber = 2.0 * PI * *frequency as f64 * refractive_index;
+        samples.par_extend(directions.par_iter().map(|&(theta, phi)| {
+            let (sin_theta, cos_theta) = theta.sin_cos();
+            let (sin_phi, cos_phi) = phi.sin_cos();
+            let direction = [sin_theta * cos_phi, sin_theta * sin_phi, cos_theta];
+            let mut n = [Complex::default(); 3];
+            let mut l = [Complex::default(); 3];
+            for (cell, (j, m)) in near_field.cells.iter().zip(currents.iter()) {
+                let projection = (0..3)
+                    .map(|i| direction[i] * cell.position[i] as f64)
+                    .sum::<f64>();
+                let (sin, cos) = (wavenumber * projection).sin_cos();
+                let phase = Complex::new(cos, sin);
+                for i in 0..3 {
+                    n[i] += j[i] * phase;
+                    l[i] += m[i] * phase;
+                }
+            }
+            let along_theta =
+                |v: [Complex; 3]| (v[0] * cos_phi + v[1] * sin_phi) * cos_theta - v[2] * sin_theta;
+            let along_phi = |v: [Complex; 3]| v[1] * cos_phi - v[0] * sin_phi;
+            let factor = Complex::new(0.0, wavenumber / (4.0 * PI));
+            FarFieldSample {
+                wavelength: 1.0 / frequency,
+                theta: theta as f32,
+                phi: phi as f32,
+                e_theta: -factor * (along_phi(l) + along_theta(n) * impedance),
+                e_phi: factor * (along_theta(l) - along_phi(n) * impedance),
+            }
+        }));
+    }
+    samples
+}
+
+/// Writes the samples as rows of the vacuum wavelength, theta and phi in degrees, the real and
+/// imaginary parts of E_theta and E_phi, and |E|².
+pub fn write_csv(path: &Path, samples: &[FarFieldSample]) -> anyhow::Result<()> {
+    let mut text =
+        "wavelength,theta,phi,e_theta_re,e_theta_im,e_phi_re,e_phi_im,intensity\n".to_string();
+    for sample in samples {
+        text += &format!(
+            "{},{},{},{},{},{},{},{}\n",
+            sample.wavelength,
+            sample.theta.to_degrees(),
+            sample.phi.to_degrees(),
+            sample.e_theta.re,
+            sample.e_theta.im,
+            sample.e_phi.re,
+            sample.e_phi.im,
+            sample.intensity()
+        );
+    }
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const X: [Complex; 3] = [
+        Complex::new(1.0, 0.0),
+        Complex::new(0.0, 0.0),
+        Complex::new(0.0, 0.0),
+    ];
+    const Y: [Complex; 3] = [
+        Complex::new(0.0, 0.0),
+        Complex::new(1.0, 0.0),
+        Complex::new(0.0, 0.0),
+    ];
+    const ZERO: [Complex; 3] = [Complex::new(0.0, 0.0); 3];
+
+    #[test]
+    fn two_current_elements_radiate_a_dipole_pattern_times_their_array_factor() {
+        // H along x on faces normal to z, J = n × H along y, half a wavelength apart along z
+        let (frequency, area) = (0.5, 0.01);
+        let fields = SurfaceFields::single_frequency(
+            frequency,
+            area,
+            &[
+                ([0.0, 0.0, -0.5], (2, 1.0), [ZERO, X]),
+                ([0.0, 0.0, 0.5], (2, 1.0), [ZERO, X]),
+            ],
+        );
+        let samples = transform(&fields, 1.0, 1.0, 7, 8);
+        assert_eq!(samples.len(), 7 * 8);
+        let wavenumber = 2.0 * PI * frequency as f64;
+        for sample in samples {
+            let (theta, phi) = (sample.theta as f64, sample.phi as f64);
+            // sin² of the angle to y, and the two elements in phase along z
+            let pattern = 1.0 - (theta.sin() * phi.sin()).powi(2);
+            let array = 4.0 * (0.5 * wavenumber * theta.cos()).cos().powi(2);
+            let expected = (wavenumber * area as f64 / (4.0 * PI)).powi(2) * pattern * array;
+            assert!(
+                (sample.intensity() - expected).abs() < 1e-6 * expected.max(1e-6),
+                "{} instead of {} at theta {}, phi {}",
+                sample.intensity(),
+                expected,
+                theta,
+                phi
+            );
+        }
+    }
+
+    #[test]
+    fn huygens_element_radiates_forward_only() {
+        // E along x and H along y with E = η H, a plane wave leaving through +z
+        let (permittivity, frequency, area) = (4.0, 1.0, 0.01);
+        let impedance = 0.5;
+        let electric = X.map(|component| component * impedance);
+        let fields = SurfaceFields::single_frequency(
+            frequency,
+            area,
+            &[([0.0; 3], (2, 1.0), [electric, Y])],
+        );
+        let samples = transform(&fields, permittivity, 1.0, 3, 4);
+        let forward = samples[0];
+        assert_eq!((forward.theta, forward.phi), (0.0, 0.0));
+        // J and M add up along the normal, each giving k η A / 4π of E_theta
+        let wavenumber = 2.0 * PI * frequency as f64 * 2.0;
+        let expected = 2.0 * wavenumber * impedance * area as f64 / (4.0 * PI);
+        assert!(
+            (forward.e_theta - Complex::new(0.0, expected))
+                .norm_sqr()
+                .sqrt()
+                < 1e-9 * expected
+        );
+        assert!(forward.e_phi.norm_sqr().sqrt() < 1e-9 * expected);
+        for backward in &samples[8..] {
+            assert!((backward.theta as f64 - PI).abs() < 1e-6);
+            assert!(backward.intensity() < 1e-12 * expected * expected);
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use wgpu::util::DeviceExt;
+
+use super::{params::Params, shaders::ShaderPaths};
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct DftParam {
+    low: [u32; 3],
+    frequency_count: u32,
+    high: [u32; 3],
+    step: u32,
+    faces: u32,
+    _padding: [u32; 3],
+}
+
+/// Faces of a box of cells, from `low` to `high` inclusive. Each face is one layer of cells,
+/// in the order low x, high x, low y, high y, low z, high z, each x-fastest over its two other
+/// axes, which is how the transforms are laid out.
+#[derive(Clone, Copy, Debug)]
+pub struct SurfaceBox {
+    pub low: [u32; 3],
+    pub high: [u32; 3],
+    pub faces: u32, // bit 2 * axis + side per face that is recorded
+}
+
+impl SurfaceBox {
+    /// All six faces of the box.
+    pub fn closed(low: [u32; 3], high: [u32; 3]) -> Self {
+        Self {
+            low,
+            high,
+            faces: 0b111111,
+        }
+    }
+
+    /// A single layer of cells normal to `axis`.
+    pub fn plane(axis: usize, low: [u32; 3], high: [u32; 3]) -> Self {
+        let mut high = high;
+        high[axis] = low[axis];
+        Self {
+            low,
+            high,
+            faces: 1 << (2 * axis),
+        }
+    }
+
+    fn extent(&self) -> [u32; 3] {
+        [0, 1, 2].map(|axis| self.high[axis] - self.low[axis] + 1)
+    }
+
+    fn face_cells(&self, face: usize) -> u32 {
+        let axis = face / 2;
+        let extent = self.extent();
+        ((self.faces >> face) & 1) * extent[(axis + 1) % 3] * extent[(axis + 2) % 3]
+    }
+
+    /// Cells over the recorded faces, the ones along edges counted once per face.
+    pub fn cell_count(&self) -> u32 {
+        (0..6).map(|face| self.face_cells(face)).sum()
+    }
+
+    /// The face cells in transform order, as the normal axis, whether the face is the high one
+    /// and the texel.
+    pub fn cells(&self) -> impl Iterator<Item = (usize, bool, [u32; 3])> + '_ {
+        let extent = self.extent();
+        (0..6).flat_map(move |face| {
+            let axis = face / 2;
+            let [u, v] = match axis {
+                0 => [1, 2],
+                1 => [0, 2],
+                _ => [0, 1],
+            };
+            let high = face % 2 == 1;
+            (0..self.face_cells(face)).map(move |local| {
+                let mut texel = self.low;
+                if high {
+                    texel[axis] = self.high[axis];
+                }
+                texel[u] += local % extent[u];
+                texel[v] += local / extent[u];
+                (axis, high, texel)
+            })
+        })
+    }
+}
+
+/// Running discrete Fourier transform of E and H at the faces of a box, at a few frequencies.
+/// Both fields are interpolated to the cell centers, H taken half a step before E as the
+/// leapfrog leaves it. The sums leave out the factor dt.
+pub struct SurfaceTransform {
+    surface: SurfaceBox,
+    frequencies: Vec<f32>, // cycles per unit of ct
+    transforms: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::ComputePipeline,
+    workgroups: [u32; 2],
+}
+
+impl SurfaceTransform {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        params: &Params,
+        shaders: &ShaderPaths,
+        electric_field_view: &wgpu::TextureView,
+        magnetic_field_view: &wgpu::TextureView,
+        surface: SurfaceBox,
+        frequencies: Vec<f32>,
+        dt: f32,
+    ) -> anyhow::Result<Self> {
+        let cells = surface.cell_count();
+        let transforms = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Surface Transforms"),
+            // four vec4 per frequency and cell
+            size: frequencies.len() as u64 * cells as u64 * 64,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let steps = frequencies
+            .iter()
+            .flat_map(|frequency| {
+                let cycles = *frequency as f64 * dt as f64;
+                [cycles as f32, (cycles * 1024.0).fract() as f32]
+            })
+            .collect::<Vec<_>>();
+        let frequency_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Surface Transform Frequencies"),
+            contents: bytemuck::cast_slice(&steps),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let field_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D3,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let buffer_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Surface Transform"),
+            entries: &[
+                field_entry(0),
+                field_entry(1),
+                buffer_entry(2, true),
+                buffer_entry(3, false),
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Surface Transform"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(electric_field_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(magnetic_field_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: frequency_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: transforms.as_entire_binding(),
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Surface Transform"),
+            bind_group_layouts: &params.bind_group_layouts(&[&bind_group_layout]),
+            push_constant_ranges: &params.push_constant_ranges(
+                wgpu::ShaderStages::COMPUTE,
+                std::mem::size_of::<DftParam>() as u32,
+            ),
+        });
+        let shader_module = device.create_shader_module(params.shader(
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Surface Transform Shader"),
+                source: wgpu::ShaderSource::Wgsl(shaders.builtin("fdtd/dft.wgsl")?.into()),
+            },
+            1,
+        ));
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Surface Transform"),
+            layout: Some(&pipeline_layout),
+            module: &shader_module,
+            entry_point: "accumulate",
+        });
+
+        // 64 cells per workgroup, wrapped into rows within the dispatch limit
+        let groups = cells.div_ceil(64);
+        let columns = groups.min(device.limits().max_compute_workgroups_per_dimension);
+        Ok(Self {
+            surface,
+            frequencies,
+            transforms,
+            bind_group,
+            pipeline,
+            workgroups: [columns, groups.div_ceil(columns)],
+        })
+    }
+
+    /// Adds the fields after `step` updates to the transforms.
+    pub fn accumulate<'a>(
+        &'a self,
+        cpass: &mut wgpu::ComputePass<'a>,
+        params: &'a Params,
+        step: u32,
+    ) {
+        let param = DftParam {
+            low: self.surface.low,
+            frequency_count: self.frequencies.len() as u32,
+            high: self.surface.high,
+            step,
+            faces: self.surface.faces,
+            _padding: [0; 3],
+        };
+        cpass.set_pipeline(&self.pipeline);
+        cpass.set_bind_group(0, &self.bind_group, &[]);
+        params.set_compute(cpass, 1, bytemuck::bytes_of(&param));
+        cpass.dispatch_workgroups(self.workgroups[0], self.workgroups[1], 1);
+    }
+
+    pub fn clear(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.clear_buffer(&self.transforms, 0, None);
+    }
+
+    pub fn surface(&self) -> SurfaceBox {
+        self.surface
+    }
+
+    pub fn frequencies(&self) -> &[f32] {
+        &self.frequencies
+    }
+
+    /// Per frequency and face cell four vec4 of f32, the real and imaginary parts of E, then
+    /// of H, in rgb.
+    pub fn transforms(&self) -> &wgpu::Buffer {
+        &self.transforms
+    }
+}
+
+/// A face cell of a surface.
+pub struct SurfaceCell {
+    pub normal: (usize, f32), // axis and sign of the outward normal
+    pub texel: [u32; 3],
+    pub position: [f32; 3], // center in world coordinates
+}
+
+/// The transforms of a surface as read back, to be post-processed on the host.
+pub struct SurfaceFields {
+    pub cells: Vec<SurfaceCell>,
+    pub frequencies: Vec<f32>, // cycles per unit of ct
+    // per frequency and cell the real and imaginary parts of E, then of H, without the factor dt
+    pub transforms: Vec<[f32; 4]>,
+    pub area: f32, // of a face cell
+    pub dt: f32,
+}
+
+impl SurfaceFields {
+    /// The transforms of E and H in `cell` at the `frequency`-th frequency, the factor dt
+    /// included.
+    pub fn fields(&self, frequency: usize, cell: usize) -> [[nalgebra::Complex<f64>; 3]; 2] {
+        let base = 4 * (frequency * self.cells.len() + cell);
+        let transform = &self.transforms[base..base + 4];
+        [0, 2].map(|first| {
+            [0, 1, 2].map(|i| {
+                nalgebra::Complex::new(transform[first][i] as f64, transform[first + 1][i] as f64)
+                    * self.dt as f64
+            })
+        })
+    }
+}
+
+/// Center, outward normal, E and H of a face cell.
+#[cfg(test)]
+type CellFields = ([f32; 3], (usize, f32), [[nalgebra::Complex<f64>; 3]; 2]);
+
+#[cfg(test)]
+impl SurfaceFields {
+    /// Transforms at a single `frequency` of cells given as their center, outward normal, E
+    /// and H, with dt 1.
+    pub fn single_frequency(frequency: f32, area: f32, cells: &[CellFields]) -> Self {
+        Self {
+            cells: cells
+                .iter()
+                .map(|(position, normal, _)| SurfaceCell {
+                    normal: *normal,
+                    texel: [0; 3],
+                    position: *position,
+                })
+                .collect(),
+            frequencies: vec![frequency],
+            transforms: cells
+                .iter()
+                .flat_map(|(_, _, fields)| {
+                    fields.iter().flat_map(|field| {
+                        [field.map(|v| v.re as f32), field.map(|v| v.im as f32)]
+                            .map(|[x, y, z]| [x, y, z, 0.0])
+                    })
+                })
+                .collect(),
+            area,
+            dt: 1.0,
+        }
+    }
+}
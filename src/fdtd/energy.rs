@@ -0,0 +1,182 @@
+use super::{params::Params, shaders::ShaderPaths};
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct EnergyParam {
+    offset: [u32; 3],
+    dt: f32,
+    end: [u32; 3],
+    _padding: u32,
+}
+
+/// Total electromagnetic energy over the interior, (eps |E|² + mu |H|²) dx³ / 2 summed over
+/// the cells. Each workgroup writes the sum of its cells, the host adds those up in f64.
+pub struct EnergySum {
+    partial_sums: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::ComputePipeline,
+    workgroups: [u32; 3],
+    param: EnergyParam,
+    cell_volume: f64,
+}
+
+impl EnergySum {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        params: &Params,
+        shaders: &ShaderPaths,
+        electric_field_view: &wgpu::TextureView,
+        magnetic_field_view: &wgpu::TextureView,
+        electric_constants_map: &wgpu::TextureView,
+        magnetic_constants_map: &wgpu::TextureView,
+        interior: [[u32; 2]; 3], // first and past the last texel along each axis
+        workgroup: [u32; 3],
+        dt: f32,
+        dx: f32,
+    ) -> anyhow::Result<Self> {
+        let workgroups =
+            [0, 1, 2].map(|axis| (interior[axis][1] - interior[axis][0]).div_ceil(workgroup[axis]));
+        let partial_sums = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Energy Partial Sums"),
+            size: workgroups
+                .iter()
+                .map(|count| *count as u64)
+                .product::<u64>()
+                * 4,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let field_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D3,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let constants_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::ReadOnly,
+                format: wgpu::TextureFormat::Rg32Float,
+                view_dimension: wgpu::TextureViewDimension::D3,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Energy"),
+            entries: &[
+                field_entry(0),
+                field_entry(1),
+                constants_entry(2),
+                constants_entry(3),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Energy"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(electric_field_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(magnetic_field_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(electric_constants_map),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(magnetic_constants_map),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: partial_sums.as_entire_binding(),
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Energy"),
+            bind_group_layouts: &params.bind_group_layouts(&[&bind_group_layout]),
+            push_constant_ranges: &params.push_constant_ranges(
+                wgpu::ShaderStages::COMPUTE,
+                std::mem::size_of::<EnergyParam>() as u32,
+            ),
+        });
+        let shader_module = device.create_shader_module(
+            params.shader(
+                wgpu::ShaderModuleDescriptor {
+                    label: Some("Energy Shader"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        shaders
+                            .builtin("fdtd/energy.wgsl")?
+                            .replace(
+                                "WORKGROUP_SIZE",
+                                format!("{}u", workgroup.iter().product::<u32>()).as_str(),
+                            )
+                            .replace("WORKGROUP_X", workgroup[0].to_string().as_str())
+                            .replace("WORKGROUP_Y", workgroup[1].to_string().as_str())
+                            .replace("WORKGROUP_Z", workgroup[2].to_string().as_str())
+                            .into(),
+                    ),
+                },
+                1,
+            ),
+        );
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Energy"),
+            layout: Some(&pipeline_layout),
+            module: &shader_module,
+            entry_point: "sum_energy",
+        });
+
+        Ok(Self {
+            partial_sums,
+            bind_group,
+            pipeline,
+            workgroups,
+            param: EnergyParam {
+                offset: interior.map(|[first, _]| first),
+                dt,
+                end: interior.map(|[_, end]| end),
+                _padding: 0,
+            },
+            cell_volume: (dx as f64).powi(3),
+        })
+    }
+
+    /// Sums the current fields into the partial sums.
+    pub fn sum<'a>(&'a self, cpass: &mut wgpu::ComputePass<'a>, params: &'a Params) {
+        cpass.set_pipeline(&self.pipeline);
+        cpass.set_bind_group(0, &self.bind_group, &[]);
+        params.set_compute(cpass, 1, bytemuck::bytes_of(&self.param));
+        cpass.dispatch_workgroups(self.workgroups[0], self.workgroups[1], self.workgroups[2]);
+    }
+
+    /// The f32 partial sums written by `sum`, one per workgroup.
+    pub fn partial_sums(&self) -> &wgpu::Buffer {
+        &self.partial_sums
+    }
+
+    /// The total energy from partial sums read back from `partial_sums`.
+    pub fn total(&self, partial_sums: &[f32]) -> f64 {
+        0.5 * self.cell_volume * partial_sums.iter().map(|sum| *sum as f64).sum::<f64>()
+    }
+}
@@ -0,0 +1,179 @@
+use super::{
+    dft::{SurfaceBox, SurfaceTransform},
+    params::Params,
+    shaders::ShaderPaths,
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct FluxParam {
+    low: [u32; 3],
+    faces: u32,
+    high: [u32; 3],
+    _padding: u32,
+}
+
+/// Running time integral of the Poynting vector through the faces of a box, per face cell and
+/// positive outwards. E and H are interpolated to the cell centers and taken after the same
+/// update, so H lags half a step. Each pass also leaves the current flux summed per workgroup.
+/// The sums leave out the factors dt and the cell area.
+pub struct SurfaceFlux {
+    surface: SurfaceBox,
+    sums: wgpu::Buffer,
+    partial_sums: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::ComputePipeline,
+    workgroups: [u32; 2],
+}
+
+impl SurfaceFlux {
+    pub fn new(
+        device: &wgpu::Device,
+        params: &Params,
+        shaders: &ShaderPaths,
+        electric_field_view: &wgpu::TextureView,
+        magnetic_field_view: &wgpu::TextureView,
+        surface: SurfaceBox,
+    ) -> anyhow::Result<Self> {
+        let cells = surface.cell_count();
+        let sums = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Surface Flux"),
+            size: cells as u64 * 4,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // 64 cells per workgroup, wrapped into rows within the dispatch limit
+        let groups = cells.div_ceil(64);
+        let columns = groups.min(device.limits().max_compute_workgroups_per_dimension);
+        let rows = groups.div_ceil(columns);
+        let partial_sums = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Surface Flux Partial Sums"),
+            size: columns as u64 * rows as u64 * 4,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let field_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D3,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let buffer_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Surface Flux"),
+            entries: &[
+                field_entry(0),
+                field_entry(1),
+                buffer_entry(2),
+                buffer_entry(3),
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Surface Flux"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(electric_field_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(magnetic_field_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: sums.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: partial_sums.as_entire_binding(),
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Surface Flux"),
+            bind_group_layouts: &params.bind_group_layouts(&[&bind_group_layout]),
+            push_constant_ranges: &params.push_constant_ranges(
+                wgpu::ShaderStages::COMPUTE,
+                std::mem::size_of::<FluxParam>() as u32,
+            ),
+        });
+        let shader_module = device.create_shader_module(params.shader(
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Surface Flux Shader"),
+                source: wgpu::ShaderSource::Wgsl(shaders.builtin("fdtd/flux.wgsl")?.into()),
+            },
+            1,
+        ));
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Surface Flux"),
+            layout: Some(&pipeline_layout),
+            module: &shader_module,
+            entry_point: "accumulate",
+        });
+
+        Ok(Self {
+            surface,
+            sums,
+            partial_sums,
+            bind_group,
+            pipeline,
+            workgroups: [columns, rows],
+        })
+    }
+
+    /// Adds the current flux to the sums and sums it per workgroup.
+    pub fn accumulate<'a>(&'a self, cpass: &mut wgpu::ComputePass<'a>, params: &'a Params) {
+        let param = FluxParam {
+            low: self.surface.low,
+            faces: self.surface.faces,
+            high: self.surface.high,
+            _padding: 0,
+        };
+        cpass.set_pipeline(&self.pipeline);
+        cpass.set_bind_group(0, &self.bind_group, &[]);
+        params.set_compute(cpass, 1, bytemuck::bytes_of(&param));
+        cpass.dispatch_workgroups(self.workgroups[0], self.workgroups[1], 1);
+    }
+
+    pub fn clear(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.clear_buffer(&self.sums, 0, None);
+    }
+
+    pub fn surface(&self) -> SurfaceBox {
+        self.surface
+    }
+
+    /// One f32 per face cell, in the order of `SurfaceBox::cells`.
+    pub fn sums(&self) -> &wgpu::Buffer {
+        &self.sums
+    }
+
+    /// One f32 per workgroup, the current flux from the latest pass.
+    pub fn partial_sums(&self) -> &wgpu::Buffer {
+        &self.partial_sums
+    }
+}
+
+/// A closed box recorded every step, each recorded face as a flux plane of its own so that the
+/// powers through the faces read back apart, with the transforms of E and H over all of them
+/// when there are frequencies.
+pub struct FluxBox {
+    pub faces: Vec<SurfaceFlux>,
+    pub transform: Option<SurfaceTransform>,
+}
@@ -0,0 +1,149 @@
+use std::cell::Cell;
+
+use super::{params::Params, shaders::ShaderPaths};
+
+/// Running average of |E|² over the steps since the last restart, updated after each step.
+/// With a time constant, older samples fade exponentially instead of being averaged evenly.
+pub struct IntensityAverage {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::ComputePipeline,
+    workgroup: [u32; 3],
+    // weight of the newest sample in the exponential average, 0 for a plain running mean
+    decay_weight: f32,
+    samples: Cell<u32>,
+}
+
+impl IntensityAverage {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        params: &Params,
+        shaders: &ShaderPaths,
+        electric_field_view: &wgpu::TextureView,
+        dimension: [u32; 3],
+        workgroup: [u32; 3],
+        dt: f32,
+        time_constant: Option<f32>,
+    ) -> anyhow::Result<Self> {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Intensity"),
+            size: wgpu::Extent3d {
+                width: dimension[0],
+                height: dimension[1],
+                depth_or_array_layers: dimension[2],
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Intensity"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::ReadWrite,
+                        format: wgpu::TextureFormat::R32Float,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Intensity"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(electric_field_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Intensity"),
+            bind_group_layouts: &params.bind_group_layouts(&[&bind_group_layout]),
+            push_constant_ranges: &params.push_constant_ranges(wgpu::ShaderStages::COMPUTE, 4),
+        });
+        let shader_module = device.create_shader_module(
+            params.shader(
+                wgpu::ShaderModuleDescriptor {
+                    label: Some("Intensity Shader"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        shaders
+                            .builtin("fdtd/intensity.wgsl")?
+                            .replace("WORKGROUP_X", workgroup[0].to_string().as_str())
+                            .replace("WORKGROUP_Y", workgroup[1].to_string().as_str())
+                            .replace("WORKGROUP_Z", workgroup[2].to_string().as_str())
+                            .into(),
+                    ),
+                },
+                1,
+            ),
+        );
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Intensity"),
+            layout: Some(&pipeline_layout),
+            module: &shader_module,
+            entry_point: "accumulate_intensity",
+        });
+
+        Ok(Self {
+            texture,
+            bind_group,
+            pipeline,
+            workgroup,
+            decay_weight: time_constant.map_or(0.0, |tau| 1.0 - (-dt / tau).exp()),
+            samples: Cell::new(0),
+        })
+    }
+
+    /// Adds the current electric field to the average.
+    pub fn accumulate<'a>(&'a self, cpass: &mut wgpu::ComputePass<'a>, params: &'a Params) {
+        let samples = self.samples.get() + 1;
+        self.samples.set(samples);
+        let weight = (1.0 / samples as f32).max(self.decay_weight);
+        let size = self.texture.size();
+        cpass.set_pipeline(&self.pipeline);
+        cpass.set_bind_group(0, &self.bind_group, &[]);
+        params.set_compute(cpass, 1, bytemuck::bytes_of(&weight));
+        cpass.dispatch_workgroups(
+            size.width.div_ceil(self.workgroup[0]),
+            size.height.div_ceil(self.workgroup[1]),
+            size.depth_or_array_layers.div_ceil(self.workgroup[2]),
+        );
+    }
+
+    /// Discards the samples so far, the next step starts a new average.
+    pub fn restart(&self) {
+        self.samples.set(0);
+    }
+
+    /// The R32Float texture holding the average.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+}
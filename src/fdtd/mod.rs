@@ -1,11 +1,127 @@
+mod dft;
+mod energy;
+mod flux;
+mod intensity;
+mod non_finite;
+mod params;
 mod pml;
+mod profiler;
+mod shaders;
 
 use wgpu::util::DeviceExt;
 
-use self::pml::PMLBoundary;
+use self::dft::SurfaceTransform;
+use self::energy::EnergySum;
+use self::flux::{FluxBox, SurfaceFlux};
+use self::intensity::IntensityAverage;
+use self::non_finite::NonFiniteCount;
+use self::pml::{PMLBoundary, PMLDescriptor};
+
+pub use self::dft::{SurfaceBox, SurfaceCell, SurfaceFields};
+pub use self::params::Params;
+pub use self::profiler::{PassClass, PassTimes, Profiler};
+pub use self::shaders::ShaderPaths;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct UpdateParam {
+    dimension: [u32; 3],
+    pmc_faces: u32,
+    offset: [u32; 3],
+    periodic: u32,         // bit per axis whose faces wrap onto each other
+    end: [u32; 3],         // past the last texel of the interior, unused by the PML field passes
+    partner_sign: f32,     // -1 for the real half of the Bloch fields, 1 for the imaginary one
+    bloch_phase: [f32; 3], // k L across each periodic axis
+    _padding2: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct VolumeExcitationParam {
+    size: [u32; 3],
+    _padding0: u32,
+    strength: [f32; 3],
+    _padding1: u32,
+    position: [u32; 3],
+    phase_offset: f32,    // -pi / 2 for the imaginary half of the Bloch fields
+    quadrature: [f32; 3], // imaginary part of the complex strength
+    _padding2: u32,
+    bloch_step: [f32; 3], // k dx
+    _padding3: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ModeExcitationParam {
+    position: [u32; 3],
+    cos_t: f32,
+    sin_t: f32,
+    envelope: f32,
+    dt: f32,
+    normal: u32,
+    bloch_step: [f32; 3], // k dx
+    phase_offset: f32,    // -pi / 2 for the imaginary half of the Bloch fields
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SliceParam {
+    slice_position: f32,
+    slice_mode: u32,
+    scaling_factor: f32,
+    log_scale: u32,
+    log_floor: f32,
+    component: u32,
+    view_scale: [f32; 2],
+    view_offset: [f32; 2],
+    overlay_opacity: f32, // of the material boundaries, 0 hides them
+    _padding: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct VolumeParam {
+    eye: [f32; 3],
+    aspect: f32,
+    half_extent: [f32; 3], // of the grid box, the longest axis is 1
+    scaling_factor: f32,
+    log_scale: u32,
+    log_floor: f32,
+    component: u32,
+    colormap: u32,
+}
 
 pub type Component = SliceMode;
 
+// zoom factor per scroll notch
+const ZOOM_STEP: f32 = 1.25;
+const MAX_ZOOM: f32 = 1024.0;
+const MATERIAL_OVERLAY_OPACITY: f32 = 0.8;
+// radians of camera orbit per window width of cursor drag
+const ORBIT_SPEED: f32 = std::f32::consts::TAU;
+const MAX_PITCH: f32 = 1.5;
+const DEFAULT_ORBIT: [f32; 2] = [0.5, 0.4];
+const DEFAULT_DISTANCE: f32 = 3.0;
+const MIN_DISTANCE: f32 = 1.5;
+const MAX_DISTANCE: f32 = 20.0;
+
+fn cursor_to_clip(cursor: [f32; 2], window_size: [f32; 2]) -> [f32; 2] {
+    [
+        cursor[0] / window_size[0] * 2.0 - 1.0,
+        1.0 - cursor[1] / window_size[1] * 2.0,
+    ]
+}
+
+/// Part of the viewed field the built-in colormaps show.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DisplayComponent {
+    X = 0,
+    Y = 1,
+    Z = 2,
+    #[default]
+    Magnitude = 3,
+}
+
 #[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub enum SliceMode {
     X = 2,
@@ -13,54 +129,649 @@ pub enum SliceMode {
     Z = 0,
 }
 
+impl SliceMode {
+    /// Grid axis along the normal of the plane.
+    pub fn axis(self) -> usize {
+        match self {
+            SliceMode::X => 0,
+            SliceMode::Y => 1,
+            SliceMode::Z => 2,
+        }
+    }
+
+    /// Grid axes spanning the plane, in the order slices show them horizontally and vertically.
+    pub fn plane_axes(self) -> [usize; 2] {
+        match self {
+            SliceMode::X => [1, 2],
+            SliceMode::Y => [0, 2],
+            SliceMode::Z => [0, 1],
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub enum FieldType {
     E,
     H,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewMode {
+    E,
+    H,
+    Material,
+    Intensity,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Colormap {
+    #[default]
+    Viridis,
+    Inferno,
+    Seismic,
+    Grayscale,
+}
+
+impl Colormap {
+    pub fn next(self) -> Self {
+        match self {
+            Colormap::Viridis => Colormap::Inferno,
+            Colormap::Inferno => Colormap::Seismic,
+            Colormap::Seismic => Colormap::Grayscale,
+            Colormap::Grayscale => Colormap::Viridis,
+        }
+    }
+
+    /// The colormap shader behind `display_scale`, the source of display_scale.wgsl.
+    fn shader(self, display_scale: &str) -> wgpu::ShaderModuleDescriptor<'static> {
+        let (label, source) = match self {
+            Colormap::Viridis => (
+                "viridis_blit.wgsl",
+                include_str!("../../shader/viridis_blit.wgsl"),
+            ),
+            Colormap::Inferno => (
+                "inferno_blit.wgsl",
+                include_str!("../../shader/inferno_blit.wgsl"),
+            ),
+            Colormap::Seismic => (
+                "seismic_blit.wgsl",
+                include_str!("../../shader/seismic_blit.wgsl"),
+            ),
+            Colormap::Grayscale => (
+                "xyz_norm_blit.wgsl",
+                include_str!("../../shader/xyz_norm_blit.wgsl"),
+            ),
+        };
+        wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl((display_scale.to_owned() + source).into()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type")]
 pub enum BoundaryCondition {
-    PML { sigma: f32, alpha: f32, cells: u32 },
+    PML {
+        sigma: f32,
+        alpha: f32,
+        cells: u32,
+        #[serde(default)]
+        faces: PMLFaces,
+        #[serde(default)]
+        symmetry: SymmetryFaces,
+        #[serde(default)]
+        periodic: [bool; 3], // axes whose two faces wrap onto each other, without PML
+        #[serde(default)]
+        bloch_k: [f32; 3], // the fields pick up exp(i k L) across a period L, zero on other axes
+    },
+    PEC,
+    PMC,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// Faces of the domain the PML covers, all by default. The others are PEC walls, unless they
+/// are symmetry planes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PMLFaces {
+    #[serde(default = "enabled")]
+    pub x_min: bool,
+    #[serde(default = "enabled")]
+    pub x_max: bool,
+    #[serde(default = "enabled")]
+    pub y_min: bool,
+    #[serde(default = "enabled")]
+    pub y_max: bool,
+    #[serde(default = "enabled")]
+    pub z_min: bool,
+    #[serde(default = "enabled")]
+    pub z_max: bool,
+}
+
+impl Default for PMLFaces {
+    fn default() -> Self {
+        Self {
+            x_min: true,
+            x_max: true,
+            y_min: true,
+            y_max: true,
+            z_min: true,
+            z_max: true,
+        }
+    }
+}
+
+impl PMLFaces {
+    /// Low and high face of each axis.
+    pub fn sides(&self) -> [[bool; 2]; 3] {
+        [
+            [self.x_min, self.x_max],
+            [self.y_min, self.y_max],
+            [self.z_min, self.z_max],
+        ]
+    }
+}
+
+/// Mirror symmetry of the fields about a face of the domain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Symmetry {
+    /// Tangential E vanishes on the plane, E mirrors odd and H even
     PEC,
+    /// Tangential H vanishes on the plane, E mirrors even and H odd
     PMC,
 }
 
+impl Symmetry {
+    /// Sign a component of `field` along `axis` takes in the mirror image across a plane
+    /// normal to `normal`.
+    pub fn parity(self, field: FieldType, axis: usize, normal: usize) -> f32 {
+        // normal E and tangential H keep their sign across a PEC plane
+        let even = (axis == normal) == matches!(field, FieldType::E);
+        if even == (self == Symmetry::PEC) {
+            1.0
+        } else {
+            -1.0
+        }
+    }
+}
+
+/// Faces of the domain that are symmetry planes, none by default. The outermost layer of cells
+/// on such a face is the plane, it gets no PML and only half the structure is simulated.
+/// Sources must be symmetric too: on the plane itself, or placed so their mirror image is
+/// the intended other half, e.g. E parallel to a PMC plane or normal to a PEC one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SymmetryFaces {
+    pub x_min: Option<Symmetry>,
+    pub x_max: Option<Symmetry>,
+    pub y_min: Option<Symmetry>,
+    pub y_max: Option<Symmetry>,
+    pub z_min: Option<Symmetry>,
+    pub z_max: Option<Symmetry>,
+}
+
+impl SymmetryFaces {
+    /// Low and high face of each axis.
+    pub fn sides(&self) -> [[Option<Symmetry>; 2]; 3] {
+        [
+            [self.x_min, self.x_max],
+            [self.y_min, self.y_max],
+            [self.z_min, self.z_max],
+        ]
+    }
+}
+
 impl BoundaryCondition {
-    pub fn get_extra_grid_extent(&self) -> u32 {
+    /// Faces the PML actually covers, symmetry planes and periodic axes left out.
+    pub fn pml_faces(&self) -> Option<PMLFaces> {
+        match *self {
+            BoundaryCondition::PML {
+                faces,
+                symmetry,
+                periodic,
+                ..
+            } => {
+                let [[x_min, x_max], [y_min, y_max], [z_min, z_max]] = [0, 1, 2].map(|axis| {
+                    [0, 1].map(|side| {
+                        faces.sides()[axis][side]
+                            && symmetry.sides()[axis][side].is_none()
+                            && !periodic[axis]
+                    })
+                });
+                Some(PMLFaces {
+                    x_min,
+                    x_max,
+                    y_min,
+                    y_max,
+                    z_min,
+                    z_max,
+                })
+            }
+            BoundaryCondition::PEC | BoundaryCondition::PMC => None,
+        }
+    }
+
+    pub fn symmetry(&self) -> SymmetryFaces {
+        match *self {
+            BoundaryCondition::PML { symmetry, .. } => symmetry,
+            BoundaryCondition::PEC | BoundaryCondition::PMC => SymmetryFaces::default(),
+        }
+    }
+
+    /// Cells added before and after the simulation interior along each axis.
+    pub fn padding(&self) -> [[u32; 2]; 3] {
+        match (*self, self.pml_faces()) {
+            (BoundaryCondition::PML { cells, .. }, Some(faces)) => faces
+                .sides()
+                .map(|sides| sides.map(|enabled| enabled as u32 * cells)),
+            _ => [[0; 2]; 3],
+        }
+    }
+
+    /// Cells added to each axis, both sides together.
+    pub fn get_extra_grid_extent(&self) -> [u32; 3] {
+        self.padding().map(|[low, high]| low + high)
+    }
+
+    /// Axes whose faces wrap onto each other, bit `axis`.
+    pub fn periodic_axes(&self) -> u32 {
         match *self {
-            BoundaryCondition::PML { cells, .. } => cells * 2,
+            BoundaryCondition::PML { periodic, .. } => periodic
+                .iter()
+                .enumerate()
+                .fold(0, |mask, (axis, periodic)| {
+                    mask | ((*periodic as u32) << axis)
+                }),
             BoundaryCondition::PEC | BoundaryCondition::PMC => 0,
         }
     }
 
-    pub fn use_pmc(&self) -> u32 {
+    /// Bloch wave vector of the periodic axes, in radians per length unit of the domain.
+    pub fn bloch_k(&self) -> [f32; 3] {
+        match *self {
+            BoundaryCondition::PML { bloch_k, .. } => bloch_k,
+            BoundaryCondition::PEC | BoundaryCondition::PMC => [0.0; 3],
+        }
+    }
+
+    /// Whether a Bloch wave vector couples a second, imaginary half of the fields to the real
+    /// one. Each half is a real simulation reading the other across the periodic faces.
+    pub fn has_bloch_phase(&self) -> bool {
+        self.bloch_k().iter().any(|k| *k != 0.0)
+    }
+
+    /// Faces whose outermost layer is a PMC wall, bit `2 * axis + side`, the others are PEC.
+    pub fn pmc_faces(&self) -> u32 {
         match *self {
-            BoundaryCondition::PML { .. } | BoundaryCondition::PEC => 0,
-            BoundaryCondition::PMC => 1,
+            BoundaryCondition::PML { symmetry, .. } => symmetry
+                .sides()
+                .iter()
+                .flatten()
+                .enumerate()
+                .fold(0, |mask, (i, symmetry)| {
+                    mask | (((*symmetry == Some(Symmetry::PMC)) as u32) << i)
+                }),
+            BoundaryCondition::PEC => 0,
+            BoundaryCondition::PMC => 0b111111,
         }
     }
 }
 
+/// Whether the grid spans all three axes, a single layer of cells normal to z or a single
+/// column of cells along z. Collapsed axes wrap onto themselves so nothing varies along them,
+/// and only the components of one polarization are kept: Ex, Ey and Hz for TE, Ez, Hx and Hy
+/// for TM, Ex and Hy in 1D. All of them run the 3D update on the collapsed grid, a dedicated
+/// Ex/Hy kernel for 1D, which would skip the four zero components, is not written yet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum DimensionMode {
+    #[default]
+    #[serde(rename = "3d")]
+    ThreeD,
+    #[serde(rename = "2d_te")]
+    TwoDTE,
+    #[serde(rename = "2d_tm")]
+    TwoDTM,
+    #[serde(rename = "1d")]
+    OneD,
+}
+
+impl DimensionMode {
+    /// Axes reduced to a single cell.
+    pub fn collapsed_axes(self) -> &'static [usize] {
+        match self {
+            DimensionMode::ThreeD => &[],
+            DimensionMode::TwoDTE | DimensionMode::TwoDTM => &[2],
+            DimensionMode::OneD => &[0, 1],
+        }
+    }
+
+    /// Number of axes the fields vary along.
+    pub fn dimensions(self) -> usize {
+        3 - self.collapsed_axes().len()
+    }
+
+    /// Which of the x, y, z components of `field` the mode keeps.
+    pub fn components(self, field: FieldType) -> [bool; 3] {
+        match (self, field) {
+            (DimensionMode::ThreeD, _) => [true; 3],
+            (DimensionMode::TwoDTE, FieldType::E) | (DimensionMode::TwoDTM, FieldType::H) => {
+                [true, true, false]
+            }
+            (DimensionMode::TwoDTE, FieldType::H) | (DimensionMode::TwoDTM, FieldType::E) => {
+                [false, false, true]
+            }
+            (DimensionMode::OneD, FieldType::E) => [true, false, false],
+            (DimensionMode::OneD, FieldType::H) => [false, true, false],
+        }
+    }
+}
+
+/// `extent / dx`, snapped to the whole number of cells within a hundredth of a cell of it. An
+/// extent meant to hold a whole number of cells, like the single cell of a collapsed axis, then
+/// holds exactly that many however its bounds round.
+pub fn steps_across(extent: f32, dx: f32) -> f32 {
+    let steps = extent / dx;
+    if (steps - steps.round()).abs() < 0.01 {
+        steps.round()
+    } else {
+        steps
+    }
+}
+
+/// Number of cells per axis, including the boundary layers.
+pub fn grid_dimension(domain: [[f32; 2]; 3], dx: f32, boundary: &BoundaryCondition) -> [u32; 3] {
+    let extra = boundary.get_extra_grid_extent();
+    [0, 1, 2].map(|i| steps_across(domain[i][1] - domain[i][0], dx).ceil() as u32 + extra[i])
+}
+
+/// Offset from world coordinates to the grid, in world units: `(position + shift) / dx` is the
+/// grid coordinate of `position`, boundary layers included. Models, sources, slices and exports
+/// all go through this so they agree on where a point lands. `padding` is the one of
+/// [`BoundaryCondition::padding`].
+pub fn grid_shift(
+    domain: [[f32; 2]; 3],
+    dx: f32,
+    padding: [[u32; 2]; 3],
+) -> nalgebra::Vector3<f32> {
+    -nalgebra::Vector3::from([0, 1, 2].map(|i| {
+        let [low, high] = domain[i];
+        let step = steps_across(high - low, dx);
+        low + (step - step.floor()) * dx * 0.5 - padding[i][0] as f32 * dx
+    }))
+}
+
+/// Grid coordinate of the world `position`, see [`grid_shift`].
+pub fn world_to_grid(
+    domain: [[f32; 2]; 3],
+    dx: f32,
+    padding: [[u32; 2]; 3],
+    position: [f32; 3],
+) -> [f32; 3] {
+    ((nalgebra::Vector3::from(position) + grid_shift(domain, dx, padding)) / dx).into()
+}
+
+/// Grid cells per axis of the world box `position` ± `size` / 2: those whose centers it holds,
+/// like a model, and at least one. Along axes where `size` is 0 it is the cell whose low face is
+/// closest to `position`, the plane of a mode source. May reach past the grid.
+pub fn box_cells(
+    domain: [[f32; 2]; 3],
+    dx: f32,
+    padding: [[u32; 2]; 3],
+    position: [f32; 3],
+    size: [f32; 3],
+) -> [std::ops::Range<i32>; 3] {
+    let low = world_to_grid(
+        domain,
+        dx,
+        padding,
+        [0, 1, 2].map(|i| position[i] - size[i] / 2.0),
+    );
+    let high = world_to_grid(
+        domain,
+        dx,
+        padding,
+        [0, 1, 2].map(|i| position[i] + size[i] / 2.0),
+    );
+    [0, 1, 2].map(|axis| {
+        if size[axis] > 0.0 {
+            // cell i is centered on grid coordinate i + 0.5
+            let start = (low[axis] - 0.5).ceil() as i32;
+            let end = (high[axis] - 0.5).floor() as i32 + 1;
+            start..end.max(start + 1)
+        } else {
+            let layer = low[axis].round() as i32;
+            layer..layer + 1
+        }
+    })
+}
+
+pub struct MemoryEstimate {
+    pub label: &'static str,
+    pub textures: u32,
+    pub bytes: u64,
+}
+
+/// Texture memory `FDTD::new` allocates for a grid, grouped by purpose.
+pub fn estimate_memory(
+    grid: [u32; 3],
+    boundary: &BoundaryCondition,
+    intensity: bool,
+) -> Vec<MemoryEstimate> {
+    let [x, y, z] = grid.map(|v| v as u64);
+    let cells = x * y * z;
+    let mut estimate = vec![
+        MemoryEstimate {
+            label: "E field (Rgba32Float)",
+            textures: 1,
+            bytes: cells * 16,
+        },
+        MemoryEstimate {
+            label: "H field (Rgba32Float)",
+            textures: 1,
+            bytes: cells * 16,
+        },
+        MemoryEstimate {
+            label: "Update constants (Rg32Float)",
+            textures: 2,
+            bytes: 2 * cells * 8,
+        },
+        MemoryEstimate {
+            label: "Permittivity map (R32Float)",
+            textures: 1,
+            bytes: cells * 4,
+        },
+    ];
+    // the imaginary half of the Bloch fields has PML of its own
+    let halves = if boundary.has_bloch_phase() {
+        estimate.push(MemoryEstimate {
+            label: "Imaginary E and H for the Bloch phase (Rgba32Float)",
+            textures: 2,
+            bytes: 2 * cells * 16,
+        });
+        2
+    } else {
+        1
+    };
+    if let (BoundaryCondition::PML { cells: c, .. }, Some(faces)) =
+        (*boundary, boundary.pml_faces())
+    {
+        let c = c as u64;
+        let extra = boundary.get_extra_grid_extent().map(|v| v as u64);
+        let [sx, sy, sz] = [x - extra[0], y - extra[1], z - extra[2]];
+        let (length, area) = ([sx, sy, sz], [sy * sz, sx * sz, sx * sy]);
+        // per field type: corners, surfaces and edges as laid out in pml.rs, present where
+        // all the faces they touch are
+        let regions = pml::regions(faces);
+        let psi = regions.corners.len() as u64 * 6 * c * c * c
+            + (0..3)
+                .map(|axis| {
+                    regions.surfaces[axis].len() as u64 * 2 * c * area[axis]
+                        + regions.edges[axis].len() as u64 * 4 * length[axis] * c * c
+                })
+                .sum::<u64>();
+        let textures = regions.corners.len() * 6
+            + (0..3)
+                .map(|axis| regions.surfaces[axis].len() * 2 + regions.edges[axis].len() * 4)
+                .sum::<usize>();
+        estimate.push(MemoryEstimate {
+            label: "PML psi (R32Float)",
+            textures: halves * 2 * textures as u32,
+            bytes: halves as u64 * 2 * psi * 4,
+        });
+        estimate.push(MemoryEstimate {
+            label: "PML constants (R32Float)",
+            textures: 2 * 6,
+            bytes: 2 * 2 * (sy * sz + sx * sz + sx * sy) * 4,
+        });
+    }
+    if intensity {
+        estimate.push(MemoryEstimate {
+            label: "Intensity average (R32Float)",
+            textures: 1,
+            bytes: cells * 4,
+        });
+    }
+    estimate
+}
+
 pub struct VisualizeComponent {
     vertex_shader: wgpu::ShaderModule,
     render_pipeline_layout: wgpu::PipelineLayout,
     rect_vertices: wgpu::Buffer,
     electric_field_render_bind_group: wgpu::BindGroup,
     magnetic_field_render_bind_group: wgpu::BindGroup,
+    material_render_bind_group: wgpu::BindGroup,
+    intensity_render_bind_group: Option<wgpu::BindGroup>,
     render_pipeline: wgpu::RenderPipeline,
+    volume_pipeline: wgpu::RenderPipeline,
+    line_pipeline: Option<wgpu::RenderPipeline>, // plots the column of the 1D mode instead of a slice
+    // None while a custom fragment shader is in use
+    colormap: Option<Colormap>,
+    shader_error: Option<String>, // why the last custom shader was rejected
+    display_scale: String,        // display_scale.wgsl, prepended to every slice shader
+}
+
+impl VisualizeComponent {
+    /// Like `create_render_pipeline`, but returns compilation and validation errors instead of
+    /// leaving them to the uncaptured error handler, which panics.
+    fn try_create_render_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        vertex_shader: &wgpu::ShaderModule,
+        fragment_shader: wgpu::ShaderModuleDescriptor,
+        render_format: wgpu::TextureFormat,
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader_module = device.create_shader_module(fragment_shader);
+        let render_pipeline = Self::create_render_pipeline(
+            device,
+            layout,
+            vertex_shader,
+            &shader_module,
+            render_format,
+        );
+        match pollster::block_on(device.pop_error_scope()) {
+            Some(err) => Err(anyhow::anyhow!("{}", err)),
+            None => Ok(render_pipeline),
+        }
+    }
+
+    fn create_render_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        vertex_shader: &wgpu::ShaderModule,
+        fragment_shader: &wgpu::ShaderModule,
+        render_format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: vertex_shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<crate::Vertex>() as _,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x2,
+                        1 => Float32x2
+                    ],
+                }],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: fragment_shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: render_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        })
+    }
+}
+
+/// Imaginary half of the complex fields of a Bloch-periodic simulation, stepped alongside the
+/// real half FDTD holds itself. The sources excite it with the quadrature of their carrier.
+struct ImaginaryFields {
+    electric_field_texture: wgpu::Texture,
+    magnetic_field_texture: wgpu::Texture,
+    electric_field_bind_group: wgpu::BindGroup,
+    magnetic_field_bind_group: wgpu::BindGroup,
+    electric_field_excitation_bind_group: wgpu::BindGroup,
+    magnetic_field_excitation_bind_group: wgpu::BindGroup,
+    pml: Option<PMLBoundary>,
+}
+
+/// The bind groups and PML of one half of the fields, with what sets it apart in the shaders.
+struct FieldHalf<'a> {
+    electric_field_bind_group: &'a wgpu::BindGroup,
+    magnetic_field_bind_group: &'a wgpu::BindGroup,
+    electric_field_excitation_bind_group: &'a wgpu::BindGroup,
+    magnetic_field_excitation_bind_group: &'a wgpu::BindGroup,
+    pml: Option<&'a PMLBoundary>,
+    partner_sign: f32, // of the partner field across the periodic faces
+    phase_offset: f32, // added to the Bloch phase of the sources
+}
+
+/// What `FDTD::new` takes besides the settings of the preset.
+pub struct FDTDDescriptor<'a> {
+    pub render_format: Option<wgpu::TextureFormat>, // None runs without visualization
+    pub shaders: &'a ShaderPaths,
+    pub workgroup_dispatch: crate::settings::WorkgroupSettings, // the preset's or the adapter default
+    pub near_field: Option<(SurfaceBox, Vec<f32>)>, // faces and frequencies of the near-field box
+    pub mode_monitors: Vec<(SurfaceBox, Vec<f32>)>, // planes and frequencies
+    pub flux_surfaces: Vec<SurfaceBox>,
+    pub flux_boxes: Vec<(SurfaceBox, Vec<f32>)>, // closed boxes and frequencies
+    pub mode_source_bind_group_layout: &'a wgpu::BindGroupLayout,
+    pub dump_materials: Option<&'a std::path::Path>,
+    pub reuse_cache: bool, // false voxelizes anew and overwrites the material cache entry
+    pub strict: bool,
+    pub params: Params,
+    pub profiler: Option<Profiler>,
 }
 
 pub struct FDTD {
-    workgroup_dispatch: crate::WorkgroupSettings,
+    workgroup_dispatch: crate::settings::WorkgroupSettings,
 
     electric_field_bind_group: wgpu::BindGroup,
-    electric_field_texture: [wgpu::Texture; 3],
+    electric_field_texture: wgpu::Texture,
     magnetic_field_bind_group: wgpu::BindGroup,
-    magnetic_field_texture: [wgpu::Texture; 3],
+    magnetic_field_texture: wgpu::Texture,
+    permittivity_texture: wgpu::Texture,
     update_magnetic_field_pipeline: wgpu::ComputePipeline,
     update_electric_field_pipeline: wgpu::ComputePipeline,
+    // magnetic then electric, None when the halo does not fit in workgroup memory
+    tiled_update_pipelines: Option<[wgpu::ComputePipeline; 2]>,
+    use_tiled_update: bool,
     electric_field_excitation_bind_group: wgpu::BindGroup,
     magnetic_field_excitation_bind_group: wgpu::BindGroup,
     excite_field_volume_pipeline: wgpu::ComputePipeline,
@@ -70,12 +781,38 @@ pub struct FDTD {
     spatial_step: f32,
     temporal_step: f32,
     boundary: BoundaryCondition,
+    dimension_mode: DimensionMode,
     pml: Option<PMLBoundary>,
+    imaginary: Option<ImaginaryFields>, // with a Bloch wave vector
+    bloch_phase: [f32; 3],              // k L across each periodic axis
+    bloch_step: [f32; 3],               // k dx
+    intensity: Option<IntensityAverage>,
+    energy: Option<EnergySum>,
+    near_field: Option<SurfaceTransform>,
+    mode_monitors: Vec<SurfaceTransform>,
+    flux_surfaces: Vec<SurfaceFlux>, // accumulated on request
+    flux_boxes: Vec<FluxBox>,
+    non_finite: NonFiniteCount,
+    params: Params,
+    profiler: Option<Profiler>,
+    clear_texture_supported: bool,
 
     slice_position: f32,
     slice_mode: SliceMode,
     field_view_mode: FieldType,
+    material_view: bool,
+    intensity_view: bool,
     scaling_factor: f32,
+    log_scale: bool,
+    log_floor: f32,
+    display_component: DisplayComponent,
+    stretch: bool,
+    zoom: f32,
+    pan: [f32; 2], // clip-space offset of the zoomed slice
+    material_overlay: bool,
+    volume_view: bool,
+    orbit: [f32; 2], // yaw and pitch of the volume camera
+    distance: f32,   // of the volume camera from the center of the grid
 
     // visualize
     visualization: Option<VisualizeComponent>,
@@ -85,27 +822,35 @@ impl FDTD {
     pub fn new(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        render_format: Option<wgpu::TextureFormat>,
-        dx: f32,
-        dt: f32,
-        dimension: [[f32; 2]; 3],
-        models: Vec<crate::ModelSettings>,
-        boundary: BoundaryCondition,
-        default_slice: crate::SliceSettings,
-        default_shader: &str,
-        default_scaling_factor: f32,
-        workgroup_dispatch: crate::WorkgroupSettings,
-        mode_source_bind_group_layout: &wgpu::BindGroupLayout,
+        settings: &crate::settings::FDTDSettings,
+        descriptor: FDTDDescriptor,
     ) -> anyhow::Result<Self> {
-        let step_x = (dimension[0][1] - dimension[0][0]) / dx;
-        let step_y = (dimension[1][1] - dimension[1][0]) / dx;
-        let step_z = (dimension[2][1] - dimension[2][0]) / dx;
-
-        let grid_x = step_x.ceil() as u32 + boundary.get_extra_grid_extent();
-        let grid_y = step_y.ceil() as u32 + boundary.get_extra_grid_extent();
-        let grid_z = step_z.ceil() as u32 + boundary.get_extra_grid_extent();
+        let FDTDDescriptor {
+            render_format,
+            shaders,
+            workgroup_dispatch,
+            near_field,
+            mode_monitors,
+            flux_surfaces,
+            flux_boxes,
+            mode_source_bind_group_layout,
+            dump_materials,
+            reuse_cache,
+            strict,
+            params,
+            profiler,
+        } = descriptor;
+        let dx = settings.spatial_step;
+        let dt = settings.temporal_step();
+        let dimension = settings.domain;
+        let background = settings.background.material();
+        let boundary = settings.boundary;
+        let dimension_mode = settings.dimension_mode;
+        let default_slice = &settings.default_slice;
+        let [grid_x, grid_y, grid_z] = grid_dimension(dimension, dx, &boundary);
 
-        let common_texture_descriptor = wgpu::TextureDescriptor {
+        // x, y, z components in rgb, a is unused
+        let field_texture_descriptor = wgpu::TextureDescriptor {
             label: None,
             size: wgpu::Extent3d {
                 width: grid_x,
@@ -115,73 +860,144 @@ impl FDTD {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D3,
-            format: wgpu::TextureFormat::R32Float,
+            format: wgpu::TextureFormat::Rgba32Float,
             usage: wgpu::TextureUsages::STORAGE_BINDING
                 | wgpu::TextureUsages::TEXTURE_BINDING
-                | wgpu::TextureUsages::COPY_SRC,
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         };
-        let electric_field_texture = [
-            device.create_texture(&common_texture_descriptor),
-            device.create_texture(&common_texture_descriptor),
-            device.create_texture(&common_texture_descriptor),
-        ];
-        let electric_field_view = [
-            electric_field_texture[0].create_view(&wgpu::TextureViewDescriptor::default()),
-            electric_field_texture[1].create_view(&wgpu::TextureViewDescriptor::default()),
-            electric_field_texture[2].create_view(&wgpu::TextureViewDescriptor::default()),
-        ];
-        let magnetic_field_texture = [
-            device.create_texture(&common_texture_descriptor),
-            device.create_texture(&common_texture_descriptor),
-            device.create_texture(&common_texture_descriptor),
-        ];
-        let magnetic_field_view = [
-            magnetic_field_texture[0].create_view(&wgpu::TextureViewDescriptor::default()),
-            magnetic_field_texture[1].create_view(&wgpu::TextureViewDescriptor::default()),
-            magnetic_field_texture[2].create_view(&wgpu::TextureViewDescriptor::default()),
-        ];
+        let electric_field_texture = device.create_texture(&field_texture_descriptor);
+        let electric_field_view =
+            electric_field_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let magnetic_field_texture = device.create_texture(&field_texture_descriptor);
+        let magnetic_field_view =
+            magnetic_field_texture.create_view(&wgpu::TextureViewDescriptor::default());
 
         let mut importer = match boundary {
-            BoundaryCondition::PML { sigma, alpha, .. } => gltf_importer::Importer::new(
-                dimension,
-                dt,
-                dx,
-                gltf_importer::MaterialConstants {
-                    permittivity: 1.0,
-                    permeability: 1.0,
-                },
-                boundary.get_extra_grid_extent(),
-                sigma,
-                alpha,
-            ),
+            BoundaryCondition::PML { sigma, alpha, .. } => {
+                // the decay rates are divided by the permittivity of each cell, scaling by the
+                // background admittance keeps the attenuation per cell of a vacuum background
+                let admittance = (background.permittivity / background.permeability).sqrt();
+                gltf_importer::Importer::new(
+                    dimension,
+                    dt,
+                    dx,
+                    background,
+                    boundary.padding(),
+                    sigma * admittance,
+                    alpha * admittance,
+                )
+            }
             BoundaryCondition::PEC | BoundaryCondition::PMC => gltf_importer::Importer::new(
                 dimension,
                 dt,
                 dx,
-                gltf_importer::MaterialConstants {
-                    permittivity: 1.0,
-                    permeability: 1.0,
-                },
-                boundary.get_extra_grid_extent(),
+                background,
+                boundary.padding(),
                 0.,
                 0.,
             ),
         };
-        for model in models {
-            importer.load_gltf(
-                &model.path,
-                model.scale,
-                model.position,
-                gltf_importer::MaterialConstants {
-                    permittivity: model.refractive_index * model.refractive_index,
-                    permeability: 1.0,
-                },
-            )?;
+        let material_cache =
+            settings
+                .material_cache
+                .as_ref()
+                .map(|directory| gltf_importer::MaterialCache {
+                    directory: directory.into(),
+                    reuse: reuse_cache,
+                });
+        let cache_path = material_cache
+            .as_ref()
+            .map(|cache| -> anyhow::Result<_> {
+                let parameters = format!(
+                    "{:?} {:?} {:?} {:?} {:?}",
+                    dimension, dx, dt, boundary, background
+                );
+                let key = gltf_importer::cache_key(
+                    &settings.models,
+                    &settings.extrusions,
+                    &settings.material_clouds,
+                    &parameters,
+                )?;
+                Ok(cache.directory.join(format!("{:016x}.zlib", key)))
+            })
+            .transpose()?;
+        let cached = match (&material_cache, &cache_path) {
+            (Some(cache), Some(path)) if cache.reuse && path.is_file() => {
+                match importer.load_cache(path) {
+                    Ok(()) => {
+                        println!("Loaded the voxelized materials from {}", path.display());
+                        true
+                    }
+                    Err(err) => {
+                        eprintln!("Ignoring material cache entry {}: {}", path.display(), err);
+                        false
+                    }
+                }
+            }
+            _ => false,
+        };
+        if !cached {
+            for model in &settings.models {
+                importer.load_gltf(
+                    &model.path,
+                    model.scale,
+                    model.rotation,
+                    model.position,
+                    model.material.importer_material(model.refractive_index),
+                    &model
+                        .materials
+                        .iter()
+                        .map(|(name, material)| {
+                            (
+                                name.clone(),
+                                material
+                                    .material
+                                    .importer_material(material.refractive_index),
+                            )
+                        })
+                        .collect(),
+                    model.subpixel_samples,
+                    model.parity_axes,
+                    model.repeat,
+                    strict,
+                )?;
+            }
+            for extrusion in &settings.extrusions {
+                importer.load_polygon_csv(
+                    &extrusion.path,
+                    extrusion.position,
+                    [extrusion.z_min, extrusion.z_max],
+                    extrusion
+                        .material
+                        .importer_material(extrusion.refractive_index),
+                    extrusion.subpixel_samples,
+                )?;
+            }
+            for cloud in &settings.material_clouds {
+                importer.load_permittivity_csv(&cloud.path, cloud.scale, cloud.position)?;
+            }
+            if let Some(path) = &cache_path {
+                if let Err(err) = importer.save_cache(path) {
+                    eprintln!(
+                        "Failed to write material cache entry {}: {}",
+                        path.display(),
+                        err
+                    );
+                }
+            }
         }
 
-        let (electric_constants_map, magnetic_constants_map, pml_constants) =
-            importer.into_constants_map(device, queue);
+        let gltf_importer::ConstantsMaps {
+            electric: electric_constants_map,
+            magnetic: magnetic_constants_map,
+            permittivity: permittivity_texture,
+            pml: pml_constants,
+        } = importer.into_constants_map(device, queue, dump_materials)?;
+
+        let permittivity_map =
+            permittivity_texture.create_view(&wgpu::TextureViewDescriptor::default());
 
         let field_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -192,7 +1008,7 @@ impl FDTD {
                         visibility: wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::StorageTexture {
                             access: wgpu::StorageTextureAccess::ReadWrite,
-                            format: wgpu::TextureFormat::R32Float,
+                            format: wgpu::TextureFormat::Rgba32Float,
                             view_dimension: wgpu::TextureViewDimension::D3,
                         },
                         count: None,
@@ -201,8 +1017,8 @@ impl FDTD {
                         binding: 1,
                         visibility: wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::ReadWrite,
-                            format: wgpu::TextureFormat::R32Float,
+                            access: wgpu::StorageTextureAccess::ReadOnly,
+                            format: wgpu::TextureFormat::Rgba32Float,
                             view_dimension: wgpu::TextureViewDimension::D3,
                         },
                         count: None,
@@ -210,172 +1026,90 @@ impl FDTD {
                     wgpu::BindGroupLayoutEntry {
                         binding: 2,
                         visibility: wgpu::ShaderStages::COMPUTE,
-                        ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::ReadWrite,
-                            format: wgpu::TextureFormat::R32Float,
-                            view_dimension: wgpu::TextureViewDimension::D3,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 3,
-                        visibility: wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::StorageTexture {
                             access: wgpu::StorageTextureAccess::ReadOnly,
-                            format: wgpu::TextureFormat::R32Float,
+                            format: wgpu::TextureFormat::Rg32Float,
                             view_dimension: wgpu::TextureViewDimension::D3,
                         },
                         count: None,
                     },
                     wgpu::BindGroupLayoutEntry {
-                        binding: 4,
+                        binding: 3,
                         visibility: wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::StorageTexture {
                             access: wgpu::StorageTextureAccess::ReadOnly,
-                            format: wgpu::TextureFormat::R32Float,
+                            format: wgpu::TextureFormat::Rgba32Float,
                             view_dimension: wgpu::TextureViewDimension::D3,
                         },
                         count: None,
                     },
+                ],
+            });
+
+        // the imaginary half of the fields, E then H
+        let imaginary_field_textures = boundary.has_bloch_phase().then(|| {
+            [(); 2].map(|_| {
+                let texture = device.create_texture(&field_texture_descriptor);
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                (texture, view)
+            })
+        });
+        // update, conjugative field and constants, then the conjugative field of the other half
+        let create_field_bind_group = |views: [&wgpu::TextureView; 4]| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &field_bind_group_layout,
+                entries: &[0, 1, 2, 3].map(|binding| wgpu::BindGroupEntry {
+                    binding,
+                    resource: wgpu::BindingResource::TextureView(views[binding as usize]),
+                }),
+            })
+        };
+        let [electric_partner_view, magnetic_partner_view] = match &imaginary_field_textures {
+            Some([(_, electric), (_, magnetic)]) => [magnetic, electric],
+            None => [&magnetic_field_view, &electric_field_view],
+        };
+
+        let electric_field_bind_group = create_field_bind_group([
+            &electric_field_view,
+            &magnetic_field_view,
+            &electric_constants_map,
+            electric_partner_view,
+        ]);
+
+        let magnetic_field_bind_group = create_field_bind_group([
+            &magnetic_field_view,
+            &electric_field_view,
+            &magnetic_constants_map,
+            magnetic_partner_view,
+        ]);
+
+        let update_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &params.bind_group_layouts(&[&field_bind_group_layout]),
+                push_constant_ranges: &params.push_constant_ranges(
+                    wgpu::ShaderStages::COMPUTE,
+                    std::mem::size_of::<UpdateParam>() as u32,
+                ),
+            });
+
+        let excite_field_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[
                     wgpu::BindGroupLayoutEntry {
-                        binding: 5,
+                        binding: 0,
                         visibility: wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::ReadOnly,
-                            format: wgpu::TextureFormat::R32Float,
+                            access: wgpu::StorageTextureAccess::ReadWrite,
+                            format: wgpu::TextureFormat::Rgba32Float,
                             view_dimension: wgpu::TextureViewDimension::D3,
                         },
                         count: None,
                     },
                     wgpu::BindGroupLayoutEntry {
-                        binding: 6,
-                        visibility: wgpu::ShaderStages::COMPUTE,
-                        ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::ReadOnly,
-                            format: wgpu::TextureFormat::Rg32Float,
-                            view_dimension: wgpu::TextureViewDimension::D3,
-                        },
-                        count: None,
-                    },
-                ],
-            });
-
-        let electric_field_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
-            layout: &field_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&electric_field_view[0]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::TextureView(&electric_field_view[1]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: wgpu::BindingResource::TextureView(&electric_field_view[2]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 3,
-                    resource: wgpu::BindingResource::TextureView(&magnetic_field_view[0]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 4,
-                    resource: wgpu::BindingResource::TextureView(&magnetic_field_view[1]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 5,
-                    resource: wgpu::BindingResource::TextureView(&magnetic_field_view[2]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 6,
-                    resource: wgpu::BindingResource::TextureView(&electric_constants_map),
-                },
-            ],
-        });
-
-        let magnetic_field_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
-            layout: &field_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&magnetic_field_view[0]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::TextureView(&magnetic_field_view[1]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: wgpu::BindingResource::TextureView(&magnetic_field_view[2]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 3,
-                    resource: wgpu::BindingResource::TextureView(&electric_field_view[0]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 4,
-                    resource: wgpu::BindingResource::TextureView(&electric_field_view[1]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 5,
-                    resource: wgpu::BindingResource::TextureView(&electric_field_view[2]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 6,
-                    resource: wgpu::BindingResource::TextureView(&magnetic_constants_map),
-                },
-            ],
-        });
-
-        let update_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: None,
-                bind_group_layouts: &[&field_bind_group_layout],
-                push_constant_ranges: &[wgpu::PushConstantRange {
-                    stages: wgpu::ShaderStages::COMPUTE,
-                    range: 0..16,
-                }],
-            });
-
-        let excite_field_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: None,
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::COMPUTE,
-                        ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::ReadWrite,
-                            format: wgpu::TextureFormat::R32Float,
-                            view_dimension: wgpu::TextureViewDimension::D3,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::COMPUTE,
-                        ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::ReadWrite,
-                            format: wgpu::TextureFormat::R32Float,
-                            view_dimension: wgpu::TextureViewDimension::D3,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStages::COMPUTE,
-                        ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::ReadWrite,
-                            format: wgpu::TextureFormat::R32Float,
-                            view_dimension: wgpu::TextureViewDimension::D3,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 3,
+                        binding: 1,
                         visibility: wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::StorageTexture {
                             access: wgpu::StorageTextureAccess::ReadOnly,
@@ -394,18 +1128,10 @@ impl FDTD {
                 entries: &[
                     wgpu::BindGroupEntry {
                         binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&electric_field_view[0]),
+                        resource: wgpu::BindingResource::TextureView(&electric_field_view),
                     },
                     wgpu::BindGroupEntry {
                         binding: 1,
-                        resource: wgpu::BindingResource::TextureView(&electric_field_view[1]),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: wgpu::BindingResource::TextureView(&electric_field_view[2]),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 3,
                         resource: wgpu::BindingResource::TextureView(&electric_constants_map),
                     },
                 ],
@@ -418,18 +1144,10 @@ impl FDTD {
                 entries: &[
                     wgpu::BindGroupEntry {
                         binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&magnetic_field_view[0]),
+                        resource: wgpu::BindingResource::TextureView(&magnetic_field_view),
                     },
                     wgpu::BindGroupEntry {
                         binding: 1,
-                        resource: wgpu::BindingResource::TextureView(&magnetic_field_view[1]),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: wgpu::BindingResource::TextureView(&magnetic_field_view[2]),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 3,
                         resource: wgpu::BindingResource::TextureView(&magnetic_constants_map),
                     },
                 ],
@@ -438,41 +1156,44 @@ impl FDTD {
         let excite_volume_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: None,
-                bind_group_layouts: &[&excite_field_bind_group_layout],
-                push_constant_ranges: &[wgpu::PushConstantRange {
-                    stages: wgpu::ShaderStages::COMPUTE,
-                    range: 0..44,
-                }],
+                bind_group_layouts: &params.bind_group_layouts(&[&excite_field_bind_group_layout]),
+                push_constant_ranges: &params.push_constant_ranges(
+                    wgpu::ShaderStages::COMPUTE,
+                    std::mem::size_of::<VolumeExcitationParam>() as u32,
+                ),
             });
 
         let excite_mode_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: None,
-                bind_group_layouts: &[
+                bind_group_layouts: &params.bind_group_layouts(&[
                     mode_source_bind_group_layout,
                     &excite_field_bind_group_layout,
-                ],
-                push_constant_ranges: &[wgpu::PushConstantRange {
-                    stages: wgpu::ShaderStages::COMPUTE,
-                    range: 0..28,
-                }],
+                ]),
+                push_constant_ranges: &params.push_constant_ranges(
+                    wgpu::ShaderStages::COMPUTE,
+                    std::mem::size_of::<ModeExcitationParam>() as u32,
+                ),
             });
 
-        // naive preprocess
-        let macro_replaced = std::fs::read_to_string(
-            std::env::current_dir()?
-                .join("shader")
-                .join("fdtd")
-                .join("fdtd-3d.wgsl"),
-        )?
-        .replace("WORKGROUP_X", workgroup_dispatch.x.to_string().as_str())
-        .replace("WORKGROUP_Y", workgroup_dispatch.y.to_string().as_str())
-        .replace("WORKGROUP_Z", workgroup_dispatch.z.to_string().as_str());
-
-        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("FDTD Shader"),
-            source: wgpu::ShaderSource::Wgsl(macro_replaced.into()),
-        });
+        // naive preprocess, the entry points are appended to the shared update code
+        let update_source = shaders.builtin("fdtd/update.wgsl")?;
+        let preprocess = |source: String| {
+            source
+                .replace("WORKGROUP_X", workgroup_dispatch.x.to_string().as_str())
+                .replace("WORKGROUP_Y", workgroup_dispatch.y.to_string().as_str())
+                .replace("WORKGROUP_Z", workgroup_dispatch.z.to_string().as_str())
+        };
+        let macro_replaced =
+            preprocess(update_source.clone() + &shaders.builtin("fdtd/fdtd-3d.wgsl")?);
+
+        let shader_module = device.create_shader_module(params.shader(
+            wgpu::ShaderModuleDescriptor {
+                label: Some("FDTD Shader"),
+                source: wgpu::ShaderSource::Wgsl(macro_replaced.into()),
+            },
+            1,
+        ));
 
         let update_magnetic_field_pipeline =
             device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
@@ -490,39 +1211,88 @@ impl FDTD {
                 entry_point: "update_electric_field",
             });
 
-        let volume_excitation_shader_module =
-            device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: Some("FDTD Volume Excitation Shader"),
-                source: wgpu::ShaderSource::Wgsl(
-                    std::fs::read_to_string(
-                        std::env::current_dir()?
-                            .join("shader")
-                            .join("fdtd")
-                            .join("excitation-volume.wgsl"),
-                    )?
-                    .replace("WORKGROUP_X", workgroup_dispatch.x.to_string().as_str())
-                    .replace("WORKGROUP_Y", workgroup_dispatch.y.to_string().as_str())
-                    .replace("WORKGROUP_Z", workgroup_dispatch.z.to_string().as_str())
-                    .into(),
+        // the tiled update stages a (workgroup + 1)^3 block of vec3<f32>, 16 bytes each
+        let halo = [
+            workgroup_dispatch.x + 1,
+            workgroup_dispatch.y + 1,
+            workgroup_dispatch.z + 1,
+        ];
+        let halo_volume = halo.iter().product::<u32>();
+        let tile_fits = halo_volume * 16 <= device.limits().max_compute_workgroup_storage_size;
+        if workgroup_dispatch.tiled && !tile_fits {
+            anyhow::bail!(
+                "Tiled update needs {} bytes of workgroup memory, the device allows {}",
+                halo_volume * 16,
+                device.limits().max_compute_workgroup_storage_size
+            );
+        }
+        let use_tiled_update = workgroup_dispatch.tiled;
+        let tiled_update_pipelines = if tile_fits {
+            let tiled_shader_module = device.create_shader_module(
+                params.shader(
+                    wgpu::ShaderModuleDescriptor {
+                        label: Some("FDTD Tiled Shader"),
+                        source: wgpu::ShaderSource::Wgsl(
+                            preprocess(
+                                update_source + &shaders.builtin("fdtd/fdtd-3d-tiled.wgsl")?,
+                            )
+                            .replace("HALO_VOLUME", halo_volume.to_string().as_str())
+                            .replace("HALO_X", halo[0].to_string().as_str())
+                            .replace("HALO_Y", halo[1].to_string().as_str())
+                            .replace("HALO_Z", halo[2].to_string().as_str())
+                            .into(),
+                        ),
+                    },
+                    1,
                 ),
-            });
+            );
+            Some(
+                ["update_magnetic_field", "update_electric_field"].map(|entry_point| {
+                    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                        label: None,
+                        layout: Some(&update_pipeline_layout),
+                        module: &tiled_shader_module,
+                        entry_point,
+                    })
+                }),
+            )
+        } else {
+            None
+        };
 
-        let mode_excitation_shader_module =
-            device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: Some("FDTD Mode Excitation Shader"),
-                source: wgpu::ShaderSource::Wgsl(
-                    std::fs::read_to_string(
-                        std::env::current_dir()?
-                            .join("shader")
-                            .join("fdtd")
-                            .join("excitation-mode.wgsl"),
-                    )?
-                    .replace("WORKGROUP_X", workgroup_dispatch.x.to_string().as_str())
-                    .replace("WORKGROUP_Y", workgroup_dispatch.y.to_string().as_str())
-                    .replace("WORKGROUP_Z", workgroup_dispatch.z.to_string().as_str())
-                    .into(),
-                ),
-            });
+        let volume_excitation_shader_module = device.create_shader_module(
+            params.shader(
+                wgpu::ShaderModuleDescriptor {
+                    label: Some("FDTD Volume Excitation Shader"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        shaders
+                            .builtin("fdtd/excitation-volume.wgsl")?
+                            .replace("WORKGROUP_X", workgroup_dispatch.x.to_string().as_str())
+                            .replace("WORKGROUP_Y", workgroup_dispatch.y.to_string().as_str())
+                            .replace("WORKGROUP_Z", workgroup_dispatch.z.to_string().as_str())
+                            .into(),
+                    ),
+                },
+                1,
+            ),
+        );
+
+        let mode_excitation_shader_module = device.create_shader_module(
+            params.shader(
+                wgpu::ShaderModuleDescriptor {
+                    label: Some("FDTD Mode Excitation Shader"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        shaders
+                            .builtin("fdtd/excitation-mode.wgsl")?
+                            .replace("WORKGROUP_X", workgroup_dispatch.x.to_string().as_str())
+                            .replace("WORKGROUP_Y", workgroup_dispatch.y.to_string().as_str())
+                            .replace("WORKGROUP_Z", workgroup_dispatch.z.to_string().as_str())
+                            .into(),
+                    ),
+                },
+                2,
+            ),
+        );
 
         let excite_field_volume_pipeline =
             device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
@@ -540,6 +1310,123 @@ impl FDTD {
                 entry_point: "excite_field_mode",
             });
 
+        let intensity = settings
+            .intensity
+            .as_ref()
+            .map(|intensity| {
+                IntensityAverage::new(
+                    device,
+                    &params,
+                    shaders,
+                    &electric_field_view,
+                    [grid_x, grid_y, grid_z],
+                    [
+                        workgroup_dispatch.x,
+                        workgroup_dispatch.y,
+                        workgroup_dispatch.z,
+                    ],
+                    dt,
+                    intensity.time_constant,
+                )
+            })
+            .transpose()?;
+        let padding = boundary.padding();
+        let energy = settings
+            .energy
+            .is_some()
+            .then(|| {
+                EnergySum::new(
+                    device,
+                    &params,
+                    shaders,
+                    &electric_field_view,
+                    &magnetic_field_view,
+                    &electric_constants_map,
+                    &magnetic_constants_map,
+                    [0, 1, 2].map(|axis| {
+                        [
+                            padding[axis][0],
+                            [grid_x, grid_y, grid_z][axis] - padding[axis][1],
+                        ]
+                    }),
+                    [
+                        workgroup_dispatch.x,
+                        workgroup_dispatch.y,
+                        workgroup_dispatch.z,
+                    ],
+                    dt,
+                    dx,
+                )
+            })
+            .transpose()?;
+        let surface_transform = |(surface, frequencies)| {
+            SurfaceTransform::new(
+                device,
+                &params,
+                shaders,
+                &electric_field_view,
+                &magnetic_field_view,
+                surface,
+                frequencies,
+                dt,
+            )
+        };
+        let near_field = near_field.map(surface_transform).transpose()?;
+        let mode_monitors = mode_monitors
+            .into_iter()
+            .map(surface_transform)
+            .collect::<anyhow::Result<_>>()?;
+        let flux_surfaces = flux_surfaces
+            .into_iter()
+            .map(|surface| {
+                SurfaceFlux::new(
+                    device,
+                    &params,
+                    shaders,
+                    &electric_field_view,
+                    &magnetic_field_view,
+                    surface,
+                )
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let flux_boxes = flux_boxes
+            .into_iter()
+            .map(|(surface, frequencies): (SurfaceBox, Vec<f32>)| {
+                let faces = (0..6)
+                    .filter(|face| (surface.faces >> face) & 1 == 1)
+                    .map(|face| {
+                        SurfaceFlux::new(
+                            device,
+                            &params,
+                            shaders,
+                            &electric_field_view,
+                            &magnetic_field_view,
+                            SurfaceBox {
+                                faces: 1 << face,
+                                ..surface
+                            },
+                        )
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+                let transform = (!frequencies.is_empty())
+                    .then(|| surface_transform((surface, frequencies)))
+                    .transpose()?;
+                Ok(FluxBox { faces, transform })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let non_finite = NonFiniteCount::new(
+            device,
+            shaders,
+            &electric_field_view,
+            &magnetic_field_view,
+            [grid_x, grid_y, grid_z],
+            [
+                workgroup_dispatch.x,
+                workgroup_dispatch.y,
+                workgroup_dispatch.z,
+            ],
+        )?;
+
         let visualization = render_format
             .map::<anyhow::Result<VisualizeComponent>, _>(|render_format| {
                 let rect = [
@@ -594,15 +1481,12 @@ impl FDTD {
                             wgpu::BindGroupLayoutEntry {
                                 binding: 1,
                                 visibility: wgpu::ShaderStages::FRAGMENT,
-                                ty: wgpu::BindingType::Texture {
-                                    sample_type: wgpu::TextureSampleType::Float {
-                                        filterable: false,
-                                    },
-                                    view_dimension: wgpu::TextureViewDimension::D3,
-                                    multisampled: false,
-                                },
+                                ty: wgpu::BindingType::Sampler(
+                                    wgpu::SamplerBindingType::NonFiltering,
+                                ),
                                 count: None,
                             },
+                            // permittivity, for the material boundary overlay
                             wgpu::BindGroupLayoutEntry {
                                 binding: 2,
                                 visibility: wgpu::ShaderStages::FRAGMENT,
@@ -615,14 +1499,6 @@ impl FDTD {
                                 },
                                 count: None,
                             },
-                            wgpu::BindGroupLayoutEntry {
-                                binding: 3,
-                                visibility: wgpu::ShaderStages::FRAGMENT,
-                                ty: wgpu::BindingType::Sampler(
-                                    wgpu::SamplerBindingType::NonFiltering,
-                                ),
-                                count: None,
-                            },
                         ],
                     });
 
@@ -633,122 +1509,190 @@ impl FDTD {
                         entries: &[
                             wgpu::BindGroupEntry {
                                 binding: 0,
-                                resource: wgpu::BindingResource::TextureView(
-                                    &electric_field_view[0],
-                                ),
+                                resource: wgpu::BindingResource::TextureView(&electric_field_view),
                             },
                             wgpu::BindGroupEntry {
                                 binding: 1,
-                                resource: wgpu::BindingResource::TextureView(
-                                    &electric_field_view[1],
+                                resource: wgpu::BindingResource::Sampler(
+                                    &device.create_sampler(&wgpu::SamplerDescriptor::default()),
                                 ),
                             },
                             wgpu::BindGroupEntry {
                                 binding: 2,
-                                resource: wgpu::BindingResource::TextureView(
-                                    &electric_field_view[2],
-                                ),
+                                resource: wgpu::BindingResource::TextureView(&permittivity_map),
+                            },
+                        ],
+                    });
+
+                let magnetic_field_render_bind_group =
+                    device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: None,
+                        layout: &field_render_bind_group_layout,
+                        entries: &[
+                            wgpu::BindGroupEntry {
+                                binding: 0,
+                                resource: wgpu::BindingResource::TextureView(&magnetic_field_view),
                             },
                             wgpu::BindGroupEntry {
-                                binding: 3,
+                                binding: 1,
                                 resource: wgpu::BindingResource::Sampler(
                                     &device.create_sampler(&wgpu::SamplerDescriptor::default()),
                                 ),
                             },
+                            wgpu::BindGroupEntry {
+                                binding: 2,
+                                resource: wgpu::BindingResource::TextureView(&permittivity_map),
+                            },
                         ],
                     });
 
-                let magnetic_field_render_bind_group =
+                // an R32Float texture loads as (value, 0, 0, 1), so permittivity shows as the x component
+                // in both the norm and the component shaders
+                let material_render_bind_group =
                     device.create_bind_group(&wgpu::BindGroupDescriptor {
                         label: None,
                         layout: &field_render_bind_group_layout,
                         entries: &[
                             wgpu::BindGroupEntry {
                                 binding: 0,
-                                resource: wgpu::BindingResource::TextureView(
-                                    &magnetic_field_view[0],
-                                ),
+                                resource: wgpu::BindingResource::TextureView(&permittivity_map),
                             },
                             wgpu::BindGroupEntry {
                                 binding: 1,
-                                resource: wgpu::BindingResource::TextureView(
-                                    &magnetic_field_view[1],
+                                resource: wgpu::BindingResource::Sampler(
+                                    &device.create_sampler(&wgpu::SamplerDescriptor::default()),
                                 ),
                             },
                             wgpu::BindGroupEntry {
                                 binding: 2,
+                                resource: wgpu::BindingResource::TextureView(&permittivity_map),
+                            },
+                        ],
+                    });
+
+                let intensity_render_bind_group = intensity.as_ref().map(|intensity| {
+                    device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: None,
+                        layout: &field_render_bind_group_layout,
+                        entries: &[
+                            wgpu::BindGroupEntry {
+                                binding: 0,
                                 resource: wgpu::BindingResource::TextureView(
-                                    &magnetic_field_view[2],
+                                    &intensity
+                                        .texture()
+                                        .create_view(&wgpu::TextureViewDescriptor::default()),
                                 ),
                             },
                             wgpu::BindGroupEntry {
-                                binding: 3,
+                                binding: 1,
                                 resource: wgpu::BindingResource::Sampler(
                                     &device.create_sampler(&wgpu::SamplerDescriptor::default()),
                                 ),
                             },
+                            wgpu::BindGroupEntry {
+                                binding: 2,
+                                resource: wgpu::BindingResource::TextureView(&permittivity_map),
+                            },
                         ],
-                    });
+                    })
+                });
 
                 let render_pipeline_layout =
                     device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                         label: None,
-                        bind_group_layouts: &[&field_render_bind_group_layout],
-                        push_constant_ranges: &[{
-                            wgpu::PushConstantRange {
-                                stages: wgpu::ShaderStages::FRAGMENT,
-                                range: 0..12,
-                            }
-                        }],
+                        bind_group_layouts: &params
+                            .bind_group_layouts(&[&field_render_bind_group_layout]),
+                        push_constant_ranges: &params
+                            .push_constant_ranges(wgpu::ShaderStages::VERTEX_FRAGMENT, 48),
                     });
 
-                let vertex_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                    label: Some(default_shader),
-                    source: wgpu::ShaderSource::Wgsl(
-                        std::fs::read_to_string(
-                            std::env::current_dir()?.join("shader").join("vertex.wgsl"),
-                        )?
-                        .into(),
-                    ),
-                });
+                let vertex_shader = device.create_shader_module(params.shader(
+                    wgpu::ShaderModuleDescriptor {
+                        label: Some("vertex.wgsl"),
+                        source: wgpu::ShaderSource::Wgsl(shaders.builtin("vertex.wgsl")?.into()),
+                    },
+                    1,
+                ));
+                let display_scale = shaders.builtin("display_scale.wgsl")?;
 
-                let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                    label: Some(default_shader),
-                    source: wgpu::ShaderSource::Wgsl(
-                        std::fs::read_to_string(default_shader)?.into(),
-                    ),
+                // a broken custom shader falls back to the colormap instead of failing the run
+                let custom_pipeline = settings.default_shader.as_deref().map(|path| {
+                    shaders
+                        .read(path)
+                        .and_then(|source| {
+                            VisualizeComponent::try_create_render_pipeline(
+                                device,
+                                &render_pipeline_layout,
+                                &vertex_shader,
+                                params.shader(
+                                    wgpu::ShaderModuleDescriptor {
+                                        label: Some(path),
+                                        source: wgpu::ShaderSource::Wgsl(
+                                            (display_scale.clone() + &source).into(),
+                                        ),
+                                    },
+                                    1,
+                                ),
+                                render_format,
+                            )
+                        })
+                        .map_err(|err| format!("Shader {} failed to load: {}", path, err))
                 });
+                if let Some(Err(err)) = &custom_pipeline {
+                    eprintln!("{}, using the {:?} colormap", err, settings.colormap);
+                }
+                let (render_pipeline, colormap, shader_error) = match custom_pipeline {
+                    Some(Ok(render_pipeline)) => (render_pipeline, None, None),
+                    custom_pipeline => (
+                        VisualizeComponent::create_render_pipeline(
+                            device,
+                            &render_pipeline_layout,
+                            &vertex_shader,
+                            &device.create_shader_module(
+                                params.shader(settings.colormap.shader(&display_scale), 1),
+                            ),
+                            render_format,
+                        ),
+                        Some(settings.colormap),
+                        custom_pipeline.and_then(Result::err),
+                    ),
+                };
 
-                let render_pipeline =
-                    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                        label: None,
-                        layout: Some(&render_pipeline_layout),
-                        vertex: wgpu::VertexState {
-                            module: &vertex_shader,
-                            entry_point: "vs_main",
-                            buffers: &[wgpu::VertexBufferLayout {
-                                array_stride: std::mem::size_of::<crate::Vertex>() as _,
-                                step_mode: wgpu::VertexStepMode::Vertex,
-                                attributes: &wgpu::vertex_attr_array![
-                                    0 => Float32x2,
-                                    1 => Float32x2
-                                ],
-                            }],
+                let volume_shader = device.create_shader_module(params.shader(
+                    wgpu::ShaderModuleDescriptor {
+                        label: Some("volume.wgsl"),
+                        source: wgpu::ShaderSource::Wgsl(
+                            (display_scale.clone() + &shaders.builtin("volume.wgsl")?).into(),
+                        ),
+                    },
+                    1,
+                ));
+                let volume_pipeline = VisualizeComponent::create_render_pipeline(
+                    device,
+                    &render_pipeline_layout,
+                    &volume_shader,
+                    &volume_shader,
+                    render_format,
+                );
+
+                let line_pipeline = if dimension_mode == DimensionMode::OneD {
+                    let line_shader = device.create_shader_module(params.shader(
+                        wgpu::ShaderModuleDescriptor {
+                            label: Some("line.wgsl"),
+                            source: wgpu::ShaderSource::Wgsl(shaders.builtin("line.wgsl")?.into()),
                         },
-                        primitive: wgpu::PrimitiveState::default(),
-                        depth_stencil: None,
-                        multisample: wgpu::MultisampleState::default(),
-                        fragment: Some(wgpu::FragmentState {
-                            module: &shader_module,
-                            entry_point: "fs_main",
-                            targets: &[Some(wgpu::ColorTargetState {
-                                format: render_format,
-                                blend: None,
-                                write_mask: wgpu::ColorWrites::ALL,
-                            })],
-                        }),
-                        multiview: None,
-                    });
+                        1,
+                    ));
+                    Some(VisualizeComponent::create_render_pipeline(
+                        device,
+                        &render_pipeline_layout,
+                        &vertex_shader,
+                        &line_shader,
+                        render_format,
+                    ))
+                } else {
+                    None
+                };
 
                 Ok(VisualizeComponent {
                     vertex_shader,
@@ -756,53 +1700,132 @@ impl FDTD {
                     rect_vertices,
                     electric_field_render_bind_group,
                     magnetic_field_render_bind_group,
+                    material_render_bind_group,
+                    intensity_render_bind_group,
                     render_pipeline,
+                    volume_pipeline,
+                    line_pipeline,
+                    colormap,
+                    shader_error,
+                    display_scale,
                 })
             })
             .transpose()?;
 
-        let shift_vector = -nalgebra::vector![
-            dimension[0][0] + (step_x - step_x.floor()) * dx * 0.5
-                - boundary.get_extra_grid_extent() as f32 * dx * 0.5,
-            dimension[1][0] + (step_y - step_y.floor()) * dx * 0.5
-                - boundary.get_extra_grid_extent() as f32 * dx * 0.5,
-            dimension[2][0] + (step_z - step_z.floor()) * dx * 0.5
-                - boundary.get_extra_grid_extent() as f32 * dx * 0.5
-        ];
+        let shift_vector = grid_shift(dimension, dx, boundary.padding());
 
         let grid_dimension = [grid_x, grid_y, grid_z];
-        let simulation_dimension = [
-            grid_x - boundary.get_extra_grid_extent(),
-            grid_y - boundary.get_extra_grid_extent(),
-            grid_z - boundary.get_extra_grid_extent(),
-        ];
+        let extra = boundary.get_extra_grid_extent();
+        let simulation_dimension = [0, 1, 2].map(|axis| grid_dimension[axis] - extra[axis]);
 
-        let pml = match boundary {
-            BoundaryCondition::PML {
-                sigma,
-                alpha,
-                cells,
-            } => Some(PMLBoundary::new(
-                &device,
-                cells,
-                alpha,
-                sigma,
-                dt,
-                &electric_field_view,
-                &magnetic_field_view,
-                &electric_constants_map,
-                &magnetic_constants_map,
-                simulation_dimension,
-                pml_constants.unwrap(),
-            )),
-            BoundaryCondition::PEC | BoundaryCondition::PMC => None,
+        let bloch_k = boundary.bloch_k();
+        let bloch_phase = [0, 1, 2].map(|axis| bloch_k[axis] * grid_dimension[axis] as f32 * dx);
+        let pml_descriptor = match (boundary, boundary.pml_faces()) {
+            (
+                BoundaryCondition::PML {
+                    sigma,
+                    alpha,
+                    cells,
+                    ..
+                },
+                Some(faces),
+            ) => {
+                let (electric_psi_constants, magnetic_psi_constants) =
+                    pml_constants.as_ref().unwrap();
+                Some(PMLDescriptor {
+                    cells,
+                    faces,
+                    pmc_faces: boundary.pmc_faces(),
+                    periodic: boundary.periodic_axes(),
+                    bloch_phase,
+                    alpha,
+                    sigma,
+                    dt,
+                    electric_constant_map: &electric_constants_map,
+                    magnetic_constant_map: &magnetic_constants_map,
+                    simulation_dimension,
+                    electric_psi_constants,
+                    magnetic_psi_constants,
+                    field_bind_group_layout: &field_bind_group_layout,
+                    workgroup: [
+                        workgroup_dispatch.x,
+                        workgroup_dispatch.y,
+                        workgroup_dispatch.z,
+                    ],
+                    params: &params,
+                })
+            }
+            _ => None,
+        };
+        let create_pml = |electric_field_view: &wgpu::TextureView,
+                          magnetic_field_view: &wgpu::TextureView,
+                          partner_sign: f32| {
+            pml_descriptor.map(|descriptor| {
+                PMLBoundary::new(
+                    device,
+                    descriptor,
+                    electric_field_view,
+                    magnetic_field_view,
+                    partner_sign,
+                )
+            })
         };
+        let pml = create_pml(&electric_field_view, &magnetic_field_view, -1.0);
+
+        // the same update for the imaginary half, with the real one as its partner
+        let imaginary = imaginary_field_textures.map(
+            |[(electric_field_texture, electric_view), (magnetic_field_texture, magnetic_view)]| {
+                let create_excitation_bind_group = |view: &wgpu::TextureView, constants_map| {
+                    device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: None,
+                        layout: &excite_field_bind_group_layout,
+                        entries: &[
+                            wgpu::BindGroupEntry {
+                                binding: 0,
+                                resource: wgpu::BindingResource::TextureView(view),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 1,
+                                resource: wgpu::BindingResource::TextureView(constants_map),
+                            },
+                        ],
+                    })
+                };
+                ImaginaryFields {
+                    electric_field_bind_group: create_field_bind_group([
+                        &electric_view,
+                        &magnetic_view,
+                        &electric_constants_map,
+                        &magnetic_field_view,
+                    ]),
+                    magnetic_field_bind_group: create_field_bind_group([
+                        &magnetic_view,
+                        &electric_view,
+                        &magnetic_constants_map,
+                        &electric_field_view,
+                    ]),
+                    electric_field_excitation_bind_group: create_excitation_bind_group(
+                        &electric_view,
+                        &electric_constants_map,
+                    ),
+                    magnetic_field_excitation_bind_group: create_excitation_bind_group(
+                        &magnetic_view,
+                        &magnetic_constants_map,
+                    ),
+                    pml: create_pml(&electric_view, &magnetic_view, 1.0),
+                    electric_field_texture,
+                    magnetic_field_texture,
+                }
+            },
+        );
 
         Ok(Self {
             electric_field_bind_group,
             magnetic_field_bind_group,
             update_magnetic_field_pipeline,
             update_electric_field_pipeline,
+            tiled_update_pipelines,
+            use_tiled_update,
             grid_dimension,
             shift_vector,
             spatial_step: dx,
@@ -822,11 +1845,38 @@ impl FDTD {
                 / dx,
             slice_mode: default_slice.mode,
             field_view_mode: default_slice.field,
-            scaling_factor: default_scaling_factor,
+            material_view: false,
+            intensity_view: false,
+            scaling_factor: settings.default_scaling_factor,
+            log_scale: false,
+            log_floor: settings.default_log_floor.min(-1.0),
+            display_component: DisplayComponent::default(),
+            stretch: default_slice.stretch,
+            zoom: 1.0,
+            pan: [0.0, 0.0],
+            material_overlay: false,
+            volume_view: false,
+            orbit: DEFAULT_ORBIT,
+            distance: DEFAULT_DISTANCE,
             electric_field_texture,
             magnetic_field_texture,
+            permittivity_texture,
             boundary,
+            dimension_mode,
             pml,
+            imaginary,
+            bloch_phase,
+            bloch_step: bloch_k.map(|k| k * dx),
+            intensity,
+            energy,
+            near_field,
+            mode_monitors,
+            flux_surfaces,
+            flux_boxes,
+            non_finite,
+            params,
+            profiler,
+            clear_texture_supported: device.features().contains(wgpu::Features::CLEAR_TEXTURE),
             temporal_step: dt,
             workgroup_dispatch,
             visualization,
@@ -836,134 +1886,619 @@ impl FDTD {
         })
     }
 
-    pub fn update_magnetic_field(&self, encoder: &mut wgpu::CommandEncoder) {
-        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
-        if let BoundaryCondition::PML { .. } = self.boundary {
-            let pml = self.pml.as_ref().unwrap();
-            pml.update_magnetic_field(&mut cpass);
-        }
-        cpass.set_pipeline(&self.update_magnetic_field_pipeline);
-        cpass.set_bind_group(0, &self.magnetic_field_bind_group, &[]);
-        cpass.set_push_constants(0, bytemuck::cast_slice(&self.grid_dimension));
-        cpass.set_push_constants(12, bytemuck::cast_slice(&[self.boundary.use_pmc()]));
-        cpass.dispatch_workgroups(
-            (self.grid_dimension[0] as f32 / self.workgroup_dispatch.x as f32).ceil() as u32,
-            (self.grid_dimension[1] as f32 / self.workgroup_dispatch.y as f32).ceil() as u32,
-            (self.grid_dimension[2] as f32 / self.workgroup_dispatch.z as f32).ceil() as u32,
-        );
+    /// Must be called before recording a frame, once everything recorded earlier was submitted.
+    pub fn begin_frame(&self) {
+        self.params.reset();
     }
 
-    pub fn excite_magnetic_field_volume(
-        &self,
-        encoder: &mut wgpu::CommandEncoder,
-        position: [u32; 3],
-        size: [u32; 3],
-        strength: [f32; 3],
-    ) {
-        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
-        cpass.set_pipeline(&self.excite_field_volume_pipeline);
-        cpass.set_bind_group(0, &self.magnetic_field_excitation_bind_group, &[]);
-        cpass.set_push_constants(0, bytemuck::cast_slice(&size));
-        cpass.set_push_constants(16, bytemuck::cast_slice(&strength));
-        cpass.set_push_constants(32, bytemuck::cast_slice(&position));
-        cpass.dispatch_workgroups(
-            (size[0] as f32 / self.workgroup_dispatch.x as f32).ceil() as u32,
-            (size[1] as f32 / self.workgroup_dispatch.y as f32).ceil() as u32,
-            (size[2] as f32 / self.workgroup_dispatch.z as f32).ceil() as u32,
-        );
+    pub fn is_profiling(&self) -> bool {
+        self.profiler.is_some()
     }
 
-    pub fn excite_magnetic_field_mode(
-        &self,
-        encoder: &mut wgpu::CommandEncoder,
-        position: [u32; 3],
-        (sin_t, cos_t): (f32, f32),
-        envelope: f32,
-        mode_bind_group: &wgpu::BindGroup,
-    ) {
-        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
-        cpass.set_pipeline(&self.excite_field_mode_pipeline);
-        cpass.set_bind_group(0, mode_bind_group, &[]);
-        cpass.set_bind_group(1, &self.magnetic_field_excitation_bind_group, &[]);
-        cpass.set_push_constants(0, bytemuck::cast_slice(&position));
-        cpass.set_push_constants(
-            12,
-            bytemuck::cast_slice(&[cos_t, sin_t, envelope, self.temporal_step]),
+    /// Samples the GPU time of the passes recorded until `end_profile` when a sample is due.
+    pub fn begin_profile(&self) {
+        if let Some(profiler) = &self.profiler {
+            profiler.begin();
+        }
+    }
+
+    /// Resolves the timestamps of the last `steps` steps into `encoder`,
+    /// `map_profile` must be called once it was submitted.
+    pub fn end_profile(&self, encoder: &mut wgpu::CommandEncoder, steps: u32) {
+        if let Some(profiler) = &self.profiler {
+            profiler.resolve(encoder, steps);
+        }
+    }
+
+    pub fn map_profile(&self) {
+        if let Some(profiler) = &self.profiler {
+            profiler.map();
+        }
+    }
+
+    /// Per-step GPU time of the last finished sample, if one arrived since the previous call.
+    pub fn collect_profile(&self, device: &wgpu::Device) -> Option<PassTimes> {
+        self.profiler.as_ref()?.collect(device)
+    }
+
+    fn begin_compute_pass<'a>(
+        &self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        class: PassClass,
+    ) -> wgpu::ComputePass<'a> {
+        encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: None,
+            timestamp_writes: self
+                .profiler
+                .as_ref()
+                .and_then(|profiler| profiler.timestamp_writes(class)),
+        })
+    }
+
+    /// Magnetic and electric interior update pipelines, tiled ones only if they were built.
+    fn interior_pipelines(&self, tiled: bool) -> [&wgpu::ComputePipeline; 2] {
+        match (&self.tiled_update_pipelines, tiled) {
+            (Some([magnetic, electric]), true) => [magnetic, electric],
+            _ => [
+                &self.update_magnetic_field_pipeline,
+                &self.update_electric_field_pipeline,
+            ],
+        }
+    }
+
+    pub fn uses_tiled_update(&self) -> bool {
+        self.use_tiled_update
+    }
+
+    /// Axes that wrap onto themselves, those of the boundary plus the ones the dimension mode
+    /// collapses whatever the boundary type, so no wall sits on the single cell.
+    fn periodic_axes(&self) -> u32 {
+        self.dimension_mode
+            .collapsed_axes()
+            .iter()
+            .fold(self.boundary.periodic_axes(), |mask, axis| mask | 1 << axis)
+    }
+
+    pub fn dimension_mode(&self) -> DimensionMode {
+        self.dimension_mode
+    }
+
+    /// World coordinate along `axis` of the center of the cells with index `index` on it.
+    pub fn cell_center(&self, axis: usize, index: u32) -> f32 {
+        (index as f32 + 0.5) * self.spatial_step - self.shift_vector[axis]
+    }
+
+    /// Runs one H and one E interior update with the plain and with the tiled kernels, both from the
+    /// same pseudo-random fields, and returns the largest absolute difference between the results.
+    /// Overwrites the simulation state.
+    pub fn compare_tiled_update(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<f32> {
+        if self.tiled_update_pipelines.is_none() {
+            anyhow::bail!(
+                "The tiled update does not fit in workgroup memory with this workgroup size"
+            );
+        }
+        let size = self.electric_field_texture.size();
+        let texels = (size.width * size.height * size.depth_or_array_layers) as usize;
+        // xorshift32, reproducible without pulling in a random number crate
+        let mut state = 0x9e3779b9u32;
+        let mut random_field = || {
+            (0..texels * 4)
+                .map(|i| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    // the unused channel stays zero like in the simulation
+                    if i % 4 == 3 {
+                        0.0
+                    } else {
+                        state as f32 / u32::MAX as f32 * 2.0 - 1.0
+                    }
+                })
+                .collect::<Vec<f32>>()
+        };
+        let initial = [random_field(), random_field()];
+
+        let mut results = vec![];
+        for tiled in [false, true] {
+            for (texture, data) in [&self.electric_field_texture, &self.magnetic_field_texture]
+                .into_iter()
+                .zip(&initial)
+            {
+                queue.write_texture(
+                    texture.as_image_copy(),
+                    bytemuck::cast_slice(data),
+                    wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(size.width * 16),
+                        rows_per_image: Some(size.height),
+                    },
+                    size,
+                );
+            }
+            let [magnetic, electric] = self.interior_pipelines(tiled);
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            {
+                let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+                self.update_interior(&mut cpass, magnetic, &self.magnetic_field_bind_group, -1.0);
+            }
+            {
+                let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+                self.update_interior(&mut cpass, electric, &self.electric_field_bind_group, -1.0);
+            }
+            queue.submit(Some(encoder.finish()));
+            self.params.reset();
+            results.push([
+                read_texture(device, queue, &self.electric_field_texture)?,
+                read_texture(device, queue, &self.magnetic_field_texture)?,
+            ]);
+        }
+
+        Ok(results[0]
+            .iter()
+            .flatten()
+            .zip(results[1].iter().flatten())
+            .map(|(plain, tiled)| (plain - tiled).abs())
+            .fold(0.0, f32::max))
+    }
+
+    /// The real half of the fields, then the imaginary one with a Bloch wave vector. Both are
+    /// updated before the fields they drive.
+    fn halves(&self) -> Vec<FieldHalf<'_>> {
+        let real = FieldHalf {
+            electric_field_bind_group: &self.electric_field_bind_group,
+            magnetic_field_bind_group: &self.magnetic_field_bind_group,
+            electric_field_excitation_bind_group: &self.electric_field_excitation_bind_group,
+            magnetic_field_excitation_bind_group: &self.magnetic_field_excitation_bind_group,
+            pml: self.pml.as_ref(),
+            partner_sign: -1.0,
+            phase_offset: 0.0,
+        };
+        let imaginary = self.imaginary.as_ref().map(|imaginary| FieldHalf {
+            electric_field_bind_group: &imaginary.electric_field_bind_group,
+            magnetic_field_bind_group: &imaginary.magnetic_field_bind_group,
+            electric_field_excitation_bind_group: &imaginary.electric_field_excitation_bind_group,
+            magnetic_field_excitation_bind_group: &imaginary.magnetic_field_excitation_bind_group,
+            pml: imaginary.pml.as_ref(),
+            partner_sign: 1.0,
+            phase_offset: -std::f32::consts::FRAC_PI_2,
+        });
+        std::iter::once(real).chain(imaginary).collect()
+    }
+
+    /// Dispatches the Yee update over the cells not covered by PML regions.
+    fn update_interior<'a>(
+        &'a self,
+        cpass: &mut wgpu::ComputePass<'a>,
+        pipeline: &'a wgpu::ComputePipeline,
+        bind_group: &'a wgpu::BindGroup,
+        partner_sign: f32,
+    ) {
+        let padding = self.boundary.padding();
+        let end = [0, 1, 2].map(|axis| self.grid_dimension[axis] - padding[axis][1]);
+        let extent = [0, 1, 2].map(|axis| end[axis] - padding[axis][0]);
+        cpass.set_pipeline(pipeline);
+        cpass.set_bind_group(0, bind_group, &[]);
+        self.params.set_compute(
+            cpass,
+            1,
+            bytemuck::bytes_of(&UpdateParam {
+                dimension: self.grid_dimension,
+                pmc_faces: self.boundary.pmc_faces(),
+                offset: padding.map(|[low, _]| low),
+                periodic: self.periodic_axes(),
+                end,
+                partner_sign,
+                bloch_phase: self.bloch_phase,
+                _padding2: 0,
+            }),
         );
         cpass.dispatch_workgroups(
-            ((self.grid_dimension[0] - self.boundary.get_extra_grid_extent()) as f32
-                / self.workgroup_dispatch.x as f32)
-                .ceil() as u32,
-            ((self.grid_dimension[1] - self.boundary.get_extra_grid_extent()) as f32
-                / self.workgroup_dispatch.y as f32)
-                .ceil() as u32,
-            1,
+            (extent[0] as f32 / self.workgroup_dispatch.x as f32).ceil() as u32,
+            (extent[1] as f32 / self.workgroup_dispatch.y as f32).ceil() as u32,
+            (extent[2] as f32 / self.workgroup_dispatch.z as f32).ceil() as u32,
         );
     }
 
-    pub fn update_electric_field(&self, encoder: &mut wgpu::CommandEncoder) {
+    pub fn update_magnetic_field(&self, encoder: &mut wgpu::CommandEncoder) {
+        for half in self.halves() {
+            if let Some(pml) = half.pml {
+                let mut cpass = self.begin_compute_pass(encoder, PassClass::Pml);
+                pml.update_magnetic_field(&mut cpass, &self.params, half.magnetic_field_bind_group);
+            }
+            let mut cpass = self.begin_compute_pass(encoder, PassClass::Update);
+            self.update_interior(
+                &mut cpass,
+                self.interior_pipelines(self.use_tiled_update)[0],
+                half.magnetic_field_bind_group,
+                half.partner_sign,
+            );
+        }
+    }
+
+    /// Adds the real part of the complex `strength` and `quadrature` to the cells, turned by
+    /// the Bloch phase of each cell.
+    pub fn excite_magnetic_field_volume(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        position: [u32; 3],
+        size: [u32; 3],
+        (strength, quadrature): ([f32; 3], [f32; 3]),
+    ) {
+        for half in self.halves() {
+            let mut cpass = self.begin_compute_pass(encoder, PassClass::Excitation);
+            cpass.set_pipeline(&self.excite_field_volume_pipeline);
+            cpass.set_bind_group(0, half.magnetic_field_excitation_bind_group, &[]);
+            self.params.set_compute(
+                &mut cpass,
+                1,
+                bytemuck::bytes_of(&VolumeExcitationParam {
+                    size,
+                    _padding0: 0,
+                    strength,
+                    _padding1: 0,
+                    position,
+                    phase_offset: half.phase_offset,
+                    quadrature,
+                    _padding2: 0,
+                    bloch_step: self.bloch_step,
+                    _padding3: 0,
+                }),
+            );
+            cpass.dispatch_workgroups(
+                (size[0] as f32 / self.workgroup_dispatch.x as f32).ceil() as u32,
+                (size[1] as f32 / self.workgroup_dispatch.y as f32).ceil() as u32,
+                (size[2] as f32 / self.workgroup_dispatch.z as f32).ceil() as u32,
+            );
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn excite_magnetic_field_mode(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        position: [u32; 3],
+        normal: SliceMode,
+        (sin_t, cos_t): (f32, f32),
+        envelope: f32,
+        mode_bind_group: &wgpu::BindGroup,
+    ) {
+        let [u, v] = normal.plane_axes();
+        let interior =
+            |axis: usize| self.grid_dimension[axis] - self.boundary.get_extra_grid_extent()[axis];
+        for half in self.halves() {
+            let mut cpass = self.begin_compute_pass(encoder, PassClass::Excitation);
+            cpass.set_pipeline(&self.excite_field_mode_pipeline);
+            cpass.set_bind_group(0, mode_bind_group, &[]);
+            cpass.set_bind_group(1, half.magnetic_field_excitation_bind_group, &[]);
+            self.params.set_compute(
+                &mut cpass,
+                2,
+                bytemuck::bytes_of(&ModeExcitationParam {
+                    position,
+                    cos_t,
+                    sin_t,
+                    envelope,
+                    dt: self.temporal_step,
+                    normal: normal.axis() as u32,
+                    bloch_step: self.bloch_step,
+                    phase_offset: half.phase_offset,
+                }),
+            );
+            cpass.dispatch_workgroups(
+                (interior(u) as f32 / self.workgroup_dispatch.x as f32).ceil() as u32,
+                (interior(v) as f32 / self.workgroup_dispatch.y as f32).ceil() as u32,
+                1,
+            );
+        }
+    }
+
+    /// Adds the electric field of the step just recorded to the time-averaged intensity.
+    pub fn accumulate_intensity(&self, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(intensity) = &self.intensity {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            intensity.accumulate(&mut cpass, &self.params);
+        }
+    }
+
+    /// Adds the fields after `step` updates to the transforms of the near-field box and the
+    /// mode monitors.
+    pub fn accumulate_surfaces(&self, encoder: &mut wgpu::CommandEncoder, step: u32) {
+        if self.near_field.is_none() && self.mode_monitors.is_empty() && self.flux_boxes.is_empty()
+        {
+            return;
+        }
         let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
-        if let BoundaryCondition::PML { .. } = self.boundary {
-            let pml = self.pml.as_ref().unwrap();
-            pml.update_electric_field(&mut cpass);
-        }
-        cpass.set_pipeline(&self.update_electric_field_pipeline);
-        cpass.set_bind_group(0, &self.electric_field_bind_group, &[]);
-        cpass.set_push_constants(0, bytemuck::cast_slice(&self.grid_dimension));
-        cpass.set_push_constants(12, bytemuck::cast_slice(&[self.boundary.use_pmc()]));
-        cpass.dispatch_workgroups(
-            (self.grid_dimension[0] as f32 / self.workgroup_dispatch.x as f32).ceil() as u32,
-            (self.grid_dimension[1] as f32 / self.workgroup_dispatch.y as f32).ceil() as u32,
-            (self.grid_dimension[2] as f32 / self.workgroup_dispatch.z as f32).ceil() as u32,
-        );
+        let flux_box_transforms = self
+            .flux_boxes
+            .iter()
+            .filter_map(|flux_box| flux_box.transform.as_ref());
+        for surface in self
+            .near_field
+            .iter()
+            .chain(&self.mode_monitors)
+            .chain(flux_box_transforms)
+        {
+            surface.accumulate(&mut cpass, &self.params, step);
+        }
+        for face in self.flux_boxes.iter().flat_map(|flux_box| &flux_box.faces) {
+            face.accumulate(&mut cpass, &self.params);
+        }
+    }
+
+    /// The transforms over the faces of the `index`-th flux box, `None` when it has no
+    /// wavelengths. Blocks until read back.
+    pub fn read_flux_box(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        index: usize,
+    ) -> anyhow::Result<Option<SurfaceFields>> {
+        self.flux_boxes[index]
+            .transform
+            .as_ref()
+            .map(|transform| self.read_surface(device, queue, transform))
+            .transpose()
+    }
+
+    /// Bytes of the partial flux sums `sum_flux` copies, `None` without flux boxes.
+    pub fn flux_sums_size(&self) -> Option<u64> {
+        let size = self
+            .flux_boxes
+            .iter()
+            .flat_map(|flux_box| &flux_box.faces)
+            .map(|face| face.partial_sums().size())
+            .sum();
+        (size > 0).then_some(size)
+    }
+
+    /// Copies the partial sums of the flux through every face of every flux box, as the latest
+    /// step left them, into `buffer` at `offset`, `flux_sums_size` bytes.
+    pub fn sum_flux(&self, encoder: &mut wgpu::CommandEncoder, buffer: &wgpu::Buffer, offset: u64) {
+        let mut offset = offset;
+        for face in self.flux_boxes.iter().flat_map(|flux_box| &flux_box.faces) {
+            let partial_sums = face.partial_sums();
+            encoder.copy_buffer_to_buffer(partial_sums, 0, buffer, offset, partial_sums.size());
+            offset += partial_sums.size();
+        }
+    }
+
+    /// Power leaving each flux box through each face from partial sums copied by `sum_flux`,
+    /// in the order low x, high x, low y, ..., zero for faces that are not recorded.
+    pub fn face_powers(&self, partial_sums: &[f32]) -> Vec<[f64; 6]> {
+        let area = (self.spatial_step * self.spatial_step) as f64;
+        let mut partial_sums = partial_sums;
+        self.flux_boxes
+            .iter()
+            .map(|flux_box| {
+                let mut powers = [0.0; 6];
+                for face in &flux_box.faces {
+                    let (sums, rest) =
+                        partial_sums.split_at(face.partial_sums().size() as usize / 4);
+                    partial_sums = rest;
+                    powers[face.surface().faces.trailing_zeros() as usize] =
+                        sums.iter().map(|sum| *sum as f64).sum::<f64>() * area;
+                }
+                powers
+            })
+            .collect()
+    }
+
+    /// The transforms of the near-field box, `None` unless the preset sets one up. Blocks until
+    /// read back.
+    pub fn read_near_field(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Option<SurfaceFields>> {
+        self.near_field
+            .as_ref()
+            .map(|near_field| self.read_surface(device, queue, near_field))
+            .transpose()
     }
 
+    /// The transforms of the `index`-th mode monitor plane. Blocks until read back.
+    pub fn read_mode_monitor(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        index: usize,
+    ) -> anyhow::Result<SurfaceFields> {
+        self.read_surface(device, queue, &self.mode_monitors[index])
+    }
+
+    fn read_surface(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        surface: &SurfaceTransform,
+    ) -> anyhow::Result<SurfaceFields> {
+        let transforms = read_buffer(device, queue, surface.transforms())?;
+        let cells = surface
+            .surface()
+            .cells()
+            .map(|(axis, high, texel)| SurfaceCell {
+                normal: (axis, if high { 1.0 } else { -1.0 }),
+                texel,
+                position: [0, 1, 2].map(|axis| self.cell_center(axis, texel[axis])),
+            })
+            .collect();
+        Ok(SurfaceFields {
+            cells,
+            frequencies: surface.frequencies().to_vec(),
+            transforms: bytemuck::cast_slice(&transforms).to_vec(),
+            area: self.spatial_step * self.spatial_step,
+            dt: self.temporal_step,
+        })
+    }
+
+    /// Adds the current flux through the `index`-th flux surface to its sums. Unlike the flux
+    /// boxes the flux surfaces are only accumulated on request.
+    pub fn accumulate_flux(&self, encoder: &mut wgpu::CommandEncoder, index: usize) {
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+        self.flux_surfaces[index].accumulate(&mut cpass, &self.params);
+    }
+
+    /// Energy that has left the `index`-th flux surface through each face since the last reset, in
+    /// the order low x, high x, low y, ..., zero for faces that are not recorded. Blocks until
+    /// read back.
+    pub fn read_flux(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        index: usize,
+    ) -> anyhow::Result<[f64; 6]> {
+        let flux = &self.flux_surfaces[index];
+        let sums = read_buffer(device, queue, flux.sums())?;
+        let mut faces = [0.0; 6];
+        for ((axis, high, _), sum) in flux.surface().cells().zip(sums) {
+            faces[2 * axis + high as usize] += sum as f64;
+        }
+        let scale = (self.spatial_step * self.spatial_step * self.temporal_step) as f64;
+        Ok(faces.map(|energy| energy * scale))
+    }
+
+    /// Bytes of the partial energy sums `sum_energy` copies, `None` unless the preset sums
+    /// the energy.
+    pub fn energy_sums_size(&self) -> Option<u64> {
+        self.energy
+            .as_ref()
+            .map(|energy| energy.partial_sums().size())
+    }
+
+    /// Sums the energy of the current fields and copies the partial sums into `buffer` at
+    /// `offset`, `energy_sums_size` bytes. Does nothing unless the preset sums the energy.
+    pub fn sum_energy(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        buffer: &wgpu::Buffer,
+        offset: u64,
+    ) {
+        let Some(energy) = &self.energy else {
+            return;
+        };
+        {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            energy.sum(&mut cpass, &self.params);
+        }
+        let partial_sums = energy.partial_sums();
+        encoder.copy_buffer_to_buffer(partial_sums, 0, buffer, offset, partial_sums.size());
+    }
+
+    /// The total energy from partial sums copied by `sum_energy`.
+    pub fn total_energy(&self, partial_sums: &[f32]) -> f64 {
+        self.energy
+            .as_ref()
+            .map_or(0.0, |energy| energy.total(partial_sums))
+    }
+
+    /// Counts the cells holding an infinite or NaN field component and copies the count, a u32,
+    /// into `buffer` at `offset`.
+    pub fn count_non_finite(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        buffer: &wgpu::Buffer,
+        offset: u64,
+    ) {
+        self.non_finite.count(encoder);
+        encoder.copy_buffer_to_buffer(self.non_finite.counter(), 0, buffer, offset, 4);
+    }
+
+    pub fn update_electric_field(&self, encoder: &mut wgpu::CommandEncoder) {
+        for half in self.halves() {
+            if let Some(pml) = half.pml {
+                let mut cpass = self.begin_compute_pass(encoder, PassClass::Pml);
+                pml.update_electric_field(&mut cpass, &self.params, half.electric_field_bind_group);
+            }
+            let mut cpass = self.begin_compute_pass(encoder, PassClass::Update);
+            self.update_interior(
+                &mut cpass,
+                self.interior_pipelines(self.use_tiled_update)[1],
+                half.electric_field_bind_group,
+                half.partner_sign,
+            );
+        }
+    }
+
+    /// Adds the real part of the complex `strength` and `quadrature` to the cells, turned by
+    /// the Bloch phase of each cell.
     pub fn excite_electric_field_volume(
         &self,
         encoder: &mut wgpu::CommandEncoder,
         position: [u32; 3],
         size: [u32; 3],
-        strength: [f32; 3],
+        (strength, quadrature): ([f32; 3], [f32; 3]),
     ) {
-        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
-        cpass.set_pipeline(&self.excite_field_volume_pipeline);
-        cpass.set_bind_group(0, &self.electric_field_excitation_bind_group, &[]);
-        cpass.set_push_constants(0, bytemuck::cast_slice(&size));
-        cpass.set_push_constants(16, bytemuck::cast_slice(&strength));
-        cpass.set_push_constants(32, bytemuck::cast_slice(&position));
-        cpass.dispatch_workgroups(
-            (size[0] as f32 / self.workgroup_dispatch.x as f32).ceil() as u32,
-            (size[1] as f32 / self.workgroup_dispatch.y as f32).ceil() as u32,
-            (size[2] as f32 / self.workgroup_dispatch.z as f32).ceil() as u32,
-        );
+        for half in self.halves() {
+            let mut cpass = self.begin_compute_pass(encoder, PassClass::Excitation);
+            cpass.set_pipeline(&self.excite_field_volume_pipeline);
+            cpass.set_bind_group(0, half.electric_field_excitation_bind_group, &[]);
+            self.params.set_compute(
+                &mut cpass,
+                1,
+                bytemuck::bytes_of(&VolumeExcitationParam {
+                    size,
+                    _padding0: 0,
+                    strength,
+                    _padding1: 0,
+                    position,
+                    phase_offset: half.phase_offset,
+                    quadrature,
+                    _padding2: 0,
+                    bloch_step: self.bloch_step,
+                    _padding3: 0,
+                }),
+            );
+            cpass.dispatch_workgroups(
+                (size[0] as f32 / self.workgroup_dispatch.x as f32).ceil() as u32,
+                (size[1] as f32 / self.workgroup_dispatch.y as f32).ceil() as u32,
+                (size[2] as f32 / self.workgroup_dispatch.z as f32).ceil() as u32,
+            );
+        }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn excite_electric_field_mode(
         &self,
         encoder: &mut wgpu::CommandEncoder,
         position: [u32; 3],
+        normal: SliceMode,
         (sin_t, cos_t): (f32, f32),
         envelope: f32,
         mode_bind_group: &wgpu::BindGroup,
     ) {
-        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
-        cpass.set_pipeline(&self.excite_field_mode_pipeline);
-        cpass.set_bind_group(0, mode_bind_group, &[]);
-        cpass.set_bind_group(1, &self.electric_field_excitation_bind_group, &[]);
-        cpass.set_push_constants(0, bytemuck::cast_slice(&position));
-        cpass.set_push_constants(
-            12,
-            bytemuck::cast_slice(&[cos_t, sin_t, envelope, self.temporal_step]),
-        );
-        cpass.dispatch_workgroups(
-            ((self.grid_dimension[0] - self.boundary.get_extra_grid_extent()) as f32
-                / self.workgroup_dispatch.x as f32)
-                .ceil() as u32,
-            ((self.grid_dimension[1] - self.boundary.get_extra_grid_extent()) as f32
-                / self.workgroup_dispatch.y as f32)
-                .ceil() as u32,
-            1,
-        );
+        let [u, v] = normal.plane_axes();
+        let interior =
+            |axis: usize| self.grid_dimension[axis] - self.boundary.get_extra_grid_extent()[axis];
+        for half in self.halves() {
+            let mut cpass = self.begin_compute_pass(encoder, PassClass::Excitation);
+            cpass.set_pipeline(&self.excite_field_mode_pipeline);
+            cpass.set_bind_group(0, mode_bind_group, &[]);
+            cpass.set_bind_group(1, half.electric_field_excitation_bind_group, &[]);
+            self.params.set_compute(
+                &mut cpass,
+                2,
+                bytemuck::bytes_of(&ModeExcitationParam {
+                    position,
+                    cos_t,
+                    sin_t,
+                    envelope,
+                    dt: self.temporal_step,
+                    normal: normal.axis() as u32,
+                    bloch_step: self.bloch_step,
+                    phase_offset: half.phase_offset,
+                }),
+            );
+            cpass.dispatch_workgroups(
+                (interior(u) as f32 / self.workgroup_dispatch.x as f32).ceil() as u32,
+                (interior(v) as f32 / self.workgroup_dispatch.y as f32).ceil() as u32,
+                1,
+            );
+        }
     }
 
     pub fn offset_slice_position(&mut self, row_delta: f32) {
@@ -977,6 +2512,11 @@ impl FDTD {
         self.slice_position = self.slice_position.min(1.0).max(0.0);
     }
 
+    /// Moves the slice to `position`, 0 and 1 being the first and last cells of the axis.
+    pub fn set_slice_position_normalized(&mut self, position: f32) {
+        self.slice_position = position.clamp(0.0, 1.0);
+    }
+
     pub fn set_slice_mode(&mut self, slice_mode: SliceMode) {
         self.slice_mode = slice_mode;
     }
@@ -1005,12 +2545,106 @@ impl FDTD {
 
     pub fn set_field_view_mode(&mut self, field_view_mode: FieldType) {
         self.field_view_mode = field_view_mode;
+        self.material_view = false;
+        self.intensity_view = false;
     }
 
     pub fn get_field_view_mode(&self) -> FieldType {
         self.field_view_mode
     }
 
+    pub fn toggle_material_view(&mut self) {
+        self.material_view = !self.material_view;
+        self.intensity_view = false;
+    }
+
+    /// Switches to or from the time-averaged intensity, when the preset enables it.
+    pub fn toggle_intensity_view(&mut self) {
+        self.intensity_view = !self.intensity_view && self.intensity.is_some();
+        self.material_view = false;
+    }
+
+    /// Restarts the time-averaged intensity, e.g. once the transient has passed.
+    pub fn restart_intensity(&self) {
+        if let Some(intensity) = &self.intensity {
+            intensity.restart();
+        }
+    }
+
+    /// The R32Float texture holding the time-averaged |E|², when the preset enables it.
+    pub fn get_intensity_texture(&self) -> Option<&wgpu::Texture> {
+        self.intensity.as_ref().map(IntensityAverage::texture)
+    }
+
+    /// Shows or hides the outlines of the materials over the slice, drawn by the built-in colormaps.
+    pub fn toggle_material_overlay(&mut self) {
+        self.material_overlay = !self.material_overlay;
+    }
+
+    /// Switches between the slice and a ray-marched view of the whole grid.
+    pub fn toggle_volume_view(&mut self) {
+        self.volume_view = !self.volume_view;
+    }
+
+    pub fn is_volume_view(&self) -> bool {
+        self.volume_view
+    }
+
+    /// Turns the volume camera around the grid along with a cursor drag of `delta` pixels.
+    pub fn orbit_camera(&mut self, delta: [f32; 2], window_size: [f32; 2]) {
+        self.orbit[0] -= delta[0] / window_size[0] * ORBIT_SPEED;
+        self.orbit[1] =
+            (self.orbit[1] + delta[1] / window_size[0] * ORBIT_SPEED).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Moves the volume camera `steps` zoom notches closer to the grid.
+    pub fn dolly_camera(&mut self, steps: f32) {
+        self.distance = (self.distance / ZOOM_STEP.powf(steps)).clamp(MIN_DISTANCE, MAX_DISTANCE);
+    }
+
+    /// Selects the component shown by the built-in colormaps, custom shaders may ignore it.
+    pub fn set_display_component(&mut self, component: DisplayComponent) {
+        self.display_component = component;
+    }
+
+    /// The viewed field and component, e.g. `E (Ez)` or `H (|H|), volume`.
+    pub fn describe_view(&self) -> String {
+        let view = self.describe_field();
+        if self.volume_view {
+            format!("{}, volume", view)
+        } else {
+            view
+        }
+    }
+
+    fn describe_field(&self) -> String {
+        let field = match self.get_view_mode() {
+            ViewMode::Material => return "Material".to_string(),
+            ViewMode::Intensity => return "<|E|^2>".to_string(),
+            ViewMode::E => "E",
+            ViewMode::H => "H",
+        };
+        match self.display_component {
+            DisplayComponent::X => format!("{} ({}x)", field, field),
+            DisplayComponent::Y => format!("{} ({}y)", field, field),
+            DisplayComponent::Z => format!("{} ({}z)", field, field),
+            DisplayComponent::Magnitude => format!("{} (|{}|)", field, field),
+        }
+    }
+
+    pub fn get_view_mode(&self) -> ViewMode {
+        if self.material_view {
+            ViewMode::Material
+        } else if self.intensity_view {
+            ViewMode::Intensity
+        } else {
+            match self.field_view_mode {
+                FieldType::E => ViewMode::E,
+                FieldType::H => ViewMode::H,
+            }
+        }
+    }
+
     pub fn get_scaling_factor(&self) -> f32 {
         self.scaling_factor
     }
@@ -1024,18 +2658,300 @@ impl FDTD {
         self.scaling_factor *= 10f32.powi(delta_exp);
     }
 
-    pub fn get_electric_field_textures<'a>(&'a self) -> &'a [wgpu::Texture; 3] {
+    pub fn toggle_log_scale(&mut self) {
+        self.log_scale = !self.log_scale;
+    }
+
+    pub fn is_log_scale(&self) -> bool {
+        self.log_scale
+    }
+
+    /// Widens (positive delta) or narrows the displayed range of the log scale, in decades.
+    pub fn offset_log_floor(&mut self, delta_decades: f32) {
+        self.log_floor = (self.log_floor - delta_decades).min(-1.0);
+    }
+
+    pub fn get_log_floor(&self) -> f32 {
+        self.log_floor
+    }
+
+    /// Zeroes all fields and PML auxiliary fields, returning the simulation to its initial state.
+    pub fn reset(&self, encoder: &mut wgpu::CommandEncoder) -> anyhow::Result<()> {
+        if !self.clear_texture_supported {
+            anyhow::bail!("Resetting requires the CLEAR_TEXTURE feature, which the device lacks");
+        }
+        for texture in [&self.electric_field_texture, &self.magnetic_field_texture]
+            .into_iter()
+            .chain(self.imaginary.iter().flat_map(|imaginary| {
+                [
+                    &imaginary.electric_field_texture,
+                    &imaginary.magnetic_field_texture,
+                ]
+            }))
+        {
+            encoder.clear_texture(texture, &wgpu::ImageSubresourceRange::default());
+        }
+        for half in self.halves() {
+            if let Some(pml) = half.pml {
+                pml.clear(encoder);
+            }
+        }
+        self.restart_intensity();
+        for surface in self.near_field.iter().chain(&self.mode_monitors) {
+            surface.clear(encoder);
+        }
+        for flux in self
+            .flux_surfaces
+            .iter()
+            .chain(self.flux_boxes.iter().flat_map(|flux_box| &flux_box.faces))
+        {
+            flux.clear(encoder);
+        }
+        for flux_box in &self.flux_boxes {
+            if let Some(transform) = &flux_box.transform {
+                transform.clear(encoder);
+            }
+        }
+        Ok(())
+    }
+
+    /// Every texture that makes up the simulation state: E, H, then the PML psi textures, the
+    /// same for the imaginary half of the fields with a Bloch wave vector.
+    pub fn state_textures(&self) -> Vec<&wgpu::Texture> {
+        let imaginary = self.imaginary.iter().flat_map(|imaginary| {
+            [
+                &imaginary.electric_field_texture,
+                &imaginary.magnetic_field_texture,
+            ]
+            .into_iter()
+            .chain(imaginary.pml.iter().flat_map(|pml| pml.psi_textures()))
+        });
+        [&self.electric_field_texture, &self.magnetic_field_texture]
+            .into_iter()
+            .chain(self.pml.iter().flat_map(|pml| pml.psi_textures()))
+            .chain(imaginary)
+            .collect()
+    }
+
+    /// The Rgba32Float texture holding the x, y, z components of E in rgb.
+    pub fn get_electric_field_texture(&self) -> &wgpu::Texture {
         &self.electric_field_texture
     }
 
-    pub fn get_magnetic_field_textures<'a>(&'a self) -> &'a [wgpu::Texture; 3] {
+    /// The Rgba32Float texture holding the x, y, z components of H in rgb.
+    pub fn get_magnetic_field_texture(&self) -> &wgpu::Texture {
         &self.magnetic_field_texture
     }
 
+    pub fn get_permittivity_texture(&self) -> &wgpu::Texture {
+        &self.permittivity_texture
+    }
+
+    /// Relative permittivity over the interior of the plane normal to `normal` at grid index
+    /// `layer`, indexed along `normal.plane_axes()`. Blocks until read back.
+    pub fn read_permittivity_plane(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        normal: SliceMode,
+        layer: u32,
+    ) -> anyhow::Result<ndarray::Array2<f32>> {
+        use ndarray::ShapeBuilder;
+        let mut origin = self.boundary.padding().map(|[low, _]| low);
+        let extra = self.boundary.get_extra_grid_extent();
+        let mut size = [0, 1, 2].map(|axis| self.grid_dimension[axis] - extra[axis]);
+        origin[normal.axis()] = layer;
+        size[normal.axis()] = 1;
+        let [u, v] = normal.plane_axes();
+        let data = read_texture_region(device, queue, &self.permittivity_texture, origin, size)?;
+        // the normal axis has extent 1, so the plane keeps x-fastest order
+        Ok(ndarray::Array2::from_shape_vec(
+            (size[u] as usize, size[v] as usize).f(),
+            data,
+        )?)
+    }
+
     pub fn get_dimension(&self) -> [u32; 3] {
         self.grid_dimension
     }
 
+    /// Normalized slice position of a plane given in world coordinates, as used by the slice settings.
+    pub fn normalize_slice_position(&self, mode: SliceMode, position: f32) -> f32 {
+        let axis = match mode {
+            SliceMode::X => 0,
+            SliceMode::Y => 1,
+            SliceMode::Z => 2,
+        };
+        (position + self.shift_vector[axis])
+            / (self.grid_dimension[axis] as f32 - 1.0)
+            / self.spatial_step
+    }
+
+    /// Grid index along the slice axis of the plane at the normalized `position`.
+    pub fn slice_index(&self, mode: SliceMode, position: f32) -> u32 {
+        let dim = self.grid_dimension[match mode {
+            SliceMode::X => 0,
+            SliceMode::Y => 1,
+            SliceMode::Z => 2,
+        }];
+        ((position.clamp(0.0, 1.0) * dim as f32).floor() as u32).min(dim - 1)
+    }
+
+    /// The slice currently on screen as axis and grid index.
+    pub fn get_current_slice(&self) -> (SliceMode, u32) {
+        (
+            self.slice_mode,
+            self.slice_index(self.slice_mode, self.slice_position),
+        )
+    }
+
+    /// Toggles between filling the window and keeping the proportions of the slice.
+    pub fn toggle_stretch(&mut self) {
+        self.stretch = !self.stretch;
+    }
+
+    /// Zooms by `steps` notches, keeping the point under `cursor` in place.
+    pub fn zoom_view(&mut self, steps: f32, cursor: [f32; 2], window_size: [f32; 2]) {
+        let zoom = (self.zoom * ZOOM_STEP.powf(steps)).clamp(1.0, MAX_ZOOM);
+        let clip = cursor_to_clip(cursor, window_size);
+        let ratio = zoom / self.zoom;
+        self.pan = [0, 1].map(|i| clip[i] - (clip[i] - self.pan[i]) * ratio);
+        self.zoom = zoom;
+    }
+
+    /// Moves the zoomed slice along with a cursor drag of `delta` pixels.
+    pub fn pan_view(&mut self, delta: [f32; 2], window_size: [f32; 2]) {
+        self.pan[0] += delta[0] / window_size[0] * 2.0;
+        self.pan[1] -= delta[1] / window_size[1] * 2.0;
+    }
+
+    /// Undoes zooming and panning, and moves the volume camera back to its start.
+    pub fn reset_view(&mut self) {
+        self.zoom = 1.0;
+        self.pan = [0.0, 0.0];
+        self.orbit = DEFAULT_ORBIT;
+        self.distance = DEFAULT_DISTANCE;
+    }
+
+    /// Scale and offset in clip space of the slice quad on a `target_size` target. The slice is
+    /// letterboxed to its physical proportions unless stretched, then zoomed and panned.
+    fn view_transform(&self, target_size: [f32; 2]) -> ([f32; 2], [f32; 2]) {
+        // cells are cubic, so the grid extents give the proportions
+        let [horizontal, vertical] = self.slice_mode.plane_axes();
+        let slice_aspect =
+            self.grid_dimension[horizontal] as f32 / self.grid_dimension[vertical] as f32;
+        let target_aspect = target_size[0] / target_size[1];
+        let scale = if self.stretch {
+            [1.0, 1.0]
+        } else if slice_aspect > target_aspect {
+            [1.0, target_aspect / slice_aspect]
+        } else {
+            [slice_aspect / target_aspect, 1.0]
+        };
+        (scale.map(|scale| scale * self.zoom), self.pan)
+    }
+
+    /// Maps a cursor position on the window to the grid cell shown there on the current slice,
+    /// `None` off the slice and in the volume view.
+    fn volume_param(&self, target_size: [u32; 2]) -> VolumeParam {
+        let [yaw, pitch] = self.orbit;
+        let longest = self.grid_dimension.into_iter().max().unwrap_or(1) as f32;
+        VolumeParam {
+            eye: [
+                pitch.cos() * yaw.sin(),
+                pitch.sin(),
+                pitch.cos() * yaw.cos(),
+            ]
+            .map(|axis| axis * self.distance),
+            aspect: target_size[0] as f32 / target_size[1] as f32,
+            half_extent: self.grid_dimension.map(|extent| extent as f32 / longest),
+            scaling_factor: self.scaling_factor,
+            log_scale: self.log_scale as u32,
+            log_floor: self.log_floor,
+            component: self.display_component as u32,
+            // a custom shader has no volume counterpart, the default colormap stands in
+            colormap: match self.get_colormap() {
+                Some(Colormap::Viridis) | None => 0,
+                Some(Colormap::Inferno) => 1,
+                Some(Colormap::Seismic) => 2,
+                Some(Colormap::Grayscale) => 3,
+            },
+        }
+    }
+
+    pub fn texel_at_cursor(&self, cursor: [f32; 2], window_size: [f32; 2]) -> Option<[u32; 3]> {
+        if self.volume_view {
+            return None;
+        }
+        if self.dimension_mode == DimensionMode::OneD {
+            // z runs across the line plot
+            let u = cursor[0] / window_size[0];
+            let cells = self.grid_dimension[2];
+            return (0.0..1.0)
+                .contains(&u)
+                .then(|| [0, 0, ((u * cells as f32).floor() as u32).min(cells - 1)]);
+        }
+        let (scale, offset) = self.view_transform(window_size);
+        let clip = cursor_to_clip(cursor, window_size);
+        let u = ((clip[0] - offset[0]) / scale[0] + 1.0) / 2.0;
+        let v = (1.0 - (clip[1] - offset[1]) / scale[1]) / 2.0;
+        if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+            return None;
+        }
+        let p = self.slice_position;
+        let coords = match self.slice_mode {
+            SliceMode::X => [p, u, v],
+            SliceMode::Y => [u, p, v],
+            SliceMode::Z => [u, v, p],
+        };
+        let texel = |axis: usize| {
+            let dim = self.grid_dimension[axis];
+            ((coords[axis] * dim as f32).floor() as u32).min(dim - 1)
+        };
+        Some([texel(0), texel(1), texel(2)])
+    }
+
+    /// Copies the values of the currently viewed field (or permittivity) at `texel` into `buffer`,
+    /// one f32 per component.
+    pub fn copy_texel(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        texel: [u32; 3],
+        buffer: &wgpu::Buffer,
+    ) {
+        let texture = match self.get_view_mode() {
+            ViewMode::E => &self.electric_field_texture,
+            ViewMode::H => &self.magnetic_field_texture,
+            ViewMode::Material => &self.permittivity_texture,
+            ViewMode::Intensity => self.get_intensity_texture().unwrap(),
+        };
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: texel[0],
+                    y: texel[1],
+                    z: texel[2],
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
     pub fn reload_shader<P: AsRef<std::path::Path>>(
         &mut self,
         path: P,
@@ -1043,73 +2959,138 @@ impl FDTD {
         render_format: wgpu::TextureFormat,
     ) -> anyhow::Result<()> {
         if let Some(visualization) = &mut self.visualization {
-            let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: Some(path.as_ref().file_name().unwrap().to_str().unwrap()),
-                source: wgpu::ShaderSource::Wgsl(std::fs::read_to_string(path.as_ref())?.into()),
-            });
+            let path = path.as_ref();
+            let render_pipeline = std::fs::read_to_string(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|source| {
+                    VisualizeComponent::try_create_render_pipeline(
+                        device,
+                        &visualization.render_pipeline_layout,
+                        &visualization.vertex_shader,
+                        self.params.shader(
+                            wgpu::ShaderModuleDescriptor {
+                                label: path.file_name().and_then(|name| name.to_str()),
+                                source: wgpu::ShaderSource::Wgsl(
+                                    (visualization.display_scale.clone() + &source).into(),
+                                ),
+                            },
+                            1,
+                        ),
+                        render_format,
+                    )
+                });
+            match render_pipeline {
+                Ok(render_pipeline) => {
+                    visualization.render_pipeline = render_pipeline;
+                    visualization.colormap = None;
+                    visualization.shader_error = None;
+                }
+                // the previous pipeline stays in use
+                Err(err) => {
+                    let message = format!("Shader {} failed to load: {}", path.display(), err);
+                    visualization.shader_error = Some(message.clone());
+                    anyhow::bail!(message);
+                }
+            }
+        }
 
-            let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: None,
-                layout: Some(&visualization.render_pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &visualization.vertex_shader,
-                    entry_point: "vs_main",
-                    buffers: &[wgpu::VertexBufferLayout {
-                        array_stride: std::mem::size_of::<crate::Vertex>() as _,
-                        step_mode: wgpu::VertexStepMode::Vertex,
-                        attributes: &wgpu::vertex_attr_array![
-                            0 => Float32x2,
-                            1 => Float32x2
-                        ],
-                    }],
-                },
-                primitive: wgpu::PrimitiveState::default(),
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState::default(),
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader_module,
-                    entry_point: "fs_main",
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: render_format,
-                        blend: None,
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                }),
-                multiview: None,
-            });
+        Ok(())
+    }
 
-            visualization.render_pipeline = render_pipeline;
+    /// Switches to the next built-in colormap, replacing a custom shader if one is loaded.
+    pub fn cycle_colormap(&mut self, device: &wgpu::Device, render_format: wgpu::TextureFormat) {
+        if let Some(visualization) = &mut self.visualization {
+            let colormap = visualization
+                .colormap
+                .map(Colormap::next)
+                .unwrap_or_default();
+            let shader_module = device.create_shader_module(
+                self.params
+                    .shader(colormap.shader(&visualization.display_scale), 1),
+            );
+            visualization.render_pipeline = VisualizeComponent::create_render_pipeline(
+                device,
+                &visualization.render_pipeline_layout,
+                &visualization.vertex_shader,
+                &shader_module,
+                render_format,
+            );
+            visualization.colormap = Some(colormap);
+            visualization.shader_error = None;
         }
+    }
 
-        Ok(())
+    pub fn get_colormap(&self) -> Option<Colormap> {
+        self.visualization
+            .as_ref()
+            .and_then(|visualization| visualization.colormap)
     }
 
-    pub fn visualize<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+    /// Compiler output of the custom shader that last failed to load, if it was not replaced since.
+    pub fn get_shader_error(&self) -> Option<&str> {
+        self.visualization
+            .as_ref()
+            .and_then(|visualization| visualization.shader_error.as_deref())
+    }
+
+    pub fn visualize<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, target_size: [u32; 2]) {
         if let Some(visualization) = &self.visualization {
-            render_pass.set_pipeline(&visualization.render_pipeline);
+            let (view_scale, view_offset) =
+                self.view_transform([target_size[0] as f32, target_size[1] as f32]);
+            render_pass.set_pipeline(if self.volume_view {
+                &visualization.volume_pipeline
+            } else {
+                &visualization.render_pipeline
+            });
             render_pass.set_vertex_buffer(0, visualization.rect_vertices.slice(..));
             render_pass.set_bind_group(
                 0,
-                match self.field_view_mode {
-                    FieldType::E => &visualization.electric_field_render_bind_group,
-                    FieldType::H => &visualization.magnetic_field_render_bind_group,
+                match self.get_view_mode() {
+                    ViewMode::E => &visualization.electric_field_render_bind_group,
+                    ViewMode::H => &visualization.magnetic_field_render_bind_group,
+                    ViewMode::Material => &visualization.material_render_bind_group,
+                    ViewMode::Intensity => {
+                        visualization.intensity_render_bind_group.as_ref().unwrap()
+                    }
                 },
                 &[],
             );
-            render_pass.set_push_constants(
-                wgpu::ShaderStages::FRAGMENT,
-                0,
-                bytemuck::cast_slice(&[self.get_slice_position_normalized()]),
-            );
-            render_pass.set_push_constants(
-                wgpu::ShaderStages::FRAGMENT,
-                4,
-                bytemuck::cast_slice(&[self.slice_mode as u32]),
-            );
-            render_pass.set_push_constants(
-                wgpu::ShaderStages::FRAGMENT,
-                8,
-                bytemuck::cast_slice(&[self.scaling_factor]),
+            if self.volume_view {
+                self.params.set_render(
+                    render_pass,
+                    1,
+                    bytemuck::bytes_of(&self.volume_param(target_size)),
+                );
+                render_pass.draw(0..6, 0..1);
+                return;
+            }
+            // the line plot spans the whole window
+            let (view_scale, view_offset) = match &visualization.line_pipeline {
+                Some(line_pipeline) => {
+                    render_pass.set_pipeline(line_pipeline);
+                    ([1.0, 1.0], [0.0, 0.0])
+                }
+                None => (view_scale, view_offset),
+            };
+            self.params.set_render(
+                render_pass,
+                1,
+                bytemuck::bytes_of(&SliceParam {
+                    slice_position: self.get_slice_position_normalized(),
+                    slice_mode: self.slice_mode as u32,
+                    scaling_factor: self.scaling_factor,
+                    log_scale: self.log_scale as u32,
+                    log_floor: self.log_floor,
+                    component: self.display_component as u32,
+                    view_scale,
+                    view_offset,
+                    overlay_opacity: if self.material_overlay {
+                        MATERIAL_OVERLAY_OPACITY
+                    } else {
+                        0.0
+                    },
+                    _padding: 0,
+                }),
             );
             render_pass.draw(0..6, 0..1);
         }
@@ -1123,16 +3104,128 @@ pub mod gltf_importer {
     use ndarray::ShapeBuilder;
     use rayon::{
         iter::{IntoParallelIterator, ParallelIterator},
-        prelude::ParallelBridge,
+        slice::{ParallelSlice, ParallelSliceMut},
     };
     use wgpu::util::DeviceExt;
 
-    #[derive(Clone, Copy)]
+    #[derive(Clone, Copy, Debug)]
     pub struct MaterialConstants {
         pub permittivity: f32,
         pub permeability: f32,
     }
 
+    /// Reports the progress of a slow import stage on stderr about once a second.
+    struct Progress {
+        label: String,
+        total: usize,
+        done: std::sync::atomic::AtomicUsize,
+        start: std::time::Instant,
+        reported: std::sync::atomic::AtomicU64, // milliseconds since start of the last report
+    }
+
+    impl Progress {
+        const INTERVAL_MS: u64 = 1000;
+
+        fn new(label: String, total: usize) -> Self {
+            Self {
+                label,
+                total,
+                done: Default::default(),
+                start: std::time::Instant::now(),
+                reported: Default::default(),
+            }
+        }
+
+        fn advance(&self, count: usize) {
+            use std::sync::atomic::Ordering;
+            let done = self.done.fetch_add(count, Ordering::Relaxed) + count;
+            let now = self.start.elapsed().as_millis() as u64;
+            let reported = self.reported.load(Ordering::Relaxed);
+            // only the thread that claims the report prints it
+            if now >= reported + Self::INTERVAL_MS
+                && self
+                    .reported
+                    .compare_exchange(reported, now, Ordering::Relaxed, Ordering::Relaxed)
+                    .is_ok()
+            {
+                eprintln!(
+                    "{}: {:.0}%",
+                    self.label,
+                    100.0 * done.min(self.total) as f32 / self.total.max(1) as f32
+                );
+            }
+        }
+
+        fn elapsed(&self) -> f32 {
+            self.start.elapsed().as_secs_f32()
+        }
+    }
+
+    /// Outcome of filling the primitives of a model.
+    #[derive(Default)]
+    struct FillReport {
+        cells: usize, // cells given constants of the model, in part or in full
+        odd_columns: Vec<(super::SliceMode, [isize; 2])>,
+    }
+
+    /// Block of interior cells `origin..origin + size`, which may reach out of the domain.
+    struct Brick {
+        origin: [isize; 3],
+        size: [usize; 3],
+    }
+
+    /// Brick a mesh is voxelized into once, then stamped at every offset of `instances`.
+    struct Placement {
+        brick: Brick,
+        instances: Vec<[isize; 3]>, // in cells
+    }
+
+    /// Copies of a model on a rectangular lattice, the first one at the model position.
+    #[derive(Clone, Copy, Debug, serde::Deserialize, serde::Serialize)]
+    pub struct Lattice {
+        pub count: [u32; 3],
+        pub pitch: [f32; 3], // same unit as the domain
+    }
+
+    impl FillReport {
+        fn merge(&mut self, other: FillReport) {
+            self.cells += other.cells;
+            self.odd_columns.extend(other.odd_columns);
+        }
+    }
+
+    /// Axes the inside test casts its rays along, `All` takes the majority of the three.
+    #[derive(Clone, Copy, Debug, Default, serde::Deserialize, serde::Serialize)]
+    pub enum ParityAxes {
+        X,
+        Y,
+        #[default]
+        Z,
+        All,
+    }
+
+    impl ParityAxes {
+        fn axes(self) -> &'static [super::SliceMode] {
+            match self {
+                ParityAxes::X => &[super::SliceMode::X],
+                ParityAxes::Y => &[super::SliceMode::Y],
+                ParityAxes::Z => &[super::SliceMode::Z],
+                ParityAxes::All => &[
+                    super::SliceMode::X,
+                    super::SliceMode::Y,
+                    super::SliceMode::Z,
+                ],
+            }
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    pub enum Material {
+        Dielectric(MaterialConstants),
+        /// zero electric constants, the electric field is never updated nor excited inside
+        Pec,
+    }
+
     #[derive(Clone, Copy)]
     struct FDTDConstants {
         pub ec2: f32,
@@ -1149,98 +3242,651 @@ pub mod gltf_importer {
             let hc2 = hc3 / dx;
             Self { ec2, ec3, hc2, hc3 }
         }
+
+        fn new(material: Material, dt: f32, dx: f32) -> Self {
+            match material {
+                Material::Dielectric(material) => Self::from_material(material, dt, dx),
+                Material::Pec => Self {
+                    ec2: 0.0,
+                    ec3: 0.0,
+                    ..Self::from_material(
+                        MaterialConstants {
+                            permittivity: 1.0,
+                            permeability: 1.0,
+                        },
+                        dt,
+                        dx,
+                    )
+                },
+            }
+        }
+    }
+
+    pub struct ConstantsMaps {
+        pub electric: wgpu::TextureView,
+        pub magnetic: wgpu::TextureView,
+        /// relative permittivity, sampleable for visualization and copyable for readback
+        pub permittivity: wgpu::Texture,
+        pub pml: Option<([wgpu::TextureView; 6], [wgpu::TextureView; 6])>,
+    }
+
+    // layout (little endian, zlib compressed): magic, version, grid dimension[3], then the
+    // electric and magnetic constants in memory order
+    const CACHE_MAGIC: &[u8; 8] = b"GREMSMAT";
+    const CACHE_VERSION: u32 = 1;
+
+    /// Where voxelized models and material clouds are kept between runs.
+    pub struct MaterialCache {
+        pub directory: std::path::PathBuf,
+        pub reuse: bool, // false voxelizes again and replaces the entry
+    }
+
+    // FNV-1a, unlike the std hasher it is stable across builds, which entries outlive
+    struct CacheKey(u64);
+
+    impl CacheKey {
+        fn write(&mut self, bytes: &[u8]) {
+            for byte in bytes {
+                self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100000001b3);
+            }
+        }
+
+        fn write_file(&mut self, path: &Path) -> anyhow::Result<()> {
+            let bytes = std::fs::read(path)
+                .map_err(|err| anyhow::anyhow!("{}: {}", path.display(), err))?;
+            self.write(&(bytes.len() as u64).to_le_bytes());
+            self.write(&bytes);
+            Ok(())
+        }
+    }
+
+    /// Key of the constants voxelized from `models`, `extrusions` and `material_clouds`. Covers
+    /// the contents of every file they read, external glTF buffers included, and `parameters`,
+    /// which must describe everything else the constants embed: grid, time step and background.
+    pub fn cache_key(
+        models: &[crate::settings::ModelSettings],
+        extrusions: &[crate::settings::ExtrusionSettings],
+        material_clouds: &[crate::settings::MaterialCloudSettings],
+        parameters: &str,
+    ) -> anyhow::Result<u64> {
+        let mut key = CacheKey(0xcbf29ce484222325);
+        key.write(&CACHE_VERSION.to_le_bytes());
+        key.write(parameters.as_bytes());
+        for model in models {
+            let path = Path::new(&model.path);
+            key.write(format!("{:?}", model).as_bytes());
+            key.write_file(path)?;
+            let gltf = gltf::Gltf::open(path)?;
+            for buffer in gltf.buffers() {
+                if let gltf::buffer::Source::Uri(uri) = buffer.source() {
+                    key.write(uri.as_bytes());
+                    let buffer_path = path.parent().unwrap_or(Path::new("")).join(uri);
+                    // percent-encoded names are left to the importer to resolve or reject
+                    if !uri.starts_with("data:") && buffer_path.is_file() {
+                        key.write_file(&buffer_path)?;
+                    }
+                }
+            }
+        }
+        for extrusion in extrusions {
+            key.write(format!("{:?}", extrusion).as_bytes());
+            key.write_file(Path::new(&extrusion.path))?;
+        }
+        for cloud in material_clouds {
+            key.write(format!("{:?}", cloud).as_bytes());
+            key.write_file(Path::new(&cloud.path))?;
+        }
+        Ok(key.0)
     }
 
+    /// Voxelizes models into the electric and magnetic constants of the whole grid. These two
+    /// maps of f32 pairs, 16 bytes per cell, are the floor of its memory, plus 5 bytes of fill
+    /// scratch per cell of the model being voxelized. With per-cell mutexes it took 48 bytes,
+    /// a tenfold cut would need maps smaller than the constants uploaded to the GPU.
     pub struct Importer {
         grid_dimension: [u32; 3],
         dt: f32,
         dx: f32,
-        electric_constants: ndarray::Array3<std::sync::Mutex<nalgebra::Vector2<f32>>>,
-        magnetic_constants: ndarray::Array3<std::sync::Mutex<nalgebra::Vector2<f32>>>,
+        electric_constants: ndarray::Array3<nalgebra::Vector2<f32>>,
+        magnetic_constants: ndarray::Array3<nalgebra::Vector2<f32>>,
         shift_vector: nalgebra::Vector3<f32>,
-        extra_extent: u32,
+        padding: [[u32; 2]; 3],
         pml_sigma: f32,
         pml_alpha: f32,
     }
 
-    impl Importer {
-        pub fn new(
-            dimension: [[f32; 2]; 3],
-            dt: f32,
-            dx: f32,
-            background: MaterialConstants,
-            extra_extent: u32,
-            pml_sigma: f32,
-            pml_alpha: f32,
-        ) -> Self {
-            let step_x = (dimension[0][1] - dimension[0][0]) / dx;
-            let step_y = (dimension[1][1] - dimension[1][0]) / dx;
-            let step_z = (dimension[2][1] - dimension[2][0]) / dx;
-            let grid_x = step_x.ceil() as u32 + extra_extent;
-            let grid_y = step_y.ceil() as u32 + extra_extent;
-            let grid_z = step_z.ceil() as u32 + extra_extent;
+    impl Importer {
+        pub fn new(
+            dimension: [[f32; 2]; 3],
+            dt: f32,
+            dx: f32,
+            background: MaterialConstants,
+            padding: [[u32; 2]; 3],
+            pml_sigma: f32,
+            pml_alpha: f32,
+        ) -> Self {
+            let step_x = (dimension[0][1] - dimension[0][0]) / dx;
+            let step_y = (dimension[1][1] - dimension[1][0]) / dx;
+            let step_z = (dimension[2][1] - dimension[2][0]) / dx;
+            let grid_x = step_x.ceil() as u32 + padding[0][0] + padding[0][1];
+            let grid_y = step_y.ceil() as u32 + padding[1][0] + padding[1][1];
+            let grid_z = step_z.ceil() as u32 + padding[2][0] + padding[2][1];
+
+            Self {
+                electric_constants: ndarray::Array3::from_elem(
+                    (grid_x as usize, grid_y as usize, grid_z as usize).f(),
+                    nalgebra::vector![
+                        dt / (dx * background.permittivity),
+                        dt / background.permittivity
+                    ],
+                ),
+                magnetic_constants: ndarray::Array3::from_elem(
+                    (grid_x as usize, grid_y as usize, grid_z as usize).f(),
+                    nalgebra::vector![
+                        dt / (dx * background.permeability),
+                        dt / background.permeability
+                    ],
+                ),
+                grid_dimension: [grid_x, grid_y, grid_z],
+                dt,
+                dx,
+                shift_vector: super::grid_shift(dimension, dx, padding),
+                padding,
+                pml_sigma,
+                pml_alpha,
+            }
+        }
+
+        /// Grid coordinates of the first interior cell.
+        fn interior_origin(&self) -> [isize; 3] {
+            self.padding.map(|[low, _]| low as isize)
+        }
+
+        /// Interior cells along each axis.
+        fn interior_size(&self) -> [isize; 3] {
+            [0, 1, 2].map(|i| {
+                (self.grid_dimension[i] - self.padding[i][0] - self.padding[i][1]) as isize
+            })
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn load_gltf<P: AsRef<Path>>(
+            &mut self,
+            path: P,
+            scale: [f32; 3],
+            rotation: [f32; 3],
+            position: [f32; 3],
+            material: Material,
+            primitive_materials: &std::collections::BTreeMap<String, Material>,
+            subpixel_samples: u32,
+            parity_axes: ParityAxes,
+            repeat: Option<Lattice>,
+            strict: bool,
+        ) -> anyhow::Result<()> {
+            let path = path.as_ref();
+            let (document, buffers, _) =
+                gltf::import(path).map_err(|err| anyhow::anyhow!("{}: {}", path.display(), err))?;
+            for name in primitive_materials.keys() {
+                if !document
+                    .materials()
+                    .any(|material| material.name() == Some(name.as_str()))
+                {
+                    eprintln!(
+                        "Warning: {} has no material named {}, its mapping is unused",
+                        path.display(),
+                        name
+                    );
+                }
+            }
+            let overrides = primitive_materials
+                .iter()
+                .map(|(name, material)| {
+                    (
+                        name.clone(),
+                        FDTDConstants::new(*material, self.dt, self.dx),
+                    )
+                })
+                .collect();
+            let scene = document
+                .default_scene()
+                .ok_or(anyhow::anyhow!("Default scene required!"))?;
+            let transform = nalgebra::Matrix4::new_translation(&(self.shift_vector / self.dx))
+                * nalgebra::Matrix4::new_translation(
+                    &(nalgebra::Vector3::from(position) / self.dx),
+                )
+                * nalgebra::Rotation3::from_euler_angles(
+                    rotation[0].to_radians(),
+                    rotation[1].to_radians(),
+                    rotation[2].to_radians(),
+                )
+                .to_homogeneous()
+                * nalgebra::Matrix4::new_nonuniform_scaling(
+                    &(nalgebra::Vector3::from(scale) / self.dx),
+                );
+            let Some([low, high]) = scene
+                .nodes()
+                .filter_map(|node| node_bounds(node, transform))
+                .reduce(|a, b| [a[0].inf(&b[0]), a[1].sup(&b[1])])
+            else {
+                eprintln!("Warning: {} has no meshes", path.display());
+                return Ok(());
+            };
+            self.check_bounds(path, [low, high], repeat, strict)?;
+
+            // the brick covers the bounds of the mesh with a cell to spare, in interior cells
+            let interior = self.interior_origin().map(|origin| origin as f32);
+            let simulation = self.interior_size();
+            let mut brick = Brick {
+                origin: [0, 1, 2].map(|i| (low[i] - interior[i] + 0.5).floor() as isize - 1),
+                size: [0; 3],
+            };
+            let mut end = [0, 1, 2].map(|i| (high[i] - interior[i] + 0.5).ceil() as isize + 1);
+            let mut instances = vec![[0; 3]];
+            if let Some(lattice) = repeat {
+                let steps = lattice.pitch.map(|pitch| pitch / self.dx);
+                if steps.iter().any(|step| (step - step.round()).abs() > 1e-3) {
+                    eprintln!(
+                        "Warning: the repeat pitch {:?} of {} is not a whole number of cells, instances are moved to the nearest cell",
+                        lattice.pitch,
+                        path.display()
+                    );
+                }
+                instances = (0..lattice.count[0] as isize)
+                    .flat_map(|a| {
+                        (0..lattice.count[1] as isize).flat_map(move |b| {
+                            (0..lattice.count[2] as isize).map(move |c| [a, b, c])
+                        })
+                    })
+                    .map(|index| [0, 1, 2].map(|i| (index[i] as f32 * steps[i]).round() as isize))
+                    .collect();
+            } else {
+                // instances are clipped when stamped, a single one need not cover more
+                for i in 0..3 {
+                    brick.origin[i] = brick.origin[i].clamp(0, simulation[i]);
+                    end[i] = end[i].clamp(0, simulation[i]);
+                }
+            }
+            brick.size = [0, 1, 2].map(|i| (end[i] - brick.origin[i]).max(0) as usize);
+
+            let axes = parity_axes.axes();
+            let columns: usize = axes
+                .iter()
+                .map(|axis| {
+                    axis.plane_axes()
+                        .iter()
+                        .map(|&i| brick.size[i])
+                        .product::<usize>()
+                })
+                .sum();
+            // every primitive casts its triangles and fills the columns along each axis
+            let (primitives, triangles) = scene
+                .nodes()
+                .map(count_primitives)
+                .fold((0, 0), |(primitives, triangles), (p, t)| {
+                    (primitives + p, triangles + t)
+                });
+            let progress = Progress::new(
+                format!("Voxelizing {}", path.display()),
+                triangles * axes.len() + primitives * columns,
+            );
+            let placement = Placement { brick, instances };
+            let mut report = FillReport::default();
+            for node in scene.nodes() {
+                report.merge(self.process_node(
+                    node,
+                    transform,
+                    &buffers,
+                    FDTDConstants::new(material, self.dt, self.dx),
+                    &overrides,
+                    subpixel_samples,
+                    parity_axes,
+                    &placement,
+                    &progress,
+                ));
+            }
+            println!(
+                "Imported {}: {} triangles filling {} cells{} in {:.1} s",
+                path.display(),
+                triangles,
+                report.cells,
+                if placement.instances.len() > 1 {
+                    format!(" over {} instances", placement.instances.len())
+                } else {
+                    String::new()
+                },
+                progress.elapsed()
+            );
+            let odd_columns = report.odd_columns;
+            if !odd_columns.is_empty() {
+                const LISTED: usize = 8;
+                eprintln!(
+                    "Warning: {} cell columns of {} cross its surface an odd number of times, the mesh is likely not watertight: {}{}",
+                    odd_columns.len(),
+                    path.display(),
+                    odd_columns
+                        .iter()
+                        .take(LISTED)
+                        .map(|(axis, [i, j])| format!("{:?} ({}, {})", axis, i, j))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    if odd_columns.len() > LISTED { ", ..." } else { "" }
+                );
+            }
+            Ok(())
+        }
+
+        /// Warns, or fails when `strict`, unless 99% of the bounding box of a model and its
+        /// repeats, `bounds` in grid coordinates, lies inside the domain.
+        fn check_bounds(
+            &self,
+            path: &Path,
+            [mut low, mut high]: [nalgebra::Vector3<f32>; 2],
+            repeat: Option<Lattice>,
+            strict: bool,
+        ) -> anyhow::Result<()> {
+            if let Some(lattice) = repeat {
+                for i in 0..3 {
+                    let span = (lattice.count[i] - 1) as f32 * lattice.pitch[i] / self.dx;
+                    low[i] += span.min(0.0);
+                    high[i] += span.max(0.0);
+                }
+            }
+            // the interior cells span [origin - 0.5, origin + cells - 0.5]
+            let (origin, size) = (self.interior_origin(), self.interior_size());
+            let domain =
+                [0, 1, 2].map(|i| [origin[i] as f32 - 0.5, (origin[i] + size[i]) as f32 - 0.5]);
+            let inside: f32 = (0..3)
+                .map(|i| {
+                    let [start, end] = domain[i];
+                    if high[i] > low[i] {
+                        (high[i].min(end) - low[i].max(start)).max(0.0) / (high[i] - low[i])
+                    } else {
+                        (start <= low[i] && low[i] <= end) as u8 as f32
+                    }
+                })
+                .product();
+            if inside >= 0.99 {
+                return Ok(());
+            }
+            let to_world =
+                |grid: [f32; 3]| [0, 1, 2].map(|i| grid[i] * self.dx - self.shift_vector[i]);
+            let message = format!(
+                "{:.1}% of the bounding box of {} lies inside the domain: the model spans {:?} to {:?}, the domain {:?} to {:?}",
+                inside * 100.0,
+                path.display(),
+                to_world(low.into()),
+                to_world(high.into()),
+                to_world(domain.map(|[start, _]| start)),
+                to_world(domain.map(|[_, end]| end)),
+            );
+            anyhow::ensure!(!strict, message);
+            eprintln!("Warning: {}", message);
+            Ok(())
+        }
+
+        /// Fills the polygons of a CSV of `polygon, x, y` rows, extruded from `z_range[0]` to
+        /// `z_range[1]` and moved by `position`. Consecutive rows of the same polygon name give
+        /// its vertices in order. A cell column is inside where the winding number of the
+        /// polygons is not zero, so a polygon wound the other way within another is a hole.
+        /// Above one sample, cells the outline or the end faces cut are blended by the fraction
+        /// of their volume inside.
+        pub fn load_polygon_csv<P: AsRef<Path>>(
+            &mut self,
+            path: P,
+            position: [f32; 3],
+            z_range: [f32; 2],
+            material: Material,
+            subpixel_samples: u32,
+        ) -> anyhow::Result<()> {
+            let path = path.as_ref();
+            let start = std::time::Instant::now();
+            // interior cell k spans [k, k + 1] in these coordinates
+            let interior = self.interior_origin().map(|origin| origin as f32);
+            let to_cell = |axis: usize, value: f32| {
+                (value + position[axis] + self.shift_vector[axis]) / self.dx - interior[axis] + 0.5
+            };
+            let mut polygons: Vec<(String, Vec<[f32; 2]>)> = vec![];
+            for record in crate::open_csv(path)?.records() {
+                let record = crate::read_csv_record(path, record)?;
+                let name: String = crate::parse_csv_field(path, &record, 0)?;
+                let vertex = [
+                    to_cell(0, crate::parse_csv_field(path, &record, 1)?),
+                    to_cell(1, crate::parse_csv_field(path, &record, 2)?),
+                ];
+                match polygons.last_mut() {
+                    Some((last, vertices)) if *last == name => vertices.push(vertex),
+                    _ => polygons.push((name, vec![vertex])),
+                }
+            }
+            if let Some((name, _)) = polygons.iter().find(|(_, vertices)| vertices.len() < 3) {
+                anyhow::bail!(
+                    "{}: polygon {} has fewer than 3 vertices",
+                    path.display(),
+                    name
+                );
+            }
+            let edges: Vec<[[f32; 2]; 2]> = polygons
+                .iter()
+                .flat_map(|(_, vertices)| {
+                    vertices
+                        .iter()
+                        .zip(vertices.iter().cycle().skip(1))
+                        .map(|(a, b)| [*a, *b])
+                })
+                .collect();
 
-            Self {
-                electric_constants: ndarray::Array3::from_shape_simple_fn(
-                    (grid_x as usize, grid_y as usize, grid_z as usize).f(),
-                    || {
-                        std::sync::Mutex::new(nalgebra::vector![
-                            dt / (dx * background.permittivity),
-                            dt / background.permittivity
-                        ])
-                    },
-                ),
-                magnetic_constants: ndarray::Array3::from_shape_simple_fn(
-                    (grid_x as usize, grid_y as usize, grid_z as usize).f(),
-                    || {
-                        std::sync::Mutex::new(nalgebra::vector![
-                            dt / (dx * background.permeability),
-                            dt / background.permeability
-                        ])
-                    },
-                ),
-                grid_dimension: [grid_x, grid_y, grid_z],
-                dt,
-                dx,
-                shift_vector: -nalgebra::vector![
-                    dimension[0][0] + (step_x - step_x.floor()) * dx * 0.5
-                        - extra_extent as f32 * dx * 0.5,
-                    dimension[1][0] + (step_y - step_y.floor()) * dx * 0.5
-                        - extra_extent as f32 * dx * 0.5,
-                    dimension[2][0] + (step_z - step_z.floor()) * dx * 0.5
-                        - extra_extent as f32 * dx * 0.5
-                ],
-                extra_extent,
-                pml_sigma,
-                pml_alpha,
+            let simulation = self.interior_size();
+            let [low, high] = edges.iter().fold(
+                [[f32::INFINITY; 2], [f32::NEG_INFINITY; 2]],
+                |[low, high], [a, _]| {
+                    [
+                        [low[0].min(a[0]), low[1].min(a[1])],
+                        [high[0].max(a[0]), high[1].max(a[1])],
+                    ]
+                },
+            );
+            let [z_low, z_high] = z_range.map(|z| to_cell(2, z));
+            let [low, high] = [
+                [low[0], low[1], z_low].map(|v| v.floor() as isize),
+                [high[0], high[1], z_high].map(|v| v.ceil() as isize),
+            ];
+            let origin = [0, 1, 2].map(|i| low[i].clamp(0, simulation[i]));
+            let size = [0, 1, 2].map(|i| (high[i].clamp(0, simulation[i]) - origin[i]) as usize);
+
+            let n = subpixel_samples as usize;
+            let weight = 1.0 / (n * n) as f32;
+            // one task per row of samples, which crosses the outline at a few points
+            let mut area = ndarray::Array2::<f32>::zeros((size[0], size[1]).f());
+            let rows: Vec<Vec<f32>> = (0..size[1] * n)
+                .into_par_iter()
+                .map(|row| {
+                    let y = origin[1] as f32 + (row as f32 + 0.5) / n as f32;
+                    let mut crossings: Vec<(f32, i32)> = edges
+                        .iter()
+                        .filter(|[a, b]| (a[1] <= y) != (b[1] <= y))
+                        .map(|[a, b]| {
+                            let x = a[0] + (y - a[1]) / (b[1] - a[1]) * (b[0] - a[0]);
+                            (x, if b[1] > a[1] { 1 } else { -1 })
+                        })
+                        .collect();
+                    crossings.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+                    let mut inside = vec![0f32; size[0]];
+                    let mut winding = 0;
+                    for (crossing, next) in crossings.iter().zip(crossings.iter().skip(1)) {
+                        winding += crossing.1;
+                        if winding == 0 {
+                            continue;
+                        }
+                        // samples of the row at origin + (i + 0.5) / n lying in the span
+                        let [first, last] = [crossing.0, next.0].map(|x| {
+                            ((x - origin[0] as f32) * n as f32 - 0.5)
+                                .ceil()
+                                .clamp(0.0, (size[0] * n) as f32)
+                                as usize
+                        });
+                        for sample in first..last {
+                            inside[sample / n] += weight;
+                        }
+                    }
+                    inside
+                })
+                .collect();
+            for (row, inside) in rows.iter().enumerate() {
+                for (x, inside) in inside.iter().enumerate() {
+                    area[[x, row / n]] += inside;
+                }
             }
+
+            let fraction = ndarray::Array3::from_shape_fn(size.f(), |(x, y, z)| {
+                let z = (origin[2] + z as isize) as f32;
+                if subpixel_samples > 1 {
+                    let height = z_high.min(z + 1.0) - z_low.max(z);
+                    (area[[x, y]] * height.clamp(0.0, 1.0)).min(1.0)
+                } else {
+                    // the single sample of a cell is its center
+                    let center = z + 0.5;
+                    (area[[x, y]] >= 1.0 && z_low <= center && center < z_high) as u8 as f32
+                }
+            });
+            let cells = self.stamp(
+                &fraction,
+                origin,
+                FDTDConstants::new(material, self.dt, self.dx),
+            );
+            println!(
+                "Imported {}: {} polygons filling {} cells in {:.1} s",
+                path.display(),
+                polygons.len(),
+                cells,
+                start.elapsed().as_secs_f32()
+            );
+            Ok(())
         }
 
-        pub fn load_gltf<P: AsRef<Path>>(
+        /// Sets the permittivity of every cell within the bounding box of a CSV point cloud of
+        /// `x, y, z, relative permittivity` rows, interpolated between the scattered points.
+        /// Points are scaled, then moved by `position`. Permeability is left as it is.
+        pub fn load_permittivity_csv<P: AsRef<Path>>(
             &mut self,
             path: P,
             scale: [f32; 3],
             position: [f32; 3],
-            constants: MaterialConstants,
         ) -> anyhow::Result<()> {
-            let (document, buffers, _) = gltf::import(path)?;
-            let scene = document
-                .default_scene()
-                .ok_or(anyhow::anyhow!("Default scene required!"))?;
-            for node in scene.nodes() {
-                self.process_node(
-                    node,
-                    nalgebra::Matrix4::new_translation(&(self.shift_vector / self.dx))
-                        * nalgebra::Matrix4::new_translation(
-                            &(nalgebra::Vector3::from(position) / self.dx),
-                        )
-                        * nalgebra::Matrix4::new_nonuniform_scaling(
-                            &(nalgebra::Vector3::from(scale) / self.dx),
-                        ),
-                    &buffers,
-                    FDTDConstants::from_material(constants, self.dt, self.dx),
+            let path = path.as_ref();
+            let mut data = vec![];
+            for record in crate::open_csv(path)?.records() {
+                let record = crate::read_csv_record(path, record)?;
+                let mut values = [0f64; 4];
+                for (i, value) in values.iter_mut().enumerate() {
+                    *value = crate::parse_csv_field(path, &record, i)?;
+                }
+                let [x, y, z, permittivity] = values;
+                anyhow::ensure!(
+                    permittivity > 0.,
+                    "{}, line {}: permittivity must be positive, got {}",
+                    path.display(),
+                    record.position().map_or(0, |position| position.line()),
+                    permittivity
                 );
+                data.push((nalgebra::vector![x, y, z], [permittivity as f32]));
+            }
+            // the linear fit may overshoot near sharp changes, never leave the measured range
+            let (min, max) = data
+                .iter()
+                .fold((f32::INFINITY, 0f32), |(min, max), (_, [v])| {
+                    (min.min(*v), max.max(*v))
+                });
+            let interpolator = crate::interpolator::Linear3DInterpolator::new(data);
+
+            let scale = nalgebra::Vector3::from(scale);
+            let offset = self.shift_vector + nalgebra::Vector3::from(position);
+            let (dt, dx) = (self.dt, self.dx);
+            ndarray::Zip::indexed(&mut self.electric_constants).par_for_each(
+                |(x, y, z), constants| {
+                    let grid = nalgebra::vector![x as f32, y as f32, z as f32] * dx;
+                    let local = (grid - offset).component_div(&scale);
+                    if let Some([permittivity]) = interpolator.interpolate(local.cast()) {
+                        let material = FDTDConstants::from_material(
+                            MaterialConstants {
+                                permittivity: permittivity.clamp(min, max),
+                                permeability: 1.0,
+                            },
+                            dt,
+                            dx,
+                        );
+                        *constants = nalgebra::vector![material.ec2, material.ec3];
+                    }
+                },
+            );
+            Ok(())
+        }
+
+        /// Writes the constants of the models and clouds loaded so far to `path`.
+        /// The file is written next to `path` first and renamed, so it is never left truncated.
+        pub fn save_cache(&self, path: &Path) -> anyhow::Result<()> {
+            use std::io::Write;
+
+            if let Some(directory) = path.parent() {
+                std::fs::create_dir_all(directory)?;
+            }
+            let temporary_path = path.with_extension("tmp");
+            let mut file = flate2::write::ZlibEncoder::new(
+                std::io::BufWriter::new(std::fs::File::create(&temporary_path)?),
+                flate2::Compression::fast(),
+            );
+            file.write_all(CACHE_MAGIC)?;
+            for value in [CACHE_VERSION].iter().chain(&self.grid_dimension) {
+                file.write_all(&value.to_le_bytes())?;
             }
+            for constants in [&self.electric_constants, &self.magnetic_constants] {
+                let values = constants
+                    .as_slice_memory_order()
+                    .ok_or_else(|| anyhow::anyhow!("Constants are not contiguous"))?;
+                file.write_all(bytemuck::cast_slice(values))?;
+            }
+            file.finish()?.into_inner()?.sync_all()?;
+            std::fs::rename(&temporary_path, path)?;
+            Ok(())
+        }
+
+        /// Replaces the constants by those `save_cache` wrote to `path` on the same grid.
+        pub fn load_cache(&mut self, path: &Path) -> anyhow::Result<()> {
+            use std::io::Read;
+
+            let mut data = vec![];
+            flate2::read::ZlibDecoder::new(std::fs::File::open(path)?).read_to_end(&mut data)?;
+            anyhow::ensure!(
+                data.len() >= 24 && &data[..8] == CACHE_MAGIC,
+                "{} is not a material cache entry",
+                path.display()
+            );
+            let header: Vec<u32> = data[8..24]
+                .chunks_exact(4)
+                .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+                .collect();
+            anyhow::ensure!(
+                header[0] == CACHE_VERSION,
+                "Unsupported material cache version {}",
+                header[0]
+            );
+            anyhow::ensure!(
+                header[1..] == self.grid_dimension,
+                "Material cache entry is for a {:?} grid, not {:?}",
+                &header[1..],
+                self.grid_dimension
+            );
+            let cells = self.electric_constants.len();
+            anyhow::ensure!(
+                data.len() == 24 + 2 * cells * std::mem::size_of::<nalgebra::Vector2<f32>>(),
+                "{} is truncated",
+                path.display()
+            );
+            let mut electric: Vec<nalgebra::Vector2<f32>> =
+                bytemuck::pod_collect_to_vec(&data[24..]);
+            let magnetic = electric.split_off(cells);
+            let shape = self.electric_constants.raw_dim().f();
+            self.electric_constants = ndarray::Array3::from_shape_vec(shape, electric)?;
+            self.magnetic_constants = ndarray::Array3::from_shape_vec(shape, magnetic)?;
             Ok(())
         }
 
@@ -1248,11 +3894,8 @@ pub mod gltf_importer {
             self,
             device: &wgpu::Device,
             queue: &wgpu::Queue,
-        ) -> (
-            wgpu::TextureView,
-            wgpu::TextureView,
-            Option<([wgpu::TextureView; 6], [wgpu::TextureView; 6])>,
-        ) {
+            dump_permittivity: Option<&Path>,
+        ) -> anyhow::Result<ConstantsMaps> {
             let common_desc = wgpu::TextureDescriptor {
                 label: None,
                 size: wgpu::Extent3d {
@@ -1268,53 +3911,41 @@ pub mod gltf_importer {
                 view_formats: &[],
             };
 
-            let mut ec_map = ndarray::Zip::from(&self.electric_constants)
-                .par_map_collect(|mutex| *mutex.lock().unwrap());
-
-            let mut hc_map = ndarray::Zip::from(&self.magnetic_constants)
-                .par_map_collect(|mutex| *mutex.lock().unwrap());
+            let start = std::time::Instant::now();
+            let mut ec_map = self.electric_constants;
+            let mut hc_map = self.magnetic_constants;
 
             let mut pml_constants = None;
 
-            if self.extra_extent > 0 {
-                let half_extent = (self.extra_extent / 2) as usize;
-                let far_x = self.grid_dimension[0] as usize - half_extent;
-                let far_y = self.grid_dimension[1] as usize - half_extent;
-                let far_z = self.grid_dimension[2] as usize - half_extent;
+            if self.padding.iter().flatten().any(|p| *p > 0) {
+                // copying the planes, then deriving their psi constants, per field,
+                // the planes of faces without padding go unused
+                let progress = Progress::new("Building PML constants".to_string(), 4);
+                let [low_x, low_y, low_z] = self.padding.map(|[low, _]| low as usize);
+                let [far_x, far_y, far_z] =
+                    [0, 1, 2].map(|i| (self.grid_dimension[i] - self.padding[i][1]) as usize);
 
-                let simulation_x = (self.grid_dimension[0] - self.extra_extent) as usize;
-                let simulation_y = (self.grid_dimension[1] - self.extra_extent) as usize;
-                let simulation_z = (self.grid_dimension[2] - self.extra_extent) as usize;
+                let simulation_x = far_x - low_x;
+                let simulation_y = far_y - low_y;
+                let simulation_z = far_z - low_z;
 
                 let x_near_plane_electric = ndarray::Array2::from_shape_vec(
                     (simulation_y, simulation_z),
                     ec_map
-                        .slice(ndarray::s![
-                            half_extent,
-                            half_extent..far_y,
-                            half_extent..far_z,
-                        ])
+                        .slice(ndarray::s![low_x, low_y..far_y, low_z..far_z])
                         .iter()
                         .cloned()
                         .collect(),
                 )
                 .unwrap();
                 ec_map
-                    .slice_mut(ndarray::s![
-                        0..half_extent,
-                        half_extent..far_y,
-                        half_extent..far_z,
-                    ])
+                    .slice_mut(ndarray::s![0..low_x, low_y..far_y, low_z..far_z])
                     .assign(&x_near_plane_electric);
 
                 let x_far_plane_electric = ndarray::Array2::from_shape_vec(
                     (simulation_y, simulation_z),
                     ec_map
-                        .slice(ndarray::s![
-                            far_x - 1,
-                            half_extent..far_y,
-                            half_extent..far_z,
-                        ])
+                        .slice(ndarray::s![far_x - 1, low_y..far_y, low_z..far_z])
                         .iter()
                         .cloned()
                         .collect(),
@@ -1323,41 +3954,29 @@ pub mod gltf_importer {
                 ec_map
                     .slice_mut(ndarray::s![
                         far_x..self.grid_dimension[0] as usize,
-                        half_extent..far_y,
-                        half_extent..far_z,
+                        low_y..far_y,
+                        low_z..far_z
                     ])
                     .assign(&x_far_plane_electric);
 
                 let y_near_plane_electric = ndarray::Array2::from_shape_vec(
                     (simulation_x, simulation_z),
                     ec_map
-                        .slice(ndarray::s![
-                            half_extent..far_x,
-                            half_extent,
-                            half_extent..far_z,
-                        ])
+                        .slice(ndarray::s![low_x..far_x, low_y, low_z..far_z])
                         .iter()
                         .cloned()
                         .collect(),
                 )
                 .unwrap();
                 ec_map
-                    .slice_mut(ndarray::s![
-                        half_extent..far_x,
-                        0..half_extent,
-                        half_extent..far_z,
-                    ])
+                    .slice_mut(ndarray::s![low_x..far_x, 0..low_y, low_z..far_z])
                     .permuted_axes([1, 0, 2])
                     .assign(&y_near_plane_electric);
 
                 let y_far_plane_electric = ndarray::Array2::from_shape_vec(
                     (simulation_x, simulation_z),
                     ec_map
-                        .slice(ndarray::s![
-                            half_extent..far_x,
-                            far_y - 1,
-                            half_extent..far_z,
-                        ])
+                        .slice(ndarray::s![low_x..far_x, far_y - 1, low_z..far_z])
                         .iter()
                         .cloned()
                         .collect(),
@@ -1365,9 +3984,9 @@ pub mod gltf_importer {
                 .unwrap();
                 ec_map
                     .slice_mut(ndarray::s![
-                        half_extent..far_x,
+                        low_x..far_x,
                         far_y..self.grid_dimension[1] as usize,
-                        half_extent..far_z,
+                        low_z..far_z
                     ])
                     .permuted_axes([1, 0, 2])
                     .assign(&y_far_plane_electric);
@@ -1375,35 +3994,32 @@ pub mod gltf_importer {
                 let mut z_near_plane_electric =
                     ndarray::Array2::default((simulation_x, simulation_y).f());
                 z_near_plane_electric.assign(&ec_map.slice(ndarray::s![
-                    half_extent..far_x,
-                    half_extent..far_y,
-                    half_extent,
+                    low_x..far_x,
+                    low_y..far_y,
+                    low_z
                 ]));
                 ec_map
-                    .slice_mut(ndarray::s![
-                        half_extent..far_x,
-                        half_extent..far_y,
-                        0..half_extent,
-                    ])
+                    .slice_mut(ndarray::s![low_x..far_x, low_y..far_y, 0..low_z])
                     .permuted_axes([2, 0, 1])
                     .assign(&z_near_plane_electric);
 
                 let mut z_far_plane_electric =
                     ndarray::Array2::default((simulation_x, simulation_y).f());
                 z_far_plane_electric.assign(&ec_map.slice(ndarray::s![
-                    half_extent..far_x,
-                    half_extent..far_y,
-                    far_z - 1,
+                    low_x..far_x,
+                    low_y..far_y,
+                    far_z - 1
                 ]));
                 ec_map
                     .slice_mut(ndarray::s![
-                        half_extent..far_x,
-                        half_extent..far_y,
-                        far_z..self.grid_dimension[2] as usize,
+                        low_x..far_x,
+                        low_y..far_y,
+                        far_z..self.grid_dimension[2] as usize
                     ])
                     .permuted_axes([2, 0, 1])
                     .assign(&z_far_plane_electric);
 
+                progress.advance(1);
                 let pml_electric_views = [
                     &x_near_plane_electric,
                     &x_far_plane_electric,
@@ -1439,35 +4055,24 @@ pub mod gltf_importer {
                         .create_view(&wgpu::TextureViewDescriptor::default())
                 });
 
+                progress.advance(1);
                 let x_near_plane_magnetic = ndarray::Array2::from_shape_vec(
                     (simulation_y, simulation_z),
                     hc_map
-                        .slice(ndarray::s![
-                            half_extent,
-                            half_extent..far_y,
-                            half_extent..far_z,
-                        ])
+                        .slice(ndarray::s![low_x, low_y..far_y, low_z..far_z])
                         .iter()
                         .cloned()
                         .collect(),
                 )
                 .unwrap();
                 hc_map
-                    .slice_mut(ndarray::s![
-                        0..half_extent,
-                        half_extent..far_y,
-                        half_extent..far_z,
-                    ])
+                    .slice_mut(ndarray::s![0..low_x, low_y..far_y, low_z..far_z])
                     .assign(&x_near_plane_magnetic);
 
                 let x_far_plane_magnetic = ndarray::Array2::from_shape_vec(
                     (simulation_y, simulation_z),
                     hc_map
-                        .slice(ndarray::s![
-                            far_x - 1,
-                            half_extent..far_y,
-                            half_extent..far_z,
-                        ])
+                        .slice(ndarray::s![far_x - 1, low_y..far_y, low_z..far_z])
                         .iter()
                         .cloned()
                         .collect(),
@@ -1476,41 +4081,29 @@ pub mod gltf_importer {
                 hc_map
                     .slice_mut(ndarray::s![
                         far_x..self.grid_dimension[0] as usize,
-                        half_extent..far_y,
-                        half_extent..far_z,
+                        low_y..far_y,
+                        low_z..far_z
                     ])
                     .assign(&x_far_plane_magnetic);
 
                 let y_near_plane_magnetic = ndarray::Array2::from_shape_vec(
                     (simulation_x, simulation_z),
                     hc_map
-                        .slice(ndarray::s![
-                            half_extent..far_x,
-                            half_extent,
-                            half_extent..far_z,
-                        ])
+                        .slice(ndarray::s![low_x..far_x, low_y, low_z..far_z])
                         .iter()
                         .cloned()
                         .collect(),
                 )
                 .unwrap();
                 hc_map
-                    .slice_mut(ndarray::s![
-                        half_extent..far_x,
-                        0..half_extent,
-                        half_extent..far_z,
-                    ])
+                    .slice_mut(ndarray::s![low_x..far_x, 0..low_y, low_z..far_z])
                     .permuted_axes([1, 0, 2])
                     .assign(&y_near_plane_magnetic);
 
                 let y_far_plane_magnetic = ndarray::Array2::from_shape_vec(
                     (simulation_x, simulation_z),
                     hc_map
-                        .slice(ndarray::s![
-                            half_extent..far_x,
-                            far_y - 1,
-                            half_extent..far_z,
-                        ])
+                        .slice(ndarray::s![low_x..far_x, far_y - 1, low_z..far_z])
                         .iter()
                         .cloned()
                         .collect(),
@@ -1518,9 +4111,9 @@ pub mod gltf_importer {
                 .unwrap();
                 hc_map
                     .slice_mut(ndarray::s![
-                        half_extent..far_x,
+                        low_x..far_x,
                         far_y..self.grid_dimension[1] as usize,
-                        half_extent..far_z,
+                        low_z..far_z
                     ])
                     .permuted_axes([1, 0, 2])
                     .assign(&y_far_plane_magnetic);
@@ -1528,35 +4121,32 @@ pub mod gltf_importer {
                 let mut z_near_plane_magnetic =
                     ndarray::Array2::default((simulation_x, simulation_y).f());
                 z_near_plane_magnetic.assign(&hc_map.slice(ndarray::s![
-                    half_extent..far_x,
-                    half_extent..far_y,
-                    half_extent,
+                    low_x..far_x,
+                    low_y..far_y,
+                    low_z
                 ]));
                 hc_map
-                    .slice_mut(ndarray::s![
-                        half_extent..far_x,
-                        half_extent..far_y,
-                        0..half_extent,
-                    ])
+                    .slice_mut(ndarray::s![low_x..far_x, low_y..far_y, 0..low_z])
                     .permuted_axes([2, 0, 1])
                     .assign(&z_near_plane_magnetic);
 
                 let mut z_far_plane_magnetic =
                     ndarray::Array2::default((simulation_x, simulation_y).f());
                 z_far_plane_magnetic.assign(&hc_map.slice(ndarray::s![
-                    half_extent..far_x,
-                    half_extent..far_y,
-                    far_z - 1,
+                    low_x..far_x,
+                    low_y..far_y,
+                    far_z - 1
                 ]));
                 hc_map
                     .slice_mut(ndarray::s![
-                        half_extent..far_x,
-                        half_extent..far_y,
-                        far_z..self.grid_dimension[2] as usize,
+                        low_x..far_x,
+                        low_y..far_y,
+                        far_z..self.grid_dimension[2] as usize
                     ])
                     .permuted_axes([2, 0, 1])
                     .assign(&z_far_plane_magnetic);
 
+                progress.advance(1);
                 let pml_magnetic_views = [
                     (x_near_plane_magnetic, x_near_plane_electric),
                     (x_far_plane_magnetic, x_far_plane_electric),
@@ -1565,10 +4155,11 @@ pub mod gltf_importer {
                     (z_near_plane_magnetic, z_near_plane_electric),
                     (z_far_plane_magnetic, z_far_plane_electric),
                 ]
-                .map(|(h, e)| {
-                    ndarray::Zip::from(&h).and(&e).par_map_collect(|h, e| {
-                        (-(self.pml_sigma + self.pml_alpha) * e.y / h.y * self.dt).exp()
-                    })
+                .map(|(_, e)| {
+                    // matched when the magnetic decay rate equals the electric one,
+                    // sigma_m / permeability = sigma / permittivity
+                    ndarray::Zip::from(&e)
+                        .par_map_collect(|e| (-(self.pml_sigma + self.pml_alpha) * e.y).exp())
                 })
                 .map(|c| {
                     device
@@ -1593,9 +4184,44 @@ pub mod gltf_importer {
                         .create_view(&wgpu::TextureViewDescriptor::default())
                 });
 
+                progress.advance(1);
                 pml_constants = Some((pml_electric_views, pml_magnetic_views));
             }
 
+            // ec3 = dt / permittivity, PEC cells come out infinite
+            let permittivity = ndarray::Zip::from(&ec_map).par_map_collect(|c| self.dt / c.y);
+
+            if let Some(path) = dump_permittivity {
+                let mut dds = ddsfile::Dds::new_dxgi(ddsfile::NewDxgiParams {
+                    height: self.grid_dimension[1],
+                    width: self.grid_dimension[0],
+                    depth: Some(self.grid_dimension[2]),
+                    format: ddsfile::DxgiFormat::R32_Float,
+                    mipmap_levels: None,
+                    array_layers: None,
+                    caps2: None,
+                    is_cubemap: false,
+                    resource_dimension: ddsfile::D3D10ResourceDimension::Texture3D,
+                    alpha_mode: ddsfile::AlphaMode::Unknown,
+                })?;
+                dds.data =
+                    bytemuck::cast_slice(permittivity.as_slice_memory_order().unwrap()).to_vec();
+
+                let mut file = std::fs::File::create(path)?;
+                dds.write(&mut file)?;
+                println!("Permittivity map written to {}", path.display());
+            }
+
+            let permittivity_map = device.create_texture_with_data(
+                queue,
+                &wgpu::TextureDescriptor {
+                    format: wgpu::TextureFormat::R32Float,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC,
+                    ..common_desc
+                },
+                bytemuck::cast_slice(permittivity.as_slice_memory_order().unwrap()),
+            );
+
             let electric_constants_map = device
                 .create_texture_with_data(
                     queue,
@@ -1612,22 +4238,38 @@ pub mod gltf_importer {
                 )
                 .create_view(&wgpu::TextureViewDescriptor::default());
 
-            (
-                electric_constants_map,
-                magnetic_constants_map,
-                pml_constants,
-            )
+            // two Rg32Float maps and the R32Float permittivity
+            let bytes = ec_map.len() * std::mem::size_of::<f32>() * 5;
+            println!(
+                "Built and uploaded the constants maps ({:.1} MiB) in {:.1} s",
+                bytes as f32 / (1 << 20) as f32,
+                start.elapsed().as_secs_f32()
+            );
+
+            Ok(ConstantsMaps {
+                electric: electric_constants_map,
+                magnetic: magnetic_constants_map,
+                permittivity: permittivity_map,
+                pml: pml_constants,
+            })
         }
 
+        #[allow(clippy::too_many_arguments)]
         fn process_node(
             &mut self,
             node: gltf::Node,
             transform: nalgebra::Matrix4<f32>,
             buffers: &Vec<gltf::buffer::Data>,
             constants: FDTDConstants,
-        ) {
+            overrides: &std::collections::BTreeMap<String, FDTDConstants>, // by glTF material name
+            subpixel_samples: u32,
+            axes: ParityAxes,
+            placement: &Placement,
+            progress: &Progress,
+        ) -> FillReport {
             let transform = transform
                 * nalgebra::Matrix4::from_iterator(node.transform().matrix().into_iter().flatten());
+            let mut report = FillReport::default();
             if let Some(mesh) = node.mesh() {
                 for primitive in mesh.primitives() {
                     let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
@@ -1648,113 +4290,834 @@ pub mod gltf_importer {
                         })
                         .collect();
 
-                    let simulation_x = self.grid_dimension[0] - self.extra_extent;
-                    let simulation_y = self.grid_dimension[1] - self.extra_extent;
-                    let simulation_z = self.grid_dimension[2] - self.extra_extent;
-
-                    let flag_map: ndarray::Array3<std::sync::Mutex<u8>> =
-                        ndarray::Array3::default((
-                            simulation_x as usize,
-                            simulation_y as usize,
-                            simulation_z as usize,
-                        ));
-
-                    let half_extent = self.extra_extent / 2;
-                    indices.chunks(3).par_bridge().for_each(|triangle| {
-                        let v0 = vertices[triangle[0] as usize];
-                        let v1 = vertices[triangle[1] as usize];
-                        let v2 = vertices[triangle[2] as usize];
-                        let edge1 = v1 - v0;
-                        let edge2 = v2 - v0;
-                        let ray = nalgebra::vector![0.0f32, 0.0, 1.0];
-                        let min_x = v0.x.min(v1.x.min(v2.x)).floor().max(0.) as u32;
-                        let max_x = v0.x.max(v1.x.max(v2.x)).ceil().max(0.) as u32;
-                        let min_y = v0.y.min(v1.y.min(v2.y)).floor().max(0.) as u32;
-                        let max_y = v0.y.max(v1.y.max(v2.y)).ceil().max(0.) as u32;
-                        (min_x..=max_x).into_par_iter().for_each(|x| {
-                            if x < half_extent || x >= self.grid_dimension[0] - half_extent {
-                                return;
+                    let constants = primitive
+                        .material()
+                        .name()
+                        .and_then(|name| overrides.get(name))
+                        .copied()
+                        .unwrap_or(constants);
+                    let (fraction, odd_columns) = self.cover(
+                        &vertices,
+                        &indices,
+                        subpixel_samples,
+                        axes,
+                        &placement.brick,
+                        progress,
+                    );
+                    report.odd_columns.extend(odd_columns);
+                    for offset in placement.instances.iter() {
+                        let origin = [0, 1, 2].map(|i| placement.brick.origin[i] + offset[i]);
+                        report.cells += self.stamp(&fraction, origin, constants);
+                    }
+                }
+            }
+            for node in node.children() {
+                report.merge(self.process_node(
+                    node,
+                    transform,
+                    buffers,
+                    constants,
+                    overrides,
+                    subpixel_samples,
+                    axes,
+                    placement,
+                    progress,
+                ));
+            }
+            report
+        }
+
+        /// Surface crossings of the rays cast along `axis`, `samples` x `samples` of them per
+        /// cell column of `brick`. Ray `i` of brick cell `x` runs at `x + (i + 0.5) / samples - 0.5`
+        /// from the first cell, rays are numbered with the second axis of the plane fastest. Each crossing holds the
+        /// ray, the grid coordinate along `axis` and whether the surface faces along the ray,
+        /// sorted by ray and coordinate.
+        fn cast_rays(
+            &self,
+            vertices: &[nalgebra::Vector3<f32>],
+            indices: &[u32],
+            axis: super::SliceMode,
+            samples: u32,
+            brick: &Brick,
+            progress: &Progress,
+        ) -> Vec<(usize, f32, bool)> {
+            let [u, v] = axis.plane_axes();
+            let n = samples as usize;
+            let rays = [u, v].map(|i| brick.size[i] * n);
+            // grid coordinate of the first cell of the brick
+            let interior = self.interior_origin();
+            let first = [0, 1, 2].map(|i| (brick.origin[i] + interior[i]) as f32);
+            let to_ray = |axis: usize, grid: f32| (grid - first[axis] + 0.5) * samples as f32 - 0.5;
+            let from_ray =
+                |axis: usize, ray: usize| (ray as f32 + 0.5) / samples as f32 - 0.5 + first[axis];
+
+            let ray = nalgebra::Vector3::ith(axis.axis(), 1.0f32);
+            let mut crossings: Vec<_> = indices
+                .par_chunks(3)
+                .flat_map_iter(|triangle| {
+                    progress.advance(1);
+                    let [v0, v1, v2] = [0, 1, 2].map(|i| vertices[triangle[i] as usize]);
+                    let edge1 = v1 - v0;
+                    let edge2 = v2 - v0;
+                    // zero for faces parallel to the ray, which are never crossed
+                    let denominator =
+                        nalgebra::Matrix3::from_columns(&[edge1, edge2, -ray]).determinant();
+                    let [u_rays, v_rays] = [(u, rays[0]), (v, rays[1])].map(|(axis, rays)| {
+                        let low = to_ray(axis, v0[axis].min(v1[axis]).min(v2[axis]));
+                        let high = to_ray(axis, v0[axis].max(v1[axis]).max(v2[axis]));
+                        low.ceil().clamp(0.0, rays as f32) as usize
+                            ..(high.floor() + 1.0).clamp(0.0, rays as f32) as usize
+                    });
+                    let mut crossings = vec![];
+                    if denominator == 0.0 {
+                        return crossings;
+                    }
+                    for i in u_rays {
+                        for j in v_rays.clone() {
+                            let mut p = nalgebra::Vector3::zeros();
+                            p[u] = from_ray(u, i);
+                            p[v] = from_ray(v, j);
+                            let a = nalgebra::Matrix3::from_columns(&[p - v0, edge2, -ray])
+                                .determinant()
+                                / denominator;
+                            let b = nalgebra::Matrix3::from_columns(&[edge1, p - v0, -ray])
+                                .determinant()
+                                / denominator;
+                            if a >= 0.0 && b >= 0.0 && a + b <= 1.0 {
+                                // the ray starts at 0 along the axis
+                                let t = nalgebra::Matrix3::from_columns(&[edge1, edge2, p - v0])
+                                    .determinant()
+                                    / denominator;
+                                crossings.push((i * rays[1] + j, t, denominator > 0.0));
                             }
-                            (min_y..=max_y).into_par_iter().for_each(|y| {
-                                if y < half_extent || y >= self.grid_dimension[1] - half_extent {
-                                    return;
-                                }
-                                let p = nalgebra::vector![x as f32, y as f32, 0.0];
-                                let denominator =
-                                    nalgebra::Matrix3::from_columns(&[edge1, edge2, -ray])
-                                        .determinant();
-                                let nominator_u =
-                                    nalgebra::Matrix3::from_columns(&[p - v0, edge2, -ray])
-                                        .determinant();
-                                let nominator_v =
-                                    nalgebra::Matrix3::from_columns(&[edge1, p - v0, -ray])
-                                        .determinant();
-                                let nominator_t =
-                                    nalgebra::Matrix3::from_columns(&[edge1, edge2, p - v0])
-                                        .determinant();
-                                if denominator != 0.0 {
-                                    let u = nominator_u / denominator;
-                                    let v = nominator_v / denominator;
-                                    let t = nominator_t / denominator;
-                                    if u >= 0.0 && v >= 0.0 && u + v <= 1.0 {
-                                        let h = p + ray * t;
-                                        let x = h.x.round() as u32 - half_extent;
-                                        let y = h.y.round() as u32 - half_extent;
-                                        let z = (h.z.max(0.).round() as u32).max(half_extent)
-                                            - half_extent;
-
-                                        if z < simulation_z - 1 {
-                                            let x = x as usize;
-                                            let y = y as usize;
-                                            let z = z as usize;
-                                            *flag_map[[x, y, z]].lock().unwrap() = 1;
-                                        }
-                                    }
+                        }
+                    }
+                    crossings
+                })
+                .collect();
+            crossings.par_sort_unstable_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+            crossings
+        }
+
+        /// Fraction of each cell of `brick` inside the mesh, found by the parity of the surface
+        /// crossings along the rays of each of `axes`. With more than one axis a cell is inside
+        /// when most axes agree. Above one sample, cells the surface cuts get the fraction of
+        /// their volume inside, averaged over the axes, otherwise it is 0 or 1. Also returns the
+        /// cell columns with an odd number of crossings, which a watertight mesh never has.
+        fn cover(
+            &self,
+            vertices: &[nalgebra::Vector3<f32>],
+            indices: &[u32],
+            samples: u32,
+            axes: ParityAxes,
+            brick: &Brick,
+            progress: &Progress,
+        ) -> (ndarray::Array3<f32>, Vec<(super::SliceMode, [isize; 2])>) {
+            let interior = self.interior_origin();
+            let n = samples as usize;
+            let weight = 1.0 / (n * n) as f32;
+            let axes = axes.axes();
+
+            let mut fraction = ndarray::Array3::<f32>::zeros(brick.size.f());
+            let mut votes = ndarray::Array3::<u8>::zeros(brick.size.f());
+            let mut odd_columns = vec![];
+            for &axis in axes {
+                let crossings = self.cast_rays(vertices, indices, axis, samples, brick, progress);
+                let [u, v] = axis.plane_axes();
+                let v_rays = brick.size[v] * n;
+                // grid coordinate of the first cell of the brick along the rays
+                let offset = (brick.origin[axis.axis()] + interior[axis.axis()]) as f32;
+                // each task owns one column of cells along the rays
+                let odd = ndarray::Zip::indexed(fraction.lanes_mut(ndarray::Axis(axis.axis())))
+                    .and(votes.lanes_mut(ndarray::Axis(axis.axis())))
+                    .par_map_collect(|(i, j), mut fraction, mut votes| {
+                        progress.advance(1);
+                        let mut coverage = vec![0f32; fraction.len()];
+                        let mut is_odd = false;
+                        for (k, l) in (0..n).flat_map(|k| (0..n).map(move |l| (k, l))) {
+                            let index = (i * n + k) * v_rays + j * n + l;
+                            let start = crossings.partition_point(|c| c.0 < index);
+                            let end = crossings.partition_point(|c| c.0 <= index);
+                            let mut ray: Vec<_> = crossings[start..end]
+                                .iter()
+                                .map(|&(_, t, facing)| (t, facing))
+                                .collect();
+                            // a ray through an edge or vertex hits every face sharing it, the
+                            // copies face the same way unless the ray only grazes the surface
+                            ray.dedup_by(|b, a| b.1 == a.1 && b.0 - a.0 < 1e-4);
+                            is_odd |= ray.len() % 2 == 1;
+                            // inside between pairs of crossings, an unpaired last one is dropped
+                            for pair in ray.chunks_exact(2) {
+                                // brick cell z now spans [z, z + 1]
+                                let [start, end] = [pair[0].0, pair[1].0].map(|t| t - offset + 0.5);
+                                let first =
+                                    start.floor().clamp(0.0, coverage.len() as f32) as usize;
+                                let last = end.ceil().clamp(0.0, coverage.len() as f32) as usize;
+                                for (z, coverage) in
+                                    coverage.iter_mut().enumerate().take(last).skip(first)
+                                {
+                                    let overlap = end.min(z as f32 + 1.0) - start.max(z as f32);
+                                    *coverage += overlap.max(0.0) * weight;
                                 }
-                            })
-                        });
+                            }
+                        }
+                        for ((fraction, votes), coverage) in
+                            fraction.iter_mut().zip(votes.iter_mut()).zip(coverage)
+                        {
+                            *fraction += coverage / axes.len() as f32;
+                            *votes += (coverage >= 0.5) as u8;
+                        }
+                        is_odd
                     });
+                odd_columns.extend(odd.indexed_iter().filter(|(_, odd)| **odd).map(
+                    |((i, j), _)| {
+                        (
+                            axis,
+                            [brick.origin[u] + i as isize, brick.origin[v] + j as isize],
+                        )
+                    },
+                ));
+            }
 
-                    let accumulator: ndarray::Array3<std::sync::Mutex<u8>> =
-                        ndarray::Array3::default((
-                            simulation_x as usize,
-                            simulation_y as usize,
-                            simulation_z as usize,
-                        ));
-
-                    (0..simulation_z).for_each(|z| {
-                        (0..simulation_x).into_par_iter().for_each(|x| {
-                            (0..simulation_y).into_par_iter().for_each(|y| {
-                                let idx_x = x as usize;
-                                let idx_y = y as usize;
-                                let idx_z = z as usize;
-
-                                let grid_x = (x + half_extent) as usize;
-                                let grid_y = (y + half_extent) as usize;
-                                let grid_z = (z + half_extent) as usize;
-                                let mut acc_write =
-                                    accumulator[[idx_x, idx_y, idx_z]].lock().unwrap();
-                                *acc_write = *flag_map[[idx_x, idx_y, idx_z]].lock().unwrap();
-                                if z > 0 {
-                                    *acc_write +=
-                                        *accumulator[[idx_x, idx_y, idx_z - 1]].lock().unwrap();
-                                }
-                                if *acc_write % 2 == 1 {
-                                    *self.electric_constants[[grid_x, grid_y, grid_z]]
-                                        .lock()
-                                        .unwrap() = nalgebra::vector![constants.ec2, constants.ec3];
-                                    *self.magnetic_constants[[grid_x, grid_y, grid_z]]
-                                        .lock()
-                                        .unwrap() = nalgebra::vector![constants.hc2, constants.hc3];
-                                }
-                            });
-                        })
+            ndarray::Zip::from(&mut fraction)
+                .and(&votes)
+                .par_for_each(|fraction, &votes| {
+                    *fraction = if samples > 1 {
+                        fraction.min(1.0)
+                    } else if votes as usize * 2 > axes.len() {
+                        1.0
+                    } else {
+                        0.0
+                    };
+                });
+            (fraction, odd_columns)
+        }
+
+        /// Blends `constants` into the cells by the `fraction` of `cover`, its first cell placed
+        /// at interior cell `origin`. Cells outside of the domain are dropped. Returns the cells
+        /// given constants of the model, in part or in full.
+        fn stamp(
+            &mut self,
+            fraction: &ndarray::Array3<f32>,
+            origin: [isize; 3],
+            constants: FDTDConstants,
+        ) -> usize {
+            let interior = self.interior_origin();
+            let simulation = self.interior_size();
+            let size = fraction.shape();
+            // overlap of the brick and the domain, in brick cells
+            let [x, y, z] = [0, 1, 2].map(|i| {
+                (origin[i].max(0) - origin[i])
+                    ..((origin[i] + size[i] as isize).min(simulation[i]) - origin[i])
+            });
+            if x.is_empty() || y.is_empty() || z.is_empty() {
+                return 0;
+            }
+            let [gx, gy, gz] = [(0, &x), (1, &y), (2, &z)].map(|(i, range)| {
+                range.start + origin[i] + interior[i]..range.end + origin[i] + interior[i]
+            });
+            let grid = ndarray::s![gx, gy, gz];
+            let dx = self.dx;
+            let cells = std::sync::atomic::AtomicUsize::new(0);
+            ndarray::Zip::from(self.electric_constants.slice_mut(grid))
+                .and(self.magnetic_constants.slice_mut(grid))
+                .and(fraction.slice(ndarray::s![x, y, z]))
+                .par_for_each(|electric, magnetic, &fraction| {
+                    if fraction <= 0.0 {
+                        return;
+                    }
+                    cells.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    *electric = blend(
+                        *electric,
+                        nalgebra::vector![constants.ec2, constants.ec3],
+                        fraction,
+                        dx,
+                    );
+                    *magnetic = blend(
+                        *magnetic,
+                        nalgebra::vector![constants.hc2, constants.hc3],
+                        fraction,
+                        dx,
+                    );
+                });
+            cells.into_inner()
+        }
+    }
+
+    /// Bounds of the vertices of a node and its children in grid coordinates, from the extent
+    /// glTF records for every position accessor.
+    fn node_bounds(
+        node: gltf::Node,
+        transform: nalgebra::Matrix4<f32>,
+    ) -> Option<[nalgebra::Vector3<f32>; 2]> {
+        let transform = transform
+            * nalgebra::Matrix4::from_iterator(node.transform().matrix().into_iter().flatten());
+        let mut corners = vec![];
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                let bounds = primitive.bounding_box();
+                for corner in 0..8 {
+                    let vertex = [0, 1, 2].map(|i| {
+                        if corner >> i & 1 == 0 {
+                            bounds.min[i]
+                        } else {
+                            bounds.max[i]
+                        }
                     });
+                    corners.push(
+                        (transform * nalgebra::vector![vertex[0], vertex[1], vertex[2], 1.0]).xyz(),
+                    );
                 }
             }
-            for node in node.children() {
-                self.process_node(node, transform, buffers, constants);
+        }
+        corners
+            .iter()
+            .map(|corner| [*corner; 2])
+            .chain(
+                node.children()
+                    .filter_map(|node| node_bounds(node, transform)),
+            )
+            .reduce(|a, b| [a[0].inf(&b[0]), a[1].sup(&b[1])])
+    }
+
+    /// Primitives and triangles of a node and its children.
+    fn count_primitives(node: gltf::Node) -> (usize, usize) {
+        let (mut primitives, mut triangles) = (0, 0);
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                primitives += 1;
+                triangles += primitive.indices().map_or(0, |indices| indices.count() / 3);
+            }
+        }
+        for (p, t) in node.children().map(count_primitives) {
+            primitives += p;
+            triangles += t;
+        }
+        (primitives, triangles)
+    }
+
+    /// Constants of a cell filled to `fraction` by the material of constants `inside`, the
+    /// rest keeping `outside`. A conductor, of zero constants, is never mixed.
+    fn blend(
+        outside: nalgebra::Vector2<f32>,
+        inside: nalgebra::Vector2<f32>,
+        fraction: f32,
+        dx: f32,
+    ) -> nalgebra::Vector2<f32> {
+        if fraction >= 1.0 {
+            return inside;
+        }
+        if inside.y == 0.0 || outside.y == 0.0 {
+            return if fraction >= 0.5 { inside } else { outside };
+        }
+        // the second constant is dt / material, the material is averaged by volume
+        let c3 = 1.0 / (fraction / inside.y + (1.0 - fraction) / outside.y);
+        nalgebra::vector![c3 / dx, c3]
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const DX: f32 = 0.05;
+
+        /// Writes a glTF of one mesh to the temporary directory, named after the test using it.
+        fn write_mesh(
+            name: &str,
+            vertices: &[nalgebra::Vector3<f32>],
+            indices: &[u32],
+        ) -> std::path::PathBuf {
+            let mut data: Vec<u8> = vertices
+                .iter()
+                .flat_map(|vertex| vertex.iter().flat_map(|v| v.to_le_bytes()))
+                .collect();
+            let positions = data.len();
+            data.extend(indices.iter().flat_map(|index| index.to_le_bytes()));
+            let buffer = format!("{}.bin", name);
+            crate::temp_files::temp_file(&buffer, &data);
+            let [min, max] = [f32::min, f32::max]
+                .map(|fold| [0, 1, 2].map(|i| vertices.iter().map(|v| v[i]).reduce(fold).unwrap()));
+            let gltf = serde_json::json!({
+                "asset": { "version": "2.0" },
+                "scene": 0,
+                "scenes": [{ "nodes": [0] }],
+                "nodes": [{ "mesh": 0 }],
+                "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1 }] }],
+                "buffers": [{ "uri": buffer, "byteLength": data.len() }],
+                "bufferViews": [
+                    { "buffer": 0, "byteOffset": 0, "byteLength": positions },
+                    { "buffer": 0, "byteOffset": positions, "byteLength": data.len() - positions },
+                ],
+                "accessors": [
+                    {
+                        "bufferView": 0,
+                        "componentType": 5126,
+                        "count": vertices.len(),
+                        "type": "VEC3",
+                        "min": min,
+                        "max": max,
+                    },
+                    {
+                        "bufferView": 1,
+                        "componentType": 5125,
+                        "count": indices.len(),
+                        "type": "SCALAR",
+                    },
+                ],
+            });
+            crate::temp_files::temp_file(&format!("{}.gltf", name), gltf.to_string())
+        }
+
+        /// Triangles of a box around `center`, facing outwards.
+        fn box_mesh(center: [f32; 3], half: [f32; 3]) -> (Vec<nalgebra::Vector3<f32>>, Vec<u32>) {
+            let vertices = (0..8)
+                .map(|corner| {
+                    nalgebra::Vector3::from(
+                        [0, 1, 2]
+                            .map(|i| center[i] + half[i] * (2 * ((corner >> i) & 1) - 1) as f32),
+                    )
+                })
+                .collect::<Vec<_>>();
+            // two triangles per face, through the diagonal of the corners 0 and 3 of the face
+            let mut indices = vec![];
+            for axis in 0..3 {
+                let [a, b] = [1 << ((axis + 1) % 3), 1 << ((axis + 2) % 3)];
+                for side in [0, 1 << axis] {
+                    indices.extend([side, side | a, side | a | b, side, side | a | b, side | b]);
+                }
+            }
+            let center = nalgebra::Vector3::from(center);
+            orient_outwards(&vertices, &mut indices, |_| center);
+            (vertices, indices)
+        }
+
+        /// Flips the triangles facing towards the point `inner` gives for their centroid.
+        fn orient_outwards(
+            vertices: &[nalgebra::Vector3<f32>],
+            indices: &mut [u32],
+            inner: impl Fn(nalgebra::Vector3<f32>) -> nalgebra::Vector3<f32>,
+        ) {
+            for triangle in indices.chunks_exact_mut(3) {
+                let [v0, v1, v2] = [0, 1, 2].map(|i| vertices[triangle[i] as usize]);
+                let centroid = (v0 + v1 + v2) / 3.0;
+                if (v1 - v0)
+                    .cross(&(v2 - v0))
+                    .dot(&(centroid - inner(centroid)))
+                    < 0.0
+                {
+                    triangle.swap(1, 2);
+                }
+            }
+        }
+
+        /// An importer over [-1, 1]^3 without padding, and the world center of each cell.
+        fn importer() -> Importer {
+            Importer::new(
+                [[-1.0, 1.0]; 3],
+                0.5 * DX,
+                DX,
+                MaterialConstants {
+                    permittivity: 1.0,
+                    permeability: 1.0,
+                },
+                [[0; 2]; 3],
+                0.0,
+                0.0,
+            )
+        }
+
+        /// Voxelizes the mesh into a fresh importer and returns the world center of each cell
+        /// with whether the mesh filled it.
+        fn voxelize(
+            name: &str,
+            (vertices, indices): (Vec<nalgebra::Vector3<f32>>, Vec<u32>),
+            rotation: [f32; 3],
+            subpixel_samples: u32,
+            parity_axes: ParityAxes,
+        ) -> Vec<(nalgebra::Vector3<f32>, bool)> {
+            let path = write_mesh(name, &vertices, &indices);
+            let mut importer = importer();
+            let background = importer.electric_constants[[0, 0, 0]];
+            importer
+                .load_gltf(
+                    &path,
+                    [1.0; 3],
+                    rotation,
+                    [0.0; 3],
+                    Material::Dielectric(MaterialConstants {
+                        permittivity: 4.0,
+                        permeability: 1.0,
+                    }),
+                    &Default::default(),
+                    subpixel_samples,
+                    parity_axes,
+                    None,
+                    true,
+                )
+                .unwrap();
+            std::fs::remove_file(path.with_extension("bin")).unwrap();
+            std::fs::remove_file(path).unwrap();
+            importer
+                .electric_constants
+                .indexed_iter()
+                .map(|((x, y, z), constants)| {
+                    (
+                        nalgebra::vector![x as f32, y as f32, z as f32] * DX
+                            - importer.shift_vector,
+                        *constants != background,
+                    )
+                })
+                .collect()
+        }
+
+        /// Checks that a cell is filled exactly when `depth`, how far inside the mesh a point
+        /// is, is positive at its center. Centers within 1e-3 of the surface are skipped.
+        /// Returns the centers of the filled cells.
+        fn check_footprint(
+            cells: &[(nalgebra::Vector3<f32>, bool)],
+            depth: impl Fn(nalgebra::Vector3<f32>) -> f32,
+        ) -> Vec<nalgebra::Vector3<f32>> {
+            for (center, filled) in cells {
+                let depth = depth(*center);
+                if depth.abs() > 1e-3 {
+                    assert_eq!(*filled, depth > 0.0, "cell at {:?}", center);
+                }
+            }
+            cells
+                .iter()
+                .filter(|(_, filled)| *filled)
+                .map(|(center, _)| *center)
+                .collect()
+        }
+
+        /// How far inside the box around `center` a point is, negative outside.
+        fn box_depth(center: [f32; 3], half: [f32; 3]) -> impl Fn(nalgebra::Vector3<f32>) -> f32 {
+            move |p| {
+                (0..3)
+                    .map(|i| half[i] - (p[i] - center[i]).abs())
+                    .fold(f32::INFINITY, f32::min)
+            }
+        }
+
+        #[test]
+        fn rotated_box_fills_its_rotated_footprint() {
+            let half = [0.61, 0.21, 0.31];
+            let rotation = [0.0, 0.0, 30.0];
+            let cells = voxelize(
+                "rotated-box",
+                box_mesh([0.0; 3], half),
+                rotation,
+                1,
+                ParityAxes::Z,
+            );
+            let to_box = nalgebra::Rotation3::from_euler_angles(
+                rotation[0].to_radians(),
+                rotation[1].to_radians(),
+                rotation[2].to_radians(),
+            )
+            .inverse();
+            // a cell is filled where the ray through its center is inside the box
+            let filled = check_footprint(&cells, |p| box_depth([0.0; 3], half)(to_box * p));
+
+            let volume = 8.0 * half.iter().product::<f32>();
+            let expected = volume / DX.powi(3);
+            assert!(
+                (filled.len() as f32 - expected).abs() < 0.05 * expected,
+                "{} cells filled instead of about {}",
+                filled.len(),
+                expected
+            );
+            // bounds of the rotated box, the filled centers lie within half a cell of them
+            let corners = box_mesh([0.0; 3], half).0;
+            let rotate = to_box.inverse();
+            for i in 0..3 {
+                let [low, high] = [f32::min, f32::max].map(|fold| {
+                    corners
+                        .iter()
+                        .map(|c| (rotate * c)[i])
+                        .reduce(fold)
+                        .unwrap()
+                });
+                let [filled_low, filled_high] = [f32::min, f32::max]
+                    .map(|fold| filled.iter().map(|c| c[i]).reduce(fold).unwrap());
+                assert!(
+                    filled_low >= low && filled_low - low < DX,
+                    "axis {}: filled from {}, the box from {}",
+                    i,
+                    filled_low,
+                    low
+                );
+                assert!(
+                    filled_high <= high && high - filled_high < DX,
+                    "axis {}: filled to {}, the box to {}",
+                    i,
+                    filled_high,
+                    high
+                );
+            }
+        }
+
+        #[test]
+        fn open_cylinder_is_filled_by_the_axes_that_cross_its_wall() {
+            // along x, without end caps, so rays along x never cross it
+            let [length, radius, segments] = [0.61, 0.31, 96.0];
+            let ring = |k: u32| {
+                let angle = k as f32 / segments * std::f32::consts::TAU;
+                [radius * angle.cos(), radius * angle.sin()]
+            };
+            let vertices = (0..segments as u32)
+                .flat_map(|k| {
+                    let [y, z] = ring(k);
+                    [-length, length].map(|x| nalgebra::vector![x, y, z])
+                })
+                .collect::<Vec<_>>();
+            let mut indices = (0..segments as u32)
+                .flat_map(|k| {
+                    let [a, b] = [2 * k, 2 * ((k + 1) % segments as u32)];
+                    [a, a + 1, b + 1, a, b + 1, b]
+                })
+                .collect::<Vec<_>>();
+            orient_outwards(&vertices, &mut indices, |p| {
+                nalgebra::vector![p.x, 0.0, 0.0]
+            });
+            let mesh = (vertices, indices);
+
+            let cells = voxelize("cylinder-x", mesh.clone(), [0.0; 3], 1, ParityAxes::X);
+            assert!(cells.iter().all(|(_, filled)| !filled));
+            // the rays along y and z outvote the ones along x, and nothing streaks past the ends
+            let cells = voxelize("cylinder-all", mesh, [0.0; 3], 1, ParityAxes::All);
+            check_footprint(&cells, |p| {
+                // the polygon lies within 2e-4 of the circle
+                (length - p.x.abs()).min(radius - p.yz().norm())
+            });
+        }
+
+        #[test]
+        fn thin_plate_fills_one_layer_without_streaks() {
+            // a fifth of a cell thick around the cell centers at z = 0
+            let half = [0.41, 0.31, 0.1 * DX];
+            let mesh = box_mesh([0.0; 3], half);
+            // too thin to cover half a cell, so the plain fill leaves it out entirely
+            let cells = voxelize("plate", mesh.clone(), [0.0; 3], 1, ParityAxes::Z);
+            assert!(cells.iter().all(|(_, filled)| !filled));
+            // subpixel sampling blends the cells it cuts, 17 x 13 of them
+            let cells = voxelize("plate-blended", mesh, [0.0; 3], 4, ParityAxes::Z);
+            let filled = check_footprint(&cells, |p| {
+                box_depth([0.0; 3], [half[0], half[1], 0.5 * DX])(p)
+            });
+            assert_eq!(filled.len(), 17 * 13);
+        }
+
+        #[test]
+        fn boxes_sharing_a_face_fill_their_union() {
+            // the shared face at z = 0 runs through cell centers, and so do the edges along x
+            // and y of the side faces, which rays along x and y hit twice
+            let half = [0.41, 0.31, 0.155];
+            let (mut vertices, mut indices) = box_mesh([0.0, 0.0, -half[2]], half);
+            let (upper, upper_indices) = box_mesh([0.0, 0.0, half[2]], half);
+            let offset = vertices.len() as u32;
+            vertices.extend(upper);
+            indices.extend(upper_indices.into_iter().map(|index| index + offset));
+
+            let union = [half[0], half[1], 2.0 * half[2]];
+            for (name, axes) in [("stack-z", ParityAxes::Z), ("stack-all", ParityAxes::All)] {
+                let cells = voxelize(name, (vertices.clone(), indices.clone()), [0.0; 3], 1, axes);
+                let filled = check_footprint(&cells, box_depth([0.0; 3], union));
+                assert_eq!(filled.len(), 17 * 13 * 13, "{:?}", axes);
+            }
+        }
+
+        #[test]
+        fn constants_match_the_importer_with_per_cell_mutexes() {
+            let dielectric = |permittivity, permeability| {
+                Material::Dielectric(MaterialConstants {
+                    permittivity,
+                    permeability,
+                })
+            };
+            let mut importer = importer();
+            // partly filled cells of a dielectric, a magnetic material and a conductor, the
+            // last overlapping nothing
+            for (name, center, half, rotation, material, samples, axes) in [
+                (
+                    "golden-dielectric",
+                    [0.1, -0.05, 0.0],
+                    [0.61, 0.21, 0.31],
+                    [0.0, 0.0, 30.0],
+                    dielectric(4.0, 1.0),
+                    4,
+                    ParityAxes::Z,
+                ),
+                (
+                    "golden-magnetic",
+                    [-0.3, 0.35, 0.2],
+                    [0.2, 0.33, 0.27],
+                    [10.0, 20.0, 0.0],
+                    dielectric(2.0, 3.0),
+                    3,
+                    ParityAxes::All,
+                ),
+                (
+                    "golden-pec",
+                    [0.4, 0.4, -0.4],
+                    [0.15, 0.12, 0.3],
+                    [0.0, 45.0, 0.0],
+                    Material::Pec,
+                    1,
+                    ParityAxes::X,
+                ),
+            ] {
+                let (vertices, indices) = box_mesh(center, half);
+                let path = write_mesh(name, &vertices, &indices);
+                importer
+                    .load_gltf(
+                        &path,
+                        [1.0; 3],
+                        rotation,
+                        [0.0; 3],
+                        material,
+                        &Default::default(),
+                        samples,
+                        axes,
+                        None,
+                        true,
+                    )
+                    .unwrap();
+                std::fs::remove_file(path.with_extension("bin")).unwrap();
+                std::fs::remove_file(path).unwrap();
+            }
+            // cells off the background and the sums of both constants of each map, as the
+            // importer wrote them before it dropped its per-cell mutexes. That change kept the
+            // maps bit for bit, casting rays over the brick of a model since rounds partial
+            // fractions differently, by up to 3e-6 of a constant.
+            let expected = [
+                (4893, [30502.594378724694, 1525.1297418042086]),
+                (1565, [31582.21129772067, 1579.1105884108692]),
+            ];
+            let maps = [&importer.electric_constants, &importer.magnetic_constants];
+            for (map, (cells, sums)) in maps.into_iter().zip(expected) {
+                let background = map[[0, 0, 0]];
+                assert_eq!(map.iter().filter(|c| **c != background).count(), cells);
+                for (i, sum) in sums.into_iter().enumerate() {
+                    let actual = map.iter().map(|c| c[i] as f64).sum::<f64>();
+                    assert!(
+                        (actual - sum).abs() < 1e-8 * sum,
+                        "{} instead of {}",
+                        actual,
+                        sum
+                    );
+                }
             }
         }
     }
 }
+
+/// Copies a texture of 32-bit float channels back to the host, blocking until done.
+fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> anyhow::Result<Vec<f32>> {
+    let size = texture.size();
+    read_texture_region(
+        device,
+        queue,
+        texture,
+        [0; 3],
+        [size.width, size.height, size.depth_or_array_layers],
+    )
+}
+
+/// Copies the `size` texels of a 3D texture of 32-bit float channels starting at `origin`
+/// back to the host, x fastest, blocking until done.
+fn read_texture_region(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    origin: [u32; 3],
+    size: [u32; 3],
+) -> anyhow::Result<Vec<f32>> {
+    let size = wgpu::Extent3d {
+        width: size[0],
+        height: size[1],
+        depth_or_array_layers: size[2],
+    };
+    let unpadded_bytes_per_row = size.width * texture.format().block_size(None).unwrap();
+    let padded_bytes_per_row_padding = (wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
+        - unpadded_bytes_per_row % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+        % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded_bytes_per_row = unpadded_bytes_per_row + padded_bytes_per_row_padding;
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: (padded_bytes_per_row * size.height * size.depth_or_array_layers) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d {
+                x: origin[0],
+                y: origin[1],
+                z: origin[2],
+            },
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(size.height),
+            },
+        },
+        size,
+    );
+    let index = queue.submit(Some(encoder.finish()));
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    buffer.slice(..).map_async(wgpu::MapMode::Read, move |v| {
+        let _ = sender.send(v);
+    });
+    device.poll(wgpu::Maintain::WaitForSubmissionIndex(index));
+    receiver.recv()??;
+
+    let data = buffer
+        .slice(..)
+        .get_mapped_range()
+        .chunks(padded_bytes_per_row as usize)
+        .flat_map(|row| bytemuck::cast_slice::<u8, f32>(&row[..unpadded_bytes_per_row as usize]))
+        .copied()
+        .collect();
+    buffer.unmap();
+    Ok(data)
+}
+
+/// The f32 contents of a `COPY_SRC` buffer. Blocks until read back.
+fn read_buffer(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    source: &wgpu::Buffer,
+) -> anyhow::Result<Vec<f32>> {
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: source.size(),
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    encoder.copy_buffer_to_buffer(source, 0, &buffer, 0, source.size());
+    let index = queue.submit(Some(encoder.finish()));
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    buffer.slice(..).map_async(wgpu::MapMode::Read, move |v| {
+        let _ = sender.send(v);
+    });
+    device.poll(wgpu::Maintain::WaitForSubmissionIndex(index));
+    receiver.recv()??;
+    let data = bytemuck::cast_slice(&buffer.slice(..).get_mapped_range()).to_vec();
+    buffer.unmap();
+    Ok(data)
+}
@@ -0,0 +1,117 @@
+use super::shaders::ShaderPaths;
+
+/// Counts the cells whose E or H holds an infinite or NaN component, over the whole grid so
+/// that a blow-up in the PML is caught as well.
+pub struct NonFiniteCount {
+    counter: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::ComputePipeline,
+    workgroups: [u32; 3],
+}
+
+impl NonFiniteCount {
+    pub fn new(
+        device: &wgpu::Device,
+        shaders: &ShaderPaths,
+        electric_field_view: &wgpu::TextureView,
+        magnetic_field_view: &wgpu::TextureView,
+        dimension: [u32; 3],
+        workgroup: [u32; 3],
+    ) -> anyhow::Result<Self> {
+        let counter = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Non-finite Cells"),
+            size: 4,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let field_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D3,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Non-finite"),
+            entries: &[
+                field_entry(0),
+                field_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Non-finite"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(electric_field_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(magnetic_field_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: counter.as_entire_binding(),
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Non-finite"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Non-finite Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                shaders
+                    .builtin("fdtd/non-finite.wgsl")?
+                    .replace("WORKGROUP_X", workgroup[0].to_string().as_str())
+                    .replace("WORKGROUP_Y", workgroup[1].to_string().as_str())
+                    .replace("WORKGROUP_Z", workgroup[2].to_string().as_str())
+                    .into(),
+            ),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Non-finite"),
+            layout: Some(&pipeline_layout),
+            module: &shader_module,
+            entry_point: "count_non_finite",
+        });
+
+        Ok(Self {
+            counter,
+            bind_group,
+            pipeline,
+            workgroups: [0, 1, 2].map(|axis| dimension[axis].div_ceil(workgroup[axis])),
+        })
+    }
+
+    /// Counts the non-finite cells of the current fields into `counter`, a u32.
+    pub fn count(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.clear_buffer(&self.counter, 0, None);
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+        cpass.set_pipeline(&self.pipeline);
+        cpass.set_bind_group(0, &self.bind_group, &[]);
+        cpass.dispatch_workgroups(self.workgroups[0], self.workgroups[1], self.workgroups[2]);
+    }
+
+    pub fn counter(&self) -> &wgpu::Buffer {
+        &self.counter
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+};
+
+// largest parameter block is the volume excitation one
+const MAX_PARAM_SIZE: u32 = 80;
+// uniform slots are rounded up to a multiple of 16 bytes
+const SLOT_SIZE: u64 = 80;
+const SLOTS: u64 = 4096;
+
+struct UniformParams {
+    queue: std::sync::Arc<wgpu::Queue>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    stride: u64,
+    cursor: Cell<u64>,
+    // blocks already written since the last reset, most dispatches repeat every step
+    written: RefCell<HashMap<Vec<u8>, u32>>,
+    wrapped: Cell<bool>,
+}
+
+impl UniformParams {
+    fn slot(&self, data: &[u8]) -> u32 {
+        if let Some(&offset) = self.written.borrow().get(data) {
+            return offset;
+        }
+        let mut slot = self.cursor.get();
+        if slot == SLOTS {
+            if !self.wrapped.replace(true) {
+                eprintln!(
+                    "More than {} parameter blocks recorded in one frame, results may be wrong",
+                    SLOTS
+                );
+            }
+            self.written.borrow_mut().clear();
+            slot = 0;
+        }
+        self.cursor.set(slot + 1);
+        let offset = (slot * self.stride) as u32;
+        self.queue.write_buffer(&self.buffer, offset as u64, data);
+        self.written.borrow_mut().insert(data.to_vec(), offset);
+        offset
+    }
+}
+
+/// Per-dispatch shader parameters. They are pushed as push constants when the adapter supports them,
+/// otherwise the same bytes go through a dynamic-offset uniform buffer bound at the group
+/// following the pipeline's own bind groups.
+pub struct Params {
+    uniform: Option<UniformParams>,
+}
+
+impl Params {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &std::sync::Arc<wgpu::Queue>,
+        force_uniform: bool,
+    ) -> Self {
+        if !force_uniform
+            && device.features().contains(wgpu::Features::PUSH_CONSTANTS)
+            && device.limits().max_push_constant_size >= MAX_PARAM_SIZE
+        {
+            return Self { uniform: None };
+        }
+
+        // dynamic offsets must be multiples of the alignment, not merely at least as large
+        let stride =
+            SLOT_SIZE.next_multiple_of(device.limits().min_uniform_buffer_offset_alignment as u64);
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Params"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(SLOT_SIZE),
+                },
+                count: None,
+            }],
+        });
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Params"),
+            size: stride * SLOTS,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Params"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(SLOT_SIZE),
+                }),
+            }],
+        });
+
+        Self {
+            uniform: Some(UniformParams {
+                queue: queue.clone(),
+                bind_group_layout,
+                buffer,
+                bind_group,
+                stride,
+                cursor: Cell::new(0),
+                written: RefCell::new(HashMap::new()),
+                wrapped: Cell::new(false),
+            }),
+        }
+    }
+
+    pub fn uses_push_constants(&self) -> bool {
+        self.uniform.is_none()
+    }
+
+    /// Rewrites the `var<push_constant>` declaration of a WGSL shader into a uniform binding at `group`.
+    pub fn shader<'a>(
+        &self,
+        descriptor: wgpu::ShaderModuleDescriptor<'a>,
+        group: u32,
+    ) -> wgpu::ShaderModuleDescriptor<'a> {
+        match (&self.uniform, descriptor.source) {
+            (Some(_), wgpu::ShaderSource::Wgsl(source)) => wgpu::ShaderModuleDescriptor {
+                label: descriptor.label,
+                source: wgpu::ShaderSource::Wgsl(
+                    source
+                        .replace(
+                            "var<push_constant>",
+                            &format!("@group({}) @binding(0) var<uniform>", group),
+                        )
+                        .into(),
+                ),
+            },
+            (_, source) => wgpu::ShaderModuleDescriptor {
+                label: descriptor.label,
+                source,
+            },
+        }
+    }
+
+    /// `bind_group_layouts` plus the parameter layout when going through the uniform buffer.
+    pub fn bind_group_layouts<'a>(
+        &'a self,
+        bind_group_layouts: &[&'a wgpu::BindGroupLayout],
+    ) -> Vec<&'a wgpu::BindGroupLayout> {
+        let mut layouts = bind_group_layouts.to_vec();
+        layouts.extend(
+            self.uniform
+                .as_ref()
+                .map(|uniform| &uniform.bind_group_layout),
+        );
+        layouts
+    }
+
+    pub fn push_constant_ranges(
+        &self,
+        stages: wgpu::ShaderStages,
+        size: u32,
+    ) -> Vec<wgpu::PushConstantRange> {
+        match self.uniform {
+            Some(_) => vec![],
+            None => vec![wgpu::PushConstantRange {
+                stages,
+                range: 0..size,
+            }],
+        }
+    }
+
+    pub fn set_compute<'a>(&'a self, cpass: &mut wgpu::ComputePass<'a>, group: u32, data: &[u8]) {
+        match &self.uniform {
+            Some(uniform) => {
+                cpass.set_bind_group(group, &uniform.bind_group, &[uniform.slot(data)])
+            }
+            None => cpass.set_push_constants(0, data),
+        }
+    }
+
+    pub fn set_render<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        group: u32,
+        data: &[u8],
+    ) {
+        match &self.uniform {
+            Some(uniform) => {
+                render_pass.set_bind_group(group, &uniform.bind_group, &[uniform.slot(data)])
+            }
+            None => render_pass.set_push_constants(wgpu::ShaderStages::VERTEX_FRAGMENT, 0, data),
+        }
+    }
+
+    /// Allows slots to be reused, must only be called once everything recorded so far was submitted.
+    pub fn reset(&self) {
+        if let Some(uniform) = &self.uniform {
+            uniform.cursor.set(0);
+            uniform.written.borrow_mut().clear();
+        }
+    }
+}
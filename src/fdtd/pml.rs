@@ -1,13 +1,66 @@
+use super::{params::Params, UpdateParam};
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PsiParam {
+    offset: [u32; 3],
+    psi_constant: f32,
+    alpha_factor: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SurfacePsiParam {
+    offset: [u32; 3],
+    alpha_factor: f32,
+}
+
+/// The bind groups of one PML region, the psi self update one first.
+trait PMLRegion {
+    // surfaces only decay psi, corners and edges also take the psi constant
+    const SURFACE: bool;
+
+    fn bind_groups(&self) -> [&wgpu::BindGroup; 2];
+}
+
+macro_rules! impl_pml_region {
+    ($($region:ty => $surface:literal),* $(,)?) => {
+        $(
+            impl PMLRegion for $region {
+                const SURFACE: bool = $surface;
+
+                fn bind_groups(&self) -> [&wgpu::BindGroup; 2] {
+                    [
+                        &self.psi_self_update_bind_group,
+                        &self.psi_field_update_bind_group,
+                    ]
+                }
+            }
+        )*
+    };
+}
+
+impl_pml_region!(
+    PMLCorner => false,
+    PMLSurfaceX => true,
+    PMLSurfaceY => true,
+    PMLSurfaceZ => true,
+    PMLEdgeX => false,
+    PMLEdgeY => false,
+    PMLEdgeZ => false,
+);
+
 pub struct PMLCorner {
     pub(crate) psi_self_update_bind_group: wgpu::BindGroup,
     pub(crate) psi_field_update_bind_group: wgpu::BindGroup,
+    pub(crate) psi_textures: [wgpu::Texture; 6],
 }
 
 impl PMLCorner {
     pub fn new(
         device: &wgpu::Device,
         cells: u32,
-        field_view: &[wgpu::TextureView; 3],
+        field_view: &wgpu::TextureView,
         constant_map: &wgpu::TextureView,
         psi_self_update_bind_group_layout: &wgpu::BindGroupLayout,
         psi_field_update_bind_group_layout: &wgpu::BindGroupLayout,
@@ -23,7 +76,9 @@ impl PMLCorner {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D3,
             format: wgpu::TextureFormat::R32Float,
-            usage: wgpu::TextureUsages::STORAGE_BINDING,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         };
         let psi_textures = [
@@ -73,18 +128,10 @@ impl PMLCorner {
                 },
                 wgpu::BindGroupEntry {
                     binding: 6,
-                    resource: wgpu::BindingResource::TextureView(&field_view[0]),
+                    resource: wgpu::BindingResource::TextureView(field_view),
                 },
                 wgpu::BindGroupEntry {
                     binding: 7,
-                    resource: wgpu::BindingResource::TextureView(&field_view[1]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 8,
-                    resource: wgpu::BindingResource::TextureView(&field_view[2]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 9,
                     resource: wgpu::BindingResource::TextureView(constant_map),
                 },
             ],
@@ -123,6 +170,7 @@ impl PMLCorner {
         Self {
             psi_self_update_bind_group,
             psi_field_update_bind_group,
+            psi_textures,
         }
     }
 }
@@ -130,6 +178,7 @@ impl PMLCorner {
 pub struct PMLSurfaceX {
     pub(crate) psi_self_update_bind_group: wgpu::BindGroup,
     pub(crate) psi_field_update_bind_group: wgpu::BindGroup,
+    pub(crate) psi_textures: [wgpu::Texture; 2],
 }
 
 impl PMLSurfaceX {
@@ -137,7 +186,7 @@ impl PMLSurfaceX {
         device: &wgpu::Device,
         cells: u32,
         simulation_dimension: [u32; 3],
-        field_view: &[wgpu::TextureView; 3],
+        field_view: &wgpu::TextureView,
         constant_map: &wgpu::TextureView,
         psi_constant_map: &wgpu::TextureView,
         psi_self_update_bind_group_layout: &wgpu::BindGroupLayout,
@@ -154,7 +203,9 @@ impl PMLSurfaceX {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D3,
             format: wgpu::TextureFormat::R32Float,
-            usage: wgpu::TextureUsages::STORAGE_BINDING,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         };
         let psi_textures = [
@@ -180,18 +231,14 @@ impl PMLSurfaceX {
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
-                    resource: wgpu::BindingResource::TextureView(&field_view[1]),
+                    resource: wgpu::BindingResource::TextureView(field_view),
                 },
                 wgpu::BindGroupEntry {
                     binding: 3,
-                    resource: wgpu::BindingResource::TextureView(&field_view[2]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 4,
                     resource: wgpu::BindingResource::TextureView(constant_map),
                 },
                 wgpu::BindGroupEntry {
-                    binding: 5,
+                    binding: 4,
                     resource: wgpu::BindingResource::TextureView(psi_constant_map),
                 },
             ],
@@ -214,6 +261,7 @@ impl PMLSurfaceX {
         Self {
             psi_self_update_bind_group,
             psi_field_update_bind_group,
+            psi_textures,
         }
     }
 }
@@ -221,6 +269,7 @@ impl PMLSurfaceX {
 pub struct PMLSurfaceY {
     pub(crate) psi_self_update_bind_group: wgpu::BindGroup,
     pub(crate) psi_field_update_bind_group: wgpu::BindGroup,
+    pub(crate) psi_textures: [wgpu::Texture; 2],
 }
 
 impl PMLSurfaceY {
@@ -228,7 +277,7 @@ impl PMLSurfaceY {
         device: &wgpu::Device,
         cells: u32,
         simulation_dimension: [u32; 3],
-        field_view: &[wgpu::TextureView; 3],
+        field_view: &wgpu::TextureView,
         constant_map: &wgpu::TextureView,
         psi_constant_map: &wgpu::TextureView,
         psi_self_update_bind_group_layout: &wgpu::BindGroupLayout,
@@ -245,7 +294,9 @@ impl PMLSurfaceY {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D3,
             format: wgpu::TextureFormat::R32Float,
-            usage: wgpu::TextureUsages::STORAGE_BINDING,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         };
         let psi_textures = [
@@ -271,18 +322,14 @@ impl PMLSurfaceY {
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
-                    resource: wgpu::BindingResource::TextureView(&field_view[0]),
+                    resource: wgpu::BindingResource::TextureView(field_view),
                 },
                 wgpu::BindGroupEntry {
                     binding: 3,
-                    resource: wgpu::BindingResource::TextureView(&field_view[2]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 4,
                     resource: wgpu::BindingResource::TextureView(constant_map),
                 },
                 wgpu::BindGroupEntry {
-                    binding: 5,
+                    binding: 4,
                     resource: wgpu::BindingResource::TextureView(psi_constant_map),
                 },
             ],
@@ -305,6 +352,7 @@ impl PMLSurfaceY {
         Self {
             psi_self_update_bind_group,
             psi_field_update_bind_group,
+            psi_textures,
         }
     }
 }
@@ -312,6 +360,7 @@ impl PMLSurfaceY {
 pub struct PMLSurfaceZ {
     pub(crate) psi_self_update_bind_group: wgpu::BindGroup,
     pub(crate) psi_field_update_bind_group: wgpu::BindGroup,
+    pub(crate) psi_textures: [wgpu::Texture; 2],
 }
 
 impl PMLSurfaceZ {
@@ -319,7 +368,7 @@ impl PMLSurfaceZ {
         device: &wgpu::Device,
         cells: u32,
         simulation_dimension: [u32; 3],
-        field_view: &[wgpu::TextureView; 3],
+        field_view: &wgpu::TextureView,
         constant_map: &wgpu::TextureView,
         psi_constant_map: &wgpu::TextureView,
         psi_self_update_bind_group_layout: &wgpu::BindGroupLayout,
@@ -336,7 +385,9 @@ impl PMLSurfaceZ {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D3,
             format: wgpu::TextureFormat::R32Float,
-            usage: wgpu::TextureUsages::STORAGE_BINDING,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         };
         let psi_textures = [
@@ -362,18 +413,14 @@ impl PMLSurfaceZ {
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
-                    resource: wgpu::BindingResource::TextureView(&field_view[0]),
+                    resource: wgpu::BindingResource::TextureView(field_view),
                 },
                 wgpu::BindGroupEntry {
                     binding: 3,
-                    resource: wgpu::BindingResource::TextureView(&field_view[1]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 4,
                     resource: wgpu::BindingResource::TextureView(constant_map),
                 },
                 wgpu::BindGroupEntry {
-                    binding: 5,
+                    binding: 4,
                     resource: wgpu::BindingResource::TextureView(psi_constant_map),
                 },
             ],
@@ -396,6 +443,7 @@ impl PMLSurfaceZ {
         Self {
             psi_self_update_bind_group,
             psi_field_update_bind_group,
+            psi_textures,
         }
     }
 }
@@ -403,6 +451,7 @@ impl PMLSurfaceZ {
 pub struct PMLEdgeX {
     pub(crate) psi_self_update_bind_group: wgpu::BindGroup,
     pub(crate) psi_field_update_bind_group: wgpu::BindGroup,
+    pub(crate) psi_textures: [wgpu::Texture; 4],
 }
 
 impl PMLEdgeX {
@@ -410,7 +459,7 @@ impl PMLEdgeX {
         device: &wgpu::Device,
         cells: u32,
         simulation_dimension: [u32; 3],
-        field_view: &[wgpu::TextureView; 3],
+        field_view: &wgpu::TextureView,
         constant_map: &wgpu::TextureView,
         psi_self_update_bind_group_layout: &wgpu::BindGroupLayout,
         psi_field_update_bind_group_layout: &wgpu::BindGroupLayout,
@@ -426,7 +475,9 @@ impl PMLEdgeX {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D3,
             format: wgpu::TextureFormat::R32Float,
-            usage: wgpu::TextureUsages::STORAGE_BINDING,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         };
         let psi_textures = [
@@ -464,18 +515,10 @@ impl PMLEdgeX {
                 },
                 wgpu::BindGroupEntry {
                     binding: 4,
-                    resource: wgpu::BindingResource::TextureView(&field_view[0]),
+                    resource: wgpu::BindingResource::TextureView(field_view),
                 },
                 wgpu::BindGroupEntry {
                     binding: 5,
-                    resource: wgpu::BindingResource::TextureView(&field_view[1]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 6,
-                    resource: wgpu::BindingResource::TextureView(&field_view[2]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 7,
                     resource: wgpu::BindingResource::TextureView(constant_map),
                 },
             ],
@@ -506,6 +549,7 @@ impl PMLEdgeX {
         Self {
             psi_self_update_bind_group,
             psi_field_update_bind_group,
+            psi_textures,
         }
     }
 }
@@ -513,6 +557,7 @@ impl PMLEdgeX {
 pub struct PMLEdgeY {
     pub(crate) psi_self_update_bind_group: wgpu::BindGroup,
     pub(crate) psi_field_update_bind_group: wgpu::BindGroup,
+    pub(crate) psi_textures: [wgpu::Texture; 4],
 }
 
 impl PMLEdgeY {
@@ -520,7 +565,7 @@ impl PMLEdgeY {
         device: &wgpu::Device,
         cells: u32,
         simulation_dimension: [u32; 3],
-        field_view: &[wgpu::TextureView; 3],
+        field_view: &wgpu::TextureView,
         constant_map: &wgpu::TextureView,
         psi_self_update_bind_group_layout: &wgpu::BindGroupLayout,
         psi_field_update_bind_group_layout: &wgpu::BindGroupLayout,
@@ -536,7 +581,9 @@ impl PMLEdgeY {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D3,
             format: wgpu::TextureFormat::R32Float,
-            usage: wgpu::TextureUsages::STORAGE_BINDING,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         };
         let psi_textures = [
@@ -574,18 +621,10 @@ impl PMLEdgeY {
                 },
                 wgpu::BindGroupEntry {
                     binding: 4,
-                    resource: wgpu::BindingResource::TextureView(&field_view[0]),
+                    resource: wgpu::BindingResource::TextureView(field_view),
                 },
                 wgpu::BindGroupEntry {
                     binding: 5,
-                    resource: wgpu::BindingResource::TextureView(&field_view[1]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 6,
-                    resource: wgpu::BindingResource::TextureView(&field_view[2]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 7,
                     resource: wgpu::BindingResource::TextureView(constant_map),
                 },
             ],
@@ -616,6 +655,7 @@ impl PMLEdgeY {
         Self {
             psi_self_update_bind_group,
             psi_field_update_bind_group,
+            psi_textures,
         }
     }
 }
@@ -623,6 +663,7 @@ impl PMLEdgeY {
 pub struct PMLEdgeZ {
     pub(crate) psi_self_update_bind_group: wgpu::BindGroup,
     pub(crate) psi_field_update_bind_group: wgpu::BindGroup,
+    pub(crate) psi_textures: [wgpu::Texture; 4],
 }
 
 impl PMLEdgeZ {
@@ -630,7 +671,7 @@ impl PMLEdgeZ {
         device: &wgpu::Device,
         cells: u32,
         simulation_dimension: [u32; 3],
-        field_view: &[wgpu::TextureView; 3],
+        field_view: &wgpu::TextureView,
         constant_map: &wgpu::TextureView,
         psi_self_update_bind_group_layout: &wgpu::BindGroupLayout,
         psi_field_update_bind_group_layout: &wgpu::BindGroupLayout,
@@ -646,7 +687,9 @@ impl PMLEdgeZ {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D3,
             format: wgpu::TextureFormat::R32Float,
-            usage: wgpu::TextureUsages::STORAGE_BINDING,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         };
         let psi_textures = [
@@ -684,18 +727,10 @@ impl PMLEdgeZ {
                 },
                 wgpu::BindGroupEntry {
                     binding: 4,
-                    resource: wgpu::BindingResource::TextureView(&field_view[0]),
+                    resource: wgpu::BindingResource::TextureView(field_view),
                 },
                 wgpu::BindGroupEntry {
                     binding: 5,
-                    resource: wgpu::BindingResource::TextureView(&field_view[1]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 6,
-                    resource: wgpu::BindingResource::TextureView(&field_view[2]),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 7,
                     resource: wgpu::BindingResource::TextureView(constant_map),
                 },
             ],
@@ -726,155 +761,174 @@ impl PMLEdgeZ {
         Self {
             psi_self_update_bind_group,
             psi_field_update_bind_group,
+            psi_textures,
         }
     }
 }
 
+/// Sides, 0 low and 1 high, of the PML regions present when only `faces` are covered.
+/// A region is present when every face it touches is. Surfaces are listed by their side along
+/// the normal axis, edges by their sides along the other two axes in order.
+pub struct Regions {
+    pub corners: Vec<[usize; 3]>,
+    pub surfaces: [Vec<usize>; 3],
+    pub edges: [Vec<[usize; 2]>; 3],
+}
+
+pub fn regions(faces: super::PMLFaces) -> Regions {
+    let sides = faces.sides();
+    // the order of a full PML, which checkpoints store the psi textures in
+    let corners = [
+        [0, 0, 0],
+        [1, 0, 0],
+        [1, 1, 0],
+        [0, 1, 0],
+        [0, 0, 1],
+        [1, 0, 1],
+        [1, 1, 1],
+        [0, 1, 1],
+    ];
+    let edges = [
+        [[0, 0], [0, 1], [1, 1], [1, 0]],
+        [[0, 0], [1, 0], [1, 1], [0, 1]],
+        [[0, 0], [1, 0], [1, 1], [0, 1]],
+    ];
+    Regions {
+        corners: corners
+            .into_iter()
+            .filter(|corner| (0..3).all(|axis| sides[axis][corner[axis]]))
+            .collect(),
+        surfaces: [0, 1, 2].map(|axis| (0..2).filter(|&side| sides[axis][side]).collect()),
+        edges: [0, 1, 2].map(|axis| {
+            let [u, v] = other_axes(axis);
+            edges[axis]
+                .into_iter()
+                .filter(|edge| sides[u][edge[0]] && sides[v][edge[1]])
+                .collect()
+        }),
+    }
+}
+
+fn other_axes(axis: usize) -> [usize; 2] {
+    match axis {
+        0 => [1, 2],
+        1 => [0, 2],
+        _ => [0, 1],
+    }
+}
+
 pub struct PMLBoundary {
     cells: u32,
+    padding: [[u32; 2]; 3],
+    regions: Regions,
+    pmc_faces: u32, // walls of the field passes, as in the main update
+    periodic: u32,
+    bloch_phase: [f32; 3], // k L across each periodic axis
+    partner_sign: f32,     // of the other half of the Bloch fields, as in the main update
+    workgroup: [u32; 3],   // of the main update, the PML shaders use the same
     alpha_factor: f32,
     psi_constant: f32,
     simulation_dimension: [u32; 3],
-    electric_field_update_bind_group: wgpu::BindGroup,
-    magnetic_field_update_bind_group: wgpu::BindGroup,
-    corner_magnetic: [PMLCorner; 8],
-    corner_electric: [PMLCorner; 8],
+    corner_magnetic: Vec<PMLCorner>,
+    corner_electric: Vec<PMLCorner>,
     corner_self_update_pipeline_magnetic: wgpu::ComputePipeline,
     corner_self_update_pipeline_electric: wgpu::ComputePipeline,
     corner_field_update_pipeline_magnetic: wgpu::ComputePipeline,
     corner_field_update_pipeline_electric: wgpu::ComputePipeline,
-    surface_x_magnetic: [PMLSurfaceX; 2],
-    surface_x_electric: [PMLSurfaceX; 2],
+    surface_x_magnetic: Vec<PMLSurfaceX>,
+    surface_x_electric: Vec<PMLSurfaceX>,
     surface_x_self_update_pipeline_magnetic: wgpu::ComputePipeline,
     surface_x_self_update_pipeline_electric: wgpu::ComputePipeline,
     surface_x_field_update_pipeline_magnetic: wgpu::ComputePipeline,
     surface_x_field_update_pipeline_electric: wgpu::ComputePipeline,
-    surface_y_magnetic: [PMLSurfaceY; 2],
-    surface_y_electric: [PMLSurfaceY; 2],
+    surface_y_magnetic: Vec<PMLSurfaceY>,
+    surface_y_electric: Vec<PMLSurfaceY>,
     surface_y_self_update_pipeline_magnetic: wgpu::ComputePipeline,
     surface_y_self_update_pipeline_electric: wgpu::ComputePipeline,
     surface_y_field_update_pipeline_magnetic: wgpu::ComputePipeline,
     surface_y_field_update_pipeline_electric: wgpu::ComputePipeline,
-    surface_z_magnetic: [PMLSurfaceZ; 2],
-    surface_z_electric: [PMLSurfaceZ; 2],
+    surface_z_magnetic: Vec<PMLSurfaceZ>,
+    surface_z_electric: Vec<PMLSurfaceZ>,
     surface_z_self_update_pipeline_magnetic: wgpu::ComputePipeline,
     surface_z_self_update_pipeline_electric: wgpu::ComputePipeline,
     surface_z_field_update_pipeline_magnetic: wgpu::ComputePipeline,
     surface_z_field_update_pipeline_electric: wgpu::ComputePipeline,
-    edge_x_magnetic: [PMLEdgeX; 4],
-    edge_x_electric: [PMLEdgeX; 4],
+    edge_x_magnetic: Vec<PMLEdgeX>,
+    edge_x_electric: Vec<PMLEdgeX>,
     edge_x_self_update_pipeline_magnetic: wgpu::ComputePipeline,
     edge_x_self_update_pipeline_electric: wgpu::ComputePipeline,
     edge_x_field_update_pipeline_magnetic: wgpu::ComputePipeline,
     edge_x_field_update_pipeline_electric: wgpu::ComputePipeline,
-    edge_y_magnetic: [PMLEdgeY; 4],
-    edge_y_electric: [PMLEdgeY; 4],
+    edge_y_magnetic: Vec<PMLEdgeY>,
+    edge_y_electric: Vec<PMLEdgeY>,
     edge_y_self_update_pipeline_magnetic: wgpu::ComputePipeline,
     edge_y_self_update_pipeline_electric: wgpu::ComputePipeline,
     edge_y_field_update_pipeline_magnetic: wgpu::ComputePipeline,
     edge_y_field_update_pipeline_electric: wgpu::ComputePipeline,
-    edge_z_magnetic: [PMLEdgeZ; 4],
-    edge_z_electric: [PMLEdgeZ; 4],
+    edge_z_magnetic: Vec<PMLEdgeZ>,
+    edge_z_electric: Vec<PMLEdgeZ>,
     edge_z_self_update_pipeline_magnetic: wgpu::ComputePipeline,
     edge_z_self_update_pipeline_electric: wgpu::ComputePipeline,
     edge_z_field_update_pipeline_magnetic: wgpu::ComputePipeline,
     edge_z_field_update_pipeline_electric: wgpu::ComputePipeline,
 }
 
+/// What the PMLs of the two halves of the Bloch fields share.
+#[derive(Clone, Copy)]
+pub struct PMLDescriptor<'a> {
+    pub cells: u32,
+    pub faces: super::PMLFaces,
+    pub pmc_faces: u32,
+    pub periodic: u32,
+    pub bloch_phase: [f32; 3], // k L across each periodic axis
+    pub alpha: f32,
+    pub sigma: f32,
+    pub dt: f32,
+    pub electric_constant_map: &'a wgpu::TextureView,
+    pub magnetic_constant_map: &'a wgpu::TextureView,
+    pub simulation_dimension: [u32; 3],
+    pub electric_psi_constants: &'a [wgpu::TextureView; 6],
+    pub magnetic_psi_constants: &'a [wgpu::TextureView; 6],
+    pub field_bind_group_layout: &'a wgpu::BindGroupLayout,
+    pub workgroup: [u32; 3],
+    pub params: &'a Params,
+}
+
 impl PMLBoundary {
+    /// The PML around one half of the fields, `partner_sign` multiplies the other half across
+    /// the periodic faces as in the main update.
     pub fn new(
         device: &wgpu::Device,
-        cells: u32,
-        alpha: f32,
-        sigma: f32,
-        dt: f32,
-        electric_field_view: &[wgpu::TextureView; 3],
-        magnetic_field_view: &[wgpu::TextureView; 3],
-        electric_constant_map: &wgpu::TextureView,
-        magnetic_constant_map: &wgpu::TextureView,
-        simulation_dimension: [u32; 3],
-        (electric_psi_constants, magnetic_psi_constants): (
-            [wgpu::TextureView; 6],
-            [wgpu::TextureView; 6],
-        ),
+        descriptor: PMLDescriptor,
+        electric_field_view: &wgpu::TextureView,
+        magnetic_field_view: &wgpu::TextureView,
+        partner_sign: f32,
     ) -> Self {
-        let field_update_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: None,
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::COMPUTE,
-                        ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::ReadWrite,
-                            format: wgpu::TextureFormat::R32Float,
-                            view_dimension: wgpu::TextureViewDimension::D3,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::COMPUTE,
-                        ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::ReadWrite,
-                            format: wgpu::TextureFormat::R32Float,
-                            view_dimension: wgpu::TextureViewDimension::D3,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStages::COMPUTE,
-                        ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::ReadWrite,
-                            format: wgpu::TextureFormat::R32Float,
-                            view_dimension: wgpu::TextureViewDimension::D3,
-                        },
-                        count: None,
-                    },
-                ],
-            });
-
-        let electric_field_update_bind_group =
-            device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: None,
-                layout: &field_update_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&electric_field_view[0]),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::TextureView(&electric_field_view[1]),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: wgpu::BindingResource::TextureView(&electric_field_view[2]),
-                    },
-                ],
-            });
-
-        let magnetic_field_update_bind_group =
-            device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: None,
-                layout: &field_update_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&magnetic_field_view[0]),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::TextureView(&magnetic_field_view[1]),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: wgpu::BindingResource::TextureView(&magnetic_field_view[2]),
-                    },
-                ],
-            });
-
+        let PMLDescriptor {
+            cells,
+            faces,
+            pmc_faces,
+            periodic,
+            bloch_phase,
+            alpha,
+            sigma,
+            dt,
+            electric_constant_map,
+            magnetic_constant_map,
+            simulation_dimension,
+            electric_psi_constants,
+            magnetic_psi_constants,
+            field_bind_group_layout,
+            workgroup,
+            params,
+        } = descriptor;
+        let regions = regions(faces);
+        let preprocess = |source: &str| {
+            source
+                .replace("WORKGROUP_X", workgroup[0].to_string().as_str())
+                .replace("WORKGROUP_Y", workgroup[1].to_string().as_str())
+                .replace("WORKGROUP_Z", workgroup[2].to_string().as_str())
+        };
         let psi_corner_self_update_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: None,
@@ -944,7 +998,7 @@ impl PMLBoundary {
                         visibility: wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::StorageTexture {
                             access: wgpu::StorageTextureAccess::ReadOnly,
-                            format: wgpu::TextureFormat::R32Float,
+                            format: wgpu::TextureFormat::Rgba32Float,
                             view_dimension: wgpu::TextureViewDimension::D3,
                         },
                         count: None,
@@ -952,26 +1006,6 @@ impl PMLBoundary {
                     wgpu::BindGroupLayoutEntry {
                         binding: 7,
                         visibility: wgpu::ShaderStages::COMPUTE,
-                        ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::ReadOnly,
-                            format: wgpu::TextureFormat::R32Float,
-                            view_dimension: wgpu::TextureViewDimension::D3,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 8,
-                        visibility: wgpu::ShaderStages::COMPUTE,
-                        ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::ReadOnly,
-                            format: wgpu::TextureFormat::R32Float,
-                            view_dimension: wgpu::TextureViewDimension::D3,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 9,
-                        visibility: wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::StorageTexture {
                             access: wgpu::StorageTextureAccess::ReadOnly,
                             format: wgpu::TextureFormat::Rg32Float,
@@ -1048,40 +1082,53 @@ impl PMLBoundary {
                     },
                 ],
             });
-        let corner_electric = [(); 8].map(|_| {
-            PMLCorner::new(
-                device,
-                cells,
-                magnetic_field_view,
-                electric_constant_map,
-                &psi_corner_self_update_bind_group_layout,
-                &psi_corner_field_update_bind_group_layout,
-            )
-        });
+        let corner_electric: Vec<_> = regions
+            .corners
+            .iter()
+            .map(|_| {
+                PMLCorner::new(
+                    device,
+                    cells,
+                    magnetic_field_view,
+                    electric_constant_map,
+                    &psi_corner_self_update_bind_group_layout,
+                    &psi_corner_field_update_bind_group_layout,
+                )
+            })
+            .collect();
 
-        let corner_magnetic = [(); 8].map(|_| {
-            PMLCorner::new(
-                device,
-                cells,
-                electric_field_view,
-                magnetic_constant_map,
-                &psi_corner_self_update_bind_group_layout,
-                &psi_corner_field_update_bind_group_layout,
-            )
-        });
+        let corner_magnetic: Vec<_> = regions
+            .corners
+            .iter()
+            .map(|_| {
+                PMLCorner::new(
+                    device,
+                    cells,
+                    electric_field_view,
+                    magnetic_constant_map,
+                    &psi_corner_self_update_bind_group_layout,
+                    &psi_corner_field_update_bind_group_layout,
+                )
+            })
+            .collect();
 
         let corner_self_update_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: None,
-                bind_group_layouts: &[&psi_corner_self_update_bind_group_layout],
-                push_constant_ranges: &[wgpu::PushConstantRange {
-                    stages: wgpu::ShaderStages::COMPUTE,
-                    range: 0..20,
-                }],
+                bind_group_layouts: &params
+                    .bind_group_layouts(&[&psi_corner_self_update_bind_group_layout]),
+                push_constant_ranges: &params.push_constant_ranges(wgpu::ShaderStages::COMPUTE, 20),
             });
 
-        let corner_self_update_shader_module = device
-            .create_shader_module(wgpu::include_wgsl!("../../shader/fdtd/pml_corner_psi.wgsl"));
+        let corner_self_update_shader_module = device.create_shader_module(params.shader(
+            wgpu::ShaderModuleDescriptor {
+                label: Some("pml_corner_psi.wgsl"),
+                source: wgpu::ShaderSource::Wgsl(
+                    preprocess(include_str!("../../shader/fdtd/pml_corner_psi.wgsl")).into(),
+                ),
+            },
+            1,
+        ));
 
         let corner_self_update_pipeline_magnetic =
             device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
@@ -1102,18 +1149,30 @@ impl PMLBoundary {
         let corner_field_update_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: None,
-                bind_group_layouts: &[
-                    &field_update_bind_group_layout,
+                bind_group_layouts: &params.bind_group_layouts(&[
+                    field_bind_group_layout,
                     &psi_corner_field_update_bind_group_layout,
-                ],
-                push_constant_ranges: &[wgpu::PushConstantRange {
-                    stages: wgpu::ShaderStages::COMPUTE,
-                    range: 0..12,
-                }],
+                ]),
+                push_constant_ranges: &params.push_constant_ranges(
+                    wgpu::ShaderStages::COMPUTE,
+                    std::mem::size_of::<UpdateParam>() as u32,
+                ),
             });
-        let corner_field_update_shader_module = device.create_shader_module(wgpu::include_wgsl!(
-            "../../shader/fdtd/pml_corner_field.wgsl"
-        ));
+        let corner_field_update_shader_module = device.create_shader_module(
+            params.shader(
+                wgpu::ShaderModuleDescriptor {
+                    label: Some("pml_corner_field.wgsl"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        preprocess(concat!(
+                            include_str!("../../shader/fdtd/update.wgsl"),
+                            include_str!("../../shader/fdtd/pml_corner_field.wgsl")
+                        ))
+                        .into(),
+                    ),
+                },
+                2,
+            ),
+        );
 
         let corner_field_update_pipeline_magnetic =
             device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
@@ -1162,7 +1221,7 @@ impl PMLBoundary {
                         visibility: wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::StorageTexture {
                             access: wgpu::StorageTextureAccess::ReadOnly,
-                            format: wgpu::TextureFormat::R32Float,
+                            format: wgpu::TextureFormat::Rgba32Float,
                             view_dimension: wgpu::TextureViewDimension::D3,
                         },
                         count: None,
@@ -1170,16 +1229,6 @@ impl PMLBoundary {
                     wgpu::BindGroupLayoutEntry {
                         binding: 3,
                         visibility: wgpu::ShaderStages::COMPUTE,
-                        ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::ReadOnly,
-                            format: wgpu::TextureFormat::R32Float,
-                            view_dimension: wgpu::TextureViewDimension::D3,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 4,
-                        visibility: wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::StorageTexture {
                             access: wgpu::StorageTextureAccess::ReadOnly,
                             format: wgpu::TextureFormat::Rg32Float,
@@ -1188,7 +1237,7 @@ impl PMLBoundary {
                         count: None,
                     },
                     wgpu::BindGroupLayoutEntry {
-                        binding: 5,
+                        binding: 4,
                         visibility: wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::StorageTexture {
                             access: wgpu::StorageTextureAccess::ReadOnly,
@@ -1226,57 +1275,67 @@ impl PMLBoundary {
                     },
                 ],
             });
-        let surface_x_electric = [0, 1].map(|idx| {
-            PMLSurfaceX::new(
-                device,
-                cells,
-                simulation_dimension,
-                magnetic_field_view,
-                electric_constant_map,
-                &electric_psi_constants[idx],
-                &psi_surface_self_update_bind_group_layout,
-                &psi_surface_field_update_bind_group_layout,
-            )
-        });
+        let surface_x_electric: Vec<_> = regions.surfaces[0]
+            .iter()
+            .map(|&side| {
+                PMLSurfaceX::new(
+                    device,
+                    cells,
+                    simulation_dimension,
+                    magnetic_field_view,
+                    electric_constant_map,
+                    &electric_psi_constants[side],
+                    &psi_surface_self_update_bind_group_layout,
+                    &psi_surface_field_update_bind_group_layout,
+                )
+            })
+            .collect();
 
-        let surface_x_magnetic = [0, 1].map(|idx| {
-            PMLSurfaceX::new(
-                device,
-                cells,
-                simulation_dimension,
-                electric_field_view,
-                magnetic_constant_map,
-                &magnetic_psi_constants[idx],
-                &psi_surface_self_update_bind_group_layout,
-                &psi_surface_field_update_bind_group_layout,
-            )
-        });
+        let surface_x_magnetic: Vec<_> = regions.surfaces[0]
+            .iter()
+            .map(|&side| {
+                PMLSurfaceX::new(
+                    device,
+                    cells,
+                    simulation_dimension,
+                    electric_field_view,
+                    magnetic_constant_map,
+                    &magnetic_psi_constants[side],
+                    &psi_surface_self_update_bind_group_layout,
+                    &psi_surface_field_update_bind_group_layout,
+                )
+            })
+            .collect();
 
         let surface_self_update_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: None,
-                bind_group_layouts: &[&psi_surface_self_update_bind_group_layout],
-                push_constant_ranges: &[wgpu::PushConstantRange {
-                    stages: wgpu::ShaderStages::COMPUTE,
-                    range: 0..20,
-                }],
+                bind_group_layouts: &params
+                    .bind_group_layouts(&[&psi_surface_self_update_bind_group_layout]),
+                push_constant_ranges: &params.push_constant_ranges(wgpu::ShaderStages::COMPUTE, 20),
             });
 
         let surface_field_update_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: None,
-                bind_group_layouts: &[
-                    &field_update_bind_group_layout,
+                bind_group_layouts: &params.bind_group_layouts(&[
+                    field_bind_group_layout,
                     &psi_surface_field_update_bind_group_layout,
-                ],
-                push_constant_ranges: &[wgpu::PushConstantRange {
-                    stages: wgpu::ShaderStages::COMPUTE,
-                    range: 0..12,
-                }],
+                ]),
+                push_constant_ranges: &params.push_constant_ranges(
+                    wgpu::ShaderStages::COMPUTE,
+                    std::mem::size_of::<UpdateParam>() as u32,
+                ),
             });
 
-        let surface_x_self_update_shader_module = device.create_shader_module(wgpu::include_wgsl!(
-            "../../shader/fdtd/pml_surface_x_psi.wgsl"
+        let surface_x_self_update_shader_module = device.create_shader_module(params.shader(
+            wgpu::ShaderModuleDescriptor {
+                label: Some("pml_surface_x_psi.wgsl"),
+                source: wgpu::ShaderSource::Wgsl(
+                    preprocess(include_str!("../../shader/fdtd/pml_surface_x_psi.wgsl")).into(),
+                ),
+            },
+            1,
         ));
 
         let surface_x_self_update_pipeline_magnetic =
@@ -1296,7 +1355,19 @@ impl PMLBoundary {
             });
 
         let surface_x_field_update_shader_module = device.create_shader_module(
-            wgpu::include_wgsl!("../../shader/fdtd/pml_surface_x_field.wgsl"),
+            params.shader(
+                wgpu::ShaderModuleDescriptor {
+                    label: Some("pml_surface_x_field.wgsl"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        preprocess(concat!(
+                            include_str!("../../shader/fdtd/update.wgsl"),
+                            include_str!("../../shader/fdtd/pml_surface_x_field.wgsl")
+                        ))
+                        .into(),
+                    ),
+                },
+                2,
+            ),
         );
 
         let surface_x_field_update_pipeline_magnetic =
@@ -1315,34 +1386,46 @@ impl PMLBoundary {
                 entry_point: "update_electric_field",
             });
 
-        let surface_y_electric = [2, 3].map(|idx| {
-            PMLSurfaceY::new(
-                device,
-                cells,
-                simulation_dimension,
-                magnetic_field_view,
-                electric_constant_map,
-                &electric_psi_constants[idx],
-                &psi_surface_self_update_bind_group_layout,
-                &psi_surface_field_update_bind_group_layout,
-            )
-        });
-
-        let surface_y_magnetic = [2, 3].map(|idx| {
-            PMLSurfaceY::new(
-                device,
-                cells,
-                simulation_dimension,
-                electric_field_view,
-                magnetic_constant_map,
-                &magnetic_psi_constants[idx],
-                &psi_surface_self_update_bind_group_layout,
-                &psi_surface_field_update_bind_group_layout,
-            )
-        });
+        let surface_y_electric: Vec<_> = regions.surfaces[1]
+            .iter()
+            .map(|&side| {
+                PMLSurfaceY::new(
+                    device,
+                    cells,
+                    simulation_dimension,
+                    magnetic_field_view,
+                    electric_constant_map,
+                    &electric_psi_constants[2 + side],
+                    &psi_surface_self_update_bind_group_layout,
+                    &psi_surface_field_update_bind_group_layout,
+                )
+            })
+            .collect();
 
-        let surface_y_self_update_shader_module = device.create_shader_module(wgpu::include_wgsl!(
-            "../../shader/fdtd/pml_surface_y_psi.wgsl"
+        let surface_y_magnetic: Vec<_> = regions.surfaces[1]
+            .iter()
+            .map(|&side| {
+                PMLSurfaceY::new(
+                    device,
+                    cells,
+                    simulation_dimension,
+                    electric_field_view,
+                    magnetic_constant_map,
+                    &magnetic_psi_constants[2 + side],
+                    &psi_surface_self_update_bind_group_layout,
+                    &psi_surface_field_update_bind_group_layout,
+                )
+            })
+            .collect();
+
+        let surface_y_self_update_shader_module = device.create_shader_module(params.shader(
+            wgpu::ShaderModuleDescriptor {
+                label: Some("pml_surface_y_psi.wgsl"),
+                source: wgpu::ShaderSource::Wgsl(
+                    preprocess(include_str!("../../shader/fdtd/pml_surface_y_psi.wgsl")).into(),
+                ),
+            },
+            1,
         ));
 
         let surface_y_self_update_pipeline_magnetic =
@@ -1362,7 +1445,19 @@ impl PMLBoundary {
             });
 
         let surface_y_field_update_shader_module = device.create_shader_module(
-            wgpu::include_wgsl!("../../shader/fdtd/pml_surface_y_field.wgsl"),
+            params.shader(
+                wgpu::ShaderModuleDescriptor {
+                    label: Some("pml_surface_y_field.wgsl"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        preprocess(concat!(
+                            include_str!("../../shader/fdtd/update.wgsl"),
+                            include_str!("../../shader/fdtd/pml_surface_y_field.wgsl")
+                        ))
+                        .into(),
+                    ),
+                },
+                2,
+            ),
         );
 
         let surface_y_field_update_pipeline_magnetic =
@@ -1381,34 +1476,46 @@ impl PMLBoundary {
                 entry_point: "update_electric_field",
             });
 
-        let surface_z_electric = [4, 5].map(|idx| {
-            PMLSurfaceZ::new(
-                device,
-                cells,
-                simulation_dimension,
-                magnetic_field_view,
-                electric_constant_map,
-                &electric_psi_constants[idx],
-                &psi_surface_self_update_bind_group_layout,
-                &psi_surface_field_update_bind_group_layout,
-            )
-        });
-
-        let surface_z_magnetic = [4, 5].map(|idx| {
-            PMLSurfaceZ::new(
-                device,
-                cells,
-                simulation_dimension,
-                electric_field_view,
-                magnetic_constant_map,
-                &magnetic_psi_constants[idx],
-                &psi_surface_self_update_bind_group_layout,
-                &psi_surface_field_update_bind_group_layout,
-            )
-        });
+        let surface_z_electric: Vec<_> = regions.surfaces[2]
+            .iter()
+            .map(|&side| {
+                PMLSurfaceZ::new(
+                    device,
+                    cells,
+                    simulation_dimension,
+                    magnetic_field_view,
+                    electric_constant_map,
+                    &electric_psi_constants[4 + side],
+                    &psi_surface_self_update_bind_group_layout,
+                    &psi_surface_field_update_bind_group_layout,
+                )
+            })
+            .collect();
 
-        let surface_z_self_update_shader_module = device.create_shader_module(wgpu::include_wgsl!(
-            "../../shader/fdtd/pml_surface_z_psi.wgsl"
+        let surface_z_magnetic: Vec<_> = regions.surfaces[2]
+            .iter()
+            .map(|&side| {
+                PMLSurfaceZ::new(
+                    device,
+                    cells,
+                    simulation_dimension,
+                    electric_field_view,
+                    magnetic_constant_map,
+                    &magnetic_psi_constants[4 + side],
+                    &psi_surface_self_update_bind_group_layout,
+                    &psi_surface_field_update_bind_group_layout,
+                )
+            })
+            .collect();
+
+        let surface_z_self_update_shader_module = device.create_shader_module(params.shader(
+            wgpu::ShaderModuleDescriptor {
+                label: Some("pml_surface_z_psi.wgsl"),
+                source: wgpu::ShaderSource::Wgsl(
+                    preprocess(include_str!("../../shader/fdtd/pml_surface_z_psi.wgsl")).into(),
+                ),
+            },
+            1,
         ));
 
         let surface_z_self_update_pipeline_magnetic =
@@ -1428,7 +1535,19 @@ impl PMLBoundary {
             });
 
         let surface_z_field_update_shader_module = device.create_shader_module(
-            wgpu::include_wgsl!("../../shader/fdtd/pml_surface_z_field.wgsl"),
+            params.shader(
+                wgpu::ShaderModuleDescriptor {
+                    label: Some("pml_surface_z_field.wgsl"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        preprocess(concat!(
+                            include_str!("../../shader/fdtd/update.wgsl"),
+                            include_str!("../../shader/fdtd/pml_surface_z_field.wgsl")
+                        ))
+                        .into(),
+                    ),
+                },
+                2,
+            ),
         );
 
         let surface_z_field_update_pipeline_magnetic =
@@ -1498,7 +1617,7 @@ impl PMLBoundary {
                         visibility: wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::StorageTexture {
                             access: wgpu::StorageTextureAccess::ReadOnly,
-                            format: wgpu::TextureFormat::R32Float,
+                            format: wgpu::TextureFormat::Rgba32Float,
                             view_dimension: wgpu::TextureViewDimension::D3,
                         },
                         count: None,
@@ -1506,26 +1625,6 @@ impl PMLBoundary {
                     wgpu::BindGroupLayoutEntry {
                         binding: 5,
                         visibility: wgpu::ShaderStages::COMPUTE,
-                        ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::ReadOnly,
-                            format: wgpu::TextureFormat::R32Float,
-                            view_dimension: wgpu::TextureViewDimension::D3,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 6,
-                        visibility: wgpu::ShaderStages::COMPUTE,
-                        ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::ReadOnly,
-                            format: wgpu::TextureFormat::R32Float,
-                            view_dimension: wgpu::TextureViewDimension::D3,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 7,
-                        visibility: wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::StorageTexture {
                             access: wgpu::StorageTextureAccess::ReadOnly,
                             format: wgpu::TextureFormat::Rg32Float,
@@ -1586,52 +1685,63 @@ impl PMLBoundary {
         let edge_self_update_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: None,
-                bind_group_layouts: &[&psi_edge_self_update_bind_group_layout],
-                push_constant_ranges: &[wgpu::PushConstantRange {
-                    stages: wgpu::ShaderStages::COMPUTE,
-                    range: 0..20,
-                }],
+                bind_group_layouts: &params
+                    .bind_group_layouts(&[&psi_edge_self_update_bind_group_layout]),
+                push_constant_ranges: &params.push_constant_ranges(wgpu::ShaderStages::COMPUTE, 20),
             });
 
         let edge_field_update_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: None,
-                bind_group_layouts: &[
-                    &field_update_bind_group_layout,
+                bind_group_layouts: &params.bind_group_layouts(&[
+                    field_bind_group_layout,
                     &psi_edge_field_update_bind_group_layout,
-                ],
-                push_constant_ranges: &[wgpu::PushConstantRange {
-                    stages: wgpu::ShaderStages::COMPUTE,
-                    range: 0..12,
-                }],
+                ]),
+                push_constant_ranges: &params.push_constant_ranges(
+                    wgpu::ShaderStages::COMPUTE,
+                    std::mem::size_of::<UpdateParam>() as u32,
+                ),
             });
 
-        let edge_x_electric = [(); 4].map(|_| {
-            PMLEdgeX::new(
-                device,
-                cells,
-                simulation_dimension,
-                magnetic_field_view,
-                electric_constant_map,
-                &psi_edge_self_update_bind_group_layout,
-                &psi_edge_field_update_bind_group_layout,
-            )
-        });
-
-        let edge_x_magnetic = [(); 4].map(|_| {
-            PMLEdgeX::new(
-                device,
-                cells,
-                simulation_dimension,
-                electric_field_view,
-                magnetic_constant_map,
-                &psi_edge_self_update_bind_group_layout,
-                &psi_edge_field_update_bind_group_layout,
-            )
-        });
+        let edge_x_electric: Vec<_> = regions.edges[0]
+            .iter()
+            .map(|_| {
+                PMLEdgeX::new(
+                    device,
+                    cells,
+                    simulation_dimension,
+                    magnetic_field_view,
+                    electric_constant_map,
+                    &psi_edge_self_update_bind_group_layout,
+                    &psi_edge_field_update_bind_group_layout,
+                )
+            })
+            .collect();
 
-        let edge_x_self_update_shader_module = device
-            .create_shader_module(wgpu::include_wgsl!("../../shader/fdtd/pml_edge_x_psi.wgsl"));
+        let edge_x_magnetic: Vec<_> = regions.edges[0]
+            .iter()
+            .map(|_| {
+                PMLEdgeX::new(
+                    device,
+                    cells,
+                    simulation_dimension,
+                    electric_field_view,
+                    magnetic_constant_map,
+                    &psi_edge_self_update_bind_group_layout,
+                    &psi_edge_field_update_bind_group_layout,
+                )
+            })
+            .collect();
+
+        let edge_x_self_update_shader_module = device.create_shader_module(params.shader(
+            wgpu::ShaderModuleDescriptor {
+                label: Some("pml_edge_x_psi.wgsl"),
+                source: wgpu::ShaderSource::Wgsl(
+                    preprocess(include_str!("../../shader/fdtd/pml_edge_x_psi.wgsl")).into(),
+                ),
+            },
+            1,
+        ));
 
         let edge_x_self_update_pipeline_magnetic =
             device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
@@ -1649,9 +1759,21 @@ impl PMLBoundary {
                 entry_point: "update_electric_psi",
             });
 
-        let edge_x_field_update_shader_module = device.create_shader_module(wgpu::include_wgsl!(
-            "../../shader/fdtd/pml_edge_x_field.wgsl"
-        ));
+        let edge_x_field_update_shader_module = device.create_shader_module(
+            params.shader(
+                wgpu::ShaderModuleDescriptor {
+                    label: Some("pml_edge_x_field.wgsl"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        preprocess(concat!(
+                            include_str!("../../shader/fdtd/update.wgsl"),
+                            include_str!("../../shader/fdtd/pml_edge_x_field.wgsl")
+                        ))
+                        .into(),
+                    ),
+                },
+                2,
+            ),
+        );
 
         let edge_x_field_update_pipeline_magnetic =
             device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
@@ -1669,32 +1791,45 @@ impl PMLBoundary {
                 entry_point: "update_electric_field",
             });
 
-        let edge_y_electric = [(); 4].map(|_| {
-            PMLEdgeY::new(
-                device,
-                cells,
-                simulation_dimension,
-                magnetic_field_view,
-                electric_constant_map,
-                &psi_edge_self_update_bind_group_layout,
-                &psi_edge_field_update_bind_group_layout,
-            )
-        });
-
-        let edge_y_magnetic = [(); 4].map(|_| {
-            PMLEdgeY::new(
-                device,
-                cells,
-                simulation_dimension,
-                electric_field_view,
-                magnetic_constant_map,
-                &psi_edge_self_update_bind_group_layout,
-                &psi_edge_field_update_bind_group_layout,
-            )
-        });
+        let edge_y_electric: Vec<_> = regions.edges[1]
+            .iter()
+            .map(|_| {
+                PMLEdgeY::new(
+                    device,
+                    cells,
+                    simulation_dimension,
+                    magnetic_field_view,
+                    electric_constant_map,
+                    &psi_edge_self_update_bind_group_layout,
+                    &psi_edge_field_update_bind_group_layout,
+                )
+            })
+            .collect();
 
-        let edge_y_self_update_shader_module = device
-            .create_shader_module(wgpu::include_wgsl!("../../shader/fdtd/pml_edge_y_psi.wgsl"));
+        let edge_y_magnetic: Vec<_> = regions.edges[1]
+            .iter()
+            .map(|_| {
+                PMLEdgeY::new(
+                    device,
+                    cells,
+                    simulation_dimension,
+                    electric_field_view,
+                    magnetic_constant_map,
+                    &psi_edge_self_update_bind_group_layout,
+                    &psi_edge_field_update_bind_group_layout,
+                )
+            })
+            .collect();
+
+        let edge_y_self_update_shader_module = device.create_shader_module(params.shader(
+            wgpu::ShaderModuleDescriptor {
+                label: Some("pml_edge_y_psi.wgsl"),
+                source: wgpu::ShaderSource::Wgsl(
+                    preprocess(include_str!("../../shader/fdtd/pml_edge_y_psi.wgsl")).into(),
+                ),
+            },
+            1,
+        ));
 
         let edge_y_self_update_pipeline_magnetic =
             device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
@@ -1712,9 +1847,21 @@ impl PMLBoundary {
                 entry_point: "update_electric_psi",
             });
 
-        let edge_y_field_update_shader_module = device.create_shader_module(wgpu::include_wgsl!(
-            "../../shader/fdtd/pml_edge_y_field.wgsl"
-        ));
+        let edge_y_field_update_shader_module = device.create_shader_module(
+            params.shader(
+                wgpu::ShaderModuleDescriptor {
+                    label: Some("pml_edge_y_field.wgsl"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        preprocess(concat!(
+                            include_str!("../../shader/fdtd/update.wgsl"),
+                            include_str!("../../shader/fdtd/pml_edge_y_field.wgsl")
+                        ))
+                        .into(),
+                    ),
+                },
+                2,
+            ),
+        );
 
         let edge_y_field_update_pipeline_magnetic =
             device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
@@ -1732,32 +1879,45 @@ impl PMLBoundary {
                 entry_point: "update_electric_field",
             });
 
-        let edge_z_electric = [(); 4].map(|_| {
-            PMLEdgeZ::new(
-                device,
-                cells,
-                simulation_dimension,
-                magnetic_field_view,
-                electric_constant_map,
-                &psi_edge_self_update_bind_group_layout,
-                &psi_edge_field_update_bind_group_layout,
-            )
-        });
-
-        let edge_z_magnetic = [(); 4].map(|_| {
-            PMLEdgeZ::new(
-                device,
-                cells,
-                simulation_dimension,
-                electric_field_view,
-                magnetic_constant_map,
-                &psi_edge_self_update_bind_group_layout,
-                &psi_edge_field_update_bind_group_layout,
-            )
-        });
+        let edge_z_electric: Vec<_> = regions.edges[2]
+            .iter()
+            .map(|_| {
+                PMLEdgeZ::new(
+                    device,
+                    cells,
+                    simulation_dimension,
+                    magnetic_field_view,
+                    electric_constant_map,
+                    &psi_edge_self_update_bind_group_layout,
+                    &psi_edge_field_update_bind_group_layout,
+                )
+            })
+            .collect();
 
-        let edge_z_self_update_shader_module = device
-            .create_shader_module(wgpu::include_wgsl!("../../shader/fdtd/pml_edge_z_psi.wgsl"));
+        let edge_z_magnetic: Vec<_> = regions.edges[2]
+            .iter()
+            .map(|_| {
+                PMLEdgeZ::new(
+                    device,
+                    cells,
+                    simulation_dimension,
+                    electric_field_view,
+                    magnetic_constant_map,
+                    &psi_edge_self_update_bind_group_layout,
+                    &psi_edge_field_update_bind_group_layout,
+                )
+            })
+            .collect();
+
+        let edge_z_self_update_shader_module = device.create_shader_module(params.shader(
+            wgpu::ShaderModuleDescriptor {
+                label: Some("pml_edge_z_psi.wgsl"),
+                source: wgpu::ShaderSource::Wgsl(
+                    preprocess(include_str!("../../shader/fdtd/pml_edge_z_psi.wgsl")).into(),
+                ),
+            },
+            1,
+        ));
 
         let edge_z_self_update_pipeline_magnetic =
             device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
@@ -1775,9 +1935,21 @@ impl PMLBoundary {
                 entry_point: "update_electric_psi",
             });
 
-        let edge_z_field_update_shader_module = device.create_shader_module(wgpu::include_wgsl!(
-            "../../shader/fdtd/pml_edge_z_field.wgsl"
-        ));
+        let edge_z_field_update_shader_module = device.create_shader_module(
+            params.shader(
+                wgpu::ShaderModuleDescriptor {
+                    label: Some("pml_edge_z_field.wgsl"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        preprocess(concat!(
+                            include_str!("../../shader/fdtd/update.wgsl"),
+                            include_str!("../../shader/fdtd/pml_edge_z_field.wgsl")
+                        ))
+                        .into(),
+                    ),
+                },
+                2,
+            ),
+        );
 
         let edge_z_field_update_pipeline_magnetic =
             device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
@@ -1801,6 +1973,15 @@ impl PMLBoundary {
             corner_field_update_pipeline_magnetic,
             corner_field_update_pipeline_electric,
             cells,
+            padding: faces
+                .sides()
+                .map(|sides| sides.map(|enabled| enabled as u32 * cells)),
+            regions,
+            pmc_faces,
+            periodic,
+            bloch_phase,
+            partner_sign,
+            workgroup,
             corner_magnetic,
             corner_electric,
             simulation_dimension,
@@ -1828,8 +2009,6 @@ impl PMLBoundary {
             surface_z_self_update_pipeline_electric,
             surface_z_field_update_pipeline_magnetic,
             surface_z_field_update_pipeline_electric,
-            electric_field_update_bind_group,
-            magnetic_field_update_bind_group,
             edge_x_magnetic,
             edge_x_electric,
             edge_x_self_update_pipeline_magnetic,
@@ -1847,538 +2026,322 @@ impl PMLBoundary {
         }
     }
 
-    pub fn update_electric_field<'a>(&'a self, cpass: &mut wgpu::ComputePass<'a>) {
-        self.corner_electric
-            .iter()
-            .enumerate()
-            .for_each(|(idx, corner)| {
-                cpass.set_pipeline(&self.corner_self_update_pipeline_electric);
-                cpass.set_bind_group(0, &corner.psi_self_update_bind_group, &[]);
-                let offset: [u32; 3] = match idx {
-                    0 => [0; 3],
-                    1 => [self.cells + self.simulation_dimension[0], 0, 0],
-                    2 => [
-                        self.cells + self.simulation_dimension[0],
-                        self.cells + self.simulation_dimension[1],
-                        0,
-                    ],
-                    3 => [0, self.cells + self.simulation_dimension[1], 0],
-                    4 => [0, 0, self.cells + self.simulation_dimension[2]],
-                    5 => [
-                        self.cells + self.simulation_dimension[0],
-                        0,
-                        self.cells + self.simulation_dimension[2],
-                    ],
-                    6 => [
-                        self.cells + self.simulation_dimension[0],
-                        self.cells + self.simulation_dimension[1],
-                        self.cells + self.simulation_dimension[2],
-                    ],
-                    7 => [
-                        0,
-                        self.cells + self.simulation_dimension[1],
-                        self.cells + self.simulation_dimension[2],
-                    ],
-                    _ => unreachable!(),
-                };
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.set_push_constants(
-                    12,
-                    bytemuck::cast_slice(&[self.psi_constant, self.alpha_factor]),
-                );
-                cpass.dispatch_workgroups(
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                );
-                cpass.set_pipeline(&self.corner_field_update_pipeline_electric);
-                cpass.set_bind_group(0, &self.electric_field_update_bind_group, &[]);
-                cpass.set_bind_group(1, &corner.psi_field_update_bind_group, &[]);
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.dispatch_workgroups(
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                );
-            });
+    // first texel of a region along `axis`, on `side` or 1 past it, the interior
+    fn offset(&self, axis: usize, side: usize) -> u32 {
+        match side {
+            0 => 0,
+            1 => self.padding[axis][0] + self.simulation_dimension[axis],
+            _ => self.padding[axis][0],
+        }
+    }
 
-        self.surface_x_electric
+    fn corner_offsets(&self) -> Vec<[u32; 3]> {
+        self.regions
+            .corners
             .iter()
-            .enumerate()
-            .for_each(|(idx, surface)| {
-                cpass.set_pipeline(&self.surface_x_self_update_pipeline_electric);
-                cpass.set_bind_group(0, &surface.psi_self_update_bind_group, &[]);
-                let offset: [u32; 3] = match idx {
-                    0 => [0, self.cells, self.cells],
-                    1 => [
-                        self.cells + self.simulation_dimension[0],
-                        self.cells,
-                        self.cells,
-                    ],
-                    _ => unreachable!(),
-                };
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.set_push_constants(12, bytemuck::cast_slice(&[self.alpha_factor]));
-                cpass.dispatch_workgroups(
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[1] as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[2] as f32 / 8.0).ceil() as u32,
-                );
-                cpass.set_pipeline(&self.surface_x_field_update_pipeline_electric);
-                cpass.set_bind_group(0, &self.electric_field_update_bind_group, &[]);
-                cpass.set_bind_group(1, &surface.psi_field_update_bind_group, &[]);
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.dispatch_workgroups(
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[1] as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[2] as f32 / 8.0).ceil() as u32,
-                );
-            });
-        self.surface_y_electric
-            .iter()
-            .enumerate()
-            .for_each(|(idx, surface)| {
-                cpass.set_pipeline(&self.surface_y_self_update_pipeline_electric);
-                cpass.set_bind_group(0, &surface.psi_self_update_bind_group, &[]);
-                let offset: [u32; 3] = match idx {
-                    0 => [self.cells, 0, self.cells],
-                    1 => [
-                        self.cells,
-                        self.cells + self.simulation_dimension[1],
-                        self.cells,
-                    ],
-                    _ => unreachable!(),
-                };
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.set_push_constants(12, bytemuck::cast_slice(&[self.alpha_factor]));
-                cpass.dispatch_workgroups(
-                    (self.simulation_dimension[0] as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[2] as f32 / 8.0).ceil() as u32,
-                );
-                cpass.set_pipeline(&self.surface_y_field_update_pipeline_electric);
-                cpass.set_bind_group(0, &self.electric_field_update_bind_group, &[]);
-                cpass.set_bind_group(1, &surface.psi_field_update_bind_group, &[]);
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.dispatch_workgroups(
-                    (self.simulation_dimension[0] as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[2] as f32 / 8.0).ceil() as u32,
-                );
-            });
+            .map(|sides| [0, 1, 2].map(|axis| self.offset(axis, sides[axis])))
+            .collect()
+    }
 
-        self.surface_z_electric
-            .iter()
-            .enumerate()
-            .for_each(|(idx, surface)| {
-                cpass.set_pipeline(&self.surface_z_self_update_pipeline_electric);
-                cpass.set_bind_group(0, &surface.psi_self_update_bind_group, &[]);
-                let offset: [u32; 3] = match idx {
-                    0 => [self.cells, self.cells, 0],
-                    1 => [
-                        self.cells,
-                        self.cells,
-                        self.cells + self.simulation_dimension[2],
-                    ],
-                    _ => unreachable!(),
-                };
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.set_push_constants(12, bytemuck::cast_slice(&[self.alpha_factor]));
-                cpass.dispatch_workgroups(
-                    (self.simulation_dimension[0] as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[1] as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                );
-                cpass.set_pipeline(&self.surface_z_field_update_pipeline_electric);
-                cpass.set_bind_group(0, &self.electric_field_update_bind_group, &[]);
-                cpass.set_bind_group(1, &surface.psi_field_update_bind_group, &[]);
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.dispatch_workgroups(
-                    (self.simulation_dimension[0] as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[1] as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                );
-            });
+    fn surface_offsets(&self) -> [Vec<[u32; 3]>; 3] {
+        [0, 1, 2].map(|normal| {
+            self.regions.surfaces[normal]
+                .iter()
+                .map(|&side| {
+                    [0, 1, 2].map(|axis| self.offset(axis, if axis == normal { side } else { 2 }))
+                })
+                .collect()
+        })
+    }
 
-        self.edge_x_electric
-            .iter()
-            .enumerate()
-            .for_each(|(idx, edge)| {
-                cpass.set_pipeline(&self.edge_x_self_update_pipeline_electric);
-                cpass.set_bind_group(0, &edge.psi_self_update_bind_group, &[]);
-                let offset: [u32; 3] = match idx {
-                    0 => [self.cells, 0, 0],
-                    1 => [self.cells, 0, self.cells + self.simulation_dimension[2]],
-                    2 => [
-                        self.cells,
-                        self.cells + self.simulation_dimension[1],
-                        self.cells + self.simulation_dimension[2],
-                    ],
-                    3 => [self.cells, self.cells + self.simulation_dimension[1], 0],
-                    _ => unreachable!(),
-                };
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.set_push_constants(
-                    12,
-                    bytemuck::cast_slice(&[self.psi_constant, self.alpha_factor]),
-                );
-                cpass.dispatch_workgroups(
-                    (self.simulation_dimension[0] as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                );
-                cpass.set_pipeline(&self.edge_x_field_update_pipeline_electric);
-                cpass.set_bind_group(0, &self.electric_field_update_bind_group, &[]);
-                cpass.set_bind_group(1, &edge.psi_field_update_bind_group, &[]);
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.dispatch_workgroups(
-                    (self.simulation_dimension[0] as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                );
-            });
+    fn edge_offsets(&self) -> [Vec<[u32; 3]>; 3] {
+        [0, 1, 2].map(|along| {
+            let [u, v] = other_axes(along);
+            self.regions.edges[along]
+                .iter()
+                .map(|sides| {
+                    let mut offset = [0; 3];
+                    offset[along] = self.offset(along, 2);
+                    offset[u] = self.offset(u, sides[0]);
+                    offset[v] = self.offset(v, sides[1]);
+                    offset
+                })
+                .collect()
+        })
+    }
 
-        self.edge_y_electric
-            .iter()
-            .enumerate()
-            .for_each(|(idx, edge)| {
-                cpass.set_pipeline(&self.edge_y_self_update_pipeline_electric);
-                cpass.set_bind_group(0, &edge.psi_self_update_bind_group, &[]);
-                let offset: [u32; 3] = match idx {
-                    0 => [0, self.cells, 0],
-                    1 => [self.cells + self.simulation_dimension[0], self.cells, 0],
-                    2 => [
-                        self.cells + self.simulation_dimension[0],
-                        self.cells,
-                        self.cells + self.simulation_dimension[2],
-                    ],
-                    3 => [0, self.cells, self.cells + self.simulation_dimension[2]],
-                    _ => unreachable!(),
-                };
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.set_push_constants(
-                    12,
-                    bytemuck::cast_slice(&[self.psi_constant, self.alpha_factor]),
+    /// Dispatches the psi passes of every region in a class, then their field passes.
+    /// The pipelines are only switched once per class. Psi is computed from the other field
+    /// than the one the field passes write, so running all psi passes first is safe.
+    #[allow(clippy::too_many_arguments)]
+    fn update_class<'a, R: PMLRegion>(
+        &self,
+        cpass: &mut wgpu::ComputePass<'a>,
+        params: &'a Params,
+        psi_pipeline: &'a wgpu::ComputePipeline,
+        field_pipeline: &'a wgpu::ComputePipeline,
+        field_bind_group: &'a wgpu::BindGroup,
+        regions: &'a [R],
+        offsets: &[[u32; 3]],
+        extent: [u32; 3],
+    ) {
+        let workgroups =
+            [0, 1, 2].map(|i| (extent[i] as f32 / self.workgroup[i] as f32).ceil() as u32);
+
+        cpass.set_pipeline(psi_pipeline);
+        for (region, &offset) in regions.iter().zip(offsets) {
+            cpass.set_bind_group(0, &region.bind_groups()[0], &[]);
+            if R::SURFACE {
+                params.set_compute(
+                    cpass,
+                    1,
+                    bytemuck::bytes_of(&SurfacePsiParam {
+                        offset,
+                        alpha_factor: self.alpha_factor,
+                    }),
                 );
-                cpass.dispatch_workgroups(
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[1] as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
+            } else {
+                params.set_compute(
+                    cpass,
+                    1,
+                    bytemuck::bytes_of(&PsiParam {
+                        offset,
+                        psi_constant: self.psi_constant,
+                        alpha_factor: self.alpha_factor,
+                    }),
                 );
-                cpass.set_pipeline(&self.edge_y_field_update_pipeline_electric);
-                cpass.set_bind_group(0, &self.electric_field_update_bind_group, &[]);
-                cpass.set_bind_group(1, &edge.psi_field_update_bind_group, &[]);
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.dispatch_workgroups(
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[1] as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                );
-            });
+            }
+            cpass.dispatch_workgroups(workgroups[0], workgroups[1], workgroups[2]);
+        }
 
-        self.edge_z_electric
-            .iter()
-            .enumerate()
-            .for_each(|(idx, edge)| {
-                cpass.set_pipeline(&self.edge_z_self_update_pipeline_electric);
-                cpass.set_bind_group(0, &edge.psi_self_update_bind_group, &[]);
-                let offset: [u32; 3] = match idx {
-                    0 => [0, 0, self.cells],
-                    1 => [self.cells + self.simulation_dimension[0], 0, self.cells],
-                    2 => [
-                        self.cells + self.simulation_dimension[0],
-                        self.cells + self.simulation_dimension[1],
-                        self.cells,
-                    ],
-                    3 => [0, self.cells + self.simulation_dimension[1], self.cells],
-                    _ => unreachable!(),
-                };
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.set_push_constants(
-                    12,
-                    bytemuck::cast_slice(&[self.psi_constant, self.alpha_factor]),
-                );
-                cpass.dispatch_workgroups(
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[2] as f32 / 8.0).ceil() as u32,
-                );
-                cpass.set_pipeline(&self.edge_z_field_update_pipeline_electric);
-                cpass.set_bind_group(0, &self.electric_field_update_bind_group, &[]);
-                cpass.set_bind_group(1, &edge.psi_field_update_bind_group, &[]);
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.dispatch_workgroups(
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[2] as f32 / 8.0).ceil() as u32,
-                );
-            });
+        cpass.set_pipeline(field_pipeline);
+        cpass.set_bind_group(0, field_bind_group, &[]);
+        for (region, &offset) in regions.iter().zip(offsets) {
+            cpass.set_bind_group(1, &region.bind_groups()[1], &[]);
+            params.set_compute(
+                cpass,
+                2,
+                bytemuck::bytes_of(&UpdateParam {
+                    dimension: [0, 1, 2].map(|axis| {
+                        self.simulation_dimension[axis]
+                            + self.padding[axis][0]
+                            + self.padding[axis][1]
+                    }),
+                    pmc_faces: self.pmc_faces,
+                    offset,
+                    periodic: self.periodic,
+                    end: [0; 3],
+                    partner_sign: self.partner_sign,
+                    bloch_phase: self.bloch_phase,
+                    _padding2: 0,
+                }),
+            );
+            cpass.dispatch_workgroups(workgroups[0], workgroups[1], workgroups[2]);
+        }
     }
 
-    pub fn update_magnetic_field<'a>(&'a self, cpass: &mut wgpu::ComputePass<'a>) {
-        self.corner_magnetic
-            .iter()
-            .enumerate()
-            .for_each(|(idx, corner)| {
-                cpass.set_pipeline(&self.corner_self_update_pipeline_magnetic);
-                cpass.set_bind_group(0, &corner.psi_self_update_bind_group, &[]);
-                let offset: [u32; 3] = match idx {
-                    0 => [0; 3],
-                    1 => [self.cells + self.simulation_dimension[0], 0, 0],
-                    2 => [
-                        self.cells + self.simulation_dimension[0],
-                        self.cells + self.simulation_dimension[1],
-                        0,
-                    ],
-                    3 => [0, self.cells + self.simulation_dimension[1], 0],
-                    4 => [0, 0, self.cells + self.simulation_dimension[2]],
-                    5 => [
-                        self.cells + self.simulation_dimension[0],
-                        0,
-                        self.cells + self.simulation_dimension[2],
-                    ],
-                    6 => [
-                        self.cells + self.simulation_dimension[0],
-                        self.cells + self.simulation_dimension[1],
-                        self.cells + self.simulation_dimension[2],
-                    ],
-                    7 => [
-                        0,
-                        self.cells + self.simulation_dimension[1],
-                        self.cells + self.simulation_dimension[2],
-                    ],
-                    _ => unreachable!(),
-                };
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.set_push_constants(
-                    12,
-                    bytemuck::cast_slice(&[self.psi_constant, self.alpha_factor]),
-                );
-                cpass.dispatch_workgroups(
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                );
-                cpass.set_pipeline(&self.corner_field_update_pipeline_magnetic);
-                cpass.set_bind_group(0, &self.magnetic_field_update_bind_group, &[]);
-                cpass.set_bind_group(1, &corner.psi_field_update_bind_group, &[]);
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.dispatch_workgroups(
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                );
-            });
-        self.surface_x_magnetic
-            .iter()
-            .enumerate()
-            .for_each(|(idx, surface)| {
-                cpass.set_pipeline(&self.surface_x_self_update_pipeline_magnetic);
-                cpass.set_bind_group(0, &surface.psi_self_update_bind_group, &[]);
-                let offset: [u32; 3] = match idx {
-                    0 => [0, self.cells, self.cells],
-                    1 => [
-                        self.cells + self.simulation_dimension[0],
-                        self.cells,
-                        self.cells,
-                    ],
-                    _ => unreachable!(),
-                };
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.set_push_constants(12, bytemuck::cast_slice(&[self.alpha_factor]));
-                cpass.dispatch_workgroups(
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[1] as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[2] as f32 / 8.0).ceil() as u32,
-                );
-                cpass.set_pipeline(&self.surface_x_field_update_pipeline_magnetic);
-                cpass.set_bind_group(0, &self.magnetic_field_update_bind_group, &[]);
-                cpass.set_bind_group(1, &surface.psi_field_update_bind_group, &[]);
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.dispatch_workgroups(
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[1] as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[2] as f32 / 8.0).ceil() as u32,
-                );
-            });
-        self.surface_y_magnetic
-            .iter()
-            .enumerate()
-            .for_each(|(idx, surface)| {
-                cpass.set_pipeline(&self.surface_y_self_update_pipeline_magnetic);
-                cpass.set_bind_group(0, &surface.psi_self_update_bind_group, &[]);
-                let offset: [u32; 3] = match idx {
-                    0 => [self.cells, 0, self.cells],
-                    1 => [
-                        self.cells,
-                        self.cells + self.simulation_dimension[1],
-                        self.cells,
-                    ],
-                    _ => unreachable!(),
-                };
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.set_push_constants(12, bytemuck::cast_slice(&[self.alpha_factor]));
-                cpass.dispatch_workgroups(
-                    (self.simulation_dimension[0] as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[2] as f32 / 8.0).ceil() as u32,
-                );
-                cpass.set_pipeline(&self.surface_y_field_update_pipeline_magnetic);
-                cpass.set_bind_group(0, &self.magnetic_field_update_bind_group, &[]);
-                cpass.set_bind_group(1, &surface.psi_field_update_bind_group, &[]);
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.dispatch_workgroups(
-                    (self.simulation_dimension[0] as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[2] as f32 / 8.0).ceil() as u32,
-                );
-            });
+    /// `field_bind_group` is the one of the main update, the PML field passes do the full
+    /// Yee update of their cells with the psi correction added.
+    pub fn update_electric_field<'a>(
+        &'a self,
+        cpass: &mut wgpu::ComputePass<'a>,
+        params: &'a Params,
+        field_bind_group: &'a wgpu::BindGroup,
+    ) {
+        let [x, y, z] = self.simulation_dimension;
+        let c = self.cells;
+        let surface_offsets = self.surface_offsets();
+        let edge_offsets = self.edge_offsets();
+
+        self.update_class(
+            cpass,
+            params,
+            &self.corner_self_update_pipeline_electric,
+            &self.corner_field_update_pipeline_electric,
+            field_bind_group,
+            &self.corner_electric,
+            &self.corner_offsets(),
+            [c, c, c],
+        );
+        self.update_class(
+            cpass,
+            params,
+            &self.surface_x_self_update_pipeline_electric,
+            &self.surface_x_field_update_pipeline_electric,
+            field_bind_group,
+            &self.surface_x_electric,
+            &surface_offsets[0],
+            [c, y, z],
+        );
+        self.update_class(
+            cpass,
+            params,
+            &self.surface_y_self_update_pipeline_electric,
+            &self.surface_y_field_update_pipeline_electric,
+            field_bind_group,
+            &self.surface_y_electric,
+            &surface_offsets[1],
+            [x, c, z],
+        );
+        self.update_class(
+            cpass,
+            params,
+            &self.surface_z_self_update_pipeline_electric,
+            &self.surface_z_field_update_pipeline_electric,
+            field_bind_group,
+            &self.surface_z_electric,
+            &surface_offsets[2],
+            [x, y, c],
+        );
+        self.update_class(
+            cpass,
+            params,
+            &self.edge_x_self_update_pipeline_electric,
+            &self.edge_x_field_update_pipeline_electric,
+            field_bind_group,
+            &self.edge_x_electric,
+            &edge_offsets[0],
+            [x, c, c],
+        );
+        self.update_class(
+            cpass,
+            params,
+            &self.edge_y_self_update_pipeline_electric,
+            &self.edge_y_field_update_pipeline_electric,
+            field_bind_group,
+            &self.edge_y_electric,
+            &edge_offsets[1],
+            [c, y, c],
+        );
+        self.update_class(
+            cpass,
+            params,
+            &self.edge_z_self_update_pipeline_electric,
+            &self.edge_z_field_update_pipeline_electric,
+            field_bind_group,
+            &self.edge_z_electric,
+            &edge_offsets[2],
+            [c, c, z],
+        );
+    }
 
-        self.surface_z_magnetic
-            .iter()
-            .enumerate()
-            .for_each(|(idx, surface)| {
-                cpass.set_pipeline(&self.surface_z_self_update_pipeline_magnetic);
-                cpass.set_bind_group(0, &surface.psi_self_update_bind_group, &[]);
-                let offset: [u32; 3] = match idx {
-                    0 => [self.cells, self.cells, 0],
-                    1 => [
-                        self.cells,
-                        self.cells,
-                        self.cells + self.simulation_dimension[2],
-                    ],
-                    _ => unreachable!(),
-                };
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.set_push_constants(12, bytemuck::cast_slice(&[self.alpha_factor]));
-                cpass.dispatch_workgroups(
-                    (self.simulation_dimension[0] as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[1] as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                );
-                cpass.set_pipeline(&self.surface_z_field_update_pipeline_magnetic);
-                cpass.set_bind_group(0, &self.magnetic_field_update_bind_group, &[]);
-                cpass.set_bind_group(1, &surface.psi_field_update_bind_group, &[]);
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.dispatch_workgroups(
-                    (self.simulation_dimension[0] as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[1] as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                );
-            });
+    /// `field_bind_group` is the one of the main update, the PML field passes do the full
+    /// Yee update of their cells with the psi correction added.
+    pub fn update_magnetic_field<'a>(
+        &'a self,
+        cpass: &mut wgpu::ComputePass<'a>,
+        params: &'a Params,
+        field_bind_group: &'a wgpu::BindGroup,
+    ) {
+        let [x, y, z] = self.simulation_dimension;
+        let c = self.cells;
+        let surface_offsets = self.surface_offsets();
+        let edge_offsets = self.edge_offsets();
+
+        self.update_class(
+            cpass,
+            params,
+            &self.corner_self_update_pipeline_magnetic,
+            &self.corner_field_update_pipeline_magnetic,
+            field_bind_group,
+            &self.corner_magnetic,
+            &self.corner_offsets(),
+            [c, c, c],
+        );
+        self.update_class(
+            cpass,
+            params,
+            &self.surface_x_self_update_pipeline_magnetic,
+            &self.surface_x_field_update_pipeline_magnetic,
+            field_bind_group,
+            &self.surface_x_magnetic,
+            &surface_offsets[0],
+            [c, y, z],
+        );
+        self.update_class(
+            cpass,
+            params,
+            &self.surface_y_self_update_pipeline_magnetic,
+            &self.surface_y_field_update_pipeline_magnetic,
+            field_bind_group,
+            &self.surface_y_magnetic,
+            &surface_offsets[1],
+            [x, c, z],
+        );
+        self.update_class(
+            cpass,
+            params,
+            &self.surface_z_self_update_pipeline_magnetic,
+            &self.surface_z_field_update_pipeline_magnetic,
+            field_bind_group,
+            &self.surface_z_magnetic,
+            &surface_offsets[2],
+            [x, y, c],
+        );
+        self.update_class(
+            cpass,
+            params,
+            &self.edge_x_self_update_pipeline_magnetic,
+            &self.edge_x_field_update_pipeline_magnetic,
+            field_bind_group,
+            &self.edge_x_magnetic,
+            &edge_offsets[0],
+            [x, c, c],
+        );
+        self.update_class(
+            cpass,
+            params,
+            &self.edge_y_self_update_pipeline_magnetic,
+            &self.edge_y_field_update_pipeline_magnetic,
+            field_bind_group,
+            &self.edge_y_magnetic,
+            &edge_offsets[1],
+            [c, y, c],
+        );
+        self.update_class(
+            cpass,
+            params,
+            &self.edge_z_self_update_pipeline_magnetic,
+            &self.edge_z_field_update_pipeline_magnetic,
+            field_bind_group,
+            &self.edge_z_magnetic,
+            &edge_offsets[2],
+            [c, c, z],
+        );
+    }
 
-        self.edge_x_magnetic
+    /// All psi textures in a fixed order.
+    pub fn psi_textures(&self) -> impl Iterator<Item = &wgpu::Texture> {
+        let corners = self.corner_magnetic.iter().chain(&self.corner_electric);
+        let surfaces_x = self
+            .surface_x_magnetic
             .iter()
-            .enumerate()
-            .for_each(|(idx, edge)| {
-                cpass.set_pipeline(&self.edge_x_self_update_pipeline_magnetic);
-                cpass.set_bind_group(0, &edge.psi_self_update_bind_group, &[]);
-                let offset: [u32; 3] = match idx {
-                    0 => [self.cells, 0, 0],
-                    1 => [self.cells, 0, self.cells + self.simulation_dimension[2]],
-                    2 => [
-                        self.cells,
-                        self.cells + self.simulation_dimension[1],
-                        self.cells + self.simulation_dimension[2],
-                    ],
-                    3 => [self.cells, self.cells + self.simulation_dimension[1], 0],
-                    _ => unreachable!(),
-                };
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.set_push_constants(
-                    12,
-                    bytemuck::cast_slice(&[self.psi_constant, self.alpha_factor]),
-                );
-                cpass.dispatch_workgroups(
-                    (self.simulation_dimension[0] as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                );
-                cpass.set_pipeline(&self.edge_x_field_update_pipeline_magnetic);
-                cpass.set_bind_group(0, &self.magnetic_field_update_bind_group, &[]);
-                cpass.set_bind_group(1, &edge.psi_field_update_bind_group, &[]);
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.dispatch_workgroups(
-                    (self.simulation_dimension[0] as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                );
-            });
-
-        self.edge_y_magnetic
+            .chain(&self.surface_x_electric);
+        let surfaces_y = self
+            .surface_y_magnetic
             .iter()
-            .enumerate()
-            .for_each(|(idx, edge)| {
-                cpass.set_pipeline(&self.edge_y_self_update_pipeline_magnetic);
-                cpass.set_bind_group(0, &edge.psi_self_update_bind_group, &[]);
-                let offset: [u32; 3] = match idx {
-                    0 => [0, self.cells, 0],
-                    1 => [self.cells + self.simulation_dimension[0], self.cells, 0],
-                    2 => [
-                        self.cells + self.simulation_dimension[0],
-                        self.cells,
-                        self.cells + self.simulation_dimension[2],
-                    ],
-                    3 => [0, self.cells, self.cells + self.simulation_dimension[2]],
-                    _ => unreachable!(),
-                };
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.set_push_constants(
-                    12,
-                    bytemuck::cast_slice(&[self.psi_constant, self.alpha_factor]),
-                );
-                cpass.dispatch_workgroups(
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[1] as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                );
-                cpass.set_pipeline(&self.edge_y_field_update_pipeline_magnetic);
-                cpass.set_bind_group(0, &self.magnetic_field_update_bind_group, &[]);
-                cpass.set_bind_group(1, &edge.psi_field_update_bind_group, &[]);
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.dispatch_workgroups(
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[1] as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                );
-            });
-
-        self.edge_z_magnetic
+            .chain(&self.surface_y_electric);
+        let surfaces_z = self
+            .surface_z_magnetic
             .iter()
-            .enumerate()
-            .for_each(|(idx, edge)| {
-                cpass.set_pipeline(&self.edge_z_self_update_pipeline_magnetic);
-                cpass.set_bind_group(0, &edge.psi_self_update_bind_group, &[]);
-                let offset: [u32; 3] = match idx {
-                    0 => [0, 0, self.cells],
-                    1 => [self.cells + self.simulation_dimension[0], 0, self.cells],
-                    2 => [
-                        self.cells + self.simulation_dimension[0],
-                        self.cells + self.simulation_dimension[1],
-                        self.cells,
-                    ],
-                    3 => [0, self.cells + self.simulation_dimension[1], self.cells],
-                    _ => unreachable!(),
-                };
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.set_push_constants(
-                    12,
-                    bytemuck::cast_slice(&[self.psi_constant, self.alpha_factor]),
-                );
-                cpass.dispatch_workgroups(
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[2] as f32 / 8.0).ceil() as u32,
-                );
-                cpass.set_pipeline(&self.edge_z_field_update_pipeline_magnetic);
-                cpass.set_bind_group(0, &self.magnetic_field_update_bind_group, &[]);
-                cpass.set_bind_group(1, &edge.psi_field_update_bind_group, &[]);
-                cpass.set_push_constants(0, bytemuck::cast_slice(&offset));
-                cpass.dispatch_workgroups(
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.cells as f32 / 8.0).ceil() as u32,
-                    (self.simulation_dimension[2] as f32 / 8.0).ceil() as u32,
-                );
-            });
+            .chain(&self.surface_z_electric);
+        let edges_x = self.edge_x_magnetic.iter().chain(&self.edge_x_electric);
+        let edges_y = self.edge_y_magnetic.iter().chain(&self.edge_y_electric);
+        let edges_z = self.edge_z_magnetic.iter().chain(&self.edge_z_electric);
+        corners
+            .flat_map(|corner| corner.psi_textures.iter())
+            .chain(surfaces_x.flat_map(|surface| surface.psi_textures.iter()))
+            .chain(surfaces_y.flat_map(|surface| surface.psi_textures.iter()))
+            .chain(surfaces_z.flat_map(|surface| surface.psi_textures.iter()))
+            .chain(edges_x.flat_map(|edge| edge.psi_textures.iter()))
+            .chain(edges_y.flat_map(|edge| edge.psi_textures.iter()))
+            .chain(edges_z.flat_map(|edge| edge.psi_textures.iter()))
+    }
+
+    /// Zeroes every psi texture.
+    pub fn clear(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.psi_textures().for_each(|texture| {
+            encoder.clear_texture(texture, &wgpu::ImageSubresourceRange::default())
+        });
     }
 }
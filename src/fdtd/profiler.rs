@@ -0,0 +1,184 @@
+use std::cell::{Cell, RefCell};
+
+// a step records a handful of passes, so one sample covers many steps
+const MAX_PASSES: u32 = 1024;
+const SAMPLE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// What the GPU time of a compute pass is accounted to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PassClass {
+    Update,
+    Pml,
+    Excitation,
+}
+
+/// Average GPU time per step spent in each pass class, in milliseconds.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PassTimes {
+    pub update: f32,
+    pub pml: f32,
+    pub excitation: f32,
+}
+
+struct Sample {
+    classes: Vec<PassClass>,
+    steps: u32,
+    receiver: Option<std::sync::mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>>,
+}
+
+/// Samples the GPU time of the compute passes of one batch of steps about once a second
+/// through timestamp queries. Pass `i` of a sample writes queries `2i` and `2i + 1`.
+pub struct Profiler {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    // nanoseconds per timestamp tick
+    period: f32,
+    recording: RefCell<Option<Vec<PassClass>>>,
+    in_flight: RefCell<Option<Sample>>,
+    last_sample: Cell<std::time::Instant>,
+}
+
+impl Profiler {
+    /// `None` when the device lacks `Features::TIMESTAMP_QUERY`.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        let size = MAX_PASSES as u64 * 2 * wgpu::QUERY_SIZE as u64;
+        Some(Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("Profiler"),
+                ty: wgpu::QueryType::Timestamp,
+                count: MAX_PASSES * 2,
+            }),
+            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Profiler Resolve"),
+                size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Profiler Readback"),
+                size,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            period: queue.get_timestamp_period(),
+            recording: RefCell::new(None),
+            in_flight: RefCell::new(None),
+            last_sample: Cell::new(std::time::Instant::now()),
+        })
+    }
+
+    /// Starts a sample with the next batch when one is due and the previous one was collected.
+    pub fn begin(&self) {
+        if self.in_flight.borrow().is_some()
+            || self.recording.borrow().is_some()
+            || self.last_sample.get().elapsed() < SAMPLE_INTERVAL
+        {
+            return;
+        }
+        self.last_sample.set(std::time::Instant::now());
+        *self.recording.borrow_mut() = Some(vec![]);
+    }
+
+    /// Timestamp writes for a pass of `class`, `None` outside of a sample or once it is full.
+    pub fn timestamp_writes(
+        &self,
+        class: PassClass,
+    ) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        let mut recording = self.recording.borrow_mut();
+        let classes = recording.as_mut()?;
+        let index = classes.len() as u32;
+        if index == MAX_PASSES {
+            return None;
+        }
+        classes.push(class);
+        Some(wgpu::ComputePassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(index * 2),
+            end_of_pass_write_index: Some(index * 2 + 1),
+        })
+    }
+
+    /// Ends the sample covering `steps` steps, `map` must be called once `encoder` was submitted.
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder, steps: u32) {
+        let Some(classes) = self.recording.borrow_mut().take() else {
+            return;
+        };
+        if classes.is_empty() || steps == 0 {
+            return;
+        }
+        let queries = classes.len() as u32 * 2;
+        encoder.resolve_query_set(&self.query_set, 0..queries, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            queries as u64 * wgpu::QUERY_SIZE as u64,
+        );
+        *self.in_flight.borrow_mut() = Some(Sample {
+            classes,
+            steps,
+            receiver: None,
+        });
+    }
+
+    /// Starts mapping the timestamps resolved by `resolve`.
+    pub fn map(&self) {
+        let mut in_flight = self.in_flight.borrow_mut();
+        let Some(sample) = in_flight
+            .as_mut()
+            .filter(|sample| sample.receiver.is_none())
+        else {
+            return;
+        };
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let size = sample.classes.len() as u64 * 2 * wgpu::QUERY_SIZE as u64;
+        self.readback_buffer
+            .slice(..size)
+            .map_async(wgpu::MapMode::Read, move |v| {
+                let _ = sender.send(v);
+            });
+        sample.receiver = Some(receiver);
+    }
+
+    /// Returns the times of a finished sample, if any.
+    pub fn collect(&self, device: &wgpu::Device) -> Option<PassTimes> {
+        let mut in_flight = self.in_flight.borrow_mut();
+        let receiver = in_flight.as_ref()?.receiver.as_ref()?;
+        device.poll(wgpu::Maintain::Poll);
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(std::sync::mpsc::TryRecvError::Empty) => return None,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                *in_flight = None;
+                return None;
+            }
+        };
+        let sample = in_flight.take().unwrap();
+        if result.is_err() {
+            self.readback_buffer.unmap();
+            return None;
+        }
+
+        let size = sample.classes.len() as u64 * 2 * wgpu::QUERY_SIZE as u64;
+        let data = self.readback_buffer.slice(..size).get_mapped_range();
+        let timestamps: &[u64] = bytemuck::cast_slice(&data);
+        let mut times = PassTimes::default();
+        for (class, pair) in sample.classes.iter().zip(timestamps.chunks_exact(2)) {
+            let milliseconds = pair[1].wrapping_sub(pair[0]) as f64 * self.period as f64 / 1e6;
+            let total = match class {
+                PassClass::Update => &mut times.update,
+                PassClass::Pml => &mut times.pml,
+                PassClass::Excitation => &mut times.excitation,
+            };
+            *total += (milliseconds / sample.steps as f64) as f32;
+        }
+        drop(data);
+        self.readback_buffer.unmap();
+        Some(times)
+    }
+}
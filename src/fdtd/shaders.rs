@@ -0,0 +1,135 @@
+use std::path::{Path, PathBuf};
+
+// copies of the runtime-loaded shaders, used when none is found on disk
+const EMBEDDED: &[(&str, &str)] = &[
+    ("vertex.wgsl", include_str!("../../shader/vertex.wgsl")),
+    (
+        "fdtd/update.wgsl",
+        include_str!("../../shader/fdtd/update.wgsl"),
+    ),
+    (
+        "fdtd/fdtd-3d.wgsl",
+        include_str!("../../shader/fdtd/fdtd-3d.wgsl"),
+    ),
+    (
+        "fdtd/fdtd-3d-tiled.wgsl",
+        include_str!("../../shader/fdtd/fdtd-3d-tiled.wgsl"),
+    ),
+    (
+        "fdtd/excitation-volume.wgsl",
+        include_str!("../../shader/fdtd/excitation-volume.wgsl"),
+    ),
+    (
+        "fdtd/excitation-mode.wgsl",
+        include_str!("../../shader/fdtd/excitation-mode.wgsl"),
+    ),
+    ("volume.wgsl", include_str!("../../shader/volume.wgsl")),
+    (
+        "display_scale.wgsl",
+        include_str!("../../shader/display_scale.wgsl"),
+    ),
+    ("line.wgsl", include_str!("../../shader/line.wgsl")),
+    (
+        "fdtd/intensity.wgsl",
+        include_str!("../../shader/fdtd/intensity.wgsl"),
+    ),
+    (
+        "fdtd/energy.wgsl",
+        include_str!("../../shader/fdtd/energy.wgsl"),
+    ),
+    (
+        "fdtd/non-finite.wgsl",
+        include_str!("../../shader/fdtd/non-finite.wgsl"),
+    ),
+    ("fdtd/dft.wgsl", include_str!("../../shader/fdtd/dft.wgsl")),
+    (
+        "fdtd/flux.wgsl",
+        include_str!("../../shader/fdtd/flux.wgsl"),
+    ),
+];
+
+/// Where shaders are read from. A built-in shader such as `fdtd/update.wgsl` is looked up
+/// in the `shader` directories next to the preset files, the later layers first, next to the
+/// executable and in the working directory, after `--shader-dir` if given, and falls back to
+/// the copy embedded at build time. Relative paths of user shaders resolve against the preset
+/// directories in the same order, then the working one.
+#[derive(Clone, Debug)]
+pub struct ShaderPaths {
+    override_directory: Option<PathBuf>,
+    preset_directories: Vec<PathBuf>,
+}
+
+impl ShaderPaths {
+    /// `presets` are the files of a layered preset, each one layered over the ones before it.
+    pub fn new(override_directory: Option<&Path>, presets: &[PathBuf]) -> Self {
+        let mut preset_directories: Vec<PathBuf> = vec![];
+        for directory in presets.iter().rev().filter_map(|preset| preset.parent()) {
+            if !preset_directories.iter().any(|known| known == directory) {
+                preset_directories.push(directory.to_path_buf());
+            }
+        }
+        Self {
+            override_directory: override_directory.map(Path::to_path_buf),
+            preset_directories,
+        }
+    }
+
+    fn shader_directories(&self) -> Vec<PathBuf> {
+        let executable_directory = std::env::current_exe()
+            .ok()
+            .and_then(|path| path.parent().map(Path::to_path_buf));
+        self.override_directory
+            .iter()
+            .cloned()
+            .chain(
+                self.preset_directories
+                    .iter()
+                    .cloned()
+                    .chain(
+                        [executable_directory, std::env::current_dir().ok()]
+                            .into_iter()
+                            .flatten(),
+                    )
+                    .map(|directory| directory.join("shader")),
+            )
+            .collect()
+    }
+
+    /// Source of the built-in shader `name`, relative to the shader directory.
+    pub fn builtin(&self, name: &str) -> anyhow::Result<String> {
+        if let Some(path) = self
+            .shader_directories()
+            .into_iter()
+            .map(|directory| directory.join(name))
+            .find(|path| path.is_file())
+        {
+            return std::fs::read_to_string(&path)
+                .map_err(|e| anyhow::anyhow!("Shader {}: {}", path.display(), e));
+        }
+        EMBEDDED
+            .iter()
+            .find(|(embedded, _)| *embedded == name)
+            .map(|(_, source)| source.to_string())
+            .ok_or_else(|| anyhow::anyhow!("Shader {} not found", name))
+    }
+
+    /// Resolves a user shader path, the path is returned unchanged when nothing matches.
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        let path = Path::new(path);
+        if path.is_absolute() {
+            return path.to_path_buf();
+        }
+        self.preset_directories
+            .iter()
+            .map(|directory| directory.join(path))
+            .find(|path| path.is_file())
+            .unwrap_or_else(|| path.to_path_buf())
+    }
+
+    /// Source of a user shader, see `resolve`.
+    pub fn read(&self, path: &str) -> anyhow::Result<String> {
+        let resolved = self.resolve(path);
+        std::fs::read_to_string(&resolved)
+            .map_err(|e| anyhow::anyhow!("Shader {}: {}", resolved.display(), e))
+    }
+}
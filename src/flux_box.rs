@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use crate::fdtd::SurfaceFields;
+
+/// Energy absorbed inside a flux box per unit frequency at one wavelength, the spectrum of a
+/// real signal folded onto positive frequencies. Dividing by the same quantity of the incident
+/// field gives the absorptance.
+#[derive(Clone, Copy, Debug)]
+pub struct AbsorptionSample {
+    pub wavelength: f32, // in vacuum
+    pub absorbed: f64,
+}
+
+/// The inflow -2 Re ∮ (E × H*) · n dA at each frequency of the transforms over a closed box.
+pub fn absorption(fields: &SurfaceFields) -> Vec<AbsorptionSample> {
+    let area = fields.area as f64;
+    fields
+        .frequencies
+        .iter()
+        .enumerate()
+        .map(|(frequency, value)| {
+            let outflow: f64 = fields
+                .cells
+                .iter()
+                .enumerate()
+                .map(|(index, cell)| {
+                    let [electric, magnetic] = fields.fields(frequency, index);
+                    let (axis, sign) = cell.normal;
+                    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+                    let poynting =
+                        electric[u] * magnetic[v].conj() - electric[v] * magnetic[u].conj();
+                    poynting.re * sign as f64
+                })
+                .sum();
+            AbsorptionSample {
+                wavelength: 1.0 / value,
+                absorbed: -2.0 * outflow * area,
+            }
+        })
+        .collect()
+}
+
+/// Writes rows of the step, the time, the power leaving through each face in the order low x,
+/// high x, low y, high y, low z, high z, and the power absorbed inside, their negated sum.
+pub fn write_power_csv(path: &Path, rows: &[(u32, [f64; 6])], dt: f32) -> anyhow::Result<()> {
+    let mut text = "step,time,low_x,high_x,low_y,high_y,low_z,high_z,absorbed\n".to_string();
+    for (step, powers) in rows {
+        text += &format!("{},{}", step, *step as f32 * dt);
+        for power in powers {
+            text += &format!(",{}", power);
+        }
+        text += &format!(",{}\n", -powers.iter().sum::<f64>());
+    }
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+/// Writes rows of the vacuum wavelength and the absorbed energy per unit frequency.
+pub fn write_absorption_csv(path: &Path, samples: &[AbsorptionSample]) -> anyhow::Result<()> {
+    let mut text = "wavelength,absorbed\n".to_string();
+    for sample in samples {
+        text += &format!("{},{}\n", sample.wavelength, sample.absorbed);
+    }
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Complex = nalgebra::Complex<f64>;
+
+    /// E along x and H along y, a wave travelling along +z, or along -z with `backward`.
+    fn wave(backward: bool) -> [[Complex; 3]; 2] {
+        let (one, zero) = (Complex::new(1.0, 0.0), Complex::default());
+        let sign = if backward { -1.0 } else { 1.0 };
+        [[one, zero, zero], [zero, one * sign, zero]]
+    }
+
+    #[test]
+    fn power_flowing_in_is_absorbed() {
+        let area = 0.25;
+        let zero = [[Complex::default(); 3]; 2];
+        // only the high z face sees a wave, travelling into the box
+        let fields = SurfaceFields::single_frequency(
+            0.5,
+            area,
+            &[
+                ([0.0, 0.0, -1.0], (2, -1.0), zero),
+                ([0.0, 0.0, 1.0], (2, 1.0), wave(true)),
+            ],
+        );
+        let samples = absorption(&fields);
+        assert_eq!(samples.len(), 1);
+        let sample = samples[0];
+        assert_eq!(sample.wavelength, 2.0);
+        // -2 Re (E × H*) · n dA with (E × H*) · n = -1
+        assert_eq!(sample.absorbed, 2.0 * area as f64);
+
+        // leaving through the high face instead, the box gives the energy out
+        let fields =
+            SurfaceFields::single_frequency(0.5, area, &[([0.0, 0.0, 1.0], (2, 1.0), wave(false))]);
+        assert_eq!(absorption(&fields)[0].absorbed, -2.0 * area as f64);
+    }
+
+    #[test]
+    fn wave_passing_through_is_not_absorbed() {
+        let fields = SurfaceFields::single_frequency(
+            0.5,
+            0.25,
+            &[
+                ([0.0, 0.0, -1.0], (2, -1.0), wave(false)),
+                ([0.0, 0.0, 1.0], (2, 1.0), wave(false)),
+            ],
+        );
+        assert_eq!(absorption(&fields)[0].absorbed, 0.0);
+    }
+
+    #[test]
+    fn absorbed_power_is_the_negated_sum_of_the_faces() {
+        let path = crate::temp_files::temp_path("flux-box-power.csv");
+        let rows = [(4, [1.0, -0.5, 0.25, 0.0, -2.0, 0.5])];
+        write_power_csv(&path, &rows, 0.5).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "step,time,low_x,high_x,low_y,high_y,low_z,high_z,absorbed\n\
+             4,2,1,-0.5,0.25,0,-2,0.5,0.75\n"
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,395 @@
+//! Runs a preset without a window, stepping and reading back synchronously. Meant for tests
+//! that check the solver against analytic results.
+
+use std::sync::Arc;
+
+use pollster::FutureExt;
+
+use crate::{fdtd, replay, settings::FDTDSettings, Source};
+
+pub use crate::far_field::FarFieldSample;
+pub use crate::fdtd::FieldType;
+pub use crate::flux_box::AbsorptionSample;
+pub use crate::mode_monitor::ModeAmplitude;
+pub use crate::resonance::Resonance;
+
+/// A simulation on an adapter of its own, a software one when available so that results do
+/// not depend on the GPU.
+pub struct Headless {
+    device: wgpu::Device,
+    queue: Arc<wgpu::Queue>,
+    fdtd: fdtd::FDTD,
+    settings: FDTDSettings,
+    magnetic_sources: Vec<Source>,
+    electric_sources: Vec<Source>,
+    field_recordings: Vec<replay::PlaneRecorder>,
+    step: u32,
+}
+
+/// Picks a software adapter over a hardware one. Downlevel adapters such as GL are left out,
+/// they lack the storage textures the update needs.
+fn request_adapter(instance: &wgpu::Instance) -> anyhow::Result<wgpu::Adapter> {
+    instance
+        .enumerate_adapters(wgpu::Backends::all())
+        .filter(|adapter| adapter.get_downlevel_capabilities().is_webgpu_compliant())
+        .min_by_key(|adapter| adapter.get_info().device_type != wgpu::DeviceType::Cpu)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "No GPU adapter found; install a Vulkan driver (lavapipe works) to run these tests"
+            )
+        })
+}
+
+impl Headless {
+    /// Sets up `preset`, given as JSON in the format of the preset files. Relative paths in it
+    /// resolve against the working directory.
+    pub fn new(preset: &str) -> anyhow::Result<Self> {
+        let settings = crate::preset::deserialize(
+            config::Config::builder()
+                .add_source(config::File::from_str(preset, config::FileFormat::Json))
+                .build()?,
+        )?;
+        Self::create(settings, &[])
+    }
+
+    /// Sets up the preset file at `path`, whose relative paths resolve against its directory
+    /// as when it is run.
+    pub fn open(path: &std::path::Path) -> anyhow::Result<Self> {
+        let paths = [path.to_string_lossy().into_owned()];
+        let preset = crate::preset::load(&paths)?;
+        let settings = crate::preset::deserialize(preset.config)?;
+        Self::create(settings, &preset.files)
+    }
+
+    /// `files` are those of the preset, whose user shaders resolve against their directories.
+    fn create(mut settings: FDTDSettings, files: &[std::path::PathBuf]) -> anyhow::Result<Self> {
+        crate::resolve_preset_files(&mut settings, std::path::Path::new(""))?;
+        crate::prepare::prepare_settings(&mut settings)?;
+
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = request_adapter(&instance)?;
+        crate::check_formats(&adapter)?;
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("Headless"),
+                    features: adapter.features(),
+                    limits: adapter.limits(),
+                },
+                None,
+            )
+            .block_on()?;
+        let queue = Arc::new(queue);
+        crate::check_workgroup(&device.limits(), &mut settings, true)?;
+
+        let params = fdtd::Params::new(&device, &queue, false);
+        let (fdtd, magnetic_sources, electric_sources) = crate::create_simulation(
+            &device,
+            &queue,
+            &adapter.limits(),
+            &mut settings,
+            &fdtd::ShaderPaths::new(None, files),
+            None,
+            params,
+            None,
+            None,
+            false,
+            false,
+        )?;
+        let field_recordings = crate::field_recorders(&device, &fdtd, &settings)?;
+        Ok(Self {
+            device,
+            queue,
+            fdtd,
+            settings,
+            magnetic_sources,
+            electric_sources,
+            field_recordings,
+            step: 0,
+        })
+    }
+
+    pub fn spatial_step(&self) -> f32 {
+        self.settings.spatial_step
+    }
+
+    pub fn temporal_step(&self) -> f32 {
+        self.settings.temporal_step()
+    }
+
+    /// Cells of the grid along x, y and z, the boundary layers included.
+    pub fn grid_dimension(&self) -> [u32; 3] {
+        self.fdtd.get_dimension()
+    }
+
+    /// Number of steps run so far.
+    pub fn step(&self) -> u32 {
+        self.step
+    }
+
+    /// Runs `steps` more steps and waits for them to finish.
+    pub fn run(&mut self, steps: u32) {
+        self.advance(steps, |_, _| ());
+    }
+
+    /// Largest difference between one tiled and one untiled interior update of the same
+    /// pseudo-random fields. Overwrites the fields, steps run afterwards start from noise.
+    pub fn compare_tiled_update(&mut self) -> anyhow::Result<f32> {
+        self.fdtd.compare_tiled_update(&self.device, &self.queue)
+    }
+
+    /// Writes out the preset's field recordings and stops them, a later run can replay them.
+    pub fn finish_recordings(&mut self) {
+        for recording in std::mem::take(&mut self.field_recordings) {
+            recording.finish(&self.device);
+        }
+    }
+
+    /// Runs `steps` more steps and returns the x, y and z components of `field` in the cells
+    /// holding each of `positions`, after every step. The outer index is the step.
+    pub fn record(
+        &mut self,
+        field: FieldType,
+        positions: &[[f32; 3]],
+        steps: u32,
+    ) -> anyhow::Result<Vec<Vec<[f32; 3]>>> {
+        let texels = positions
+            .iter()
+            .map(|position| self.texel(*position))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let buffer = self.readback_buffer(steps as usize * texels.len());
+        let mut offset = 0;
+        self.advance(steps, |fdtd, encoder| {
+            for texel in texels.iter() {
+                copy_texel(fdtd, encoder, field, *texel, &buffer, offset);
+                offset += TEXEL_SIZE;
+            }
+        });
+        let values = read_texels(&self.read_back(&buffer)?);
+        Ok(values
+            .chunks(texels.len().max(1))
+            .map(|step| step.to_vec())
+            .collect())
+    }
+
+    /// The x, y and z components of `field` in the cell holding the world `position`.
+    pub fn field_at(&self, field: FieldType, position: [f32; 3]) -> anyhow::Result<[f32; 3]> {
+        let texel = self.texel(position)?;
+        let buffer = self.readback_buffer(1);
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        copy_texel(&self.fdtd, &mut encoder, field, texel, &buffer, 0);
+        self.queue.submit(Some(encoder.finish()));
+        Ok(read_texels(&self.read_back(&buffer)?)[0])
+    }
+
+    /// Total field energy over the interior, the preset needs an `energy` section.
+    pub fn energy(&self) -> anyhow::Result<f64> {
+        let size = self
+            .fdtd
+            .energy_sums_size()
+            .ok_or_else(|| anyhow::anyhow!("The preset does not sum the energy"))?;
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Headless Energy Readback"),
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        self.fdtd.sum_energy(&mut encoder, &buffer, 0);
+        self.queue.submit(Some(encoder.finish()));
+        Ok(self.fdtd.total_energy(&self.read_back(&buffer)?))
+    }
+
+    /// The far field of the near-field box from the steps run so far, the preset needs a
+    /// `near_field` section.
+    pub fn far_field(&self) -> anyhow::Result<Vec<FarFieldSample>> {
+        crate::far_field(&self.fdtd, &self.device, &self.queue, &self.settings)?
+            .ok_or_else(|| anyhow::anyhow!("The preset has no near-field box"))
+    }
+
+    /// Forward and backward amplitudes of the `index`-th mode monitor from the steps run so
+    /// far, one per wavelength.
+    pub fn mode_amplitudes(&self, index: usize) -> anyhow::Result<Vec<ModeAmplitude>> {
+        anyhow::ensure!(
+            index < self.settings.mode_monitors.len(),
+            "The preset has no mode monitor {}",
+            index
+        );
+        crate::mode_amplitudes(&self.fdtd, &self.device, &self.queue, &self.settings, index)
+    }
+
+    /// Power leaving each flux box through each face at the current step, in the order low x,
+    /// high x, low y, high y, low z, high z.
+    pub fn face_powers(&self) -> anyhow::Result<Vec<[f64; 6]>> {
+        let size = self
+            .fdtd
+            .flux_sums_size()
+            .ok_or_else(|| anyhow::anyhow!("The preset has no flux boxes"))?;
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Headless Flux Readback"),
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        self.fdtd.sum_flux(&mut encoder, &buffer, 0);
+        self.queue.submit(Some(encoder.finish()));
+        Ok(self.fdtd.face_powers(&self.read_back(&buffer)?))
+    }
+
+    /// Energy absorbed inside the `index`-th flux box per unit frequency from the steps run so
+    /// far, one sample per wavelength.
+    pub fn absorption(&self, index: usize) -> anyhow::Result<Vec<AbsorptionSample>> {
+        anyhow::ensure!(
+            index < self.settings.flux_boxes.len(),
+            "The preset has no flux box {}",
+            index
+        );
+        crate::absorption(&self.fdtd, &self.device, &self.queue, index)
+    }
+
+    /// Resonances in `samples` of (step, value), analysed with the preset's `resonance`
+    /// settings or the defaults.
+    pub fn resonances(&self, samples: &[(u32, f32)]) -> Vec<Resonance> {
+        let settings = self.settings.resonance.clone().unwrap_or_default();
+        crate::resonance::analyse(samples, self.settings.temporal_step(), &settings)
+    }
+
+    /// Records `steps` steps in batches of at most `max_steps_per_frame`, calling `after_step`
+    /// after each one, and waits for them to finish.
+    fn advance(
+        &mut self,
+        steps: u32,
+        mut after_step: impl FnMut(&fdtd::FDTD, &mut wgpu::CommandEncoder),
+    ) {
+        let mut remaining = steps;
+        while remaining > 0 {
+            let batch = remaining.min(self.settings.max_steps_per_frame.max(1));
+            self.fdtd.begin_frame();
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            for recording in self.field_recordings.iter_mut() {
+                recording.begin(&self.device, self.step, batch);
+            }
+            for _ in 0..batch {
+                crate::step_fields(
+                    &self.device,
+                    &self.fdtd,
+                    &mut encoder,
+                    &self.settings,
+                    &self.magnetic_sources,
+                    &self.electric_sources,
+                    self.step,
+                );
+                after_step(&self.fdtd, &mut encoder);
+                self.step += 1;
+                for recording in self.field_recordings.iter_mut() {
+                    recording.record(&mut encoder, self.step);
+                }
+            }
+            self.queue.submit(Some(encoder.finish()));
+            for recording in self.field_recordings.iter_mut() {
+                recording.map_pending();
+            }
+            self.device.poll(wgpu::Maintain::Wait);
+            for recording in self.field_recordings.iter_mut() {
+                recording.collect(&self.device);
+            }
+            remaining -= batch;
+        }
+    }
+
+    fn texel(&self, position: [f32; 3]) -> anyhow::Result<[u32; 3]> {
+        let settings = &self.settings;
+        let grid = fdtd::grid_dimension(settings.domain, settings.spatial_step, &settings.boundary);
+        let texel = fdtd::world_to_grid(
+            settings.domain,
+            settings.spatial_step,
+            settings.boundary.padding(),
+            position,
+        )
+        .map(f32::floor);
+        anyhow::ensure!(
+            (0..3).all(|axis| texel[axis] >= 0.0 && texel[axis] < grid[axis] as f32),
+            "Position {:?} is outside the grid",
+            position
+        );
+        Ok(texel.map(|coordinate| coordinate as u32))
+    }
+
+    fn readback_buffer(&self, texels: usize) -> wgpu::Buffer {
+        self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Headless Readback"),
+            size: (texels.max(1) as u64) * TEXEL_SIZE,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn read_back(&self, buffer: &wgpu::Buffer) -> anyhow::Result<Vec<f32>> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        buffer.slice(..).map_async(wgpu::MapMode::Read, move |v| {
+            let _ = sender.send(v);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver.recv()??;
+        let values = bytemuck::cast_slice(&buffer.slice(..).get_mapped_range()).to_vec();
+        buffer.unmap();
+        Ok(values)
+    }
+}
+
+fn read_texels(values: &[f32]) -> Vec<[f32; 3]> {
+    values
+        .chunks_exact(4)
+        .map(|texel| [texel[0], texel[1], texel[2]])
+        .collect()
+}
+
+/// Bytes of one Rgba32Float texel.
+const TEXEL_SIZE: u64 = 16;
+
+fn copy_texel(
+    fdtd: &fdtd::FDTD,
+    encoder: &mut wgpu::CommandEncoder,
+    field: FieldType,
+    texel: [u32; 3],
+    buffer: &wgpu::Buffer,
+    offset: u64,
+) {
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture: match field {
+                FieldType::E => fdtd.get_electric_field_texture(),
+                FieldType::H => fdtd.get_magnetic_field_texture(),
+            },
+            mip_level: 0,
+            origin: wgpu::Origin3d {
+                x: texel[0],
+                y: texel[1],
+                z: texel[2],
+            },
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer,
+            layout: wgpu::ImageDataLayout {
+                offset,
+                bytes_per_row: None,
+                rows_per_image: None,
+            },
+        },
+        wgpu::Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+    );
+}
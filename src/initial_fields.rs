@@ -0,0 +1,88 @@
+use crate::fdtd;
+
+/// Reads one component of a D3 export, checking it against the grid and, when the export has
+/// a metadata sidecar, against the spatial step.
+fn read_component(path: &str, dimension: [u32; 3], spatial_step: f32) -> anyhow::Result<Vec<f32>> {
+    let path = std::path::Path::new(path);
+    let file = std::fs::File::open(path)
+        .map_err(|err| anyhow::anyhow!("Cannot open initial field {}: {}", path.display(), err))?;
+    let dds = ddsfile::Dds::read(std::io::BufReader::new(file))?;
+    let extent = [dds.get_width(), dds.get_height(), dds.get_depth()];
+    anyhow::ensure!(
+        extent == dimension,
+        "Initial field {} has {:?} cells, the grid {:?}",
+        path.display(),
+        extent,
+        dimension
+    );
+    let values = match dds.get_dxgi_format() {
+        Some(ddsfile::DxgiFormat::R32_Float) => bytemuck::pod_collect_to_vec(&dds.data),
+        Some(ddsfile::DxgiFormat::R16_Float) => bytemuck::pod_collect_to_vec::<u8, u16>(&dds.data)
+            .into_iter()
+            .map(crate::export::from_f16)
+            .collect(),
+        format => anyhow::bail!(
+            "Initial field {} is {:?}, expected an R32_Float or R16_Float export",
+            path.display(),
+            format
+        ),
+    };
+    anyhow::ensure!(
+        values.len() == dimension.iter().product::<u32>() as usize,
+        "Initial field {} is truncated",
+        path.display()
+    );
+
+    let sidecar = path.with_extension("json");
+    if sidecar.exists() {
+        let metadata: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&sidecar)?)?;
+        if let Some(exported_step) = metadata["spatial_step"].as_f64() {
+            anyhow::ensure!(
+                (exported_step as f32 - spatial_step).abs() <= 1e-6 * spatial_step,
+                "Initial field {} was exported with spatial_step = {}, the preset uses {}",
+                path.display(),
+                exported_step,
+                spatial_step
+            );
+        }
+    }
+    Ok(values)
+}
+
+/// Uploads the fields `initial` points at, a field without files stays as it is. The PML
+/// auxiliary fields are not part of an export and stay as they are too.
+pub fn load(
+    queue: &wgpu::Queue,
+    fdtd: &fdtd::FDTD,
+    initial: &crate::settings::InitialFieldsSettings,
+    spatial_step: f32,
+) -> anyhow::Result<()> {
+    let dimension = fdtd.get_dimension();
+    for (paths, texture) in [
+        (&initial.electric, fdtd.get_electric_field_texture()),
+        (&initial.magnetic, fdtd.get_magnetic_field_texture()),
+    ] {
+        let Some(paths) = paths else {
+            continue;
+        };
+        let components = paths
+            .iter()
+            .map(|path| read_component(path, dimension, spatial_step))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let texels: Vec<[f32; 4]> = (0..components[0].len())
+            .map(|i| [components[0][i], components[1][i], components[2][i], 0.0])
+            .collect();
+        queue.write_texture(
+            texture.as_image_copy(),
+            bytemuck::cast_slice(&texels),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(dimension[0] * 16),
+                rows_per_image: Some(dimension[1]),
+            },
+            texture.size(),
+        );
+    }
+    Ok(())
+}
@@ -1,11 +1,170 @@
+/// What `Linear2DInterpolator::interpolate` returns outside the convex hull of the data.
+#[derive(Clone, Copy, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Extrapolation {
+    /// Nothing, which callers fill with zero
+    Zero,
+    /// Value of the closest data point
+    #[default]
+    Nearest,
+    /// Value at the closest point of the hull
+    ClampToHull,
+}
+
+/// Uniform grid over axis-aligned boxes. Each cell lists the boxes overlapping it in
+/// ascending order, so a lookup finds the same item a linear scan would.
+struct BoxGrid {
+    origin: nalgebra::Vector2<f64>,
+    cell_size: nalgebra::Vector2<f64>,
+    resolution: [usize; 2],
+    // items of cell i are items[starts[i]..starts[i + 1]]
+    starts: Vec<usize>,
+    items: Vec<usize>,
+}
+
+impl BoxGrid {
+    fn new(boxes: &[[nalgebra::Vector2<f64>; 2]], bounding_box: [[f64; 2]; 2]) -> Self {
+        let count = boxes.len();
+        let extent = nalgebra::vector![
+            bounding_box[0][1] - bounding_box[0][0],
+            bounding_box[1][1] - bounding_box[1][0]
+        ];
+        // about one item per cell, with cells roughly square
+        let aspect = if extent.x > 0. && extent.y > 0. {
+            extent.x / extent.y
+        } else {
+            1.
+        };
+        let resolution = [
+            ((count as f64 * aspect).sqrt().ceil() as usize).clamp(1, 4096),
+            ((count as f64 / aspect).sqrt().ceil() as usize).clamp(1, 4096),
+        ];
+        let cell_size = nalgebra::vector![
+            if extent.x > 0. {
+                extent.x / resolution[0] as f64
+            } else {
+                1.
+            },
+            if extent.y > 0. {
+                extent.y / resolution[1] as f64
+            } else {
+                1.
+            }
+        ];
+        let mut grid = Self {
+            origin: nalgebra::vector![bounding_box[0][0], bounding_box[1][0]],
+            cell_size,
+            resolution,
+            starts: vec![0; resolution[0] * resolution[1] + 1],
+            items: vec![],
+        };
+
+        let ranges = boxes
+            .iter()
+            .map(|[min, max]| [grid.cell(*min), grid.cell(*max)])
+            .collect::<Vec<_>>();
+
+        // count per cell, then fill in item order
+        for [min, max] in &ranges {
+            for y in min[1]..=max[1] {
+                for x in min[0]..=max[0] {
+                    grid.starts[y * resolution[0] + x + 1] += 1;
+                }
+            }
+        }
+        for i in 1..grid.starts.len() {
+            grid.starts[i] += grid.starts[i - 1];
+        }
+        let mut cursor = grid.starts.clone();
+        grid.items = vec![0; *grid.starts.last().unwrap()];
+        for (item, [min, max]) in ranges.into_iter().enumerate() {
+            for y in min[1]..=max[1] {
+                for x in min[0]..=max[0] {
+                    let cell = y * resolution[0] + x;
+                    grid.items[cursor[cell]] = item;
+                    cursor[cell] += 1;
+                }
+            }
+        }
+
+        grid
+    }
+
+    fn cell(&self, p: nalgebra::Vector2<f64>) -> [usize; 2] {
+        let local = (p - self.origin).component_div(&self.cell_size);
+        [
+            (local.x.floor().max(0.) as usize).min(self.resolution[0] - 1),
+            (local.y.floor().max(0.) as usize).min(self.resolution[1] - 1),
+        ]
+    }
+
+    fn items(&self, [x, y]: [usize; 2]) -> &[usize] {
+        let cell = y * self.resolution[0] + x;
+        &self.items[self.starts[cell]..self.starts[cell + 1]]
+    }
+
+    fn candidates(&self, p: nalgebra::Vector2<f64>) -> &[usize] {
+        self.items(self.cell(p))
+    }
+
+    /// Visits the cells in rings of growing Chebyshev distance around the cell of `p`
+    /// until `visit` returns a distance no cell of the next ring can beat.
+    fn search_rings(&self, p: nalgebra::Vector2<f64>, mut visit: impl FnMut(&[usize]) -> f64) {
+        let [cx, cy] = self.cell(p);
+        let [rx, ry] = [self.resolution[0] as isize, self.resolution[1] as isize];
+        let step = self.cell_size.x.min(self.cell_size.y);
+        let max = self.origin
+            + self
+                .cell_size
+                .component_mul(&nalgebra::vector![rx as f64, ry as f64]);
+        // distance from p to the grid, nonzero outside of it
+        let outside = (p - p.sup(&self.origin).inf(&max)).norm_squared();
+        let mut best = f64::INFINITY;
+        for ring in 0..=rx.max(ry) {
+            // p projected onto the grid lies in the center cell, at least ring - 1 cells from
+            // this ring, and the projection is orthogonal to anything inside the grid
+            let gap = (ring - 1).max(0) as f64 * step;
+            if outside + gap * gap > best * best {
+                break;
+            }
+            let [cx, cy] = [cx as isize, cy as isize];
+            let mut cells = vec![];
+            for x in (cx - ring).max(0)..=(cx + ring).min(rx - 1) {
+                cells.push([x, cy - ring]);
+                if ring > 0 {
+                    cells.push([x, cy + ring]);
+                }
+            }
+            for y in (cy - ring + 1).max(0)..=(cy + ring - 1).min(ry - 1) {
+                cells.push([cx - ring, y]);
+                if ring > 0 {
+                    cells.push([cx + ring, y]);
+                }
+            }
+            for [x, y] in cells {
+                if (0..rx).contains(&x) && (0..ry).contains(&y) {
+                    best = best.min(visit(self.items([x as usize, y as usize])));
+                }
+            }
+        }
+    }
+}
+
 pub struct Linear2DInterpolator<const N: usize> {
     data: Vec<(nalgebra::Vector2<f64>, [f32; N])>,
     triangles: Vec<usize>,
+    hull: Vec<usize>,
     bounding_box: [[f64; 2]; 2],
+    triangle_grid: BoxGrid,
+    point_grid: BoxGrid,
+    extrapolation: Extrapolation,
 }
 
 impl<const N: usize> Linear2DInterpolator<N> {
-    pub fn new(data: Vec<(nalgebra::Vector2<f64>, [f32; N])>) -> Self {
+    pub fn new(
+        data: Vec<(nalgebra::Vector2<f64>, [f32; N])>,
+        extrapolation: Extrapolation,
+    ) -> Self {
         let mut max_x = f64::NEG_INFINITY;
         let mut min_x = f64::INFINITY;
         let mut max_y = f64::NEG_INFINITY;
@@ -22,19 +181,50 @@ impl<const N: usize> Linear2DInterpolator<N> {
             })
             .collect::<Vec<_>>();
 
+        let triangulation = delaunator::triangulate(&points);
+        let bounding_box = [[min_x, max_x], [min_y, max_y]];
+        // barycentric coordinates down to -eps reach eps times the triangle height beyond its edges
+        let margin =
+            Self::EPS * 2. * (max_x - min_x).max(max_y - min_y).max(0.) + f64::MIN_POSITIVE;
+        let triangle_boxes = triangulation
+            .triangles
+            .chunks(3)
+            .map(|verts| {
+                let mut min = data[verts[0]].0;
+                let mut max = min;
+                for &vert in &verts[1..] {
+                    min = min.inf(&data[vert].0);
+                    max = max.sup(&data[vert].0);
+                }
+                [min.add_scalar(-margin), max.add_scalar(margin)]
+            })
+            .collect::<Vec<_>>();
+        let point_boxes = data.iter().map(|(p, _)| [*p, *p]).collect::<Vec<_>>();
+
         Self {
+            triangle_grid: BoxGrid::new(&triangle_boxes, bounding_box),
+            point_grid: BoxGrid::new(&point_boxes, bounding_box),
             data,
-            triangles: delaunator::triangulate(&points).triangles,
-            bounding_box: [[min_x, max_x], [min_y, max_y]],
+            triangles: triangulation.triangles,
+            hull: triangulation.hull,
+            bounding_box,
+            extrapolation,
         }
     }
 
     pub fn interpolate(&self, p: nalgebra::Vector2<f64>) -> Option<[f32; N]> {
-        self.find_simplex(p).map(|(tri, bary)| {
-            let d1 = self.data[self.triangles[tri * 3]].1;
-            let d2 = self.data[self.triangles[tri * 3 + 1]].1;
-            let d3 = self.data[self.triangles[tri * 3 + 2]].1;
+        let Some((tri, bary)) = self.find_simplex(p) else {
+            return match self.extrapolation {
+                Extrapolation::Zero => None,
+                Extrapolation::Nearest => self.nearest(p).map(|i| self.data[i].1),
+                Extrapolation::ClampToHull => self.clamp_to_hull(p),
+            };
+        };
+        let d1 = self.data[self.triangles[tri * 3]].1;
+        let d2 = self.data[self.triangles[tri * 3 + 1]].1;
+        let d3 = self.data[self.triangles[tri * 3 + 2]].1;
 
+        Some(
             d1.into_iter()
                 .zip(d2.into_iter())
                 .zip(d3.into_iter())
@@ -43,12 +233,55 @@ impl<const N: usize> Linear2DInterpolator<N> {
                 })
                 .collect::<Vec<_>>()
                 .try_into()
-                .unwrap()
-        })
+                .unwrap(),
+        )
+    }
+
+    /// Index of the data point closest to `p`, the lowest one on ties.
+    fn nearest(&self, p: nalgebra::Vector2<f64>) -> Option<usize> {
+        let mut best: Option<(f64, usize)> = None;
+        self.point_grid.search_rings(p, |items| {
+            for &i in items {
+                let distance = (self.data[i].0 - p).norm();
+                if best.is_none_or(|(d, j)| distance < d || (distance == d && i < j)) {
+                    best = Some((distance, i));
+                }
+            }
+            best.map_or(f64::INFINITY, |(d, _)| d)
+        });
+        best.map(|(_, i)| i)
+    }
+
+    /// Linear interpolation along the hull edge closest to `p`, which is what the triangle
+    /// owning that edge gives there.
+    fn clamp_to_hull(&self, p: nalgebra::Vector2<f64>) -> Option<[f32; N]> {
+        let (a, b, t) = self
+            .hull
+            .iter()
+            .zip(self.hull.iter().cycle().skip(1))
+            .map(|(&a, &b)| {
+                let (pa, pb) = (self.data[a].0, self.data[b].0);
+                let edge = pb - pa;
+                let t = if edge.norm_squared() > 0. {
+                    ((p - pa).dot(&edge) / edge.norm_squared()).clamp(0., 1.)
+                } else {
+                    0.
+                };
+                (a, b, t, (pa + edge * t - p).norm_squared())
+            })
+            .min_by(|x, y| x.3.total_cmp(&y.3))
+            .map(|(a, b, t, _)| (a, b, t))?;
+        let (da, db) = (self.data[a].1, self.data[b].1);
+
+        Some(std::array::from_fn(|i| {
+            da[i] * (1. - t) as f32 + db[i] * t as f32
+        }))
     }
 
+    const EPS: f64 = f64::EPSILON * 100.;
+
     fn find_simplex(&self, p: nalgebra::Vector2<f64>) -> Option<(usize, [f64; 3])> {
-        let eps = std::f64::EPSILON * 100.;
+        let eps = Self::EPS;
 
         if p.x < self.bounding_box[0][0] - eps
             || p.x > self.bounding_box[0][1] + eps
@@ -58,11 +291,8 @@ impl<const N: usize> Linear2DInterpolator<N> {
             return None;
         }
 
-        if self.triangles.len() <= 0 {
-            return None;
-        }
-
-        for (tri, verts) in self.triangles.chunks(3).enumerate() {
+        for &tri in self.triangle_grid.candidates(p) {
+            let verts = &self.triangles[tri * 3..tri * 3 + 3];
             let p1 = self.data[verts[0]].0;
             let p2 = self.data[verts[1]].0;
             let p3 = self.data[verts[2]].0;
@@ -70,15 +300,21 @@ impl<const N: usize> Linear2DInterpolator<N> {
             let e1 = p2 - p1;
             let e2 = p3 - p1;
 
+            // twice the signed area, dividing by it makes the coordinates independent of winding
             let denom = nalgebra::Matrix2::from_columns(&[e1, e2]).determinant();
+            // zero-area triangles cover nothing and would divide by zero
+            if denom.abs() <= f64::EPSILON * e1.norm_squared().max(e2.norm_squared()) {
+                continue;
+            }
 
             let r1 = p1 - p;
             let r2 = p2 - p;
             let r3 = p3 - p;
 
+            // each sub-triangle taken with the winding of the whole one
             let b1 = nalgebra::Matrix2::from_columns(&[r1, r2]).determinant() / denom;
             let b2 = nalgebra::Matrix2::from_columns(&[r2, r3]).determinant() / denom;
-            let b3 = 1. - b1 - b2;
+            let b3 = nalgebra::Matrix2::from_columns(&[r3, r1]).determinant() / denom;
 
             if b1 > 0. - eps && b2 > 0. - eps && b3 > 0. - eps {
                 return Some((tri, [b1, b2, b3]));
@@ -88,3 +324,541 @@ impl<const N: usize> Linear2DInterpolator<N> {
         None
     }
 }
+
+/// Uniform grid over points in 3D, each cell lists its points in ascending order.
+struct PointGrid {
+    origin: nalgebra::Vector3<f64>,
+    cell_size: f64,
+    resolution: [usize; 3],
+    // points of cell i are points[starts[i]..starts[i + 1]]
+    starts: Vec<usize>,
+    points: Vec<usize>,
+}
+
+impl PointGrid {
+    fn new(points: &[nalgebra::Vector3<f64>], bounding_box: [[f64; 2]; 3]) -> Self {
+        let extent = bounding_box.map(|[min, max]| max - min);
+        // about one point per cell
+        let volume = extent
+            .iter()
+            .map(|e| e.max(f64::MIN_POSITIVE))
+            .product::<f64>();
+        let mut cell_size = (volume / points.len().max(1) as f64).cbrt();
+        if !cell_size.is_normal() {
+            cell_size = extent.into_iter().fold(0., f64::max).max(1.);
+        }
+        let resolution = extent.map(|e| ((e / cell_size).ceil() as usize).clamp(1, 1024));
+        let mut grid = Self {
+            origin: nalgebra::vector![bounding_box[0][0], bounding_box[1][0], bounding_box[2][0]],
+            cell_size,
+            resolution,
+            starts: vec![0; resolution.iter().product::<usize>() + 1],
+            points: vec![0; points.len()],
+        };
+
+        let cells = points
+            .iter()
+            .map(|p| grid.index(grid.cell(*p)))
+            .collect::<Vec<_>>();
+        for &cell in &cells {
+            grid.starts[cell + 1] += 1;
+        }
+        for i in 1..grid.starts.len() {
+            grid.starts[i] += grid.starts[i - 1];
+        }
+        let mut cursor = grid.starts.clone();
+        for (point, cell) in cells.into_iter().enumerate() {
+            grid.points[cursor[cell]] = point;
+            cursor[cell] += 1;
+        }
+
+        grid
+    }
+
+    fn cell(&self, p: nalgebra::Vector3<f64>) -> [usize; 3] {
+        let local = (p - self.origin) / self.cell_size;
+        [0, 1, 2].map(|i| (local[i].floor().max(0.) as usize).min(self.resolution[i] - 1))
+    }
+
+    fn index(&self, [x, y, z]: [usize; 3]) -> usize {
+        (z * self.resolution[1] + y) * self.resolution[0] + x
+    }
+
+    /// The `k` points closest to `p` as (distance, index), nearest first.
+    fn nearest(
+        &self,
+        points: &[nalgebra::Vector3<f64>],
+        p: nalgebra::Vector3<f64>,
+        k: usize,
+    ) -> Vec<(f64, usize)> {
+        let center = self.cell(p).map(|c| c as isize);
+        let resolution = self.resolution.map(|r| r as isize);
+        let max = self.origin
+            + nalgebra::Vector3::from(self.resolution.map(|r| r as f64)) * self.cell_size;
+        let outside = (p - p.sup(&self.origin).inf(&max)).norm_squared();
+        let mut found: Vec<(f64, usize)> = Vec::with_capacity(k + 1);
+        for ring in 0..=resolution.into_iter().max().unwrap() {
+            // same bound as the 2D ring search
+            let gap = (ring - 1).max(0) as f64 * self.cell_size;
+            if found.len() == k && outside + gap * gap > found[k - 1].0.powi(2) {
+                break;
+            }
+            let [lo, hi] = [-1, 1]
+                .map(|s| [0, 1, 2].map(|i| (center[i] + s * ring).clamp(0, resolution[i] - 1)));
+            for z in lo[2]..=hi[2] {
+                for y in lo[1]..=hi[1] {
+                    for x in lo[0]..=hi[0] {
+                        let cell = [x, y, z];
+                        if (0..3).all(|i| cell[i].abs_diff(center[i]) < ring as usize) {
+                            continue;
+                        }
+                        let cell = self.index(cell.map(|c| c as usize));
+                        for &i in &self.points[self.starts[cell]..self.starts[cell + 1]] {
+                            let distance = (points[i] - p).norm();
+                            let at = found.partition_point(|&(d, j)| (d, j) < (distance, i));
+                            if at < k {
+                                found.insert(at, (distance, i));
+                                found.truncate(k);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        found
+    }
+}
+
+/// Scattered-data interpolation in 3D. Fits a weighted least-squares linear function to the
+/// nearest points, which reproduces linear fields exactly, and falls back to inverse distance
+/// weighting where those points are too close to coplanar for a fit.
+pub struct Linear3DInterpolator<const N: usize> {
+    points: Vec<nalgebra::Vector3<f64>>,
+    values: Vec<[f32; N]>,
+    bounding_box: [[f64; 2]; 3],
+    grid: PointGrid,
+}
+
+impl<const N: usize> Linear3DInterpolator<N> {
+    const NEIGHBOURS: usize = 16;
+    const EPS: f64 = f64::EPSILON * 100.;
+
+    pub fn new(data: Vec<(nalgebra::Vector3<f64>, [f32; N])>) -> Self {
+        let (points, values): (Vec<_>, Vec<_>) = data.into_iter().unzip();
+        let bounding_box = [0, 1, 2].map(|i| {
+            points
+                .iter()
+                .fold([f64::INFINITY, f64::NEG_INFINITY], |[min, max], p| {
+                    [min.min(p[i]), max.max(p[i])]
+                })
+        });
+
+        Self {
+            grid: PointGrid::new(&points, bounding_box),
+            points,
+            values,
+            bounding_box,
+        }
+    }
+
+    /// `None` outside the bounding box of the data.
+    pub fn interpolate(&self, p: nalgebra::Vector3<f64>) -> Option<[f32; N]> {
+        let eps = Self::EPS;
+        if self.points.is_empty()
+            || (0..3).any(|i| {
+                p[i] < self.bounding_box[i][0] - eps || p[i] > self.bounding_box[i][1] + eps
+            })
+        {
+            return None;
+        }
+
+        let neighbours = self.grid.nearest(&self.points, p, Self::NEIGHBOURS);
+        let (nearest, nearest_index) = neighbours[0];
+        let radius = neighbours.last().unwrap().0;
+        if nearest <= eps * radius.max(1.) {
+            return Some(self.values[nearest_index]);
+        }
+
+        let weights = self.fit_weights(p, &neighbours, radius).unwrap_or_else(|| {
+            let inverse = neighbours
+                .iter()
+                .map(|&(d, _)| 1. / (d * d))
+                .collect::<Vec<_>>();
+            let total = inverse.iter().sum::<f64>();
+            inverse.into_iter().map(|w| w / total).collect()
+        });
+
+        let mut value = [0f32; N];
+        for (&(_, i), w) in neighbours.iter().zip(weights) {
+            for (v, d) in value.iter_mut().zip(self.values[i]) {
+                *v += d * w as f32;
+            }
+        }
+        Some(value)
+    }
+
+    /// Weights of the neighbour values in the least-squares fit evaluated at `p`.
+    fn fit_weights(
+        &self,
+        p: nalgebra::Vector3<f64>,
+        neighbours: &[(f64, usize)],
+        radius: f64,
+    ) -> Option<Vec<f64>> {
+        if neighbours.len() < 4 {
+            return None;
+        }
+        // offsets scaled by the neighbourhood size keep the normal matrix well conditioned
+        let basis = neighbours
+            .iter()
+            .map(|&(_, i)| {
+                let offset = (self.points[i] - p) / radius;
+                nalgebra::vector![1., offset.x, offset.y, offset.z]
+            })
+            .collect::<Vec<_>>();
+        let weights = neighbours
+            .iter()
+            .map(|&(d, _)| (1. - d / (radius * 1.01)).powi(2))
+            .collect::<Vec<_>>();
+        let normal = basis
+            .iter()
+            .zip(&weights)
+            .fold(nalgebra::Matrix4::zeros(), |m, (b, w)| {
+                m + b * b.transpose() * *w
+            });
+
+        let eigenvalues = normal.symmetric_eigenvalues();
+        if eigenvalues.min() <= 1e-8 * eigenvalues.max() {
+            return None;
+        }
+        // the value at p is the constant term of the fit
+        let row = normal.try_inverse()?.row(0).into_owned();
+        Some(
+            basis
+                .iter()
+                .zip(weights)
+                .map(|(b, w)| (row * b)[0] * w)
+                .collect(),
+        )
+    }
+}
+
+/// Piecewise linear interpolation of samples along one axis.
+pub struct Linear1DInterpolator<const N: usize> {
+    data: Vec<(f64, [f32; N])>,
+}
+
+impl<const N: usize> Linear1DInterpolator<N> {
+    /// `data` must be sorted by strictly increasing abscissa.
+    pub fn new(data: Vec<(f64, [f32; N])>) -> Self {
+        Self { data }
+    }
+
+    /// `None` outside the range of the data.
+    pub fn interpolate(&self, x: f64) -> Option<[f32; N]> {
+        // number of samples at or before x
+        let i = self.data.partition_point(|(xi, _)| *xi <= x);
+        if i == 0 {
+            return None;
+        }
+        if i == self.data.len() {
+            let (last_x, last) = self.data[i - 1];
+            return (last_x == x).then_some(last);
+        }
+        let (x0, v0) = self.data[i - 1];
+        let (x1, v1) = self.data[i];
+        let t = ((x - x0) / (x1 - x0)) as f32;
+        Some(std::array::from_fn(|k| v0[k] + (v1[k] - v0[k]) * t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Seeded splitmix64, for random layouts that are the same on every run.
+    struct Random(u64);
+
+    impl Random {
+        /// Uniform in [min, max).
+        fn range(&mut self, min: f64, max: f64) -> f64 {
+            self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            let unit = ((z ^ (z >> 31)) >> 11) as f64 / (1u64 << 53) as f64;
+            min + (max - min) * unit
+        }
+
+        fn point2(&mut self, [[x0, x1], [y0, y1]]: [[f64; 2]; 2]) -> nalgebra::Vector2<f64> {
+            nalgebra::vector![self.range(x0, x1), self.range(y0, y1)]
+        }
+
+        fn point3(
+            &mut self,
+            [[x0, x1], [y0, y1], [z0, z1]]: [[f64; 2]; 3],
+        ) -> nalgebra::Vector3<f64> {
+            nalgebra::vector![self.range(x0, x1), self.range(y0, y1), self.range(z0, z1)]
+        }
+    }
+
+    /// Points spread over `area`, a third of them in a small cluster and some repeated, so
+    /// that cells hold very different numbers of points.
+    fn scattered_points(
+        random: &mut Random,
+        count: usize,
+        area: [[f64; 2]; 2],
+    ) -> Vec<nalgebra::Vector2<f64>> {
+        let mut points = vec![];
+        for i in 0..count {
+            points.push(match i % 6 {
+                0 | 1 => random.point2([[0.1, 0.15], [0.2, 0.22]]),
+                2 if i > 2 => points[i / 2],
+                _ => random.point2(area),
+            });
+        }
+        points
+    }
+
+    /// 1 + 2x - 3y at each of `points`.
+    fn linear_data(points: &[[f64; 2]]) -> Vec<(nalgebra::Vector2<f64>, [f32; 1])> {
+        points
+            .iter()
+            .map(|&[x, y]| (nalgebra::vector![x, y], [(1. + 2. * x - 3. * y) as f32]))
+            .collect()
+    }
+
+    /// The interpolator of a single triangle, its vertices listed clockwise when `clockwise`.
+    fn triangle(points: &[[f64; 2]; 3], clockwise: bool) -> Linear2DInterpolator<1> {
+        let mut interpolator = Linear2DInterpolator::new(linear_data(points), Extrapolation::Zero);
+        assert_eq!(interpolator.triangles.len(), 3);
+        let [a, b, c] = [0, 1, 2].map(|i| interpolator.data[interpolator.triangles[i]].0);
+        let counter_clockwise = nalgebra::Matrix2::from_columns(&[b - a, c - a]).determinant() > 0.;
+        if counter_clockwise == clockwise {
+            interpolator.triangles.swap(1, 2);
+        }
+        interpolator
+    }
+
+    #[test]
+    fn either_winding_interpolates_inside_and_rejects_outside() {
+        let points = [[0., 0.], [1., 0.2], [0.3, 1.]];
+        for clockwise in [false, true] {
+            let interpolator = triangle(&points, clockwise);
+            for [x, y] in [[0.4, 0.4], [0.1, 0.05], [0.5, 0.1], [0.3, 0.99]] {
+                let value = interpolator.interpolate(nalgebra::vector![x, y]).unwrap()[0];
+                assert!(
+                    (value - (1. + 2. * x - 3. * y) as f32).abs() < 1e-6,
+                    "{} at {:?}, clockwise {}",
+                    value,
+                    [x, y],
+                    clockwise
+                );
+            }
+            // on the vertices and the edges
+            for [x, y] in points.into_iter().chain([[0.5, 0.1], [0.15, 0.5]]) {
+                assert!(interpolator.interpolate(nalgebra::vector![x, y]).is_some());
+            }
+            // inside the bounding box but outside the triangle
+            for [x, y] in [[0.9, 0.9], [0.05, 0.9], [0.6, 0.05]] {
+                assert!(
+                    interpolator.interpolate(nalgebra::vector![x, y]).is_none(),
+                    "{:?}, clockwise {}",
+                    [x, y],
+                    clockwise
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sliver_triangles_interpolate_along_their_length() {
+        // a millionth as high as long
+        let points = [[0., 0.], [1., 0.], [0.5, 1e-6]];
+        for clockwise in [false, true] {
+            let interpolator = triangle(&points, clockwise);
+            for x in [0.01f64, 0.25, 0.5, 0.75, 0.99] {
+                // a quarter of the way up at x
+                let p = nalgebra::vector![x, 0.25e-6 * (1. - (2. * x - 1.).abs())];
+                let value = interpolator.interpolate(p).unwrap()[0];
+                assert!(
+                    (value - (1. + 2. * x) as f32).abs() < 1e-6,
+                    "{} at {}",
+                    value,
+                    x
+                );
+            }
+            // just above the apex
+            assert!(interpolator
+                .interpolate(nalgebra::vector![0.5, 2e-6])
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn collinear_points_cover_nothing() {
+        // all on one line, as are the vertices of a zero-area triangle
+        let data = linear_data(&[[0., 0.], [0.5, 0.5], [1., 1.]]);
+        let mut interpolator = Linear2DInterpolator::new(data, Extrapolation::Zero);
+        interpolator.triangles = vec![0, 1, 2];
+        interpolator.triangle_grid = BoxGrid::new(
+            &[[nalgebra::vector![0., 0.], nalgebra::vector![1., 1.]]],
+            interpolator.bounding_box,
+        );
+        assert!(interpolator
+            .find_simplex(nalgebra::vector![0.5, 0.5])
+            .is_none());
+        interpolator.extrapolation = Extrapolation::Nearest;
+        assert_eq!(
+            interpolator.interpolate(nalgebra::vector![0.6, 0.5]),
+            Some([0.5])
+        );
+    }
+
+    #[test]
+    fn box_grid_lists_every_box_holding_a_point() {
+        let mut random = Random(1);
+        for count in [1, 7, 200] {
+            // wide and flat, some boxes collapsed to points or lines
+            let boxes = (0..count)
+                .map(|i| {
+                    let min = random.point2([[-1.0, 2.0], [0.0, 0.5]]);
+                    let size = match i % 4 {
+                        0 => nalgebra::vector![0.0, 0.0],
+                        1 => nalgebra::vector![random.range(0.0, 0.4), 0.0],
+                        _ => random.point2([[0.0, 0.4], [0.0, 0.2]]),
+                    };
+                    [min, min + size]
+                })
+                .collect::<Vec<_>>();
+            let bounding_box = [0, 1].map(|axis| {
+                boxes.iter().fold(
+                    [f64::INFINITY, f64::NEG_INFINITY],
+                    |[lo, hi], [min, max]| [lo.min(min[axis]), hi.max(max[axis])],
+                )
+            });
+            let grid = BoxGrid::new(&boxes, bounding_box);
+            // corners of the boxes too, where they touch the cell borders
+            let queries = (0..500)
+                .map(|_| random.point2([[-1.2, 2.6], [-0.2, 0.9]]))
+                .chain(boxes.iter().flatten().copied());
+            for p in queries {
+                let candidates = grid.candidates(p);
+                assert!(candidates.windows(2).all(|pair| pair[0] < pair[1]));
+                for (i, [min, max]) in boxes.iter().enumerate() {
+                    if min.x <= p.x && p.x <= max.x && min.y <= p.y && p.y <= max.y {
+                        assert!(candidates.contains(&i), "box {} misses {:?}", i, p);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_data_point_matches_a_linear_scan() {
+        let mut random = Random(2);
+        for count in [3, 10, 300] {
+            let points = scattered_points(&mut random, count, [[-1.0, 3.0], [0.0, 1.0]]);
+            let data = points.iter().map(|p| (*p, [0.0f32])).collect();
+            let interpolator = Linear2DInterpolator::<1>::new(data, Extrapolation::Nearest);
+            // far outside too, where the ring search starts from a border cell
+            let queries = (0..500)
+                .map(|_| random.point2([[-3.0, 5.0], [-2.0, 3.0]]))
+                .chain(points.iter().copied());
+            for p in queries {
+                let expected = points
+                    .iter()
+                    .enumerate()
+                    .map(|(i, point)| ((point - p).norm(), i))
+                    .min_by(|a, b| a.partial_cmp(b).unwrap())
+                    .map(|(_, i)| i);
+                assert_eq!(interpolator.nearest(p), expected, "nearest to {:?}", p);
+            }
+        }
+    }
+
+    #[test]
+    fn scattered_3d_data_reproduces_a_linear_field() {
+        let field = |p: nalgebra::Vector3<f64>| {
+            [
+                (1. + 2. * p.x - p.y + 0.5 * p.z) as f32,
+                (-3. + 4. * p.z) as f32,
+            ]
+        };
+        let mut random = Random(4);
+        let area = [[-1.0, 1.0], [0.0, 2.0], [0.0, 0.5]];
+        // scattered, and on a grid, where the nearest points tie
+        let scattered = (0..600).map(|_| random.point3(area)).collect::<Vec<_>>();
+        let grid = (0..11 * 11 * 6)
+            .map(|i| {
+                nalgebra::vector![
+                    (i % 11) as f64 * 0.2 - 1.0,
+                    (i / 11 % 11) as f64 * 0.2,
+                    (i / 121) as f64 * 0.1
+                ]
+            })
+            .collect::<Vec<_>>();
+        for points in [scattered, grid] {
+            let data = points.iter().map(|p| (*p, field(*p))).collect();
+            let interpolator = Linear3DInterpolator::<2>::new(data);
+            // on the data points as well, and at the corners of the bounding box
+            let corners = [0, 1].map(|side| interpolator.bounding_box.map(|range| range[side]));
+            let queries = (0..300)
+                .map(|_| random.point3(interpolator.bounding_box))
+                .chain(points.iter().take(50).copied())
+                .chain(corners.map(nalgebra::Vector3::from));
+            for p in queries {
+                let value = interpolator.interpolate(p).unwrap();
+                let expected = field(p);
+                for (value, expected) in value.into_iter().zip(expected) {
+                    assert!(
+                        (value - expected).abs() < 1e-4,
+                        "{} instead of {} at {:?}",
+                        value,
+                        expected,
+                        p
+                    );
+                }
+            }
+            assert!(interpolator
+                .interpolate(nalgebra::vector![0.0, 1.0, 0.6])
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn point_grid_finds_the_nearest_points_of_a_linear_scan() {
+        let mut random = Random(3);
+        for count in [1, 20, 400] {
+            let area = [[-1.0, 1.0], [0.0, 3.0], [0.0, 0.1]];
+            let mut points = vec![];
+            for i in 0..count {
+                points.push(match i % 5 {
+                    0 => random.point3([[0.0, 0.05], [0.0, 0.05], [0.0, 0.05]]),
+                    1 if i > 1 => points[i / 3],
+                    _ => random.point3(area),
+                });
+            }
+            let bounding_box = [0, 1, 2].map(|axis| {
+                points
+                    .iter()
+                    .fold([f64::INFINITY, f64::NEG_INFINITY], |[lo, hi], p| {
+                        [lo.min(p[axis]), hi.max(p[axis])]
+                    })
+            });
+            let grid = PointGrid::new(&points, bounding_box);
+            for _ in 0..300 {
+                let p = random.point3([[-2.0, 2.0], [-1.0, 4.0], [-1.0, 1.0]]);
+                let mut expected = points
+                    .iter()
+                    .enumerate()
+                    .map(|(i, point)| ((point - p).norm(), i))
+                    .collect::<Vec<_>>();
+                expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                for k in [1, 4, 16, count + 1] {
+                    let found = grid.nearest(&points, p, k);
+                    assert_eq!(found, expected[..k.min(count)], "{} nearest to {:?}", k, p);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,1656 @@
+use std::path::Path;
+
+use clap::Parser;
+use pollster::FutureExt;
+use wgpu::util::DeviceExt;
+
+use mode_profile::ModeProfile;
+use settings::{
+    EnergySettings, ExportSettings, FDTDSettings, ModeMonitorSettings, ModeSettings,
+    NearFieldSettings, ProbeSettings, WorkgroupSettings,
+};
+use source::Source;
+mod checkpoint;
+mod export;
+mod far_field;
+mod fdtd;
+mod flux_box;
+pub mod headless;
+mod initial_fields;
+mod interpolator;
+mod mode_monitor;
+mod mode_profile;
+mod mode_solver;
+mod periodic;
+mod plot;
+mod prepare;
+mod preset;
+mod probe;
+mod readout;
+mod recording;
+mod replay;
+mod resonance;
+mod schedule;
+mod screenshot;
+mod settings;
+mod simulation;
+mod source;
+#[cfg(test)]
+mod temp_files;
+mod units;
+mod window;
+
+/// Gpu-accelerated Rusty Electro-Magnetic field Simulator
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct GremOptions {
+    #[arg(long)]
+    /// Print device infos and quit
+    info: bool,
+    #[arg(long, value_enum, default_value_t = Backend::Auto)]
+    /// Graphics API to run on
+    backend: Backend,
+    #[arg(long, value_name = "INDEX", conflicts_with = "adapter_name")]
+    /// Run on the adapter with this index in the --info listing
+    adapter: Option<usize>,
+    #[arg(long, value_name = "SUBSTRING")]
+    /// Run on the first adapter whose name contains this (case-insensitive)
+    adapter_name: Option<String>,
+    #[arg(long)]
+    /// Pass shader parameters through uniform buffers even when push constants are available
+    no_push_constants: bool,
+    #[arg(long, value_name = "DIR")]
+    /// Look for the shaders in this directory before the ones next to the preset and executable
+    shader_dir: Option<String>,
+    #[arg(long)]
+    /// Run without a window until the last step of the preset, pause points are skipped
+    no_visual: bool,
+    #[arg(long)]
+    /// Validate the preset, print grid and memory estimates and quit without simulating
+    check: bool,
+    #[arg(long)]
+    /// Write the voxelized permittivity map to `<preset>-materials.dds` before simulating
+    dump_materials: bool,
+    #[arg(long)]
+    /// Voxelize the models again even when the material cache holds them, replacing the entry
+    no_cache: bool,
+    #[arg(long)]
+    /// Fail instead of warning when a model reaches out of the simulation domain
+    strict: bool,
+    #[arg(long)]
+    /// Shrink a workgroup the adapter cannot run to the nearest shape it can, instead of failing
+    clamp_workgroup: bool,
+    #[arg(long, value_name = "N")]
+    /// Write `<preset>-checkpoint.bin` every N steps
+    checkpoint_every: Option<u32>,
+    #[arg(long, value_name = "MIB")]
+    /// Refuse to start when the estimated texture memory exceeds this many MiB
+    memory_limit: Option<u64>,
+    #[arg(long, value_name = "FILE")]
+    /// Continue from a checkpoint written by --checkpoint-every
+    resume: Option<String>,
+    #[arg(long)]
+    /// Compare one tiled update step against the untiled one on a random field and quit
+    verify_tiling: bool,
+    #[arg(long, value_enum, default_value_t = SurfaceEncoding::Srgb)]
+    /// Color encoding of the window surface
+    surface_format: SurfaceEncoding,
+    #[arg(long, value_enum, default_value_t = PresentMode::AutoVsync)]
+    /// How frames are presented, the simulation rate does not depend on it
+    present_mode: PresentMode,
+    #[arg(long, value_name = "PX", default_value_t = 20.0)]
+    /// Pixels of touchpad scrolling that count as one mouse wheel notch
+    scroll_pixels_per_line: f32,
+    #[arg(long)]
+    /// Show the GPU time per step of the update, PML and excitation passes, needs timestamp queries
+    profile: bool,
+    #[arg(long, value_name = "PERCENT")]
+    /// Pause once the field energy grows by more than this between two sums after the sources
+    /// are done, sums the energy even if the preset does not
+    watch_energy: Option<f32>,
+    #[arg(required_unless_present = "info")]
+    /// Simulation preset files, each one overrides the settings of the ones before it. Outputs
+    /// are named after the last one, and its directory is where relative file names point
+    preset: Vec<String>,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Backend {
+    /// Vulkan, Metal, DX12 or WebGPU, whichever the platform provides
+    Auto,
+    Vulkan,
+    Dx12,
+    Metal,
+    Gl,
+}
+
+impl Backend {
+    fn backends(self) -> wgpu::Backends {
+        match self {
+            Backend::Auto => wgpu::Backends::PRIMARY,
+            Backend::Vulkan => wgpu::Backends::VULKAN,
+            Backend::Dx12 => wgpu::Backends::DX12,
+            Backend::Metal => wgpu::Backends::METAL,
+            Backend::Gl => wgpu::Backends::GL,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum PresentMode {
+    /// Synchronized to the display, whichever way the platform supports
+    AutoVsync,
+    /// Unsynchronized, may tear
+    NoVsync,
+    Fifo,
+    Mailbox,
+}
+
+impl PresentMode {
+    /// The mode to configure the surface with, Fifo when `supported` lacks the requested one.
+    fn select(self, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+        let mode = match self {
+            PresentMode::AutoVsync => return wgpu::PresentMode::AutoVsync,
+            PresentMode::NoVsync => return wgpu::PresentMode::AutoNoVsync,
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+        };
+        if supported.contains(&mode) {
+            mode
+        } else {
+            eprintln!("Present mode {:?} is unsupported, using Fifo", mode);
+            wgpu::PresentMode::Fifo
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum SurfaceEncoding {
+    /// An *Srgb format, the colors written are encoded for display
+    Srgb,
+    /// A plain unorm format, the colors written are shown as they are
+    Linear,
+}
+
+/// What recordings of a run without a window are rendered to.
+const HEADLESS_RENDER_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Surface format of the requested encoding, 8-bit RGBA or BGRA ones first since screenshots
+/// and recordings support only those. Falls back to the preferred format of the surface.
+fn select_surface_format(
+    formats: &[wgpu::TextureFormat],
+    encoding: SurfaceEncoding,
+) -> anyhow::Result<wgpu::TextureFormat> {
+    anyhow::ensure!(
+        !formats.is_empty(),
+        "The window surface is not supported by the adapter"
+    );
+    let encoded =
+        |format: &&wgpu::TextureFormat| format.is_srgb() == (encoding == SurfaceEncoding::Srgb);
+    let format = formats
+        .iter()
+        .filter(encoded)
+        .find(|format| screenshot::needs_red_blue_swap(**format).is_ok())
+        .or_else(|| formats.iter().find(encoded))
+        .copied()
+        .unwrap_or_else(|| {
+            eprintln!(
+                "No {:?} surface format available, using {:?}",
+                encoding, formats[0]
+            );
+            formats[0]
+        });
+    Ok(format)
+}
+
+fn timestamp_millis() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Vertex {
+    pos: [f32; 2],
+    tex_coord: [f32; 2],
+}
+
+pub fn open_csv(path: &Path) -> anyhow::Result<csv::Reader<std::fs::File>> {
+    csv::Reader::from_path(path).map_err(|err| anyhow::anyhow!("{}: {}", path.display(), err))
+}
+
+/// Reads the next row of a CSV file opened with `open_csv`, errors name the file.
+pub fn read_csv_record(
+    path: &Path,
+    record: Result<csv::StringRecord, csv::Error>,
+) -> anyhow::Result<csv::StringRecord> {
+    record.map_err(|err| anyhow::anyhow!("{}: {}", path.display(), err))
+}
+
+/// Parses column `column` (0-based) of a CSV row. Errors name the file, line, 1-based column
+/// and the offending token.
+pub fn parse_csv_field<T>(
+    path: &Path,
+    record: &csv::StringRecord,
+    column: usize,
+) -> anyhow::Result<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    let line = record.position().map_or(0, |position| position.line());
+    let token = record.get(column).ok_or_else(|| {
+        anyhow::anyhow!(
+            "{}, line {}: missing column {}",
+            path.display(),
+            line,
+            column + 1
+        )
+    })?;
+    token.trim().parse().map_err(|err| {
+        anyhow::anyhow!(
+            "{}, line {}, column {}: cannot parse {:?}: {}",
+            path.display(),
+            line,
+            column + 1,
+            token,
+            err
+        )
+    })
+}
+
+/// Cells of a closed box around `position`, which must keep a cell of the interior outside
+/// each face. Collapsed axes are spanned in full and get no faces.
+fn closed_box(
+    settings: &FDTDSettings,
+    label: &str,
+    position: [f32; 3],
+    size: [f32; 3],
+) -> anyhow::Result<fdtd::SurfaceBox> {
+    let padding = settings.boundary.padding();
+    let grid = fdtd::grid_dimension(settings.domain, settings.spatial_step, &settings.boundary);
+    let corner = |sign: f32| {
+        fdtd::world_to_grid(
+            settings.domain,
+            settings.spatial_step,
+            padding,
+            [0, 1, 2].map(|axis| position[axis] + sign * 0.5 * size[axis]),
+        )
+        .map(f32::floor)
+    };
+    let (mut low, mut high) = (corner(-1.0), corner(1.0));
+    let mut surface = fdtd::SurfaceBox::closed([0; 3], [0; 3]);
+    for axis in 0..3 {
+        if settings.dimension_mode.collapsed_axes().contains(&axis) {
+            (low[axis], high[axis]) = (0.0, (grid[axis] - 1) as f32);
+            surface.faces &= !(0b11 << (2 * axis));
+            continue;
+        }
+        let interior = (padding[axis][0] + 1) as f32..(grid[axis] - padding[axis][1] - 1) as f32;
+        anyhow::ensure!(
+            interior.contains(&low[axis]) && interior.contains(&high[axis]) && low[axis] < high[axis],
+            "{} at {:?} of size {:?} needs at least two cells along {} and a cell of the interior outside its faces",
+            label,
+            position,
+            size,
+            ["x", "y", "z"][axis]
+        );
+    }
+    surface.low = low.map(|v| v as u32);
+    surface.high = high.map(|v| v as u32);
+    Ok(surface)
+}
+
+/// Texels `export` covers, the whole grid without a region.
+fn export_crop(settings: &FDTDSettings, export: &ExportSettings) -> anyhow::Result<export::Crop> {
+    let grid = fdtd::grid_dimension(settings.domain, settings.spatial_step, &settings.boundary);
+    anyhow::ensure!(
+        export.stride.iter().all(|stride| *stride > 0),
+        "Export stride {:?} must be positive along every axis",
+        export.stride
+    );
+    let Some(region) = &export.region else {
+        return Ok(export::Crop {
+            stride: export.stride,
+            ..export::Crop::full(grid)
+        });
+    };
+    let padding = settings.boundary.padding();
+    // texel i has its center at grid coordinate i + 0.5
+    let center = |position| {
+        fdtd::world_to_grid(settings.domain, settings.spatial_step, padding, position)
+            .map(|c| c - 0.5)
+    };
+    let (low, high) = (
+        center(region.min).map(f32::ceil),
+        center(region.max).map(f32::floor),
+    );
+    let mut crop = export::Crop::full(grid);
+    crop.stride = export.stride;
+    for axis in 0..3 {
+        let (low, high) = (low[axis].max(0.0), high[axis].min((grid[axis] - 1) as f32));
+        anyhow::ensure!(
+            low <= high,
+            "Export region from {:?} to {:?} holds no cell centers of the grid along {}",
+            region.min,
+            region.max,
+            ["x", "y", "z"][axis]
+        );
+        crop.low[axis] = low as u32;
+        crop.size[axis] = (high - low) as u32 + 1;
+    }
+    Ok(crop)
+}
+
+/// Cells of the near-field box.
+fn near_field_box(
+    settings: &FDTDSettings,
+    near_field: &NearFieldSettings,
+) -> anyhow::Result<fdtd::SurfaceBox> {
+    closed_box(
+        settings,
+        "Near-field box",
+        near_field.position,
+        near_field.size,
+    )
+}
+
+/// Cells of the `index`-th flux box.
+fn flux_box(settings: &FDTDSettings, index: usize) -> anyhow::Result<fdtd::SurfaceBox> {
+    let flux_box = &settings.flux_boxes[index];
+    closed_box(
+        settings,
+        &format!("Flux box {}", flux_box.name(index)),
+        flux_box.position,
+        flux_box.size,
+    )
+}
+
+/// Energy absorbed inside the `index`-th flux box per unit frequency from the steps run so far,
+/// one sample per wavelength. Blocks until the transforms are read back.
+fn absorption(
+    fdtd: &fdtd::FDTD,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    index: usize,
+) -> anyhow::Result<Vec<flux_box::AbsorptionSample>> {
+    Ok(fdtd
+        .read_flux_box(device, queue, index)?
+        .map(|fields| flux_box::absorption(&fields))
+        .unwrap_or_default())
+}
+
+/// The far field of the near-field box at the current step, `None` without one. Blocks until
+/// the transforms are read back.
+fn far_field(
+    fdtd: &fdtd::FDTD,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    settings: &FDTDSettings,
+) -> anyhow::Result<Option<Vec<far_field::FarFieldSample>>> {
+    let Some(near_field_settings) = &settings.near_field else {
+        return Ok(None);
+    };
+    let Some(near_field) = fdtd.read_near_field(device, queue)? else {
+        return Ok(None);
+    };
+    let background = settings.background.material();
+    Ok(Some(far_field::transform(
+        &near_field,
+        background.permittivity,
+        background.permeability,
+        near_field_settings.theta_count,
+        near_field_settings.phi_count,
+    )))
+}
+
+/// The interior cells of the plane a mode monitor projects.
+fn mode_monitor_plane(
+    settings: &FDTDSettings,
+    monitor: &ModeMonitorSettings,
+) -> anyhow::Result<fdtd::SurfaceBox> {
+    let normal = monitor
+        .mode
+        .normal()
+        .ok_or_else(|| anyhow::anyhow!("Mode monitor at {:?} has no plane", monitor.position))?;
+    let axis = normal.axis();
+    let padding = settings.boundary.padding();
+    let grid = fdtd::grid_dimension(settings.domain, settings.spatial_step, &settings.boundary);
+    let mut low = padding.map(|[low, _]| low);
+    low[axis] = mode_profile::mode_layer(settings, monitor.position, normal)?;
+    let high = [0, 1, 2].map(|axis| grid[axis] - padding[axis][1] - 1);
+    Ok(fdtd::SurfaceBox::plane(axis, low, high))
+}
+
+/// Forward and backward amplitudes of the mode of the `index`-th mode monitor from the steps
+/// run so far, one per wavelength. Blocks until the transforms are read back.
+fn mode_amplitudes(
+    fdtd: &fdtd::FDTD,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    settings: &FDTDSettings,
+    index: usize,
+) -> anyhow::Result<Vec<mode_monitor::ModeAmplitude>> {
+    let monitor = &settings.mode_monitors[index];
+    let fields = fdtd.read_mode_monitor(device, queue, index)?;
+    let normal = monitor.mode.normal().unwrap();
+    let [u, v] = normal.plane_axes();
+    let layer = mode_profile::mode_layer(settings, monitor.position, normal)?;
+    let padding = settings.boundary.padding();
+    (0..fields.frequencies.len())
+        .map(|frequency| {
+            let placement = monitor.placement(1.0 / fields.frequencies[frequency]);
+            let profiles = match &monitor.mode {
+                ModeSettings::Texture {
+                    ex,
+                    ey,
+                    ez,
+                    hx,
+                    hy,
+                    hz,
+                    spatial_step,
+                    normal,
+                    extrapolation,
+                } => mode_profile::texture_profiles(
+                    settings,
+                    &placement,
+                    [ex, ey, ez, hx, hy, hz],
+                    *spatial_step,
+                    *extrapolation,
+                    &monitor.csv,
+                    *normal,
+                )?,
+                ModeSettings::GaussianBeam {
+                    waist,
+                    focus_offset,
+                    polarization,
+                    normal,
+                } => mode_profile::gaussian_beam_profiles(
+                    settings,
+                    &placement,
+                    *waist,
+                    *focus_offset,
+                    *polarization,
+                    *normal,
+                )?,
+                ModeSettings::Port {
+                    mode_index,
+                    polarization,
+                    normal,
+                } => mode_profile::port_profiles(
+                    settings,
+                    &placement,
+                    fdtd,
+                    *mode_index,
+                    *polarization,
+                    *normal,
+                    layer,
+                    device,
+                    queue,
+                )?,
+                ModeSettings::PointCloud { .. }
+                | ModeSettings::Volume { .. }
+                | ModeSettings::Replay { .. } => unreachable!(),
+            };
+            // the profiles span the interior cells of the plane
+            let mode: Vec<_> = fields
+                .cells
+                .iter()
+                .map(|cell| {
+                    let index = [u, v].map(|axis| (cell.texel[axis] - padding[axis][0]) as usize);
+                    let component = |profile: &Option<ModeProfile>| {
+                        profile
+                            .as_ref()
+                            .and_then(|profile| profile.get(index))
+                            .map_or(nalgebra::Complex::default(), |value| {
+                                nalgebra::Complex::new(value.x as f64, value.y as f64)
+                            })
+                    };
+                    [[0, 1, 2], [3, 4, 5]].map(|field| field.map(|i| component(&profiles[i])))
+                })
+                .collect();
+            mode_monitor::project(&fields, frequency, normal.axis(), &mode)
+        })
+        .collect()
+}
+
+/// Grid cell sampled by a probe, which must lie in the interior cells.
+fn probe_texel(settings: &FDTDSettings, probe: &ProbeSettings) -> anyhow::Result<[u32; 3]> {
+    let padding = settings.boundary.padding();
+    let grid = fdtd::grid_dimension(settings.domain, settings.spatial_step, &settings.boundary);
+    let texel = fdtd::world_to_grid(
+        settings.domain,
+        settings.spatial_step,
+        padding,
+        probe.position,
+    )
+    .map(f32::floor);
+    for axis in 0..3 {
+        let interior = padding[axis][0] as f32..(grid[axis] - padding[axis][1]) as f32;
+        anyhow::ensure!(
+            interior.contains(&texel[axis]),
+            "Probe at {:?} is outside the interior cells along {}",
+            probe.position,
+            ["x", "y", "z"][axis]
+        );
+    }
+    Ok(texel.map(|v| v as u32))
+}
+
+/// Recorders of the preset's field recordings.
+fn field_recorders(
+    device: &wgpu::Device,
+    fdtd: &fdtd::FDTD,
+    settings: &FDTDSettings,
+) -> anyhow::Result<Vec<replay::PlaneRecorder>> {
+    settings
+        .field_recordings
+        .iter()
+        .map(|recording| {
+            Ok(replay::PlaneRecorder::new(
+                device,
+                fdtd,
+                source::replay_plane(settings, recording.position, recording.normal)?,
+                recording.file.clone().into(),
+                settings.spatial_step,
+                settings.temporal_step(),
+            ))
+        })
+        .collect()
+}
+
+/// Records one full time step, the magnetic half step followed by the electric one, with sources.
+fn step_fields<'a>(
+    device: &wgpu::Device,
+    fdtd: &fdtd::FDTD,
+    encoder: &mut wgpu::CommandEncoder,
+    settings: &FDTDSettings,
+    magnetic_sources: impl IntoIterator<Item = &'a Source>,
+    electric_sources: impl IntoIterator<Item = &'a Source>,
+    step: u32,
+) {
+    let time = step as f32 * settings.temporal_step();
+    fdtd.update_magnetic_field(encoder);
+    for source in magnetic_sources
+        .into_iter()
+        .filter(|source| source.is_active(time))
+    {
+        source::excite_source(
+            device,
+            fdtd,
+            encoder,
+            settings,
+            source,
+            fdtd::FieldType::H,
+            step,
+        );
+    }
+    fdtd.update_electric_field(encoder);
+    for source in electric_sources
+        .into_iter()
+        .filter(|source| source.is_active(time))
+    {
+        source::excite_source(
+            device,
+            fdtd,
+            encoder,
+            settings,
+            source,
+            fdtd::FieldType::E,
+            step,
+        );
+    }
+    fdtd.accumulate_intensity(encoder);
+    fdtd.accumulate_surfaces(encoder, step + 1);
+}
+
+/// Picks the adapter requested on the command line, or the high performance default.
+/// Indices follow the order printed by --info.
+fn select_adapter(
+    instance: &wgpu::Instance,
+    options: &GremOptions,
+    surface: Option<&wgpu::Surface>,
+) -> anyhow::Result<wgpu::Adapter> {
+    let adapter = if let Some(index) = options.adapter {
+        instance
+            .enumerate_adapters(options.backend.backends())
+            .nth(index)
+            .ok_or_else(|| anyhow::anyhow!("No adapter with index {}, see --info", index))?
+    } else if let Some(name) = &options.adapter_name {
+        let name = name.to_lowercase();
+        instance
+            .enumerate_adapters(options.backend.backends())
+            .find(|adapter| adapter.get_info().name.to_lowercase().contains(&name))
+            .ok_or_else(|| anyhow::anyhow!("No adapter name contains \"{}\", see --info", name))?
+    } else {
+        instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                force_fallback_adapter: false,
+                compatible_surface: surface,
+            })
+            .block_on()
+            .ok_or_else(|| anyhow::anyhow!("No suitable adapter found"))?
+    };
+    if let Some(surface) = surface {
+        anyhow::ensure!(
+            adapter.is_surface_supported(surface),
+            "Adapter {} cannot present to the window, pick another one or use --no-visual",
+            adapter.get_info().name
+        );
+    }
+    Ok(adapter)
+}
+
+/// The field textures are read and written in place as Rgba32Float storage textures,
+/// which WebGPU does not guarantee.
+fn check_formats(adapter: &wgpu::Adapter) -> anyhow::Result<()> {
+    let flags = adapter
+        .get_texture_format_features(wgpu::TextureFormat::Rgba32Float)
+        .flags;
+    // GL adapters report read-write storage for every float format, but GLES only allows it for
+    // the r32 ones and the shaders then fail to compile
+    anyhow::ensure!(
+        adapter.get_info().backend != wgpu::Backend::Gl
+            && adapter
+                .features()
+                .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+            && flags.contains(wgpu::TextureFormatFeatureFlags::STORAGE_READ_WRITE),
+        "Adapter {} cannot read and write Rgba32Float storage textures",
+        adapter.get_info().name
+    );
+    Ok(())
+}
+
+fn print_memory_estimate(estimate: &[fdtd::MemoryEstimate]) {
+    for entry in estimate.iter() {
+        println!(
+            "  {}: {} textures, {:.1} MiB",
+            entry.label,
+            entry.textures,
+            entry.bytes as f64 / (1024.0 * 1024.0)
+        );
+    }
+    println!(
+        "  Total: {:.1} MiB",
+        estimate.iter().map(|entry| entry.bytes).sum::<u64>() as f64 / (1024.0 * 1024.0)
+    );
+}
+
+/// Refuses grids the adapter cannot hold before any texture is created,
+/// wgpu would otherwise fail with a lost device halfway through allocation.
+fn check_memory(
+    limits: &wgpu::Limits,
+    settings: &FDTDSettings,
+    memory_limit: Option<u64>,
+) -> anyhow::Result<()> {
+    let grid = fdtd::grid_dimension(settings.domain, settings.spatial_step, &settings.boundary);
+    for (axis, extent) in ["x", "y", "z"].into_iter().zip(grid) {
+        anyhow::ensure!(
+            extent <= limits.max_texture_dimension_3d,
+            "Grid needs {} cells along {}, the adapter supports at most {} per 3D texture axis",
+            extent,
+            axis,
+            limits.max_texture_dimension_3d
+        );
+    }
+    let estimate = fdtd::estimate_memory(grid, &settings.boundary, settings.intensity.is_some());
+    let total = estimate.iter().map(|entry| entry.bytes).sum::<u64>();
+    if let Some(memory_limit) = memory_limit {
+        if total > memory_limit * 1024 * 1024 {
+            println!("Grid: {} x {} x {}", grid[0], grid[1], grid[2]);
+            print_memory_estimate(&estimate);
+            anyhow::bail!(
+                "Simulation needs {:.1} MiB of texture memory, the limit is {} MiB",
+                total as f64 / (1024.0 * 1024.0),
+                memory_limit
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Refuses workgroups beyond the adapter limits, which would otherwise fail in shader module
+/// creation without naming the preset field. With `clamp`, halves the largest axis until it fits.
+fn check_workgroup(
+    limits: &wgpu::Limits,
+    settings: &mut FDTDSettings,
+    clamp: bool,
+) -> anyhow::Result<()> {
+    let Some(workgroup) = settings.workgroup.as_mut() else {
+        return Ok(());
+    };
+    anyhow::ensure!(
+        workgroup.x > 0 && workgroup.y > 0 && workgroup.z > 0,
+        "Workgroup {} x {} x {} has an empty axis",
+        workgroup.x,
+        workgroup.y,
+        workgroup.z
+    );
+    let max = [
+        limits.max_compute_workgroup_size_x,
+        limits.max_compute_workgroup_size_y,
+        limits.max_compute_workgroup_size_z,
+    ];
+    let fits = |size: [u32; 3]| {
+        (0..3).all(|i| size[i] <= max[i])
+            && size.iter().product::<u32>() <= limits.max_compute_invocations_per_workgroup
+    };
+    let size = [workgroup.x, workgroup.y, workgroup.z];
+    if fits(size) {
+        return Ok(());
+    }
+    anyhow::ensure!(
+        clamp,
+        "Workgroup {} x {} x {} does not fit the adapter, which supports at most {} x {} x {} and {} invocations, pass --clamp-workgroup to shrink it",
+        size[0],
+        size[1],
+        size[2],
+        max[0],
+        max[1],
+        max[2],
+        limits.max_compute_invocations_per_workgroup
+    );
+    let mut clamped = [0, 1, 2].map(|i| size[i].min(max[i]));
+    while !fits(clamped) {
+        let largest = (0..3).max_by_key(|i| clamped[*i]).unwrap();
+        clamped[largest] = (clamped[largest] / 2).max(1);
+    }
+    println!(
+        "Workgroup {} x {} x {} clamped to {} x {} x {}",
+        size[0], size[1], size[2], clamped[0], clamped[1], clamped[2]
+    );
+    [workgroup.x, workgroup.y, workgroup.z] = clamped;
+    Ok(())
+}
+
+fn check_preset(settings: &FDTDSettings, preset: &str) -> anyhow::Result<()> {
+    if let fdtd::BoundaryCondition::PML {
+        sigma,
+        alpha,
+        cells,
+        ..
+    } = settings.boundary
+    {
+        anyhow::ensure!(cells > 0, "PML needs at least one cell");
+        anyhow::ensure!(
+            settings.boundary.pml_faces().is_some_and(|faces| faces
+                .sides()
+                .iter()
+                .flatten()
+                .any(|enabled| *enabled)),
+            "PML covers none of the faces, use a PEC boundary instead"
+        );
+        for (axis, sides) in settings.boundary.symmetry().sides().iter().enumerate() {
+            anyhow::ensure!(
+                settings.boundary.periodic_axes() & (1 << axis) == 0 || sides == &[None; 2],
+                "Axis {} is periodic and cannot have symmetry planes",
+                ["x", "y", "z"][axis]
+            );
+        }
+        anyhow::ensure!(
+            sigma >= 0.0 && alpha >= 0.0,
+            "PML sigma and alpha must not be negative"
+        );
+        for (axis, k) in settings.boundary.bloch_k().iter().enumerate() {
+            anyhow::ensure!(
+                *k == 0.0 || settings.boundary.periodic_axes() & (1 << axis) != 0,
+                "bloch_k is nonzero along {}, which is not periodic",
+                ["x", "y", "z"][axis]
+            );
+        }
+        // a recording holds the real fields only, the imaginary half would go without a source
+        anyhow::ensure!(
+            !settings.boundary.has_bloch_phase()
+                || !settings
+                    .sources
+                    .iter()
+                    .any(|source| matches!(source.mode, ModeSettings::Replay { .. })),
+            "Replay sources cannot drive a simulation with a Bloch wave vector"
+        );
+    }
+
+    // the files were found while loading the preset
+    for source in settings.sources.iter() {
+        match &source.mode {
+            ModeSettings::PointCloud { normal, .. }
+            | ModeSettings::Texture { normal, .. }
+            | ModeSettings::GaussianBeam { normal, .. }
+            | ModeSettings::Port { normal, .. } => {
+                mode_profile::mode_layer(settings, source.position, *normal)?;
+            }
+            ModeSettings::Replay { normal, .. } => {
+                source::replay_plane(settings, source.position, *normal)?;
+            }
+            ModeSettings::Volume { .. } => (),
+        }
+    }
+    for recording in settings.field_recordings.iter() {
+        source::replay_plane(settings, recording.position, recording.normal)?;
+    }
+    for probe in settings.probes.iter() {
+        probe_texel(settings, probe)?;
+    }
+    if let Some(near_field) = &settings.near_field {
+        near_field_box(settings, near_field)?;
+    }
+    for index in 0..settings.flux_boxes.len() {
+        flux_box(settings, index)?;
+    }
+    for monitor in settings.mode_monitors.iter() {
+        mode_monitor_plane(settings, monitor)?;
+    }
+
+    let grid = fdtd::grid_dimension(settings.domain, settings.spatial_step, &settings.boundary);
+    let cells = grid.iter().map(|&v| v as u64).product::<u64>();
+    println!("Preset {} is valid", preset);
+    println!(
+        "Grid: {} x {} x {} = {} cells (dx = {}, dt = {})",
+        grid[0],
+        grid[1],
+        grid[2],
+        cells,
+        settings.spatial_step,
+        settings.temporal_step() / units::speed_of_light(settings.units.as_ref())
+    );
+    print_memory_estimate(&fdtd::estimate_memory(
+        grid,
+        &settings.boundary,
+        settings.intensity.is_some(),
+    ));
+
+    let last_scheduled = settings
+        .pause_at
+        .iter()
+        .chain(settings.exports.iter().map(|export| &export.timing))
+        .filter(|timing| !timing.is_periodic())
+        .map(|timing| timing.to_step(settings.temporal_step()))
+        .max();
+    match settings.last_step() {
+        Some(last_step) => println!(
+            "Runs {} steps ({})",
+            last_step,
+            units::describe_time(
+                settings.units.as_ref(),
+                last_step as f32 * settings.temporal_step()
+            )
+        ),
+        None => println!(
+            "Runs until closed, last scheduled pause or export at step {}",
+            last_scheduled.map_or("-".to_string(), |step| step.to_string())
+        ),
+    }
+    // in the order of the preset, periodic pauses as "every N"
+    let pauses = settings
+        .pause_at
+        .iter()
+        .map(|timing| {
+            let step = timing.to_step(settings.temporal_step());
+            if timing.is_periodic() {
+                format!("every {}", step)
+            } else {
+                step.to_string()
+            }
+        })
+        .collect::<Vec<_>>();
+    if !pauses.is_empty() {
+        println!("Pauses at steps {}", pauses.join(", "));
+    }
+
+    Ok(())
+}
+
+/// Every file the preset reads, by its field in the preset.
+fn preset_files(settings: &mut FDTDSettings) -> Vec<(String, &mut String)> {
+    let mut files = vec![];
+    if let Some(path) = &mut settings.default_shader {
+        files.push(("default_shader".to_string(), path));
+    }
+    for (index, model) in settings.models.iter_mut().enumerate() {
+        files.push((format!("models[{}].path", index), &mut model.path));
+    }
+    for (index, extrusion) in settings.extrusions.iter_mut().enumerate() {
+        files.push((format!("extrusions[{}].path", index), &mut extrusion.path));
+    }
+    for (index, cloud) in settings.material_clouds.iter_mut().enumerate() {
+        files.push((format!("material_clouds[{}].path", index), &mut cloud.path));
+    }
+    for (index, source) in settings.sources.iter_mut().enumerate() {
+        if let Some(path) = &mut source.waveform {
+            files.push((format!("sources[{}].waveform", index), path));
+        }
+        for (key, path) in source.mode.files() {
+            files.push((format!("sources[{}].mode.settings.{}", index, key), path));
+        }
+    }
+    for (index, monitor) in settings.mode_monitors.iter_mut().enumerate() {
+        for (key, path) in monitor.mode.files() {
+            files.push((
+                format!("mode_monitors[{}].mode.settings.{}", index, key),
+                path,
+            ));
+        }
+    }
+    if let Some(initial) = &mut settings.initial_fields {
+        for (field, paths) in [
+            ("electric", &mut initial.electric),
+            ("magnetic", &mut initial.magnetic),
+        ] {
+            for (component, path) in paths.iter_mut().flatten().enumerate() {
+                files.push((format!("initial_fields.{}[{}]", field, component), path));
+            }
+        }
+    }
+    files
+}
+
+/// Resolves the relative paths of the files the preset reads against `directory`. Preset files
+/// already had theirs joined to their own directory when loaded, so that presets can be moved
+/// along with their files. Fails naming every preset field whose file cannot be read.
+fn resolve_preset_files(settings: &mut FDTDSettings, directory: &Path) -> anyhow::Result<()> {
+    let mut missing = vec![];
+    for (field, path) in preset_files(settings) {
+        let joined = directory.join(&*path);
+        let resolved = std::path::absolute(&joined).unwrap_or(joined);
+        match std::fs::metadata(&resolved) {
+            Ok(metadata) if metadata.is_file() => {
+                *path = resolved.to_string_lossy().into_owned();
+            }
+            Ok(_) => missing.push(format!(
+                "{} = {:?}: {} is not a file",
+                field,
+                path,
+                resolved.display()
+            )),
+            Err(err) => missing.push(format!(
+                "{} = {:?}: {}: {}",
+                field,
+                path,
+                resolved.display(),
+                err
+            )),
+        }
+    }
+    anyhow::ensure!(
+        missing.is_empty(),
+        "The preset names files that cannot be read:\n  {}",
+        missing.join("\n  ")
+    );
+    Ok(())
+}
+
+/// Workgroup of about the largest invocation count the adapter allows, spread evenly over the
+/// axes the dimension mode keeps.
+fn default_workgroup(
+    limits: &wgpu::Limits,
+    dimension_mode: fdtd::DimensionMode,
+) -> WorkgroupSettings {
+    let invocations = limits.max_compute_invocations_per_workgroup as f32;
+    let cell = match dimension_mode.dimensions() {
+        1 => invocations,
+        2 => invocations.sqrt(),
+        _ => invocations.cbrt(),
+    } as u32;
+    let max = [
+        limits.max_compute_workgroup_size_x,
+        limits.max_compute_workgroup_size_y,
+        limits.max_compute_workgroup_size_z,
+    ];
+    // collapsed axes have no use for an extent
+    let [x, y, z] = [0, 1, 2].map(|axis| {
+        if dimension_mode.collapsed_axes().contains(&axis) {
+            1
+        } else {
+            cell.min(max[axis])
+        }
+    });
+    WorkgroupSettings {
+        x,
+        y,
+        z,
+        tiled: false,
+    }
+}
+
+/// Sets up the sources and the simulation of a prepared preset. Returns the simulation with
+/// its magnetic and electric sources.
+#[allow(clippy::too_many_arguments)]
+fn create_simulation(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    limits: &wgpu::Limits,
+    settings: &mut FDTDSettings,
+    shaders: &fdtd::ShaderPaths,
+    render_format: Option<wgpu::TextureFormat>,
+    params: fdtd::Params,
+    profiler: Option<fdtd::Profiler>,
+    dump_materials: Option<&Path>,
+    reuse_cache: bool,
+    strict: bool,
+) -> anyhow::Result<(fdtd::FDTD, Vec<Source>, Vec<Source>)> {
+    let mode_source_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::ReadOnly,
+                        format: wgpu::TextureFormat::Rg32Float,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::ReadOnly,
+                        format: wgpu::TextureFormat::Rg32Float,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::ReadOnly,
+                        format: wgpu::TextureFormat::Rg32Float,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+    let empty_placeholder = device
+        .create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("EMPTY D2 Rg32Float"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rg32Float,
+                usage: wgpu::TextureUsages::STORAGE_BINDING,
+                view_formats: &[],
+            },
+            bytemuck::cast_slice(&[0f32; 2]),
+        )
+        .create_view(&wgpu::TextureViewDescriptor::default());
+
+    let mut electric_sources = vec![];
+    let mut magnetic_sources = vec![];
+
+    let mut ports = vec![];
+    for (index, source) in settings.sources.iter().enumerate() {
+        let waveform = source
+            .waveform
+            .as_ref()
+            .map(|path| {
+                source::load_waveform(
+                    Path::new(path),
+                    settings.temporal_step(),
+                    source.delay,
+                    units::speed_of_light(settings.units.as_ref()),
+                )
+            })
+            .transpose()?;
+        let mode = match &source.mode {
+            ModeSettings::Texture {
+                ex,
+                ey,
+                ez,
+                hx,
+                hy,
+                hz,
+                spatial_step,
+                normal,
+                extrapolation,
+            } => Some((
+                *normal,
+                mode_profile::mode_textures(
+                    device,
+                    queue,
+                    mode_profile::texture_profiles(
+                        settings,
+                        &source.placement(),
+                        [ex, ey, ez, hx, hy, hz],
+                        *spatial_step,
+                        *extrapolation,
+                        &source.csv,
+                        *normal,
+                    )?,
+                ),
+            )),
+            ModeSettings::GaussianBeam {
+                waist,
+                focus_offset,
+                polarization,
+                normal,
+            } => Some((
+                *normal,
+                mode_profile::mode_textures(
+                    device,
+                    queue,
+                    mode_profile::gaussian_beam_profiles(
+                        settings,
+                        &source.placement(),
+                        *waist,
+                        *focus_offset,
+                        *polarization,
+                        *normal,
+                    )?,
+                ),
+            )),
+            ModeSettings::Volume { direction, field } => {
+                match field {
+                    fdtd::FieldType::E => electric_sources.push(Source::Volume {
+                        direction: *direction,
+                        waveform: waveform.clone(),
+                        tones: source.tones(),
+                        chirp_rate: source.chirp_rate,
+                        position: source.position,
+                        size: source.size,
+                        phase: source.phase,
+                        delay: source.delay,
+                        fwhm: source.fwhm,
+                        power: source.power,
+                        envelope: source.envelope,
+                        ramp_time: source.ramp_time,
+                        active: source.active(),
+                        origin: index,
+                        scale: 1.0,
+                    }),
+                    fdtd::FieldType::H => magnetic_sources.push(Source::Volume {
+                        direction: *direction,
+                        waveform: waveform.clone(),
+                        tones: source.tones(),
+                        chirp_rate: source.chirp_rate,
+                        position: source.position,
+                        size: source.size,
+                        phase: source.phase,
+                        delay: source.delay,
+                        fwhm: source.fwhm,
+                        power: source.power,
+                        envelope: source.envelope,
+                        ramp_time: source.ramp_time,
+                        active: source.active(),
+                        origin: index,
+                        scale: 1.0,
+                    }),
+                }
+                None
+            }
+            ModeSettings::Replay {
+                file,
+                backward,
+                normal,
+            } => {
+                let plane = source::replay_plane(settings, source.position, *normal)?;
+                let stream = std::sync::Arc::new(replay::ReplayStream::open(
+                    Path::new(file),
+                    &plane,
+                    settings.spatial_step,
+                    settings.temporal_step(),
+                )?);
+                for (sources, field) in [
+                    (&mut electric_sources, fdtd::FieldType::E),
+                    (&mut magnetic_sources, fdtd::FieldType::H),
+                ] {
+                    sources.extend(source::replay_source(
+                        device,
+                        &mode_source_bind_group_layout,
+                        &empty_placeholder,
+                        source,
+                        index,
+                        &plane,
+                        stream.clone(),
+                        *backward,
+                        field,
+                        settings.dimension_mode,
+                    ));
+                }
+                None
+            }
+            ModeSettings::PointCloud {
+                file,
+                exclude,
+                extrapolation,
+                normal,
+            } => {
+                let profile = mode_profile::point_cloud_profile(
+                    file,
+                    &source.placement(),
+                    settings.domain,
+                    settings.spatial_step,
+                    settings.boundary.padding(),
+                    *extrapolation,
+                    &source.csv,
+                    *normal,
+                )?;
+                // the one profile drives every component the source does not exclude
+                let mut profiles: [Option<ModeProfile>; 6] =
+                    std::array::from_fn(|_| Some(profile.clone()));
+                for (field, component) in exclude {
+                    let first = match field {
+                        fdtd::FieldType::E => 0,
+                        fdtd::FieldType::H => 3,
+                    };
+                    profiles[first + component.axis()] = None;
+                }
+                Some((
+                    *normal,
+                    mode_profile::mode_textures(device, queue, profiles),
+                ))
+            }
+            ModeSettings::Port { .. } => {
+                // solved once the materials are voxelized
+                ports.push((index, source, waveform));
+                continue;
+            }
+        };
+
+        let Some((normal, components)) = mode else {
+            continue;
+        };
+        let layer = mode_profile::mode_layer(settings, source.position, normal)?;
+        let [electric, magnetic] = source::mode_sources(
+            device,
+            &mode_source_bind_group_layout,
+            &empty_placeholder,
+            source,
+            index,
+            &waveform,
+            normal,
+            layer,
+            components,
+            settings.dimension_mode,
+        );
+        electric_sources.extend(electric);
+        magnetic_sources.extend(magnetic);
+    }
+
+    let near_field = settings
+        .near_field
+        .as_ref()
+        .map(|near_field| {
+            let frequencies = near_field
+                .wavelengths
+                .iter()
+                .map(|wavelength| 1.0 / wavelength)
+                .collect();
+            near_field_box(settings, near_field).map(|surface| (surface, frequencies))
+        })
+        .transpose()?;
+    let mode_monitors = settings
+        .mode_monitors
+        .iter()
+        .map(|monitor| {
+            let frequencies = monitor
+                .wavelengths
+                .iter()
+                .map(|wavelength| 1.0 / wavelength)
+                .collect();
+            mode_monitor_plane(settings, monitor).map(|surface| (surface, frequencies))
+        })
+        .collect::<anyhow::Result<_>>()?;
+    let flux_surfaces = settings
+        .sources
+        .iter()
+        .filter(|source| source.normalize.is_some())
+        .map(|source| source::normalization_box(settings, source))
+        .collect::<anyhow::Result<_>>()?;
+    let flux_boxes = (0..settings.flux_boxes.len())
+        .map(|index| {
+            let frequencies = settings.flux_boxes[index]
+                .wavelengths
+                .iter()
+                .map(|wavelength| 1.0 / wavelength)
+                .collect();
+            flux_box(settings, index).map(|surface| (surface, frequencies))
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+    let workgroup_dispatch = settings
+        .workgroup
+        .take()
+        .unwrap_or_else(|| default_workgroup(limits, settings.dimension_mode));
+    let fdtd = fdtd::FDTD::new(
+        device,
+        queue,
+        settings,
+        fdtd::FDTDDescriptor {
+            render_format,
+            shaders,
+            workgroup_dispatch,
+            near_field,
+            mode_monitors,
+            flux_surfaces,
+            flux_boxes,
+            mode_source_bind_group_layout: &mode_source_bind_group_layout,
+            dump_materials,
+            reuse_cache,
+            strict,
+            params,
+            profiler,
+        },
+    )?;
+    if let Some(err) = device.pop_error_scope().block_on() {
+        let grid = fdtd::grid_dimension(settings.domain, settings.spatial_step, &settings.boundary);
+        println!("Grid: {} x {} x {}", grid[0], grid[1], grid[2]);
+        print_memory_estimate(&fdtd::estimate_memory(
+            grid,
+            &settings.boundary,
+            settings.intensity.is_some(),
+        ));
+        anyhow::bail!("Out of GPU memory while allocating the simulation: {}", err);
+    }
+    if fdtd.uses_tiled_update() {
+        println!("Using the shared-memory tiled update");
+    }
+    for (index, source, waveform) in ports {
+        let ModeSettings::Port {
+            mode_index,
+            polarization,
+            normal,
+        } = source.mode
+        else {
+            unreachable!()
+        };
+        let layer = mode_profile::mode_layer(settings, source.position, normal)?;
+        let components = mode_profile::mode_textures(
+            device,
+            queue,
+            mode_profile::port_profiles(
+                settings,
+                &source.placement(),
+                &fdtd,
+                mode_index,
+                polarization,
+                normal,
+                layer,
+                device,
+                queue,
+            )?,
+        );
+        let [electric, magnetic] = source::mode_sources(
+            device,
+            &mode_source_bind_group_layout,
+            &empty_placeholder,
+            source,
+            index,
+            &waveform,
+            normal,
+            layer,
+            components,
+            settings.dimension_mode,
+        );
+        electric_sources.extend(electric);
+        magnetic_sources.extend(magnetic);
+    }
+    source::normalize_sources(
+        device,
+        queue,
+        &fdtd,
+        settings,
+        &mut magnetic_sources,
+        &mut electric_sources,
+    )?;
+    if let Some(initial) = &settings.initial_fields {
+        initial_fields::load(queue, &fdtd, initial, settings.spatial_step)?;
+    }
+
+    Ok((fdtd, magnetic_sources, electric_sources))
+}
+
+/// Runs the command line of the grems binary.
+pub fn run() -> anyhow::Result<()> {
+    let options = GremOptions::parse();
+    anyhow::ensure!(
+        options.scroll_pixels_per_line > 0.0,
+        "--scroll-pixels-per-line must be positive"
+    );
+
+    if options.info {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: options.backend.backends(),
+            ..Default::default()
+        });
+        for (index, adapter) in instance
+            .enumerate_adapters(options.backend.backends())
+            .enumerate()
+        {
+            let info = adapter.get_info();
+            println!(
+                "[{}] {} ({:?}, {:?})",
+                index, info.name, info.backend, info.device_type
+            );
+            println!("Device: {:?}", info);
+            println!("{:?}", adapter.limits());
+        }
+        return Ok(());
+    }
+
+    let preset = options.preset.last().unwrap().clone();
+    let layers = preset::load(&options.preset)?;
+    let mut settings = preset::deserialize(layers.config)?;
+    // what is still relative after loading is relative to the working directory
+    resolve_preset_files(&mut settings, Path::new(""))?;
+    prepare::prepare_settings(&mut settings)?;
+    if let Some(watch_energy) = options.watch_energy {
+        anyhow::ensure!(
+            watch_energy > 0.0,
+            "--watch-energy needs a positive percentage"
+        );
+        settings.energy.get_or_insert(EnergySettings {
+            every: settings::default_energy_every(),
+        });
+    }
+
+    let shaders =
+        fdtd::ShaderPaths::new(options.shader_dir.as_deref().map(Path::new), &layers.files);
+
+    if options.check {
+        return check_preset(&settings, &preset);
+    }
+    anyhow::ensure!(
+        !options.no_visual || settings.last_step().is_some(),
+        "--no-visual needs max_steps or end_time in the preset, nothing resumes a paused run without a window"
+    );
+
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: options.backend.backends(),
+        ..Default::default()
+    });
+    let window = (!options.no_visual)
+        .then(|| window::Window::new(&instance, &preset))
+        .transpose()?;
+    let adapter = select_adapter(
+        &instance,
+        &options,
+        window.as_ref().map(window::Window::surface),
+    )?;
+    let adapter_info = adapter.get_info();
+    println!(
+        "Using adapter {} ({:?}, {:?})",
+        adapter_info.name, adapter_info.backend, adapter_info.device_type
+    );
+    check_formats(&adapter)?;
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: adapter.features(),
+                limits: adapter.limits(),
+            },
+            None,
+        )
+        .block_on()?;
+    let device = std::sync::Arc::new(device);
+    let queue = std::sync::Arc::new(queue);
+
+    check_memory(&device.limits(), &settings, options.memory_limit)?;
+    check_workgroup(&device.limits(), &mut settings, options.clamp_workgroup)?;
+
+    // everything drawn to the window, text included, targets this one format
+    let format = match &window {
+        Some(window) => select_surface_format(
+            &window.surface().get_capabilities(&adapter).formats,
+            options.surface_format,
+        )?,
+        None => HEADLESS_RENDER_FORMAT,
+    };
+    let params = fdtd::Params::new(&device, &queue, options.no_push_constants);
+    if !params.uses_push_constants() {
+        println!("Push constants unavailable, passing shader parameters through uniform buffers");
+    }
+    let profiler = if options.profile {
+        let profiler = fdtd::Profiler::new(&device, &queue);
+        if profiler.is_none() {
+            println!("Timestamp queries unsupported by the adapter, profiling disabled");
+        }
+        profiler
+    } else {
+        None
+    };
+    let (fdtd, magnetic_sources, electric_sources) = create_simulation(
+        &device,
+        &queue,
+        &adapter.limits(),
+        &mut settings,
+        &shaders,
+        Some(format),
+        params,
+        profiler,
+        options
+            .dump_materials
+            .then(|| format!("{}-materials.dds", preset))
+            .as_deref()
+            .map(Path::new),
+        !options.no_cache,
+        options.strict,
+    )?;
+    if options.verify_tiling {
+        let difference = fdtd.compare_tiled_update(&device, &queue)?;
+        println!(
+            "Largest difference between tiled and untiled update: {}",
+            difference
+        );
+        return Ok(());
+    }
+
+    let mut step_counter = 0;
+
+    if let Some(resume) = &options.resume {
+        // entries up to the checkpoint never fire again since steps only move forward
+        step_counter = checkpoint::load(&queue, &fdtd, &settings, Path::new(resume))?;
+        println!("Resumed from {} at step {}", resume, step_counter);
+    }
+
+    let probes = settings
+        .probes
+        .iter()
+        .map(|probe| {
+            Ok(probe::Probe {
+                name: probe
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| format!("{:?}", probe.position)),
+                texel: probe_texel(&settings, probe)?,
+                field: probe.field,
+                component: probe.component,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let probes = probe::ProbeRecorder::new(probes, settings.probe_history);
+
+    let fdtd = std::sync::Arc::new(std::sync::Mutex::new(fdtd));
+    let Some(window) = window else {
+        let last_step = settings.last_step();
+        let status = simulation::Simulation::spawn(
+            device,
+            queue,
+            fdtd,
+            settings,
+            magnetic_sources,
+            electric_sources,
+            probes,
+            options.watch_energy,
+            preset,
+            format,
+            options.checkpoint_every,
+            step_counter,
+            None,
+        )?
+        .wait();
+        if let Some(last_step) = last_step {
+            anyhow::ensure!(
+                status.step >= last_step,
+                "Stopped at step {} before the last step {}",
+                status.step,
+                last_step
+            );
+        }
+        return Ok(());
+    };
+    let session = window::Session {
+        device: device.clone(),
+        queue: queue.clone(),
+        fdtd: fdtd.clone(),
+        traces: probes.traces(),
+        preset: preset.clone(),
+        step_batch: settings.step_batch,
+        temporal_step: settings.temporal_step(),
+        units: settings.units,
+        resonance: settings.resonance.clone().unwrap_or_default(),
+        probe_history: settings.probe_history,
+        // spawned last since it takes the settings
+        simulation: simulation::Simulation::spawn(
+            device,
+            queue,
+            fdtd,
+            settings,
+            magnetic_sources,
+            electric_sources,
+            probes,
+            options.watch_energy,
+            preset,
+            format,
+            options.checkpoint_every,
+            step_counter,
+            Some(window.proxy()),
+        )?,
+    };
+    window.run(&options, &adapter, format, session)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn box_cells_hold_the_centers_inside_the_box() {
+        let domain = [[-1.0, 1.0], [-1.0, 1.0], [-0.5, 0.5]];
+        let padding = [[20, 20]; 3];
+        // interior cell j spans [-1 + 0.05 j, -1 + 0.05 (j + 1)] along x and y
+        let cells = fdtd::box_cells(domain, 0.05, padding, [0.3, 0.1, 0.0], [0.4, 0.3, 0.0]);
+        assert_eq!(cells, [42..50, 39..45, 30..31]);
+        // a box off the faces holds the cells whose centers it covers, at least one
+        let cells = fdtd::box_cells(domain, 0.05, padding, [0.31, 0.1, 0.0], [0.4, 0.01, 0.0]);
+        assert_eq!(cells, [42..50, 42..43, 30..31]);
+        // reaches past the grid rather than being cut
+        let cells = fdtd::box_cells(domain, 0.05, padding, [-2.0, 0.0, 0.0], [0.2, 0.0, 0.0]);
+        assert_eq!(cells[0], -2..2);
+    }
+}
@@ -0,0 +1,159 @@
+use std::path::Path;
+
+use crate::fdtd::SurfaceFields;
+
+type Complex = nalgebra::Complex<f64>;
+
+/// Amplitudes of a guided mode on a monitor plane at one wavelength, scaled so that |a|² is
+/// the energy the mode carries through the plane per unit bandwidth. Dividing by the same
+/// quantity of a reference run gives |S21|².
+#[derive(Clone, Copy, Debug)]
+pub struct ModeAmplitude {
+    pub wavelength: f32,  // in vacuum
+    pub forward: Complex, // along the direction the mode travels
+    pub backward: Complex,
+}
+
+// (a × b) · e_axis
+fn cross_along(axis: usize, a: &[Complex; 3], b: &[Complex; 3]) -> Complex {
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    a[u] * b[v] - a[v] * b[u]
+}
+
+/// Projects the transforms at the `frequency`-th frequency of a plane normal to `axis` onto
+/// a mode given as E and H per plane cell, by the overlap integrals of E × H_m* and
+/// E_m* × H over the plane.
+pub fn project(
+    fields: &SurfaceFields,
+    frequency: usize,
+    axis: usize,
+    mode: &[[[Complex; 3]; 2]],
+) -> anyhow::Result<ModeAmplitude> {
+    let conjugate = |field: &[Complex; 3]| field.map(|component| component.conj());
+    let mut field_by_mode = Complex::default();
+    let mut mode_by_field = Complex::default();
+    let mut mode_power = 0.0;
+    for (cell, [mode_electric, mode_magnetic]) in mode.iter().enumerate() {
+        let [electric, magnetic] = fields.fields(frequency, cell);
+        field_by_mode += cross_along(axis, &electric, &conjugate(mode_magnetic));
+        mode_by_field += cross_along(axis, &conjugate(mode_electric), &magnetic);
+        mode_power += 0.5 * cross_along(axis, mode_electric, &conjugate(mode_magnetic)).re;
+    }
+    anyhow::ensure!(
+        mode_power != 0.0,
+        "The mode carries no power through the monitor plane, its E and H do not overlap"
+    );
+    let area = fields.area as f64;
+    // a mode travelling against the axis turns the integrals around
+    let sign = mode_power.signum();
+    let (field_by_mode, mode_by_field) = (field_by_mode * area * sign, mode_by_field * area * sign);
+    let norm = 4.0 * (mode_power.abs() * area).sqrt();
+    Ok(ModeAmplitude {
+        wavelength: 1.0 / fields.frequencies[frequency],
+        forward: (field_by_mode + mode_by_field) / norm,
+        backward: (field_by_mode - mode_by_field) / norm,
+    })
+}
+
+/// Writes rows of the vacuum wavelength, then |a|² and the phase in degrees of the forward
+/// and of the backward amplitude.
+pub fn write_csv(path: &Path, amplitudes: &[ModeAmplitude]) -> anyhow::Result<()> {
+    let mut text =
+        "wavelength,forward_power,forward_phase,backward_power,backward_phase\n".to_string();
+    for amplitude in amplitudes {
+        text += &format!(
+            "{},{},{},{},{}\n",
+            amplitude.wavelength,
+            amplitude.forward.norm_sqr(),
+            amplitude
+                .forward
+                .im
+                .atan2(amplitude.forward.re)
+                .to_degrees(),
+            amplitude.backward.norm_sqr(),
+            amplitude
+                .backward
+                .im
+                .atan2(amplitude.backward.re)
+                .to_degrees()
+        );
+    }
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: f32 = 0.04;
+
+    /// E along x and H along y of a mode on a plane normal to z, travelling along +z or -z.
+    fn mode(profile: &[f64], forward: bool) -> Vec<[[Complex; 3]; 2]> {
+        let zero = Complex::default();
+        let sign = if forward { 1.0 } else { -1.0 };
+        profile
+            .iter()
+            .map(|value| {
+                let value = Complex::new(*value, 0.0);
+                [[value, zero, zero], [zero, value * sign, zero]]
+            })
+            .collect()
+    }
+
+    /// Transforms of `forward` times the mode along +z plus `backward` times the one along -z.
+    fn fields(profile: &[f64], forward: Complex, backward: Complex) -> SurfaceFields {
+        let [along, against] = [true, false].map(|direction| mode(profile, direction));
+        let cells: Vec<_> = along
+            .iter()
+            .zip(&against)
+            .enumerate()
+            .map(|(index, (along, against))| {
+                let field = [0, 1]
+                    .map(|i| [0, 1, 2].map(|j| along[i][j] * forward + against[i][j] * backward));
+                ([index as f32, 0.0, 0.0], (2, 1.0), field)
+            })
+            .collect();
+        SurfaceFields::single_frequency(0.5, AREA, &cells)
+    }
+
+    #[test]
+    fn overlaps_split_a_field_into_its_forward_and_backward_amplitudes() {
+        let profile = [0.25, 1.0, 0.5, -0.5];
+        let (forward, backward) = (Complex::new(0.6, -0.8), Complex::new(-0.1, 0.3));
+        // |a|² is the power of the mode, 0.5 ∑ E × H*, times the area and |amplitude|²
+        let norm = (0.5 * profile.iter().map(|v| v * v).sum::<f64>() * AREA as f64).sqrt();
+        let amplitude = project(
+            &fields(&profile, forward, backward),
+            0,
+            2,
+            &mode(&profile, true),
+        )
+        .unwrap();
+        assert_eq!(amplitude.wavelength, 2.0);
+        assert!((amplitude.forward - forward * norm).norm_sqr() < 1e-12);
+        assert!((amplitude.backward - backward * norm).norm_sqr() < 1e-12);
+
+        // against the axis forward and backward trade places
+        let amplitude = project(
+            &fields(&profile, forward, backward),
+            0,
+            2,
+            &mode(&profile, false),
+        )
+        .unwrap();
+        assert!((amplitude.forward - backward * norm).norm_sqr() < 1e-12);
+        assert!((amplitude.backward - forward * norm).norm_sqr() < 1e-12);
+    }
+
+    #[test]
+    fn mode_without_power_is_refused() {
+        let profile = [1.0, 1.0];
+        let mut electric_only = mode(&profile, true);
+        for [_, magnetic] in &mut electric_only {
+            *magnetic = [Complex::default(); 3];
+        }
+        let fields = fields(&profile, Complex::new(1.0, 0.0), Complex::default());
+        assert!(project(&fields, 0, 2, &electric_only).is_err());
+    }
+}
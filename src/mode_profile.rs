@@ -0,0 +1,690 @@
+//! Mode profiles of sources and monitors on their planes, from files, beams and the mode
+//! solver, and the textures they are uploaded to.
+
+use std::path::Path;
+
+use ndarray::ShapeBuilder;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    fdtd, interpolator, mode_solver, parse_csv_field, read_csv_record,
+    settings::{CsvSettings, FDTDSettings, ProfilePlacement},
+};
+
+struct RG32;
+
+impl resize::PixelFormat for RG32 {
+    type InputPixel = nalgebra::Vector2<f32>;
+
+    type OutputPixel = nalgebra::Vector2<f32>;
+
+    type Accumulator = nalgebra::Vector2<f32>;
+
+    #[inline(always)]
+    fn new() -> Self::Accumulator {
+        nalgebra::vector![0.0, 0.0]
+    }
+
+    #[inline(always)]
+    fn add(&self, acc: &mut Self::Accumulator, inp: Self::InputPixel, coeff: f32) {
+        acc.x += inp.x * coeff;
+        acc.y += inp.y * coeff;
+    }
+
+    #[inline(always)]
+    fn add_acc(acc: &mut Self::Accumulator, inp: Self::Accumulator, coeff: f32) {
+        acc.x += inp.x * coeff;
+        acc.y += inp.y * coeff;
+    }
+
+    #[inline(always)]
+    fn into_pixel(&self, acc: Self::Accumulator) -> Self::OutputPixel {
+        acc
+    }
+}
+
+/// Grid index of the plane a mode source injects into, `position` along `normal` picks it.
+/// Refuses planes outside the interior, which would write into the boundary layers.
+pub fn mode_layer(
+    settings: &FDTDSettings,
+    position: [f32; 3],
+    normal: fdtd::SliceMode,
+) -> anyhow::Result<u32> {
+    let axis = normal.axis();
+    let padding = settings.boundary.padding();
+    let grid = fdtd::grid_dimension(settings.domain, settings.spatial_step, &settings.boundary);
+    let layer = fdtd::box_cells(
+        settings.domain,
+        settings.spatial_step,
+        padding,
+        position,
+        [0.0; 3],
+    )[axis]
+        .start as f32;
+    let interior = padding[axis][0] as f32..(grid[axis] - padding[axis][1]) as f32;
+    anyhow::ensure!(
+        interior.contains(&layer),
+        "Mode source plane at {:?} along {:?} is cell {}, outside the interior cells {} to {}",
+        position,
+        normal,
+        layer,
+        interior.start,
+        interior.end - 1.0
+    );
+    Ok(layer as u32)
+}
+
+/// Rg32Float texture holding the real and imaginary part of one mode component, sized like
+/// the interior of the injection plane.
+fn create_mode_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    mode: &ModeProfile,
+) -> wgpu::TextureView {
+    device
+        .create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: mode.dim().0 as _,
+                    height: mode.dim().1 as _,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rg32Float,
+                usage: wgpu::TextureUsages::STORAGE_BINDING,
+                view_formats: &[],
+            },
+            bytemuck::cast_slice(mode.as_slice_memory_order().unwrap()),
+        )
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// Uploads the profiles of a mode source.
+pub fn mode_textures(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    profiles: [Option<ModeProfile>; 6],
+) -> [Option<wgpu::TextureView>; 6] {
+    profiles.map(|profile| profile.map(|profile| create_mode_texture(device, queue, &profile)))
+}
+
+/// Ex, Ey, Ez, Hx, Hy, Hz profiles read from the CSV files of a texture mode, `None` where
+/// a component has no file.
+pub fn texture_profiles(
+    settings: &FDTDSettings,
+    placement: &ProfilePlacement,
+    paths: [&Option<String>; 6],
+    texture_dx: f32,
+    extrapolation: interpolator::Extrapolation,
+    csv: &CsvSettings,
+    normal: fdtd::SliceMode,
+) -> anyhow::Result<[Option<ModeProfile>; 6]> {
+    let mut profiles = [None, None, None, None, None, None];
+    for (profile, path) in profiles.iter_mut().zip(paths) {
+        *profile = path
+            .as_ref()
+            .map(|path| {
+                real_imag_csv_profile(
+                    path,
+                    placement,
+                    settings.domain,
+                    settings.spatial_step,
+                    settings.boundary.padding(),
+                    texture_dx,
+                    extrapolation,
+                    csv,
+                    normal,
+                )
+            })
+            .transpose()?;
+    }
+    Ok(profiles)
+}
+
+/// Ex, Ey, Ez, Hx, Hy, Hz profiles of a Gaussian beam in the background medium crossing the
+/// injection plane through the source position and travelling along `normal`. The focus lies
+/// `focus_offset` from the plane along the normal, away from it the profile picks up the
+/// wavefront curvature and Gouy phase. Components the polarization leaves at zero get no profile.
+pub fn gaussian_beam_profiles(
+    settings: &FDTDSettings,
+    source: &ProfilePlacement,
+    waist: f32,
+    focus_offset: f32,
+    polarization: [f32; 3],
+    normal: fdtd::SliceMode,
+) -> anyhow::Result<[Option<ModeProfile>; 6]> {
+    let axis = normal.axis();
+    let [u, v] = normal.plane_axes();
+    let mut electric = nalgebra::Vector3::from(polarization);
+    electric[axis] = 0.0;
+    anyhow::ensure!(
+        waist > 0.0 && electric.norm() > 0.0,
+        "Gaussian beam at {:?} needs a positive waist and a polarization across the plane",
+        source.position
+    );
+    let electric = electric.normalize();
+    let background = settings.background.material();
+    // H = n x E / impedance
+    let magnetic = nalgebra::Vector3::ith(axis, 1.0).cross(&electric)
+        * (background.permittivity / background.permeability).sqrt();
+
+    let dx = settings.spatial_step;
+    let padding = settings.boundary.padding();
+    let grid = [u, v].map(|i| {
+        fdtd::steps_across(settings.domain[i][1] - settings.domain[i][0], dx).ceil() as usize
+    });
+    // world position of the first interior cell
+    let shift = fdtd::grid_shift(settings.domain, dx, padding);
+    let origin = [0, 1, 2].map(|i| padding[i][0] as f32 * dx - shift[i]);
+
+    let wavelength = source.wavelength / settings.background.refractive_index();
+    let k = 2.0 * std::f32::consts::PI / wavelength;
+    let rayleigh = std::f32::consts::PI * waist * waist / wavelength;
+    // the plane, relative to the focus
+    let z = -focus_offset;
+    let width = waist * (1.0 + (z / rayleigh).powi(2)).sqrt();
+    let curvature = z / (z * z + rayleigh * rayleigh);
+    let gouy = (z / rayleigh).atan();
+    let (ps, pc) = source.phase.to_radians().sin_cos();
+
+    let profile = ndarray::Array2::from_shape_fn((grid[0], grid[1]).f(), |(x, y)| {
+        let r2 = (origin[u] + x as f32 * dx - source.position[u]).powi(2)
+            + (origin[v] + y as f32 * dx - source.position[v]).powi(2);
+        let amplitude = waist / width * (-r2 / (width * width)).exp() * source.power;
+        let (sin, cos) = (gouy - k * z - 0.5 * k * r2 * curvature).sin_cos();
+        let (re, im) = (amplitude * cos, amplitude * sin);
+        nalgebra::vector![re * pc - im * ps, re * ps + im * pc]
+    });
+
+    Ok([
+        electric[0],
+        electric[1],
+        electric[2],
+        magnetic[0],
+        magnetic[1],
+        magnetic[2],
+    ]
+    .map(|component| (component != 0.0).then(|| profile.mapv(|p| p * component))))
+}
+
+/// Ex, Ey, Ez, Hx, Hy, Hz profiles of the `mode_index`-th guided mode of the cross-section
+/// of the materials in the injection plane, within the source size around the source position.
+/// The scalar mode is polarized along `polarization` and travels along `normal`.
+#[allow(clippy::too_many_arguments)]
+pub fn port_profiles(
+    settings: &FDTDSettings,
+    source: &ProfilePlacement,
+    fdtd: &fdtd::FDTD,
+    mode_index: usize,
+    polarization: [f32; 3],
+    normal: fdtd::SliceMode,
+    layer: u32,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> anyhow::Result<[Option<ModeProfile>; 6]> {
+    let axis = normal.axis();
+    let [u, v] = normal.plane_axes();
+    let mut electric = nalgebra::Vector3::from(polarization);
+    electric[axis] = 0.0;
+    anyhow::ensure!(
+        electric.norm() > 0.0,
+        "Port at {:?} needs a polarization across the plane",
+        source.position
+    );
+    let electric = electric.normalize();
+    let magnetic = nalgebra::Vector3::ith(axis, 1.0).cross(&electric);
+
+    let dx = settings.spatial_step;
+    let padding = settings.boundary.padding();
+    let permittivity = fdtd.read_permittivity_plane(device, queue, normal, layer)?;
+    // world position of the first interior cell
+    let shift = fdtd::grid_shift(settings.domain, dx, padding);
+    let origin = [0, 1, 2].map(|i| padding[i][0] as f32 * dx - shift[i]);
+    let [(u_start, u_end), (v_start, v_end)] = [(u, 0), (v, 1)].map(|(i, dim)| {
+        let cells = permittivity.dim();
+        let cells = if dim == 0 { cells.0 } else { cells.1 };
+        let low = (source.position[i] - source.size[i] / 2.0 - origin[i]) / dx;
+        let high = (source.position[i] + source.size[i] / 2.0 - origin[i]) / dx;
+        (
+            (low.ceil().max(0.0) as usize).min(cells),
+            (high.floor() as isize + 1).clamp(0, cells as isize) as usize,
+        )
+    });
+    anyhow::ensure!(
+        u_end >= u_start + 3 && v_end >= v_start + 3,
+        "Port at {:?} covers less than 3 x 3 cells of the plane, enlarge its size",
+        source.position
+    );
+
+    let mode = mode_solver::solve(
+        &permittivity
+            .slice(ndarray::s![u_start..u_end, v_start..v_end])
+            .to_owned(),
+        dx,
+        source.wavelength,
+        mode_index,
+    )?;
+    println!(
+        "Port at {:?}: mode {} has an effective index of {:.4}",
+        source.position, mode_index, mode.effective_index
+    );
+
+    let (ps, pc) = source.phase.to_radians().sin_cos();
+    let mut profile = ndarray::Array2::from_elem(permittivity.raw_dim().f(), 0.0);
+    profile
+        .slice_mut(ndarray::s![u_start..u_end, v_start..v_end])
+        .assign(&mode.profile.mapv(|p| p * source.power));
+    Ok([
+        electric[0],
+        electric[1],
+        electric[2],
+        magnetic[0] * mode.effective_index,
+        magnetic[1] * mode.effective_index,
+        magnetic[2] * mode.effective_index,
+    ]
+    .map(|component| {
+        (component != 0.0).then(|| profile.mapv(|p| nalgebra::vector![p * pc, p * ps] * component))
+    }))
+}
+
+/// A complex profile over the interior cells of a plane, x-fastest along its two axes.
+pub type ModeProfile = ndarray::Array2<nalgebra::Vector2<f32>>;
+
+#[allow(clippy::too_many_arguments)]
+fn real_imag_csv_profile<P: AsRef<Path>>(
+    path: P,
+    placement: &ProfilePlacement,
+    domain: [[f32; 2]; 3],
+    dx: f32,
+    padding: [[u32; 2]; 3],
+    texture_dx: f32,
+    extrapolation: interpolator::Extrapolation,
+    csv: &CsvSettings,
+    normal: fdtd::SliceMode,
+) -> anyhow::Result<ModeProfile> {
+    let ProfilePlacement {
+        position,
+        size: dimension_scale,
+        phase,
+        power: power_scale,
+        ..
+    } = *placement;
+    // the CSV x and y run along these grid axes
+    let [u, v] = normal.plane_axes();
+    let step_x = (domain[u][1] - domain[u][0]) / dx;
+    let step_y = (domain[v][1] - domain[v][0]) / dx;
+
+    let grid_x = step_x.ceil() as usize;
+    let grid_y = step_y.ceil() as usize;
+
+    let path = path.as_ref();
+    let (mut rdr, [x_column, y_column, real_column, imag_column]) = csv.open(path)?;
+    let mut min_x = f32::INFINITY;
+    let mut max_x = f32::NEG_INFINITY;
+    let mut min_y = f32::INFINITY;
+    let mut max_y = f32::NEG_INFINITY;
+
+    for record in rdr.records() {
+        let record = read_csv_record(path, record)?;
+        let x: f32 = parse_csv_field(path, &record, x_column)?;
+        let y: f32 = parse_csv_field(path, &record, y_column)?;
+        min_x = min_x.min(x);
+        max_x = max_x.max(x);
+        min_y = min_y.min(y);
+        max_y = max_y.max(y);
+    }
+
+    let width = max_x - min_x;
+    let height = max_y - min_y;
+
+    anyhow::ensure!(width > 0. && height > 0.);
+
+    let texture_width = (width / texture_dx).ceil() as usize + 1;
+    let texture_height = (height / texture_dx).ceil() as usize + 1;
+
+    let mut input_texture =
+        ndarray::Array2::<nalgebra::Vector2<f32>>::default((texture_width, texture_height).f());
+    let (ps, pc) = phase.to_radians().sin_cos();
+
+    let (mut rdr, _) = csv.open(path)?;
+
+    for record in rdr.records() {
+        let record = read_csv_record(path, record)?;
+        let x: f32 = parse_csv_field(path, &record, x_column)?;
+        let y: f32 = parse_csv_field(path, &record, y_column)?;
+        let real_amp: f32 = parse_csv_field(path, &record, real_column)?;
+        let imag_amp: f32 = parse_csv_field(path, &record, imag_column)?;
+
+        let x = ((x - min_x) / texture_dx).round() as usize;
+        let y = ((y - min_y) / texture_dx).round() as usize;
+
+        input_texture[[x, y]] =
+            nalgebra::vector![real_amp * pc - imag_amp * ps, real_amp * ps + imag_amp * pc,]
+                * power_scale;
+    }
+
+    let dst_width = (width * dimension_scale[u] / dx).ceil() as usize;
+    let dst_height = (height * dimension_scale[v] / dx).ceil() as usize;
+
+    let mut result_texture =
+        ndarray::Array2::<nalgebra::Vector2<f32>>::default((dst_width, dst_height).f());
+
+    let mut resizer = resize::new(
+        texture_width,
+        texture_height,
+        dst_width,
+        dst_height,
+        RG32,
+        resize::Type::Lanczos3,
+    )?;
+
+    resizer.resize(
+        input_texture.as_slice_memory_order().unwrap(),
+        result_texture.as_slice_memory_order_mut().unwrap(),
+    )?;
+
+    let mut embed_texture =
+        ndarray::Array2::<nalgebra::Vector2<f32>>::default((grid_x, grid_y).f());
+
+    // the mode covers the cells a volume source of its size at `position` would, the embedding
+    // is in interior cells like the texture
+    let mut extent = [0.0; 3];
+    extent[u] = dst_width as f32 * dx;
+    extent[v] = dst_height as f32 * dx;
+    let cells = fdtd::box_cells(domain, dx, padding, position, extent);
+    let offset_x = cells[u].start - padding[u][0] as i32;
+    let offset_y = cells[v].start - padding[v][0] as i32;
+
+    for embed_x in 0..grid_x as i32 {
+        for embed_y in 0..grid_y as i32 {
+            let x = embed_x - offset_x;
+            let y = embed_y - offset_y;
+
+            let inside = x >= 0 && y >= 0 && x < dst_width as i32 && y < dst_height as i32;
+            // the hull of the resampled profile is its rectangle, the closest point on it is
+            // also the closest texel
+            if inside || !matches!(extrapolation, interpolator::Extrapolation::Zero) {
+                embed_texture[[embed_x as usize, embed_y as usize]] = result_texture[[
+                    x.clamp(0, dst_width as i32 - 1) as usize,
+                    y.clamp(0, dst_height as i32 - 1) as usize,
+                ]];
+            }
+        }
+    }
+
+    Ok(embed_texture)
+}
+
+/// Profile interpolated over the interior cells of the plane from the scattered rows of a
+/// point cloud CSV, whose x and y are scaled by the source size and offset by its position.
+/// Cells outside the hull of the cloud follow `extrapolation`.
+#[allow(clippy::too_many_arguments)]
+pub fn point_cloud_profile<P: AsRef<Path>>(
+    path: P,
+    placement: &ProfilePlacement,
+    domain: [[f32; 2]; 3],
+    dx: f32,
+    padding: [[u32; 2]; 3],
+    extrapolation: interpolator::Extrapolation,
+    csv: &CsvSettings,
+    normal: fdtd::SliceMode,
+) -> anyhow::Result<ModeProfile> {
+    let ProfilePlacement {
+        position,
+        size: dimension_scale,
+        phase,
+        power: power_scale,
+        ..
+    } = *placement;
+    // the CSV x and y run along these grid axes
+    let [u, v] = normal.plane_axes();
+    let step_x = (domain[u][1] - domain[u][0]) / dx;
+    let step_y = (domain[v][1] - domain[v][0]) / dx;
+
+    let grid_x = step_x.ceil() as usize;
+    let grid_y = step_y.ceil() as usize;
+
+    let path = path.as_ref();
+    let (mut rdr, [x_column, y_column, real_column, imag_column]) = csv.open(path)?;
+
+    let interp = interpolator::Linear2DInterpolator::<2>::new(
+        rdr.records()
+            .map(|record| {
+                let record = read_csv_record(path, record)?;
+                let x: f32 = parse_csv_field(path, &record, x_column)?;
+                let y: f32 = parse_csv_field(path, &record, y_column)?;
+                let real_amp: f32 = parse_csv_field(path, &record, real_column)?;
+                let imag_amp: f32 = parse_csv_field(path, &record, imag_column)?;
+
+                let x = x * dimension_scale[u] + position[u];
+                let y = y * dimension_scale[v] + position[v];
+
+                Ok((nalgebra::vector![x as f64, y as f64], [real_amp, imag_amp]))
+            })
+            .collect::<anyhow::Result<_>>()?,
+        extrapolation,
+    );
+
+    let (ps, pc) = phase.to_radians().sin_cos();
+    // world position of the first interior cell
+    let shift = fdtd::grid_shift(domain, dx, padding);
+    let origin = [0, 1, 2].map(|i| padding[i][0] as f32 * dx - shift[i]);
+
+    let texture_array: ndarray::Array2<nalgebra::Vector2<f32>> =
+        ndarray::Array2::from_shape_fn((grid_x as usize, grid_y as usize).f(), |(x, y)| {
+            let v = interp
+                .interpolate(nalgebra::vector![
+                    (origin[u] + x as f32 * dx) as f64,
+                    (origin[v] + y as f32 * dx) as f64
+                ])
+                .unwrap_or_default();
+
+            nalgebra::vector![v[0] * pc - v[1] * ps, v[0] * ps + v[1] * pc] * power_scale
+        });
+
+    Ok(texture_array)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_files::temp_file;
+
+    /// A profile at the origin, neither scaled nor phase shifted.
+    fn placement() -> ProfilePlacement {
+        ProfilePlacement {
+            position: [0.0; 3],
+            size: [1.0; 3],
+            wavelength: 1.0,
+            phase: 0.0,
+            power: 1.0,
+        }
+    }
+
+    #[test]
+    fn point_cloud_profile_extrapolates_past_the_cloud() {
+        // 1 + x on a 5 x 5 grid over [-0.5, 0.5]^2
+        let mut csv = String::from("x,y,real,imag\n");
+        for i in 0..5 {
+            for j in 0..5 {
+                let [x, y] = [i, j].map(|k| -0.5 + 0.25 * k as f32);
+                csv += &format!("{},{},{},0\n", x, y, 1.0 + x);
+            }
+        }
+        let path = temp_file("extrapolated-cloud.csv", &csv);
+        for extrapolation in [
+            interpolator::Extrapolation::Zero,
+            interpolator::Extrapolation::Nearest,
+            interpolator::Extrapolation::ClampToHull,
+        ] {
+            let profile = point_cloud_profile(
+                &path,
+                &placement(),
+                [[-1.0, 1.0]; 3],
+                0.1,
+                [[3, 3]; 3],
+                extrapolation,
+                &CsvSettings::default(),
+                fdtd::SliceMode::Z,
+            )
+            .unwrap();
+            assert_eq!(profile.dim(), (20, 20));
+            // the profile samples -1 + 0.1 i along x and y
+            for ((i, j), value) in profile.indexed_iter() {
+                let [x, y] = [i, j].map(|k| -1.0 + 0.1 * k as f32);
+                if [x, y].iter().any(|c| (c.abs() - 0.5).abs() < 0.01) {
+                    continue;
+                }
+                let clamped = x.clamp(-0.5, 0.5);
+                let expected = match (x.abs() < 0.5 && y.abs() < 0.5, extrapolation) {
+                    (true, _) => 1.0 + x,
+                    (false, interpolator::Extrapolation::Zero) => 0.0,
+                    // the cloud point closest to (x, y) is on its 0.25 grid
+                    (false, interpolator::Extrapolation::Nearest) => {
+                        1.0 + (clamped / 0.25).round() * 0.25
+                    }
+                    (false, interpolator::Extrapolation::ClampToHull) => 1.0 + clamped,
+                };
+                assert!(
+                    (value.x - expected).abs() < 1e-5 && value.y == 0.0,
+                    "{:?} instead of {} at {:?} with {:?}",
+                    value,
+                    expected,
+                    [x, y],
+                    extrapolation
+                );
+            }
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn malformed_point_cloud_rows_are_named() {
+        let path = temp_file(
+            "malformed-cloud.csv",
+            "x,y,real,imag\n0,0,1,0\n1,0,1,0\n0,1e,1,0\n1,1,1,0\n",
+        );
+        let err = point_cloud_profile(
+            &path,
+            &placement(),
+            [[-1.0, 1.0]; 3],
+            0.1,
+            [[0, 0]; 3],
+            interpolator::Extrapolation::Nearest,
+            &CsvSettings::default(),
+            fdtd::SliceMode::Z,
+        )
+        .unwrap_err()
+        .to_string();
+        // the header is line 1
+        for part in [&path.display().to_string(), "line 4", "column 2", "\"1e\""] {
+            assert!(err.contains(part), "{:?} does not name {}", err, part);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn point_cloud_profile_follows_the_normal() {
+        // neither symmetric in x and y nor in either of them
+        let mut csv = String::from("x,y,real,imag\n");
+        for i in 0..6 {
+            for j in 0..6 {
+                let [x, y] = [i, j].map(|k| -0.5 + 0.2 * k as f32);
+                csv += &format!("{},{},{},{}\n", x, y, 1.0 + x, 0.5 * y + x * y);
+            }
+        }
+        let path = temp_file("normal-cloud.csv", &csv);
+        // the plane spans the first two entries, the normal the last
+        let profile = |normal: fdtd::SliceMode, axes: [usize; 3]| {
+            let rotate = |values: [f32; 3]| {
+                let mut rotated = [0.0; 3];
+                for (value, axis) in values.into_iter().zip(axes) {
+                    rotated[axis] = value;
+                }
+                rotated
+            };
+            let [a, b, c] = [[-1.0, 1.0], [-1.5, 1.5], [-0.2, 0.2]];
+            let mut domain = [[0.0; 2]; 3];
+            let mut padding = [[0; 2]; 3];
+            for ((range, cells), axis) in [(a, [2, 3]), (b, [4, 1]), (c, [0, 0])]
+                .into_iter()
+                .zip(axes)
+            {
+                domain[axis] = range;
+                padding[axis] = cells;
+            }
+            let placement = ProfilePlacement {
+                position: rotate([0.1, -0.2, 0.0]),
+                size: rotate([1.0, 2.0, 1.0]),
+                ..placement()
+            };
+            point_cloud_profile(
+                &path,
+                &placement,
+                domain,
+                0.1,
+                padding,
+                interpolator::Extrapolation::Zero,
+                &CsvSettings::default(),
+                normal,
+            )
+            .unwrap()
+        };
+        let expected = profile(fdtd::SliceMode::Z, [0, 1, 2]);
+        assert_eq!(expected.dim(), (20, 30));
+        assert!(expected
+            .iter()
+            .any(|value| value.x != 0.0 && value.y != 0.0));
+        assert_eq!(profile(fdtd::SliceMode::X, [1, 2, 0]), expected);
+        assert_eq!(profile(fdtd::SliceMode::Y, [0, 2, 1]), expected);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn point_cloud_dialect_picks_the_columns() {
+        // the same cloud as a plain CSV and tab separated with the columns moved around
+        let [mut plain, mut tabbed] = [
+            String::from("x,y,real,imag\n"),
+            String::from("Y\tEr\tnote\tX\tEi\n"),
+        ];
+        for i in 0..4 {
+            for j in 0..4 {
+                let [x, y] = [i, j].map(|k| -0.3 + 0.2 * k as f32);
+                let [real, imag] = [1.0 + x, 0.5 * y];
+                plain += &format!("{},{},{},{}\n", x, y, real, imag);
+                tabbed += &format!("{}\t{}\tsample\t{}\t{}\n", y, real, x, imag);
+            }
+        }
+        let plain = temp_file("dialect-plain.csv", &plain);
+        let tabbed = temp_file("dialect-tabbed.csv", &tabbed);
+        let profile = |path: &std::path::Path, csv: &str| {
+            point_cloud_profile(
+                path,
+                &placement(),
+                [[-1.0, 1.0]; 3],
+                0.1,
+                [[0, 0]; 3],
+                interpolator::Extrapolation::Nearest,
+                &serde_json::from_str(csv).unwrap(),
+                fdtd::SliceMode::Z,
+            )
+        };
+        // by name and by index
+        let columns = r#"{ "x": "X", "y": "Y", "real": "Er", "imag": 4 }"#;
+        let expected = profile(&plain, "{}").unwrap();
+        assert!(expected.iter().any(|value| value.y != 0.0));
+        let dialect = format!(r#"{{ "delimiter": "\t", "columns": {} }}"#, columns);
+        assert_eq!(profile(&tabbed, &dialect).unwrap(), expected);
+
+        let dialect = dialect.replace(r#""X""#, r#""x""#);
+        let err = profile(&tabbed, &dialect).unwrap_err().to_string();
+        assert!(err.contains(r#"no column named "x" for x"#), "{}", err);
+        for path in [plain, tabbed] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,357 @@
+/// Guided mode of a waveguide cross-section.
+pub struct Mode {
+    pub effective_index: f32,
+    /// Scalar field over the plane, with a peak of 1
+    pub profile: ndarray::Array2<f32>,
+}
+
+// the Lanczos basis is kept in full for reorthogonalization, which bounds the plane size
+const MAX_BASIS_BYTES: usize = 512 << 20;
+const MAX_STEPS: usize = 200;
+const TOLERANCE: f64 = 1e-8;
+// relative residual of the inner solves
+const SOLVE_TOLERANCE: f64 = 1e-12;
+
+/// Finds the `index`-th guided mode of the cross-section, counting from the fundamental one
+/// by decreasing effective index. Solves the scalar Helmholtz equation
+/// `(∇² + k0² ε) φ = β² φ` with the five-point Laplacian and a zero field around the plane
+/// and in cells of infinite permittivity, which are conductors.
+///
+/// The operator is shifted by the largest `k0² ε`, which makes `σ - A` positive definite with
+/// the guided modes at its smallest eigenvalues. Lanczos iterations on its inverse, applied
+/// with conjugate gradients, then converge on them in a few dozen steps.
+pub fn solve(
+    permittivity: &ndarray::Array2<f32>,
+    dx: f32,
+    wavelength: f32,
+    index: usize,
+) -> anyhow::Result<Mode> {
+    let (width, height) = permittivity.dim();
+    let n = width * height;
+    anyhow::ensure!(n > index, "Mode {} asked of a plane of {} cells", index, n);
+    let k0 = 2.0 * std::f64::consts::PI / wavelength as f64;
+    let mut potential = vec![0.0; n];
+    let mut conductor = vec![false; n];
+    for ((x, y), epsilon) in permittivity.indexed_iter() {
+        if epsilon.is_finite() {
+            potential[x + y * width] = k0 * k0 * *epsilon as f64;
+        } else {
+            conductor[x + y * width] = true;
+        }
+    }
+    anyhow::ensure!(
+        conductor.iter().any(|c| !c),
+        "Mode plane lies entirely within a conductor"
+    );
+    let sigma = potential.iter().fold(0.0f64, |max, p| max.max(*p));
+    let inv_dx2 = 1.0 / (dx as f64 * dx as f64);
+
+    // out = (σ - A) v
+    let shifted = |v: &[f64], out: &mut [f64]| {
+        for y in 0..height {
+            for x in 0..width {
+                let i = x + y * width;
+                if conductor[i] {
+                    // decoupled and far from the guided modes
+                    out[i] = (sigma + 8.0 * inv_dx2) * v[i];
+                    continue;
+                }
+                let mut laplacian = -4.0 * v[i];
+                for (inside, j) in [
+                    (x > 0, i.wrapping_sub(1)),
+                    (x + 1 < width, i + 1),
+                    (y > 0, i.wrapping_sub(width)),
+                    (y + 1 < height, i + width),
+                ] {
+                    if inside && !conductor[j] {
+                        laplacian += v[j];
+                    }
+                }
+                out[i] = (sigma - potential[i]) * v[i] - laplacian * inv_dx2;
+            }
+        }
+    };
+    let dot = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(a, b)| a * b).sum::<f64>();
+    // x = (σ - A)⁻¹ b by conjugate gradients
+    let solve_shifted = |b: &[f64], x: &mut [f64]| -> anyhow::Result<()> {
+        x.fill(0.0);
+        let mut r = b.to_vec();
+        let mut p = r.clone();
+        let mut ap = vec![0.0; n];
+        let mut rr = dot(&r, &r);
+        let target = rr * SOLVE_TOLERANCE * SOLVE_TOLERANCE;
+        let mut iterations = 0;
+        while rr > target {
+            anyhow::ensure!(
+                iterations < 10 * n,
+                "Mode solver: inner solve did not converge"
+            );
+            shifted(&p, &mut ap);
+            let step = rr / dot(&p, &ap);
+            for i in 0..n {
+                x[i] += step * p[i];
+                r[i] -= step * ap[i];
+            }
+            let next = dot(&r, &r);
+            for i in 0..n {
+                p[i] = r[i] + next / rr * p[i];
+            }
+            rr = next;
+            iterations += 1;
+        }
+        Ok(())
+    };
+
+    // overlaps the core, perturbed so modes of any symmetry are reached
+    let mut seed = 0x2545f4914f6cdd1du64;
+    let mut v: Vec<f64> = potential
+        .iter()
+        .zip(&conductor)
+        .map(|(p, conductor)| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let noise = k0 * k0 * ((seed >> 11) as f64 / (1u64 << 53) as f64);
+            if *conductor {
+                0.0
+            } else {
+                p + noise
+            }
+        })
+        .collect();
+    let norm = dot(&v, &v).sqrt();
+    v.iter_mut().for_each(|v| *v /= norm);
+
+    let max_steps = (MAX_BASIS_BYTES / (8 * n)).min(MAX_STEPS).min(n);
+    let mut basis: Vec<Vec<f64>> = Vec::with_capacity(max_steps);
+    let mut alpha = vec![];
+    let mut beta: Vec<f64> = vec![];
+    let mut w = vec![0.0; n];
+    for step in 0..max_steps {
+        solve_shifted(&v, &mut w)?;
+        let a = dot(&w, &v);
+        for (w, v) in w.iter_mut().zip(&v) {
+            *w -= a * v;
+        }
+        if let (Some(previous), Some(b)) = (basis.last(), beta.last()) {
+            for (w, p) in w.iter_mut().zip(previous) {
+                *w -= b * p;
+            }
+        }
+        basis.push(v);
+        // full reorthogonalization, twice is enough
+        for _ in 0..2 {
+            for q in basis.iter() {
+                let c = dot(&w, q);
+                for (w, q) in w.iter_mut().zip(q) {
+                    *w -= c * q;
+                }
+            }
+        }
+        alpha.push(a);
+        let b = dot(&w, &w).sqrt();
+
+        let m = step + 1;
+        let exhausted = b <= f64::EPSILON * a.abs();
+        if m > index {
+            let tridiagonal = nalgebra::DMatrix::from_fn(m, m, |i, j| {
+                if i == j {
+                    alpha[i]
+                } else if i == j + 1 {
+                    beta[j]
+                } else if j == i + 1 {
+                    beta[i]
+                } else {
+                    0.0
+                }
+            });
+            let eigen = tridiagonal.symmetric_eigen();
+            // the largest eigenvalues of the inverse belong to the largest β²
+            let mut order: Vec<usize> = (0..m).collect();
+            order.sort_by(|&i, &j| eigen.eigenvalues[j].total_cmp(&eigen.eigenvalues[i]));
+            let k = order[index];
+            let eigenvalue = eigen.eigenvalues[k];
+            let ritz = eigen.eigenvectors.column(k);
+            if exhausted || b * ritz[m - 1].abs() <= TOLERANCE * eigenvalue.abs() {
+                return into_mode(
+                    permittivity,
+                    &basis,
+                    ritz.as_slice(),
+                    sigma - 1.0 / eigenvalue,
+                    k0,
+                    index,
+                );
+            }
+        }
+        if exhausted {
+            break;
+        }
+        beta.push(b);
+        v = w.iter().map(|w| w / b).collect();
+    }
+    anyhow::bail!(
+        "Mode solver did not converge on mode {} within {} iterations",
+        index,
+        basis.len()
+    )
+}
+
+fn into_mode(
+    permittivity: &ndarray::Array2<f32>,
+    basis: &[Vec<f64>],
+    ritz: &[f64],
+    propagation_constant2: f64,
+    k0: f64,
+    index: usize,
+) -> anyhow::Result<Mode> {
+    let (width, height) = permittivity.dim();
+    let cladding = permittivity
+        .iter()
+        .fold(f32::INFINITY, |min, v| min.min(*v)) as f64;
+    // a plane bounded by conductors guides below the lowest permittivity, as in a hollow pipe
+    let cladding = if permittivity.iter().any(|v| v.is_infinite()) {
+        0.0
+    } else {
+        cladding
+    };
+    let effective_index = propagation_constant2.max(0.0).sqrt() / k0;
+    anyhow::ensure!(
+        effective_index * effective_index > cladding,
+        "Mode {} has an effective index of {:.4}, not above the cladding index {:.4}, it is not guided",
+        index,
+        effective_index,
+        cladding.sqrt()
+    );
+
+    let mut field = vec![0.0; width * height];
+    for (q, y) in basis.iter().zip(ritz) {
+        for (f, q) in field.iter_mut().zip(q) {
+            *f += y * q;
+        }
+    }
+    let peak = field.iter().fold(
+        0.0f64,
+        |peak, f| if f.abs() > peak.abs() { *f } else { peak },
+    );
+    Ok(Mode {
+        effective_index: effective_index as f32,
+        profile: ndarray::Array2::from_shape_fn((width, height), |(x, y)| {
+            (field[x + y * width] / peak) as f32
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hollow_box_modes_match_the_discrete_laplacian() {
+        // 12 x 8 cells inside a ring of conductor cells
+        let (width, height, dx, wavelength) = (14, 10, 0.1f32, 0.5f32);
+        let permittivity = ndarray::Array2::from_shape_fn((width, height), |(x, y)| {
+            if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+                f32::INFINITY
+            } else {
+                1.0
+            }
+        });
+        let k0 = 2.0 * std::f64::consts::PI / wavelength as f64;
+        // eigenvalues of the five-point Laplacian with a zero field on the ring
+        let eigenvalue = |p: usize, q: usize| {
+            let [a, b] = [(p, width - 1), (q, height - 1)].map(|(mode, cells)| {
+                (mode as f64 * std::f64::consts::PI / (2.0 * cells as f64))
+                    .sin()
+                    .powi(2)
+            });
+            -4.0 * (a + b) / (dx as f64 * dx as f64)
+        };
+        let mut expected: Vec<(f64, [usize; 2])> = (1..width - 1)
+            .flat_map(|p| (1..height - 1).map(move |q| (eigenvalue(p, q), [p, q])))
+            .collect();
+        expected.sort_by(|a, b| b.0.total_cmp(&a.0));
+        for (index, (laplacian, [p, q])) in expected.iter().take(3).enumerate() {
+            let mode = solve(&permittivity, dx, wavelength, index).unwrap();
+            let effective_index = ((k0 * k0 + laplacian).sqrt() / k0) as f32;
+            assert!(
+                (mode.effective_index - effective_index).abs() < 1e-5,
+                "mode {}: {} instead of {}",
+                index,
+                mode.effective_index,
+                effective_index
+            );
+            // sin(p π x / 13) sin(q π y / 9) up to its sign
+            let shape = |x: usize, y: usize| {
+                ((p * x) as f64 * std::f64::consts::PI / (width - 1) as f64).sin()
+                    * ((q * y) as f64 * std::f64::consts::PI / (height - 1) as f64).sin()
+            };
+            let peak = (1..width - 1)
+                .flat_map(|x| (1..height - 1).map(move |y| shape(x, y).abs()))
+                .fold(0.0f64, f64::max);
+            let sign = (mode.profile[[1, 1]] as f64 * shape(1, 1)).signum() / peak;
+            for ((x, y), value) in mode.profile.indexed_iter() {
+                assert!(
+                    (*value as f64 - sign * shape(x, y)).abs() < 1e-4,
+                    "mode {} at {:?}: {}",
+                    index,
+                    [x, y],
+                    value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn slab_mode_has_the_analytic_effective_index() {
+        // a core of 0.4 with n = 2 in vacuum, running across the whole width of the plane
+        let (width, height, dx, wavelength, thickness) = (99, 70, 0.02f32, 1.0f32, 0.4f32);
+        let [core, cladding] = [2.0f64, 1.0];
+        let permittivity = ndarray::Array2::from_shape_fn((width, height), |(_, y)| {
+            let center = (y as f32 + 0.5) * dx - height as f32 * dx / 2.0;
+            if center.abs() < thickness / 2.0 {
+                (core * core) as f32
+            } else {
+                (cladding * cladding) as f32
+            }
+        });
+        let mode = solve(&permittivity, dx, wavelength, 0).unwrap();
+        // the problem separates, the zero field at both ends of the width only lowering β² by
+        // the fundamental eigenvalue of the Laplacian across it
+        let k0 = 2.0 * std::f64::consts::PI / wavelength as f64;
+        let across = 4.0 / (dx as f64 * dx as f64)
+            * (std::f64::consts::PI / (2.0 * (width + 1) as f64))
+                .sin()
+                .powi(2);
+        let slab_index = ((mode.effective_index as f64).powi(2) + across / (k0 * k0)).sqrt();
+
+        // the fundamental TE mode solves tan(κ d / 2) = γ / κ, bisected over the indices
+        // where κ d / 2 stays below π / 2
+        let kappa = |index: f64| k0 * (core * core - index * index).sqrt();
+        let mismatch = |index: f64| {
+            let gamma = k0 * (index * index - cladding * cladding).sqrt();
+            (kappa(index) * thickness as f64 / 2.0).tan() - gamma / kappa(index)
+        };
+        let cutoff = std::f64::consts::PI / (k0 * thickness as f64);
+        let (mut low, mut high) = ((core * core - cutoff * cutoff).sqrt().max(cladding), core);
+        for _ in 0..100 {
+            let middle = 0.5 * (low + high);
+            if mismatch(middle) > 0.0 {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        assert!(
+            (slab_index - low).abs() < 5e-3,
+            "{} instead of {}",
+            slab_index,
+            low
+        );
+        // even about the center of the core, and decaying into the cladding
+        let profile = mode.profile.row(width / 2);
+        for y in 0..height / 2 {
+            assert!((profile[y] - profile[height - 1 - y]).abs() < 1e-4);
+        }
+        assert!(profile[0].abs() < 1e-2 && profile[height / 2].abs() > 0.99);
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::VecDeque;
+
+struct Batch {
+    buffer: wgpu::Buffer,
+    steps: Vec<u32>,
+    receiver: Option<std::sync::mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>>,
+}
+
+/// Reads back a sample of `sample_size` bytes every `every` steps without stalling the
+/// simulation. The samples of a batch of steps are copied into one buffer, which is mapped
+/// once the batch was submitted and handed out on a later poll.
+pub struct PeriodicReadback {
+    label: &'static str,
+    every: u32,
+    sample_size: u64,
+    recording: Option<Batch>,
+    in_flight: VecDeque<Batch>,
+    free: Vec<wgpu::Buffer>,
+}
+
+impl PeriodicReadback {
+    pub fn new(label: &'static str, every: u32, sample_size: u64) -> Self {
+        Self {
+            label,
+            every: every.max(1),
+            sample_size,
+            recording: None,
+            in_flight: VecDeque::new(),
+            free: vec![],
+        }
+    }
+
+    /// Starts a batch of at most `steps` steps following `step`.
+    pub fn begin(&mut self, device: &wgpu::Device, step: u32, steps: u32) {
+        let samples = (step + steps) / self.every - step / self.every;
+        if samples == 0 {
+            return;
+        }
+        let size = samples as u64 * self.sample_size;
+        let buffer = match self.free.iter().position(|buffer| buffer.size() >= size) {
+            Some(index) => self.free.swap_remove(index),
+            None => device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(self.label),
+                size,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+        };
+        self.recording = Some(Batch {
+            buffer,
+            steps: Vec::with_capacity(samples as usize),
+            receiver: None,
+        });
+    }
+
+    /// Where to copy the sample of `step`, `None` if it is not due.
+    pub fn sample(&mut self, step: u32) -> Option<(&wgpu::Buffer, u64)> {
+        let batch = self.recording.as_mut()?;
+        let offset = batch.steps.len() as u64 * self.sample_size;
+        if !step.is_multiple_of(self.every) || offset + self.sample_size > batch.buffer.size() {
+            return None;
+        }
+        batch.steps.push(step);
+        Some((&batch.buffer, offset))
+    }
+
+    /// Starts mapping the batch recorded since `begin`, once its encoder was submitted.
+    pub fn map_pending(&mut self) {
+        let Some(mut batch) = self.recording.take() else {
+            return;
+        };
+        if batch.steps.is_empty() {
+            self.free.push(batch.buffer);
+            return;
+        }
+        let (sender, receiver) = std::sync::mpsc::channel();
+        batch
+            .buffer
+            .slice(..batch.steps.len() as u64 * self.sample_size)
+            .map_async(wgpu::MapMode::Read, move |v| {
+                let _ = sender.send(v);
+            });
+        batch.receiver = Some(receiver);
+        self.in_flight.push_back(batch);
+    }
+
+    /// Hands the samples of finished batches to `read` with their step, oldest first.
+    pub fn collect(&mut self, device: &wgpu::Device, mut read: impl FnMut(u32, &[u8])) {
+        if self.in_flight.is_empty() {
+            return;
+        }
+        device.poll(wgpu::Maintain::Poll);
+        // batches finish in submission order
+        while let Some(batch) = self.in_flight.front() {
+            let result = match batch.receiver.as_ref().unwrap().try_recv() {
+                Ok(result) => result,
+                Err(std::sync::mpsc::TryRecvError::Empty) => break,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => Err(wgpu::BufferAsyncError),
+            };
+            let batch = self.in_flight.pop_front().unwrap();
+            if result.is_ok() {
+                let size = batch.steps.len() as u64 * self.sample_size;
+                let data = batch.buffer.slice(..size).get_mapped_range();
+                for (step, sample) in batch
+                    .steps
+                    .iter()
+                    .zip(data.chunks_exact(self.sample_size as usize))
+                {
+                    read(*step, sample);
+                }
+                drop(data);
+                batch.buffer.unmap();
+            }
+            self.free.push(batch.buffer);
+        }
+    }
+
+    /// Drops the batches still in flight, e.g. after a reset.
+    pub fn clear(&mut self) {
+        self.in_flight.clear();
+    }
+}
@@ -0,0 +1,171 @@
+use crate::probe;
+
+// of the window, the plot sits in the bottom right corner
+const WIDTH: f32 = 0.4;
+const HEIGHT: f32 = 0.25;
+const MARGIN: f32 = 10.0; // pixels
+const FRAME_COLOR: [f32; 4] = [0.5, 0.5, 0.5, 1.0];
+const ZERO_COLOR: [f32; 4] = [0.3, 0.3, 0.3, 1.0];
+const TRACE_COLOR: [f32; 4] = [1.0, 0.8, 0.0, 1.0];
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PlotVertex {
+    position: [f32; 2], // clip space
+    color: [f32; 4],
+}
+
+/// Where a prepared plot landed and the values its bottom and top edges stand for.
+pub struct PlotArea {
+    pub top_left: [f32; 2], // pixels
+    pub range: [f32; 2],
+}
+
+/// Line plot of a probe trace over the last `history` steps, drawn over the slice.
+pub struct TracePlot {
+    pipeline: wgpu::RenderPipeline,
+    vertices: wgpu::Buffer,
+    history: usize,
+    vertex_count: u32,
+}
+
+impl TracePlot {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, history: usize) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../shader/plot.wgsl"));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Plot"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Plot"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<PlotVertex>() as _,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x2,
+                        1 => Float32x4
+                    ],
+                }],
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+        // the frame, the zero line and a segment between each pair of samples
+        let capacity = 10 + 2 * history.max(1);
+        Self {
+            pipeline,
+            vertices: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Plot Vertices"),
+                size: (capacity * std::mem::size_of::<PlotVertex>()) as u64,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            history: history.max(1),
+            vertex_count: 0,
+        }
+    }
+
+    /// Lays out `trace` for a `target_size` window, with the y axis fit to its samples.
+    pub fn prepare(
+        &mut self,
+        queue: &wgpu::Queue,
+        trace: &probe::Trace,
+        target_size: [f32; 2],
+    ) -> PlotArea {
+        let [width, height] = target_size;
+        let size = [width * WIDTH, height * HEIGHT];
+        let top_left = [width - MARGIN - size[0], height - MARGIN - size[1]];
+        let to_clip = |x: f32, y: f32| {
+            [
+                (top_left[0] + x * size[0]) / width * 2.0 - 1.0,
+                1.0 - (top_left[1] + (1.0 - y) * size[1]) / height * 2.0,
+            ]
+        };
+
+        let (min, max) = trace.samples.iter().fold(
+            (f32::INFINITY, f32::NEG_INFINITY),
+            |(min, max), (_, value)| (min.min(*value), max.max(*value)),
+        );
+        let range = if min <= max {
+            // a flat trace still gets some height
+            let span = (max - min)
+                .max(max.abs().max(min.abs()) * 1e-3)
+                .max(f32::MIN_POSITIVE);
+            let padding = (span - (max - min)) / 2.0 + span * 0.05;
+            [min - padding, max + padding]
+        } else {
+            [-1.0, 1.0]
+        };
+
+        let line = |from: [f32; 2], to: [f32; 2], color| {
+            [
+                PlotVertex {
+                    position: to_clip(from[0], from[1]),
+                    color,
+                },
+                PlotVertex {
+                    position: to_clip(to[0], to[1]),
+                    color,
+                },
+            ]
+        };
+        let mut vertices = vec![];
+        for (from, to) in [
+            ([0.0, 0.0], [1.0, 0.0]),
+            ([1.0, 0.0], [1.0, 1.0]),
+            ([1.0, 1.0], [0.0, 1.0]),
+            ([0.0, 1.0], [0.0, 0.0]),
+        ] {
+            vertices.extend(line(from, to, FRAME_COLOR));
+        }
+        if range[0] < 0.0 && range[1] > 0.0 {
+            let zero = -range[0] / (range[1] - range[0]);
+            vertices.extend(line([0.0, zero], [1.0, zero], ZERO_COLOR));
+        }
+        // the newest sample sits at the right edge, older ones scroll to the left
+        if let Some(&(last_step, _)) = trace.samples.back() {
+            let span = (self.history - 1).max(1) as f32;
+            let point = |(step, value): (u32, f32)| {
+                [
+                    1.0 - (last_step - step) as f32 / span,
+                    (value - range[0]) / (range[1] - range[0]),
+                ]
+            };
+            for (from, to) in trace.samples.iter().zip(trace.samples.iter().skip(1)) {
+                vertices.extend(line(point(*from), point(*to), TRACE_COLOR));
+            }
+        }
+        queue.write_buffer(&self.vertices, 0, bytemuck::cast_slice(&vertices));
+        self.vertex_count = vertices.len() as u32;
+
+        PlotArea { top_left, range }
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.vertex_count == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.vertices.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
@@ -0,0 +1,596 @@
+//! Turns loaded settings into those a run uses: defaults filled in, times in ct and the axes
+//! of the dimension mode collapsed.
+
+use crate::{
+    export, export_crop, fdtd,
+    settings::{
+        Envelope, ExportFieldSettings, FDTDSettings, ModeSettings, ModelMaterial, Normalization,
+        TimingSettings, WavelengthReference,
+    },
+    units,
+};
+
+/// Collapses the axes the dimension mode drops to a single cell through the middle of the
+/// domain, and moves the volume sources, probes and default slice onto it. Collapsed axes wrap
+/// onto themselves instead of getting PML.
+fn flatten_settings(settings: &mut FDTDSettings) -> anyhow::Result<()> {
+    let dx = settings.spatial_step;
+    for &axis in settings.dimension_mode.collapsed_axes() {
+        let [low, high] = settings.domain[axis];
+        let middle = 0.5 * (low + high);
+        // exactly one cell, which fdtd::steps_across keeps at one whatever the rounding
+        settings.domain[axis] = [middle - 0.5 * dx, middle + 0.5 * dx];
+        if let fdtd::BoundaryCondition::PML { periodic, .. } = &mut settings.boundary {
+            periodic[axis] = true;
+        }
+        for source in settings.sources.iter_mut() {
+            match &source.mode {
+                ModeSettings::Volume { .. } => {
+                    source.position[axis] = middle;
+                    source.size[axis] = dx;
+                }
+                ModeSettings::PointCloud { normal, .. }
+                | ModeSettings::Texture { normal, .. }
+                | ModeSettings::GaussianBeam { normal, .. }
+                | ModeSettings::Port { normal, .. }
+                | ModeSettings::Replay { normal, .. } => anyhow::ensure!(
+                    normal.axis() != axis,
+                    "Mode source at {:?} is normal to {}, which the {:?} mode collapses",
+                    source.position,
+                    ["x", "y", "z"][axis],
+                    settings.dimension_mode
+                ),
+            }
+        }
+        for recording in settings.field_recordings.iter_mut() {
+            anyhow::ensure!(
+                recording.normal.axis() != axis,
+                "Field recording {} is normal to {}, which the {:?} mode collapses",
+                recording.file,
+                ["x", "y", "z"][axis],
+                settings.dimension_mode
+            );
+            recording.position[axis] = middle;
+        }
+        for probe in settings.probes.iter_mut() {
+            probe.position[axis] = middle;
+        }
+        for monitor in settings.mode_monitors.iter_mut() {
+            anyhow::ensure!(
+                monitor
+                    .mode
+                    .normal()
+                    .is_none_or(|normal| normal.axis() != axis),
+                "Mode monitor at {:?} is normal to {}, which the {:?} mode collapses",
+                monitor.position,
+                ["x", "y", "z"][axis],
+                settings.dimension_mode
+            );
+            monitor.position[axis] = middle;
+        }
+        for flux_box in settings.flux_boxes.iter_mut() {
+            flux_box.position[axis] = middle;
+        }
+        for region in settings
+            .exports
+            .iter_mut()
+            .filter_map(|export| export.region.as_mut())
+        {
+            (region.min[axis], region.max[axis]) =
+                (settings.domain[axis][0], settings.domain[axis][1]);
+        }
+    }
+    // a slice across the first collapsed axis shows everything there is
+    let axis = settings.dimension_mode.collapsed_axes()[0];
+    settings.default_slice.mode =
+        [fdtd::SliceMode::X, fdtd::SliceMode::Y, fdtd::SliceMode::Z][axis];
+    settings.default_slice.position = settings.domain[axis][0] + 0.5 * dx;
+    Ok(())
+}
+
+/// Turns the times of a preset, given in its time unit, into ct, the unit of length the light
+/// travels in that time. Wavelengths, and so frequencies as 1 / wavelength, stay lengths, but
+/// rates per unit of time such as the Gaussian bandwidth become rates per ct.
+fn times_to_ct(settings: &mut FDTDSettings, speed_of_light: f32) {
+    if let Some(temporal_step) = settings.temporal_step.as_mut() {
+        *temporal_step *= speed_of_light;
+    }
+    if let Some(end_time) = settings.end_time.as_mut() {
+        *end_time *= speed_of_light;
+    }
+    for timing in settings
+        .pause_at
+        .iter_mut()
+        .chain(settings.exports.iter_mut().map(|export| &mut export.timing))
+    {
+        if let TimingSettings::Time(time) | TimingSettings::EveryTime(time) = timing {
+            *time *= speed_of_light;
+        }
+    }
+    if let Some(resonance) = settings.resonance.as_mut() {
+        resonance.start_time *= speed_of_light;
+    }
+    if let Some(time_constant) = settings
+        .intensity
+        .as_mut()
+        .and_then(|intensity| intensity.time_constant.as_mut())
+    {
+        *time_constant *= speed_of_light;
+    }
+    for source in settings.sources.iter_mut() {
+        source.delay *= speed_of_light;
+        source.start_time *= speed_of_light;
+        if let Some(stop_time) = source.stop_time.as_mut() {
+            *stop_time *= speed_of_light;
+        }
+        source.ramp_time *= speed_of_light;
+        source.fwhm /= speed_of_light;
+        source.chirp_rate /= speed_of_light;
+    }
+}
+
+/// Checks a freshly loaded preset and fills in what derives from other fields, such as
+/// `temporal_step` from `courant_factor`.
+pub fn prepare_settings(settings: &mut FDTDSettings) -> anyhow::Result<()> {
+    if settings.dimension_mode != fdtd::DimensionMode::ThreeD {
+        flatten_settings(settings)?;
+    }
+    let background = settings.background.material();
+    anyhow::ensure!(
+        background.permittivity > 0.0 && background.permeability > 0.0,
+        "The background permittivity and permeability must be positive"
+    );
+    for model in settings.models.iter() {
+        anyhow::ensure!(
+            model.subpixel_samples > 0,
+            "Model {} has subpixel_samples = 0, use 1 to disable averaging",
+            model.path
+        );
+        if let Some(lattice) = model.repeat {
+            anyhow::ensure!(
+                lattice.count.iter().all(|count| *count > 0),
+                "Model {} repeats {:?} times, every count must be positive",
+                model.path,
+                lattice.count
+            );
+        }
+        for (name, material) in model.materials.iter() {
+            anyhow::ensure!(
+                material.material == ModelMaterial::Pec || material.refractive_index > 0.0,
+                "Material {} of model {} needs a positive refractive_index",
+                name,
+                model.path
+            );
+        }
+    }
+    for extrusion in settings.extrusions.iter() {
+        anyhow::ensure!(
+            extrusion.z_min < extrusion.z_max,
+            "Extrusion {} needs z_min below z_max, got {} and {}",
+            extrusion.path,
+            extrusion.z_min,
+            extrusion.z_max
+        );
+        anyhow::ensure!(
+            extrusion.subpixel_samples > 0,
+            "Extrusion {} has subpixel_samples = 0, use 1 to disable averaging",
+            extrusion.path
+        );
+        anyhow::ensure!(
+            extrusion.material == ModelMaterial::Pec || extrusion.refractive_index > 0.0,
+            "Extrusion {} needs a positive refractive_index",
+            extrusion.path
+        );
+    }
+    if settings.source_wavelengths == WavelengthReference::Background {
+        let index = settings.background.refractive_index();
+        for source in settings.sources.iter_mut() {
+            source.wavelength *= index;
+            for tone in source.tones.iter_mut() {
+                tone.wavelength *= index;
+            }
+            source.chirp_rate /= index;
+        }
+        for wavelengths in settings
+            .near_field
+            .iter_mut()
+            .map(|near_field| &mut near_field.wavelengths)
+            .chain(
+                settings
+                    .mode_monitors
+                    .iter_mut()
+                    .map(|monitor| &mut monitor.wavelengths),
+            )
+            .chain(
+                settings
+                    .flux_boxes
+                    .iter_mut()
+                    .map(|flux_box| &mut flux_box.wavelengths),
+            )
+        {
+            for wavelength in wavelengths.iter_mut() {
+                *wavelength *= index;
+            }
+        }
+    }
+
+    // waves are slowest in the densest medium but the limit is set by the fastest one
+    let min_refractive_index = settings
+        .models
+        .iter()
+        .flat_map(|model| {
+            model
+                .materials
+                .values()
+                .map(|material| (material.material, material.refractive_index))
+                .chain([(model.material, model.refractive_index)])
+        })
+        .chain(
+            settings
+                .extrusions
+                .iter()
+                .map(|extrusion| (extrusion.material, extrusion.refractive_index)),
+        )
+        .filter(|(material, _)| *material == ModelMaterial::Dielectric)
+        .map(|(_, refractive_index)| refractive_index)
+        .fold(settings.background.refractive_index(), f32::min);
+    let dimensions = settings.dimension_mode.dimensions();
+    // in the time unit of the preset, ct when it has no units
+    let speed_of_light = units::speed_of_light(settings.units.as_ref());
+    let courant_limit = settings.spatial_step * min_refractive_index
+        / (speed_of_light * (dimensions as f32).sqrt());
+    if let Some(temporal_step) = settings.temporal_step {
+        anyhow::ensure!(
+            temporal_step > 0.0,
+            "temporal_step must be positive, got {}",
+            temporal_step
+        );
+    }
+    if let Some(courant_factor) = settings.courant_factor {
+        anyhow::ensure!(
+            courant_factor > 0.0 && courant_factor <= 1.0,
+            "courant_factor must be in (0, 1], got {}",
+            courant_factor
+        );
+        if let Some(temporal_step) = settings.temporal_step {
+            anyhow::bail!(
+                "temporal_step = {} and courant_factor = {} are both set, remove one of them",
+                temporal_step,
+                courant_factor
+            );
+        }
+        settings.temporal_step = Some(courant_factor * courant_limit);
+        println!(
+            "Derived temporal_step = {} from courant_factor = {}",
+            settings.temporal_step(),
+            courant_factor
+        );
+    }
+    anyhow::ensure!(
+        settings.temporal_step.is_some(),
+        "Either temporal_step or courant_factor must be set"
+    );
+    anyhow::ensure!(
+        settings.temporal_step() <= courant_limit,
+        "temporal_step = {} exceeds the Courant limit dx * n_min / (c * sqrt({})) = {} (dx = {}, n_min = {}, c = {}), the simulation would diverge",
+        settings.temporal_step(),
+        dimensions,
+        courant_limit,
+        settings.spatial_step,
+        min_refractive_index,
+        speed_of_light
+    );
+    times_to_ct(settings, speed_of_light);
+
+    for timing in settings
+        .pause_at
+        .iter()
+        .chain(settings.exports.iter().map(|export| &export.timing))
+    {
+        match *timing {
+            TimingSettings::Every(every) => {
+                anyhow::ensure!(every > 0, "\"every\" timing needs a positive step count")
+            }
+            TimingSettings::EveryTime(every) => anyhow::ensure!(
+                every >= settings.temporal_step(),
+                "\"every_time\" timing of {} is shorter than a time step ({})",
+                every,
+                settings.temporal_step()
+            ),
+            TimingSettings::Step(..) | TimingSettings::Time(..) => (),
+        }
+    }
+    anyhow::ensure!(
+        settings.intensity.is_some()
+            || !settings
+                .exports
+                .iter()
+                .any(|export| matches!(export.quantity, export::ExportQuantity::Intensity)),
+        "Intensity exports need an \"intensity\" section"
+    );
+    anyhow::ensure!(
+        settings.dimension_mode == fdtd::DimensionMode::OneD
+            || !settings
+                .exports
+                .iter()
+                .any(|export| matches!(export.export, ExportFieldSettings::D1 { .. })),
+        "D1 exports need dimension_mode = \"1d\""
+    );
+    for export in settings.exports.iter() {
+        export_crop(settings, export)?;
+        anyhow::ensure!(
+            !export.unfold || (export.region.is_none() && export.stride == [1; 3]),
+            "Unfolded exports mirror the whole grid, they take no region or stride"
+        );
+        anyhow::ensure!(
+            export.precision == export::Precision::F32
+                || !matches!(export.export, ExportFieldSettings::D1 { .. }),
+            "D1 exports are written as CSV text, they take no precision"
+        );
+    }
+    if let Some(time_constant) = settings
+        .intensity
+        .as_ref()
+        .and_then(|intensity| intensity.time_constant)
+    {
+        anyhow::ensure!(
+            time_constant > 0.0,
+            "Intensity time_constant must be positive"
+        );
+    }
+    anyhow::ensure!(
+        settings
+            .steps_per_second_limit
+            .is_none_or(|limit| limit >= 0.0),
+        "steps_per_second_limit must not be negative, leave it out or set 0 for no limit"
+    );
+    anyhow::ensure!(
+        settings.probe_history > 1,
+        "probe_history must keep at least 2 steps"
+    );
+    if let Some(resonance) = &settings.resonance {
+        anyhow::ensure!(
+            resonance.zero_padding > 0 && (0.0..=1.0).contains(&resonance.threshold),
+            "The resonance analysis needs a positive zero_padding and a threshold from 0 to 1"
+        );
+        anyhow::ensure!(
+            resonance
+                .frequency_range
+                .is_none_or(|[low, high]| 0.0 <= low && low < high),
+            "The resonance frequency_range must run from a frequency of 0 or more to a higher one"
+        );
+    }
+    anyhow::ensure!(
+        settings
+            .energy
+            .as_ref()
+            .is_none_or(|energy| energy.every > 0),
+        "energy.every needs a positive step count"
+    );
+    if let Some(near_field) = &settings.near_field {
+        anyhow::ensure!(
+            settings.dimension_mode == fdtd::DimensionMode::ThreeD,
+            "The near-field box needs dimension_mode = \"3d\""
+        );
+        anyhow::ensure!(
+            settings.boundary.periodic_axes() == 0
+                && settings.boundary.symmetry() == fdtd::SymmetryFaces::default(),
+            "The near-field box needs a domain without periodic axes or symmetry planes, it sees only the simulated part of the radiation"
+        );
+        anyhow::ensure!(
+            !near_field.wavelengths.is_empty()
+                && near_field
+                    .wavelengths
+                    .iter()
+                    .all(|wavelength| *wavelength > 0.0),
+            "The near-field box needs one or more positive wavelengths"
+        );
+        anyhow::ensure!(
+            near_field.theta_count > 1 && near_field.phi_count > 0,
+            "The near-field box needs theta_count of at least 2 and a positive phi_count"
+        );
+    }
+    for (index, monitor) in settings.mode_monitors.iter().enumerate() {
+        anyhow::ensure!(
+            matches!(
+                monitor.mode,
+                ModeSettings::Texture { .. }
+                    | ModeSettings::GaussianBeam { .. }
+                    | ModeSettings::Port { .. }
+            ),
+            "Mode monitor {} needs a texture, gaussian_beam or port mode",
+            monitor.name(index)
+        );
+        anyhow::ensure!(
+            !monitor.wavelengths.is_empty()
+                && monitor
+                    .wavelengths
+                    .iter()
+                    .all(|wavelength| *wavelength > 0.0),
+            "Mode monitor {} needs one or more positive wavelengths",
+            monitor.name(index)
+        );
+    }
+    for (index, flux_box) in settings.flux_boxes.iter().enumerate() {
+        anyhow::ensure!(
+            flux_box
+                .wavelengths
+                .iter()
+                .all(|wavelength| *wavelength > 0.0),
+            "Flux box {} has a wavelength that is not positive",
+            flux_box.name(index)
+        );
+    }
+    for source in settings.sources.iter() {
+        anyhow::ensure!(
+            !source.active().is_empty(),
+            "Source at {:?} has stop_time = {} before start_time = {}, it would never be injected",
+            source.position,
+            source.active().end,
+            source.start_time
+        );
+        if matches!(source.mode, ModeSettings::Replay { .. }) {
+            anyhow::ensure!(
+                source.normalize.is_none() && source.waveform.is_none(),
+                "Replay source at {:?} plays its recording back as it is, it cannot be normalized or take a waveform",
+                source.position
+            );
+        }
+        for tone in source.tones() {
+            anyhow::ensure!(
+                tone.wavelength > 0.0,
+                "Source at {:?} has a tone of wavelength {}, wavelengths must be positive",
+                source.position,
+                tone.wavelength
+            );
+        }
+        match source.envelope {
+            Envelope::Gaussian => (),
+            Envelope::Cw => anyhow::ensure!(
+                source.ramp_time >= 0.0,
+                "Source at {:?} has a negative ramp_time",
+                source.position
+            ),
+            Envelope::RampedCw => anyhow::ensure!(
+                source.ramp_time > 0.0,
+                "Source at {:?} uses the ramped_cw envelope but has no positive ramp_time",
+                source.position
+            ),
+        }
+        match source.normalize {
+            None => (),
+            Some(Normalization::Energy) => anyhow::ensure!(
+                source.power > 0.0
+                    && (source.stop_time.is_some()
+                        || source.waveform.is_some()
+                        || matches!(source.envelope, Envelope::Gaussian) && source.fwhm > 0.0),
+                "Source at {:?} is normalized to its energy, which needs a positive power and a source that ends: a stop_time, a waveform or a gaussian envelope with a positive fwhm",
+                source.position
+            ),
+            Some(Normalization::Power) => anyhow::ensure!(
+                source.power > 0.0
+                    && source.waveform.is_none()
+                    && !matches!(source.envelope, Envelope::Gaussian),
+                "Source at {:?} is normalized to its power, which needs a positive power and a cw or ramped_cw envelope without a waveform",
+                source.position
+            ),
+        }
+    }
+
+    anyhow::ensure!(
+        settings.domain[0][1] > settings.domain[0][0],
+        "RHS of domain[0] is less or equal than LHS!"
+    );
+    anyhow::ensure!(
+        settings.domain[1][1] > settings.domain[1][0],
+        "RHS of domain[1] is less or equal than LHS!"
+    );
+    anyhow::ensure!(
+        settings.domain[2][1] > settings.domain[2][0],
+        "RHS of domain[2] is less or equal than LHS!"
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::source_envelope;
+
+    #[test]
+    fn collapsed_axes_hold_one_cell_centered_on_the_middle() {
+        let padding = [[0; 2]; 3];
+        for (middle, dx) in [(0.0, 0.05), (0.35, 0.1), (100.3, 0.01), (-7.7, 0.3)] {
+            let mut settings: FDTDSettings = serde_json::from_value(serde_json::json!({
+                "spatial_step": dx,
+                "temporal_step": 0.1 * dx,
+                "domain": [[-1.0, 1.0], [-1.0, 1.0], [middle - 2.5, middle + 2.5]],
+                "dimension_mode": "2d_tm",
+                "boundary": { "type": "PEC" },
+                "default_slice": { "field": "E", "mode": "Z", "position": 0 },
+                "default_scaling_factor": 1,
+                "pause_at": [],
+                "exports": [],
+                "models": [],
+                "sources": [],
+            }))
+            .unwrap();
+            flatten_settings(&mut settings).unwrap();
+            let grid = fdtd::grid_dimension(settings.domain, dx, &settings.boundary);
+            assert_eq!(grid[2], 1, "{} cells at {} with dx {}", grid[2], middle, dx);
+            let z = fdtd::world_to_grid(settings.domain, dx, padding, [0.0, 0.0, middle])[2];
+            assert!((z - 0.5).abs() < 1e-2, "middle at {} of the cell", z);
+            // where flatten_settings puts a volume source
+            let size = [0.0, 0.0, dx];
+            assert_eq!(
+                fdtd::box_cells(settings.domain, dx, padding, [0.0, 0.0, middle], size)[2],
+                0..1
+            );
+        }
+    }
+
+    #[test]
+    fn gaussian_envelopes_do_not_depend_on_the_units() {
+        let c = units::speed_of_light(Some(&units::Units {
+            length: units::LengthUnit::Nm,
+            time: units::TimeUnit::Fs,
+        }));
+        // a pulse of 0.05 / fs bandwidth peaking at 30 fs, stepped every 0.01 fs, given in fs
+        // or in ct, which is nm here
+        let prepared = |units: serde_json::Value, time: f32| {
+            let mut settings: FDTDSettings = serde_json::from_value(serde_json::json!({
+                "units": units,
+                "spatial_step": 10.0,
+                "temporal_step": 0.01 * time,
+                "domain": [[-100.0, 100.0], [-100.0, 100.0], [-100.0, 100.0]],
+                "boundary": { "type": "PEC" },
+                "default_slice": { "field": "E", "mode": "Z", "position": 0 },
+                "default_scaling_factor": 1,
+                "pause_at": [],
+                "exports": [],
+                "models": [],
+                "sources": [{
+                    "wavelength": 500.0,
+                    "position": [0, 0, 0],
+                    "size": [0, 0, 0],
+                    "mode": { "type": "volume", "settings": { "direction": [0, 0, 1], "field": "E" } },
+                    "phase": 0,
+                    "delay": 30.0 * time,
+                    "fwhm": 0.05 / time,
+                    "power": 1
+                }],
+            }))
+            .unwrap();
+            let speed_of_light = units::speed_of_light(settings.units.as_ref());
+            times_to_ct(&mut settings, speed_of_light);
+            settings
+        };
+        let with_units = prepared(serde_json::json!({ "length": "nm", "time": "fs" }), 1.0);
+        let in_ct = prepared(serde_json::Value::Null, c);
+        assert_eq!(with_units.temporal_step(), in_ct.temporal_step());
+        let envelope = |settings: &FDTDSettings, step: u32| {
+            let source = &settings.sources[0];
+            source_envelope(
+                source.envelope,
+                source.fwhm,
+                source.delay,
+                0.0,
+                0.0,
+                step as f32 * settings.temporal_step(),
+            )
+        };
+        // the pulse is some 20 fs wide around step 3000
+        assert!(envelope(&with_units, 3000) > 0.99 && envelope(&with_units, 1500) < 0.5);
+        for step in (0..6000).step_by(100) {
+            let (with_units, in_ct) = (envelope(&with_units, step), envelope(&in_ct, step));
+            assert!(
+                (with_units - in_ct).abs() < 1e-5,
+                "{} with units, {} in ct at step {}",
+                with_units,
+                in_ct,
+                step
+            );
+        }
+    }
+}
@@ -0,0 +1,287 @@
+//! Turns preset files into settings, refusing keys the settings have no use for.
+
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::settings::FDTDSettings;
+
+// keys the settings also accept under another name, as their `alias` attributes say
+const ALIASES: &[(&str, &str)] = &[("courant", "courant_factor"), ("plane", "normal")];
+
+// keys of the files a preset reads, as listed by `preset_files`, `[]` stands for every entry
+// of an array
+const FILE_KEYS: &[&str] = &[
+    "default_shader",
+    "models[].path",
+    "extrusions[].path",
+    "material_clouds[].path",
+    "sources[].waveform",
+    "initial_fields.electric[]",
+    "initial_fields.magnetic[]",
+];
+
+// tables holding mode settings, and the keys of the files modes read
+const MODE_KEYS: &[&str] = &["sources[].mode.settings", "mode_monitors[].mode.settings"];
+const MODE_FILE_KEYS: &[&str] = &["file", "ex", "ey", "ez", "hx", "hy", "hz"];
+
+/// A preset built from layered files.
+pub struct Preset {
+    pub config: config::Config,
+    /// Every file read, the files a file includes before it, each one layered over the ones
+    /// before it.
+    pub files: Vec<PathBuf>,
+}
+
+/// Builds the preset of `paths`, each one layered over the ones before it. A file can name
+/// files to layer under itself with `include = ["base.toml"]`, relative to its directory.
+/// The files a layer reads are relative to its directory as well.
+pub fn load(paths: &[String]) -> anyhow::Result<Preset> {
+    let mut preset = Value::Object(Default::default());
+    let mut files = vec![];
+    for path in paths {
+        merge(
+            &mut preset,
+            read_layer(Path::new(path), &mut vec![], &mut files)?,
+        );
+    }
+    // as JSON, serializing the value into the config drops empty arrays
+    let config = config::Config::builder()
+        .add_source(config::File::from_str(
+            &preset.to_string(),
+            config::FileFormat::Json,
+        ))
+        .build()?;
+    Ok(Preset { config, files })
+}
+
+/// Reads the preset file at `path` over the files it includes, adding each file read to
+/// `files`. `including` holds the files whose includes led here, one of them coming back is
+/// an include cycle.
+fn read_layer(
+    path: &Path,
+    including: &mut Vec<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> anyhow::Result<Value> {
+    let canonical = std::fs::canonicalize(path).unwrap_or(path.to_path_buf());
+    anyhow::ensure!(
+        !including.contains(&canonical),
+        "Preset {} includes itself",
+        path.display()
+    );
+    let mut layer: Value = config::Config::builder()
+        .add_source(config::File::with_name(&path.to_string_lossy()))
+        .build()?
+        .try_deserialize()?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+    for key in FILE_KEYS
+        .iter()
+        .map(|key| key.to_string())
+        .chain(MODE_KEYS.iter().flat_map(|mode| {
+            MODE_FILE_KEYS
+                .iter()
+                .map(move |file| format!("{}.{}", mode, file))
+        }))
+    {
+        resolve_files(&mut layer, &key, directory);
+    }
+    let includes = match layer
+        .as_object_mut()
+        .and_then(|layer| layer.remove("include"))
+    {
+        None => vec![],
+        Some(Value::Array(includes)) => includes,
+        Some(include) => vec![include],
+    };
+    // kept as it is, so that its appends extend the layers under it
+    if includes.is_empty() {
+        files.push(path.to_path_buf());
+        return Ok(layer);
+    }
+
+    including.push(canonical);
+    let mut preset = Value::Object(Default::default());
+    for include in includes {
+        let Value::String(include) = include else {
+            anyhow::bail!(
+                "Preset {}: include takes file names, got {}",
+                path.display(),
+                include
+            );
+        };
+        let include = directory.join(include);
+        merge(&mut preset, read_layer(&include, including, files)?);
+    }
+    including.pop();
+    files.push(path.to_path_buf());
+    merge(&mut preset, layer);
+    Ok(preset)
+}
+
+/// Joins the relative paths under `key` of a layer, a key of `FILE_KEYS`, to `directory`,
+/// the one of the layer. Missing keys are left alone.
+fn resolve_files(layer: &mut Value, key: &str, directory: &Path) {
+    let (segment, rest) = key.split_once('.').unwrap_or((key, ""));
+    let (name, each) = match segment.strip_suffix("[]") {
+        Some(name) => (name, true),
+        None => (segment, false),
+    };
+    let Some(value) = layer.get_mut(name) else {
+        return;
+    };
+    let entries = match value {
+        // the entries of an append table are under `append`
+        Value::Object(table) if each => match table.get_mut("append") {
+            Some(Value::Array(entries)) => entries.iter_mut().collect(),
+            _ => vec![],
+        },
+        Value::Array(entries) if each => entries.iter_mut().collect(),
+        value => vec![value],
+    };
+    for entry in entries {
+        match entry {
+            Value::String(path) if rest.is_empty() => {
+                *path = directory.join(&*path).to_string_lossy().into_owned();
+            }
+            entry => resolve_files(entry, rest, directory),
+        }
+    }
+}
+
+/// Layers `layer` over `base`. Tables merge key by key and anything else replaces what it
+/// overrides, arrays included, as with the sources of a config. A table of just an `append`
+/// array, as in `pause_at = { append = [...] }`, extends the array it overrides instead.
+fn merge(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (base, Value::Object(mut layer))
+            if layer.len() == 1 && layer.get("append").is_some_and(Value::is_array) =>
+        {
+            match (base, layer.remove("append").unwrap()) {
+                (Value::Array(array), Value::Array(items)) => array.extend(items),
+                (base, items) => *base = items,
+            }
+        }
+        (Value::Object(base), Value::Object(layer)) => {
+            for (key, value) in layer {
+                merge(base.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        // a table over something else starts from an empty one, so appends inside it apply
+        (base, Value::Object(layer)) => {
+            *base = Value::Object(Default::default());
+            merge(base, Value::Object(layer));
+        }
+        (base, layer) => *base = layer,
+    }
+}
+
+/// Deserializes the settings of a built preset. Keys the settings do not read are an error
+/// naming each of them with the known key it most likely misspells. They are found by
+/// serializing the settings back and comparing the keys, which also covers the tagged and
+/// untagged enums, where `deny_unknown_fields` does not work.
+pub fn deserialize(config: config::Config) -> anyhow::Result<FDTDSettings> {
+    let raw: Value = config.clone().try_deserialize()?;
+    let settings: FDTDSettings = config
+        .try_deserialize()
+        .map_err(|err| with_typo_hint(err, &raw))?;
+    let used = serde_json::to_value(&settings)?;
+    let mut unknown = vec![];
+    unknown_keys(&raw, &used, "", &mut unknown);
+    anyhow::ensure!(
+        unknown.is_empty(),
+        "The preset has keys that are not settings:\n  {}",
+        unknown.join("\n  ")
+    );
+    Ok(settings)
+}
+
+fn unknown_keys(raw: &Value, used: &Value, path: &str, unknown: &mut Vec<String>) {
+    match (raw, used) {
+        (Value::Object(raw), Value::Object(used)) => {
+            for (key, value) in raw {
+                let field = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                let known = used.get(key).or_else(|| {
+                    ALIASES
+                        .iter()
+                        .find(|(alias, _)| alias == key)
+                        .and_then(|(_, name)| used.get(*name))
+                });
+                match known {
+                    Some(used) => unknown_keys(value, used, &field, unknown),
+                    None => unknown.push(match closest(key, used.keys().map(String::as_str)) {
+                        Some(name) => format!("{}, did you mean {}?", field, name),
+                        None => field,
+                    }),
+                }
+            }
+        }
+        (Value::Array(raw), Value::Array(used)) => {
+            for (index, (raw, used)) in raw.iter().zip(used).enumerate() {
+                unknown_keys(raw, used, &format!("{}[{}]", path, index), unknown);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Whether `key` is close enough to `name` to be a typo of it.
+fn is_typo(key: &str, name: &str) -> bool {
+    strsim::levenshtein(key, name) <= (name.len() / 3).max(1)
+}
+
+/// The one of `names` that `key` most likely misspells.
+fn closest<'a>(key: &str, names: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    names
+        .filter(|name| is_typo(key, name))
+        .min_by_key(|name| strsim::levenshtein(key, name))
+}
+
+/// Points a missing field error at keys of the preset that look like typos of the field.
+fn with_typo_hint(err: config::ConfigError, raw: &Value) -> anyhow::Error {
+    let message = err.to_string();
+    let Some(field) = message
+        .split("missing field `")
+        .nth(1)
+        .and_then(|rest| rest.split('`').next())
+    else {
+        return err.into();
+    };
+    let mut keys = vec![];
+    all_keys(raw, "", &mut keys);
+    let typos: Vec<_> = keys
+        .iter()
+        .filter(|(_, key)| key != field && is_typo(key, field))
+        .map(|(path, _)| path.as_str())
+        .collect();
+    if typos.is_empty() {
+        return err.into();
+    }
+    anyhow::anyhow!("{}, is {} a typo of it?", message, typos.join(" or "))
+}
+
+/// Every key of `value` with its path.
+fn all_keys(value: &Value, path: &str, keys: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                let field = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                all_keys(value, &field, keys);
+                keys.push((field, key.clone()));
+            }
+        }
+        Value::Array(array) => {
+            for (index, value) in array.iter().enumerate() {
+                all_keys(value, &format!("{}[{}]", path, index), keys);
+            }
+        }
+        _ => (),
+    }
+}
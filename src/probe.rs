@@ -0,0 +1,210 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::fdtd;
+
+// of the Rgba32Float field textures
+const TEXEL_SIZE: u64 = 16;
+
+/// A grid cell whose field is sampled after every step.
+pub struct Probe {
+    pub name: String,
+    pub texel: [u32; 3],
+    pub field: fdtd::FieldType,
+    pub component: Option<fdtd::Component>, // magnitude when unset
+}
+
+impl Probe {
+    fn value(&self, texel: [f32; 4]) -> f32 {
+        match self.component {
+            Some(component) => texel[component.axis()],
+            None => (texel[0] * texel[0] + texel[1] * texel[1] + texel[2] * texel[2]).sqrt(),
+        }
+    }
+}
+
+/// The latest samples of a probe as `(step, value)`, oldest first.
+pub struct Trace {
+    pub name: String,
+    pub samples: VecDeque<(u32, f32)>,
+}
+
+/// Traces shared between the simulation thread, which fills them, and the window.
+pub type Traces = Arc<Mutex<Vec<Trace>>>;
+
+struct Batch {
+    buffer: wgpu::Buffer,
+    steps: Vec<u32>,
+    receiver: Option<std::sync::mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>>,
+}
+
+/// Samples the probes after every step without stalling the simulation. The texels of a batch
+/// of steps are copied into one buffer, which is mapped once the batch was submitted and read
+/// into the traces on a later poll.
+pub struct ProbeRecorder {
+    probes: Vec<Probe>,
+    history: usize, // samples kept per trace
+    recording: Option<Batch>,
+    in_flight: VecDeque<Batch>,
+    free: Vec<wgpu::Buffer>,
+    traces: Traces,
+}
+
+impl ProbeRecorder {
+    pub fn new(probes: Vec<Probe>, history: usize) -> Self {
+        let traces = probes
+            .iter()
+            .map(|probe| Trace {
+                name: probe.name.clone(),
+                samples: VecDeque::with_capacity(history),
+            })
+            .collect();
+        Self {
+            probes,
+            history,
+            recording: None,
+            in_flight: VecDeque::new(),
+            free: vec![],
+            traces: Arc::new(Mutex::new(traces)),
+        }
+    }
+
+    pub fn traces(&self) -> Traces {
+        self.traces.clone()
+    }
+
+    /// Starts a batch of at most `steps` steps.
+    pub fn begin(&mut self, device: &wgpu::Device, steps: u32) {
+        if self.probes.is_empty() || steps == 0 {
+            return;
+        }
+        let size = steps as u64 * self.probes.len() as u64 * TEXEL_SIZE;
+        let buffer = match self.free.iter().position(|buffer| buffer.size() >= size) {
+            Some(index) => self.free.swap_remove(index),
+            None => device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Probe Readback"),
+                size,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+        };
+        self.recording = Some(Batch {
+            buffer,
+            steps: Vec::with_capacity(steps as usize),
+            receiver: None,
+        });
+    }
+
+    /// Records copies of the probed texels after `step` into `encoder`.
+    pub fn sample(&mut self, fdtd: &fdtd::FDTD, encoder: &mut wgpu::CommandEncoder, step: u32) {
+        let Some(batch) = &mut self.recording else {
+            return;
+        };
+        let stride = self.probes.len() as u64 * TEXEL_SIZE;
+        if (batch.steps.len() as u64 + 1) * stride > batch.buffer.size() {
+            return;
+        }
+        for (index, probe) in self.probes.iter().enumerate() {
+            let texture = match probe.field {
+                fdtd::FieldType::E => fdtd.get_electric_field_texture(),
+                fdtd::FieldType::H => fdtd.get_magnetic_field_texture(),
+            };
+            encoder.copy_texture_to_buffer(
+                wgpu::ImageCopyTexture {
+                    texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: probe.texel[0],
+                        y: probe.texel[1],
+                        z: probe.texel[2],
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::ImageCopyBuffer {
+                    buffer: &batch.buffer,
+                    layout: wgpu::ImageDataLayout {
+                        offset: batch.steps.len() as u64 * stride + index as u64 * TEXEL_SIZE,
+                        bytes_per_row: None,
+                        rows_per_image: None,
+                    },
+                },
+                wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        batch.steps.push(step);
+    }
+
+    /// Starts mapping the batch recorded since `begin`, once its encoder was submitted.
+    pub fn map_pending(&mut self) {
+        let Some(mut batch) = self.recording.take() else {
+            return;
+        };
+        if batch.steps.is_empty() {
+            self.free.push(batch.buffer);
+            return;
+        }
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let size = batch.steps.len() as u64 * self.probes.len() as u64 * TEXEL_SIZE;
+        batch
+            .buffer
+            .slice(..size)
+            .map_async(wgpu::MapMode::Read, move |v| {
+                let _ = sender.send(v);
+            });
+        batch.receiver = Some(receiver);
+        self.in_flight.push_back(batch);
+    }
+
+    /// Appends the samples of finished batches to the traces, returns whether there were any.
+    pub fn collect(&mut self, device: &wgpu::Device) -> bool {
+        if self.in_flight.is_empty() {
+            return false;
+        }
+        device.poll(wgpu::Maintain::Poll);
+        let mut collected = false;
+        // batches finish in submission order
+        while let Some(batch) = self.in_flight.front() {
+            let result = match batch.receiver.as_ref().unwrap().try_recv() {
+                Ok(result) => result,
+                Err(std::sync::mpsc::TryRecvError::Empty) => break,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => Err(wgpu::BufferAsyncError),
+            };
+            let batch = self.in_flight.pop_front().unwrap();
+            if result.is_ok() {
+                let size = batch.steps.len() as u64 * self.probes.len() as u64 * TEXEL_SIZE;
+                let data = batch.buffer.slice(..size).get_mapped_range();
+                let texels: &[[f32; 4]] = bytemuck::cast_slice(&data);
+                let mut traces = self.traces.lock().unwrap();
+                for (step, row) in batch
+                    .steps
+                    .iter()
+                    .zip(texels.chunks_exact(self.probes.len()))
+                {
+                    for ((trace, probe), texel) in traces.iter_mut().zip(&self.probes).zip(row) {
+                        if trace.samples.len() == self.history {
+                            trace.samples.pop_front();
+                        }
+                        trace.samples.push_back((*step, probe.value(*texel)));
+                    }
+                }
+                drop(data);
+                batch.buffer.unmap();
+                collected = true;
+            }
+            self.free.push(batch.buffer);
+        }
+        collected
+    }
+
+    /// Empties the traces, e.g. after a reset. Batches still in flight are dropped.
+    pub fn clear(&mut self) {
+        self.in_flight.clear();
+        for trace in self.traces.lock().unwrap().iter_mut() {
+            trace.samples.clear();
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::fdtd;
+
+type Request = ([u32; 3], fdtd::ViewMode);
+
+/// Reads back the texel under the cursor without stalling the render loop:
+/// the copy is recorded into the frame encoder and mapped once the frame is submitted,
+/// the result is picked up on a later redraw.
+pub struct TexelReadout {
+    buffer: wgpu::Buffer,
+    in_flight: Option<Request>,
+    receiver: Option<std::sync::mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>>,
+    last: Option<(Request, [f32; 3])>,
+    stale: bool,
+}
+
+impl TexelReadout {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Texel Readout"),
+                size: 16,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            in_flight: None,
+            receiver: None,
+            last: None,
+            stale: true,
+        }
+    }
+
+    /// Marks the last value as outdated, e.g. after a simulation step.
+    pub fn invalidate(&mut self) {
+        self.stale = true;
+    }
+
+    /// Collects a finished readback, if any.
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        let Some(receiver) = &self.receiver else {
+            return;
+        };
+        device.poll(wgpu::Maintain::Poll);
+        match receiver.try_recv() {
+            Ok(result) => {
+                if result.is_ok() {
+                    let data = self.buffer.slice(..).get_mapped_range();
+                    let values: [f32; 4] = bytemuck::pod_read_unaligned(&data);
+                    drop(data);
+                    self.last = Some((self.in_flight.unwrap(), [values[0], values[1], values[2]]));
+                }
+                self.buffer.unmap();
+                self.receiver = None;
+                self.in_flight = None;
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => (),
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                self.receiver = None;
+                self.in_flight = None;
+            }
+        }
+    }
+
+    /// Records a copy of `texel` into `encoder` when the cached value is outdated.
+    /// `map` must be called once the encoder has been submitted.
+    pub fn request(
+        &mut self,
+        fdtd: &fdtd::FDTD,
+        encoder: &mut wgpu::CommandEncoder,
+        texel: Option<[u32; 3]>,
+    ) {
+        let Some(texel) = texel else {
+            return;
+        };
+        if self.in_flight.is_some() {
+            return;
+        }
+        let request = (texel, fdtd.get_view_mode());
+        if !self.stale && matches!(self.last, Some((last, _)) if last == request) {
+            return;
+        }
+        fdtd.copy_texel(encoder, texel, &self.buffer);
+        self.in_flight = Some(request);
+        self.stale = false;
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.in_flight.is_some()
+    }
+
+    /// Starts mapping the buffer after the copy recorded by `request` was submitted.
+    pub fn map(&mut self) {
+        if self.in_flight.is_none() || self.receiver.is_some() {
+            return;
+        }
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |v| {
+                let _ = sender.send(v);
+            });
+        self.receiver = Some(receiver);
+    }
+
+    /// Formats the last known value if it belongs to `texel`.
+    pub fn describe(&self, texel: Option<[u32; 3]>) -> Option<String> {
+        let ((last_texel, mode), values) = self.last?;
+        if Some(last_texel) != texel {
+            return None;
+        }
+        let [i, j, k] = last_texel;
+        Some(match mode {
+            fdtd::ViewMode::Material if values[0].is_infinite() => format!("({i},{j},{k}) = PEC"),
+            fdtd::ViewMode::Material => format!("({i},{j},{k}) = {:.3}", values[0]),
+            fdtd::ViewMode::Intensity => format!("({i},{j},{k}) = {:.3e}", values[0]),
+            fdtd::ViewMode::E | fdtd::ViewMode::H => format!(
+                "({i},{j},{k}) = ({:.3e}, {:.3e}, {:.3e})",
+                values[0], values[1], values[2]
+            ),
+        })
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{fdtd, screenshot};
+
+const STAGING_BUFFERS: usize = 3;
+
+struct Staging {
+    buffer: wgpu::Buffer,
+    frame: Option<u32>,
+    receiver: Option<std::sync::mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>>,
+}
+
+/// Captures the slice view into numbered PNGs at a fixed resolution.
+/// Frames are rendered offscreen into the frame encoder and read back through a small pool of
+/// staging buffers, encoding happens on a writer thread.
+pub struct Recorder {
+    view: wgpu::TextureView,
+    texture: wgpu::Texture,
+    size: [u32; 2],
+    padded_bytes_per_row: u32,
+    unpadded_bytes_per_row: u32,
+    staging: Vec<Staging>,
+    directory: std::path::PathBuf,
+    next_frame: u32,
+    sender: std::sync::mpsc::Sender<(std::path::PathBuf, Vec<u8>)>,
+    writer: std::thread::JoinHandle<()>,
+}
+
+impl Recorder {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        size: [u32; 2],
+        directory: std::path::PathBuf,
+    ) -> anyhow::Result<Self> {
+        let swap_red_blue = screenshot::needs_red_blue_swap(format)?;
+        std::fs::create_dir_all(&directory)?;
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Recording"),
+            size: wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let unpadded_bytes_per_row = size[0] * 4;
+        let padded_bytes_per_row_padding = (wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
+            - unpadded_bytes_per_row % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row + padded_bytes_per_row_padding;
+
+        let staging = (0..STAGING_BUFFERS)
+            .map(|_| Staging {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Recording Staging"),
+                    size: (padded_bytes_per_row * size[1]) as u64,
+                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                }),
+                frame: None,
+                receiver: None,
+            })
+            .collect();
+
+        let (sender, receiver) = std::sync::mpsc::channel::<(std::path::PathBuf, Vec<u8>)>();
+        let writer = std::thread::spawn(move || {
+            for (path, pixels) in receiver {
+                if let Err(err) = screenshot::write_png(&path, size, pixels, swap_red_blue) {
+                    eprintln!("Failed to write frame {}: {}", path.display(), err);
+                }
+            }
+        });
+
+        Ok(Self {
+            view,
+            texture,
+            size,
+            padded_bytes_per_row,
+            unpadded_bytes_per_row,
+            staging,
+            directory,
+            next_frame: 0,
+            sender,
+            writer,
+        })
+    }
+
+    /// Renders the current slice and records its readback into `encoder`.
+    /// Blocks only if every staging buffer is still waiting for the GPU.
+    pub fn capture(
+        &mut self,
+        device: &wgpu::Device,
+        fdtd: &fdtd::FDTD,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        if self.staging.iter().all(|staging| staging.frame.is_some()) {
+            self.collect(device, true);
+        }
+        let Some(staging) = self
+            .staging
+            .iter_mut()
+            .find(|staging| staging.frame.is_none())
+        else {
+            return;
+        };
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            fdtd.visualize(&mut render_pass, self.size);
+        }
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &staging.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: self.size[0],
+                height: self.size[1],
+                depth_or_array_layers: 1,
+            },
+        );
+        staging.frame = Some(self.next_frame);
+        self.next_frame += 1;
+    }
+
+    /// Starts mapping the buffers filled by `capture`, must be called after the encoder was submitted.
+    pub fn map_pending(&mut self) {
+        for staging in self
+            .staging
+            .iter_mut()
+            .filter(|staging| staging.frame.is_some() && staging.receiver.is_none())
+        {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            staging
+                .buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |v| {
+                    let _ = sender.send(v);
+                });
+            staging.receiver = Some(receiver);
+        }
+    }
+
+    /// Hands finished readbacks to the writer thread.
+    pub fn collect(&mut self, device: &wgpu::Device, wait: bool) {
+        if self
+            .staging
+            .iter()
+            .all(|staging| staging.receiver.is_none())
+        {
+            return;
+        }
+        device.poll(if wait {
+            wgpu::Maintain::Wait
+        } else {
+            wgpu::Maintain::Poll
+        });
+        for staging in self.staging.iter_mut() {
+            let Some(receiver) = &staging.receiver else {
+                continue;
+            };
+            let result = match receiver.try_recv() {
+                Ok(result) => result,
+                Err(std::sync::mpsc::TryRecvError::Empty) => continue,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => Err(wgpu::BufferAsyncError),
+            };
+            let frame = staging.frame.take().unwrap();
+            staging.receiver = None;
+            if result.is_err() {
+                eprintln!("Failed to read back frame {}", frame);
+                continue;
+            }
+            let pixels: Vec<u8> = staging
+                .buffer
+                .slice(..)
+                .get_mapped_range()
+                .chunks(self.padded_bytes_per_row as usize)
+                .flat_map(|row| &row[..self.unpadded_bytes_per_row as usize])
+                .cloned()
+                .collect();
+            staging.buffer.unmap();
+            let _ = self
+                .sender
+                .send((self.directory.join(format!("{:05}.png", frame)), pixels));
+        }
+    }
+
+    /// Flushes every outstanding frame to disk.
+    pub fn finish(mut self, device: &wgpu::Device) {
+        self.map_pending();
+        self.collect(device, true);
+        drop(self.sender);
+        let _ = self.writer.join();
+        println!(
+            "Recorded {} frames to {}",
+            self.next_frame,
+            self.directory.display()
+        );
+    }
+}
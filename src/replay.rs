@@ -0,0 +1,496 @@
+//! Records the tangential fields on a plane every step and plays such a recording back as a
+//! source, so that a simulation can be split into stages at a plane.
+//!
+//! A recording is a header followed by one record per step, both in native byte order. A
+//! record holds E along the two plane axes, then H along them, as four f32 per interior cell
+//! of the plane with the first axis running fastest.
+
+use std::io::{Read, Seek, Write};
+use std::sync::{mpsc, Arc, Mutex};
+
+use wgpu::util::DeviceExt;
+
+use crate::{fdtd, periodic};
+
+const MAGIC: [u8; 8] = *b"GREMSRPL";
+const VERSION: u32 = 1;
+// records handed ahead by the loader thread of a replay
+const READ_AHEAD: usize = 16;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Header {
+    magic: [u8; 8],
+    version: u32,
+    normal: u32, // grid axis, 0 = x, 1 = y, 2 = z
+    size: [u32; 2],
+    spatial_step: f32,
+    temporal_step: f32,
+    first_step: u32, // the step of the first record
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PlaneParam {
+    low: [u32; 3],
+    normal: u32,
+    size: [u32; 2],
+    _padding: [u32; 2],
+}
+
+/// The interior cells of a grid plane.
+#[derive(Clone, Copy, Debug)]
+pub struct Plane {
+    pub normal: fdtd::SliceMode,
+    pub low: [u32; 3],  // first texel, boundary layers included
+    pub size: [u32; 2], // cells along the two other axes, in x, y, z order
+}
+
+impl Plane {
+    fn cells(&self) -> u32 {
+        self.size[0] * self.size[1]
+    }
+
+    fn record_size(&self) -> u64 {
+        self.cells() as u64 * 16
+    }
+}
+
+enum Message {
+    Record(u32, Vec<u8>),
+    Restart, // the run went back to its initial state
+}
+
+/// Copies the tangential fields on a plane after every step and appends them to a file. The
+/// copies are read back in batches, writing happens on a thread of its own.
+pub struct PlaneRecorder {
+    plane: Plane,
+    path: std::path::PathBuf,
+    tangential: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::ComputePipeline,
+    readback: periodic::PeriodicReadback,
+    records: u32,
+    sender: mpsc::Sender<Message>,
+    writer: std::thread::JoinHandle<()>,
+}
+
+impl PlaneRecorder {
+    pub fn new(
+        device: &wgpu::Device,
+        fdtd: &fdtd::FDTD,
+        plane: Plane,
+        path: std::path::PathBuf,
+        spatial_step: f32,
+        temporal_step: f32,
+    ) -> Self {
+        let tangential = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Field Recording"),
+            size: plane.record_size(),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let param = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Field Recording Plane"),
+            contents: bytemuck::bytes_of(&PlaneParam {
+                low: plane.low,
+                normal: plane.normal.axis() as u32,
+                size: plane.size,
+                _padding: [0; 2],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let shader_module =
+            device.create_shader_module(wgpu::include_wgsl!("../shader/fdtd/plane.wgsl"));
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Field Recording"),
+            layout: None,
+            module: &shader_module,
+            entry_point: "record",
+        });
+        let electric_field_view = fdtd
+            .get_electric_field_texture()
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let magnetic_field_view = fdtd
+            .get_magnetic_field_texture()
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Field Recording"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&electric_field_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&magnetic_field_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: param.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: tangential.as_entire_binding(),
+                },
+            ],
+        });
+
+        let header = Header {
+            magic: MAGIC,
+            version: VERSION,
+            normal: plane.normal.axis() as u32,
+            size: plane.size,
+            spatial_step,
+            temporal_step,
+            first_step: 0,
+        };
+        let (sender, receiver) = mpsc::channel();
+        let writer = {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                let mut file = None;
+                for message in receiver {
+                    match message {
+                        Message::Record(step, record) => {
+                            if let Err(err) = append(&mut file, &path, header, step, &record) {
+                                eprintln!(
+                                    "Failed to record step {} to {}: {}",
+                                    step,
+                                    path.display(),
+                                    err
+                                );
+                            }
+                        }
+                        Message::Restart => file = None,
+                    }
+                }
+                if let Some((mut file, _)) = file {
+                    if let Err(err) = file.flush() {
+                        eprintln!("Failed to write {}: {}", path.display(), err);
+                    }
+                }
+            })
+        };
+
+        Self {
+            plane,
+            path,
+            tangential,
+            bind_group,
+            pipeline,
+            readback: periodic::PeriodicReadback::new(
+                "Field Recording Readback",
+                1,
+                plane.record_size(),
+            ),
+            records: 0,
+            sender,
+            writer,
+        }
+    }
+
+    /// Starts a batch of at most `steps` steps following `step`.
+    pub fn begin(&mut self, device: &wgpu::Device, step: u32, steps: u32) {
+        self.readback.begin(device, step, steps);
+    }
+
+    /// Copies the fields after `step` updates.
+    pub fn record(&mut self, encoder: &mut wgpu::CommandEncoder, step: u32) {
+        let Some((buffer, offset)) = self.readback.sample(step) else {
+            return;
+        };
+        {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Field Recording"),
+                timestamp_writes: None,
+            });
+            cpass.set_pipeline(&self.pipeline);
+            cpass.set_bind_group(0, &self.bind_group, &[]);
+            cpass.dispatch_workgroups(
+                self.plane.size[0].div_ceil(8),
+                self.plane.size[1].div_ceil(8),
+                1,
+            );
+        }
+        encoder.copy_buffer_to_buffer(
+            &self.tangential,
+            0,
+            buffer,
+            offset,
+            self.plane.record_size(),
+        );
+    }
+
+    /// Starts mapping the batch recorded since `begin`, once its encoder was submitted.
+    pub fn map_pending(&mut self) {
+        self.readback.map_pending();
+    }
+
+    /// Hands the finished records to the writer thread.
+    pub fn collect(&mut self, device: &wgpu::Device) {
+        let (sender, records) = (&self.sender, &mut self.records);
+        self.readback.collect(device, |step, record| {
+            *records += 1;
+            let _ = sender.send(Message::Record(step, record.to_vec()));
+        });
+    }
+
+    /// Starts the file over, e.g. after a reset.
+    pub fn restart(&mut self) {
+        self.readback.clear();
+        self.records = 0;
+        let _ = self.sender.send(Message::Restart);
+    }
+
+    /// Blocks until every record is on disk.
+    pub fn finish(mut self, device: &wgpu::Device) {
+        self.readback.map_pending();
+        device.poll(wgpu::Maintain::Wait);
+        self.collect(device);
+        drop(self.sender);
+        let _ = self.writer.join();
+        println!(
+            "Recorded {} steps of the fields on a plane to {}",
+            self.records,
+            self.path.display()
+        );
+    }
+}
+
+/// Appends the record of `step`, starting the file with its header at the first record. Steps
+/// whose readback failed are left zero.
+fn append(
+    file: &mut Option<(std::io::BufWriter<std::fs::File>, u32)>,
+    path: &std::path::Path,
+    header: Header,
+    step: u32,
+    record: &[u8],
+) -> anyhow::Result<()> {
+    let (writer, next) = match file {
+        Some(file) => file,
+        None => {
+            let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+            writer.write_all(bytemuck::bytes_of(&Header {
+                first_step: step,
+                ..header
+            }))?;
+            file.insert((writer, step))
+        }
+    };
+    anyhow::ensure!(
+        step >= *next,
+        "step {} comes after step {}",
+        step,
+        *next - 1
+    );
+    for _ in *next..step {
+        writer.write_all(&vec![0; record.len()])?;
+    }
+    writer.write_all(record)?;
+    *next = step + 1;
+    Ok(())
+}
+
+struct Loader {
+    cached: Vec<(u32, Arc<Vec<[f32; 4]>>)>, // the latest records, both fields of a step use them
+    next: u32,                              // step of the next record the receiver hands out
+    receiver: Option<mpsc::Receiver<Vec<[f32; 4]>>>,
+}
+
+/// A recording opened for replay. Records are read ahead in order on a loader thread, going
+/// back in time, as after a reset, starts it over.
+pub struct ReplayStream {
+    path: std::path::PathBuf,
+    header: Header,
+    records: u32,
+    loader: Mutex<Loader>,
+}
+
+impl ReplayStream {
+    /// Opens the recording at `path`, which must have been made on `plane` with the same steps.
+    pub fn open(
+        path: &std::path::Path,
+        plane: &Plane,
+        spatial_step: f32,
+        temporal_step: f32,
+    ) -> anyhow::Result<Self> {
+        let mut file = std::fs::File::open(path)
+            .map_err(|err| anyhow::anyhow!("Cannot open recording {}: {}", path.display(), err))?;
+        let mut header: Header = bytemuck::Zeroable::zeroed();
+        file.read_exact(bytemuck::bytes_of_mut(&mut header))
+            .map_err(|err| anyhow::anyhow!("{}: {}", path.display(), err))?;
+        anyhow::ensure!(
+            header.magic == MAGIC && header.version == VERSION,
+            "{} is not a field recording of this version of grems",
+            path.display()
+        );
+        anyhow::ensure!(
+            header.normal == plane.normal.axis() as u32 && header.size == plane.size,
+            "{} was recorded on a plane of {:?} cells normal to {}, the replay plane has {:?} cells normal to {}",
+            path.display(),
+            header.size,
+            ["x", "y", "z"][header.normal.min(2) as usize],
+            plane.size,
+            ["x", "y", "z"][plane.normal.axis()]
+        );
+        for (name, recorded, used) in [
+            ("spatial_step", header.spatial_step, spatial_step),
+            ("temporal_step", header.temporal_step, temporal_step),
+        ] {
+            anyhow::ensure!(
+                (recorded - used).abs() <= 1e-6 * used,
+                "{} was recorded with {} = {}, the preset uses {}",
+                path.display(),
+                name,
+                recorded,
+                used
+            );
+        }
+        let length = file.metadata()?.len();
+        let records =
+            ((length - std::mem::size_of::<Header>() as u64) / plane.record_size()) as u32;
+        Ok(Self {
+            path: path.into(),
+            header,
+            records,
+            loader: Mutex::new(Loader {
+                cached: vec![],
+                next: 0,
+                receiver: None,
+            }),
+        })
+    }
+
+    /// Whether no record is left from `step` on.
+    pub fn is_finished(&self, step: u32) -> bool {
+        step >= self.header.first_step + self.records
+    }
+
+    /// The record of `step`, `None` outside the recording.
+    fn record(&self, step: u32) -> Option<Arc<Vec<[f32; 4]>>> {
+        let first = self.header.first_step;
+        if step < first || self.is_finished(step) {
+            return None;
+        }
+        let mut loader = self.loader.lock().unwrap();
+        if let Some((_, record)) = loader.cached.iter().find(|(cached, _)| *cached == step) {
+            return Some(record.clone());
+        }
+        if loader.receiver.is_none() || step < loader.next {
+            loader.receiver = Some(self.spawn_loader(step));
+            loader.next = step;
+        }
+        while loader.next <= step {
+            let Ok(record) = loader.receiver.as_ref().unwrap().recv() else {
+                loader.receiver = None;
+                return None;
+            };
+            let record = Arc::new(record);
+            if loader.cached.len() == 2 {
+                loader.cached.remove(0);
+            }
+            let next = loader.next;
+            loader.cached.push((next, record));
+            loader.next += 1;
+        }
+        loader.cached.last().map(|(_, record)| record.clone())
+    }
+
+    fn spawn_loader(&self, step: u32) -> mpsc::Receiver<Vec<[f32; 4]>> {
+        let (sender, receiver) = mpsc::sync_channel(READ_AHEAD);
+        let path = self.path.clone();
+        let record_size = (self.header.size[0] * self.header.size[1]) as usize * 16;
+        let skipped = (step - self.header.first_step) as u64;
+        let count = self.records - (step - self.header.first_step);
+        std::thread::spawn(move || {
+            let result = (|| -> anyhow::Result<()> {
+                let mut file = std::fs::File::open(&path)?;
+                file.seek(std::io::SeekFrom::Start(
+                    std::mem::size_of::<Header>() as u64 + skipped * record_size as u64,
+                ))?;
+                let mut reader = std::io::BufReader::new(file);
+                for _ in 0..count {
+                    let mut record = vec![0; record_size];
+                    reader.read_exact(&mut record)?;
+                    if sender.send(bytemuck::pod_collect_to_vec(&record)).is_err() {
+                        break;
+                    }
+                }
+                Ok(())
+            })();
+            if let Err(err) = result {
+                eprintln!("Failed to read recording {}: {}", path.display(), err);
+            }
+        });
+        receiver
+    }
+
+    /// Uploads the profiles of a sheet of currents that radiates the recorded fields towards
+    /// `sign` along the normal and nothing the other way: J = n × H for `field` E, with H of
+    /// the record of `step` + 1, half a step ahead, and M = -n × E for `field` H, with E of the
+    /// record of `step`. Each is spread over a cell, so divided by `spatial_step`. Returns
+    /// whether there was a record to upload.
+    #[allow(clippy::too_many_arguments)]
+    pub fn upload(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        profiles: &[wgpu::Texture; 2],
+        field: fdtd::FieldType,
+        sign: f32,
+        spatial_step: f32,
+        step: u32,
+    ) -> bool {
+        let (record, offset) = match field {
+            fdtd::FieldType::E => (self.record(step + 1), 2),
+            fdtd::FieldType::H => (self.record(step), 0),
+        };
+        let Some(record) = record else {
+            return false;
+        };
+        // n × (a e_u + b e_v) = s (a e_v - b e_u), s turning over when u, v, n are not in
+        // cyclic order, as for y
+        let orientation = if self.header.normal == 1 { -1.0 } else { 1.0 };
+        // the excitation adds the negated current
+        let scale = match field {
+            fdtd::FieldType::E => -1.0,
+            fdtd::FieldType::H => 1.0,
+        } * sign
+            * orientation
+            / spatial_step;
+
+        let [width, height] = self.header.size;
+        let row = width as usize * 8;
+        let padded_row = (row as u32).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) as usize;
+        let mut texels = vec![0u8; 2 * padded_row * height as usize];
+        let (u_profile, v_profile) = texels.split_at_mut(padded_row * height as usize);
+        for (index, cell) in record.iter().enumerate() {
+            let (a, b) = (cell[offset], cell[offset + 1]);
+            let at = (index / width as usize) * padded_row + (index % width as usize) * 8;
+            u_profile[at..at + 8].copy_from_slice(bytemuck::bytes_of(&[-b * scale, 0.0]));
+            v_profile[at..at + 8].copy_from_slice(bytemuck::bytes_of(&[a * scale, 0.0]));
+        }
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Replay Upload"),
+            contents: &texels,
+            usage: wgpu::BufferUsages::COPY_SRC,
+        });
+        for (index, profile) in profiles.iter().enumerate() {
+            encoder.copy_buffer_to_texture(
+                wgpu::ImageCopyBuffer {
+                    buffer: &buffer,
+                    layout: wgpu::ImageDataLayout {
+                        offset: (index * padded_row * height as usize) as u64,
+                        bytes_per_row: Some(padded_row as u32),
+                        rows_per_image: Some(height),
+                    },
+                },
+                profile.as_image_copy(),
+                profile.size(),
+            );
+        }
+        true
+    }
+}
@@ -0,0 +1,275 @@
+use std::path::Path;
+
+use crate::settings::ResonanceSettings;
+
+type Complex = nalgebra::Complex<f64>;
+
+/// Taper applied to a trace before its transform.
+#[derive(Clone, Copy, Default, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Window {
+    #[default]
+    Rectangular, // keeps the linewidth of a trace that has rung down
+    Hann, // suppresses the leakage of a trace cut off while still ringing
+}
+
+impl Window {
+    fn weight(self, index: usize, count: usize) -> f64 {
+        match self {
+            Window::Rectangular => 1.0,
+            Window::Hann => 0.5 - 0.5 * (std::f64::consts::TAU * index as f64 / count as f64).cos(),
+        }
+    }
+
+    /// Full width at half power of the spectrum of a pure tone, in bins of the unpadded trace.
+    fn resolution(self) -> f64 {
+        match self {
+            Window::Rectangular => 0.886,
+            Window::Hann => 1.44,
+        }
+    }
+}
+
+/// A peak of the power spectrum of a trace.
+#[derive(Clone, Copy, Debug)]
+pub struct Resonance {
+    pub frequency: f64, // 1 / wavelength in vacuum
+    pub power: f64,     // relative to the strongest peak
+    pub linewidth: f64, // full width at half power, in units of frequency
+    pub q: f64,         // frequency / linewidth
+    pub resolved: bool, // false when the linewidth is that of the window, Q is a lower bound then
+}
+
+/// In-place radix-2 FFT with the kernel exp(-i 2 pi k n / N), `values` a power of 2 long.
+fn fft(values: &mut [Complex]) {
+    let count = values.len();
+    let mut j = 0;
+    for i in 1..count {
+        let mut bit = count >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            values.swap(i, j);
+        }
+    }
+    let mut length = 2;
+    while length <= count {
+        let angle = -std::f64::consts::TAU / length as f64;
+        for chunk in values.chunks_mut(length) {
+            let (low, high) = chunk.split_at_mut(length / 2);
+            for (k, (a, b)) in low.iter_mut().zip(high).enumerate() {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let t = *b * Complex::new(cos, sin);
+                *b = *a - t;
+                *a += t;
+            }
+        }
+        length <<= 1;
+    }
+}
+
+/// Finds the resonances in `samples` of (step, value) taken every `dt`, from the windowed and
+/// zero-padded transform of the samples from `start_time` on. Peaks are refined by a parabola
+/// through the log power of the bins around them, linewidths are read off the half-power
+/// points. Sorted by frequency.
+pub fn analyse(samples: &[(u32, f32)], dt: f32, settings: &ResonanceSettings) -> Vec<Resonance> {
+    let trace: Vec<f32> = samples
+        .iter()
+        .filter(|(step, _)| *step as f32 * dt >= settings.start_time)
+        .map(|(_, value)| *value)
+        .collect();
+    if trace.len() < 4 {
+        return vec![];
+    }
+    let mean = trace.iter().map(|value| *value as f64).sum::<f64>() / trace.len() as f64;
+    let length = (trace.len() * settings.zero_padding.max(1) as usize).next_power_of_two();
+    let mut values = vec![Complex::default(); length];
+    for (index, value) in trace.iter().enumerate() {
+        values[index] = Complex::new(
+            (*value as f64 - mean) * settings.window.weight(index, trace.len()),
+            0.0,
+        );
+    }
+    fft(&mut values);
+
+    let bin = 1.0 / (length as f64 * dt as f64);
+    let power: Vec<f64> = values[..=length / 2].iter().map(|v| v.norm_sqr()).collect();
+    let [low, high] = settings
+        .frequency_range
+        .map_or([0.0, f64::INFINITY], |range| range.map(|f| f as f64));
+    let in_range = |k: usize| (low..=high).contains(&(k as f64 * bin));
+    let strongest = (1..power.len() - 1)
+        .filter(|k| in_range(*k))
+        .map(|k| power[k])
+        .fold(0.0, f64::max);
+    if strongest == 0.0 {
+        return vec![];
+    }
+    let resolution = settings.window.resolution() / (trace.len() as f64 * dt as f64);
+
+    (1..power.len() - 1)
+        .filter(|&k| {
+            in_range(k)
+                && power[k] > power[k - 1]
+                && power[k] >= power[k + 1]
+                && power[k] >= settings.threshold as f64 * strongest
+        })
+        .map(|k| {
+            let [before, at, after] = [power[k - 1], power[k], power[k + 1]].map(f64::ln);
+            let curvature = before - 2.0 * at + after;
+            let shift = if curvature < 0.0 {
+                0.5 * (before - after) / curvature
+            } else {
+                0.0
+            };
+            let peak = (at - 0.25 * (before - after) * shift).exp();
+            // where the power falls to half the peak on either side, between two bins
+            let crossing = |mut i: usize, forward: bool| -> Option<f64> {
+                loop {
+                    let next = if forward { i + 1 } else { i.checked_sub(1)? };
+                    if next >= power.len() {
+                        return None;
+                    }
+                    if power[next] < 0.5 * peak {
+                        let t = (power[i] - 0.5 * peak) / (power[i] - power[next]);
+                        return Some(i as f64 + t * (next as f64 - i as f64));
+                    }
+                    i = next;
+                }
+            };
+            let frequency = (k as f64 + shift) * bin;
+            let linewidth = match (crossing(k, false), crossing(k, true)) {
+                (Some(below), Some(above)) => (above - below) * bin,
+                _ => f64::NAN,
+            };
+            Resonance {
+                frequency,
+                power: peak / strongest,
+                linewidth,
+                q: frequency / linewidth,
+                resolved: linewidth > 1.05 * resolution,
+            }
+        })
+        .collect()
+}
+
+/// Prints the resonances of the probe `name`, one per line.
+pub fn print(name: &str, resonances: &[Resonance]) {
+    println!("Resonances of probe {}:", name);
+    if resonances.is_empty() {
+        println!("  none found");
+    }
+    for resonance in resonances {
+        println!(
+            "  f = {:.6} (wavelength {:.6}), relative power {:.3e}, linewidth {:.3e}, Q = {}{:.1}",
+            resonance.frequency,
+            1.0 / resonance.frequency,
+            resonance.power,
+            resonance.linewidth,
+            if resonance.resolved { "" } else { ">= " },
+            resonance.q
+        );
+    }
+}
+
+/// Writes rows of the probe name, the frequency, the vacuum wavelength, the relative power,
+/// the linewidth, Q and whether the linewidth was resolved.
+pub fn write_csv(path: &Path, resonances: &[(String, Vec<Resonance>)]) -> anyhow::Result<()> {
+    let mut text = "probe,frequency,wavelength,power,linewidth,q,resolved\n".to_string();
+    for (name, resonances) in resonances {
+        for resonance in resonances {
+            text += &format!(
+                "{:?},{},{},{},{},{},{}\n",
+                name,
+                resonance.frequency,
+                1.0 / resonance.frequency,
+                resonance.power,
+                resonance.linewidth,
+                resonance.q,
+                resonance.resolved
+            );
+        }
+    }
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ringdown of `decay` per unit time at each of the `tones` (frequency, amplitude),
+    /// sampled every `dt` until `end`.
+    fn ringdown(tones: &[(f64, f64)], decay: f64, dt: f32, end: f32) -> Vec<(u32, f32)> {
+        (0..(end / dt) as u32)
+            .map(|step| {
+                let time = step as f64 * dt as f64;
+                let value: f64 = tones
+                    .iter()
+                    .map(|(frequency, amplitude)| {
+                        amplitude * (std::f64::consts::TAU * frequency * time).cos()
+                    })
+                    .sum();
+                (step, (value * (-decay * time).exp()) as f32)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fft_matches_the_direct_transform() {
+        let values: Vec<Complex> = (0..16)
+            .map(|n| Complex::new((n as f64 * 0.7).sin(), (n * n % 5) as f64))
+            .collect();
+        let mut transformed = values.clone();
+        fft(&mut transformed);
+        for (k, value) in transformed.iter().enumerate() {
+            let direct: Complex = values
+                .iter()
+                .enumerate()
+                .map(|(n, v)| {
+                    let angle = -std::f64::consts::TAU * (k * n) as f64 / 16.0;
+                    v * Complex::new(angle.cos(), angle.sin())
+                })
+                .sum();
+            assert!((value - direct).norm_sqr() < 1e-20, "bin {}", k);
+        }
+    }
+
+    #[test]
+    fn ringdown_gives_its_frequency_and_lorentzian_linewidth() {
+        // the power spectrum of exp(-γt) cos(2π f t) is half its peak at f ± γ / 2π
+        let (frequency, decay) = (1.0, 0.05);
+        let samples = ringdown(&[(frequency, 1.0)], decay, 0.05, 300.0);
+        let resonances = analyse(&samples, 0.05, &ResonanceSettings::default());
+        assert_eq!(resonances.len(), 1, "{:?}", resonances);
+        let resonance = resonances[0];
+        let linewidth = decay / std::f64::consts::PI;
+        assert!((resonance.frequency - frequency).abs() < 1e-4 * frequency);
+        assert!((resonance.linewidth - linewidth).abs() < 0.02 * linewidth);
+        assert!((resonance.q - frequency / linewidth).abs() < 0.02 * frequency / linewidth);
+        assert!(resonance.resolved);
+        // the refined peak lies a little above the strongest bin
+        assert!((resonance.power - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn tone_cut_off_is_limited_by_the_window() {
+        // without decay the linewidth is that of the trace length, Q only a bound
+        let samples = ringdown(&[(0.8, 1.0), (1.3, 0.5)], 0.0, 0.05, 40.0);
+        let settings = ResonanceSettings {
+            window: Window::Hann,
+            ..Default::default()
+        };
+        let resonances = analyse(&samples, 0.05, &settings);
+        assert_eq!(resonances.len(), 2, "{:?}", resonances);
+        for (resonance, (frequency, power)) in resonances.iter().zip([(0.8, 1.0), (1.3, 0.25)]) {
+            assert!((resonance.frequency - frequency).abs() < 1e-3);
+            assert!((resonance.power - power).abs() < 0.01 * power);
+            assert!((resonance.linewidth - 1.44 / 40.0).abs() < 0.05 * 1.44 / 40.0);
+            assert!(!resonance.resolved);
+        }
+    }
+}
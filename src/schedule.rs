@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+
+use crate::settings::{FDTDSettings, TimingSettings};
+
+/// Something the simulation does once a step is done.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Action {
+    Pause,
+    /// The export at this index of the preset's `exports`
+    Export(usize),
+}
+
+/// When the pause points and exports of a preset fire. One-shot timings are resolved to their
+/// step once, so a `time` and a `step` entry landing on the same step both fire there.
+/// Periodic timings are checked against each step.
+pub struct Schedule {
+    once: BTreeMap<u32, Vec<Action>>,
+    periodic: Vec<(TimingSettings, Action)>,
+    temporal_step: f32,
+}
+
+impl Schedule {
+    pub fn new(settings: &FDTDSettings) -> Self {
+        let mut once: BTreeMap<u32, Vec<Action>> = BTreeMap::new();
+        let mut periodic = vec![];
+        let timings = settings
+            .pause_at
+            .iter()
+            .map(|timing| (timing, Action::Pause))
+            .chain(
+                settings
+                    .exports
+                    .iter()
+                    .enumerate()
+                    .map(|(index, export)| (&export.timing, Action::Export(index))),
+            );
+        for (timing, action) in timings {
+            if timing.is_periodic() {
+                periodic.push((timing.clone(), action));
+            } else {
+                once.entry(timing.to_step(settings.temporal_step()))
+                    .or_default()
+                    .push(action);
+            }
+        }
+        Self {
+            once,
+            periodic,
+            temporal_step: settings.temporal_step(),
+        }
+    }
+
+    /// Actions due at `step`, the pause first, then the exports in preset order.
+    pub fn actions_at(&self, step: u32) -> Vec<Action> {
+        let mut actions: Vec<Action> = self
+            .once
+            .get(&step)
+            .into_iter()
+            .flatten()
+            .copied()
+            .chain(
+                self.periodic
+                    .iter()
+                    .filter(|(timing, _)| timing.fires_at(step, self.temporal_step))
+                    .map(|(_, action)| *action),
+            )
+            .collect();
+        actions.sort_unstable();
+        actions.dedup();
+        actions
+    }
+
+    /// Actions due on the state a run starts from, before its first update. Those of step 0
+    /// on the initial state, none on a state resumed at a later step, whose actions already ran
+    /// after that step's update.
+    pub fn actions_on_start(&self, step: u32) -> Vec<Action> {
+        if step == 0 {
+            self.actions_at(0)
+        } else {
+            vec![]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Settings with a step of 0.3 and the given `pause_at` and `exports` timings, as JSON.
+    fn settings(pause_at: &str, export_timings: &[&str]) -> FDTDSettings {
+        let exports = export_timings
+            .iter()
+            .map(|timing| {
+                format!(
+                    r#"{{ "timing": {}, "export": {{ "dimension": "D3", "settings": {{ "field": "E" }} }} }}"#,
+                    timing
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        serde_json::from_str(&format!(
+            r#"{{
+                "domain": [[-1, 1], [-1, 1], [-1, 1]],
+                "boundary": {{ "type": "PEC" }},
+                "spatial_step": 0.5,
+                "temporal_step": 0.3,
+                "default_slice": {{ "field": "E", "mode": "Z", "position": 0 }},
+                "default_scaling_factor": 1,
+                "pause_at": [{}],
+                "exports": [{}],
+                "models": [],
+                "sources": []
+            }}"#,
+            pause_at, exports
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn time_and_step_entries_on_one_step_fire_together() {
+        // 1.2 and 1.15 round to step 4, the two pauses there are one
+        let schedule = Schedule::new(&settings(
+            r#"{ "type": "step", "value": 4 }, { "type": "time", "value": 1.15 }"#,
+            &[r#"{ "type": "time", "value": 1.2 }"#],
+        ));
+        assert_eq!(schedule.actions_at(4), [Action::Pause, Action::Export(0)]);
+        assert!(schedule.actions_at(3).is_empty());
+        assert!(schedule.actions_at(5).is_empty());
+    }
+
+    #[test]
+    fn step_zero_is_the_initial_state() {
+        // 0.1 rounds to step 0, periodic entries never fire there
+        let schedule = Schedule::new(&settings(
+            r#"{ "type": "step", "value": 0 }, { "type": "every", "value": 2 }"#,
+            &[
+                r#"{ "type": "time", "value": 0.1 }"#,
+                r#"{ "type": "every_time", "value": 0.6 }"#,
+            ],
+        ));
+        assert_eq!(schedule.actions_at(0), [Action::Pause, Action::Export(0)]);
+        assert_eq!(schedule.actions_at(2), [Action::Pause, Action::Export(1)]);
+        assert!(schedule.actions_at(1).is_empty());
+    }
+
+    #[test]
+    fn step_zero_actions_run_on_start_only() {
+        let schedule = Schedule::new(&settings(
+            r#"{ "type": "step", "value": 0 }"#,
+            &[
+                r#"{ "type": "time", "value": 0 }"#,
+                r#"{ "type": "every", "value": 1 }"#,
+                r#"{ "type": "step", "value": 6 }"#,
+            ],
+        ));
+        // a fresh or reset run, the every-step export waits for the first update
+        assert_eq!(
+            schedule.actions_on_start(0),
+            [Action::Pause, Action::Export(0)]
+        );
+        // resumed from checkpoints, before and on a scheduled step
+        assert!(schedule.actions_on_start(5).is_empty());
+        assert!(schedule.actions_on_start(6).is_empty());
+    }
+
+    #[test]
+    fn periodic_times_off_the_step_grid_fire_at_the_nearest_step() {
+        // every 1.0 with steps of 0.3 lands a third of a step off the grid, both ways
+        let timing = TimingSettings::EveryTime(1.0);
+        let fired: Vec<u32> = (0..=20)
+            .filter(|step| timing.fires_at(*step, 0.3))
+            .collect();
+        assert_eq!(fired, [3, 7, 10, 13, 17, 20]);
+        let timing = TimingSettings::Every(4);
+        let fired: Vec<u32> = (0..=20)
+            .filter(|step| timing.fires_at(*step, 0.3))
+            .collect();
+        assert_eq!(fired, [4, 8, 12, 16, 20]);
+    }
+}
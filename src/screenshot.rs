@@ -0,0 +1,140 @@
+use crate::fdtd;
+
+/// Renders the current slice into an offscreen texture and writes it to `path` as PNG.
+/// Only the GPU copy is waited for, encoding and writing happen on a worker thread.
+pub fn capture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    fdtd: &fdtd::FDTD,
+    format: wgpu::TextureFormat,
+    size: [u32; 2],
+    path: std::path::PathBuf,
+) -> anyhow::Result<()> {
+    let swap_red_blue = needs_red_blue_swap(format)?;
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Screenshot"),
+        size: wgpu::Extent3d {
+            width: size[0],
+            height: size[1],
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let bytes_per_pixel = 4;
+    let unpadded_bytes_per_row = size[0] * bytes_per_pixel;
+    let padded_bytes_per_row_padding = (wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
+        - unpadded_bytes_per_row % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+        % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded_bytes_per_row = unpadded_bytes_per_row + padded_bytes_per_row_padding;
+
+    let copy_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Screenshot"),
+        size: (padded_bytes_per_row * size[1]) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        fdtd.visualize(&mut render_pass, size);
+    }
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &copy_buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: None,
+            },
+        },
+        wgpu::Extent3d {
+            width: size[0],
+            height: size[1],
+            depth_or_array_layers: 1,
+        },
+    );
+    let index = queue.submit(Some(encoder.finish()));
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    copy_buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |v| {
+            let _ = sender.send(v);
+        });
+    device.poll(wgpu::Maintain::WaitForSubmissionIndex(index));
+
+    std::thread::spawn(move || {
+        let result = receiver
+            .recv()
+            .map_err(anyhow::Error::from)
+            .and_then(|mapped| {
+                mapped?;
+                let pixels: Vec<u8> = copy_buffer
+                    .slice(..)
+                    .get_mapped_range()
+                    .chunks(padded_bytes_per_row as usize)
+                    .flat_map(|row| &row[..unpadded_bytes_per_row as usize])
+                    .cloned()
+                    .collect();
+                copy_buffer.unmap();
+                write_png(&path, size, pixels, swap_red_blue)
+            });
+        match result {
+            Ok(()) => println!("Screenshot written to {}", path.display()),
+            Err(err) => eprintln!("Failed to write screenshot {}: {}", path.display(), err),
+        }
+    });
+
+    Ok(())
+}
+
+/// Whether pixels rendered in `format` have to be swizzled to RGBA before encoding.
+pub fn needs_red_blue_swap(format: wgpu::TextureFormat) -> anyhow::Result<bool> {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => Ok(false),
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => Ok(true),
+        _ => anyhow::bail!("Capturing surface format {:?} is not supported", format),
+    }
+}
+
+pub fn write_png(
+    path: &std::path::Path,
+    size: [u32; 2],
+    mut pixels: Vec<u8>,
+    swap_red_blue: bool,
+) -> anyhow::Result<()> {
+    if swap_red_blue {
+        pixels
+            .chunks_exact_mut(4)
+            .for_each(|pixel| pixel.swap(0, 2));
+    }
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let mut encoder = png::Encoder::new(file, size[0], size[1]);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&pixels)?;
+    Ok(())
+}
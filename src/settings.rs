@@ -0,0 +1,827 @@
+//! What a preset deserializes into, after the layers of a run are merged.
+
+use std::path::Path;
+
+use crate::{export, fdtd, interpolator, resonance, units};
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct FDTDSettings {
+    pub units: Option<units::Units>, // times in the time unit instead of ct when set
+    pub domain: [[f32; 2]; 3],
+    pub workgroup: Option<WorkgroupSettings>, // this is kind of 'meta', maybe move it to another configs?
+    pub boundary: crate::fdtd::BoundaryCondition,
+    #[serde(default)]
+    pub dimension_mode: fdtd::DimensionMode, // "2d_te" or "2d_tm" collapse z to one periodic cell
+    pub spatial_step: f32,
+    pub temporal_step: Option<f32>, // derived from courant_factor when unset
+    #[serde(alias = "courant")]
+    pub courant_factor: Option<f32>, // derive temporal_step as this fraction of the Courant limit
+    #[serde(default)]
+    pub steps_per_second_limit: Option<f32>, // uncapped when unset or 0
+    pub default_slice: SliceSettings,
+    pub default_scaling_factor: f32,
+    #[serde(default = "default_log_floor")]
+    pub default_log_floor: f32, // lowest decade shown in log scale mode, relative to the scaled value 1
+    // custom fragment shader, takes precedence over `colormap`. shader/display_scale.wgsl is
+    // prepended to it, so it can call display_scale(value, c_param.log_scale, c_param.log_floor)
+    // to follow the Ctrl+L log mode like the built-in colormaps
+    pub default_shader: Option<String>,
+    #[serde(default)]
+    pub colormap: fdtd::Colormap,
+    pub pause_at: Vec<TimingSettings>,
+    pub exports: Vec<ExportSettings>,
+    pub initial_fields: Option<InitialFieldsSettings>, // start from exported fields instead of zero
+    #[serde(default)]
+    pub field_recordings: Vec<FieldRecordingSettings>, // planes written every step, for replay sources
+    #[serde(default = "default_export_metadata")]
+    pub export_metadata: bool, // a .json sidecar describing the grid and the run next to each export
+    #[serde(default)]
+    pub record: RecordSettings,
+    pub intensity: Option<IntensitySettings>, // time-averaged |E|^2, off when unset
+    pub energy: Option<EnergySettings>, // total field energy over the interior, off when unset
+    pub near_field: Option<NearFieldSettings>, // far-field pattern written at the end, off when unset
+    #[serde(default)]
+    pub mode_monitors: Vec<ModeMonitorSettings>, // mode amplitudes at port planes, written at the end
+    #[serde(default)]
+    pub flux_boxes: Vec<FluxBoxSettings>, // absorbed power in closed boxes
+    #[serde(default = "default_flux_every")]
+    pub flux_every: u32, // steps between two samples of the flux box face powers
+    #[serde(default = "default_non_finite_check_every")]
+    pub non_finite_check_every: u32, // steps between checks for NaN or infinite fields, 0 turns them off
+    #[serde(default)]
+    pub probes: Vec<ProbeSettings>, // sampled every step and plotted in the window
+    #[serde(default = "default_probe_history")]
+    pub probe_history: usize, // steps kept per probe
+    pub resonance: Option<ResonanceSettings>, // analysis of the probe traces at the end, off when unset
+    pub max_steps: Option<u32>,
+    pub end_time: Option<f32>, // same unit as temporal_step, whichever of max_steps and end_time comes first ends the run
+    #[serde(default = "default_step_batch")]
+    pub step_batch: u32, // steps advanced by Ctrl+Shift+N while paused
+    #[serde(default = "default_max_steps_per_frame")]
+    pub max_steps_per_frame: u32, // upper bound on steps recorded into one submission
+    #[serde(default)]
+    pub background: BackgroundSettings,
+    #[serde(default)]
+    pub source_wavelengths: WavelengthReference, // vacuum wavelengths unless set to background
+    pub models: Vec<ModelSettings>,
+    #[serde(default)]
+    pub extrusions: Vec<ExtrusionSettings>, // applied after the models
+    #[serde(default)]
+    pub material_clouds: Vec<MaterialCloudSettings>, // applied after the models and extrusions
+    pub material_cache: Option<String>, // directory keeping voxelized models between runs, off when unset
+    pub sources: Vec<SourceSettings>,
+}
+
+fn default_export_metadata() -> bool {
+    true
+}
+
+fn default_stride() -> [u32; 3] {
+    [1; 3]
+}
+
+fn default_step_batch() -> u32 {
+    10
+}
+
+fn default_max_steps_per_frame() -> u32 {
+    8
+}
+
+fn default_mode_normal() -> fdtd::SliceMode {
+    fdtd::SliceMode::Z
+}
+
+fn default_tone_power() -> f32 {
+    1.0
+}
+
+fn default_refractive_index() -> f32 {
+    1.0
+}
+
+fn default_subpixel_samples() -> u32 {
+    1
+}
+
+fn default_permeability() -> f32 {
+    1.0
+}
+
+fn default_log_floor() -> f32 {
+    -6.0
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct ProbeSettings {
+    pub name: Option<String>, // shown over the plot, the position when unset
+    pub position: [f32; 3],
+    pub field: fdtd::FieldType,
+    pub component: Option<fdtd::Component>, // the magnitude when unset
+}
+
+fn default_probe_history() -> usize {
+    2048
+}
+
+/// A box whose faces record the DFT of E and H at a few wavelengths during the run. At the end
+/// they are transformed into the far field, written to `<preset>-far-field.csv`.
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct NearFieldSettings {
+    pub position: [f32; 3], // center
+    pub size: [f32; 3],
+    pub wavelengths: Vec<f32>, // in the medium `source_wavelengths` refers to
+    #[serde(default = "default_theta_count")]
+    pub theta_count: u32, // polar angles from 0 to 180 degrees inclusive
+    #[serde(default = "default_phi_count")]
+    pub phi_count: u32, // azimuths over a full turn
+}
+
+/// A plane whose fields are projected onto a guided mode at a few wavelengths during the run.
+/// At the end the forward and backward mode amplitudes are written to `<preset>-<name>.csv`.
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct ModeMonitorSettings {
+    pub name: Option<String>, // `mode-monitor-<index>` when unset
+    pub position: [f32; 3],   // picks the plane along the normal of the mode
+    #[serde(default)]
+    pub size: [f32; 3], // as for a mode source, bounds a port and scales a texture
+    pub wavelengths: Vec<f32>, // in the medium `source_wavelengths` refers to
+    pub mode: ModeSettings,   // texture, gaussian_beam or port, as for mode sources
+    #[serde(default)]
+    pub csv: CsvSettings, // layout of the mode files
+}
+
+/// A plane whose tangential E and H are appended to `file` after every step, to be played back
+/// by a replay source in a later run on the same grid.
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct FieldRecordingSettings {
+    pub file: String,
+    pub position: [f32; 3], // picks the plane along the normal
+    #[serde(default = "default_mode_normal")]
+    pub normal: fdtd::SliceMode,
+}
+
+impl ModeMonitorSettings {
+    pub fn name(&self, index: usize) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("mode-monitor-{}", index))
+    }
+
+    pub fn placement(&self, wavelength: f32) -> ProfilePlacement {
+        ProfilePlacement {
+            position: self.position,
+            size: self.size,
+            wavelength,
+            phase: 0.0,
+            power: 1.0,
+        }
+    }
+}
+
+/// A closed box whose net inflow through its faces is the power absorbed inside. The power
+/// through each face goes to `<preset>-<name>-flux.csv` every `flux_every` steps, and at the end
+/// the absorbed energy per unit frequency at the wavelengths to `<preset>-<name>-absorption.csv`.
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct FluxBoxSettings {
+    pub name: Option<String>, // `flux-box-<index>` when unset
+    pub position: [f32; 3],   // center
+    pub size: [f32; 3],
+    #[serde(default)]
+    pub wavelengths: Vec<f32>, // in the medium `source_wavelengths` refers to
+}
+
+impl FluxBoxSettings {
+    pub fn name(&self, index: usize) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("flux-box-{}", index))
+    }
+}
+
+/// Resonances found by a Fourier transform of the probe traces. Printed and written to
+/// `<preset>-resonances.csv` at the end of the run, and printed on demand with Q.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct ResonanceSettings {
+    #[serde(default)]
+    pub start_time: f32, // same unit as temporal_step, samples before it are left out, e.g. the excitation
+    #[serde(default)]
+    pub window: resonance::Window,
+    #[serde(default = "default_zero_padding")]
+    pub zero_padding: u32, // the transform is this many times the trace, rounded up to a power of 2
+    #[serde(default = "default_peak_threshold")]
+    pub threshold: f32, // peaks weaker than this fraction of the strongest one are left out
+    pub frequency_range: Option<[f32; 2]>, // of 1 / wavelength in vacuum, all frequencies when unset
+}
+
+impl Default for ResonanceSettings {
+    fn default() -> Self {
+        Self {
+            start_time: 0.0,
+            window: resonance::Window::default(),
+            zero_padding: default_zero_padding(),
+            threshold: default_peak_threshold(),
+            frequency_range: None,
+        }
+    }
+}
+
+fn default_zero_padding() -> u32 {
+    4
+}
+
+fn default_peak_threshold() -> f32 {
+    0.01
+}
+
+fn default_theta_count() -> u32 {
+    37
+}
+
+fn default_phi_count() -> u32 {
+    72
+}
+
+pub fn default_energy_every() -> u32 {
+    100
+}
+
+fn default_non_finite_check_every() -> u32 {
+    100
+}
+
+fn default_flux_every() -> u32 {
+    1
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct IntensitySettings {
+    pub time_constant: Option<f32>, // time over which older samples fade by 1/e, an even average when unset
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct EnergySettings {
+    #[serde(default = "default_energy_every")]
+    pub every: u32, // steps between two sums, each one is printed
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct WorkgroupSettings {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+    #[serde(default)]
+    pub tiled: bool, // stage neighbours in workgroup memory, the halo must fit
+}
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct SliceSettings {
+    pub field: fdtd::FieldType,
+    pub mode: fdtd::SliceMode,
+    pub position: f32,
+    #[serde(default)]
+    pub stretch: bool, // fill the window instead of keeping the proportions of the slice
+}
+
+/// When a pause or an export fires. Step N sees the fields after N updates, so step 0, or a
+/// time rounding to it, is the initial state before the first update.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type", content = "value")]
+pub enum TimingSettings {
+    Step(u32),
+    Time(f32),
+    Every(u32),     // every N steps
+    EveryTime(f32), // every T in the unit of temporal_step, rounded to the nearest step
+}
+
+impl FDTDSettings {
+    /// In ct once `prepare_settings` has set it, from the preset or its `courant_factor`.
+    pub fn temporal_step(&self) -> f32 {
+        self.temporal_step
+            .expect("temporal_step is set by prepare_settings")
+    }
+
+    pub fn last_step(&self) -> Option<u32> {
+        let end_step = self
+            .end_time
+            .map(|time| TimingSettings::Time(time).to_step(self.temporal_step()));
+        match (self.max_steps, end_step) {
+            (Some(max_steps), Some(end_step)) => Some(max_steps.min(end_step)),
+            (max_steps, end_step) => max_steps.or(end_step),
+        }
+    }
+}
+
+impl TimingSettings {
+    /// The step this fires at, the first one for periodic timings.
+    pub fn to_step(&self, temporal_step: f32) -> u32 {
+        match *self {
+            TimingSettings::Step(step) | TimingSettings::Every(step) => step,
+            TimingSettings::Time(time) | TimingSettings::EveryTime(time) => {
+                (time / temporal_step).round() as u32
+            }
+        }
+    }
+
+    pub fn is_periodic(&self) -> bool {
+        matches!(
+            self,
+            TimingSettings::Every(..) | TimingSettings::EveryTime(..)
+        )
+    }
+
+    pub fn fires_at(&self, step: u32, temporal_step: f32) -> bool {
+        match *self {
+            TimingSettings::Step(..) | TimingSettings::Time(..) => {
+                self.to_step(temporal_step) == step
+            }
+            TimingSettings::Every(every) => step > 0 && step.is_multiple_of(every),
+            TimingSettings::EveryTime(every) => {
+                let period = (step as f32 * temporal_step / every).round();
+                period >= 1.0 && (period * every / temporal_step).round() as u32 == step
+            }
+        }
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct ExportSettings {
+    pub timing: TimingSettings,
+    pub export: ExportFieldSettings,
+    #[serde(default)]
+    pub quantity: export::ExportQuantity,
+    #[serde(default)]
+    pub unfold: bool, // mirror the data across the symmetry planes of the boundary
+    pub region: Option<ExportRegionSettings>, // the whole grid when unset
+    #[serde(default = "default_stride")]
+    pub stride: [u32; 3], // keep every n-th cell along each axis
+    #[serde(default)]
+    pub precision: export::Precision, // "f32" or "f16" values in the DDS files
+}
+
+/// D3 exports of an earlier run the fields start from, one DDS file per component. Taking E and
+/// H from the same step keeps them half a step apart as the leapfrog expects.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct InitialFieldsSettings {
+    pub electric: Option<[String; 3]>, // Ex, Ey and Ez, zero when unset
+    pub magnetic: Option<[String; 3]>, // Hx, Hy and Hz, zero when unset
+}
+
+/// Box an export is cropped to, in world coordinates. Cells whose centers lie inside are kept.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct ExportRegionSettings {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "dimension", content = "settings")]
+pub enum ExportFieldSettings {
+    D3 { field: fdtd::FieldType },
+    D2(SliceSettings),
+    D1 { field: fdtd::FieldType }, // the column of the 1D mode, as CSV
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct RecordSettings {
+    pub every: u32, // capture a frame every N steps
+    pub resolution: [u32; 2],
+    #[serde(default)]
+    pub start: bool, // record from the first step instead of waiting for Ctrl+R
+}
+
+impl Default for RecordSettings {
+    fn default() -> Self {
+        Self {
+            every: 10,
+            resolution: [1280, 720],
+            start: false,
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct ModelSettings {
+    pub path: String,
+    pub position: [f32; 3],
+    pub scale: [f32; 3],
+    #[serde(default)]
+    pub rotation: [f32; 3], // euler angles (roll, pitch, yaw) in degrees, applied after scaling
+    #[serde(default = "default_refractive_index")]
+    pub refractive_index: f32, // ignored by pec models
+    #[serde(default)]
+    pub material: ModelMaterial,
+    #[serde(default = "default_subpixel_samples")]
+    pub subpixel_samples: u32, // rays per cell column, above 1 the cut cells are averaged
+    #[serde(default)]
+    pub parity_axes: fdtd::gltf_importer::ParityAxes, // ray direction of the inside test
+    pub repeat: Option<fdtd::gltf_importer::Lattice>, // voxelized once, then copied
+    #[serde(default)]
+    pub materials: std::collections::BTreeMap<String, PrimitiveMaterialSettings>, // by glTF material name, others take the model material
+}
+
+/// Material of the primitives using one glTF material, in place of the model material.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct PrimitiveMaterialSettings {
+    #[serde(default = "default_refractive_index")]
+    pub refractive_index: f32, // ignored by pec
+    #[serde(default)]
+    pub material: ModelMaterial,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelMaterial {
+    #[default]
+    Dielectric, // given by refractive_index
+    Pec, // perfect electric conductor, the electric field stays zero inside
+}
+
+impl ModelMaterial {
+    pub fn importer_material(self, refractive_index: f32) -> fdtd::gltf_importer::Material {
+        match self {
+            Self::Dielectric => {
+                fdtd::gltf_importer::Material::Dielectric(fdtd::gltf_importer::MaterialConstants {
+                    permittivity: refractive_index * refractive_index,
+                    permeability: 1.0,
+                })
+            }
+            Self::Pec => fdtd::gltf_importer::Material::Pec,
+        }
+    }
+}
+
+/// Medium filling the domain outside of models and material clouds, vacuum by default.
+#[derive(Clone, Copy, serde::Deserialize, serde::Serialize)]
+#[serde(untagged)]
+pub enum BackgroundSettings {
+    Index {
+        refractive_index: f32,
+    },
+    Material {
+        permittivity: f32,
+        #[serde(default = "default_permeability")]
+        permeability: f32,
+    },
+}
+
+impl Default for BackgroundSettings {
+    fn default() -> Self {
+        Self::Index {
+            refractive_index: 1.0,
+        }
+    }
+}
+
+impl BackgroundSettings {
+    pub fn material(&self) -> fdtd::gltf_importer::MaterialConstants {
+        match *self {
+            Self::Index { refractive_index } => fdtd::gltf_importer::MaterialConstants {
+                permittivity: refractive_index * refractive_index,
+                permeability: 1.0,
+            },
+            Self::Material {
+                permittivity,
+                permeability,
+            } => fdtd::gltf_importer::MaterialConstants {
+                permittivity,
+                permeability,
+            },
+        }
+    }
+
+    pub fn refractive_index(&self) -> f32 {
+        let material = self.material();
+        (material.permittivity * material.permeability).sqrt()
+    }
+}
+
+/// Medium the source wavelengths are measured in.
+#[derive(Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WavelengthReference {
+    #[default]
+    Vacuum,
+    Background, // multiplied by the background refractive index on load, chirp rates divided
+}
+
+/// Polygons of a layout extruded along z, as integrated photonics structures are drawn.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct ExtrusionSettings {
+    pub path: String, // CSV of polygon name, x, y rows after a header, a polygon wound the other way is a hole
+    #[serde(default)]
+    pub position: [f32; 3],
+    pub z_min: f32,
+    pub z_max: f32,
+    #[serde(default = "default_refractive_index")]
+    pub refractive_index: f32, // ignored by pec
+    #[serde(default)]
+    pub material: ModelMaterial,
+    #[serde(default = "default_subpixel_samples")]
+    pub subpixel_samples: u32, // samples per cell side, above 1 the cut cells are averaged
+}
+
+/// Measured permittivity given as scattered points, interpolated onto the grid.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct MaterialCloudSettings {
+    pub path: String, // CSV of x, y, z, relative permittivity rows after a header
+    pub position: [f32; 3],
+    pub scale: [f32; 3],
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type", content = "settings")]
+pub enum ModeSettings {
+    PointCloud {
+        file: String,
+        exclude: Vec<(fdtd::FieldType, fdtd::Component)>,
+        // cell centers at the grid boundary always fall just outside the hull of the cloud
+        #[serde(default)]
+        extrapolation: interpolator::Extrapolation,
+        #[serde(default = "default_mode_normal")]
+        normal: fdtd::SliceMode, // the source position along it picks the injection plane
+    },
+    Texture {
+        ex: Option<String>,
+        ey: Option<String>,
+        ez: Option<String>,
+        hx: Option<String>,
+        hy: Option<String>,
+        hz: Option<String>,
+        spatial_step: f32,
+        #[serde(default = "default_mode_normal")]
+        normal: fdtd::SliceMode, // the CSV x and y span the other two axes, in x, y, z order
+        // cells of the plane past the edges of the resampled profile
+        #[serde(default)]
+        extrapolation: interpolator::Extrapolation,
+    },
+    GaussianBeam {
+        waist: f32, // 1/e field radius at the focus
+        #[serde(default)]
+        focus_offset: f32, // focus along the normal, relative to the source position
+        polarization: [f32; 3], // electric field direction, projected onto the plane
+        #[serde(default = "default_mode_normal")]
+        normal: fdtd::SliceMode,
+    },
+    Volume {
+        direction: [f32; 3],
+        field: fdtd::FieldType,
+    },
+    Port {
+        #[serde(default)]
+        mode_index: usize, // 0 is the fundamental mode
+        polarization: [f32; 3], // electric field direction, projected onto the plane
+        #[serde(default = "default_mode_normal", alias = "plane")]
+        normal: fdtd::SliceMode, // the source size across it bounds the solved cross-section
+    },
+    // plays back a field recording as it is, the carrier, envelope and power are not used
+    Replay {
+        file: String, // written by a field recording on a plane of the same cells
+        #[serde(default)]
+        backward: bool, // radiates towards the low side of the plane instead of the high one
+        #[serde(default = "default_mode_normal")]
+        normal: fdtd::SliceMode,
+    },
+}
+
+impl ModeSettings {
+    /// The axis the plane of the mode is normal to, `None` for volume sources.
+    pub fn normal(&self) -> Option<fdtd::SliceMode> {
+        match self {
+            ModeSettings::PointCloud { normal, .. }
+            | ModeSettings::Texture { normal, .. }
+            | ModeSettings::GaussianBeam { normal, .. }
+            | ModeSettings::Port { normal, .. }
+            | ModeSettings::Replay { normal, .. } => Some(*normal),
+            ModeSettings::Volume { .. } => None,
+        }
+    }
+
+    /// The files the mode reads, by their key in the mode settings.
+    pub fn files(&mut self) -> Vec<(&'static str, &mut String)> {
+        match self {
+            ModeSettings::PointCloud { file, .. } | ModeSettings::Replay { file, .. } => {
+                vec![("file", file)]
+            }
+            ModeSettings::Texture {
+                ex,
+                ey,
+                ez,
+                hx,
+                hy,
+                hz,
+                ..
+            } => ["ex", "ey", "ez", "hx", "hy", "hz"]
+                .into_iter()
+                .zip([ex, ey, ez, hx, hy, hz])
+                .filter_map(|(key, path)| Some((key, path.as_mut()?)))
+                .collect(),
+            ModeSettings::GaussianBeam { .. }
+            | ModeSettings::Volume { .. }
+            | ModeSettings::Port { .. } => vec![],
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct SourceSettings {
+    pub wavelength: f32,
+    pub position: [f32; 3],
+    pub size: [f32; 3],
+    pub mode: ModeSettings,
+    pub phase: f32,
+    pub delay: f32,
+    pub fwhm: f32, // bandwidth of the gaussian envelope, per unit of time like chirp_rate
+    pub power: f32,
+    #[serde(default)]
+    pub csv: CsvSettings, // layout of the mode files
+    #[serde(default)]
+    pub start_time: f32, // same unit as temporal_step, the source is not injected outside [start_time, stop_time)
+    pub stop_time: Option<f32>,
+    #[serde(default)]
+    pub envelope: Envelope,
+    #[serde(default)]
+    pub ramp_time: f32, // raised-cosine turn-on of continuous-wave envelopes, from start_time
+    #[serde(default)]
+    pub tones: Vec<Tone>, // summed into the carrier, replaces wavelength when not empty
+    #[serde(default)]
+    pub chirp_rate: f32, // change of the carrier frequency 1 / wavelength per unit of time, from delay
+    pub waveform: Option<String>, // CSV of time, amplitude rows used instead of carrier and envelope
+    pub normalize: Option<Normalization>, // rescales the source so that it radiates `power`
+}
+
+/// What `power` means for a normalized source. A calibration run before the simulation injects
+/// the source alone and measures the flux through a box two cells around it, in the units of
+/// the grid where the vacuum permittivity and permeability are 1.
+#[derive(Clone, Copy, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Normalization {
+    Energy, // the energy radiated in total, the source must end
+    Power,  // the mean power radiated over ten periods once a continuous wave has settled
+}
+
+/// One frequency component of a source carrier.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct Tone {
+    pub wavelength: f32,
+    #[serde(default = "default_tone_power")]
+    pub power: f32, // relative to the source power
+    #[serde(default)]
+    pub phase: f32, // degrees, offset of this tone within the carrier
+}
+
+#[derive(Clone, Copy, Default, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Envelope {
+    #[default]
+    Gaussian, // centered on delay, width given by fwhm
+    Cw,       // constant after an optional ramp
+    RampedCw, // like cw, but the ramp is required
+}
+
+/// Where a mode profile lies on its plane and how it is scaled, from a source or a monitor.
+pub struct ProfilePlacement {
+    pub position: [f32; 3],
+    pub size: [f32; 3],
+    pub wavelength: f32,
+    pub phase: f32, // degrees
+    pub power: f32,
+}
+
+impl SourceSettings {
+    pub fn placement(&self) -> ProfilePlacement {
+        ProfilePlacement {
+            position: self.position,
+            size: self.size,
+            wavelength: self.wavelength,
+            phase: self.phase,
+            power: self.power,
+        }
+    }
+
+    pub fn active(&self) -> std::ops::Range<f32> {
+        self.start_time..self.stop_time.unwrap_or(f32::INFINITY)
+    }
+
+    pub fn tones(&self) -> Vec<Tone> {
+        if self.tones.is_empty() {
+            vec![Tone {
+                wavelength: self.wavelength,
+                power: 1.0,
+                phase: 0.0,
+            }]
+        } else {
+            self.tones.clone()
+        }
+    }
+}
+
+/// A CSV column, by 0-based index or by header name.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+#[serde(untagged)]
+pub enum CsvColumn {
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct CsvColumns {
+    pub x: CsvColumn,
+    pub y: CsvColumn,
+    pub real: CsvColumn,
+    pub imag: CsvColumn,
+}
+
+impl Default for CsvColumns {
+    fn default() -> Self {
+        Self {
+            x: CsvColumn::Index(0),
+            y: CsvColumn::Index(1),
+            real: CsvColumn::Index(2),
+            imag: CsvColumn::Index(3),
+        }
+    }
+}
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct CsvSettings {
+    pub has_header: bool,
+    pub delimiter: char,
+    pub columns: CsvColumns,
+}
+
+impl Default for CsvSettings {
+    fn default() -> Self {
+        Self {
+            has_header: true,
+            delimiter: ',',
+            columns: CsvColumns::default(),
+        }
+    }
+}
+
+impl CsvSettings {
+    /// Opens `path` with this dialect and resolves the x, y, real and imaginary column indices.
+    pub fn open(&self, path: &Path) -> anyhow::Result<(csv::Reader<std::fs::File>, [usize; 4])> {
+        anyhow::ensure!(
+            self.delimiter.is_ascii(),
+            "CSV delimiter must be an ASCII character, got {:?}",
+            self.delimiter
+        );
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(self.has_header)
+            .delimiter(self.delimiter as u8)
+            .from_path(path)
+            .map_err(|err| anyhow::anyhow!("{}: {}", path.display(), err))?;
+        let headers = if self.has_header {
+            Some(
+                reader
+                    .headers()
+                    .map_err(|err| anyhow::anyhow!("{}: {}", path.display(), err))?
+                    .clone(),
+            )
+        } else {
+            None
+        };
+
+        let columns = &self.columns;
+        let mut indices = [0; 4];
+        for (index, (role, column)) in indices.iter_mut().zip([
+            ("x", &columns.x),
+            ("y", &columns.y),
+            ("real", &columns.real),
+            ("imag", &columns.imag),
+        ]) {
+            *index = match column {
+                CsvColumn::Index(index) => *index,
+                CsvColumn::Name(name) => {
+                    let headers = headers.as_ref().ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "{}: the {} column is named {:?} but has_header is false",
+                            path.display(),
+                            role,
+                            name
+                        )
+                    })?;
+                    headers
+                        .iter()
+                        .position(|header| header.trim() == name)
+                        .ok_or_else(|| {
+                            anyhow::anyhow!(
+                                "{}: no column named {:?} for {}, the header has {:?}",
+                                path.display(),
+                                name,
+                                role,
+                                headers.iter().collect::<Vec<_>>()
+                            )
+                        })?
+                }
+            };
+        }
+
+        Ok((reader, indices))
+    }
+}
@@ -0,0 +1,1022 @@
+use std::sync::{mpsc, Arc, Mutex};
+
+use crate::{checkpoint, export, fdtd, periodic, probe, recording, replay, resonance, schedule};
+
+/// Requests from the UI thread, handled between two batches of steps.
+pub enum Command {
+    TogglePause,
+    /// Advances this many steps while paused.
+    Step(u32),
+    Reset,
+    Export(fdtd::FieldType, export::Region),
+    ToggleRecording,
+}
+
+/// Progress published by the simulation thread after every batch.
+#[derive(Clone, Copy, Default)]
+pub struct Status {
+    pub step: u32,
+    pub paused: bool,
+    pub finished: bool,
+    /// GPU time per step of the latest profiling sample, with `--profile`.
+    pub pass_times: Option<fdtd::PassTimes>,
+    /// Latest field energy sum, when the preset or `--watch-energy` asks for it.
+    pub energy: Option<f64>,
+    /// First check that found a NaN or infinite field since the start or the last reset.
+    pub non_finite_at: Option<u32>,
+}
+
+/// Spreads steps over wall-clock time according to `steps_per_second_limit`.
+struct Pacer {
+    tau: Option<std::time::Duration>, // None runs as fast as the GPU allows
+    elapsed: std::time::Duration,
+    last: std::time::Instant,
+}
+
+impl Pacer {
+    /// `None` or 0 leaves the step rate uncapped.
+    fn new(steps_per_second_limit: Option<f32>) -> Self {
+        let now = std::time::Instant::now();
+        Self {
+            tau: steps_per_second_limit
+                .filter(|limit| *limit > 0.0)
+                .map(|limit| std::time::Duration::from_secs_f32(1.0 / limit)),
+            elapsed: std::time::Duration::ZERO,
+            last: now,
+        }
+    }
+
+    /// Forgets the time since the last step, must be called whenever stepping (re)starts.
+    fn restart(&mut self) {
+        self.elapsed = std::time::Duration::ZERO;
+        self.last = std::time::Instant::now();
+    }
+
+    fn is_uncapped(&self) -> bool {
+        self.tau.is_none()
+    }
+
+    /// Time left until the next step is due.
+    fn until_due(&self) -> std::time::Duration {
+        self.until_due_at(std::time::Instant::now())
+    }
+
+    /// `until_due` seen at `now`.
+    fn until_due_at(&self, now: std::time::Instant) -> std::time::Duration {
+        self.tau.map_or(std::time::Duration::ZERO, |tau| {
+            tau.saturating_sub(self.elapsed + (now - self.last))
+        })
+    }
+
+    /// Number of steps due this frame, at most `max_steps_per_frame`.
+    fn steps_due(&mut self, max_steps_per_frame: u32) -> u32 {
+        self.steps_due_at(std::time::Instant::now(), max_steps_per_frame)
+    }
+
+    /// `steps_due` for a frame starting at `now`.
+    fn steps_due_at(&mut self, now: std::time::Instant, max_steps_per_frame: u32) -> u32 {
+        let max_steps_per_frame = max_steps_per_frame.max(1);
+        let Some(tau) = self.tau else {
+            return max_steps_per_frame;
+        };
+        // time owed beyond what the next frame may catch up on is dropped,
+        // so a stall never turns into a burst of steps
+        self.elapsed = (self.elapsed + (now - self.last)).min(tau * (max_steps_per_frame + 1));
+        self.last = now;
+
+        let due =
+            ((self.elapsed.as_secs_f64() / tau.as_secs_f64()) as u32).min(max_steps_per_frame);
+        self.elapsed -= tau * due;
+        due
+    }
+}
+
+fn start_recording(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    preset: &str,
+    record: &crate::settings::RecordSettings,
+) -> anyhow::Result<recording::Recorder> {
+    let directory = std::env::current_dir()?.join(format!(
+        "{}-recording-{}",
+        preset,
+        crate::timestamp_millis()
+    ));
+    println!(
+        "Recording every {} steps to {}",
+        record.every,
+        directory.display()
+    );
+    recording::Recorder::new(device, format, record.resolution, directory)
+}
+
+struct Worker {
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    fdtd: Arc<Mutex<fdtd::FDTD>>,
+    settings: crate::settings::FDTDSettings,
+    magnetic_sources: Vec<crate::Source>,
+    electric_sources: Vec<crate::Source>,
+    preset: String,
+    render_format: wgpu::TextureFormat,
+    checkpoint_every: Option<u32>,
+    checkpoint_path: std::path::PathBuf,
+    exporter: Option<export::Exporter>,
+    recorder: Option<recording::Recorder>,
+    field_recordings: Vec<replay::PlaneRecorder>,
+    probes: probe::ProbeRecorder,
+    energy: Option<periodic::PeriodicReadback>,
+    watch_energy: Option<f32>, // percent of growth between two sums that pauses
+    // the previous sum taken while no source injected, what `watch_energy` compares against
+    quiet_energy: Option<(u32, f64)>,
+    latest_energy: Option<f64>,
+    non_finite: Option<periodic::PeriodicReadback>,
+    flux: Option<periodic::PeriodicReadback>,
+    flux_rows: Vec<Vec<(u32, [f64; 6])>>, // per flux box the face powers sampled so far
+    clean_check: u32,                     // the latest check that found the fields finite
+    non_finite_at: Option<u32>,
+    schedule: schedule::Schedule,
+    pacer: Pacer,
+    // the latest batch, waited for before the next one when the step rate is uncapped
+    last_batch: Option<wgpu::SubmissionIndex>,
+    step: u32,
+    paused: bool,
+    pending_steps: u32,
+    profiling: bool,
+    pass_times: Option<fdtd::PassTimes>,
+    started: std::time::Instant,
+    status: Arc<Mutex<Status>>,
+    proxy: Option<winit::event_loop::EventLoopProxy<()>>, // None without a window
+}
+
+impl Worker {
+    // how often outstanding readbacks are polled while idle
+    const IDLE_POLL: std::time::Duration = std::time::Duration::from_millis(50);
+
+    fn run(mut self, receiver: mpsc::Receiver<Command>) {
+        self.run_initial_actions();
+        loop {
+            let timeout = if self.paused && self.pending_steps == 0 {
+                Self::IDLE_POLL
+            } else if self.paused {
+                std::time::Duration::ZERO
+            } else {
+                self.pacer.until_due().min(Self::IDLE_POLL)
+            };
+            match receiver.recv_timeout(timeout) {
+                Ok(command) => self.handle(command),
+                Err(mpsc::RecvTimeoutError::Timeout) => (),
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+            while let Ok(command) = receiver.try_recv() {
+                self.handle(command);
+            }
+
+            if let Some(exporter) = &self.exporter {
+                exporter.poll(&self.device);
+            }
+            if let Some(recorder) = &mut self.recorder {
+                recorder.collect(&self.device, false);
+            }
+            for recording in self.field_recordings.iter_mut() {
+                recording.collect(&self.device);
+            }
+            if self.probes.collect(&self.device) {
+                // redraws the plot, which also freezes once paused
+                self.publish();
+            }
+            let mut energy_sums = vec![];
+            if let Some(readback) = &mut self.energy {
+                let fdtd = self.fdtd.lock().unwrap();
+                readback.collect(&self.device, |step, sums| {
+                    energy_sums.push((step, fdtd.total_energy(bytemuck::cast_slice(sums))));
+                });
+            }
+            self.collect_flux();
+            let mut non_finite_counts = vec![];
+            if let Some(readback) = &mut self.non_finite {
+                readback.collect(&self.device, |step, count| {
+                    non_finite_counts.push((step, bytemuck::pod_read_unaligned::<u32>(count)));
+                });
+            }
+            for (step, energy) in energy_sums.iter() {
+                self.check_energy(*step, *energy);
+            }
+            for (step, cells) in non_finite_counts.iter() {
+                self.check_non_finite(*step, *cells);
+            }
+            if !energy_sums.is_empty() || !non_finite_counts.is_empty() {
+                self.publish();
+            }
+            if self.profiling {
+                let pass_times = self.fdtd.lock().unwrap().collect_profile(&self.device);
+                if let Some(times) = pass_times {
+                    // shown in the status text when there is a window
+                    if self.proxy.is_none() {
+                        println!(
+                            "Step {}: GPU ms/step: update {:.3}, PML {:.3}, excitation {:.3}",
+                            self.step, times.update, times.pml, times.excitation
+                        );
+                    }
+                    self.pass_times = pass_times;
+                    self.publish();
+                }
+            }
+
+            let steps = if self.paused {
+                std::mem::take(&mut self.pending_steps)
+            } else {
+                self.pacer.steps_due(self.settings.max_steps_per_frame)
+            };
+            if steps > 0 {
+                self.run_steps(steps);
+                self.publish();
+            }
+            if self.paused && self.proxy.is_none() {
+                eprintln!(
+                    "Stopping at step {}, a run without a window cannot be resumed",
+                    self.step
+                );
+                break;
+            }
+            if self.reached_last_step() {
+                println!(
+                    "Finished {} steps ({}) in {:.1}s",
+                    self.step,
+                    crate::units::describe_time(
+                        self.settings.units.as_ref(),
+                        self.step as f32 * self.settings.temporal_step()
+                    ),
+                    self.started.elapsed().as_secs_f32()
+                );
+                break;
+            }
+        }
+
+        if let Some(exporter) = self.exporter.take() {
+            exporter.finish(&self.device);
+        }
+        for recording in std::mem::take(&mut self.field_recordings) {
+            recording.finish(&self.device);
+        }
+        self.write_far_field();
+        self.write_mode_amplitudes();
+        self.write_flux_boxes();
+        self.write_resonances();
+        if let Some(recorder) = self.recorder.take() {
+            recorder.finish(&self.device);
+        }
+        self.status.lock().unwrap().finished = true;
+        self.notify();
+    }
+
+    /// Writes the far field of the near-field box to `<preset>-far-field.csv`, when the preset
+    /// sets one up and a step was run.
+    fn write_far_field(&self) {
+        if self.settings.near_field.is_none() || self.step == 0 {
+            return;
+        }
+        let fdtd = self.fdtd.lock().unwrap();
+        let path = std::path::PathBuf::from(format!("{}-far-field.csv", self.preset));
+        let result = crate::far_field(&fdtd, &self.device, &self.queue, &self.settings)
+            .and_then(|samples| crate::far_field::write_csv(&path, &samples.unwrap_or_default()));
+        match result {
+            Ok(()) => println!("Far field written to {}", path.display()),
+            Err(err) => eprintln!("Failed to write the far field: {}", err),
+        }
+    }
+
+    /// Writes the amplitudes of every mode monitor to `<preset>-<name>.csv`, when a step was
+    /// run.
+    fn write_mode_amplitudes(&self) {
+        if self.step == 0 {
+            return;
+        }
+        let fdtd = self.fdtd.lock().unwrap();
+        for (index, monitor) in self.settings.mode_monitors.iter().enumerate() {
+            let path =
+                std::path::PathBuf::from(format!("{}-{}.csv", self.preset, monitor.name(index)));
+            let result =
+                crate::mode_amplitudes(&fdtd, &self.device, &self.queue, &self.settings, index)
+                    .and_then(|amplitudes| crate::mode_monitor::write_csv(&path, &amplitudes));
+            match result {
+                Ok(()) => println!("Mode amplitudes written to {}", path.display()),
+                Err(err) => eprintln!("Failed to write {}: {}", path.display(), err),
+            }
+        }
+    }
+
+    /// Prints the resonances of every probe trace and writes them to
+    /// `<preset>-resonances.csv`, when the preset asks for the analysis and a step was run.
+    fn write_resonances(&mut self) {
+        let Some(settings) = &self.settings.resonance else {
+            return;
+        };
+        if self.step == 0 {
+            return;
+        }
+        // the latest batches
+        self.device.poll(wgpu::Maintain::Wait);
+        self.probes.collect(&self.device);
+        let traces = self.probes.traces();
+        let resonances: Vec<(String, Vec<resonance::Resonance>)> = traces
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|trace| {
+                let samples: Vec<(u32, f32)> = trace.samples.iter().copied().collect();
+                let resonances =
+                    resonance::analyse(&samples, self.settings.temporal_step(), settings);
+                resonance::print(&trace.name, &resonances);
+                (trace.name.clone(), resonances)
+            })
+            .collect();
+        let path = std::path::PathBuf::from(format!("{}-resonances.csv", self.preset));
+        match resonance::write_csv(&path, &resonances) {
+            Ok(()) => println!("Resonances written to {}", path.display()),
+            Err(err) => eprintln!("Failed to write {}: {}", path.display(), err),
+        }
+    }
+
+    /// Appends the face powers of finished flux readbacks to the rows of the flux boxes.
+    fn collect_flux(&mut self) {
+        let Some(readback) = &mut self.flux else {
+            return;
+        };
+        let fdtd = self.fdtd.lock().unwrap();
+        let flux_rows = &mut self.flux_rows;
+        readback.collect(&self.device, |step, sums| {
+            let powers = fdtd.face_powers(bytemuck::cast_slice(sums));
+            for (rows, powers) in flux_rows.iter_mut().zip(powers) {
+                rows.push((step, powers));
+            }
+        });
+    }
+
+    /// Writes the face powers of every flux box to `<preset>-<name>-flux.csv` and its absorption
+    /// spectrum to `<preset>-<name>-absorption.csv`, when a step was run.
+    fn write_flux_boxes(&mut self) {
+        if self.step == 0 {
+            return;
+        }
+        // the latest batches
+        self.device.poll(wgpu::Maintain::Wait);
+        self.collect_flux();
+        let fdtd = self.fdtd.lock().unwrap();
+        for (index, flux_box) in self.settings.flux_boxes.iter().enumerate() {
+            let name = flux_box.name(index);
+            let path = std::path::PathBuf::from(format!("{}-{}-flux.csv", self.preset, name));
+            match crate::flux_box::write_power_csv(
+                &path,
+                &self.flux_rows[index],
+                self.settings.temporal_step(),
+            ) {
+                Ok(()) => println!("Flux box powers written to {}", path.display()),
+                Err(err) => eprintln!("Failed to write {}: {}", path.display(), err),
+            }
+            if flux_box.wavelengths.is_empty() {
+                continue;
+            }
+            let path = std::path::PathBuf::from(format!("{}-{}-absorption.csv", self.preset, name));
+            let result = crate::absorption(&fdtd, &self.device, &self.queue, index)
+                .and_then(|samples| crate::flux_box::write_absorption_csv(&path, &samples));
+            match result {
+                Ok(()) => println!("Absorption spectrum written to {}", path.display()),
+                Err(err) => eprintln!("Failed to write {}: {}", path.display(), err),
+            }
+        }
+    }
+
+    /// Runs the actions due on the state stepping starts from, before the first update.
+    fn run_initial_actions(&mut self) {
+        let actions = self.schedule.actions_on_start(self.step);
+        let fdtd = self.fdtd.lock().unwrap();
+        fdtd.begin_frame();
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        if run_actions(
+            &actions,
+            &self.settings,
+            self.exporter.as_mut(),
+            &self.device,
+            &fdtd,
+            &mut encoder,
+            self.step,
+        ) && self.pauses_at_schedule()
+        {
+            self.paused = true;
+        }
+        self.queue.submit(Some(encoder.finish()));
+        drop(fdtd);
+        if let Some(exporter) = &mut self.exporter {
+            exporter.map_pending();
+        }
+        self.publish();
+    }
+
+    /// Logs an energy sum and pauses when `--watch-energy` sees it grow too fast. Sums taken
+    /// while a source still injects are not compared, the energy grows legitimately then.
+    fn check_energy(&mut self, step: u32, energy: f64) {
+        println!("Step {}: field energy {:.6e}", step, energy);
+        self.latest_energy = Some(energy);
+        let Some(watch_energy) = self.watch_energy else {
+            return;
+        };
+        let dt = self.settings.temporal_step();
+        let quiet = self
+            .magnetic_sources
+            .iter()
+            .chain(&self.electric_sources)
+            .all(|source| source.is_finished(step as f32 * dt, dt));
+        if !quiet {
+            self.quiet_energy = None;
+            return;
+        }
+        if let Some((last_step, last_energy)) = self.quiet_energy {
+            let limit = last_energy * (1.0 + watch_energy as f64 / 100.0);
+            if !self.paused && (energy.is_nan() || energy > limit) {
+                eprintln!(
+                    "Field energy grew by more than {}% from {:.6e} at step {} to {:.6e} at step {}, pausing",
+                    watch_energy, last_energy, last_step, energy, step
+                );
+                self.paused = true;
+            }
+        }
+        self.quiet_energy = Some((step, energy));
+    }
+
+    /// Pauses on the first check that finds NaN or infinite fields, they would only spread.
+    fn check_non_finite(&mut self, step: u32, cells: u32) {
+        if self.non_finite_at.is_some() {
+            return;
+        }
+        if cells == 0 {
+            self.clean_check = step;
+            return;
+        }
+        eprintln!(
+            "{} cells hold NaN or infinite fields at step {}, the check at step {} was clean, pausing",
+            cells, step, self.clean_check
+        );
+        self.non_finite_at = Some(step);
+        self.paused = true;
+    }
+
+    fn reached_last_step(&self) -> bool {
+        self.settings
+            .last_step()
+            .is_some_and(|last_step| self.step >= last_step)
+    }
+
+    fn publish(&self) {
+        *self.status.lock().unwrap() = Status {
+            step: self.step,
+            paused: self.paused,
+            finished: false,
+            pass_times: self.pass_times,
+            energy: self.latest_energy,
+            non_finite_at: self.non_finite_at,
+        };
+        self.notify();
+    }
+
+    fn notify(&self) {
+        if let Some(proxy) = &self.proxy {
+            let _ = proxy.send_event(());
+        }
+    }
+
+    /// Pause points of the schedule only pause with a window, nothing resumes a run without one.
+    fn pauses_at_schedule(&self) -> bool {
+        self.proxy.is_some()
+    }
+
+    fn handle(&mut self, command: Command) {
+        match command {
+            Command::TogglePause => {
+                self.paused = !self.paused;
+                if !self.paused {
+                    self.pacer.restart();
+                }
+            }
+            Command::Step(steps) => {
+                if self.paused {
+                    self.pending_steps += steps;
+                }
+            }
+            Command::Reset => {
+                let fdtd = self.fdtd.lock().unwrap();
+                let mut encoder = self
+                    .device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+                match fdtd.reset(&mut encoder) {
+                    Ok(()) => {
+                        self.queue.submit(Some(encoder.finish()));
+                        if let Some(initial) = &self.settings.initial_fields {
+                            if let Err(err) = crate::initial_fields::load(
+                                &self.queue,
+                                &fdtd,
+                                initial,
+                                self.settings.spatial_step,
+                            ) {
+                                eprintln!("Failed to load the initial fields: {}", err);
+                            }
+                        }
+                        self.probes.clear();
+                        for recording in self.field_recordings.iter_mut() {
+                            recording.restart();
+                        }
+                        for readback in self
+                            .energy
+                            .iter_mut()
+                            .chain(&mut self.non_finite)
+                            .chain(&mut self.flux)
+                        {
+                            readback.clear();
+                        }
+                        for rows in self.flux_rows.iter_mut() {
+                            rows.clear();
+                        }
+                        self.quiet_energy = None;
+                        self.latest_energy = None;
+                        self.clean_check = 0;
+                        self.non_finite_at = None;
+                        self.step = 0;
+                        self.pacer.restart();
+                        drop(fdtd);
+                        // back at the initial state, so its actions run again
+                        self.run_initial_actions();
+                    }
+                    Err(err) => eprintln!("{}", err),
+                }
+            }
+            Command::Export(field, region) => {
+                let Some(exporter) = &mut self.exporter else {
+                    return;
+                };
+                let fdtd = self.fdtd.lock().unwrap();
+                fdtd.begin_frame();
+                let mut encoder = self
+                    .device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+                match exporter.request(
+                    &self.device,
+                    &fdtd,
+                    &mut encoder,
+                    field,
+                    export::ExportQuantity::Components,
+                    region,
+                    export::Crop::full(fdtd.get_dimension()),
+                    export::Precision::F32,
+                    None,
+                    self.step,
+                ) {
+                    Ok(()) => {
+                        self.queue.submit(Some(encoder.finish()));
+                        exporter.map_pending();
+                        println!("Exporting {:?} at step {}", field, self.step);
+                    }
+                    Err(err) => eprintln!("Failed to export at step {}: {}", self.step, err),
+                }
+            }
+            Command::ToggleRecording => match self.recorder.take() {
+                Some(recorder) => recorder.finish(&self.device),
+                None => match start_recording(
+                    &self.device,
+                    self.render_format,
+                    &self.preset,
+                    &self.settings.record,
+                ) {
+                    Ok(recorder) => self.recorder = Some(recorder),
+                    Err(err) => eprintln!("Failed to start recording: {}", err),
+                },
+            },
+        }
+        self.publish();
+    }
+
+    /// Records and submits up to `steps` steps, stopping early at pause points and the last step.
+    fn run_steps(&mut self, steps: u32) {
+        // keeps uncapped stepping from queueing up work faster than the GPU gets through it
+        if let Some(last_batch) = self.last_batch.take() {
+            self.device
+                .poll(wgpu::Maintain::WaitForSubmissionIndex(last_batch));
+        }
+        let fdtd = self.fdtd.lock().unwrap();
+        fdtd.begin_frame();
+        fdtd.begin_profile();
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let settings = &self.settings;
+        let first_step = self.step;
+        self.probes.begin(&self.device, steps);
+        for readback in self
+            .energy
+            .iter_mut()
+            .chain(&mut self.non_finite)
+            .chain(&mut self.flux)
+        {
+            readback.begin(&self.device, self.step, steps);
+        }
+        for recording in self.field_recordings.iter_mut() {
+            recording.begin(&self.device, self.step, steps);
+        }
+
+        for _ in 0..steps {
+            if self.reached_last_step() {
+                break;
+            }
+            crate::step_fields(
+                &self.device,
+                &fdtd,
+                &mut encoder,
+                settings,
+                &self.magnetic_sources,
+                &self.electric_sources,
+                self.step,
+            );
+            self.step += 1;
+            let step = self.step;
+            let mut reached_pause = false;
+            self.probes.sample(&fdtd, &mut encoder, step);
+            for recording in self.field_recordings.iter_mut() {
+                recording.record(&mut encoder, step);
+            }
+            if let Some((buffer, offset)) =
+                self.energy.as_mut().and_then(|energy| energy.sample(step))
+            {
+                fdtd.sum_energy(&mut encoder, buffer, offset);
+            }
+            if let Some((buffer, offset)) = self
+                .non_finite
+                .as_mut()
+                .and_then(|non_finite| non_finite.sample(step))
+            {
+                fdtd.count_non_finite(&mut encoder, buffer, offset);
+            }
+            if let Some((buffer, offset)) = self.flux.as_mut().and_then(|flux| flux.sample(step)) {
+                fdtd.sum_flux(&mut encoder, buffer, offset);
+            }
+
+            if let Some(recorder) = &mut self.recorder {
+                if step.is_multiple_of(settings.record.every.max(1)) {
+                    recorder.capture(&self.device, &fdtd, &mut encoder);
+                }
+            }
+
+            if run_actions(
+                &self.schedule.actions_at(step),
+                settings,
+                self.exporter.as_mut(),
+                &self.device,
+                &fdtd,
+                &mut encoder,
+                step,
+            ) && self.pauses_at_schedule()
+            {
+                self.paused = true;
+                reached_pause = true;
+            }
+
+            if let Some(every) = self.checkpoint_every {
+                if every > 0 && step.is_multiple_of(every) {
+                    self.queue.submit(Some(
+                        std::mem::replace(
+                            &mut encoder,
+                            self.device
+                                .create_command_encoder(&wgpu::CommandEncoderDescriptor::default()),
+                        )
+                        .finish(),
+                    ));
+                    if let Err(err) = checkpoint::save(
+                        &self.device,
+                        &self.queue,
+                        &fdtd,
+                        step,
+                        settings,
+                        &self.checkpoint_path,
+                    ) {
+                        eprintln!("Failed to write checkpoint at step {}: {}", step, err);
+                    }
+                }
+            }
+
+            // stop a batch of manual steps at the next pause point
+            if reached_pause {
+                break;
+            }
+        }
+
+        fdtd.end_profile(&mut encoder, self.step - first_step);
+        let batch = self.queue.submit(Some(encoder.finish()));
+        if self.pacer.is_uncapped() {
+            self.last_batch = Some(batch);
+        }
+        fdtd.map_profile();
+        drop(fdtd);
+        if let Some(exporter) = &mut self.exporter {
+            exporter.map_pending();
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.map_pending();
+        }
+        self.probes.map_pending();
+        for recording in self.field_recordings.iter_mut() {
+            recording.map_pending();
+        }
+        for readback in self
+            .energy
+            .iter_mut()
+            .chain(&mut self.non_finite)
+            .chain(&mut self.flux)
+        {
+            readback.map_pending();
+        }
+    }
+}
+
+/// Records `actions`, those of the schedule for `step`, which runs once that many updates are
+/// done. Returns whether one of them pauses.
+fn run_actions(
+    actions: &[schedule::Action],
+    settings: &crate::settings::FDTDSettings,
+    mut exporter: Option<&mut export::Exporter>,
+    device: &wgpu::Device,
+    fdtd: &fdtd::FDTD,
+    encoder: &mut wgpu::CommandEncoder,
+    step: u32,
+) -> bool {
+    let mut paused = false;
+    for action in actions {
+        let export = match *action {
+            schedule::Action::Pause => {
+                paused = true;
+                continue;
+            }
+            schedule::Action::Export(index) => &settings.exports[index],
+        };
+        let (field, region) = match export.export {
+            crate::settings::ExportFieldSettings::D3 { field } => (field, export::Region::Volume),
+            crate::settings::ExportFieldSettings::D1 { field } => {
+                (field, export::Region::line(fdtd))
+            }
+            crate::settings::ExportFieldSettings::D2(ref slice) => (
+                slice.field,
+                export::Region::Slice(
+                    slice.mode,
+                    fdtd.slice_index(
+                        slice.mode,
+                        fdtd.normalize_slice_position(slice.mode, slice.position),
+                    ),
+                ),
+            ),
+        };
+        if let Some(exporter) = exporter.as_deref_mut() {
+            if let Err(err) = crate::export_crop(settings, export).and_then(|crop| {
+                exporter.request(
+                    device,
+                    fdtd,
+                    encoder,
+                    field,
+                    export.quantity,
+                    region,
+                    crop,
+                    export.precision,
+                    export.unfold.then(|| settings.boundary.symmetry()),
+                    step,
+                )
+            }) {
+                eprintln!("Failed to export {:?} at step {}: {}", field, step, err);
+            }
+        }
+    }
+    paused
+}
+
+/// Runs the simulation on its own thread so that window drags, resizes and modal loops
+/// on the UI thread don't stall it. GPU state is shared through the `FDTD` mutex, which is
+/// held while a batch is recorded and submitted, the UI thread only locks it to render.
+pub struct Simulation {
+    sender: mpsc::Sender<Command>,
+    status: Arc<Mutex<Status>>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+impl Simulation {
+    /// `proxy` receives an event whenever there is something new to draw, without one the
+    /// simulation runs until the last step or a pause from a check.
+    #[allow(clippy::too_many_arguments)]
+    pub fn spawn(
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+        fdtd: Arc<Mutex<fdtd::FDTD>>,
+        settings: crate::settings::FDTDSettings,
+        magnetic_sources: Vec<crate::Source>,
+        electric_sources: Vec<crate::Source>,
+        probes: probe::ProbeRecorder,
+        watch_energy: Option<f32>,
+        preset: String,
+        render_format: wgpu::TextureFormat,
+        checkpoint_every: Option<u32>,
+        step: u32,
+        proxy: Option<winit::event_loop::EventLoopProxy<()>>,
+    ) -> anyhow::Result<Self> {
+        let checkpoint_path = std::env::current_dir()?.join(format!("{}-checkpoint.bin", preset));
+        let recorder = if settings.record.start {
+            Some(start_recording(
+                &device,
+                render_format,
+                &preset,
+                &settings.record,
+            )?)
+        } else {
+            None
+        };
+        let status = Arc::new(Mutex::new(Status {
+            step,
+            paused: false,
+            finished: false,
+            pass_times: None,
+            energy: None,
+            non_finite_at: None,
+        }));
+        let profiling = fdtd.lock().unwrap().is_profiling();
+        let energy = settings.energy.as_ref().and_then(|energy| {
+            let sums_size = fdtd.lock().unwrap().energy_sums_size()?;
+            Some(periodic::PeriodicReadback::new(
+                "Energy Readback",
+                energy.every,
+                sums_size,
+            ))
+        });
+        let non_finite = (settings.non_finite_check_every > 0).then(|| {
+            periodic::PeriodicReadback::new(
+                "Non-finite Readback",
+                settings.non_finite_check_every,
+                4,
+            )
+        });
+        let flux = fdtd.lock().unwrap().flux_sums_size().map(|sums_size| {
+            periodic::PeriodicReadback::new("Flux Readback", settings.flux_every, sums_size)
+        });
+        let flux_rows = settings.flux_boxes.iter().map(|_| vec![]).collect();
+        let field_recordings = crate::field_recorders(&device, &fdtd.lock().unwrap(), &settings)?;
+        let worker = Worker {
+            exporter: Some(export::Exporter::new(
+                &device,
+                preset.clone(),
+                settings.export_metadata.then_some(export::RunMetadata {
+                    spatial_step: settings.spatial_step,
+                    temporal_step: settings.temporal_step(),
+                    units: settings.units,
+                    domain: settings.domain,
+                    boundary: settings.boundary,
+                    dimension_mode: settings.dimension_mode,
+                }),
+            )),
+            recorder,
+            field_recordings,
+            probes,
+            energy,
+            watch_energy,
+            quiet_energy: None,
+            latest_energy: None,
+            non_finite,
+            flux,
+            flux_rows,
+            clean_check: step,
+            non_finite_at: None,
+            schedule: schedule::Schedule::new(&settings),
+            pacer: Pacer::new(settings.steps_per_second_limit),
+            last_batch: None,
+            device,
+            queue,
+            fdtd,
+            settings,
+            magnetic_sources,
+            electric_sources,
+            preset,
+            render_format,
+            checkpoint_every,
+            checkpoint_path,
+            step,
+            paused: false,
+            pending_steps: 0,
+            profiling,
+            pass_times: None,
+            started: std::time::Instant::now(),
+            status: status.clone(),
+            proxy,
+        };
+        let (sender, receiver) = mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("simulation".to_string())
+            .spawn(move || worker.run(receiver))?;
+
+        Ok(Self {
+            sender,
+            status,
+            thread,
+        })
+    }
+
+    pub fn send(&self, command: Command) {
+        let _ = self.sender.send(command);
+    }
+
+    pub fn status(&self) -> Status {
+        *self.status.lock().unwrap()
+    }
+
+    /// Stops stepping and waits until outstanding exports and recordings are flushed.
+    pub fn join(self) {
+        drop(self.sender);
+        let _ = self.thread.join();
+    }
+
+    /// Waits until the simulation ends by itself and its outputs are flushed.
+    pub fn wait(self) -> Status {
+        let _ = self.thread.join();
+        let status = *self.status.lock().unwrap();
+        drop(self.sender);
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn a_stall_catches_up_one_frame_at_most() {
+        // 10 steps per second, 4 steps per frame
+        let mut pacer = Pacer::new(Some(10.0));
+        let tau = pacer.tau.unwrap();
+        let later = pacer.last + Duration::from_secs(10);
+        assert_eq!(pacer.steps_due_at(later, 4), 4);
+        // of the 100 steps owed, one more frame's worth was kept
+        assert_eq!(pacer.steps_due_at(later, 4), 1);
+        assert_eq!(pacer.steps_due_at(later, 4), 0);
+        assert_eq!(pacer.steps_due_at(later + tau, 4), 1);
+    }
+
+    #[test]
+    fn restart_after_a_pause_owes_no_steps() {
+        let mut pacer = Pacer::new(Some(100.0));
+        let tau = pacer.tau.unwrap();
+        let start = pacer.last;
+        assert_eq!(pacer.steps_due_at(start + tau / 2, 4), 0);
+        // paused with half a step owed, for longer than any frame may catch up on
+        std::thread::sleep(tau * 6);
+        pacer.restart();
+        let resumed = pacer.last;
+        assert_eq!(pacer.steps_due_at(resumed, 4), 0);
+        assert_eq!(pacer.steps_due_at(resumed + tau * 9 / 10, 4), 0);
+        assert_eq!(pacer.steps_due_at(resumed + tau, 4), 1);
+    }
+
+    #[test]
+    fn frames_at_60_fps_hold_the_limit() {
+        // 100 steps per second over 10 s of frames
+        let mut pacer = Pacer::new(Some(100.0));
+        let start = pacer.last;
+        let frame = Duration::from_secs(1) / 60;
+        let mut steps = 0;
+        for index in 1..=600 {
+            let due = pacer.steps_due_at(start + frame * index, 4);
+            assert!((1..=2).contains(&due), "{} steps in frame {}", due, index);
+            steps += due;
+        }
+        assert!((999..=1000).contains(&steps), "{} steps", steps);
+    }
+
+    #[test]
+    fn waking_when_due_runs_one_step_per_period() {
+        // as the simulation thread does, sleeping for until_due between batches
+        let mut pacer = Pacer::new(Some(100.0));
+        let tau = pacer.tau.unwrap();
+        let start = pacer.last;
+        let mut now = start;
+        assert_eq!(pacer.until_due_at(now), tau);
+        for _ in 0..1000 {
+            now += pacer.until_due_at(now);
+            assert_eq!(pacer.steps_due_at(now, 4), 1);
+            assert_eq!(pacer.until_due_at(now), tau);
+        }
+        assert_eq!(now - start, tau * 1000);
+    }
+
+    #[test]
+    fn uncapped_runs_full_frames() {
+        for limit in [None, Some(0.0)] {
+            let mut pacer = Pacer::new(limit);
+            assert!(pacer.is_uncapped());
+            assert_eq!(pacer.steps_due(16), 16);
+            assert_eq!(pacer.steps_due(0), 1);
+        }
+    }
+}
@@ -0,0 +1,856 @@
+//! Sources as the simulation drives them, built from their settings, stepped into the fields
+//! and normalized to the power they radiate.
+
+use std::path::Path;
+
+use crate::mode_profile::mode_layer;
+use crate::{
+    fdtd, interpolator, open_csv, parse_csv_field, read_csv_record, replay,
+    settings::{Envelope, FDTDSettings, Normalization, SourceSettings, Tone},
+    step_fields,
+};
+
+/// Amplitude envelope of a source at `time`.
+pub fn source_envelope(
+    envelope: Envelope,
+    fwhm: f32,
+    delay: f32,
+    ramp_start: f32,
+    ramp_time: f32,
+    time: f32,
+) -> f32 {
+    match envelope {
+        Envelope::Gaussian => {
+            (-((std::f32::consts::PI * fwhm * (time - delay)).powi(2) / (4.0 * 2f32.ln())).powi(2))
+                .exp()
+        }
+        Envelope::Cw | Envelope::RampedCw => {
+            let ramp = (time - ramp_start) / ramp_time;
+            if ramp_time > 0.0 && ramp < 1.0 {
+                0.5 - 0.5 * (std::f32::consts::PI * ramp.max(0.0)).cos()
+            } else {
+                1.0
+            }
+        }
+    }
+}
+
+pub enum Source {
+    Texture {
+        source_bind_group: wgpu::BindGroup,
+        normal: fdtd::SliceMode,
+        layer: u32, // grid index along the normal, boundary layers included
+        waveform: Option<Vec<f32>>, // one sample per step, replaces carrier and envelope
+        tones: Vec<Tone>,
+        chirp_rate: f32,
+        delay: f32,
+        fwhm: f32,
+        envelope: Envelope,
+        ramp_time: f32,
+        active: std::ops::Range<f32>,
+        origin: usize, // index of the preset source
+        scale: f32,    // of the amplitude, set by normalization
+    },
+    Volume {
+        direction: [f32; 3],
+        waveform: Option<Vec<f32>>,
+        tones: Vec<Tone>,
+        chirp_rate: f32,
+        position: [f32; 3],
+        size: [f32; 3],
+        phase: f32,
+        delay: f32,
+        fwhm: f32,
+        power: f32,
+        envelope: Envelope,
+        ramp_time: f32,
+        active: std::ops::Range<f32>,
+        origin: usize,
+        scale: f32,
+    },
+    Replay {
+        source_bind_group: wgpu::BindGroup,
+        profiles: [wgpu::Texture; 2], // along the two plane axes, rewritten every step
+        normal: fdtd::SliceMode,
+        layer: u32,
+        stream: std::sync::Arc<replay::ReplayStream>, // shared by the E and H entries
+        sign: f32,                                    // of the normal the fields go along
+        active: std::ops::Range<f32>,
+        origin: usize,
+        scale: f32,
+    },
+}
+
+impl Source {
+    fn origin(&self) -> usize {
+        let (Source::Texture { origin, .. }
+        | Source::Volume { origin, .. }
+        | Source::Replay { origin, .. }) = self;
+        *origin
+    }
+
+    fn set_scale(&mut self, value: f32) {
+        let (Source::Texture { scale, .. }
+        | Source::Volume { scale, .. }
+        | Source::Replay { scale, .. }) = self;
+        *scale = value;
+    }
+
+    /// Complex carrier at `time`, the sum of `power * exp(i * (phase - 2 pi * cycles))` over
+    /// the tones, where `cycles` counts the periods elapsed since `delay` with the chirp applied.
+    fn carrier(&self, time: f32) -> nalgebra::Complex<f32> {
+        let (Source::Texture {
+            tones,
+            chirp_rate,
+            delay,
+            ..
+        }
+        | Source::Volume {
+            tones,
+            chirp_rate,
+            delay,
+            ..
+        }) = self
+        else {
+            return nalgebra::Complex::default();
+        };
+        let t = time - delay;
+        tones
+            .iter()
+            .map(|tone| {
+                let cycles = t / tone.wavelength + 0.5 * chirp_rate * t * t;
+                let (sin, cos) =
+                    (tone.phase.to_radians() - 2.0 * std::f32::consts::PI * cycles).sin_cos();
+                nalgebra::Complex::new(cos, sin) * tone.power
+            })
+            .sum()
+    }
+
+    /// Sample of the waveform at `step`, `None` for analytic sources.
+    fn waveform_sample(&self, step: u32) -> Option<f32> {
+        let (Source::Texture { waveform, .. } | Source::Volume { waveform, .. }) = self else {
+            return None;
+        };
+        waveform
+            .as_ref()
+            .map(|waveform| waveform.get(step as usize).copied().unwrap_or(0.0))
+    }
+
+    /// Whether the source injects nothing from `time` on: its window has closed, its waveform
+    /// or recording has run out or its Gaussian pulse has faded out.
+    pub fn is_finished(&self, time: f32, dt: f32) -> bool {
+        if let Source::Replay { stream, active, .. } = self {
+            return time >= active.end || stream.is_finished((time / dt).round() as u32);
+        }
+        let (Source::Texture {
+            waveform,
+            delay,
+            fwhm,
+            envelope,
+            active,
+            ..
+        }
+        | Source::Volume {
+            waveform,
+            delay,
+            fwhm,
+            envelope,
+            active,
+            ..
+        }) = self
+        else {
+            unreachable!()
+        };
+        if time >= active.end {
+            return true;
+        }
+        match (waveform, envelope) {
+            (Some(waveform), _) => time >= waveform.len() as f32 * dt,
+            (None, Envelope::Gaussian) => {
+                time > *delay
+                    && source_envelope(Envelope::Gaussian, *fwhm, *delay, 0.0, 0.0, time) < 1e-6
+            }
+            (None, Envelope::Cw | Envelope::RampedCw) => false,
+        }
+    }
+
+    /// Whether the source is injected at `time`, sources outside their window are not dispatched.
+    pub fn is_active(&self, time: f32) -> bool {
+        match self {
+            Source::Texture { active, .. }
+            | Source::Volume { active, .. }
+            | Source::Replay { active, .. } => active.contains(&time),
+        }
+    }
+}
+
+/// Loads a CSV waveform of `time, amplitude` rows after a header, shifted by `delay` and
+/// resampled onto the time steps. Steps outside the waveform are zero. The times are in the
+/// time unit of the preset, `speed_of_light` turns them into ct.
+pub fn load_waveform(
+    path: &Path,
+    dt: f32,
+    delay: f32,
+    speed_of_light: f32,
+) -> anyhow::Result<Vec<f32>> {
+    let mut data: Vec<(f64, [f32; 1])> = vec![];
+    for record in open_csv(path)?.records() {
+        let record = read_csv_record(path, record)?;
+        let time: f64 = parse_csv_field(path, &record, 0)?;
+        let amplitude: f32 = parse_csv_field(path, &record, 1)?;
+        if let Some(&(last, _)) = data.last() {
+            anyhow::ensure!(
+                time > last,
+                "{}, line {}: time stamps must increase, {} follows {}",
+                path.display(),
+                record.position().map_or(0, |position| position.line()),
+                time,
+                last
+            );
+        }
+        data.push((time * speed_of_light as f64, [amplitude]));
+    }
+    anyhow::ensure!(
+        data.len() >= 2,
+        "{}: a waveform needs at least two samples",
+        path.display()
+    );
+
+    let end = data.last().unwrap().0 + delay as f64;
+    let steps = (end / dt as f64).floor().max(0.0) as usize + 1;
+    let interpolator = interpolator::Linear1DInterpolator::new(data);
+    Ok((0..steps)
+        .map(|step| {
+            interpolator
+                .interpolate(step as f64 * dt as f64 - delay as f64)
+                .map_or(0.0, |[amplitude]| amplitude)
+        })
+        .collect())
+}
+
+/// Box two cells clear of a normalized source, through which its calibration run measures what
+/// it radiates. A mode source spans its whole plane. Faces normal to periodic and collapsed axes
+/// are left out, the flux through them cancels or vanishes.
+pub fn normalization_box(
+    settings: &FDTDSettings,
+    source: &SourceSettings,
+) -> anyhow::Result<fdtd::SurfaceBox> {
+    let padding = settings.boundary.padding();
+    let grid = fdtd::grid_dimension(settings.domain, settings.spatial_step, &settings.boundary);
+    let first = padding.map(|[low, _]| low as i64);
+    let last = [0, 1, 2].map(|axis| (grid[axis] - padding[axis][1]) as i64 - 1);
+    let (mut low, mut high) = match source.mode.normal() {
+        Some(normal) => {
+            let axis = normal.axis();
+            let layer = mode_layer(settings, source.position, normal)? as i64;
+            let (mut low, mut high) = (first, last);
+            (low[axis], high[axis]) = (layer, layer);
+            (low, high)
+        }
+        // the cells excite_source covers
+        None => {
+            let low = fdtd::world_to_grid(
+                settings.domain,
+                settings.spatial_step,
+                padding,
+                [0, 1, 2].map(|axis| source.position[axis] - source.size[axis] / 2.0),
+            )
+            .map(|c| c.ceil() as i64);
+            let high = [0, 1, 2].map(|axis| {
+                low[axis] + (source.size[axis] / settings.spatial_step).ceil().max(1.0) as i64 - 1
+            });
+            (low, high)
+        }
+    };
+    let open = settings
+        .dimension_mode
+        .collapsed_axes()
+        .iter()
+        .fold(settings.boundary.periodic_axes(), |mask, axis| {
+            mask | 1 << axis
+        });
+    let mut faces = 0;
+    for axis in 0..3 {
+        if (open >> axis) & 1 == 1 {
+            (low[axis], high[axis]) = (first[axis], last[axis]);
+            continue;
+        }
+        (low[axis], high[axis]) = (low[axis] - 2, high[axis] + 2);
+        anyhow::ensure!(
+            low[axis] > first[axis] && high[axis] < last[axis],
+            "Normalized source at {:?} needs three cells of the interior around it along {}",
+            source.position,
+            ["x", "y", "z"][axis]
+        );
+        faces |= 0b11 << (2 * axis);
+    }
+    Ok(fdtd::SurfaceBox {
+        low: low.map(|v| v as u32),
+        high: high.map(|v| v as u32),
+        faces,
+    })
+}
+
+/// Mode sources driving the electric and the magnetic field from the Ex, Ey, Ez, Hx, Hy, Hz
+/// profiles, `None` for a field none of whose components has a profile.
+#[allow(clippy::too_many_arguments)]
+pub fn mode_sources(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    placeholder: &wgpu::TextureView,
+    source: &SourceSettings,
+    origin: usize,
+    waveform: &Option<Vec<f32>>,
+    normal: fdtd::SliceMode,
+    layer: u32,
+    components: [Option<wgpu::TextureView>; 6],
+    dimension_mode: fdtd::DimensionMode,
+) -> [Option<Source>; 2] {
+    let [ex, ey, ez, hx, hy, hz] = components;
+    [
+        ([ex, ey, ez], fdtd::FieldType::E),
+        ([hx, hy, hz], fdtd::FieldType::H),
+    ]
+    .map(|(mut field, field_type)| {
+        // the 2D modes drop the components of the other polarization
+        for (component, kept) in field.iter_mut().zip(dimension_mode.components(field_type)) {
+            if !kept {
+                *component = None;
+            }
+        }
+        if field.iter().all(Option::is_none) {
+            return None;
+        }
+        let entries = [0, 1, 2].map(|i| wgpu::BindGroupEntry {
+            binding: i as u32,
+            resource: wgpu::BindingResource::TextureView(field[i].as_ref().unwrap_or(placeholder)),
+        });
+        Some(Source::Texture {
+            source_bind_group: device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout,
+                entries: &entries,
+            }),
+            tones: source.tones(),
+            chirp_rate: source.chirp_rate,
+            delay: source.delay,
+            fwhm: source.fwhm,
+            normal,
+            layer,
+            waveform: waveform.clone(),
+            envelope: source.envelope,
+            ramp_time: source.ramp_time,
+            active: source.active(),
+            origin,
+            scale: 1.0,
+        })
+    })
+}
+
+/// The interior cells of the plane a field recording or a replay source at `position` lies on.
+pub fn replay_plane(
+    settings: &FDTDSettings,
+    position: [f32; 3],
+    normal: fdtd::SliceMode,
+) -> anyhow::Result<replay::Plane> {
+    let padding = settings.boundary.padding();
+    let grid = fdtd::grid_dimension(settings.domain, settings.spatial_step, &settings.boundary);
+    let mut low = padding.map(|[low, _]| low);
+    low[normal.axis()] = mode_layer(settings, position, normal)?;
+    Ok(replay::Plane {
+        normal,
+        low,
+        size: normal
+            .plane_axes()
+            .map(|axis| grid[axis] - padding[axis][0] - padding[axis][1]),
+    })
+}
+
+/// The replay source driving `field` from `stream`, `None` when the 2D mode drops both of the
+/// field's components along the plane.
+#[allow(clippy::too_many_arguments)]
+pub fn replay_source(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    placeholder: &wgpu::TextureView,
+    source: &SourceSettings,
+    origin: usize,
+    plane: &replay::Plane,
+    stream: std::sync::Arc<replay::ReplayStream>,
+    backward: bool,
+    field: fdtd::FieldType,
+    dimension_mode: fdtd::DimensionMode,
+) -> Option<Source> {
+    let kept = dimension_mode.components(field);
+    let axes = plane.normal.plane_axes();
+    if !axes.iter().any(|axis| kept[*axis]) {
+        return None;
+    }
+    let profiles = axes.map(|_| {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Replay Profile"),
+            size: wgpu::Extent3d {
+                width: plane.size[0],
+                height: plane.size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rg32Float,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        })
+    });
+    let views = profiles
+        .each_ref()
+        .map(|profile| profile.create_view(&wgpu::TextureViewDescriptor::default()));
+    let entries = [0, 1, 2].map(|i| wgpu::BindGroupEntry {
+        binding: i as u32,
+        resource: wgpu::BindingResource::TextureView(
+            match axes.iter().position(|axis| *axis == i) {
+                Some(index) if kept[i] => &views[index],
+                _ => placeholder,
+            },
+        ),
+    });
+    Some(Source::Replay {
+        source_bind_group: device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &entries,
+        }),
+        profiles,
+        normal: plane.normal,
+        layer: plane.low[plane.normal.axis()],
+        stream,
+        sign: if backward { -1.0 } else { 1.0 },
+        active: source.active(),
+        origin,
+        scale: 1.0,
+    })
+}
+
+pub fn excite_source(
+    device: &wgpu::Device,
+    fdtd: &fdtd::FDTD,
+    encoder: &mut wgpu::CommandEncoder,
+    settings: &FDTDSettings,
+    source: &Source,
+    field: fdtd::FieldType,
+    step: u32,
+) {
+    match source {
+        Source::Texture {
+            source_bind_group,
+            normal,
+            layer,
+            delay,
+            fwhm,
+            envelope,
+            ramp_time,
+            active,
+            scale,
+            ..
+        } => {
+            let pulse_envelope = source_envelope(
+                *envelope,
+                *fwhm,
+                *delay,
+                active.start,
+                *ramp_time,
+                step as f32 * settings.temporal_step(),
+            );
+
+            let mut position = settings.boundary.padding().map(|[low, _]| low);
+            position[normal.axis()] = *layer;
+
+            // a waveform scales the real part of the mode
+            let (pulse_envelope, phasor) = match source.waveform_sample(step) {
+                Some(sample) => (1.0, (0.0, sample)),
+                None => {
+                    let carrier = source.carrier(step as f32 * settings.temporal_step());
+                    (pulse_envelope, (carrier.im, carrier.re))
+                }
+            };
+            let pulse_envelope = pulse_envelope * scale;
+
+            match field {
+                fdtd::FieldType::E => fdtd.excite_electric_field_mode(
+                    encoder,
+                    position,
+                    *normal,
+                    phasor,
+                    pulse_envelope,
+                    source_bind_group,
+                ),
+                fdtd::FieldType::H => fdtd.excite_magnetic_field_mode(
+                    encoder,
+                    position,
+                    *normal,
+                    phasor,
+                    pulse_envelope,
+                    source_bind_group,
+                ),
+            }
+        }
+        Source::Volume {
+            direction,
+            position,
+            size,
+            phase,
+            delay,
+            fwhm,
+            power,
+            envelope,
+            ramp_time,
+            active,
+            scale,
+            ..
+        } => {
+            let pulse_envelope = source_envelope(
+                *envelope,
+                *fwhm,
+                *delay,
+                active.start,
+                *ramp_time,
+                step as f32 * settings.temporal_step(),
+            );
+
+            // complex, the imaginary part only drives the imaginary half of Bloch fields
+            let (sin_phase, cos_phase) = phase.to_radians().sin_cos();
+            let signal = match source.waveform_sample(step) {
+                Some(sample) => nalgebra::Complex::new(sample, 0.0),
+                None => {
+                    source.carrier(step as f32 * settings.temporal_step())
+                        * nalgebra::Complex::new(cos_phase, sin_phase)
+                        * pulse_envelope
+                }
+            };
+
+            let direction = nalgebra::Vector3::from(*direction).normalize();
+            let cells = fdtd::box_cells(
+                settings.domain,
+                settings.spatial_step,
+                settings.boundary.padding(),
+                *position,
+                *size,
+            )
+            .map(|cells| cells.start.max(0) as u32..cells.end.max(0) as u32);
+            let actual_position = cells.clone().map(|cells| cells.start);
+            let actual_size = cells.map(|cells| cells.len() as u32);
+            let kept = settings.dimension_mode.components(field);
+            let [excitation, quadrature] = [signal.re, signal.im].map(|signal| {
+                let excitation: [f32; 3] = (direction * signal * *power * *scale).into();
+                [0, 1, 2].map(|i| if kept[i] { excitation[i] } else { 0.0 })
+            });
+
+            match field {
+                fdtd::FieldType::E => fdtd.excite_electric_field_volume(
+                    encoder,
+                    actual_position,
+                    actual_size,
+                    (excitation, quadrature),
+                ),
+                fdtd::FieldType::H => fdtd.excite_magnetic_field_volume(
+                    encoder,
+                    actual_position,
+                    actual_size,
+                    (excitation, quadrature),
+                ),
+            }
+        }
+        Source::Replay {
+            source_bind_group,
+            profiles,
+            normal,
+            layer,
+            stream,
+            sign,
+            scale,
+            ..
+        } => {
+            if !stream.upload(
+                device,
+                encoder,
+                profiles,
+                field,
+                *sign,
+                settings.spatial_step,
+                step,
+            ) {
+                return;
+            }
+            let mut position = settings.boundary.padding().map(|[low, _]| low);
+            position[normal.axis()] = *layer;
+            // the profiles are real
+            match field {
+                fdtd::FieldType::E => fdtd.excite_electric_field_mode(
+                    encoder,
+                    position,
+                    *normal,
+                    (0.0, 1.0),
+                    *scale,
+                    source_bind_group,
+                ),
+                fdtd::FieldType::H => fdtd.excite_magnetic_field_mode(
+                    encoder,
+                    position,
+                    *normal,
+                    (0.0, 1.0),
+                    *scale,
+                    source_bind_group,
+                ),
+            }
+        }
+    }
+}
+
+/// Runs every normalized source alone from zero fields, measures what it radiates through its
+/// flux box and scales it so that this is its `power`. Light is given twice the time to cross
+/// the box in the background material to get out. The fields are left reset.
+pub fn normalize_sources(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    fdtd: &fdtd::FDTD,
+    settings: &FDTDSettings,
+    magnetic_sources: &mut [Source],
+    electric_sources: &mut [Source],
+) -> anyhow::Result<()> {
+    let dt = settings.temporal_step();
+    let normalized = settings
+        .sources
+        .iter()
+        .enumerate()
+        .filter_map(|(index, source)| source.normalize.map(|mode| (index, source, mode)));
+    for (flux, (index, source, mode)) in normalized.enumerate() {
+        let surface = normalization_box(settings, source)?;
+        let diagonal = (0..3)
+            .map(|axis| ((surface.high[axis] - surface.low[axis] + 1) as f32).powi(2))
+            .sum::<f32>()
+            .sqrt();
+        let crossing =
+            2.0 * diagonal * settings.spatial_step * settings.background.refractive_index();
+        let magnetic: Vec<&Source> = magnetic_sources
+            .iter()
+            .filter(|entry| entry.origin() == index)
+            .collect();
+        let electric: Vec<&Source> = electric_sources
+            .iter()
+            .filter(|entry| entry.origin() == index)
+            .collect();
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        fdtd.reset(&mut encoder)?;
+        queue.submit(Some(encoder.finish()));
+        let run = |steps: std::ops::Range<u32>| {
+            for batch in steps.clone().step_by(256) {
+                let mut encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+                for step in batch..(batch + 256).min(steps.end) {
+                    step_fields(
+                        device,
+                        fdtd,
+                        &mut encoder,
+                        settings,
+                        magnetic.iter().copied(),
+                        electric.iter().copied(),
+                        step,
+                    );
+                    fdtd.accumulate_flux(&mut encoder, flux);
+                }
+                queue.submit(Some(encoder.finish()));
+                device.poll(wgpu::Maintain::Wait);
+            }
+        };
+        let measured = match mode {
+            Normalization::Energy => {
+                let mut end = 0;
+                while !magnetic
+                    .iter()
+                    .chain(&electric)
+                    .all(|entry| entry.is_finished(end as f32 * dt, dt))
+                {
+                    end += 1;
+                }
+                run(0..end + (crossing / dt).ceil() as u32);
+                fdtd.read_flux(device, queue, flux)?.iter().sum::<f64>()
+            }
+            Normalization::Power => {
+                let settled = source.start_time + source.ramp_time + crossing;
+                let window = 10.0
+                    * source
+                        .tones()
+                        .iter()
+                        .map(|tone| tone.wavelength)
+                        .fold(0.0, f32::max);
+                let start = (settled / dt).ceil() as u32;
+                let end = ((settled + window) / dt).ceil() as u32;
+                run(0..start);
+                let before = fdtd.read_flux(device, queue, flux)?.iter().sum::<f64>();
+                run(start..end);
+                let after = fdtd.read_flux(device, queue, flux)?.iter().sum::<f64>();
+                (after - before) / ((end - start) as f64 * dt as f64)
+            }
+        };
+        let scale = normalization_scale(source.power, measured).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Source {} at {:?} radiated nothing through its calibration box, it cannot be normalized",
+                index,
+                source.position
+            )
+        })?;
+        println!(
+            "Source {} at {:?} radiated {} {:e} in its calibration run, scaled by {} (power = {} without normalize)",
+            index,
+            source.position,
+            match mode {
+                Normalization::Energy => "an energy of",
+                Normalization::Power => "a power of",
+            },
+            measured,
+            scale,
+            source.power * scale
+        );
+        for entry in magnetic_sources
+            .iter_mut()
+            .chain(electric_sources.iter_mut())
+            .filter(|entry| entry.origin() == index)
+        {
+            entry.set_scale(scale);
+        }
+    }
+    if settings
+        .sources
+        .iter()
+        .any(|source| source.normalize.is_some())
+    {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        fdtd.reset(&mut encoder)?;
+        queue.submit(Some(encoder.finish()));
+    }
+    Ok(())
+}
+
+/// Amplitude factor that makes a source which radiated `measured` radiate `target`, energy and
+/// power going with the square of the amplitude. None when nothing was measured.
+fn normalization_scale(target: f32, measured: f64) -> Option<f32> {
+    (measured.is_finite() && measured > 0.0).then(|| (target as f64 / measured).sqrt() as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::ComplexField;
+
+    use super::*;
+
+    /// A Gaussian volume source with the given carrier.
+    fn volume_source(tones: Vec<Tone>, chirp_rate: f32, delay: f32) -> Source {
+        Source::Volume {
+            direction: [0.0, 0.0, 1.0],
+            waveform: None,
+            tones,
+            chirp_rate,
+            position: [0.0; 3],
+            size: [0.0; 3],
+            phase: 0.0,
+            delay,
+            fwhm: 1.0,
+            power: 1.0,
+            envelope: Envelope::Gaussian,
+            ramp_time: 0.0,
+            active: 0.0..f32::INFINITY,
+            origin: 0,
+            scale: 1.0,
+        }
+    }
+
+    fn tone(wavelength: f32, power: f32, phase: f32) -> Tone {
+        Tone {
+            wavelength,
+            power,
+            phase,
+        }
+    }
+
+    #[test]
+    fn normalized_sources_radiate_their_power() {
+        // a source of amplitude a radiating 0.3 a², asked for 2
+        let radiated = |amplitude: f32| 0.3 * amplitude as f64 * amplitude as f64;
+        for amplitude in [1.0, 1e-3, 250.0] {
+            let scale = normalization_scale(2.0, radiated(amplitude)).unwrap();
+            let rescaled = radiated(amplitude * scale);
+            assert!(
+                (rescaled - 2.0).abs() < 1e-5,
+                "{} radiates {}",
+                amplitude,
+                rescaled
+            );
+        }
+        for measured in [0.0, -1e-9, f64::NAN, f64::INFINITY] {
+            assert_eq!(normalization_scale(2.0, measured), None);
+        }
+    }
+
+    #[test]
+    fn carrier_starts_at_the_tone_phase() {
+        let source = volume_source(vec![tone(1.0, 2.0, 90.0)], 0.0, 3.0);
+        let carrier = source.carrier(3.0);
+        assert!((carrier.modulus() - 2.0).abs() < 1e-5);
+        assert!((carrier.argument() - std::f32::consts::FRAC_PI_2).abs() < 1e-5);
+    }
+
+    #[test]
+    fn chirped_carrier_sweeps_its_frequency_without_phase_jumps() {
+        let [wavelength, chirp_rate, delay, dt] = [1.0, 0.2, 2.0, 0.01];
+        let source = volume_source(vec![tone(wavelength, 1.0, 0.0)], chirp_rate, delay);
+        let mut previous = source.carrier(0.0);
+        for step in 1..1000 {
+            let time = step as f32 * dt;
+            let carrier = source.carrier(time);
+            assert!(
+                (carrier.modulus() - 1.0).abs() < 1e-4,
+                "amplitude at {}",
+                time
+            );
+            // the phase turns back by the instantaneous frequency times the step, from 0.6
+            // before the delay up to 2.6 at the end
+            let frequency = 1.0 / wavelength + chirp_rate * (time - 0.5 * dt - delay);
+            let turned = (carrier / previous).argument();
+            assert!(
+                (turned + 2.0 * std::f32::consts::PI * frequency * dt).abs() < 5e-3,
+                "phase turned by {} at {}",
+                turned,
+                time
+            );
+            previous = carrier;
+        }
+    }
+
+    #[test]
+    fn tones_sum_into_a_continuous_carrier() {
+        let tones = vec![tone(1.0, 1.0, 0.0), tone(0.8, 0.5, 90.0)];
+        let source = volume_source(tones.clone(), 0.0, 1.0);
+        let singles: Vec<Source> = tones
+            .into_iter()
+            .map(|tone| volume_source(vec![tone], 0.0, 1.0))
+            .collect();
+        let dt = 0.01;
+        // no sample moves further than the tones can turn in a step, through the beats too
+        let bound = 2.0 * std::f32::consts::PI * dt * (1.0 / 1.0 + 0.5 / 0.8);
+        let mut previous = source.carrier(0.0);
+        for step in 1..1000 {
+            let time = step as f32 * dt;
+            let carrier = source.carrier(time);
+            let sum: nalgebra::Complex<f32> =
+                singles.iter().map(|single| single.carrier(time)).sum();
+            assert!((carrier - sum).modulus() < 1e-5, "sum at {}", time);
+            assert!(
+                (carrier - previous).modulus() <= bound * 1.001,
+                "jump at {}",
+                time
+            );
+            previous = carrier;
+        }
+    }
+}
@@ -0,0 +1,17 @@
+//! Temporary files of the unit tests.
+
+use std::path::PathBuf;
+
+/// Path of `name` in a temporary directory of this test run, named after the test using it.
+pub fn temp_path(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("grems-unit-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    directory.join(name)
+}
+
+/// Writes `contents` to `name` in the temporary directory of this test run.
+pub fn temp_file(name: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+    let path = temp_path(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
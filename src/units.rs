@@ -0,0 +1,109 @@
+//! Physical units of a preset. Without them lengths and times share one unit and the speed
+//! of light is 1, so times are given as ct.
+
+/// Speed of light in vacuum, in m/s.
+const SPEED_OF_LIGHT: f64 = 299_792_458.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LengthUnit {
+    M,
+    Mm,
+    Um,
+    Nm,
+}
+
+impl LengthUnit {
+    fn meters(self) -> f64 {
+        match self {
+            LengthUnit::M => 1.0,
+            LengthUnit::Mm => 1e-3,
+            LengthUnit::Um => 1e-6,
+            LengthUnit::Nm => 1e-9,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeUnit {
+    S,
+    Ms,
+    Us,
+    Ns,
+    Ps,
+    Fs,
+}
+
+impl TimeUnit {
+    fn seconds(self) -> f64 {
+        match self {
+            TimeUnit::S => 1.0,
+            TimeUnit::Ms => 1e-3,
+            TimeUnit::Us => 1e-6,
+            TimeUnit::Ns => 1e-9,
+            TimeUnit::Ps => 1e-12,
+            TimeUnit::Fs => 1e-15,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            TimeUnit::S => "s",
+            TimeUnit::Ms => "ms",
+            TimeUnit::Us => "us",
+            TimeUnit::Ns => "ns",
+            TimeUnit::Ps => "ps",
+            TimeUnit::Fs => "fs",
+        }
+    }
+}
+
+/// `units = { length = "um", time = "fs" }` in a preset. Lengths, wavelengths included, stay
+/// in the length unit, times are converted to ct when the preset is loaded.
+#[derive(Clone, Copy, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Units {
+    pub length: LengthUnit,
+    pub time: TimeUnit,
+}
+
+impl Units {
+    /// In length units per time unit.
+    pub fn speed_of_light(&self) -> f32 {
+        (SPEED_OF_LIGHT * self.time.seconds() / self.length.meters()) as f32
+    }
+}
+
+/// The speed of light of a preset, 1 without units.
+pub fn speed_of_light(units: Option<&Units>) -> f32 {
+    units.map_or(1.0, Units::speed_of_light)
+}
+
+/// Describes an internal time `ct`, in the time unit of the preset when it has one.
+pub fn describe_time(units: Option<&Units>, ct: f32) -> String {
+    match units {
+        Some(units) => format!(
+            "t = {:.3} {}",
+            ct / units.speed_of_light(),
+            units.time.label()
+        ),
+        None => format!("ct = {:.3}", ct),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn light_covers_about_300_nm_per_fs() {
+        let units = |length, time| Units { length, time };
+        let c = units(LengthUnit::Nm, TimeUnit::Fs).speed_of_light();
+        assert!((c - 299.792_46).abs() < 1e-3, "{} nm/fs", c);
+        let c = units(LengthUnit::Um, TimeUnit::Ps).speed_of_light();
+        assert!((c - 299.792_46).abs() < 1e-3, "{} um/ps", c);
+        let c = units(LengthUnit::M, TimeUnit::S).speed_of_light();
+        assert_eq!(c, SPEED_OF_LIGHT as f32);
+        assert_eq!(speed_of_light(None), 1.0);
+    }
+}
@@ -0,0 +1,610 @@
+//! The window a run is shown in, with the key bindings and mouse controls of the view.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use wgpu_text::{
+    glyph_brush::{Section as TextSection, Text},
+    BrushBuilder,
+};
+use winit::{
+    event::{ElementState, KeyEvent},
+    keyboard::PhysicalKey,
+};
+
+use crate::settings::ResonanceSettings;
+use crate::{export, fdtd, plot, probe, readout, resonance, screenshot, simulation, units};
+
+/// Names the preset so windows of different runs can be told apart, and marks a paused run.
+fn window_title(preset: &str, status: &simulation::Status) -> String {
+    let name = Path::new(preset)
+        .file_stem()
+        .map_or(preset.into(), |stem| stem.to_string_lossy());
+    if status.paused {
+        format!("GREMS - {} [PAUSED at step {}]", name, status.step)
+    } else {
+        format!("GREMS - {}", name)
+    }
+}
+
+/// The simulation a window shows and steers, with what the key bindings need from the
+/// settings the simulation thread took.
+pub struct Session {
+    pub device: Arc<wgpu::Device>,
+    pub queue: Arc<wgpu::Queue>,
+    pub fdtd: Arc<Mutex<fdtd::FDTD>>,
+    pub traces: probe::Traces,
+    pub preset: String,
+    pub step_batch: u32, // steps advanced by Ctrl+Shift+N
+    pub temporal_step: f32,
+    pub units: Option<units::Units>,
+    pub resonance: ResonanceSettings,
+    pub probe_history: usize,
+    pub simulation: simulation::Simulation,
+}
+
+/// Opened before the adapter is picked, so that the adapter can present to it.
+pub struct Window {
+    event_loop: winit::event_loop::EventLoop<()>,
+    window: Arc<winit::window::Window>,
+    surface: wgpu::Surface,
+}
+
+impl Window {
+    pub fn new(instance: &wgpu::Instance, preset: &str) -> anyhow::Result<Self> {
+        let event_loop = winit::event_loop::EventLoop::new()?;
+        let window = Arc::new(
+            winit::window::WindowBuilder::new()
+                .with_title(window_title(preset, &simulation::Status::default()))
+                .build(&event_loop)?,
+        );
+        let surface = unsafe { instance.create_surface(&window)? };
+        Ok(Self {
+            event_loop,
+            window,
+            surface,
+        })
+    }
+
+    pub fn surface(&self) -> &wgpu::Surface {
+        &self.surface
+    }
+
+    /// For the simulation thread to tell when there is something new to draw.
+    pub fn proxy(&self) -> winit::event_loop::EventLoopProxy<()> {
+        self.event_loop.create_proxy()
+    }
+
+    /// Shows the session until the window is closed or the simulation finishes.
+    pub fn run(
+        self,
+        options: &crate::GremOptions,
+        adapter: &wgpu::Adapter,
+        format: wgpu::TextureFormat,
+        session: Session,
+    ) -> anyhow::Result<()> {
+        let Self {
+            event_loop,
+            window,
+            surface,
+        } = self;
+        let Session {
+            device,
+            queue,
+            fdtd,
+            traces,
+            preset,
+            step_batch,
+            temporal_step,
+            units,
+            resonance: resonance_settings,
+            probe_history,
+            simulation,
+        } = session;
+        let caps = surface.get_capabilities(adapter);
+
+        let mut surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: window.inner_size().width,
+            height: window.inner_size().height,
+            present_mode: options.present_mode.select(&caps.present_modes),
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            // views are only ever created in the surface format itself
+            view_formats: vec![],
+        };
+
+        surface.configure(&device, &surface_config);
+
+        let mut brush =
+            BrushBuilder::using_font_bytes(include_bytes!("../fonts/Roboto-Regular.ttf"))?.build(
+                &device,
+                surface_config.width,
+                surface_config.height,
+                surface_config.format,
+            );
+
+        let mut trace_plot = plot::TracePlot::new(&device, surface_config.format, probe_history);
+        // index of the plotted probe, None hides the plot
+        let mut shown_probe = (!traces.lock().unwrap().is_empty()).then_some(0);
+
+        // taken on exit so outstanding exports and recordings are flushed exactly once
+        let mut simulation = Some(simulation);
+        let mut status = simulation.as_ref().unwrap().status();
+        let mut title = window.title();
+
+        let mut last_display_step = status.step;
+        let mut last_display_time = std::time::Instant::now();
+        let mut steps_per_second = 0f32;
+        let mut frames_per_second = 0f32;
+        let mut frame_counter = 0u32;
+        let show_fps_duration = std::time::Duration::from_secs_f32(1f32);
+
+        let mut ctrl_pressed = false;
+        let mut shift_pressed = false;
+        let mut cursor_position: Option<[f32; 2]> = None;
+        // cursor x and normalized slice position where a middle or Ctrl+left drag started
+        let mut slice_drag: Option<(f32, f32)> = None;
+        let mut pan_drag = false;
+        let mut screenshot_requested = false;
+        let mut readout = readout::TexelReadout::new(&device);
+
+        event_loop.run(move |event, target| match event {
+        winit::event::Event::WindowEvent { window_id, event } if window_id == window.id() => {
+            match event {
+                winit::event::WindowEvent::CloseRequested => {
+                    if let Some(simulation) = simulation.take() {
+                        simulation.join();
+                    }
+                    target.exit();
+                }
+                winit::event::WindowEvent::Resized(new_size) => {
+                    if new_size.width > 0 && new_size.height > 0 {
+                        surface_config.width = new_size.width;
+                        surface_config.height = new_size.height;
+                        surface.configure(&device, &surface_config);
+                        brush.resize_view(new_size.width as f32, new_size.height as f32, &queue);
+                        window.request_redraw();
+                    }
+                },
+                winit::event::WindowEvent::MouseWheel { delta, .. } => {
+                    let row = match delta {
+                        winit::event::MouseScrollDelta::LineDelta(_, row) => row,
+                        // touchpads scroll by fractions of a notch, moving everything smoothly
+                        winit::event::MouseScrollDelta::PixelDelta(position) => {
+                            position.y as f32 / options.scroll_pixels_per_line
+                        }
+                    };
+                    let mut fdtd = fdtd.lock().unwrap();
+                    if ctrl_pressed && fdtd.is_volume_view() {
+                        fdtd.dolly_camera(row);
+                    } else if ctrl_pressed {
+                        let window_size = [surface_config.width as f32, surface_config.height as f32];
+                        fdtd.zoom_view(
+                            row,
+                            cursor_position.unwrap_or(window_size.map(|size| size / 2.0)),
+                            window_size,
+                        );
+                    } else {
+                        fdtd.offset_slice_position(if shift_pressed { row * 10.0 } else { row });
+                    }
+                    window.request_redraw();
+                }
+                winit::event::WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    button,
+                    ..
+                } if button == winit::event::MouseButton::Middle
+                    || (button == winit::event::MouseButton::Left && ctrl_pressed) =>
+                {
+                    if let Some([x, _]) = cursor_position {
+                        slice_drag = Some((x, fdtd.lock().unwrap().get_slice_position_normalized()));
+                    }
+                }
+                winit::event::WindowEvent::MouseInput {
+                    state,
+                    button: winit::event::MouseButton::Right,
+                    ..
+                } => {
+                    pan_drag = state == ElementState::Pressed;
+                }
+                winit::event::WindowEvent::MouseInput {
+                    state: ElementState::Released,
+                    ..
+                } => {
+                    slice_drag = None;
+                }
+                winit::event::WindowEvent::CursorMoved { position, .. } => {
+                    let position = [position.x as f32, position.y as f32];
+                    if let (true, Some(previous)) = (pan_drag, cursor_position) {
+                        let delta = [position[0] - previous[0], position[1] - previous[1]];
+                        let window_size = [surface_config.width as f32, surface_config.height as f32];
+                        let mut fdtd = fdtd.lock().unwrap();
+                        // the right drag turns the camera around the grid in the volume view
+                        if fdtd.is_volume_view() {
+                            fdtd.orbit_camera(delta, window_size);
+                        } else {
+                            fdtd.pan_view(delta, window_size);
+                        }
+                    }
+                    cursor_position = Some(position);
+                    // the window width spans the whole axis
+                    if let Some((start, slice_position)) = slice_drag {
+                        fdtd.lock().unwrap().set_slice_position_normalized(
+                            slice_position + (position[0] - start) / surface_config.width as f32,
+                        );
+                    }
+                    window.request_redraw();
+                }
+                winit::event::WindowEvent::CursorLeft { .. } => {
+                    cursor_position = None;
+                    slice_drag = None;
+                    pan_drag = false;
+                    window.request_redraw();
+                }
+                winit::event::WindowEvent::KeyboardInput {
+                    event: KeyEvent {
+                        physical_key: PhysicalKey::Code(keycode),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                    ..
+                } if ctrl_pressed => match keycode {
+                    winit::keyboard::KeyCode::Space => {
+                        if let Some(simulation) = &simulation {
+                            simulation.send(simulation::Command::TogglePause);
+                        }
+                    },
+                    winit::keyboard::KeyCode::KeyN => {
+                        if let Some(simulation) = &simulation {
+                            simulation.send(simulation::Command::Step(if shift_pressed { step_batch } else { 1 }));
+                        }
+                    }
+                    winit::keyboard::KeyCode::Digit0 => {
+                        if let Some(simulation) = &simulation {
+                            simulation.send(simulation::Command::Reset);
+                        }
+                    }
+                    // Ctrl+0 already resets, so the magnitude sits next to the components
+                    winit::keyboard::KeyCode::Digit1
+                    | winit::keyboard::KeyCode::Digit2
+                    | winit::keyboard::KeyCode::Digit3
+                    | winit::keyboard::KeyCode::Digit4 => {
+                        fdtd.lock().unwrap().set_display_component(match keycode {
+                            winit::keyboard::KeyCode::Digit1 => fdtd::DisplayComponent::X,
+                            winit::keyboard::KeyCode::Digit2 => fdtd::DisplayComponent::Y,
+                            winit::keyboard::KeyCode::Digit3 => fdtd::DisplayComponent::Z,
+                            _ => fdtd::DisplayComponent::Magnitude,
+                        });
+                        window.request_redraw();
+                    }
+                    winit::keyboard::KeyCode::KeyI => {
+                        // Shift restarts the average, e.g. once the transient has passed
+                        if shift_pressed {
+                            fdtd.lock().unwrap().restart_intensity();
+                        } else {
+                            fdtd.lock().unwrap().toggle_intensity_view();
+                        }
+                        window.request_redraw();
+                    }
+                    winit::keyboard::KeyCode::KeyO => {
+                        fdtd.lock().unwrap().toggle_material_overlay();
+                        window.request_redraw();
+                    }
+                    winit::keyboard::KeyCode::KeyP => {
+                        // cycles through the probes, then hides the plot
+                        let probes = traces.lock().unwrap().len();
+                        shown_probe = match shown_probe {
+                            Some(index) if index + 1 < probes => Some(index + 1),
+                            Some(_) => None,
+                            None => (probes > 0).then_some(0),
+                        };
+                        window.request_redraw();
+                    }
+                    winit::keyboard::KeyCode::KeyQ => {
+                        // the resonances of the probe traces held so far
+                        for trace in traces.lock().unwrap().iter() {
+                            let samples: Vec<(u32, f32)> = trace.samples.iter().copied().collect();
+                            resonance::print(
+                                &trace.name,
+                                &resonance::analyse(&samples, temporal_step, &resonance_settings),
+                            );
+                        }
+                    }
+                    winit::keyboard::KeyCode::KeyT => {
+                        fdtd.lock().unwrap().toggle_volume_view();
+                        window.request_redraw();
+                    }
+                    winit::keyboard::KeyCode::KeyV => {
+                        fdtd.lock().unwrap().reset_view();
+                        window.request_redraw();
+                    }
+                    winit::keyboard::KeyCode::KeyF => {
+                        fdtd.lock().unwrap().toggle_stretch();
+                        window.request_redraw();
+                    }
+                    winit::keyboard::KeyCode::KeyX => {
+                        fdtd.lock().unwrap().set_slice_mode(fdtd::SliceMode::X);
+                        window.request_redraw();
+                    },
+                    winit::keyboard::KeyCode::KeyY => {
+                        fdtd.lock().unwrap().set_slice_mode(fdtd::SliceMode::Y);
+                        window.request_redraw();
+                    },
+                    winit::keyboard::KeyCode::KeyZ => {
+                        fdtd.lock().unwrap().set_slice_mode(fdtd::SliceMode::Z);
+                        window.request_redraw();
+                    }
+                    winit::keyboard::KeyCode::KeyE => {
+                        fdtd.lock().unwrap().set_field_view_mode(fdtd::FieldType::E);
+                        window.request_redraw();
+                    }
+                    winit::keyboard::KeyCode::KeyH => {
+                        fdtd.lock().unwrap().set_field_view_mode(fdtd::FieldType::H);
+                        window.request_redraw();
+                    }
+                    winit::keyboard::KeyCode::KeyM => {
+                        fdtd.lock().unwrap().toggle_material_view();
+                        window.request_redraw();
+                    }
+                    winit::keyboard::KeyCode::KeyC => {
+                        fdtd.lock().unwrap().cycle_colormap(&device, surface_config.format);
+                        window.request_redraw();
+                    }
+                    winit::keyboard::KeyCode::KeyR => {
+                        if let Some(simulation) = &simulation {
+                            simulation.send(simulation::Command::ToggleRecording);
+                        }
+                    }
+                    winit::keyboard::KeyCode::KeyS => {
+                        screenshot_requested = true;
+                        window.request_redraw();
+                    }
+                    winit::keyboard::KeyCode::KeyD => {
+                        // the viewed field right now, Shift limits it to the slice on screen, 1D writes the column
+                        let fdtd = fdtd.lock().unwrap();
+                        let region = if fdtd.dimension_mode() == fdtd::DimensionMode::OneD {
+                            export::Region::line(&fdtd)
+                        } else if shift_pressed {
+                            let (mode, index) = fdtd.get_current_slice();
+                            export::Region::Slice(mode, index)
+                        } else {
+                            export::Region::Volume
+                        };
+                        if let Some(simulation) = &simulation {
+                            simulation.send(simulation::Command::Export(fdtd.get_field_view_mode(), region));
+                        }
+                    }
+                    winit::keyboard::KeyCode::KeyL => {
+                        fdtd.lock().unwrap().toggle_log_scale();
+                        window.request_redraw();
+                    }
+                    winit::keyboard::KeyCode::ArrowLeft => {
+                        let mut fdtd = fdtd.lock().unwrap();
+                        if fdtd.is_log_scale() {
+                            fdtd.offset_log_floor(-1.0);
+                        } else {
+                            fdtd.scale_linear(-1.0);
+                        }
+                        window.request_redraw();
+                    }
+                    winit::keyboard::KeyCode::ArrowRight => {
+                        let mut fdtd = fdtd.lock().unwrap();
+                        if fdtd.is_log_scale() {
+                            fdtd.offset_log_floor(1.0);
+                        } else {
+                            fdtd.scale_linear(1.0);
+                        }
+                        window.request_redraw();
+                    }
+                    winit::keyboard::KeyCode::ArrowUp => {
+                        fdtd.lock().unwrap().scale_exponential(1);
+                        window.request_redraw();
+                    }
+                    winit::keyboard::KeyCode::ArrowDown => {
+                        fdtd.lock().unwrap().scale_exponential(-1);
+                        window.request_redraw();
+                    }
+                    _ => (),
+                }
+                winit::event::WindowEvent::ModifiersChanged(modifiers) => {
+                    ctrl_pressed = modifiers.state().control_key();
+                    shift_pressed = modifiers.state().shift_key();
+                }
+                winit::event::WindowEvent::DroppedFile(file) => {
+                    if let Err(err) = fdtd.lock().unwrap().reload_shader(file, &device, surface_config.format) {
+                        eprintln!("{}", err);
+                    }
+                    window.request_redraw();
+                }
+                winit::event::WindowEvent::RedrawRequested => {
+                    readout.poll(&device);
+                    if let Some(simulation) = &simulation {
+                        let latest = simulation.status();
+                        if latest.step != status.step {
+                            readout.invalidate();
+                        }
+                        status = latest;
+                    }
+                    let latest_title = window_title(&preset, &status);
+                    if latest_title != title {
+                        window.set_title(&latest_title);
+                        title = latest_title;
+                    }
+
+                    // acquired before locking so the simulation thread never waits on the swapchain
+                    let surface_texture = match surface.get_current_texture() {
+                        Ok(texture) => texture,
+                        Err(err) => match err {
+                            wgpu::SurfaceError::Timeout => {
+                                return;
+                            }
+                            wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost => {
+                                surface.configure(&device, &surface_config);
+                                return;
+                            }
+                            wgpu::SurfaceError::OutOfMemory => panic!("OUT OF MEMORY!"),
+                        },
+                    };
+                    let surf_texture_view = surface_texture
+                        .texture
+                        .create_view(&wgpu::TextureViewDescriptor::default());
+
+                    let fdtd = fdtd.lock().unwrap();
+                    fdtd.begin_frame();
+                    let mut encoder =
+                        device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+
+                    let cursor_texel = cursor_position.and_then(|cursor| {
+                        fdtd.texel_at_cursor(cursor, [surface_config.width as f32, surface_config.height as f32])
+                    });
+                    readout.request(&fdtd, &mut encoder, cursor_texel);
+
+                    let shader_error = fdtd.get_shader_error().map(|err| format!("\n{}", err));
+                    let plot_label = shown_probe.map(|index| {
+                        let traces = traces.lock().unwrap();
+                        let trace = &traces[index];
+                        let area = trace_plot.prepare(
+                            &queue,
+                            trace,
+                            [surface_config.width as f32, surface_config.height as f32],
+                        );
+                        (
+                            area.top_left,
+                            format!("{}: {:.3e} to {:.3e}", trace.name, area.range[0], area.range[1]),
+                        )
+                    });
+                    let non_finite_warning = status
+                        .non_finite_at
+                        .map(|step| format!("NaN or infinite field at step {}, ", step));
+                        brush.queue(&device, &queue, vec![TextSection {
+                            screen_position: (0.0, 0.0),
+                            bounds: (surface_config.width as f32, surface_config.height as f32),
+                            // a blow-up is the first thing to read, in the color of shader errors
+                            text: non_finite_warning.as_deref().map(|warning| {
+                                Text::new(warning).with_color([1.0, 1.0, 0.0, 1.0]).with_scale(20.0)
+                            }).into_iter().chain([Text::new(&format!(
+                                "{}Time step: {} ({}), Steps/sec: {:.3}, Frames/sec: {:.1}, Slice position: {:?} = {}, Scaling factor: {:.1} ({}), field: {}, colormap: {}{}{}{}",
+                                if status.paused { "PAUSED, " } else { "" },
+                                status.step,
+                                units::describe_time(units.as_ref(), status.step as f32 * temporal_step),
+                                steps_per_second,
+                                frames_per_second,
+                                fdtd.get_slice_mode(),
+                                fdtd.get_slice_position(),
+                                fdtd.get_scaling_factor(),
+                                if fdtd.is_log_scale() {
+                                    format!("LOG, floor 1e{}", fdtd.get_log_floor())
+                                } else {
+                                    "LIN".to_string()
+                                },
+                                fdtd.describe_view(),
+                                fdtd.get_colormap().map_or("custom".to_string(), |colormap| format!("{:?}", colormap)),
+                                readout
+                                    .describe(cursor_texel)
+                                    .map(|value| format!(", {}", value))
+                                    .unwrap_or_default(),
+                                status
+                                    .energy
+                                    .map(|energy| format!(", Energy: {:.4e}", energy))
+                                    .unwrap_or_default(),
+                                status
+                                    .pass_times
+                                    .map(|times| format!(
+                                        ", GPU ms/step: update {:.3}, PML {:.3}, excitation {:.3}",
+                                        times.update, times.pml, times.excitation
+                                    ))
+                                    .unwrap_or_default()
+                            ))
+                            // dimmed while paused so screenshots tell the state apart
+                            .with_color(if status.paused { [0.5, 0.5, 0.5, 1.0] } else { [1.0, 0.0, 0.0, 1.0] })
+                            .with_scale(20.0)])
+                            .chain(shader_error.as_deref().map(|err| {
+                                Text::new(err).with_color([1.0, 1.0, 0.0, 1.0]).with_scale(20.0)
+                            }))
+                            .collect(),
+                            ..Default::default()
+                        }].into_iter().chain(plot_label.as_ref().map(|(top_left, label)| TextSection {
+                            screen_position: (top_left[0], top_left[1] - 20.0),
+                            bounds: (surface_config.width as f32, surface_config.height as f32),
+                            text: vec![Text::new(label).with_color([1.0, 0.8, 0.0, 1.0]).with_scale(16.0)],
+                            ..Default::default()
+                        })).collect::<Vec<_>>()).unwrap();
+
+                    {
+                        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            label: None,
+                            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                                view: &surf_texture_view,
+                                resolve_target: None,
+                                ops: wgpu::Operations {
+                                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                                    store: wgpu::StoreOp::Store,
+                                },
+                            })],
+                            depth_stencil_attachment: None,
+                            timestamp_writes: None,
+                            occlusion_query_set: None,
+                        });
+
+                        fdtd.visualize(&mut render_pass, [surface_config.width, surface_config.height]);
+                        if shown_probe.is_some() {
+                            trace_plot.draw(&mut render_pass);
+                        }
+                        brush.draw(&mut render_pass);
+                    }
+
+                    frame_counter += 1;
+                    let last_display_delta = last_display_time.elapsed();
+                    if last_display_delta >= show_fps_duration {
+                        steps_per_second = status.step.saturating_sub(last_display_step) as f32 / last_display_delta.as_secs_f32();
+                        frames_per_second = frame_counter as f32 / last_display_delta.as_secs_f32();
+                        last_display_time = std::time::Instant::now();
+                        last_display_step = status.step;
+                        frame_counter = 0;
+                    }
+
+                    queue.submit(std::iter::once(encoder.finish()));
+                    surface_texture.present();
+
+                    readout.map();
+
+                    if screenshot_requested {
+                        screenshot_requested = false;
+                        if let Err(err) = screenshot::capture(
+                            &device,
+                            &queue,
+                            &fdtd,
+                            surface_config.format,
+                            [surface_config.width, surface_config.height],
+                            std::env::current_dir().unwrap().join(format!(
+                                "{}-screenshot-{}.png",
+                                preset,
+                                crate::timestamp_millis()
+                            )),
+                        ) {
+                            eprintln!("{}", err);
+                        }
+                    }
+                    // keep redrawing while paused until the readback arrives
+                    if status.paused && readout.is_pending() {
+                        window.request_redraw();
+                    }
+                }
+                _ => (),
+            }
+        }
+        // sent by the simulation thread whenever it has progressed
+        winit::event::Event::UserEvent(()) => {
+            if simulation.as_ref().is_some_and(|simulation| simulation.status().finished) {
+                simulation.take().unwrap().join();
+                target.exit();
+            } else {
+                window.request_redraw();
+            }
+        }
+        _ => (),
+        })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,1030 @@
+//! Small scenarios checked against analytic results. They need an adapter, a software one such
+//! as lavapipe is picked when available, so they only run with `cargo test -- --ignored`.
+
+use std::path::PathBuf;
+
+use common::{temp_file, temp_path};
+use grems::headless::{FieldType, Headless};
+
+mod common;
+
+/// A Gaussian pulse of standard deviation `width` peaking at `center`, as a waveform CSV.
+fn gaussian_waveform(name: &str, center: f32, width: f32) -> PathBuf {
+    let mut csv = String::from("time,amplitude\n");
+    let samples = (2.0 * center / 0.005) as u32;
+    for sample in 0..=samples {
+        let time = sample as f32 * 0.005;
+        let amplitude = (-0.5 * ((time - center) / width).powi(2)).exp();
+        csv += &format!("{},{}\n", time, amplitude);
+    }
+    temp_file(name, &csv)
+}
+
+/// Ez along a bar of 8 x 8 cells in y and z, periodic in both, with PML at the x faces, so a
+/// sheet source launches plane waves along x.
+fn plane_wave_preset(waveform: &PathBuf, extrusions: &str, mode_monitors: &str) -> String {
+    format!(
+        r#"{{
+            "domain": [[-3, 3], [-0.2, 0.2], [-0.2, 0.2]],
+            "boundary": {{ "type": "PML", "sigma": 30, "alpha": 10, "cells": 10, "periodic": [false, true, true] }},
+            "spatial_step": 0.05,
+            "courant_factor": 0.5,
+            "default_slice": {{ "field": "E", "mode": "Z", "position": 0 }},
+            "default_scaling_factor": 1,
+            "pause_at": [],
+            "exports": [],
+            "models": [],
+            "extrusions": [{}],
+            "mode_monitors": [{}],
+            "sources": [{{
+                "wavelength": 1,
+                "position": [-2, 0, 0],
+                "size": [0, 0.4, 0.4],
+                "mode": {{ "type": "volume", "settings": {{ "direction": [0, 0, 1], "field": "E" }} }},
+                "phase": 0,
+                "delay": 0,
+                "fwhm": 0,
+                "power": 1,
+                "waveform": {:?}
+            }}]
+        }}"#,
+        extrusions, mode_monitors, waveform
+    )
+}
+
+/// Ez after each of `steps` steps at each of `positions`.
+fn ez_traces(simulation: &mut Headless, positions: &[[f32; 3]], steps: u32) -> Vec<Vec<f32>> {
+    let samples = simulation.record(FieldType::E, positions, steps).unwrap();
+    (0..positions.len())
+        .map(|probe| samples.iter().map(|step| step[probe][2]).collect())
+        .collect()
+}
+
+/// Time of the largest |value| of `trace`, refined by a parabola through the samples around it.
+fn peak_time(trace: &[f32], dt: f32) -> f32 {
+    let (peak, _) = trace
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+        .unwrap();
+    assert!(
+        peak > 0 && peak + 1 < trace.len(),
+        "peak at the end of the trace"
+    );
+    let [before, at, after] = [trace[peak - 1], trace[peak], trace[peak + 1]];
+    let shift = 0.5 * (before - after) / (before - 2.0 * at + after);
+    (peak as f32 + shift) * dt
+}
+
+#[test]
+#[ignore]
+fn preset_files_resolve_against_their_directory() {
+    let waveform = gaussian_waveform("open-waveform.csv", 0.8, 0.15);
+    // named without its directory, which only the preset's own directory supplies
+    let name = PathBuf::from(waveform.file_name().unwrap());
+    let preset = temp_file("open-preset.json", &plane_wave_preset(&name, "", ""));
+    let probe = [[-1.0, 0.0, 0.0]];
+    let opened = ez_traces(&mut Headless::open(&preset).unwrap(), &probe, 200);
+    let inline = ez_traces(
+        &mut Headless::new(&plane_wave_preset(&waveform, "", "")).unwrap(),
+        &probe,
+        200,
+    );
+    assert_eq!(opened, inline);
+    assert!(opened[0].iter().any(|value| *value != 0.0));
+    std::fs::remove_file(waveform).unwrap();
+    std::fs::remove_file(preset).unwrap();
+}
+
+#[test]
+#[ignore]
+fn tiled_update_matches_the_untiled_one() {
+    let waveform = gaussian_waveform("tiled-waveform.csv", 0.8, 0.15);
+    let mut simulation = Headless::new(&plane_wave_preset(&waveform, "", "")).unwrap();
+    // both kernels evaluate the same curls of the same values, only where they read them differs
+    let difference = simulation.compare_tiled_update().unwrap();
+    assert!(
+        difference <= 1e-6,
+        "tiled and untiled updates differ by up to {}",
+        difference
+    );
+    std::fs::remove_file(waveform).unwrap();
+}
+
+/// A z-normal source at `position` in a box with 20 PML cells on every face, `mode` in the
+/// format of the `mode` of a source, driven by the waveform at `waveform`.
+fn offset_source_preset(
+    mode: &str,
+    position: [f32; 3],
+    size: [f32; 3],
+    waveform: &PathBuf,
+) -> String {
+    format!(
+        r#"{{
+            "domain": [[-1, 1], [-1, 1], [-0.5, 0.5]],
+            "boundary": {{ "type": "PML", "sigma": 30, "alpha": 10, "cells": 20 }},
+            "spatial_step": 0.05,
+            "courant_factor": 0.5,
+            "default_slice": {{ "field": "E", "mode": "Z", "position": 0 }},
+            "default_scaling_factor": 1,
+            "pause_at": [],
+            "exports": [],
+            "models": [],
+            "sources": [{{
+                "wavelength": 1,
+                "position": {:?},
+                "size": {:?},
+                "mode": {},
+                "phase": 0,
+                "delay": 0,
+                "fwhm": 0,
+                "power": 1,
+                "waveform": {:?}
+            }}]
+        }}"#,
+        position, size, mode, waveform
+    )
+}
+
+#[test]
+#[ignore]
+fn volume_and_texture_sources_excite_the_same_cells() {
+    let waveform = temp_file("offset-waveform.csv", "time,amplitude\n0,1\n1,1\n");
+    // uniform over 0.4 x 0.3, the extent of the volume source
+    let mut csv = String::from("x,y,real,imag\n");
+    for x in 0..=8 {
+        for y in 0..=6 {
+            csv += &format!(
+                "{:.2},{:.2},1,0\n",
+                -0.2 + 0.05 * x as f32,
+                -0.15 + 0.05 * y as f32
+            );
+        }
+    }
+    let ez = temp_file("offset-ez.csv", &csv);
+    let volume = r#"{ "type": "volume", "settings": { "direction": [0, 0, 1], "field": "E" } }"#;
+    let texture = format!(
+        r#"{{ "type": "texture", "settings": {{ "ez": {:?}, "spatial_step": 0.05, "normal": "Z", "extrapolation": "zero" }} }}"#,
+        ez
+    );
+    // off the center of the domain, so that a shift by the PML would show
+    let position = [0.3, 0.1, 0.0];
+    // the centers of the interior cells of the plane of the source
+    let cells: Vec<[f32; 3]> = (0..40 * 40)
+        .map(|cell| {
+            let [x, y] = [cell % 40, cell / 40].map(|index| -1.0 + 0.05 * (index as f32 + 0.5));
+            [x, y, 0.025]
+        })
+        .collect();
+    let excited = |preset: String| {
+        let mut simulation = Headless::new(&preset).unwrap();
+        // the first step only adds the source to the zero fields
+        let ez = simulation
+            .record(FieldType::E, &cells, 1)
+            .unwrap()
+            .remove(0);
+        cells
+            .iter()
+            .zip(ez)
+            .filter(|(_, field)| field[2] != 0.0)
+            .map(|(cell, _)| [cell[0], cell[1]])
+            .collect::<Vec<_>>()
+    };
+    let from_volume = excited(offset_source_preset(
+        volume,
+        position,
+        [0.4, 0.3, 0.0],
+        &waveform,
+    ));
+    let from_texture = excited(offset_source_preset(
+        &texture, position, [1.0; 3], &waveform,
+    ));
+    assert_eq!(
+        from_volume.len(),
+        8 * 6,
+        "volume source excites {:?}",
+        from_volume
+    );
+    assert_eq!(from_texture, from_volume);
+    for path in [waveform, ez] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+#[ignore]
+fn pml_without_loss_updates_like_the_interior() {
+    let waveform = gaussian_waveform("lossless-waveform.csv", 0.3, 0.08);
+    // 32 cells of 1/16 and 10 PML cells on each face, or the 52 cells all interior
+    let preset = |domain: f32, boundary: &str| {
+        format!(
+            r#"{{
+                "domain": [[-{0}, {0}], [-{0}, {0}], [-{0}, {0}]],
+                "boundary": {1},
+                "spatial_step": 0.0625,
+                "courant_factor": 0.9,
+                "default_slice": {{ "field": "E", "mode": "Z", "position": 0 }},
+                "default_scaling_factor": 1,
+                "pause_at": [],
+                "exports": [],
+                "models": [],
+                "sources": [{{
+                    "wavelength": 1,
+                    "position": [0.1, -0.2, 0],
+                    "size": [0, 0, 0.25],
+                    "mode": {{ "type": "volume", "settings": {{ "direction": [0, 0, 1], "field": "E" }} }},
+                    "phase": 0,
+                    "delay": 0,
+                    "fwhm": 0,
+                    "power": 1,
+                    "waveform": {2:?}
+                }}]
+            }}"#,
+            domain, boundary, waveform
+        )
+    };
+    // no sigma leaves psi at zero, so the PML passes only run the Yee update
+    let pml = r#"{ "type": "PML", "sigma": 0, "alpha": 1, "cells": 10 }"#;
+    let cells: Vec<[f32; 3]> = (0..52 * 52 * 52)
+        .map(|cell| {
+            [cell % 52, cell / 52 % 52, cell / (52 * 52)]
+                .map(|index| -1.625 + 0.0625 * (index as f32 + 0.5))
+        })
+        .collect();
+    let fields = |preset: String| {
+        let mut simulation = Headless::new(&preset).unwrap();
+        assert_eq!(simulation.grid_dimension(), [52; 3]);
+        // the pulse crosses into the PML cells and back off the walls
+        simulation.run(79);
+        // each record runs one more step, the same ones in both grids
+        [FieldType::H, FieldType::E].map(|field| simulation.record(field, &cells, 1).unwrap())
+    };
+    let [h, e] = fields(preset(1.0, pml));
+    let [expected_h, expected_e] = fields(preset(1.625, r#"{ "type": "PEC" }"#));
+    let in_pml = |cell: &[f32; 3]| cell.iter().any(|c| c.abs() > 1.0);
+    assert!(e[0]
+        .iter()
+        .zip(&cells)
+        .any(|(value, cell)| in_pml(cell) && value.iter().any(|c| *c != 0.0)));
+    // The walls are the outermost layer of both grids, where the PML passes and the interior
+    // one zero the same components. Both store through the same expression of update.wgsl with
+    // a zero psi, so the two runs agree bit for bit.
+    for (field, values, expected) in [("H", &h[0], &expected_h[0]), ("E", &e[0], &expected_e[0])] {
+        for ((value, expected), cell) in values.iter().zip(expected).zip(&cells) {
+            assert_eq!(value, expected, "{} at {:?}", field, cell);
+        }
+    }
+    std::fs::remove_file(waveform).unwrap();
+}
+
+#[test]
+#[ignore]
+fn pulse_travels_at_the_speed_of_light() {
+    let waveform = gaussian_waveform("speed-waveform.csv", 0.8, 0.15);
+    let mut simulation = Headless::new(&plane_wave_preset(&waveform, "", "")).unwrap();
+    let dt = simulation.temporal_step();
+    // the pulse passes x = -1 at t = 1.8 and x = 1 at t = 3.8, when whatever the x_min PML
+    // echoes reaches x = -1, so the first probe only looks at the start of its trace
+    let traces = ez_traces(
+        &mut simulation,
+        &[[-1.0, 0.0, 0.0], [1.0, 0.0, 0.0]],
+        (4.6 / dt) as u32,
+    );
+    let arrival = [
+        peak_time(&traces[0][..(2.8 / dt) as usize], dt),
+        peak_time(&traces[1], dt),
+    ];
+    let speed = 2.0 / (arrival[1] - arrival[0]);
+    assert!(
+        (speed - 1.0).abs() < 0.02,
+        "pulse speed {} instead of 1",
+        speed
+    );
+    std::fs::remove_file(waveform).unwrap();
+}
+
+#[test]
+#[ignore]
+fn pulse_travels_at_the_speed_of_light_in_preset_units() {
+    // um per fs
+    let c = 0.299_792_46;
+    let waveform = gaussian_waveform("units-waveform.csv", 0.8 / c, 0.15 / c);
+    let preset = plane_wave_preset(&waveform, "", "").replacen(
+        '{',
+        r#"{ "units": { "length": "um", "time": "fs" },"#,
+        1,
+    );
+    let mut simulation = Headless::new(&preset).unwrap();
+    // in fs, the steps count ct
+    let dt = simulation.temporal_step() / c;
+    let traces = ez_traces(
+        &mut simulation,
+        &[[-1.0, 0.0, 0.0], [1.0, 0.0, 0.0]],
+        (4.6 / c / dt) as u32,
+    );
+    let arrival = [
+        peak_time(&traces[0][..(2.8 / c / dt) as usize], dt),
+        peak_time(&traces[1], dt),
+    ];
+    let speed = 2.0 / (arrival[1] - arrival[0]);
+    assert!(
+        (speed / c - 1.0).abs() < 0.02,
+        "pulse speed {} um/fs instead of {}",
+        speed,
+        c
+    );
+    std::fs::remove_file(waveform).unwrap();
+}
+
+#[test]
+#[ignore]
+fn pulse_travels_at_the_speed_of_light_in_1d() {
+    let waveform = gaussian_waveform("speed-1d-waveform.csv", 0.8, 0.15);
+    let preset = format!(
+        r#"{{
+            "domain": [[-0.2, 0.2], [-0.2, 0.2], [-3, 3]],
+            "boundary": {{ "type": "PML", "sigma": 30, "alpha": 10, "cells": 10 }},
+            "dimension_mode": "1d",
+            "spatial_step": 0.05,
+            "courant_factor": 0.5,
+            "default_slice": {{ "field": "E", "mode": "X", "position": 0 }},
+            "default_scaling_factor": 1,
+            "pause_at": [],
+            "exports": [],
+            "models": [],
+            "sources": [{{
+                "wavelength": 1,
+                "position": [0, 0, -2],
+                "size": [0, 0, 0],
+                "mode": {{ "type": "volume", "settings": {{ "direction": [1, 0, 0], "field": "E" }} }},
+                "phase": 0,
+                "delay": 0,
+                "fwhm": 0,
+                "power": 1,
+                "waveform": {:?}
+            }}]
+        }}"#,
+        waveform
+    );
+    let mut simulation = Headless::new(&preset).unwrap();
+    let dt = simulation.temporal_step();
+    let positions = [[0.0, 0.0, -1.0], [0.0, 0.0, 1.0]];
+    let samples = simulation
+        .record(FieldType::E, &positions, (4.6 / dt) as u32)
+        .unwrap();
+    let ex = |probe: usize| {
+        samples
+            .iter()
+            .map(|step| step[probe][0])
+            .collect::<Vec<_>>()
+    };
+    // same timing as in 3D, the first probe only looks at the start of its trace
+    let arrival = [
+        peak_time(&ex(0)[..(2.8 / dt) as usize], dt),
+        peak_time(&ex(1), dt),
+    ];
+    let speed = 2.0 / (arrival[1] - arrival[0]);
+    assert!(
+        (speed - 1.0).abs() < 0.02,
+        "pulse speed {} instead of 1",
+        speed
+    );
+    std::fs::remove_file(waveform).unwrap();
+}
+
+#[test]
+#[ignore]
+fn replayed_plane_launches_the_recorded_pulse_one_way() {
+    let waveform = gaussian_waveform("replay-waveform.csv", 0.8, 0.15);
+    let recording = temp_path("replay.bin");
+    let preset = plane_wave_preset(&waveform, "", "");
+    let probes = [[-2.5, 0.0, 0.0], [1.0, 0.0, 0.0]];
+
+    // run A records the plane x = -1 the pulse crosses on its way to the probe at x = 1
+    let mut simulation = Headless::new(&preset.replace(
+        "\"sources\":",
+        &format!(
+            r#""field_recordings": [{{ "file": {:?}, "position": [-1, 0, 0], "normal": "X" }}],
+            "sources":"#,
+            recording
+        ),
+    ))
+    .unwrap();
+    let dt = simulation.temporal_step();
+    let steps = (4.6 / dt) as u32;
+    let recorded = ez_traces(&mut simulation, &probes, steps);
+    simulation.finish_recordings();
+
+    // run B plays the plane back instead of running the source
+    let mut simulation = Headless::new(
+        &preset
+            .replace("[-2, 0, 0]", "[-1, 0, 0]")
+            .replace(
+                r#"{ "type": "volume", "settings": { "direction": [0, 0, 1], "field": "E" } }"#,
+                &format!(
+                    r#"{{ "type": "replay", "settings": {{ "file": {:?}, "normal": "X" }} }}"#,
+                    recording
+                ),
+            )
+            .replace(
+                &format!("\"waveform\": {:?}", waveform),
+                "\"start_time\": 0",
+            ),
+    )
+    .unwrap();
+    let replayed = ez_traces(&mut simulation, &probes, steps);
+
+    let peak = |trace: &[f32]| trace.iter().fold(0f32, |peak, value| peak.max(value.abs()));
+    let arrival = [peak_time(&recorded[1], dt), peak_time(&replayed[1], dt)];
+    assert!(
+        (arrival[0] - arrival[1]).abs() < 2.0 * dt,
+        "the replayed pulse arrives at t = {} instead of {}",
+        arrival[1],
+        arrival[0]
+    );
+    let amplitude = peak(&replayed[1]) / peak(&recorded[1]);
+    assert!(
+        (amplitude - 1.0).abs() < 0.05,
+        "the replayed pulse has {} times the recorded amplitude",
+        amplitude
+    );
+    // the sheet is one cell thick and E and H sit half a cell and step apart on it, so a
+    // little leaks behind
+    let behind = peak(&replayed[0]) / peak(&recorded[1]);
+    assert!(
+        behind < 0.1,
+        "{} of the pulse leaks behind the plane",
+        behind
+    );
+    for path in [&waveform, &recording] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+#[ignore]
+fn dielectric_interface_reflects_the_fresnel_amplitude() {
+    let index = 2.0;
+    let waveform = gaussian_waveform("fresnel-waveform.csv", 0.8, 0.15);
+    let outline = temp_file(
+        "fresnel-outline.csv",
+        "name,x,y\nslab,0.5,-1\nslab,3.5,-1\nslab,3.5,1\nslab,0.5,1\n",
+    );
+    let slab = format!(
+        r#"{{ "path": {:?}, "z_min": -1, "z_max": 1, "refractive_index": {} }}"#,
+        outline, index
+    );
+    let probe = [[-1.0, 0.0, 0.0]];
+    // the reflection reaches the probe at t = 4.8, the one of the back of the slab much later
+    let mut reference = Headless::new(&plane_wave_preset(&waveform, "", "")).unwrap();
+    let steps = (5.8 / reference.temporal_step()) as u32;
+    let incident = ez_traces(&mut reference, &probe, steps).remove(0);
+    let mut simulation = Headless::new(&plane_wave_preset(&waveform, &slab, "")).unwrap();
+    let total = ez_traces(&mut simulation, &probe, steps).remove(0);
+
+    let incident_peak = incident.iter().copied().fold(0.0, f32::max);
+    let reflected: Vec<f32> = total
+        .iter()
+        .zip(incident.iter())
+        .map(|(a, b)| a - b)
+        .collect();
+    let reflected_peak = reflected
+        .iter()
+        .copied()
+        .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+        .unwrap();
+    let coefficient = reflected_peak / incident_peak;
+    let expected = (1.0 - index) / (1.0 + index);
+    assert!(
+        (coefficient - expected).abs() < 0.02,
+        "reflection coefficient {} instead of {}",
+        coefficient,
+        expected
+    );
+    std::fs::remove_file(waveform).unwrap();
+    std::fs::remove_file(outline).unwrap();
+}
+
+/// A uniform profile over the cross-section of the plane wave bar, as a mode CSV.
+fn uniform_mode(name: &str, value: f32) -> PathBuf {
+    let mut csv = String::from("y,z,real,imag\n");
+    for y in 0..=8 {
+        for z in 0..=8 {
+            csv += &format!(
+                "{},{},{},0\n",
+                -0.2 + 0.05 * y as f32,
+                -0.2 + 0.05 * z as f32,
+                value
+            );
+        }
+    }
+    temp_file(name, &csv)
+}
+
+#[test]
+#[ignore]
+fn mode_monitor_sees_the_plane_wave_travel() {
+    let waveform = gaussian_waveform("monitor-waveform.csv", 0.8, 0.15);
+    // Ez with Hy = -Ez travels along +x in vacuum
+    let ez = uniform_mode("monitor-ez.csv", 1.0);
+    let hy = uniform_mode("monitor-hy.csv", -1.0);
+    let monitor = |x: f32| {
+        format!(
+            r#"{{
+                "position": [{}, 0, 0],
+                "size": [1, 1, 1],
+                "wavelengths": [1],
+                "mode": {{ "type": "texture", "settings": {{ "ez": {:?}, "hy": {:?}, "spatial_step": 0.05, "normal": "X" }} }}
+            }}"#,
+            x, ez, hy
+        )
+    };
+    let monitors = format!("{}, {}", monitor(0.0), monitor(0.25));
+    let mut simulation = Headless::new(&plane_wave_preset(&waveform, "", &monitors)).unwrap();
+    // the pulse has passed both planes by t = 4
+    simulation.run((4.0 / simulation.temporal_step()) as u32);
+    let [near, far] = [0, 1].map(|index| simulation.mode_amplitudes(index).unwrap()[0]);
+
+    let power = near.forward.norm_sqr();
+    assert!(power > 0.0, "nothing reached the monitor");
+    assert!(
+        (far.forward.norm_sqr() / power - 1.0).abs() < 0.02,
+        "forward power {} after a quarter wavelength instead of {}",
+        far.forward.norm_sqr(),
+        power
+    );
+    assert!(
+        near.backward.norm_sqr() < 0.01 * power,
+        "backward power {} of a wave travelling forward",
+        near.backward.norm_sqr()
+    );
+    // a quarter wavelength further on the phase lags by 90 degrees
+    let lag = (near.forward * far.forward.conj())
+        .im
+        .atan2((near.forward * far.forward.conj()).re);
+    assert!(
+        (lag.to_degrees() - 90.0).abs() < 3.0,
+        "phase lag of {} degrees over a quarter wavelength",
+        lag.to_degrees()
+    );
+    for path in [waveform, ez, hy] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+#[ignore]
+fn flux_box_in_vacuum_absorbs_nothing() {
+    let waveform = gaussian_waveform("flux-waveform.csv", 0.8, 0.15);
+    let preset = plane_wave_preset(&waveform, "", "").replace(
+        "\"sources\":",
+        r#""flux_boxes": [{ "position": [0, 0, 0], "size": [1, 0.2, 0.2], "wavelengths": [1] }],
+            "sources":"#,
+    );
+    let mut simulation = Headless::new(&preset).unwrap();
+    let dt = simulation.temporal_step() as f64;
+    // the pulse has crossed the box by t = 4
+    let (mut incoming, mut absorbed) = (0.0, 0.0);
+    for _ in 0..(4.0 / dt) as u32 {
+        simulation.run(1);
+        let powers = simulation.face_powers().unwrap()[0];
+        incoming -= powers[0] * dt;
+        absorbed -= powers.iter().sum::<f64>() * dt;
+    }
+    assert!(incoming > 0.0, "nothing entered the box");
+    assert!(
+        absorbed.abs() < 0.02 * incoming,
+        "vacuum absorbed {} of the {} that entered",
+        absorbed,
+        incoming
+    );
+    assert_eq!(simulation.absorption(0).unwrap().len(), 1);
+    std::fs::remove_file(waveform).unwrap();
+}
+
+/// Amplitude and phase of the `frequency` component of `trace`, Hann windowed, as in
+/// `amplitude * cos(2 pi frequency t - phase)` with `t` counted from the first sample.
+fn tone(trace: &[f32], dt: f32, frequency: f32) -> (f32, f32) {
+    let count = trace.len() as f32;
+    let (re, im) = trace
+        .iter()
+        .enumerate()
+        .fold((0.0, 0.0), |(re, im), (step, value)| {
+            let window = 0.5 - 0.5 * (std::f32::consts::TAU * step as f32 / count).cos();
+            let (sin, cos) = (std::f32::consts::TAU * frequency * step as f32 * dt).sin_cos();
+            (re + window * value * cos, im + window * value * sin)
+        });
+    ((re * re + im * im).sqrt(), im.atan2(re))
+}
+
+#[test]
+#[ignore]
+fn angled_plane_wave_crosses_the_bloch_periodic_faces() {
+    // 20 degrees off x at a wavelength of 1, over a period of 1 along y
+    let angle = 20f32.to_radians();
+    let [kx, ky] = [angle.cos(), angle.sin()].map(|k| std::f32::consts::TAU * k);
+    let preset = format!(
+        r#"{{
+            "domain": [[-3, 3], [-0.5, 0.5], [-0.05, 0.05]],
+            "boundary": {{ "type": "PML", "sigma": 30, "alpha": 10, "cells": 10, "periodic": [false, true, true], "bloch_k": [0, {}, 0] }},
+            "spatial_step": 0.05,
+            "courant_factor": 0.5,
+            "default_slice": {{ "field": "E", "mode": "Z", "position": 0 }},
+            "default_scaling_factor": 1,
+            "pause_at": [],
+            "exports": [],
+            "models": [],
+            "sources": [{{
+                "wavelength": 1,
+                "position": [-2, 0, 0],
+                "size": [0, 1, 0.1],
+                "mode": {{ "type": "volume", "settings": {{ "direction": [0, 0, 1], "field": "E" }} }},
+                "phase": 0,
+                "delay": 0,
+                "fwhm": 0,
+                "power": 1,
+                "envelope": "ramped_cw",
+                "ramp_time": 2
+            }}]
+        }}"#,
+        ky
+    );
+    let mut simulation = Headless::new(&preset).unwrap();
+    let dt = simulation.temporal_step();
+    // cell centers along y, next to both periodic faces among them, then one further along x
+    let mut positions: Vec<[f32; 3]> = [0, 3, 7, 11, 15, 19]
+        .iter()
+        .map(|cell| [0.025, -0.475 + 0.05 * *cell as f32, 0.0])
+        .collect();
+    positions.push([0.225, -0.475, 0.0]);
+    // the wave is steady at the probes from t = 5 on
+    simulation.run((6.0 / dt) as u32);
+    let tones: Vec<(f32, f32)> = ez_traces(&mut simulation, &positions, (4.0 / dt) as u32)
+        .iter()
+        .map(|trace| tone(trace, dt, 1.0))
+        .collect();
+    let phase_error = |probe: usize, expected: f32| {
+        let error = (tones[probe].1 - tones[0].1 - expected).rem_euclid(std::f32::consts::TAU);
+        error.min(std::f32::consts::TAU - error)
+    };
+
+    let amplitude = tones[0].0;
+    assert!(amplitude > 0.0, "nothing reached the probes");
+    // a single wave, its amplitude is the same all over the period and its phase advances by
+    // ky y, also between the cells on either side of the periodic faces
+    for probe in 1..6 {
+        assert!(
+            (tones[probe].0 / amplitude - 1.0).abs() < 0.02,
+            "amplitude {} at y = {} instead of {}",
+            tones[probe].0,
+            positions[probe][1],
+            amplitude
+        );
+        let error = phase_error(probe, ky * (positions[probe][1] - positions[0][1]));
+        assert!(
+            error < 0.02,
+            "phase off by {} rad at y = {}",
+            error,
+            positions[probe][1]
+        );
+    }
+    // and by kx x along x, up to the numerical dispersion
+    let error = phase_error(6, kx * 0.2);
+    assert!(error < 0.03, "phase off by {} rad along x", error);
+}
+
+/// A thin PEC box of `a` x `b` with a line source along z, the energy is summed.
+fn cavity_preset(a: f32, b: f32, dimension_mode: &str, waveform: &PathBuf) -> String {
+    format!(
+        r#"{{
+            "domain": [[0, {}], [0, {}], [0, 0.1]],
+            "boundary": {{ "type": "PEC" }},
+            "dimension_mode": "{}",
+            "spatial_step": 0.025,
+            "courant_factor": 0.9,
+            "default_slice": {{ "field": "E", "mode": "Z", "position": 0.05 }},
+            "default_scaling_factor": 1,
+            "pause_at": [],
+            "exports": [],
+            "energy": {{}},
+            "models": [],
+            "sources": [{{
+                "wavelength": 1,
+                "position": [0.3, 0.25, 0.05],
+                "size": [0, 0, 0.1],
+                "mode": {{ "type": "volume", "settings": {{ "direction": [0, 0, 1], "field": "E" }} }},
+                "phase": 0,
+                "delay": 0,
+                "fwhm": 0,
+                "power": 1,
+                "waveform": {:?}
+            }}]
+        }}"#,
+        a, b, dimension_mode, waveform
+    )
+}
+
+/// Frequency in `range` where the Hann windowed spectrum of `trace` peaks, scanned in 1000 steps.
+fn spectral_peak(trace: &[f32], dt: f32, range: std::ops::Range<f32>) -> f32 {
+    let count = trace.len() as f32;
+    let spectrum = |frequency: f32| {
+        let (re, im) = trace
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(re, im), (step, value)| {
+                let window = 0.5 - 0.5 * (std::f32::consts::TAU * step as f32 / count).cos();
+                let (sin, cos) = (std::f32::consts::TAU * frequency * step as f32 * dt).sin_cos();
+                (re + window * value * cos, im - window * value * sin)
+            });
+        re * re + im * im
+    };
+    let (peak, _) = (0..=1000)
+        .map(|i| range.start + (range.end - range.start) * i as f32 / 1000.0)
+        .map(|frequency| (frequency, spectrum(frequency)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap();
+    peak
+}
+
+/// Checks the resonance of a 1 x 0.8 cavity simulated in `dimension_mode` against TM110.
+fn check_cavity_resonance(dimension_mode: &str, name: &str) {
+    let [a, b] = [1.0f32, 0.8];
+    let waveform = gaussian_waveform(name, 0.5, 0.1);
+    let mut simulation = Headless::new(&cavity_preset(a, b, dimension_mode, &waveform)).unwrap();
+    let dt = simulation.temporal_step();
+    let dx = simulation.spatial_step();
+    // the cavity is too thin for modes varying along z below f = 5, so Ez holds TM_mn0 only
+    simulation.run((1.0 / dt) as u32);
+    let trace = ez_traces(&mut simulation, &[[0.7, 0.55, 0.05]], (60.0 / dt) as u32).remove(0);
+    // scanned around TM110 and below TM210
+    let resonance = spectral_peak(&trace, dt, 0.6..1.0);
+
+    // the Yee walls sit within a cell of the domain faces
+    let tm110 = |a: f32, b: f32| 0.5 * (a.powi(-2) + b.powi(-2)).sqrt();
+    let range = tm110(a, b) * 0.995..tm110(a - dx, b - dx) * 1.005;
+    assert!(
+        range.contains(&resonance),
+        "resonance at {} outside {:?}",
+        resonance,
+        range
+    );
+    std::fs::remove_file(waveform).unwrap();
+}
+
+#[test]
+#[ignore]
+fn pec_cavity_resonates_at_the_tm110_frequency() {
+    check_cavity_resonance("3d", "cavity-waveform.csv");
+}
+
+#[test]
+#[ignore]
+fn pec_cavity_resonates_at_the_tm110_frequency_in_2d_tm() {
+    check_cavity_resonance("2d_tm", "cavity-2d-waveform.csv");
+}
+
+#[test]
+#[ignore]
+fn resonance_analysis_finds_the_tm110_frequency() {
+    let [a, b] = [1.0f32, 0.8];
+    let waveform = gaussian_waveform("resonance-waveform.csv", 0.5, 0.1);
+    let mut simulation = Headless::new(&cavity_preset(a, b, "3d", &waveform)).unwrap();
+    let dt = simulation.temporal_step();
+    let dx = simulation.spatial_step();
+    let first = (1.0 / dt) as u32;
+    simulation.run(first);
+    let trace = ez_traces(&mut simulation, &[[0.7, 0.55, 0.05]], (60.0 / dt) as u32).remove(0);
+    let samples: Vec<(u32, f32)> = (first + 1..).zip(trace).collect();
+    let strongest = simulation
+        .resonances(&samples)
+        .into_iter()
+        .filter(|resonance| (0.6..1.0).contains(&resonance.frequency))
+        .max_by(|a, b| a.power.total_cmp(&b.power))
+        .expect("no resonance between f = 0.6 and 1");
+
+    // as in check_cavity_resonance
+    let tm110 = |a: f32, b: f32| 0.5 * (a.powi(-2) + b.powi(-2)).sqrt() as f64;
+    let range = tm110(a, b) * 0.995..tm110(a - dx, b - dx) * 1.005;
+    assert!(
+        range.contains(&strongest.frequency),
+        "resonance at {} outside {:?}",
+        strongest.frequency,
+        range
+    );
+    // the cavity is lossless, so the line is as narrow as the trace allows
+    assert!(!strongest.resolved || strongest.q > 30.0);
+    std::fs::remove_file(waveform).unwrap();
+}
+
+/// Writes `values` over a grid of `dimension` cells as a DDS volume, as a D3 export would.
+fn dds_volume(name: &str, dimension: [u32; 3], values: &[f32]) -> PathBuf {
+    let mut dds = ddsfile::Dds::new_dxgi(ddsfile::NewDxgiParams {
+        height: dimension[1],
+        width: dimension[0],
+        depth: Some(dimension[2]),
+        format: ddsfile::DxgiFormat::R32_Float,
+        mipmap_levels: None,
+        array_layers: None,
+        caps2: None,
+        is_cubemap: false,
+        resource_dimension: ddsfile::D3D10ResourceDimension::Texture3D,
+        alpha_mode: ddsfile::AlphaMode::Unknown,
+    })
+    .unwrap();
+    dds.data = values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    let path = temp_file(name, "");
+    dds.write(&mut std::fs::File::create(&path).unwrap())
+        .unwrap();
+    path
+}
+
+#[test]
+#[ignore]
+fn cavity_started_from_its_tm110_mode_oscillates_in_place() {
+    let [a, b] = [1.0f32, 0.8];
+    let silent = temp_file("silent-waveform.csv", "time,amplitude\n0,0\n1,0\n");
+    let preset = cavity_preset(a, b, "2d_tm", &silent);
+    let (dimension, dx) = {
+        let simulation = Headless::new(&preset).unwrap();
+        (simulation.grid_dimension(), simulation.spatial_step())
+    };
+    let cells = (0..dimension[2])
+        .flat_map(|_| (0..dimension[1]).flat_map(move |y| (0..dimension[0]).map(move |x| (x, y))));
+    let ez: Vec<f32> = cells
+        .map(|(x, y)| {
+            let [x, y] = [x, y].map(|i| (i as f32 + 0.5) * dx);
+            (std::f32::consts::PI * x / a).sin() * (std::f32::consts::PI * y / b).sin()
+        })
+        .collect();
+    let zero = vec![0.0; ez.len()];
+    let files = [
+        dds_volume("initial-ex.dds", dimension, &zero),
+        dds_volume("initial-ey.dds", dimension, &zero),
+        dds_volume("initial-ez.dds", dimension, &ez),
+    ];
+    let preset = preset.replace(
+        "\"sources\":",
+        &format!(
+            r#""initial_fields": {{ "electric": [{:?}, {:?}, {:?}] }},
+            "sources":"#,
+            files[0], files[1], files[2]
+        ),
+    );
+    let mut simulation = Headless::new(&preset).unwrap();
+    let center = [0.5, 0.4, 0.05];
+    let initial = simulation.field_at(FieldType::E, center).unwrap()[2];
+    assert!((initial - 1.0).abs() < 0.01, "Ez starts at {}", initial);
+
+    // with H zero, Ez follows cos(2 pi f t) and has turned around after half a period
+    let tm110 = 0.5 * (a.powi(-2) + b.powi(-2)).sqrt();
+    simulation.run((0.5 / tm110 / simulation.temporal_step()).round() as u32);
+    let turned = simulation.field_at(FieldType::E, center).unwrap()[2];
+    assert!(
+        (turned + initial).abs() < 0.1,
+        "Ez is {} half a period after starting at {}",
+        turned,
+        initial
+    );
+    for path in files.iter().chain([&silent]) {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+#[ignore]
+fn pec_cavity_conserves_energy() {
+    let waveform = gaussian_waveform("energy-waveform.csv", 0.5, 0.1);
+    let mut simulation = Headless::new(&cavity_preset(1.0, 0.8, "3d", &waveform)).unwrap();
+    let dt = simulation.temporal_step();
+    // the waveform ends at t = 1
+    simulation.run((1.5 / dt) as u32);
+    let initial = simulation.energy().unwrap();
+    assert!(initial > 0.0, "no energy was injected");
+    // E and H are half a step apart, so the sum wobbles a little around the conserved value
+    for _ in 0..10 {
+        simulation.run((5.0 / dt) as u32);
+        let energy = simulation.energy().unwrap();
+        assert!(
+            (energy / initial - 1.0).abs() < 0.05,
+            "energy {} at step {} drifted from {}",
+            energy,
+            simulation.step(),
+            initial
+        );
+    }
+    std::fs::remove_file(waveform).unwrap();
+}
+
+/// A short z-directed current element at the origin in vacuum, surrounded by PML, with a
+/// near-field box around it.
+fn dipole_preset(waveform: &PathBuf) -> String {
+    format!(
+        r#"{{
+            "domain": [[-1, 1], [-1, 1], [-1, 1]],
+            "boundary": {{ "type": "PML", "sigma": 30, "alpha": 10, "cells": 10 }},
+            "spatial_step": 0.05,
+            "courant_factor": 0.5,
+            "default_slice": {{ "field": "E", "mode": "Z", "position": 0 }},
+            "default_scaling_factor": 1,
+            "pause_at": [],
+            "exports": [],
+            "models": [],
+            "near_field": {{
+                "position": [0, 0, 0],
+                "size": [1.2, 1.2, 1.2],
+                "wavelengths": [1],
+                "theta_count": 5,
+                "phi_count": 4
+            }},
+            "sources": [{{
+                "wavelength": 1,
+                "position": [0, 0, 0],
+                "size": [0.05, 0.05, 0.05],
+                "mode": {{ "type": "volume", "settings": {{ "direction": [0, 0, 1], "field": "E" }} }},
+                "phase": 0,
+                "delay": 0,
+                "fwhm": 0,
+                "power": 1,
+                "waveform": {:?}
+            }}]
+        }}"#,
+        waveform
+    )
+}
+
+#[test]
+#[ignore]
+fn dipole_far_field_follows_sin_squared() {
+    let waveform = gaussian_waveform("dipole-waveform.csv", 0.8, 0.2);
+    let mut simulation = Headless::new(&dipole_preset(&waveform)).unwrap();
+    let dt = simulation.temporal_step();
+    // the pulse has left the box by t = 3
+    simulation.run((3.5 / dt) as u32);
+    let samples = simulation.far_field().unwrap();
+    assert_eq!(samples.len(), 5 * 4);
+    // theta runs 0, 45, 90, 135, 180 degrees, four azimuths each
+    let mean = |theta: usize| {
+        samples[4 * theta..4 * (theta + 1)]
+            .iter()
+            .map(|sample| sample.intensity())
+            .sum::<f64>()
+            / 4.0
+    };
+    let broadside = mean(2);
+    assert!(broadside > 0.0, "nothing radiated");
+    for theta in [1, 3] {
+        let ratio = mean(theta) / broadside;
+        assert!(
+            (ratio - 0.5).abs() < 0.05,
+            "intensity at {} degrees is {} of broadside instead of sin^2 = 0.5",
+            45 * theta,
+            ratio
+        );
+    }
+    for theta in [0, 4] {
+        assert!(
+            mean(theta) < 0.02 * broadside,
+            "dipole radiates {} of broadside along its axis",
+            mean(theta) / broadside
+        );
+    }
+    // E points along theta, phi carries next to nothing
+    for sample in &samples[8..12] {
+        assert!(sample.e_phi.norm_sqr() < 0.01 * sample.intensity());
+    }
+    std::fs::remove_file(waveform).unwrap();
+}
+
+#[test]
+#[ignore]
+fn energy_normalized_dipoles_radiate_alike_at_any_spatial_step() {
+    let waveform = gaussian_waveform("normalized-waveform.csv", 0.8, 0.2);
+    let broadside = |spatial_step: &str| {
+        let preset = dipole_preset(&waveform)
+            .replace(
+                "\"spatial_step\": 0.05",
+                &format!("\"spatial_step\": {}", spatial_step),
+            )
+            .replace("\"power\": 1,", "\"power\": 1, \"normalize\": \"energy\",");
+        let mut simulation = Headless::new(&preset).unwrap();
+        let dt = simulation.temporal_step();
+        simulation.run((3.5 / dt) as u32);
+        // theta of 90 degrees, four azimuths
+        simulation.far_field().unwrap()[8..12]
+            .iter()
+            .map(|sample| sample.intensity())
+            .sum::<f64>()
+    };
+    let (coarse, fine) = (broadside("0.05"), broadside("0.04"));
+    assert!(coarse > 0.0, "nothing radiated");
+    assert!(
+        (fine / coarse - 1.0).abs() < 0.05,
+        "broadside intensity {} at dx = 0.04 differs from {} at dx = 0.05",
+        fine,
+        coarse
+    );
+    std::fs::remove_file(waveform).unwrap();
+}
@@ -0,0 +1,19 @@
+//! Fixtures shared by the integration tests.
+
+use std::path::PathBuf;
+
+/// Path of `name` in a temporary directory of this test run, its parent directories created.
+pub fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir()
+        .join(format!("grems-tests-{}", std::process::id()))
+        .join(name);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    path
+}
+
+/// Writes `contents` to `name` in the temporary directory of this test run.
+pub fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = temp_path(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
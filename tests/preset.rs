@@ -0,0 +1,247 @@
+//! Presets layered from several files, checked with `grems --check`, which needs no adapter.
+//! Tests running a preset need one and only run with `cargo test -- --ignored`.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use common::temp_file;
+
+mod common;
+
+/// A plane wave source in a bar with PML at the x faces, paused at step 10.
+const BASE: &str = r#"{
+    "domain": [[-3, 3], [-0.2, 0.2], [-0.2, 0.2]],
+    "boundary": { "type": "PML", "sigma": 30, "alpha": 10, "cells": 10, "periodic": [false, true, true] },
+    "spatial_step": 0.05,
+    "courant_factor": 0.5,
+    "default_slice": { "field": "E", "mode": "Z", "position": 0 },
+    "default_scaling_factor": 1,
+    "pause_at": [{ "type": "step", "value": 10 }],
+    "exports": [],
+    "models": [],
+    "sources": [{
+        "wavelength": 1,
+        "position": [-2, 0, 0],
+        "size": [0, 0.4, 0.4],
+        "mode": { "type": "volume", "settings": { "direction": [0, 0, 1], "field": "E" } },
+        "phase": 0,
+        "delay": 3,
+        "fwhm": 0.5,
+        "power": 1
+    }]
+}"#;
+
+/// What `grems --check` prints for the presets at `paths`, layered in that order.
+fn check(paths: &[&PathBuf]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_grems"))
+        .arg("--check")
+        .args(paths)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn later_presets_override_earlier_ones() {
+    let base = temp_file("override-base.json", BASE);
+    let coarse = temp_file(
+        "override-coarse.toml",
+        "spatial_step = 0.1\nmax_steps = 50\n",
+    );
+    let fine = temp_file("override-fine.toml", "spatial_step = 0.025\n");
+    let output = check(&[&base, &coarse, &fine]);
+    assert!(output.contains("dx = 0.025,"), "{}", output);
+    assert!(output.contains("Runs 50 steps"), "{}", output);
+    let output = check(&[&base, &fine, &coarse]);
+    assert!(output.contains("dx = 0.1,"), "{}", output);
+    for path in [base, coarse, fine] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn includes_are_layered_under_the_including_preset() {
+    let base = temp_file("include-base.json", BASE);
+    let short = temp_file("include-short.toml", "max_steps = 20\n");
+    let preset = temp_file(
+        "include-preset.toml",
+        "include = [\"include-base.json\", \"include-short.toml\"]\nmax_steps = 30\n",
+    );
+    let output = check(&[&preset]);
+    assert!(output.contains("Runs 30 steps"), "{}", output);
+    // without a value of its own, the one of the includes holds
+    let unset = temp_file(
+        "include-unset.toml",
+        "include = [\"include-base.json\", \"include-short.toml\"]\n",
+    );
+    let output = check(&[&unset]);
+    assert!(output.contains("Runs 20 steps"), "{}", output);
+    for path in [base, short, preset, unset] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn arrays_are_replaced_unless_appended() {
+    let base = temp_file("arrays-base.json", BASE);
+    let replace = temp_file(
+        "arrays-replace.toml",
+        "pause_at = [{ type = \"step\", value = 5 }]\n",
+    );
+    let output = check(&[&base, &replace]);
+    assert!(output.contains("pause or export at step 5"), "{}", output);
+    assert!(output.contains("Pauses at steps 5\n"), "{}", output);
+    let append = temp_file(
+        "arrays-append.toml",
+        "pause_at = { append = [{ type = \"step\", value = 5 }] }\n",
+    );
+    let output = check(&[&base, &append]);
+    assert!(output.contains("pause or export at step 10"), "{}", output);
+    // the entry of the base is kept ahead of the appended one
+    assert!(output.contains("Pauses at steps 10, 5\n"), "{}", output);
+    for path in [base, replace, append] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn files_resolve_against_the_preset_naming_them() {
+    // the waveform is next to the included preset, not the one including it
+    let waveform = temp_file("layers/files-pulse.csv", "time,amplitude\n0,0\n1,1\n2,0\n");
+    let base = temp_file(
+        "layers/files-base.json",
+        &BASE.replace(
+            r#""power": 1"#,
+            r#""power": 1, "waveform": "files-pulse.csv""#,
+        ),
+    );
+    let preset = temp_file(
+        "files-preset.toml",
+        "include = [\"layers/files-base.json\"]\nmax_steps = 20\n",
+    );
+    let output = check(&[&preset]);
+    assert!(output.contains("Runs 20 steps"), "{}", output);
+    // a source appended by the including preset names files relative to it
+    let appended = temp_file(
+        "files-appended.toml",
+        &format!(
+            "include = [\"layers/files-base.json\"]\n[sources]\nappend = [{}]\n",
+            r#"{ wavelength = 1, position = [2, 0, 0], size = [0, 0.4, 0.4], mode = { type = "volume", settings = { direction = [0, 0, 1], field = "E" } }, phase = 0, delay = 3, fwhm = 0.5, power = 1, waveform = "layers/files-pulse.csv" }"#
+        ),
+    );
+    check(&[&appended]);
+    for path in [waveform, base, preset, appended] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn zero_temporal_step_is_rejected() {
+    // alongside courant_factor, and in place of it
+    let base = temp_file("zero-step-base.json", BASE);
+    let zero = temp_file("zero-step.toml", "temporal_step = 0\n");
+    let alone = temp_file(
+        "zero-step-alone.json",
+        &BASE.replace(r#""courant_factor": 0.5"#, r#""temporal_step": 0"#),
+    );
+    for paths in [vec![&base, &zero], vec![&alone]] {
+        let output = Command::new(env!("CARGO_BIN_EXE_grems"))
+            .arg("--check")
+            .args(&paths)
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("temporal_step must be positive, got 0"),
+            "{}",
+            stderr
+        );
+    }
+    for path in [base, zero, alone] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn bloch_k_needs_a_periodic_axis() {
+    let base = temp_file("bloch-base.json", BASE);
+    let along_y = temp_file("bloch-y.toml", "[boundary]\nbloch_k = [0, 1.5, 0]\n");
+    let output = check(&[&base, &along_y]);
+    assert!(output.contains("Imaginary E and H"), "{}", output);
+    // x has PML
+    let along_x = temp_file("bloch-x.toml", "[boundary]\nbloch_k = [1.5, 0, 0]\n");
+    let output = Command::new(env!("CARGO_BIN_EXE_grems"))
+        .arg("--check")
+        .args([&base, &along_x])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("bloch_k is nonzero along x, which is not periodic"),
+        "{}",
+        stderr
+    );
+    for path in [base, along_y, along_x] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn no_visual_needs_a_last_step() {
+    // the base only pauses, which nothing would resume
+    let base = temp_file("no-visual-base.json", BASE);
+    let output = Command::new(env!("CARGO_BIN_EXE_grems"))
+        .arg("--no-visual")
+        .arg(&base)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("--no-visual needs max_steps or end_time"),
+        "{}",
+        stderr
+    );
+    std::fs::remove_file(base).unwrap();
+}
+
+#[test]
+#[ignore]
+fn no_visual_runs_past_pauses_to_the_last_step() {
+    let base = temp_file(
+        "no-visual-run.json",
+        &BASE.replace(
+            r#""exports": []"#,
+            r#""max_steps": 20, "exports": [{ "timing": { "type": "step", "value": 15 }, "export": { "dimension": "D3", "settings": { "field": "E" } } }]"#,
+        ),
+    );
+    let output = Command::new(env!("CARGO_BIN_EXE_grems"))
+        .arg("--no-visual")
+        .arg(&base)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}{}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("Finished 20 steps"), "{}", stdout);
+    // the export after the pause at step 10 is flushed before the run returns
+    let exports: Vec<PathBuf> = ["Ex", "Ey", "Ez"]
+        .iter()
+        .map(|column| PathBuf::from(format!("{}-D3-{}-15.dds", base.display(), column)))
+        .collect();
+    for path in exports.iter().chain([&base]) {
+        assert!(path.exists(), "{} is missing", path.display());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,86 @@
+//! Step rate benchmarks, run through `grems --no-visual --profile` on the default adapter. They
+//! only run with `cargo test --release --test throughput -- --ignored --nocapture`, which prints
+//! the GPU time per pass and the steps per second of each grid.
+
+use std::process::Command;
+
+use common::temp_file;
+
+mod common;
+
+/// A cube of `cells` cells a side with `pml` cells of PML on every face, driven by a point
+/// source at its center for `steps` steps.
+fn cube_preset(cells: u32, pml: u32, steps: u32) -> String {
+    let half = cells as f32 * 0.05 / 2.0;
+    format!(
+        r#"{{
+            "domain": [[-{half}, {half}], [-{half}, {half}], [-{half}, {half}]],
+            "boundary": {{ "type": "PML", "sigma": 30, "alpha": 10, "cells": {pml} }},
+            "spatial_step": 0.05,
+            "courant_factor": 0.5,
+            "max_steps": {steps},
+            "max_steps_per_frame": 50,
+            "default_slice": {{ "field": "E", "mode": "Z", "position": 0 }},
+            "default_scaling_factor": 1,
+            "pause_at": [],
+            "exports": [],
+            "models": [],
+            "sources": [{{
+                "wavelength": 1,
+                "position": [0, 0, 0],
+                "size": [0, 0, 0],
+                "mode": {{ "type": "volume", "settings": {{ "direction": [0, 0, 1], "field": "E" }} }},
+                "phase": 0,
+                "delay": 0,
+                "fwhm": 0,
+                "power": 1
+            }}]
+        }}"#
+    )
+}
+
+/// Runs `preset` for `steps` steps and prints the sampled pass times and the step rate. The rate
+/// includes the setup after the worker starts, which the step count keeps small.
+fn benchmark(name: &str, preset: &str, steps: u32) {
+    let path = temp_file(&format!("{}.json", name), preset);
+    let output = Command::new(env!("CARGO_BIN_EXE_grems"))
+        .args(["--no-visual", "--profile"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(path).unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        output.status.success(),
+        "{}{}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let last_sample = stdout.lines().rfind(|line| line.contains("GPU ms/step"));
+    // "Finished <steps> steps (<time>) in <seconds>s"
+    let seconds: f32 = stdout
+        .lines()
+        .find(|line| line.starts_with("Finished"))
+        .and_then(|line| line.rsplit(' ').next())
+        .and_then(|seconds| seconds.trim_end_matches('s').parse().ok())
+        .unwrap_or_else(|| panic!("no run time in:\n{}", stdout));
+    println!(
+        "{}: {:.1} steps/s, {}",
+        name,
+        steps as f32 / seconds,
+        last_sample.unwrap_or("no timestamp queries on this adapter")
+    );
+}
+
+#[test]
+#[ignore]
+fn cube_of_300_cells() {
+    benchmark("cube-300", &cube_preset(300, 10, 500), 500);
+}
+
+/// Mostly PML, so the PML passes dominate the step.
+#[test]
+#[ignore]
+fn cube_of_96_cells_with_thick_pml() {
+    benchmark("cube-96-pml", &cube_preset(96, 24, 2000), 2000);
+}